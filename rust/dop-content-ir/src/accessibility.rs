@@ -0,0 +1,169 @@
+//! Accessibility export
+//!
+//! This module converts a laid-out Content IR tree into an accesskit
+//! `TreeUpdate`, which the window layer publishes to the platform
+//! accessibility API so screen readers can read rendered pages.
+
+use accesskit::{Node, NodeId, Rect, Tree, TreeUpdate};
+
+use crate::primitives::{NodeTable, NodeType};
+use crate::properties::{PropertyTable, Role};
+use crate::render::{compute_layout, LayoutRect};
+
+/// Resolve the effective role of a node, deriving it from the node type when set to `Auto`
+pub fn resolve_role(node_type: NodeType, role: Role) -> Role {
+    if role != Role::Auto {
+        return role;
+    }
+    match node_type {
+        NodeType::Root => Role::Document,
+        NodeType::Paragraph => Role::Paragraph,
+        NodeType::Span | NodeType::TextCluster => Role::Label,
+        NodeType::Link => Role::Link,
+        NodeType::Stack | NodeType::Grid | NodeType::Scroll | NodeType::Rect => Role::Generic,
+    }
+}
+
+/// Map a Content IR role onto the accesskit role
+fn to_accesskit_role(role: Role) -> accesskit::Role {
+    match role {
+        Role::Auto | Role::None | Role::Generic => accesskit::Role::GenericContainer,
+        Role::Document => accesskit::Role::Document,
+        Role::Heading => accesskit::Role::Heading,
+        Role::Paragraph => accesskit::Role::Paragraph,
+        Role::Label => accesskit::Role::Label,
+        Role::Link => accesskit::Role::Link,
+        Role::Button => accesskit::Role::Button,
+        Role::Image => accesskit::Role::Image,
+        Role::List => accesskit::Role::List,
+        Role::ListItem => accesskit::Role::ListItem,
+        Role::TextInput => accesskit::Role::TextInput,
+        Role::CheckBox => accesskit::Role::CheckBox,
+        Role::Navigation => accesskit::Role::Navigation,
+        Role::Main => accesskit::Role::Main,
+    }
+}
+
+/// Build an accesskit tree update from a tree and its computed layout
+///
+/// Node IDs are preserved (Content IR node `n` becomes accesskit `NodeId(n)`),
+/// so action requests coming back from the platform can be mapped directly to
+/// IR nodes. `focus` is the focused node ID; 0 means the root.
+pub fn build_tree_update(
+    nodes: &NodeTable,
+    props: &PropertyTable,
+    layout: &[LayoutRect],
+    focus: u32,
+) -> TreeUpdate {
+    let mut update_nodes = Vec::with_capacity(nodes.len());
+
+    for idx in 0..nodes.len() {
+        let id = idx as u32 + 1;
+        if !nodes.is_live(id) {
+            continue;
+        }
+        let node_type = nodes.node_types[idx];
+        let role = resolve_role(node_type, props.role[idx]);
+        let mut node = Node::new(to_accesskit_role(role));
+
+        if let Some(rect) = layout.get(idx) {
+            node.set_bounds(Rect::new(
+                rect.x as f64,
+                rect.y as f64,
+                (rect.x + rect.width) as f64,
+                (rect.y + rect.height) as f64,
+            ));
+        }
+
+        // Explicit name wins; text nodes fall back to their content
+        if !props.accessible_name[idx].is_empty() {
+            node.set_label(props.accessible_name[idx].clone());
+        } else if !props.text_content[idx].is_empty() {
+            node.set_label(props.text_content[idx].clone());
+        }
+
+        if !props.accessible_description[idx].is_empty() {
+            node.set_description(props.accessible_description[idx].clone());
+        }
+
+        let children: Vec<NodeId> = nodes
+            .get_children(id)
+            .into_iter()
+            .map(|child| NodeId(child as u64))
+            .collect();
+        if !children.is_empty() {
+            node.set_children(children);
+        }
+
+        update_nodes.push((NodeId(id as u64), node));
+    }
+
+    let root = NodeId(1);
+    let focus = if nodes.is_live(focus) {
+        NodeId(focus as u64)
+    } else {
+        root
+    };
+
+    TreeUpdate {
+        nodes: update_nodes,
+        tree: Some(Tree::new(root)),
+        focus,
+    }
+}
+
+/// Lay out the tree for the given viewport and build an accesskit tree update
+pub fn export_tree(
+    nodes: &NodeTable,
+    props: &PropertyTable,
+    viewport_width: f32,
+    viewport_height: f32,
+) -> TreeUpdate {
+    let layout = compute_layout(nodes, props, viewport_width, viewport_height);
+    build_tree_update(nodes, props, &layout, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_tree_update() {
+        let mut nodes = NodeTable::new();
+        let root = nodes.create_node(NodeType::Root, 0, 0);
+        let link = nodes.create_node(NodeType::Link, root, 0);
+        let button = nodes.create_node(NodeType::Rect, root, 0);
+        let text = nodes.create_node(NodeType::Span, link, 0);
+
+        let mut props = PropertyTable::new();
+        props.resize(nodes.len());
+        props.role[button as usize - 1] = Role::Button;
+        props.accessible_name[button as usize - 1] = "Submit".to_string();
+        props.set_text(link as usize - 1, "Home");
+        props.accessible_name[text as usize - 1] = "Go home".to_string();
+        props.set_text(text as usize - 1, "ignored");
+
+        let layout = vec![LayoutRect::default(); nodes.len()];
+        let update = build_tree_update(&nodes, &props, &layout, link);
+        let node = |id: u32| &update.nodes.iter().find(|(n, _)| *n == NodeId(id as u64)).unwrap().1;
+
+        // Roles derive from node types unless set explicitly
+        assert_eq!(node(root).role(), accesskit::Role::Document);
+        assert_eq!(node(link).role(), accesskit::Role::Link);
+        assert_eq!(node(button).role(), accesskit::Role::Button);
+        assert_eq!(node(text).role(), accesskit::Role::Label);
+
+        // An explicit name wins; otherwise the text is the label
+        assert_eq!(node(button).label(), Some("Submit"));
+        assert_eq!(node(link).label(), Some("Home"));
+        assert_eq!(node(text).label(), Some("Go home"));
+        assert_eq!(node(root).label(), None);
+
+        // Children keep their IR node IDs
+        assert_eq!(node(root).children(), &[NodeId(link as u64), NodeId(button as u64)]);
+        assert_eq!(node(link).children(), &[NodeId(text as u64)]);
+        assert!(node(text).children().is_empty());
+        assert_eq!(update.focus, NodeId(link as u64));
+        assert_eq!(update.tree.as_ref().map(|t| t.root), Some(NodeId(root as u64)));
+    }
+}
@@ -0,0 +1,323 @@
+//! Property Animation
+//!
+//! This module drives keyframe tracks that interpolate a single node property
+//! from a start to an end value over time. `Animator::tick` writes the
+//! interpolated values straight into the `PropertyTable`, so the host only
+//! advances the clock each frame instead of pushing every intermediate value
+//! over FFI.
+
+use crate::properties::PropertyTable;
+use crate::style::overrides;
+
+/// Node property that can be animated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AnimatedProperty {
+    Width = 0,
+    Height = 1,
+    GapRow = 2,
+    GapCol = 3,
+    InsetTop = 4,
+    InsetRight = 5,
+    InsetBottom = 6,
+    InsetLeft = 7,
+    BorderRadius = 8,
+    FontSize = 9,
+    FillR = 10,
+    FillG = 11,
+    FillB = 12,
+    FillA = 13,
+    TextColorA = 14,
+}
+
+impl AnimatedProperty {
+    /// Convert a raw property tag, returning None for unknown values
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(AnimatedProperty::Width),
+            1 => Some(AnimatedProperty::Height),
+            2 => Some(AnimatedProperty::GapRow),
+            3 => Some(AnimatedProperty::GapCol),
+            4 => Some(AnimatedProperty::InsetTop),
+            5 => Some(AnimatedProperty::InsetRight),
+            6 => Some(AnimatedProperty::InsetBottom),
+            7 => Some(AnimatedProperty::InsetLeft),
+            8 => Some(AnimatedProperty::BorderRadius),
+            9 => Some(AnimatedProperty::FontSize),
+            10 => Some(AnimatedProperty::FillR),
+            11 => Some(AnimatedProperty::FillG),
+            12 => Some(AnimatedProperty::FillB),
+            13 => Some(AnimatedProperty::FillA),
+            14 => Some(AnimatedProperty::TextColorA),
+            _ => None,
+        }
+    }
+
+    /// Check if changing the property can move or resize nodes
+    pub fn affects_layout(self) -> bool {
+        !matches!(
+            self,
+            AnimatedProperty::BorderRadius
+                | AnimatedProperty::FillR
+                | AnimatedProperty::FillG
+                | AnimatedProperty::FillB
+                | AnimatedProperty::FillA
+                | AnimatedProperty::TextColorA
+        )
+    }
+
+    /// Style-backed property group (`style::overrides` bit) the property belongs to
+    pub fn style_group(self) -> u32 {
+        match self {
+            AnimatedProperty::Width => overrides::WIDTH,
+            AnimatedProperty::Height => overrides::HEIGHT,
+            AnimatedProperty::GapRow => overrides::GAP_ROW,
+            AnimatedProperty::GapCol => overrides::GAP_COL,
+            AnimatedProperty::InsetTop => overrides::INSET_TOP,
+            AnimatedProperty::InsetRight => overrides::INSET_RIGHT,
+            AnimatedProperty::InsetBottom => overrides::INSET_BOTTOM,
+            AnimatedProperty::InsetLeft => overrides::INSET_LEFT,
+            AnimatedProperty::BorderRadius => overrides::BORDER_RADIUS,
+            AnimatedProperty::FontSize => overrides::FONT_SIZE,
+            AnimatedProperty::FillR | AnimatedProperty::FillG | AnimatedProperty::FillB | AnimatedProperty::FillA => {
+                overrides::FILL
+            }
+            AnimatedProperty::TextColorA => overrides::TEXT_COLOR,
+        }
+    }
+}
+
+/// Easing curve applied to animation progress
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Easing {
+    #[default]
+    Linear = 0,
+    EaseIn = 1,
+    EaseOut = 2,
+    EaseInOut = 3,
+}
+
+impl Easing {
+    /// Convert a raw easing tag (unknown values map to Linear)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Easing::EaseIn,
+            2 => Easing::EaseOut,
+            3 => Easing::EaseInOut,
+            _ => Easing::Linear,
+        }
+    }
+
+    /// Map linear progress in [0, 1] onto the eased curve (cubic)
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => {
+                let u = 1.0 - t;
+                1.0 - u * u * u
+            }
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    let u = -2.0 * t + 2.0;
+                    1.0 - u * u * u / 2.0
+                }
+            }
+        }
+    }
+}
+
+/// A single running animation on one node property
+#[derive(Clone, Debug)]
+pub struct AnimationTrack {
+    pub id: u32,
+    pub node_id: u32,
+    pub property: AnimatedProperty,
+    pub from: f32,
+    pub to: f32,
+    /// Duration in seconds
+    pub duration: f32,
+    /// Time elapsed since the track started, in seconds
+    pub elapsed: f32,
+    pub easing: Easing,
+}
+
+impl AnimationTrack {
+    /// Current interpolated value
+    pub fn value(&self) -> f32 {
+        let t = if self.duration > 0.0 {
+            self.elapsed / self.duration
+        } else {
+            1.0
+        };
+        self.from + (self.to - self.from) * self.easing.apply(t)
+    }
+
+    /// Check if the track has reached its end value
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// Collection of running animation tracks
+#[derive(Default, Debug)]
+pub struct Animator {
+    tracks: Vec<AnimationTrack>,
+    next_id: u32,
+}
+
+impl Animator {
+    /// Create a new animator with no tracks
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start animating a node property and return the track ID (1-indexed)
+    ///
+    /// A track already running on the same node and property is replaced.
+    pub fn start(
+        &mut self,
+        node_id: u32,
+        property: AnimatedProperty,
+        from: f32,
+        to: f32,
+        duration: f32,
+        easing: Easing,
+    ) -> u32 {
+        self.tracks
+            .retain(|t| !(t.node_id == node_id && t.property == property));
+
+        self.next_id += 1;
+        self.tracks.push(AnimationTrack {
+            id: self.next_id,
+            node_id,
+            property,
+            from,
+            to,
+            duration: duration.max(0.0),
+            elapsed: 0.0,
+            easing,
+        });
+        self.next_id
+    }
+
+    /// Stop a track, leaving the property at its current value
+    pub fn stop(&mut self, track_id: u32) -> bool {
+        let before = self.tracks.len();
+        self.tracks.retain(|t| t.id != track_id);
+        self.tracks.len() != before
+    }
+
+    /// Stop every track on a node
+    pub fn stop_node(&mut self, node_id: u32) {
+        self.tracks.retain(|t| t.node_id != node_id);
+    }
+
+    /// Get the number of running tracks
+    pub fn len(&self) -> usize {
+        self.tracks.len()
+    }
+
+    /// Check if no tracks are running
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    /// Advance all tracks by `dt` seconds and write their values into the property table
+    ///
+    /// Finished tracks write their end value and are removed. Returns the
+    /// number of tracks still running.
+    pub fn tick(&mut self, dt: f32, props: &mut PropertyTable) -> usize {
+        for track in &mut self.tracks {
+            track.elapsed += dt.max(0.0);
+            write_property(props, track.node_id, track.property, track.value());
+        }
+        self.tracks.retain(|t| !t.is_finished());
+        self.tracks.len()
+    }
+}
+
+/// Write an animated value into the property table
+fn write_property(props: &mut PropertyTable, node_id: u32, property: AnimatedProperty, value: f32) {
+    if node_id == 0 || node_id as usize > props.width.len() {
+        return;
+    }
+    let idx = node_id as usize - 1;
+    props.mark_dirty(idx);
+    let channel = value.round().clamp(0.0, 255.0) as u8;
+
+    match property {
+        AnimatedProperty::Width => props.width[idx] = value,
+        AnimatedProperty::Height => props.height[idx] = value,
+        AnimatedProperty::GapRow => props.gap_row[idx] = value,
+        AnimatedProperty::GapCol => props.gap_col[idx] = value,
+        AnimatedProperty::InsetTop => props.inset_top[idx] = value,
+        AnimatedProperty::InsetRight => props.inset_right[idx] = value,
+        AnimatedProperty::InsetBottom => props.inset_bottom[idx] = value,
+        AnimatedProperty::InsetLeft => props.inset_left[idx] = value,
+        AnimatedProperty::BorderRadius => props.border_radius[idx] = value,
+        AnimatedProperty::FontSize => props.font_size[idx] = value,
+        AnimatedProperty::FillR => props.fill_r[idx] = channel,
+        AnimatedProperty::FillG => props.fill_g[idx] = channel,
+        AnimatedProperty::FillB => props.fill_b[idx] = channel,
+        AnimatedProperty::FillA => props.fill_a[idx] = channel,
+        AnimatedProperty::TextColorA => props.text_color_a[idx] = channel,
+    }
+    if property.affects_layout() {
+        props.mark_layout_dirty(idx);
+    }
+    // The animated value replaces the style's until the node is reset
+    props.set_override(idx, property.style_group());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> PropertyTable {
+        let mut props = PropertyTable::new();
+        props.resize(2);
+        props
+    }
+
+    #[test]
+    fn test_easing_start_middle_end() {
+        // Width of node 2 from 10 to 30 over 2 seconds: (easing, value halfway)
+        for (easing, middle) in [
+            (Easing::Linear, 20.0),
+            (Easing::EaseIn, 12.5),
+            (Easing::EaseOut, 27.5),
+            (Easing::EaseInOut, 20.0),
+        ] {
+            let mut props = table();
+            let mut animator = Animator::new();
+            animator.start(2, AnimatedProperty::Width, 10.0, 30.0, 2.0, easing);
+
+            assert_eq!(animator.tick(0.0, &mut props), 1);
+            assert_eq!(props.width[1], 10.0, "{easing:?} start");
+            assert_eq!(animator.tick(1.0, &mut props), 1);
+            assert_eq!(props.width[1], middle, "{easing:?} middle");
+            assert_eq!(animator.tick(1.0, &mut props), 0);
+            assert_eq!(props.width[1], 30.0, "{easing:?} end");
+        }
+    }
+
+    #[test]
+    fn test_color_channel_clamped() {
+        let mut props = table();
+        let mut animator = Animator::new();
+        animator.start(2, AnimatedProperty::FillA, -100.0, 400.0, 1.0, Easing::Linear);
+
+        animator.tick(0.0, &mut props);
+        assert_eq!(props.fill_a[1], 0);
+        animator.tick(0.5, &mut props);
+        assert_eq!(props.fill_a[1], 150);
+        animator.tick(0.5, &mut props);
+        assert_eq!(props.fill_a[1], 255);
+        assert!(animator.is_empty());
+    }
+}
@@ -0,0 +1,415 @@
+//! CMMB Binary Format
+//!
+//! This module saves Content IR trees in the binary format of dop-parser's
+//! `CompiledUnit` (magic "CMMB": header, packed nodes, flattened styles) and
+//! loads them back. The compiler's format carries no per-node properties, so
+//! they follow the styles in a "PROP" section, one column per property. The
+//! compiler's reader stops after the styles and ignores it; files from the
+//! compiler load here with default properties.
+
+use zerocopy::{FromBytes, IntoBytes};
+
+use crate::primitives::{NodeTable, NodeType};
+use crate::properties::{Align, Direction, Pack, PropertyTable, Role, SizeUnit};
+use crate::style::{FlatStyle, StyleTable};
+
+/// Binary format magic number "CMMB" (shared with dop-parser)
+pub const MAGIC_NUMBER: u32 = 0x434D4D42;
+/// Binary format version (shared with dop-parser)
+pub const FORMAT_VERSION: u32 = 2;
+/// Marker of the property section "PROP"
+pub const PROPERTY_SECTION: u32 = 0x50524F50;
+
+/// Serialize node, property and style tables
+pub fn write_binary(nodes: &NodeTable, props: &PropertyTable, styles: &StyleTable) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let n = nodes.len();
+    let styles = styles.as_flat();
+
+    // Header, with the compiler's checksum of an environment-less unit
+    let checksum = (n as u64).wrapping_mul(31).wrapping_mul(31).wrapping_add(styles.len() as u64);
+    buf.extend_from_slice(&MAGIC_NUMBER.to_le_bytes());
+    buf.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    buf.extend_from_slice(&0u32.to_le_bytes());
+    buf.extend_from_slice(&checksum.to_le_bytes());
+
+    // Node data (packed)
+    put_u32(&mut buf, n as u32);
+    for i in 0..n {
+        buf.push(nodes.node_types[i] as u8);
+        buf.extend_from_slice(&nodes.parents[i].to_le_bytes());
+        buf.extend_from_slice(&nodes.first_children[i].to_le_bytes());
+        buf.extend_from_slice(&nodes.next_siblings[i].to_le_bytes());
+        buf.extend_from_slice(&nodes.style_ids[i].to_le_bytes());
+    }
+
+    // Style data (using zerocopy)
+    put_u32(&mut buf, styles.len() as u32);
+    for style in styles {
+        buf.extend_from_slice(style.as_bytes());
+    }
+
+    // Property columns, then the removed slots awaiting reuse
+    put_u32(&mut buf, PROPERTY_SECTION);
+    put_u32(&mut buf, n as u32);
+    let p = props;
+    buf.extend(p.direction.iter().map(|&v| v as u8));
+    buf.extend(p.pack.iter().map(|&v| v as u8));
+    buf.extend(p.align.iter().map(|&v| v as u8));
+    buf.extend(p.width_unit.iter().map(|&v| v as u8));
+    buf.extend(p.height_unit.iter().map(|&v| v as u8));
+    for column in [
+        &p.width, &p.height, &p.gap_row, &p.gap_col,
+        &p.inset_top, &p.inset_right, &p.inset_bottom, &p.inset_left,
+        &p.offset_top, &p.offset_right, &p.offset_bottom, &p.offset_left,
+        &p.border_radius, &p.scroll_x, &p.scroll_y, &p.font_size,
+    ] {
+        column.iter().for_each(|&v| put_f32(&mut buf, v));
+    }
+    for column in [
+        &p.fill_r, &p.fill_g, &p.fill_b, &p.fill_a,
+        &p.text_color_r, &p.text_color_g, &p.text_color_b, &p.text_color_a,
+    ] {
+        buf.extend_from_slice(column);
+    }
+    buf.extend(p.role.iter().map(|&v| v as u8));
+    p.overrides.iter().for_each(|&v| put_u32(&mut buf, v));
+    for (columns, (column_span, row_span)) in p.grid_columns.iter().zip(p.grid_column_span.iter().zip(&p.grid_row_span)) {
+        put_u32(&mut buf, columns.len() as u32);
+        columns.iter().for_each(|&v| put_f32(&mut buf, v));
+        buf.extend_from_slice(&column_span.to_le_bytes());
+        buf.extend_from_slice(&row_span.to_le_bytes());
+    }
+    for column in [&p.text_content, &p.accessible_name, &p.accessible_description] {
+        for text in column {
+            put_u32(&mut buf, text.len() as u32);
+            buf.extend_from_slice(text.as_bytes());
+        }
+    }
+    put_u32(&mut buf, nodes.free_slots.len() as u32);
+    nodes.free_slots.iter().for_each(|&id| put_u32(&mut buf, id));
+
+    buf
+}
+
+/// Deserialize tables written by `write_binary` or the compiler
+///
+/// Returns None if the data is truncated, not CMMB version 1, has no root
+/// node, or links nodes into anything but trees (see `links_are_valid`).
+/// All nodes come back dirty, as they have never been painted.
+pub fn read_binary(data: &[u8]) -> Option<(NodeTable, PropertyTable, StyleTable)> {
+    let mut r = Reader { data, offset: 0 };
+    if r.u32()? != MAGIC_NUMBER || r.u32()? != FORMAT_VERSION {
+        return None;
+    }
+    // Environment ID and checksum
+    r.take(12)?;
+
+    let n = r.u32()? as usize;
+    let mut nodes = NodeTable::new();
+    for _ in 0..n {
+        let node = r.take(17)?;
+        let field = |at: usize| u32::from_le_bytes([node[at], node[at + 1], node[at + 2], node[at + 3]]);
+        nodes.node_types.push(NodeType::from_u8(node[0]));
+        nodes.parents.push(field(1));
+        nodes.first_children.push(field(5));
+        nodes.next_siblings.push(field(9));
+        nodes.style_ids.push(field(13));
+        nodes.dirty.push(true);
+        nodes.removed.push(false);
+    }
+    if nodes.node_types.first() != Some(&NodeType::Root) {
+        return None;
+    }
+
+    let style_count = r.u32()? as usize;
+    // The count is untrusted, so it may not reserve more than the data can hold
+    if style_count > r.remaining() / std::mem::size_of::<FlatStyle>() {
+        return None;
+    }
+    let mut styles = Vec::with_capacity(style_count);
+    for _ in 0..style_count {
+        styles.push(FlatStyle::read_from_bytes(r.take(std::mem::size_of::<FlatStyle>())?).ok()?);
+    }
+
+    let mut props = PropertyTable::new();
+    props.resize(n);
+    if r.offset < data.len() {
+        if r.u32()? != PROPERTY_SECTION || r.u32()? as usize != n {
+            return None;
+        }
+        read_properties(&mut r, &mut props, n)?;
+        let free_count = r.u32()? as usize;
+        for _ in 0..free_count {
+            let id = r.u32()?;
+            if id == 0 || id as usize > n || nodes.removed[id as usize - 1] {
+                return None;
+            }
+            nodes.free_slots.push(id);
+            nodes.removed[id as usize - 1] = true;
+        }
+    }
+    if !links_are_valid(&nodes) {
+        return None;
+    }
+
+    Some((nodes, props, StyleTable::from_flat(&styles)))
+}
+
+/// Check that loaded links form trees that traversal can walk safely
+///
+/// Every link must name an existing node. Walking each node without a parent
+/// (the root and detached subtrees) through its child and sibling chains must
+/// reach every node exactly once, from the parent it names, so no chain
+/// loops. The root has no parent or siblings, and free slots are unlinked.
+fn links_are_valid(nodes: &NodeTable) -> bool {
+    let n = nodes.len() as u32;
+    let links = [&nodes.parents, &nodes.first_children, &nodes.next_siblings];
+    if links.iter().any(|column| column.iter().any(|&id| id > n)) {
+        return false;
+    }
+    if nodes.parents[0] != 0 || nodes.next_siblings[0] != 0 {
+        return false;
+    }
+
+    let mut visited = vec![false; n as usize];
+    let mut stack = Vec::new();
+    for top in 1..=n {
+        if nodes.parents[top as usize - 1] != 0 || visited[top as usize - 1] {
+            continue;
+        }
+        if top != 1 && nodes.next_siblings[top as usize - 1] != 0 {
+            return false;
+        }
+        visited[top as usize - 1] = true;
+        stack.push(top);
+        while let Some(node) = stack.pop() {
+            let mut child = nodes.first_children[node as usize - 1];
+            while child != 0 {
+                let idx = child as usize - 1;
+                if visited[idx] || nodes.parents[idx] != node {
+                    return false;
+                }
+                visited[idx] = true;
+                stack.push(child);
+                child = nodes.next_siblings[idx];
+            }
+        }
+    }
+    if visited.contains(&false) {
+        return false;
+    }
+
+    nodes.free_slots.iter().all(|&id| {
+        let idx = id as usize - 1;
+        id != 1 && nodes.parents[idx] == 0 && nodes.first_children[idx] == 0
+    })
+}
+
+/// Read the property columns in `write_binary` order
+fn read_properties(r: &mut Reader, p: &mut PropertyTable, n: usize) -> Option<()> {
+    p.direction = r.take(n)?.iter().map(|&v| Direction::from_u8(v)).collect();
+    p.pack = r.take(n)?.iter().map(|&v| Pack::from_u8(v)).collect();
+    p.align = r.take(n)?.iter().map(|&v| Align::from_u8(v)).collect();
+    p.width_unit = r.take(n)?.iter().map(|&v| SizeUnit::from_u8(v)).collect();
+    p.height_unit = r.take(n)?.iter().map(|&v| SizeUnit::from_u8(v)).collect();
+    for column in [
+        &mut p.width, &mut p.height, &mut p.gap_row, &mut p.gap_col,
+        &mut p.inset_top, &mut p.inset_right, &mut p.inset_bottom, &mut p.inset_left,
+        &mut p.offset_top, &mut p.offset_right, &mut p.offset_bottom, &mut p.offset_left,
+        &mut p.border_radius, &mut p.scroll_x, &mut p.scroll_y, &mut p.font_size,
+    ] {
+        *column = r.f32s(n)?;
+    }
+    for column in [
+        &mut p.fill_r, &mut p.fill_g, &mut p.fill_b, &mut p.fill_a,
+        &mut p.text_color_r, &mut p.text_color_g, &mut p.text_color_b, &mut p.text_color_a,
+    ] {
+        *column = r.take(n)?.to_vec();
+    }
+    p.role = r.take(n)?.iter().map(|&v| Role::from_u8(v)).collect();
+    p.overrides = (0..n).map(|_| r.u32()).collect::<Option<_>>()?;
+    for idx in 0..n {
+        let count = r.u32()? as usize;
+        p.grid_columns[idx] = r.f32s(count)?;
+        p.grid_column_span[idx] = r.u16()?;
+        p.grid_row_span[idx] = r.u16()?;
+    }
+    for column in [&mut p.text_content, &mut p.accessible_name, &mut p.accessible_description] {
+        for text in column.iter_mut() {
+            let len = r.u32()? as usize;
+            *text = String::from_utf8(r.take(len)?.to_vec()).ok()?;
+        }
+    }
+    Some(())
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_f32(buf: &mut Vec<u8>, value: f32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Little-endian cursor over the input; every read fails past the end
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.offset..self.offset.checked_add(len)?)?;
+        self.offset += len;
+        Some(bytes)
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.offset
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn f32s(&mut self, count: usize) -> Option<Vec<f32>> {
+        let bytes = self.take(count.checked_mul(4)?)?;
+        Some(bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::render;
+    use crate::{Color, ContentBuilder, FlatStyle, SizeUnit};
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let mut builder = ContentBuilder::new();
+        builder.styles_mut().add_style(FlatStyle { gap_row: 4.0, ..Default::default() });
+        builder.begin_grid().grid_columns(&[30.0, 0.0]).width_percent(50.0);
+        builder.rect().fill(Color::new(10, 20, 30, 255)).grid_span(2, 1).style(1);
+        builder.rect();
+        builder.end();
+        builder.begin_paragraph();
+        builder.span("héllo").accessible_name("greeting");
+        builder.end();
+        builder.remove(4);
+
+        let bytes = builder.to_binary();
+        let restored = ContentBuilder::from_binary(&bytes).unwrap();
+        let (nodes, props) = builder.tables();
+        let (restored_nodes, restored_props) = restored.tables();
+        assert_eq!(restored_nodes.parents, nodes.parents);
+        assert_eq!(restored_nodes.next_siblings, nodes.next_siblings);
+        assert_eq!(restored_nodes.style_ids, nodes.style_ids);
+        assert_eq!(restored_nodes.free_slots, vec![4]);
+        assert_eq!(restored.styles().len(), 1);
+        assert_eq!(restored_props.width_unit[1], SizeUnit::Percent);
+        assert_eq!(restored_props.overrides, props.overrides);
+        assert_eq!(restored_props.grid_columns[1], vec![30.0, 0.0]);
+        assert_eq!(restored_props.grid_column_span[2], 2);
+        assert_eq!(restored_props.fill_b[2], 30);
+        assert_eq!(restored_props.text_content[5], "héllo");
+        assert_eq!(restored_props.accessible_name[5], "greeting");
+        assert_eq!(
+            render(restored_nodes, restored_props, 200.0, 100.0),
+            render(nodes, props, 200.0, 100.0)
+        );
+
+        assert!(ContentBuilder::from_binary(&bytes[..bytes.len() - 1]).is_none());
+        assert!(ContentBuilder::from_binary(b"CMMB").is_none());
+    }
+
+    /// Root with a rect, a paragraph holding a span, and freed slot 3
+    fn sample() -> Vec<u8> {
+        let mut builder = ContentBuilder::new();
+        builder.rect();
+        builder.rect();
+        builder.begin_paragraph();
+        builder.span("hi");
+        builder.end();
+        builder.remove(3);
+        builder.to_binary()
+    }
+
+    /// Overwrite a u32 link field (1 parent, 5 first child, 9 next sibling)
+    fn set_link(bytes: &mut [u8], id: usize, field: usize, value: u32) {
+        let at = 24 + (id - 1) * 17 + field;
+        bytes[at..at + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn rejects(bytes: &[u8]) -> bool {
+        ContentBuilder::from_binary(bytes).is_none()
+    }
+
+    #[test]
+    fn test_binary_rejects_bad_version() {
+        let mut bytes = sample();
+        assert!(!rejects(&bytes));
+        bytes[4..8].copy_from_slice(&(super::FORMAT_VERSION + 1).to_le_bytes());
+        assert!(rejects(&bytes));
+    }
+
+    #[test]
+    fn test_binary_rejects_out_of_range_links() {
+        for field in [1, 5, 9] {
+            let mut bytes = sample();
+            set_link(&mut bytes, 2, field, 999);
+            assert!(rejects(&bytes), "field {field}");
+        }
+    }
+
+    #[test]
+    fn test_binary_rejects_cycles() {
+        let mut bytes = sample();
+        set_link(&mut bytes, 2, 9, 2);
+        assert!(rejects(&bytes));
+
+        // The span claims to hold the paragraph that holds it
+        let mut bytes = sample();
+        set_link(&mut bytes, 5, 5, 4);
+        set_link(&mut bytes, 4, 1, 5);
+        assert!(rejects(&bytes));
+    }
+
+    #[test]
+    fn test_binary_rejects_inconsistent_parents() {
+        let mut bytes = sample();
+        set_link(&mut bytes, 5, 1, 2);
+        assert!(rejects(&bytes));
+
+        // A child list reaching a node twice
+        let mut bytes = sample();
+        set_link(&mut bytes, 2, 5, 5);
+        assert!(rejects(&bytes));
+    }
+
+    #[test]
+    fn test_binary_rejects_huge_style_count() {
+        let mut bytes = sample();
+        let at = 24 + 5 * 17;
+        bytes[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(rejects(&bytes));
+    }
+
+    #[test]
+    fn test_binary_rejects_linked_free_slot() {
+        // Slot 3 is free, so linking it back under the root must fail
+        let mut bytes = sample();
+        set_link(&mut bytes, 2, 9, 3);
+        set_link(&mut bytes, 3, 1, 1);
+        set_link(&mut bytes, 3, 9, 4);
+        assert!(rejects(&bytes));
+
+        let mut bytes = sample();
+        let free_list = bytes.len() - 4;
+        bytes[free_list..].copy_from_slice(&2u32.to_le_bytes());
+        assert!(rejects(&bytes));
+    }
+}
@@ -3,13 +3,27 @@
 //! This module provides a fluent builder API for constructing Content IR trees.
 
 use crate::primitives::{NodeTable, NodeType};
-use crate::properties::{PropertyTable, Direction, Pack, Align, Color};
+use crate::properties::{PropertyTable, Direction, Pack, Align, Color, Role, SizeUnit};
+use crate::binary::{read_binary, write_binary};
+use crate::damage::DamageTracker;
+use crate::render::{
+    compute_layout_with_measure, estimate_text_size, hit_test, relayout_roots, render_with_layout, update_layout,
+    update_layout_rects, LayoutRect, RenderCommand, TextMeasure,
+};
+use crate::style::{overrides, resolve_node, resolve_styles, StyleTable};
 
 /// Builder for constructing Content-- trees
 pub struct ContentBuilder {
     nodes: NodeTable,
     properties: PropertyTable,
+    styles: StyleTable,
+    damage: DamageTracker,
+    text_measure: Option<Box<TextMeasure>>,
+    layout: Vec<LayoutRect>,
+    /// Viewport `layout` was fully computed for, None if it came from elsewhere
+    layout_viewport: Option<(f32, f32)>,
     current_parent: u32,
+    last_node: u32,
 }
 
 impl ContentBuilder {
@@ -25,10 +39,47 @@ impl ContentBuilder {
         Self {
             nodes,
             properties,
+            styles: StyleTable::new(),
+            damage: DamageTracker::new(),
+            text_measure: None,
+            layout: Vec::new(),
+            layout_viewport: None,
             current_parent: root_id,
+            last_node: root_id,
         }
     }
     
+    /// Load a builder from a CMMB binary (see `to_binary`), None if it is invalid
+    ///
+    /// Building continues at the end of the root's children.
+    pub fn from_binary(data: &[u8]) -> Option<Self> {
+        let (nodes, properties, styles) = read_binary(data)?;
+        let last_node = nodes.len() as u32;
+        Some(Self {
+            nodes,
+            properties,
+            styles,
+            damage: DamageTracker::new(),
+            text_measure: None,
+            layout: Vec::new(),
+            layout_viewport: None,
+            current_parent: 1,
+            last_node,
+        })
+    }
+    
+    /// Save the tree, its properties and shared styles in the compiler's CMMB binary format
+    pub fn to_binary(&self) -> Vec<u8> {
+        write_binary(&self.nodes, &self.properties, &self.styles)
+    }
+    
+    /// Reserve capacity for at least `additional` more nodes
+    pub fn reserve(&mut self, additional: usize) -> &mut Self {
+        self.nodes.reserve(additional);
+        self.properties.reserve(additional);
+        self
+    }
+    
     /// Begin a Stack container
     pub fn begin_stack(&mut self) -> &mut Self {
         let id = self.create_node(NodeType::Stack);
@@ -36,6 +87,20 @@ impl ContentBuilder {
         self
     }
     
+    /// Begin a Grid container
+    pub fn begin_grid(&mut self) -> &mut Self {
+        let id = self.create_node(NodeType::Grid);
+        self.current_parent = id;
+        self
+    }
+    
+    /// Begin a Scroll container, which clips its children to its rectangle
+    pub fn begin_scroll(&mut self) -> &mut Self {
+        let id = self.create_node(NodeType::Scroll);
+        self.current_parent = id;
+        self
+    }
+    
     /// End the current container (move up to parent)
     pub fn end(&mut self) -> &mut Self {
         if self.current_parent > 0 {
@@ -74,6 +139,7 @@ impl ContentBuilder {
         let idx = self.current_parent as usize - 1;
         if idx < self.properties.direction.len() {
             self.properties.direction[idx] = dir;
+            self.properties.set_override(idx, overrides::DIRECTION);
         }
         self
     }
@@ -83,6 +149,7 @@ impl ContentBuilder {
         let idx = self.current_parent as usize - 1;
         if idx < self.properties.pack.len() {
             self.properties.pack[idx] = pack;
+            self.properties.set_override(idx, overrides::PACK);
         }
         self
     }
@@ -92,26 +159,38 @@ impl ContentBuilder {
         let idx = self.current_parent as usize - 1;
         if idx < self.properties.align.len() {
             self.properties.align[idx] = align;
+            self.properties.set_override(idx, overrides::ALIGN);
         }
         self
     }
     
     /// Set width on current node
     pub fn width(&mut self, w: f32) -> &mut Self {
-        let idx = self.current_parent as usize - 1;
-        if idx < self.properties.width.len() {
-            self.properties.width[idx] = w;
-        }
-        self
+        self.set_width(w, SizeUnit::Px)
+    }
+    
+    /// Set width on current node as a percentage of its parent's content width
+    pub fn width_percent(&mut self, percent: f32) -> &mut Self {
+        self.set_width(percent, SizeUnit::Percent)
     }
     
     /// Set height on current node
     pub fn height(&mut self, h: f32) -> &mut Self {
-        let idx = self.current_parent as usize - 1;
-        if idx < self.properties.height.len() {
-            self.properties.height[idx] = h;
-        }
-        self
+        self.set_height(h, SizeUnit::Px)
+    }
+    
+    /// Set height on current node as a percentage of its parent's content height
+    pub fn height_percent(&mut self, percent: f32) -> &mut Self {
+        self.set_height(percent, SizeUnit::Percent)
+    }
+    
+    /// Let current node grow along its parent stack's main axis
+    ///
+    /// Flex nodes share the parent's leftover space in proportion to their
+    /// weights; the cross axis stays content-sized.
+    pub fn flex_grow(&mut self, weight: f32) -> &mut Self {
+        self.set_width(weight, SizeUnit::Flex);
+        self.set_height(weight, SizeUnit::Flex)
     }
     
     /// Set gap on current node
@@ -120,13 +199,44 @@ impl ContentBuilder {
         if idx < self.properties.gap_row.len() {
             self.properties.gap_row[idx] = gap;
             self.properties.gap_col[idx] = gap;
+            self.properties.set_override(idx, overrides::GAP_ROW | overrides::GAP_COL);
+        }
+        self
+    }
+    
+    /// Set row and column gaps separately on current node
+    pub fn grid_gap(&mut self, row: f32, column: f32) -> &mut Self {
+        let idx = self.current_parent as usize - 1;
+        if idx < self.properties.gap_row.len() {
+            self.properties.gap_row[idx] = row;
+            self.properties.gap_col[idx] = column;
+            self.properties.set_override(idx, overrides::GAP_ROW | overrides::GAP_COL);
+        }
+        self
+    }
+    
+    /// Set grid column sizes on current node (> 0 is pixels, 0 shares the leftover width)
+    pub fn grid_columns(&mut self, sizes: &[f32]) -> &mut Self {
+        let idx = self.current_parent as usize - 1;
+        if idx < self.properties.grid_columns.len() {
+            self.properties.grid_columns[idx] = sizes.to_vec();
+        }
+        self
+    }
+    
+    /// Set how many grid columns and rows the last created node spans
+    pub fn grid_span(&mut self, columns: u16, rows: u16) -> &mut Self {
+        let idx = self.last_node as usize - 1;
+        if idx < self.properties.grid_column_span.len() {
+            self.properties.grid_column_span[idx] = columns.max(1);
+            self.properties.grid_row_span[idx] = rows.max(1);
         }
         self
     }
     
     /// Set fill color on last created node
     pub fn fill(&mut self, color: Color) -> &mut Self {
-        let idx = (self.nodes.len() - 1).max(0);
+        let idx = self.last_node as usize - 1;
         self.properties.set_fill(idx, color);
         self
     }
@@ -155,9 +265,10 @@ impl ContentBuilder {
     
     /// Set border radius on last created node
     pub fn border_radius(&mut self, radius: f32) -> &mut Self {
-        let idx = (self.nodes.len() - 1).max(0);
+        let idx = self.last_node as usize - 1;
         if idx < self.properties.border_radius.len() {
             self.properties.border_radius[idx] = radius;
+            self.properties.set_override(idx, overrides::BORDER_RADIUS);
         }
         self
     }
@@ -167,6 +278,7 @@ impl ContentBuilder {
         let idx = self.current_parent as usize - 1;
         if idx < self.properties.font_size.len() {
             self.properties.font_size[idx] = size;
+            self.properties.set_override(idx, overrides::FONT_SIZE);
         }
         self
     }
@@ -186,6 +298,88 @@ impl ContentBuilder {
         self
     }
     
+    /// Set accessibility role on last created node
+    pub fn role(&mut self, role: Role) -> &mut Self {
+        let idx = self.last_node as usize - 1;
+        if idx < self.properties.role.len() {
+            self.properties.role[idx] = role;
+        }
+        self
+    }
+    
+    /// Set accessible name on last created node
+    pub fn accessible_name(&mut self, name: &str) -> &mut Self {
+        let idx = self.last_node as usize - 1;
+        if idx < self.properties.accessible_name.len() {
+            self.properties.accessible_name[idx] = name.to_string();
+        }
+        self
+    }
+    
+    /// Set accessible description on last created node
+    pub fn accessible_description(&mut self, description: &str) -> &mut Self {
+        let idx = self.last_node as usize - 1;
+        if idx < self.properties.accessible_description.len() {
+            self.properties.accessible_description[idx] = description.to_string();
+        }
+        self
+    }
+    
+    /// Set the scroll offset of a Scroll node by ID
+    pub fn scroll_offset(&mut self, node_id: u32, dx: f32, dy: f32) -> &mut Self {
+        if node_id > 0 {
+            self.properties.set_scroll_offset(node_id as usize - 1, dx, dy);
+        }
+        self
+    }
+    
+    /// Set style ID on last created node
+    ///
+    /// Properties the node has not set itself take the style's values at once.
+    pub fn style(&mut self, style_id: u32) -> &mut Self {
+        let idx = self.last_node as usize - 1;
+        self.nodes.style_ids[idx] = style_id;
+        let style = self.styles.get(style_id).copied().unwrap_or_default();
+        resolve_node(&mut self.properties, idx, &style);
+        self
+    }
+    
+    /// Get the ID of the last created node
+    pub fn last_node(&self) -> u32 {
+        self.last_node
+    }
+    
+    /// Remove a node and its descendants (the root can't be removed)
+    ///
+    /// Their IDs are reused by nodes created later. Building continues in the
+    /// removed node's parent if the current container was removed.
+    pub fn remove(&mut self, node_id: u32) -> &mut Self {
+        let parent = self.nodes.get_node(node_id).map_or(0, |node| node.parent);
+        let removed = self.nodes.remove_subtree(node_id);
+        for &id in &removed {
+            self.properties.reset(id as usize - 1);
+        }
+        if removed.contains(&self.current_parent) {
+            self.current_parent = parent;
+        }
+        if removed.contains(&self.last_node) {
+            self.last_node = parent;
+        }
+        self
+    }
+    
+    /// Move a node under `parent`, before its child `reference` (0 appends)
+    pub fn insert_before(&mut self, parent: u32, node_id: u32, reference: u32) -> &mut Self {
+        self.nodes.insert_before(parent, node_id, reference);
+        self
+    }
+    
+    /// Move a node to the end of another node's children
+    pub fn reparent(&mut self, node_id: u32, new_parent: u32) -> &mut Self {
+        self.nodes.reparent(node_id, new_parent);
+        self
+    }
+    
     /// Consume the builder and return the node and property tables
     pub fn build(self) -> (NodeTable, PropertyTable) {
         (self.nodes, self.properties)
@@ -201,10 +395,201 @@ impl ContentBuilder {
         (&mut self.nodes, &mut self.properties)
     }
     
+    /// Get the shared style table referenced by node style IDs
+    pub fn styles(&self) -> &StyleTable {
+        &self.styles
+    }
+    
+    /// Get the shared style table mutably
+    ///
+    /// Styled nodes pick up changed styles at the next layout.
+    pub fn styles_mut(&mut self) -> &mut StyleTable {
+        &mut self.styles
+    }
+    
+    /// Set the callback layout measures text with (None restores the estimate)
+    pub fn set_text_measure(&mut self, measure: Option<Box<TextMeasure>>) {
+        self.text_measure = measure;
+        // Text already laid out was sized by the old callback
+        self.layout_viewport = None;
+    }
+    
+    /// Get the text measure callback, if one was set
+    pub fn text_measure(&self) -> Option<&TextMeasure> {
+        self.text_measure.as_deref()
+    }
+    
+    /// Lay out the tree and keep the result for hit testing
+    ///
+    /// Text is measured with the builder's callback, or estimated if none is set.
+    pub fn compute_layout(&mut self, viewport_width: f32, viewport_height: f32) -> &[LayoutRect] {
+        let measure = self.text_measure.take();
+        self.layout_tree(viewport_width, viewport_height, measure.as_deref().unwrap_or(&estimate_text_size));
+        self.text_measure = measure;
+        &self.layout
+    }
+    
+    /// Lay out again only the subtrees changed since the last layout and
+    /// return their render commands
+    ///
+    /// Nodes added, mutated through dirty-marking setters or marked with
+    /// `mark_layout_dirty` are relaid out from their nearest fixed-size
+    /// ancestor (see `relayout_roots`). Needs an earlier full layout; the
+    /// viewport is taken from it.
+    pub fn update_layout(&mut self) -> Vec<RenderCommand> {
+        let measure = self.text_measure.take();
+        let commands = self.update_layout_with_measure(measure.as_deref().unwrap_or(&estimate_text_size));
+        self.text_measure = measure;
+        commands
+    }
+    
+    /// Like `update_layout`, measuring text with `measure`
+    pub fn update_layout_with_measure(&mut self, measure: &TextMeasure) -> Vec<RenderCommand> {
+        resolve_styles(&self.nodes, &mut self.properties, &self.styles);
+        let roots = relayout_roots(&self.nodes, &self.properties);
+        let commands = update_layout(&self.nodes, &self.properties, &mut self.layout, &roots, measure);
+        self.clear_layout_dirty();
+        commands
+    }
+    
+    /// Get the layout from the last `compute_layout`, render or damage collection
+    pub fn layout(&self) -> &[LayoutRect] {
+        &self.layout
+    }
+    
+    /// Use positions computed elsewhere as the layout (one rectangle per node,
+    /// indexed by node ID - 1) for rendering, hit testing and damage
+    pub fn set_layout(&mut self, positions: Vec<LayoutRect>) {
+        self.layout = positions;
+        self.layout_viewport = None;
+        self.clear_layout_dirty();
+    }
+    
+    /// Get a node's rectangle in the last computed layout (node IDs are 1-based)
+    pub fn layout_rect(&self, node_id: u32) -> Option<LayoutRect> {
+        self.layout.get((node_id as usize).checked_sub(1)?).copied()
+    }
+    
+    /// Lay out and render the tree, keeping the layout for `layout_rect` and hit testing
+    pub fn render(&mut self, viewport_width: f32, viewport_height: f32) -> Vec<RenderCommand> {
+        self.compute_layout(viewport_width, viewport_height);
+        self.render_layout()
+    }
+    
+    /// Like `render`, measuring text with `measure`
+    pub fn render_with_measure(
+        &mut self,
+        viewport_width: f32,
+        viewport_height: f32,
+        measure: &TextMeasure,
+    ) -> Vec<RenderCommand> {
+        self.layout_tree(viewport_width, viewport_height, measure);
+        self.render_layout()
+    }
+    
+    /// Render the last computed layout without laying out again
+    ///
+    /// Returns no commands if nodes were added since that layout.
+    pub fn render_layout(&self) -> Vec<RenderCommand> {
+        render_with_layout(&self.nodes, &self.properties, &self.layout)
+    }
+    
+    /// Find the topmost node at a point in the last computed layout, 0 if none
+    pub fn hit_test(&self, x: f32, y: f32) -> u32 {
+        hit_test(&self.nodes, &self.properties, &self.layout, x, y)
+    }
+    
+    /// Lay out the tree and return the areas changed since the last call
+    ///
+    /// Clears all dirty flags. The first call damages every node. Text is
+    /// measured with the builder's callback, or estimated if none is set.
+    /// With the viewport of the kept layout, only dirty subtrees are laid
+    /// out again (see `relayout_roots`).
+    pub fn collect_damage(&mut self, viewport_width: f32, viewport_height: f32) -> Vec<LayoutRect> {
+        let measure = self.text_measure.take();
+        let damage = self.collect_damage_with_measure(
+            viewport_width,
+            viewport_height,
+            measure.as_deref().unwrap_or(&estimate_text_size),
+        );
+        self.text_measure = measure;
+        damage
+    }
+    
+    /// Like `collect_damage`, measuring text with `measure`
+    ///
+    /// `measure` should be the one the kept layout was computed with.
+    pub fn collect_damage_with_measure(
+        &mut self,
+        viewport_width: f32,
+        viewport_height: f32,
+        measure: &TextMeasure,
+    ) -> Vec<LayoutRect> {
+        let root_resized = self.properties.layout_dirty.first().copied().unwrap_or(true);
+        if self.layout_viewport == Some((viewport_width, viewport_height)) && !root_resized {
+            resolve_styles(&self.nodes, &mut self.properties, &self.styles);
+            let roots = relayout_roots(&self.nodes, &self.properties);
+            update_layout_rects(&self.nodes, &self.properties, &mut self.layout, &roots, measure);
+            self.clear_layout_dirty();
+        } else {
+            self.layout_tree(viewport_width, viewport_height, measure);
+        }
+        self.collect_layout_damage()
+    }
+    
+    /// Return the areas changed since the last damage collection using the
+    /// kept layout (e.g. after `update_layout`) instead of laying out again
+    pub fn collect_layout_damage(&mut self) -> Vec<LayoutRect> {
+        self.damage.collect(&self.nodes, &mut self.properties, &self.layout)
+    }
+    
+    /// Forget the painted frame so the next damage collection covers everything
+    pub fn reset_damage(&mut self) {
+        self.damage.reset();
+    }
+    
+    // Internal helper to lay out with shared styles resolved and keep the result
+    fn layout_tree(&mut self, viewport_width: f32, viewport_height: f32, measure: &TextMeasure) {
+        resolve_styles(&self.nodes, &mut self.properties, &self.styles);
+        self.layout = compute_layout_with_measure(&self.nodes, &self.properties, viewport_width, viewport_height, measure);
+        self.layout_viewport = Some((viewport_width, viewport_height));
+        self.clear_layout_dirty();
+    }
+    
+    // Internal helper to forget changes once they are laid out
+    fn clear_layout_dirty(&mut self) {
+        self.nodes.clear_dirty();
+        self.properties.layout_dirty.iter_mut().for_each(|d| *d = false);
+    }
+    
+    // Internal helpers to set a dimension on the current node
+    fn set_width(&mut self, value: f32, unit: SizeUnit) -> &mut Self {
+        let idx = self.current_parent as usize - 1;
+        if idx < self.properties.width.len() {
+            self.properties.width[idx] = value;
+            self.properties.width_unit[idx] = unit;
+            self.properties.set_override(idx, overrides::WIDTH);
+            self.properties.mark_layout_dirty(idx);
+        }
+        self
+    }
+    
+    fn set_height(&mut self, value: f32, unit: SizeUnit) -> &mut Self {
+        let idx = self.current_parent as usize - 1;
+        if idx < self.properties.height.len() {
+            self.properties.height[idx] = value;
+            self.properties.height_unit[idx] = unit;
+            self.properties.set_override(idx, overrides::HEIGHT);
+            self.properties.mark_layout_dirty(idx);
+        }
+        self
+    }
+    
     // Internal helper to create a node
     fn create_node(&mut self, node_type: NodeType) -> u32 {
         let id = self.nodes.create_node(node_type, self.current_parent, 0);
         self.properties.resize(self.nodes.len());
+        self.last_node = id;
         id
     }
 }
@@ -0,0 +1,155 @@
+//! Damage Tracking
+//!
+//! This module turns node dirty flags into damage rectangles for the
+//! renderer. A node is damaged when it was marked dirty by a mutation or when
+//! its layout rectangle moved; both its previous and current rectangles are
+//! reported so stale pixels are repainted too.
+
+use crate::primitives::NodeTable;
+use crate::properties::PropertyTable;
+use crate::render::LayoutRect;
+
+/// Remembers the last painted layout so changes can be diffed
+#[derive(Clone, Default, Debug)]
+pub struct DamageTracker {
+    previous: Vec<LayoutRect>,
+}
+
+impl DamageTracker {
+    /// Create a tracker with no painted frame
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget the previous frame, so the next collection damages every node
+    pub fn reset(&mut self) {
+        self.previous.clear();
+    }
+
+    /// Collect damage for a newly computed layout and clear all dirty flags
+    pub fn collect(&mut self, nodes: &NodeTable, props: &mut PropertyTable, layout: &[LayoutRect]) -> Vec<LayoutRect> {
+        let mut damage = Vec::new();
+
+        for (idx, rect) in layout.iter().enumerate().take(nodes.len()) {
+            let old = self.previous.get(idx);
+            let dirty = props.dirty.get(idx).copied().unwrap_or(true);
+            if !dirty && old == Some(rect) {
+                continue;
+            }
+
+            push_rect(&mut damage, *rect);
+            if let Some(old) = old {
+                if old != rect {
+                    push_rect(&mut damage, *old);
+                }
+            }
+        }
+
+        // Nodes that no longer exist leave their old area behind
+        for old in self.previous.iter().skip(layout.len()) {
+            push_rect(&mut damage, *old);
+        }
+
+        props.dirty.iter_mut().for_each(|d| *d = false);
+        self.previous = layout.to_vec();
+        damage
+    }
+}
+
+/// More damage rectangles than this are merged into their bounding box,
+/// which keeps each push (and so a collection) linear in the node count
+const MAX_DAMAGE_RECTS: usize = 32;
+
+/// Add a rectangle unless it is empty or already covered by an earlier one
+fn push_rect(damage: &mut Vec<LayoutRect>, rect: LayoutRect) {
+    if rect.width <= 0.0 || rect.height <= 0.0 {
+        return;
+    }
+    let covered = damage.iter().any(|d| {
+        d.x <= rect.x
+            && d.y <= rect.y
+            && d.x + d.width >= rect.x + rect.width
+            && d.y + d.height >= rect.y + rect.height
+    });
+    if covered {
+        return;
+    }
+    if damage.len() < MAX_DAMAGE_RECTS {
+        damage.push(rect);
+        return;
+    }
+    let bounds = damage.drain(..).fold(rect, |a, b| {
+        let (x, y) = (a.x.min(b.x), a.y.min(b.y));
+        LayoutRect {
+            x,
+            y,
+            width: (a.x + a.width).max(b.x + b.width) - x,
+            height: (a.y + a.height).max(b.y + b.height) - y,
+        }
+    });
+    damage.push(bounds);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, ContentBuilder};
+
+    /// Root stacking three 100x20 rects
+    fn stack() -> ContentBuilder {
+        let mut builder = ContentBuilder::new();
+        for _ in 0..3 {
+            builder.begin_stack().width(100.0).height(20.0).end();
+        }
+        builder.collect_damage(200.0, 200.0);
+        builder
+    }
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> LayoutRect {
+        LayoutRect { x, y, width, height }
+    }
+
+    #[test]
+    fn test_damage_unchanged_tree() {
+        let mut builder = ContentBuilder::new();
+        assert!(!builder.collect_damage(200.0, 200.0).is_empty());
+        let mut builder = stack();
+        assert!(builder.collect_damage(200.0, 200.0).is_empty());
+    }
+
+    #[test]
+    fn test_damage_color_change() {
+        let mut builder = stack();
+        builder.tables_mut().1.set_fill(2, Color::new(255, 0, 0, 255));
+        assert_eq!(builder.collect_damage(200.0, 200.0), vec![rect(0.0, 20.0, 100.0, 20.0)]);
+    }
+
+    #[test]
+    fn test_damage_move() {
+        // Growing the first rect pushes the others down
+        let mut builder = stack();
+        builder.tables_mut().1.set_size(1, 100.0, 30.0);
+        let damage = builder.collect_damage(200.0, 200.0);
+        for moved in [rect(0.0, 30.0, 100.0, 20.0), rect(0.0, 20.0, 100.0, 20.0)] {
+            assert!(damage.contains(&moved), "{damage:?}");
+        }
+        assert!(damage.contains(&rect(0.0, 50.0, 100.0, 20.0)));
+        assert!(damage.contains(&rect(0.0, 40.0, 100.0, 20.0)));
+    }
+
+    #[test]
+    fn test_damage_removal() {
+        let mut builder = stack();
+        builder.remove(4);
+        assert_eq!(builder.collect_damage(200.0, 200.0), vec![rect(0.0, 40.0, 100.0, 20.0)]);
+    }
+
+    #[test]
+    fn test_damage_merges_into_bounds() {
+        let mut damage = Vec::new();
+        for i in 0..MAX_DAMAGE_RECTS + 1 {
+            push_rect(&mut damage, rect(i as f32 * 10.0, 0.0, 5.0, 5.0));
+        }
+        assert_eq!(damage, vec![rect(0.0, 0.0, MAX_DAMAGE_RECTS as f32 * 10.0 + 5.0, 5.0)]);
+    }
+}
@@ -3,218 +3,835 @@
 //! This module provides C-compatible FFI functions for calling from Julia.
 
 use std::ffi::CStr;
-use std::os::raw::c_char;
+use std::os::raw::{c_char, c_int, c_void};
 
+use crate::animation::{AnimatedProperty, Animator, Easing};
 use crate::builder::ContentBuilder;
-use crate::properties::{Direction, Pack, Align, Color};
+use crate::ffi_guard::ffi_guard;
+use crate::properties::{Direction, Pack, Align, Color, Role};
+use crate::render::LayoutRect;
+use crate::stats::NodeStats;
+use crate::style::FlatStyle;
+use crate::traversal::{SubtreeCursor, TraversalOrder};
 
 /// Opaque handle for ContentBuilder
 pub struct BuilderHandle {
     builder: Box<ContentBuilder>,
 }
 
+impl BuilderHandle {
+    /// Get the wrapped builder (for Rust crates sharing the handle)
+    pub fn builder(&self) -> &ContentBuilder {
+        &self.builder
+    }
+    
+    /// Get the wrapped builder mutably (for Rust crates sharing the handle)
+    pub fn builder_mut(&mut self) -> &mut ContentBuilder {
+        &mut self.builder
+    }
+}
+
+/// Get the message of the last panic caught on this thread (null if none)
+///
+/// Functions that panic return 0 or null and record the message here. The
+/// string is owned by the library and valid until the next error or
+/// `content_ir_clear_last_error` on the same thread.
+#[no_mangle]
+pub extern "C" fn content_ir_last_error() -> *const c_char {
+    crate::ffi_guard::last_error_ptr()
+}
+
+/// Clear the last error of this thread
+#[no_mangle]
+pub extern "C" fn content_ir_clear_last_error() {
+    crate::ffi_guard::clear_last_error();
+}
+
 /// Create a new ContentBuilder
 #[no_mangle]
 pub extern "C" fn content_builder_new() -> *mut BuilderHandle {
-    let builder = Box::new(ContentBuilder::new());
-    Box::into_raw(Box::new(BuilderHandle { builder }))
+    ffi_guard("content_builder_new", || {
+        let builder = Box::new(ContentBuilder::new());
+        Box::into_raw(Box::new(BuilderHandle { builder }))
+    })
 }
 
 /// Free a ContentBuilder
 #[no_mangle]
 pub extern "C" fn content_builder_free(handle: *mut BuilderHandle) {
-    if !handle.is_null() {
-        unsafe {
-            let _ = Box::from_raw(handle);
+    ffi_guard("content_builder_free", || {
+        if !handle.is_null() {
+            unsafe {
+                let _ = Box::from_raw(handle);
+            }
         }
-    }
+    })
+}
+
+/// Reserve capacity for additional nodes
+#[no_mangle]
+pub extern "C" fn content_builder_reserve(handle: *mut BuilderHandle, additional: usize) {
+    ffi_guard("content_builder_reserve", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.reserve(additional);
+        }
+    })
 }
 
 /// Begin a Stack container
 #[no_mangle]
 pub extern "C" fn content_builder_begin_stack(handle: *mut BuilderHandle) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        h.builder.begin_stack();
-    }
+    ffi_guard("content_builder_begin_stack", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.begin_stack();
+        }
+    })
+}
+
+/// Begin a Grid container
+#[no_mangle]
+pub extern "C" fn content_builder_begin_grid(handle: *mut BuilderHandle) {
+    ffi_guard("content_builder_begin_grid", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.begin_grid();
+        }
+    })
+}
+
+/// Begin a Scroll container
+#[no_mangle]
+pub extern "C" fn content_builder_begin_scroll(handle: *mut BuilderHandle) {
+    ffi_guard("content_builder_begin_scroll", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.begin_scroll();
+        }
+    })
 }
 
 /// End the current container
 #[no_mangle]
 pub extern "C" fn content_builder_end(handle: *mut BuilderHandle) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        h.builder.end();
-    }
+    ffi_guard("content_builder_end", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.end();
+        }
+    })
 }
 
 /// Add a Rect node
 #[no_mangle]
 pub extern "C" fn content_builder_rect(handle: *mut BuilderHandle) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        h.builder.rect();
-    }
+    ffi_guard("content_builder_rect", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.rect();
+        }
+    })
 }
 
 /// Begin a Paragraph node
 #[no_mangle]
 pub extern "C" fn content_builder_begin_paragraph(handle: *mut BuilderHandle) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        h.builder.begin_paragraph();
-    }
+    ffi_guard("content_builder_begin_paragraph", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.begin_paragraph();
+        }
+    })
 }
 
 /// Add a Span node with text
 #[no_mangle]
 pub extern "C" fn content_builder_span(handle: *mut BuilderHandle, text: *const c_char) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        if !text.is_null() {
-            if let Ok(text_str) = unsafe { CStr::from_ptr(text) }.to_str() {
-                h.builder.span(text_str);
+    ffi_guard("content_builder_span", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            if !text.is_null() {
+                if let Ok(text_str) = unsafe { CStr::from_ptr(text) }.to_str() {
+                    h.builder.span(text_str);
+                }
             }
         }
-    }
+    })
 }
 
 /// Set direction
 #[no_mangle]
 pub extern "C" fn content_builder_direction(handle: *mut BuilderHandle, dir: u8) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        let direction = match dir {
-            0 => Direction::Down,
-            1 => Direction::Up,
-            2 => Direction::Right,
-            3 => Direction::Left,
-            _ => Direction::Down,
-        };
-        h.builder.direction(direction);
-    }
+    ffi_guard("content_builder_direction", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.direction(Direction::from_u8(dir));
+        }
+    })
 }
 
 /// Set pack
 #[no_mangle]
 pub extern "C" fn content_builder_pack(handle: *mut BuilderHandle, pack: u8) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        let pack_val = match pack {
-            0 => Pack::Start,
-            1 => Pack::End,
-            2 => Pack::Center,
-            3 => Pack::SpaceBetween,
-            4 => Pack::SpaceAround,
-            5 => Pack::SpaceEvenly,
-            _ => Pack::Start,
-        };
-        h.builder.pack(pack_val);
-    }
+    ffi_guard("content_builder_pack", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.pack(Pack::from_u8(pack));
+        }
+    })
 }
 
 /// Set align
 #[no_mangle]
 pub extern "C" fn content_builder_align(handle: *mut BuilderHandle, align: u8) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        let align_val = match align {
-            0 => Align::Start,
-            1 => Align::End,
-            2 => Align::Center,
-            3 => Align::Stretch,
-            _ => Align::Start,
-        };
-        h.builder.align(align_val);
-    }
+    ffi_guard("content_builder_align", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.align(Align::from_u8(align));
+        }
+    })
 }
 
 /// Set width
 #[no_mangle]
 pub extern "C" fn content_builder_width(handle: *mut BuilderHandle, width: f32) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        h.builder.width(width);
-    }
+    ffi_guard("content_builder_width", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.width(width);
+        }
+    })
 }
 
 /// Set height
 #[no_mangle]
 pub extern "C" fn content_builder_height(handle: *mut BuilderHandle, height: f32) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        h.builder.height(height);
-    }
+    ffi_guard("content_builder_height", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.height(height);
+        }
+    })
+}
+
+/// Set width as a percentage of the parent's content width
+#[no_mangle]
+pub extern "C" fn content_builder_width_percent(handle: *mut BuilderHandle, percent: f32) {
+    ffi_guard("content_builder_width_percent", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.width_percent(percent);
+        }
+    })
+}
+
+/// Set height as a percentage of the parent's content height
+#[no_mangle]
+pub extern "C" fn content_builder_height_percent(handle: *mut BuilderHandle, percent: f32) {
+    ffi_guard("content_builder_height_percent", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.height_percent(percent);
+        }
+    })
+}
+
+/// Share the parent stack's leftover main-axis space by weight
+#[no_mangle]
+pub extern "C" fn content_builder_flex_grow(handle: *mut BuilderHandle, weight: f32) {
+    ffi_guard("content_builder_flex_grow", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.flex_grow(weight);
+        }
+    })
 }
 
 /// Set gap
 #[no_mangle]
 pub extern "C" fn content_builder_gap(handle: *mut BuilderHandle, gap: f32) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        h.builder.gap(gap);
-    }
+    ffi_guard("content_builder_gap", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.gap(gap);
+        }
+    })
+}
+
+/// Set row and column gaps separately
+#[no_mangle]
+pub extern "C" fn content_builder_grid_gap(handle: *mut BuilderHandle, row: f32, column: f32) {
+    ffi_guard("content_builder_grid_gap", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.grid_gap(row, column);
+        }
+    })
+}
+
+/// Set grid column sizes from `count` floats (> 0 is pixels, 0 shares the leftover width)
+#[no_mangle]
+pub extern "C" fn content_builder_grid_columns(handle: *mut BuilderHandle, sizes: *const f32, count: usize) {
+    ffi_guard("content_builder_grid_columns", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            let sizes = if sizes.is_null() {
+                &[][..]
+            } else {
+                unsafe { std::slice::from_raw_parts(sizes, count) }
+            };
+            h.builder.grid_columns(sizes);
+        }
+    })
+}
+
+/// Set the grid column and row span of the last created node
+#[no_mangle]
+pub extern "C" fn content_builder_grid_span(handle: *mut BuilderHandle, columns: u16, rows: u16) {
+    ffi_guard("content_builder_grid_span", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.grid_span(columns, rows);
+        }
+    })
 }
 
 /// Set fill color from hex string
 #[no_mangle]
 pub extern "C" fn content_builder_fill_hex(handle: *mut BuilderHandle, hex: *const c_char) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        if !hex.is_null() {
-            if let Ok(hex_str) = unsafe { CStr::from_ptr(hex) }.to_str() {
-                h.builder.fill_hex(hex_str);
+    ffi_guard("content_builder_fill_hex", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            if !hex.is_null() {
+                if let Ok(hex_str) = unsafe { CStr::from_ptr(hex) }.to_str() {
+                    h.builder.fill_hex(hex_str);
+                }
             }
         }
-    }
+    })
 }
 
 /// Set fill color from RGBA
 #[no_mangle]
 pub extern "C" fn content_builder_fill_rgba(handle: *mut BuilderHandle, r: u8, g: u8, b: u8, a: u8) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        h.builder.fill(Color::new(r, g, b, a));
-    }
+    ffi_guard("content_builder_fill_rgba", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.fill(Color::new(r, g, b, a));
+        }
+    })
 }
 
 /// Set inset (padding)
 #[no_mangle]
 pub extern "C" fn content_builder_inset(handle: *mut BuilderHandle, inset: f32) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        h.builder.inset(inset);
-    }
+    ffi_guard("content_builder_inset", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.inset(inset);
+        }
+    })
 }
 
 /// Set inset with individual sides
 #[no_mangle]
 pub extern "C" fn content_builder_inset_trbl(handle: *mut BuilderHandle, top: f32, right: f32, bottom: f32, left: f32) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        h.builder.inset_trbl(top, right, bottom, left);
-    }
+    ffi_guard("content_builder_inset_trbl", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.inset_trbl(top, right, bottom, left);
+        }
+    })
 }
 
 /// Set border radius
 #[no_mangle]
 pub extern "C" fn content_builder_border_radius(handle: *mut BuilderHandle, radius: f32) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        h.builder.border_radius(radius);
-    }
+    ffi_guard("content_builder_border_radius", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.border_radius(radius);
+        }
+    })
 }
 
 /// Set font size
 #[no_mangle]
 pub extern "C" fn content_builder_font_size(handle: *mut BuilderHandle, size: f32) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        h.builder.font_size(size);
-    }
+    ffi_guard("content_builder_font_size", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.font_size(size);
+        }
+    })
 }
 
 /// Set text color from hex string
 #[no_mangle]
 pub extern "C" fn content_builder_text_color_hex(handle: *mut BuilderHandle, hex: *const c_char) {
-    if let Some(h) = unsafe { handle.as_mut() } {
-        if !hex.is_null() {
-            if let Ok(hex_str) = unsafe { CStr::from_ptr(hex) }.to_str() {
-                h.builder.text_color_hex(hex_str);
+    ffi_guard("content_builder_text_color_hex", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            if !hex.is_null() {
+                if let Ok(hex_str) = unsafe { CStr::from_ptr(hex) }.to_str() {
+                    h.builder.text_color_hex(hex_str);
+                }
             }
         }
-    }
+    })
+}
+
+/// Set accessibility role on last created node
+#[no_mangle]
+pub extern "C" fn content_builder_role(handle: *mut BuilderHandle, role: u8) {
+    ffi_guard("content_builder_role", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.role(Role::from_u8(role));
+        }
+    })
+}
+
+/// Set accessible name on last created node
+#[no_mangle]
+pub extern "C" fn content_builder_accessible_name(handle: *mut BuilderHandle, name: *const c_char) {
+    ffi_guard("content_builder_accessible_name", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            if !name.is_null() {
+                if let Ok(name_str) = unsafe { CStr::from_ptr(name) }.to_str() {
+                    h.builder.accessible_name(name_str);
+                }
+            }
+        }
+    })
+}
+
+/// Set accessible description on last created node
+#[no_mangle]
+pub extern "C" fn content_builder_accessible_description(handle: *mut BuilderHandle, description: *const c_char) {
+    ffi_guard("content_builder_accessible_description", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            if !description.is_null() {
+                if let Ok(desc_str) = unsafe { CStr::from_ptr(description) }.to_str() {
+                    h.builder.accessible_description(desc_str);
+                }
+            }
+        }
+    })
+}
+
+/// Set style ID on last created node
+#[no_mangle]
+pub extern "C" fn content_builder_style(handle: *mut BuilderHandle, style_id: u32) {
+    ffi_guard("content_builder_style", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.style(style_id);
+        }
+    })
+}
+
+/// Load flattened styles from the compiler (entry `i` becomes style ID `i + 1`)
+#[no_mangle]
+pub extern "C" fn content_builder_load_styles(handle: *mut BuilderHandle, styles: *const FlatStyle, count: usize) {
+    ffi_guard("content_builder_load_styles", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            if styles.is_null() {
+                h.builder.styles_mut().load_flat(&[]);
+            } else {
+                let slice = unsafe { std::slice::from_raw_parts(styles, count) };
+                h.builder.styles_mut().load_flat(slice);
+            }
+        }
+    })
+}
+
+/// Set how far a Scroll node's content is scrolled (node IDs are 1-based, in creation order)
+#[no_mangle]
+pub extern "C" fn content_builder_scroll_offset(handle: *mut BuilderHandle, node_id: u32, dx: f32, dy: f32) {
+    ffi_guard("content_builder_scroll_offset", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.scroll_offset(node_id, dx, dy);
+        }
+    })
+}
+
+/// Mark a node as changed so its area is repainted
+#[no_mangle]
+pub extern "C" fn content_builder_mark_dirty(handle: *mut BuilderHandle, node_id: u32) {
+    ffi_guard("content_builder_mark_dirty", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            if node_id > 0 {
+                h.builder.tables_mut().1.mark_dirty(node_id as usize - 1);
+            }
+        }
+    })
+}
+
+/// Text measure callback: UTF-8 text and its byte length, font size and the
+/// registered user data; writes the text's width and height
+pub type TextMeasureCallback =
+    extern "C" fn(text: *const u8, len: usize, font_size: f32, user_data: *mut c_void, out_width: *mut f32, out_height: *mut f32);
+
+/// Measure text for layout with a host callback (null restores the built-in estimate)
+/// `user_data` is passed back to every call and must stay valid while the builder uses it
+#[no_mangle]
+pub extern "C" fn content_builder_set_text_measure(
+    handle: *mut BuilderHandle,
+    callback: Option<TextMeasureCallback>,
+    user_data: *mut c_void,
+) {
+    ffi_guard("content_builder_set_text_measure", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            let measure = callback.map(|callback| -> Box<crate::render::TextMeasure> {
+                Box::new(move |text: &str, font_size: f32| {
+                    let (mut width, mut height) = (0.0, 0.0);
+                    callback(text.as_ptr(), text.len(), font_size, user_data, &mut width, &mut height);
+                    (width, height)
+                })
+            });
+            h.builder.set_text_measure(measure);
+        }
+    })
+}
+
+/// Collect damage rectangles since the last call as packed (x, y, width, height) floats
+/// Writes up to `capacity` rectangles to `out` and returns the total number of rectangles
+#[no_mangle]
+pub extern "C" fn content_builder_collect_damage(
+    handle: *mut BuilderHandle,
+    viewport_width: f32,
+    viewport_height: f32,
+    out: *mut f32,
+    capacity: usize,
+) -> usize {
+    ffi_guard("content_builder_collect_damage", || {
+        let Some(h) = (unsafe { handle.as_mut() }) else {
+            return 0;
+        };
+        let damage = h.builder.collect_damage(viewport_width, viewport_height);
+        if !out.is_null() {
+            let out = unsafe { std::slice::from_raw_parts_mut(out, capacity * 4) };
+            for (chunk, rect) in out.chunks_exact_mut(4).zip(&damage) {
+                chunk.copy_from_slice(&[rect.x, rect.y, rect.width, rect.height]);
+            }
+        }
+        damage.len()
+    })
+}
+
+/// Mark a node's size or position inputs as changed so the next layout update redoes it
+#[no_mangle]
+pub extern "C" fn content_builder_mark_layout_dirty(handle: *mut BuilderHandle, node_id: u32) {
+    ffi_guard("content_builder_mark_layout_dirty", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            if node_id > 0 {
+                h.builder.tables_mut().1.mark_layout_dirty(node_id as usize - 1);
+            }
+        }
+    })
+}
+
+/// Lay out again only the subtrees changed since the last layout
+/// Returns the number of render commands for the updated subtrees
+#[no_mangle]
+pub extern "C" fn content_builder_update_layout(handle: *mut BuilderHandle) -> usize {
+    ffi_guard("content_builder_update_layout", || {
+        match unsafe { handle.as_mut() } {
+            Some(h) => h.builder.update_layout().len(),
+            None => 0,
+        }
+    })
+}
+
+/// Lay out the tree for a viewport and keep the result for hit testing
+#[no_mangle]
+pub extern "C" fn content_builder_compute_layout(handle: *mut BuilderHandle, viewport_width: f32, viewport_height: f32) {
+    ffi_guard("content_builder_compute_layout", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.compute_layout(viewport_width, viewport_height);
+        }
+    })
+}
+
+/// Upload externally computed layout as packed (x, y, width, height) floats, one rectangle per node
+/// Rectangle `i` belongs to node ID `i + 1`; it replaces the builder's layout until the next layout pass
+#[no_mangle]
+pub extern "C" fn content_builder_set_layout(handle: *mut BuilderHandle, positions: *const f32, count: usize) {
+    ffi_guard("content_builder_set_layout", || {
+        let Some(h) = (unsafe { handle.as_mut() }) else {
+            return;
+        };
+        let positions = if positions.is_null() {
+            Vec::new()
+        } else {
+            unsafe { std::slice::from_raw_parts(positions, count * 4) }
+                .chunks_exact(4)
+                .map(|p| LayoutRect { x: p[0], y: p[1], width: p[2], height: p[3] })
+                .collect()
+        };
+        h.builder.set_layout(positions);
+    })
+}
+
+/// Get the ID of the topmost node at a point, 0 if none
+/// Uses the layout from the last `content_builder_compute_layout`, render or damage collection
+#[no_mangle]
+pub extern "C" fn content_render_hit_test(handle: *const BuilderHandle, x: f32, y: f32) -> u32 {
+    ffi_guard("content_render_hit_test", || {
+        match unsafe { handle.as_ref() } {
+            Some(h) => h.builder.hit_test(x, y),
+            None => 0,
+        }
+    })
+}
+
+/// Read a node's rectangle from the last computed layout into the out pointers
+/// Returns 1 on success, 0 if the node has no layout yet
+#[no_mangle]
+pub extern "C" fn content_layout_get_rect(
+    handle: *const BuilderHandle,
+    node_id: u32,
+    x: *mut f32,
+    y: *mut f32,
+    width: *mut f32,
+    height: *mut f32,
+) -> c_int {
+    ffi_guard("content_layout_get_rect", || {
+        let Some(rect) = (unsafe { handle.as_ref() }).and_then(|h| h.builder.layout_rect(node_id)) else {
+            return 0;
+        };
+        for (out, value) in [(x, rect.x), (y, rect.y), (width, rect.width), (height, rect.height)] {
+            if let Some(out) = unsafe { out.as_mut() } {
+                *out = value;
+            }
+        }
+        1
+    })
+}
+
+/// Get the ID of the last created node (IDs of removed nodes are reused)
+#[no_mangle]
+pub extern "C" fn content_builder_last_node(handle: *const BuilderHandle) -> u32 {
+    ffi_guard("content_builder_last_node", || {
+        match unsafe { handle.as_ref() } {
+            Some(h) => h.builder.last_node(),
+            None => 0,
+        }
+    })
+}
+
+/// Remove a node and its descendants
+#[no_mangle]
+pub extern "C" fn content_builder_remove(handle: *mut BuilderHandle, node_id: u32) {
+    ffi_guard("content_builder_remove", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.remove(node_id);
+        }
+    })
+}
+
+/// Move a node under `parent`, before its child `reference` (0 appends)
+#[no_mangle]
+pub extern "C" fn content_builder_insert_before(handle: *mut BuilderHandle, parent: u32, node_id: u32, reference: u32) {
+    ffi_guard("content_builder_insert_before", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.insert_before(parent, node_id, reference);
+        }
+    })
+}
+
+/// Move a node to the end of another node's children
+#[no_mangle]
+pub extern "C" fn content_builder_reparent(handle: *mut BuilderHandle, node_id: u32, new_parent: u32) {
+    ffi_guard("content_builder_reparent", || {
+        if let Some(h) = unsafe { handle.as_mut() } {
+            h.builder.reparent(node_id, new_parent);
+        }
+    })
+}
+
+/// Save the builder's tree in the CMMB binary format into a malloc'd buffer
+/// Free the buffer with `content_binary_buffer_free`; returns 1 on success, 0 on failure
+#[no_mangle]
+pub extern "C" fn content_builder_write_binary(handle: *const BuilderHandle, buffer: *mut *mut u8, length: *mut usize) -> c_int {
+    ffi_guard("content_builder_write_binary", || {
+        let (Some(h), false, false) = (unsafe { handle.as_ref() }, buffer.is_null(), length.is_null()) else {
+            return 0;
+        };
+        let bytes = h.builder.to_binary();
+        unsafe {
+            let ptr = libc::malloc(bytes.len()) as *mut u8;
+            if ptr.is_null() {
+                return 0;
+            }
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
+            *buffer = ptr;
+            *length = bytes.len();
+        }
+        1
+    })
+}
+
+/// Free a buffer from `content_builder_write_binary`
+#[no_mangle]
+pub extern "C" fn content_binary_buffer_free(buffer: *mut u8) {
+    ffi_guard("content_binary_buffer_free", || {
+        if !buffer.is_null() {
+            unsafe {
+                libc::free(buffer as *mut c_void);
+            }
+        }
+    })
+}
+
+/// Load a builder from a CMMB binary (returns null if the data is invalid)
+#[no_mangle]
+pub extern "C" fn content_builder_read_binary(data: *const u8, length: usize) -> *mut BuilderHandle {
+    ffi_guard("content_builder_read_binary", || {
+        if data.is_null() {
+            return std::ptr::null_mut();
+        }
+        let data = unsafe { std::slice::from_raw_parts(data, length) };
+        match ContentBuilder::from_binary(data) {
+            Some(builder) => Box::into_raw(Box::new(BuilderHandle { builder: Box::new(builder) })),
+            None => std::ptr::null_mut(),
+        }
+    })
 }
 
 /// Get node count
 #[no_mangle]
 pub extern "C" fn content_builder_node_count(handle: *const BuilderHandle) -> usize {
-    if let Some(h) = unsafe { handle.as_ref() } {
-        h.builder.tables().0.len()
-    } else {
-        0
-    }
+    ffi_guard("content_builder_node_count", || {
+        if let Some(h) = unsafe { handle.as_ref() } {
+            h.builder.tables().0.len()
+        } else {
+            0
+        }
+    })
+}
+
+/// Compute tree statistics into `out` (returns 1 on success, 0 on failure)
+#[no_mangle]
+pub extern "C" fn content_builder_stats(handle: *const BuilderHandle, out: *mut NodeStats) -> c_int {
+    ffi_guard("content_builder_stats", || {
+        match unsafe { (handle.as_ref(), out.as_mut()) } {
+            (Some(h), Some(out)) => {
+                *out = h.builder.tables().0.stats();
+                1
+            }
+            _ => 0,
+        }
+    })
+}
+
+/// Copy up to `capacity` orphaned node IDs into `out` and return the total orphan count
+#[no_mangle]
+pub extern "C" fn content_builder_orphans(handle: *const BuilderHandle, out: *mut u32, capacity: usize) -> usize {
+    ffi_guard("content_builder_orphans", || {
+        let Some(h) = (unsafe { handle.as_ref() }) else {
+            return 0;
+        };
+        let orphans = h.builder.tables().0.find_orphans();
+        if !out.is_null() {
+            let n = orphans.len().min(capacity);
+            unsafe {
+                std::ptr::copy_nonoverlapping(orphans.as_ptr(), out, n);
+            }
+        }
+        orphans.len()
+    })
+}
+
+/// Create a subtree cursor rooted at a node (order: 0=depth-first, 1=breadth-first)
+#[no_mangle]
+pub extern "C" fn content_subtree_cursor_new(root: u32, order: u8) -> *mut SubtreeCursor {
+    ffi_guard("content_subtree_cursor_new", || {
+        let order = match order {
+            1 => TraversalOrder::BreadthFirst,
+            _ => TraversalOrder::DepthFirst,
+        };
+        Box::into_raw(Box::new(SubtreeCursor::new(root, order)))
+    })
+}
+
+/// Free a subtree cursor
+#[no_mangle]
+pub extern "C" fn content_subtree_cursor_free(cursor: *mut SubtreeCursor) {
+    ffi_guard("content_subtree_cursor_free", || {
+        if !cursor.is_null() {
+            unsafe {
+                let _ = Box::from_raw(cursor);
+            }
+        }
+    })
+}
+
+/// Advance a subtree cursor over the builder's tree (returns node ID, 0 when done)
+#[no_mangle]
+pub extern "C" fn content_subtree_cursor_next(cursor: *mut SubtreeCursor, handle: *const BuilderHandle) -> u32 {
+    ffi_guard("content_subtree_cursor_next", || {
+        match unsafe { (cursor.as_mut(), handle.as_ref()) } {
+            (Some(c), Some(h)) => c.next_node(h.builder.tables().0),
+            _ => 0,
+        }
+    })
+}
+
+/// Restart a subtree cursor from its root
+#[no_mangle]
+pub extern "C" fn content_subtree_cursor_reset(cursor: *mut SubtreeCursor) {
+    ffi_guard("content_subtree_cursor_reset", || {
+        if let Some(c) = unsafe { cursor.as_mut() } {
+            c.reset();
+        }
+    })
+}
+
+/// Create a new animator
+#[no_mangle]
+pub extern "C" fn content_animator_new() -> *mut Animator {
+    ffi_guard("content_animator_new", || {
+        Box::into_raw(Box::new(Animator::new()))
+    })
+}
+
+/// Free an animator
+#[no_mangle]
+pub extern "C" fn content_animator_free(animator: *mut Animator) {
+    ffi_guard("content_animator_free", || {
+        if !animator.is_null() {
+            unsafe {
+                let _ = Box::from_raw(animator);
+            }
+        }
+    })
+}
+
+/// Start animating a node property (returns track ID, 0 on failure)
+#[no_mangle]
+pub extern "C" fn content_animator_start(
+    animator: *mut Animator,
+    node_id: u32,
+    property: u8,
+    from: f32,
+    to: f32,
+    duration: f32,
+    easing: u8,
+) -> u32 {
+    ffi_guard("content_animator_start", || {
+        let Some(a) = (unsafe { animator.as_mut() }) else {
+            return 0;
+        };
+        match AnimatedProperty::from_u8(property) {
+            Some(property) => a.start(node_id, property, from, to, duration, Easing::from_u8(easing)),
+            None => 0,
+        }
+    })
+}
+
+/// Stop an animation track (returns 1 if it was running)
+#[no_mangle]
+pub extern "C" fn content_animator_stop(animator: *mut Animator, track_id: u32) -> c_int {
+    ffi_guard("content_animator_stop", || {
+        match unsafe { animator.as_mut() } {
+            Some(a) => a.stop(track_id) as c_int,
+            None => 0,
+        }
+    })
+}
+
+/// Stop all animation tracks on a node
+#[no_mangle]
+pub extern "C" fn content_animator_stop_node(animator: *mut Animator, node_id: u32) {
+    ffi_guard("content_animator_stop_node", || {
+        if let Some(a) = unsafe { animator.as_mut() } {
+            a.stop_node(node_id);
+        }
+    })
+}
+
+/// Advance animations by `dt` seconds, writing values into the builder's properties
+/// Returns the number of tracks still running
+#[no_mangle]
+pub extern "C" fn content_animator_tick(animator: *mut Animator, handle: *mut BuilderHandle, dt: f32) -> u32 {
+    ffi_guard("content_animator_tick", || {
+        match unsafe { (animator.as_mut(), handle.as_mut()) } {
+            (Some(a), Some(h)) => a.tick(dt, h.builder.tables_mut().1) as u32,
+            _ => 0,
+        }
+    })
 }
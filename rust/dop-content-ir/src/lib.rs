@@ -8,8 +8,22 @@ pub mod primitives;
 pub mod properties;
 pub mod builder;
 pub mod ffi;
+pub mod ffi_guard;
 pub mod render;
+pub mod traversal;
+pub mod stats;
+pub mod animation;
+pub mod style;
+pub mod damage;
+pub mod binary;
+#[cfg(feature = "accessibility")]
+pub mod accessibility;
 
 pub use primitives::{NodeType, NodeTable, ContentNode};
-pub use properties::{PropertyTable, Direction, Pack, Align, Color};
+pub use properties::{PropertyTable, Direction, Pack, Align, Color, Role, SizeUnit};
 pub use builder::ContentBuilder;
+pub use stats::NodeStats;
+pub use damage::DamageTracker;
+pub use style::{FlatStyle, StyleTable};
+pub use animation::{Animator, AnimatedProperty, Easing};
+pub use traversal::{TraversalOrder, SubtreeCursor, DepthFirst, BreadthFirst};
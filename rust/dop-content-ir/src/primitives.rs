@@ -19,6 +19,23 @@ pub enum NodeType {
     TextCluster = 8,
 }
 
+impl NodeType {
+    /// Convert a raw type tag into a NodeType (unknown values map to Root)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => NodeType::Stack,
+            2 => NodeType::Grid,
+            3 => NodeType::Scroll,
+            4 => NodeType::Rect,
+            5 => NodeType::Paragraph,
+            6 => NodeType::Span,
+            7 => NodeType::Link,
+            8 => NodeType::TextCluster,
+            _ => NodeType::Root,
+        }
+    }
+}
+
 /// A single Content IR node (SoA row representation)
 #[derive(Clone, Debug)]
 pub struct ContentNode {
@@ -42,6 +59,12 @@ pub struct NodeTable {
     pub next_siblings: Vec<u32>,
     /// Style ID for each node
     pub style_ids: Vec<u32>,
+    /// Nodes created or given new children since the last layout pass
+    pub dirty: Vec<bool>,
+    /// IDs of removed nodes, reused by `create_node` before the table grows
+    pub free_slots: Vec<u32>,
+    /// Nodes removed and not yet reused (their IDs are in `free_slots`)
+    pub removed: Vec<bool>,
 }
 
 impl NodeTable {
@@ -60,34 +83,154 @@ impl NodeTable {
         self.node_types.is_empty()
     }
     
-    /// Create a new node and return its ID (1-indexed)
+    /// Reserve capacity for at least `additional` more nodes
+    pub fn reserve(&mut self, additional: usize) {
+        self.node_types.reserve(additional);
+        self.parents.reserve(additional);
+        self.first_children.reserve(additional);
+        self.next_siblings.reserve(additional);
+        self.style_ids.reserve(additional);
+        self.dirty.reserve(additional);
+        self.removed.reserve(additional);
+    }
+    
+    /// Create a new node as the last child of `parent` and return its ID (1-indexed)
+    ///
+    /// Slots of removed nodes are reused first, so IDs are not always increasing.
+    /// The node is left detached if `parent` is 0 or not a live node.
     pub fn create_node(&mut self, node_type: NodeType, parent: u32, style_id: u32) -> u32 {
-        let id = self.node_types.len() as u32 + 1;
-        
-        self.node_types.push(node_type);
-        self.parents.push(parent);
-        self.first_children.push(0);
-        self.next_siblings.push(0);
-        self.style_ids.push(style_id);
-        
-        // Update parent's child pointers
-        if parent > 0 && parent <= self.node_types.len() as u32 {
-            let parent_idx = parent as usize - 1;
-            if self.first_children[parent_idx] == 0 {
-                self.first_children[parent_idx] = id;
-            } else {
-                // Find last sibling and update
-                let mut sibling = self.first_children[parent_idx];
-                while self.next_siblings[sibling as usize - 1] != 0 {
-                    sibling = self.next_siblings[sibling as usize - 1];
-                }
-                self.next_siblings[sibling as usize - 1] = id;
+        // Checked before a slot is taken, so a removed parent can't be handed back as the child
+        let attach = self.is_live(parent);
+        let id = match self.free_slots.pop() {
+            Some(id) => {
+                let idx = id as usize - 1;
+                self.node_types[idx] = node_type;
+                self.style_ids[idx] = style_id;
+                self.dirty[idx] = true;
+                self.removed[idx] = false;
+                id
+            }
+            None => {
+                self.node_types.push(node_type);
+                self.parents.push(0);
+                self.first_children.push(0);
+                self.next_siblings.push(0);
+                self.style_ids.push(style_id);
+                self.dirty.push(true);
+                self.removed.push(false);
+                self.node_types.len() as u32
             }
+        };
+        
+        if attach {
+            self.link(id, parent, 0);
         }
         
         id
     }
     
+    /// Check if a node ID refers to a node that has not been removed
+    pub fn is_live(&self, id: u32) -> bool {
+        id > 0 && id as usize <= self.len() && !self.removed[id as usize - 1]
+    }
+    
+    /// Remove a node and its descendants, returning the removed IDs (pre-order)
+    ///
+    /// The root can't be removed. Removed slots go on the free list for reuse;
+    /// callers own any per-node data (e.g. properties) and should reset it.
+    pub fn remove_subtree(&mut self, id: u32) -> Vec<u32> {
+        if id <= 1 || !self.is_live(id) {
+            return Vec::new();
+        }
+        self.unlink(id);
+        let removed: Vec<u32> = self.depth_first(id).collect();
+        for &node in &removed {
+            let idx = node as usize - 1;
+            self.parents[idx] = 0;
+            self.first_children[idx] = 0;
+            self.next_siblings[idx] = 0;
+            self.style_ids[idx] = 0;
+            self.dirty[idx] = false;
+            self.removed[idx] = true;
+        }
+        // Reuse the subtree root's slot first
+        self.free_slots.extend(removed.iter().rev());
+        removed
+    }
+    
+    /// Move `node` under `parent`, just before its child `reference` (0 appends)
+    ///
+    /// Returns false and changes nothing if either node is missing, the
+    /// reference is not a child of the parent, or the parent is inside the
+    /// moved subtree.
+    pub fn insert_before(&mut self, parent: u32, node: u32, reference: u32) -> bool {
+        if node <= 1 || node == reference || !self.is_live(node) || !self.is_live(parent) {
+            return false;
+        }
+        if reference != 0 && (!self.is_live(reference) || self.parents[reference as usize - 1] != parent) {
+            return false;
+        }
+        let mut ancestor = parent;
+        while ancestor != 0 {
+            if ancestor == node {
+                return false;
+            }
+            ancestor = self.parents[ancestor as usize - 1];
+        }
+        self.unlink(node);
+        self.link(node, parent, reference);
+        self.dirty[node as usize - 1] = true;
+        true
+    }
+    
+    /// Move `node` to the end of `new_parent`'s children
+    pub fn reparent(&mut self, node: u32, new_parent: u32) -> bool {
+        self.insert_before(new_parent, node, 0)
+    }
+    
+    // Internal helper to link a detached node into a parent's child list
+    fn link(&mut self, id: u32, parent: u32, reference: u32) {
+        let parent_idx = parent as usize - 1;
+        self.parents[id as usize - 1] = parent;
+        self.next_siblings[id as usize - 1] = reference;
+        self.dirty[parent_idx] = true;
+        if self.first_children[parent_idx] == reference {
+            self.first_children[parent_idx] = id;
+        } else {
+            // Find the sibling before the reference (or the last one) and update
+            let mut sibling = self.first_children[parent_idx];
+            while self.next_siblings[sibling as usize - 1] != reference {
+                sibling = self.next_siblings[sibling as usize - 1];
+            }
+            self.next_siblings[sibling as usize - 1] = id;
+        }
+    }
+    
+    // Internal helper to take a node out of its parent's child list
+    fn unlink(&mut self, id: u32) {
+        let idx = id as usize - 1;
+        let parent = self.parents[idx];
+        let next = self.next_siblings[idx];
+        self.parents[idx] = 0;
+        self.next_siblings[idx] = 0;
+        if parent == 0 {
+            return;
+        }
+        let parent_idx = parent as usize - 1;
+        self.dirty[parent_idx] = true;
+        if self.first_children[parent_idx] == id {
+            self.first_children[parent_idx] = next;
+        } else {
+            let mut sibling = self.first_children[parent_idx];
+            while sibling != 0 && self.next_siblings[sibling as usize - 1] != id {
+                sibling = self.next_siblings[sibling as usize - 1];
+            }
+            if sibling != 0 {
+                self.next_siblings[sibling as usize - 1] = next;
+            }
+        }
+    }
+    
     /// Get a node by ID
     pub fn get_node(&self, id: u32) -> Option<ContentNode> {
         if id == 0 || id > self.node_types.len() as u32 {
@@ -118,4 +261,85 @@ impl NodeTable {
         }
         children
     }
+    
+    /// Mark a node's children as changed so the next layout pass redoes it
+    pub fn mark_dirty(&mut self, node_id: u32) {
+        if node_id > 0 && node_id as usize <= self.dirty.len() {
+            self.dirty[node_id as usize - 1] = true;
+        }
+    }
+    
+    /// Forget structural changes once they have been laid out
+    pub fn clear_dirty(&mut self) {
+        self.dirty.iter_mut().for_each(|d| *d = false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, ContentBuilder};
+
+    #[test]
+    fn test_tree_mutation() {
+        // Root holding rects 2 and 3, then stack 4 holding rect 5
+        let mut builder = ContentBuilder::new();
+        builder.rect().fill(Color::new(255, 0, 0, 255));
+        builder.rect();
+        builder.begin_stack();
+        builder.rect();
+        builder.end();
+
+        let nodes = builder.tables_mut().0;
+        assert!(nodes.insert_before(1, 4, 2));
+        assert_eq!(nodes.get_children(1), vec![4, 2, 3]);
+        assert!(nodes.reparent(3, 4));
+        assert_eq!(nodes.get_children(1), vec![4, 2]);
+        assert_eq!(nodes.get_children(4), vec![5, 3]);
+        // A node can't move into its own subtree, nor before a non-child
+        assert!(!nodes.reparent(4, 5));
+        assert!(!nodes.insert_before(4, 2, 5 + 1));
+        assert!(!nodes.insert_before(1, 2, 5));
+
+        builder.remove(4);
+        let nodes = builder.tables().0;
+        assert_eq!(nodes.get_children(1), vec![2]);
+        assert!(!nodes.is_live(3));
+        assert_eq!(nodes.stats().total, 2);
+        assert!(nodes.find_orphans().is_empty());
+
+        // Removed slots are reused, latest first, with fresh properties
+        builder.remove(2);
+        builder.rect();
+        assert_eq!(builder.last_node(), 2);
+        let (nodes, props) = builder.tables();
+        assert_eq!(nodes.get_children(1), vec![2]);
+        assert_eq!(props.fill_a[1], 0);
+        builder.rect();
+        assert_eq!(builder.last_node(), 4);
+        assert_eq!(builder.tables().0.get_children(1), vec![2, 4]);
+    }
+
+    #[test]
+    fn test_create_node_under_removed_parent() {
+        let mut nodes = NodeTable::new();
+        nodes.create_node(NodeType::Root, 0, 0);
+        let stack = nodes.create_node(NodeType::Stack, 1, 0);
+        nodes.remove_subtree(stack);
+        assert!(!nodes.is_live(stack));
+
+        // The freed slot is reused but must not become its own parent
+        let rect = nodes.create_node(NodeType::Rect, stack, 0);
+        assert_eq!(rect, stack);
+        assert!(nodes.is_live(rect));
+        assert_eq!(nodes.parents[rect as usize - 1], 0);
+        assert_eq!(nodes.first_children[rect as usize - 1], 0);
+        assert_eq!(nodes.depth_first(rect).collect::<Vec<_>>(), vec![rect]);
+
+        // Nor can a new node name itself as parent
+        let next = nodes.len() as u32 + 1;
+        assert_eq!(nodes.create_node(NodeType::Rect, next, 0), next);
+        assert_eq!(nodes.parents[next as usize - 1], 0);
+        assert_eq!(nodes.get_children(1), Vec::<u32>::new());
+    }
 }
@@ -4,17 +4,38 @@
 
 use zerocopy::{Immutable, IntoBytes, KnownLayout};
 
+use crate::style::overrides;
+
+/// Font size in pixels of nodes that neither set one nor take one from a style
+pub const DEFAULT_FONT_SIZE: f32 = 16.0;
+
 /// Direction enum for Stack layout
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, IntoBytes, Immutable, KnownLayout)]
 #[repr(u8)]
 pub enum Direction {
+    /// Top to bottom
     #[default]
     Down = 0,
+    /// Bottom to top, starting at the bottom edge
     Up = 1,
+    /// Left to right
     Right = 2,
+    /// Right to left, starting at the right edge
     Left = 3,
 }
 
+impl Direction {
+    /// Convert a raw FFI value into a Direction (unknown values map to Down)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Direction::Up,
+            2 => Direction::Right,
+            3 => Direction::Left,
+            _ => Direction::Down,
+        }
+    }
+}
+
 /// Pack (justify-content equivalent)
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, IntoBytes, Immutable, KnownLayout)]
 #[repr(u8)]
@@ -28,6 +49,20 @@ pub enum Pack {
     SpaceEvenly = 5,
 }
 
+impl Pack {
+    /// Convert a raw FFI value into a Pack (unknown values map to Start)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Pack::End,
+            2 => Pack::Center,
+            3 => Pack::SpaceBetween,
+            4 => Pack::SpaceAround,
+            5 => Pack::SpaceEvenly,
+            _ => Pack::Start,
+        }
+    }
+}
+
 /// Align (align-items equivalent)
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, IntoBytes, Immutable, KnownLayout)]
 #[repr(u8)]
@@ -39,6 +74,105 @@ pub enum Align {
     Stretch = 3,
 }
 
+impl Align {
+    /// Convert a raw FFI value into an Align (unknown values map to Start)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Align::End,
+            2 => Align::Center,
+            3 => Align::Stretch,
+            _ => Align::Start,
+        }
+    }
+}
+
+/// How a width or height value is interpreted
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, IntoBytes, Immutable, KnownLayout)]
+#[repr(u8)]
+pub enum SizeUnit {
+    /// Pixels; 0 means auto
+    #[default]
+    Px = 0,
+    /// Percent of the parent's content size
+    Percent = 1,
+    /// Share of the parent stack's leftover main-axis space, weighted by the
+    /// value; auto on the cross axis
+    Flex = 2,
+    /// Sized from content; the value is ignored
+    Auto = 3,
+}
+
+impl SizeUnit {
+    /// Convert a raw FFI value into a SizeUnit (unknown values map to Px)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => SizeUnit::Percent,
+            2 => SizeUnit::Flex,
+            3 => SizeUnit::Auto,
+            _ => SizeUnit::Px,
+        }
+    }
+
+    /// Resolve a value against the parent's content size, None if it is
+    /// auto or flex
+    pub fn resolve(self, value: f32, parent: f32) -> Option<f32> {
+        match self {
+            SizeUnit::Px if value > 0.0 => Some(value),
+            SizeUnit::Percent => Some((parent * value / 100.0).max(0.0)),
+            _ => None,
+        }
+    }
+}
+
+/// Accessibility role exposed to assistive technologies
+///
+/// `Auto` derives the role from the node type (e.g. Paragraph, Link).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, IntoBytes, Immutable, KnownLayout)]
+#[repr(u8)]
+pub enum Role {
+    #[default]
+    Auto = 0,
+    None = 1,
+    Generic = 2,
+    Document = 3,
+    Heading = 4,
+    Paragraph = 5,
+    Label = 6,
+    Link = 7,
+    Button = 8,
+    Image = 9,
+    List = 10,
+    ListItem = 11,
+    TextInput = 12,
+    CheckBox = 13,
+    Navigation = 14,
+    Main = 15,
+}
+
+impl Role {
+    /// Convert a raw FFI value into a Role (unknown values map to Auto)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Role::None,
+            2 => Role::Generic,
+            3 => Role::Document,
+            4 => Role::Heading,
+            5 => Role::Paragraph,
+            6 => Role::Label,
+            7 => Role::Link,
+            8 => Role::Button,
+            9 => Role::Image,
+            10 => Role::List,
+            11 => Role::ListItem,
+            12 => Role::TextInput,
+            13 => Role::CheckBox,
+            14 => Role::Navigation,
+            15 => Role::Main,
+            _ => Role::Auto,
+        }
+    }
+}
+
 /// RGBA color
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Color {
@@ -85,7 +219,7 @@ impl Color {
 }
 
 /// Property table storing node properties in SoA format
-#[derive(Default, Debug)]
+#[derive(Clone, Default, Debug)]
 pub struct PropertyTable {
     // Layout properties
     pub direction: Vec<Direction>,
@@ -93,9 +227,16 @@ pub struct PropertyTable {
     pub align: Vec<Align>,
     pub width: Vec<f32>,
     pub height: Vec<f32>,
+    pub width_unit: Vec<SizeUnit>,
+    pub height_unit: Vec<SizeUnit>,
     pub gap_row: Vec<f32>,
     pub gap_col: Vec<f32>,
     
+    // Grid tracks and placement (column sizes: > 0 is pixels, 0 shares the leftover width)
+    pub grid_columns: Vec<Vec<f32>>,
+    pub grid_column_span: Vec<u16>,
+    pub grid_row_span: Vec<u16>,
+    
     // Inset (padding equivalent)
     pub inset_top: Vec<f32>,
     pub inset_right: Vec<f32>,
@@ -117,6 +258,10 @@ pub struct PropertyTable {
     // Border radius
     pub border_radius: Vec<f32>,
     
+    // Scroll offset (how far Scroll node content is scrolled right/down)
+    pub scroll_x: Vec<f32>,
+    pub scroll_y: Vec<f32>,
+    
     // Text content (for Span/Paragraph)
     pub text_content: Vec<String>,
     pub font_size: Vec<f32>,
@@ -124,6 +269,19 @@ pub struct PropertyTable {
     pub text_color_g: Vec<u8>,
     pub text_color_b: Vec<u8>,
     pub text_color_a: Vec<u8>,
+    
+    // Style-backed properties set on the node itself (bits of `style::overrides`)
+    pub overrides: Vec<u32>,
+    
+    // Accessibility metadata
+    pub role: Vec<Role>,
+    pub accessible_name: Vec<String>,
+    pub accessible_description: Vec<String>,
+    
+    // Damage tracking (set on mutation, cleared when damage is collected)
+    pub dirty: Vec<bool>,
+    // Geometry changes (set on mutation, cleared by a layout pass)
+    pub layout_dirty: Vec<bool>,
 }
 
 impl PropertyTable {
@@ -139,9 +297,15 @@ impl PropertyTable {
         self.align.resize(n, Align::Start);
         self.width.resize(n, 0.0);
         self.height.resize(n, 0.0);
+        self.width_unit.resize(n, SizeUnit::Px);
+        self.height_unit.resize(n, SizeUnit::Px);
         self.gap_row.resize(n, 0.0);
         self.gap_col.resize(n, 0.0);
         
+        self.grid_columns.resize(n, Vec::new());
+        self.grid_column_span.resize(n, 1);
+        self.grid_row_span.resize(n, 1);
+        
         self.inset_top.resize(n, 0.0);
         self.inset_right.resize(n, 0.0);
         self.inset_bottom.resize(n, 0.0);
@@ -159,12 +323,134 @@ impl PropertyTable {
         
         self.border_radius.resize(n, 0.0);
         
+        self.scroll_x.resize(n, 0.0);
+        self.scroll_y.resize(n, 0.0);
+        
         self.text_content.resize(n, String::new());
-        self.font_size.resize(n, 16.0);
+        self.font_size.resize(n, DEFAULT_FONT_SIZE);
         self.text_color_r.resize(n, 0);
         self.text_color_g.resize(n, 0);
         self.text_color_b.resize(n, 0);
         self.text_color_a.resize(n, 255);
+        
+        self.overrides.resize(n, 0);
+        
+        self.role.resize(n, Role::Auto);
+        self.accessible_name.resize(n, String::new());
+        self.accessible_description.resize(n, String::new());
+        
+        // New nodes have never been painted or laid out
+        self.dirty.resize(n, true);
+        self.layout_dirty.resize(n, true);
+    }
+    
+    /// Restore a node's properties to the defaults (for a reused node slot)
+    pub fn reset(&mut self, idx: usize) {
+        if idx >= self.direction.len() {
+            return;
+        }
+        self.direction[idx] = Direction::Down;
+        self.pack[idx] = Pack::Start;
+        self.align[idx] = Align::Start;
+        self.width[idx] = 0.0;
+        self.height[idx] = 0.0;
+        self.width_unit[idx] = SizeUnit::Px;
+        self.height_unit[idx] = SizeUnit::Px;
+        self.gap_row[idx] = 0.0;
+        self.gap_col[idx] = 0.0;
+        
+        self.grid_columns[idx] = Vec::new();
+        self.grid_column_span[idx] = 1;
+        self.grid_row_span[idx] = 1;
+        
+        self.inset_top[idx] = 0.0;
+        self.inset_right[idx] = 0.0;
+        self.inset_bottom[idx] = 0.0;
+        self.inset_left[idx] = 0.0;
+        
+        self.offset_top[idx] = 0.0;
+        self.offset_right[idx] = 0.0;
+        self.offset_bottom[idx] = 0.0;
+        self.offset_left[idx] = 0.0;
+        
+        self.fill_r[idx] = 0;
+        self.fill_g[idx] = 0;
+        self.fill_b[idx] = 0;
+        self.fill_a[idx] = 0;
+        
+        self.border_radius[idx] = 0.0;
+        
+        self.scroll_x[idx] = 0.0;
+        self.scroll_y[idx] = 0.0;
+        
+        self.text_content[idx] = String::new();
+        self.font_size[idx] = DEFAULT_FONT_SIZE;
+        self.text_color_r[idx] = 0;
+        self.text_color_g[idx] = 0;
+        self.text_color_b[idx] = 0;
+        self.text_color_a[idx] = 255;
+        
+        self.overrides[idx] = 0;
+        
+        self.role[idx] = Role::Auto;
+        self.accessible_name[idx] = String::new();
+        self.accessible_description[idx] = String::new();
+        
+        self.dirty[idx] = true;
+        self.layout_dirty[idx] = true;
+    }
+    
+    /// Reserve capacity for at least `additional` more nodes
+    pub fn reserve(&mut self, additional: usize) {
+        self.direction.reserve(additional);
+        self.pack.reserve(additional);
+        self.align.reserve(additional);
+        self.width.reserve(additional);
+        self.height.reserve(additional);
+        self.width_unit.reserve(additional);
+        self.height_unit.reserve(additional);
+        self.gap_row.reserve(additional);
+        self.gap_col.reserve(additional);
+        
+        self.grid_columns.reserve(additional);
+        self.grid_column_span.reserve(additional);
+        self.grid_row_span.reserve(additional);
+        
+        self.inset_top.reserve(additional);
+        self.inset_right.reserve(additional);
+        self.inset_bottom.reserve(additional);
+        self.inset_left.reserve(additional);
+        
+        self.offset_top.reserve(additional);
+        self.offset_right.reserve(additional);
+        self.offset_bottom.reserve(additional);
+        self.offset_left.reserve(additional);
+        
+        self.fill_r.reserve(additional);
+        self.fill_g.reserve(additional);
+        self.fill_b.reserve(additional);
+        self.fill_a.reserve(additional);
+        
+        self.border_radius.reserve(additional);
+        
+        self.scroll_x.reserve(additional);
+        self.scroll_y.reserve(additional);
+        
+        self.text_content.reserve(additional);
+        self.font_size.reserve(additional);
+        self.text_color_r.reserve(additional);
+        self.text_color_g.reserve(additional);
+        self.text_color_b.reserve(additional);
+        self.text_color_a.reserve(additional);
+        
+        self.overrides.reserve(additional);
+        
+        self.role.reserve(additional);
+        self.accessible_name.reserve(additional);
+        self.accessible_description.reserve(additional);
+        
+        self.dirty.reserve(additional);
+        self.layout_dirty.reserve(additional);
     }
     
     /// Set properties for a node
@@ -174,6 +460,8 @@ impl PropertyTable {
             self.fill_g[idx] = color.g;
             self.fill_b[idx] = color.b;
             self.fill_a[idx] = color.a;
+            self.overrides[idx] |= overrides::FILL;
+            self.dirty[idx] = true;
         }
     }
     
@@ -183,6 +471,8 @@ impl PropertyTable {
             self.text_color_g[idx] = color.g;
             self.text_color_b[idx] = color.b;
             self.text_color_a[idx] = color.a;
+            self.overrides[idx] |= overrides::TEXT_COLOR;
+            self.dirty[idx] = true;
         }
     }
     
@@ -192,6 +482,67 @@ impl PropertyTable {
             self.inset_right[idx] = right;
             self.inset_bottom[idx] = bottom;
             self.inset_left[idx] = left;
+            self.overrides[idx] |= overrides::INSET;
+            self.dirty[idx] = true;
+            self.layout_dirty[idx] = true;
+        }
+    }
+    
+    /// Set a node's width and height in pixels (0 means auto)
+    pub fn set_size(&mut self, idx: usize, width: f32, height: f32) {
+        if idx < self.width.len() {
+            self.width[idx] = width;
+            self.height[idx] = height;
+            self.width_unit[idx] = SizeUnit::Px;
+            self.height_unit[idx] = SizeUnit::Px;
+            self.overrides[idx] |= overrides::WIDTH | overrides::HEIGHT;
+            self.mark_layout_dirty(idx);
+        }
+    }
+    
+    /// Flag property groups (`style::overrides` bits) as set on the node, so
+    /// its style no longer replaces them
+    pub fn set_override(&mut self, idx: usize, groups: u32) {
+        if idx < self.overrides.len() {
+            self.overrides[idx] |= groups;
+        }
+    }
+    
+    /// Replace a node's text
+    pub fn set_text(&mut self, idx: usize, text: &str) {
+        if idx < self.text_content.len() {
+            self.text_content[idx] = text.to_string();
+            self.mark_layout_dirty(idx);
         }
     }
+    
+    /// Set how far a Scroll node's content is scrolled
+    pub fn set_scroll_offset(&mut self, idx: usize, x: f32, y: f32) {
+        if idx < self.scroll_x.len() {
+            self.scroll_x[idx] = x;
+            self.scroll_y[idx] = y;
+            self.dirty[idx] = true;
+            self.layout_dirty[idx] = true;
+        }
+    }
+    
+    /// Mark a node as changed so its area is repainted
+    pub fn mark_dirty(&mut self, idx: usize) {
+        if idx < self.dirty.len() {
+            self.dirty[idx] = true;
+        }
+    }
+    
+    /// Mark a node's geometry as changed so it is laid out and repainted
+    pub fn mark_layout_dirty(&mut self, idx: usize) {
+        if idx < self.layout_dirty.len() {
+            self.dirty[idx] = true;
+            self.layout_dirty[idx] = true;
+        }
+    }
+    
+    /// Check if any node has changed since damage was last collected
+    pub fn has_dirty(&self) -> bool {
+        self.dirty.iter().any(|&d| d)
+    }
 }
@@ -11,13 +11,28 @@
 //! - Unicode and mature mathematical libraries
 //!
 //! The Rust side should accept pre-computed layout positions from Julia and focus on
-//! efficient rendering with minimal layout overhead.
+//! efficient rendering with minimal layout overhead; `render_with_layout` emits
+//! commands for such positions without running the layout pass.
+//!
+//! Grid nodes place their children into columns and auto-sized rows (see
+//! `layout_grid`).
+//!
+//! Scroll nodes lay out their children shifted by the node's scroll offset and
+//! wrap them in a `PushClip`/`PopClip` pair, so content outside the node's
+//! rectangle is not drawn.
+//!
+//! `hit_test` maps a point back to the node drawn on top there, using the
+//! same paint order and clips.
+//!
+//! `update_layout` lays out again only the subtrees `relayout_roots` finds
+//! from the node and property dirty flags, reusing the rest of a kept layout.
 
 use crate::primitives::{NodeTable, NodeType};
-use crate::properties::PropertyTable;
+use crate::properties::{Align, Direction, Pack, PropertyTable, SizeUnit};
+use crate::style::{resolve_styles, StyleTable};
 
 /// Render command for GPU
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum RenderCommand {
     /// Draw a filled rectangle
     FillRect {
@@ -42,15 +57,48 @@ pub enum RenderCommand {
         b: u8,
         a: u8,
     },
+    /// Clip following commands to a rectangle until the matching `PopClip`
+    ///
+    /// Clips nest: each one intersects with the clips already pushed.
+    PushClip {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+    /// Remove the most recently pushed clip
+    PopClip,
 }
 
-/// Layout state for a node
-#[derive(Clone, Debug, Default)]
-struct LayoutState {
-    x: f32,
-    y: f32,
-    width: f32,
-    height: f32,
+/// Computed layout rectangle for a node
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LayoutRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Text measure callback: (text, font size) -> (width, height)
+pub type TextMeasure = dyn Fn(&str, f32) -> (f32, f32);
+
+/// Content-based size of a node, insets included
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IntrinsicSize {
+    /// Narrowest width without overflow (widest word or fixed child)
+    pub min_width: f32,
+    /// Width with no wrapping at all
+    pub max_width: f32,
+    /// Height at the max-content width
+    pub height: f32,
+}
+
+/// Estimate the size of a single line of text without font data
+///
+/// The default text measure; hosts with real fonts should pass their own to
+/// the `*_with_measure` functions.
+pub fn estimate_text_size(text: &str, font_size: f32) -> (f32, f32) {
+    (text.chars().count() as f32 * font_size * 0.6, font_size)
 }
 
 /// Render the Content IR tree to a list of render commands
@@ -61,23 +109,326 @@ struct LayoutState {
 /// - Optimized SIMD computation using Julia's mature libraries
 /// - Unicode support for text layout
 pub fn render(nodes: &NodeTable, props: &PropertyTable, viewport_width: f32, viewport_height: f32) -> Vec<RenderCommand> {
+    render_with_measure(nodes, props, viewport_width, viewport_height, &estimate_text_size)
+}
+
+/// Render the tree, sizing text with `measure(text, font_size) -> (width, height)`
+pub fn render_with_measure(
+    nodes: &NodeTable,
+    props: &PropertyTable,
+    viewport_width: f32,
+    viewport_height: f32,
+    measure: &TextMeasure,
+) -> Vec<RenderCommand> {
+    let layout_states = compute_layout_with_measure(nodes, props, viewport_width, viewport_height, measure);
+    render_with_layout(nodes, props, &layout_states)
+}
+
+/// Render the tree at positions computed elsewhere (e.g. by the Julia layout engine)
+///
+/// `positions` holds one rectangle per node, indexed by node ID - 1; no
+/// layout is done here. Returns no commands if it has fewer entries than
+/// there are nodes.
+pub fn render_with_layout(nodes: &NodeTable, props: &PropertyTable, positions: &[LayoutRect]) -> Vec<RenderCommand> {
     let mut commands = Vec::new();
-    let mut layout_states = vec![LayoutState::default(); nodes.len()];
+    if positions.len() >= nodes.len() {
+        render_node(nodes, props, 1, positions, &mut commands);
+    }
+    commands
+}
+
+/// Render the tree after resolving node properties against shared styles
+///
+/// Resolution writes the styled values into `props` (see `resolve_styles`).
+pub fn render_styled(
+    nodes: &NodeTable,
+    props: &mut PropertyTable,
+    styles: &StyleTable,
+    viewport_width: f32,
+    viewport_height: f32,
+) -> Vec<RenderCommand> {
+    render_styled_with_measure(nodes, props, styles, viewport_width, viewport_height, &estimate_text_size)
+}
+
+/// Render the tree after resolving styles, sizing text with `measure`
+pub fn render_styled_with_measure(
+    nodes: &NodeTable,
+    props: &mut PropertyTable,
+    styles: &StyleTable,
+    viewport_width: f32,
+    viewport_height: f32,
+    measure: &TextMeasure,
+) -> Vec<RenderCommand> {
+    resolve_styles(nodes, props, styles);
+    render_with_measure(nodes, props, viewport_width, viewport_height, measure)
+}
+
+/// Run the minimal layout pass and return one rectangle per node (indexed by node ID - 1)
+pub fn compute_layout(nodes: &NodeTable, props: &PropertyTable, viewport_width: f32, viewport_height: f32) -> Vec<LayoutRect> {
+    compute_layout_with_measure(nodes, props, viewport_width, viewport_height, &estimate_text_size)
+}
+
+/// Run the minimal layout pass, sizing text with `measure(text, font_size) -> (width, height)`
+pub fn compute_layout_with_measure(
+    nodes: &NodeTable,
+    props: &PropertyTable,
+    viewport_width: f32,
+    viewport_height: f32,
+    measure: &TextMeasure,
+) -> Vec<LayoutRect> {
+    let mut layout_states = vec![LayoutRect::default(); nodes.len()];
     
-    // Minimal layout pass - just basic positioning
+    // Minimal layout pass - measure content bottom-up, then place top-down
     // For complex layout, delegate to Julia layout engine
     if !nodes.is_empty() {
-        layout_states[0].width = viewport_width;
-        layout_states[0].height = viewport_height;
-        layout_node_minimal(nodes, props, 1, 0.0, 0.0, viewport_width, viewport_height, &mut layout_states);
+        let mut input = LayoutInput {
+            nodes,
+            props,
+            intrinsic: vec![None; nodes.len()],
+        };
+        measure_node(&mut input, 1, measure);
+        let (width, height) = input.definite_size(0, viewport_width, viewport_height);
+        let width = width.unwrap_or(viewport_width);
+        let height = height.unwrap_or(viewport_height);
+        layout_node_minimal(&input, 1, 0.0, 0.0, width, height, &mut layout_states);
     }
     
-    // Render pass
-    render_node(nodes, props, 1, &layout_states, &mut commands);
-    
+    layout_states
+}
+
+/// Find the subtrees the dirty flags require laying out again
+///
+/// A changed node can resize itself and so move its siblings, so each dirty
+/// node maps to its nearest ancestor with a fixed pixel width and height
+/// (or the root): that ancestor's rectangle can't change, and redoing its
+/// subtree alone is exact. Roots inside other roots are left out.
+pub fn relayout_roots(nodes: &NodeTable, props: &PropertyTable) -> Vec<u32> {
+    let fixed = |idx: usize| {
+        props.width_unit[idx] == SizeUnit::Px
+            && props.height_unit[idx] == SizeUnit::Px
+            && props.width[idx] > 0.0
+            && props.height[idx] > 0.0
+    };
+    let mut roots: Vec<u32> = Vec::new();
+    // Detached and removed nodes (no parent, not the root) aren't laid out
+    for idx in (0..nodes.len()).filter(|&idx| idx == 0 || nodes.parents[idx] != 0) {
+        let structure = nodes.dirty.get(idx).copied().unwrap_or(true);
+        let geometry = props.layout_dirty.get(idx).copied().unwrap_or(true);
+        if !structure && !geometry {
+            continue;
+        }
+        let mut root = nodes.parents[idx];
+        while root > 1 && !fixed(root as usize - 1) {
+            root = nodes.parents[root as usize - 1];
+        }
+        let root = root.max(1);
+        if !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+    let is_inside = |mut id: u32, roots: &[u32]| {
+        while id != 0 {
+            id = nodes.parents[id as usize - 1];
+            if roots.contains(&id) {
+                return true;
+            }
+        }
+        false
+    };
+    let nested: Vec<bool> = roots.iter().map(|&root| is_inside(root, &roots)).collect();
+    roots.into_iter().zip(nested).filter(|&(_, nested)| !nested).map(|(root, _)| root).collect()
+}
+
+/// Lay out the subtrees under `dirty_roots` again and render them
+///
+/// Each root keeps its rectangle from `layout`, which must come from an
+/// earlier layout of the same tree; its descendants are measured and placed
+/// again in place, and nodes without a parent (removed ones) lose their
+/// rectangles. Returns the commands for the updated subtrees, inside the
+/// clips of their Scroll ancestors. Backgrounds of ancestors are not
+/// repeated, so hosts painting over old pixels should repaint the roots'
+/// areas from a full command list.
+pub fn update_layout(
+    nodes: &NodeTable,
+    props: &PropertyTable,
+    layout: &mut Vec<LayoutRect>,
+    dirty_roots: &[u32],
+    measure: &TextMeasure,
+) -> Vec<RenderCommand> {
+    update_layout_rects(nodes, props, layout, dirty_roots, measure);
+    let mut commands = Vec::new();
+    for &root in dirty_roots {
+        if root == 0 || root as usize > nodes.len() {
+            continue;
+        }
+        let mut clips = Vec::new();
+        let mut ancestor = nodes.parents[root as usize - 1];
+        while ancestor != 0 {
+            if nodes.node_types[ancestor as usize - 1] == NodeType::Scroll {
+                clips.push(layout[ancestor as usize - 1]);
+            }
+            ancestor = nodes.parents[ancestor as usize - 1];
+        }
+        for clip in clips.iter().rev() {
+            commands.push(RenderCommand::PushClip {
+                x: clip.x,
+                y: clip.y,
+                width: clip.width,
+                height: clip.height,
+            });
+        }
+        render_node(nodes, props, root, layout, &mut commands);
+        commands.extend(clips.iter().map(|_| RenderCommand::PopClip));
+    }
     commands
 }
 
+/// Like `update_layout`, but only updates `layout` without rendering
+pub fn update_layout_rects(
+    nodes: &NodeTable,
+    props: &PropertyTable,
+    layout: &mut Vec<LayoutRect>,
+    dirty_roots: &[u32],
+    measure: &TextMeasure,
+) {
+    layout.resize(nodes.len(), LayoutRect::default());
+    for (idx, rect) in layout.iter_mut().enumerate().skip(1) {
+        if nodes.parents[idx] == 0 {
+            *rect = LayoutRect::default();
+        }
+    }
+    let mut input = LayoutInput {
+        nodes,
+        props,
+        intrinsic: vec![None; nodes.len()],
+    };
+    for &root in dirty_roots {
+        if root == 0 || root as usize > nodes.len() {
+            continue;
+        }
+        let rect = layout[root as usize - 1];
+        measure_node(&mut input, root, measure);
+        layout_node_minimal(&input, root, rect.x, rect.y, rect.width, rect.height, layout);
+    }
+}
+
+/// Node tables plus the measured sizes the placement pass works from
+struct LayoutInput<'a> {
+    nodes: &'a NodeTable,
+    props: &'a PropertyTable,
+    /// Content size per node; None for empty leaves, which fill their space
+    intrinsic: Vec<Option<IntrinsicSize>>,
+}
+
+impl LayoutInput<'_> {
+    /// Width and height set in pixels or percent of the parent's content size
+    fn definite_size(&self, idx: usize, parent_width: f32, parent_height: f32) -> (Option<f32>, Option<f32>) {
+        let props = self.props;
+        (
+            props.width_unit[idx].resolve(props.width[idx], parent_width),
+            props.height_unit[idx].resolve(props.height[idx], parent_height),
+        )
+    }
+
+    /// Size a child takes part in its parent's measurement with: pixel sizes
+    /// win, empty leaves count as zero
+    ///
+    /// Percent sizes count as content-sized since the parent's size is what
+    /// is being measured.
+    fn outer_size(&self, idx: usize) -> IntrinsicSize {
+        let mut size = self.intrinsic[idx].unwrap_or_default();
+        let (width, height) = self.definite_size(idx, 0.0, 0.0);
+        if let Some(width) = width.filter(|_| self.props.width_unit[idx] == SizeUnit::Px) {
+            size.min_width = width;
+            size.max_width = width;
+        }
+        if let Some(height) = height.filter(|_| self.props.height_unit[idx] == SizeUnit::Px) {
+            size.height = height;
+        }
+        size
+    }
+
+    /// Resolve a stacked child's size inside `available` space
+    ///
+    /// Auto sizes shrink to content (never below the min-content width)
+    /// unless stretched; empty leaves fill the available space.
+    fn child_size(&self, idx: usize, available: (f32, f32), stretch: (bool, bool)) -> (f32, f32) {
+        let size = self.intrinsic[idx];
+        let (definite_width, definite_height) = self.definite_size(idx, available.0, available.1);
+        let width = match (definite_width, size) {
+            (Some(width), _) => width,
+            (None, Some(size)) if !stretch.0 => size.max_width.min(available.0).max(size.min_width),
+            _ => available.0,
+        };
+        let height = match (definite_height, size) {
+            (Some(height), _) => height,
+            (None, Some(size)) if !stretch.1 => size.height,
+            _ => available.1,
+        };
+        (width, height)
+    }
+}
+
+/// Measure a node's content size after its children's (post-order)
+///
+/// Text nodes measure their text (min width is the widest word), stacks sum
+/// children along the main axis and take the maximum across it, grids add up
+/// their tracks. Results land in `input.intrinsic`.
+fn measure_node(input: &mut LayoutInput, node_id: u32, measure: &TextMeasure) {
+    if node_id == 0 || node_id > input.nodes.len() as u32 {
+        return;
+    }
+    let idx = node_id as usize - 1;
+    let children = input.nodes.get_children(node_id);
+    for &child_id in &children {
+        measure_node(input, child_id, measure);
+    }
+
+    let props = input.props;
+    let text = &props.text_content[idx];
+    let content = if !text.is_empty() {
+        let font_size = props.font_size[idx];
+        let (max_width, height) = measure(text, font_size);
+        let min_width = text
+            .split_whitespace()
+            .map(|word| measure(word, font_size).0)
+            .fold(0.0, f32::max);
+        IntrinsicSize { min_width, max_width, height }
+    } else if children.is_empty() {
+        return;
+    } else if input.nodes.node_types[idx] == NodeType::Grid {
+        measure_grid(input, idx, &children)
+    } else {
+        let horizontal = matches!(props.direction[idx], Direction::Right | Direction::Left);
+        let gap = if horizontal { props.gap_col[idx] } else { props.gap_row[idx] };
+        let gaps = gap * (children.len() - 1) as f32;
+        let sizes: Vec<IntrinsicSize> = children.iter().map(|&c| input.outer_size(c as usize - 1)).collect();
+        let sum = |f: fn(&IntrinsicSize) -> f32| sizes.iter().map(f).sum::<f32>() + gaps;
+        let max = |f: fn(&IntrinsicSize) -> f32| sizes.iter().map(f).fold(0.0, f32::max);
+        if horizontal {
+            IntrinsicSize {
+                min_width: sum(|s| s.min_width),
+                max_width: sum(|s| s.max_width),
+                height: max(|s| s.height),
+            }
+        } else {
+            IntrinsicSize {
+                min_width: max(|s| s.min_width),
+                max_width: max(|s| s.max_width),
+                height: sum(|s| s.height),
+            }
+        }
+    };
+
+    let inset_x = props.inset_left[idx] + props.inset_right[idx];
+    let inset_y = props.inset_top[idx] + props.inset_bottom[idx];
+    input.intrinsic[idx] = Some(IntrinsicSize {
+        min_width: content.min_width + inset_x,
+        max_width: content.max_width + inset_x,
+        height: content.height + inset_y,
+    });
+}
+
 /// Perform minimal layout for a single node
 /// 
 /// This is a simplified layout function for immediate rendering needs.
@@ -88,44 +439,33 @@ pub fn render(nodes: &NodeTable, props: &PropertyTable, viewport_width: f32, vie
 /// - SIMD-optimized computation
 /// - Proper text shaping with Unicode support
 ///
-/// This minimal version only handles basic vertical stacking.
+/// This minimal version stacks children along the node's direction with its
+/// gap, pack and align (see `layout_stack`), and places Grid children.
+/// Parents resolve each child's size (units, content, flex) and pass it in.
 fn layout_node_minimal(
-    nodes: &NodeTable,
-    props: &PropertyTable,
+    input: &LayoutInput,
     node_id: u32,
     x: f32,
     y: f32,
-    available_width: f32,
-    available_height: f32,
-    layout_states: &mut [LayoutState],
+    width: f32,
+    height: f32,
+    layout_states: &mut [LayoutRect],
 ) {
+    let (nodes, props) = (input.nodes, input.props);
     if node_id == 0 || node_id > nodes.len() as u32 {
         return;
     }
     
     let idx = node_id as usize - 1;
     
-    // Use explicit size if provided, otherwise use available space
-    let width = if props.width[idx] > 0.0 {
-        props.width[idx]
-    } else {
-        available_width
-    };
-    
-    let height = if props.height[idx] > 0.0 {
-        props.height[idx]
-    } else {
-        available_height
-    };
-    
     // Store layout state
     layout_states[idx].x = x;
     layout_states[idx].y = y;
     layout_states[idx].width = width;
     layout_states[idx].height = height;
     
-    // Minimal child layout - just stack vertically
-    // For complex layouts (direction, pack, align, gap), use Julia layout engine
+    // Minimal child layout - stacking or grid placement, no wrapping or flex sizing
+    // For complex layouts, use Julia layout engine
     let children = nodes.get_children(node_id);
     if !children.is_empty() {
         let inset_left = props.inset_left[idx];
@@ -133,37 +473,341 @@ fn layout_node_minimal(
         let inset_right = props.inset_right[idx];
         let inset_bottom = props.inset_bottom[idx];
         
-        let content_x = x + inset_left;
-        let mut content_y = y + inset_top;
+        // Scrolled content moves up/left under the node's clip
+        let (scroll_x, scroll_y) = if nodes.node_types[idx] == NodeType::Scroll {
+            (props.scroll_x[idx], props.scroll_y[idx])
+        } else {
+            (0.0, 0.0)
+        };
+        let content_x = x + inset_left - scroll_x;
+        let content_y = y + inset_top - scroll_y;
         let content_width = width - inset_left - inset_right;
         let content_height = height - inset_top - inset_bottom;
         
-        // Simple vertical stacking only
-        for child_id in children {
-            layout_node_minimal(
-                nodes,
-                props,
-                child_id,
-                content_x,
-                content_y,
-                content_width,
-                content_height,
-                layout_states,
-            );
-            
-            // Stack vertically with minimal gap
-            let child_idx = child_id as usize - 1;
-            content_y += layout_states[child_idx].height;
+        let content = LayoutRect {
+            x: content_x,
+            y: content_y,
+            width: content_width,
+            height: content_height,
+        };
+        if nodes.node_types[idx] == NodeType::Grid {
+            layout_grid(input, idx, &children, content, layout_states);
+        } else {
+            layout_stack(input, idx, &children, content, layout_states);
         }
     }
 }
 
+/// Lay out children one after another inside a node's content rectangle
+///
+/// Down and Right stack children top-to-bottom and left-to-right. Up and Left
+/// start from the bottom/right edge instead, so their main axis and Pack are
+/// mirrored (Start packs at the far edge). Auto-sized children take their
+/// content size, and empty leaves fill the content rectangle. Pack
+/// distributes the free main-axis space (space-* modes fall back to Start on
+/// overflow) and Align places children on the cross axis, where Stretch
+/// also fills it with auto-sized children.
+fn layout_stack(
+    input: &LayoutInput,
+    idx: usize,
+    children: &[u32],
+    content: LayoutRect,
+    layout_states: &mut [LayoutRect],
+) {
+    let props = input.props;
+    let direction = props.direction[idx];
+    let horizontal = matches!(direction, Direction::Right | Direction::Left);
+    let (main, cross) = if horizontal {
+        (content.width, content.height)
+    } else {
+        (content.height, content.width)
+    };
+    let gap = if horizontal { props.gap_col[idx] } else { props.gap_row[idx] };
+    let reverse = matches!(direction, Direction::Up | Direction::Left);
+
+    // Size children first: main and cross size of each, flex items starting at 0
+    let stretch = props.align[idx] == Align::Stretch;
+    let main_units = if horizontal { &props.width_unit } else { &props.height_unit };
+    let main_values = if horizontal { &props.width } else { &props.height };
+    let flex = |child_id: u32| {
+        let child_idx = child_id as usize - 1;
+        match main_units[child_idx] {
+            SizeUnit::Flex => main_values[child_idx].max(0.0),
+            _ => 0.0,
+        }
+    };
+    let mut items: Vec<(u32, f32, f32)> = children
+        .iter()
+        .map(|&child_id| {
+            let available = (content.width, content.height);
+            let stretch = if horizontal { (false, stretch) } else { (stretch, false) };
+            let (width, height) = input.child_size(child_id as usize - 1, available, stretch);
+            let (main_size, cross_size) = if horizontal { (width, height) } else { (height, width) };
+            if flex(child_id) > 0.0 {
+                (child_id, 0.0, cross_size)
+            } else {
+                (child_id, main_size, cross_size)
+            }
+        })
+        .collect();
+
+    let count = items.len() as f32;
+    let used: f32 = items.iter().map(|(_, size, _)| size).sum::<f32>() + gap * (count - 1.0);
+    let mut free = main - used;
+
+    // Flex items split the free space by weight, leaving none to pack
+    let total_flex: f32 = children.iter().map(|&c| flex(c)).sum();
+    if total_flex > 0.0 {
+        let share = free.max(0.0) / total_flex;
+        for (child_id, size, _) in &mut items {
+            *size += flex(*child_id) * share;
+        }
+        free = free.min(0.0);
+    }
+    let (mut offset, spacing) = match props.pack[idx] {
+        Pack::Start => (0.0, 0.0),
+        Pack::End => (free, 0.0),
+        Pack::Center => (free / 2.0, 0.0),
+        _ if free <= 0.0 => (0.0, 0.0),
+        Pack::SpaceBetween if count > 1.0 => (0.0, free / (count - 1.0)),
+        Pack::SpaceBetween => (0.0, 0.0),
+        Pack::SpaceAround => (free / count / 2.0, free / count),
+        Pack::SpaceEvenly => (free / (count + 1.0), free / (count + 1.0)),
+    };
+
+    for (child_id, size, cross_size) in items {
+        let cross_offset = match props.align[idx] {
+            Align::Start | Align::Stretch => 0.0,
+            Align::End => cross - cross_size,
+            Align::Center => (cross - cross_size) / 2.0,
+        };
+        let main_offset = if reverse { main - offset - size } else { offset };
+        let (x, y, width, height) = if horizontal {
+            (content.x + main_offset, content.y + cross_offset, size, cross_size)
+        } else {
+            (content.x + cross_offset, content.y + main_offset, cross_size, size)
+        };
+        layout_node_minimal(input, child_id, x, y, width, height, layout_states);
+        offset += size + gap + spacing;
+    }
+}
+
+/// Split `available` space between tracks: positive sizes are fixed pixels,
+/// the rest share what is left equally
+fn size_tracks(sizes: &[f32], available: f32, gap: f32) -> Vec<f32> {
+    let fixed: f32 = sizes.iter().filter(|&&s| s > 0.0).sum();
+    let flexible = sizes.iter().filter(|&&s| s <= 0.0).count();
+    let gaps = gap * sizes.len().saturating_sub(1) as f32;
+    let share = if flexible > 0 {
+        ((available - fixed - gaps) / flexible as f32).max(0.0)
+    } else {
+        0.0
+    };
+    sizes.iter().map(|&s| if s > 0.0 { s } else { share }).collect()
+}
+
+/// Offset of each track's start, with `gap` between tracks
+fn track_offsets(sizes: &[f32], gap: f32) -> Vec<f32> {
+    let mut offset = 0.0;
+    sizes
+        .iter()
+        .map(|size| {
+            let start = offset;
+            offset += size + gap;
+            start
+        })
+        .collect()
+}
+
+/// Column sizes of a Grid node (one flexible column if unset)
+fn grid_columns(props: &PropertyTable, idx: usize) -> Vec<f32> {
+    if props.grid_columns[idx].is_empty() {
+        vec![0.0]
+    } else {
+        props.grid_columns[idx].clone()
+    }
+}
+
+/// One child's cell in a grid
+#[derive(Clone, Copy, Debug)]
+struct GridPlacement {
+    child_id: u32,
+    row: usize,
+    col: usize,
+    row_span: usize,
+    col_span: usize,
+}
+
+/// Auto-place children row by row into the first free cells that fit their
+/// spans; returns the placements and the number of rows used
+fn place_grid_items(props: &PropertyTable, children: &[u32], column_count: usize) -> (Vec<GridPlacement>, usize) {
+    let mut occupied: Vec<Vec<bool>> = Vec::new();
+    let mut placements = Vec::with_capacity(children.len());
+    let (mut row, mut col) = (0, 0);
+    for &child_id in children {
+        let child_idx = child_id as usize - 1;
+        let col_span = (props.grid_column_span[child_idx].max(1) as usize).min(column_count);
+        let row_span = props.grid_row_span[child_idx].max(1) as usize;
+        loop {
+            if col + col_span > column_count {
+                row += 1;
+                col = 0;
+                continue;
+            }
+            let free = (row..row + row_span).all(|r| {
+                occupied.get(r).is_none_or(|cells| !cells[col..col + col_span].iter().any(|&c| c))
+            });
+            if free {
+                break;
+            }
+            col += 1;
+        }
+        if occupied.len() < row + row_span {
+            occupied.resize(row + row_span, vec![false; column_count]);
+        }
+        for cells in &mut occupied[row..row + row_span] {
+            cells[col..col + col_span].iter_mut().for_each(|c| *c = true);
+        }
+        placements.push(GridPlacement { child_id, row, col, row_span, col_span });
+        col += col_span;
+    }
+    (placements, occupied.len())
+}
+
+/// Content height of each grid row: its tallest single-row child (0 if none)
+fn grid_row_heights(input: &LayoutInput, placements: &[GridPlacement], row_count: usize) -> Vec<f32> {
+    let mut row_heights = vec![0.0f32; row_count];
+    for p in placements.iter().filter(|p| p.row_span == 1) {
+        let height = input.outer_size(p.child_id as usize - 1).height;
+        row_heights[p.row] = row_heights[p.row].max(height);
+    }
+    row_heights
+}
+
+/// Content size of a Grid node: fixed columns plus the widest single-column
+/// child of each flexible column, and the sum of row heights
+fn measure_grid(input: &LayoutInput, idx: usize, children: &[u32]) -> IntrinsicSize {
+    let props = input.props;
+    let (gap_row, gap_col) = (props.gap_row[idx], props.gap_col[idx]);
+    let columns = grid_columns(props, idx);
+    let (placements, row_count) = place_grid_items(props, children, columns.len());
+
+    let (mut min_width, mut max_width) = (0.0, 0.0);
+    for (col, &size) in columns.iter().enumerate() {
+        if size > 0.0 {
+            min_width += size;
+            max_width += size;
+            continue;
+        }
+        let cells = placements.iter().filter(|p| p.col == col && p.col_span == 1);
+        let sizes: Vec<IntrinsicSize> = cells.map(|p| input.outer_size(p.child_id as usize - 1)).collect();
+        min_width += sizes.iter().map(|s| s.min_width).fold(0.0, f32::max);
+        max_width += sizes.iter().map(|s| s.max_width).fold(0.0, f32::max);
+    }
+    let column_gaps = gap_col * (columns.len() - 1) as f32;
+    let row_heights = grid_row_heights(input, &placements, row_count);
+    IntrinsicSize {
+        min_width: min_width + column_gaps,
+        max_width: max_width + column_gaps,
+        height: row_heights.iter().sum::<f32>() + gap_row * row_count.saturating_sub(1) as f32,
+    }
+}
+
+/// Lay out the children of a Grid node inside its content rectangle
+///
+/// Columns come from `grid_columns` (one flexible column if unset), and
+/// children are placed by `place_grid_items`. A row is as tall as its
+/// tallest single-row child; rows without sized children share the leftover
+/// height equally. Auto-sized children fill their cells.
+fn layout_grid(
+    input: &LayoutInput,
+    idx: usize,
+    children: &[u32],
+    content: LayoutRect,
+    layout_states: &mut [LayoutRect],
+) {
+    let props = input.props;
+    let (gap_row, gap_col) = (props.gap_row[idx], props.gap_col[idx]);
+    let columns = grid_columns(props, idx);
+    let column_sizes = size_tracks(&columns, content.width, gap_col);
+    let column_offsets = track_offsets(&column_sizes, gap_col);
+
+    let (placements, row_count) = place_grid_items(props, children, columns.len());
+    let row_heights = grid_row_heights(input, &placements, row_count);
+    let row_heights = size_tracks(&row_heights, content.height, gap_row);
+    let row_offsets = track_offsets(&row_heights, gap_row);
+
+    let span_size = |sizes: &[f32], start: usize, span: usize, gap: f32| {
+        sizes[start..start + span].iter().sum::<f32>() + gap * (span - 1) as f32
+    };
+    for p in placements {
+        let cell_width = span_size(&column_sizes, p.col, p.col_span, gap_col);
+        let cell_height = span_size(&row_heights, p.row, p.row_span, gap_row);
+        let (width, height) = input.definite_size(p.child_id as usize - 1, cell_width, cell_height);
+        layout_node_minimal(
+            input,
+            p.child_id,
+            content.x + column_offsets[p.col],
+            content.y + row_offsets[p.row],
+            width.unwrap_or(cell_width),
+            height.unwrap_or(cell_height),
+            layout_states,
+        );
+    }
+}
+
+/// Find the topmost node whose area contains the point, 0 if none
+///
+/// Nodes painted later win, so children beat their parent and later
+/// siblings beat earlier ones. Scroll nodes clip their descendants to their
+/// own rectangle, and rounded corners are left out of a node's area.
+/// `layout` is the result of `compute_layout` for the same tables.
+pub fn hit_test(nodes: &NodeTable, props: &PropertyTable, layout: &[LayoutRect], x: f32, y: f32) -> u32 {
+    if nodes.is_empty() {
+        return 0;
+    }
+    hit_test_node(nodes, props, layout, 1, x, y).unwrap_or(0)
+}
+
+/// Hit test a node's subtree, topmost child first
+fn hit_test_node(nodes: &NodeTable, props: &PropertyTable, layout: &[LayoutRect], node_id: u32, x: f32, y: f32) -> Option<u32> {
+    if node_id == 0 || node_id > nodes.len() as u32 {
+        return None;
+    }
+    let idx = node_id as usize - 1;
+    let rect = layout.get(idx)?;
+    let inside = x >= rect.x && x < rect.x + rect.width && y >= rect.y && y < rect.y + rect.height;
+    
+    // Children may overflow their parent unless it clips them
+    if inside || nodes.node_types[idx] != NodeType::Scroll {
+        for child_id in nodes.get_children(node_id).into_iter().rev() {
+            if let Some(hit) = hit_test_node(nodes, props, layout, child_id, x, y) {
+                return Some(hit);
+            }
+        }
+    }
+    
+    if !inside {
+        return None;
+    }
+    let radius = props.border_radius[idx].min(rect.width / 2.0).min(rect.height / 2.0);
+    if radius > 0.0 {
+        // Distance from the center of the nearest corner arc
+        let dx = x - x.min(rect.x + rect.width - radius).max(rect.x + radius);
+        let dy = y - y.min(rect.y + rect.height - radius).max(rect.y + radius);
+        if dx * dx + dy * dy > radius * radius {
+            return None;
+        }
+    }
+    Some(node_id)
+}
+
 /// Render a single node recursively
 fn render_node(
     nodes: &NodeTable,
     props: &PropertyTable,
     node_id: u32,
-    layout_states: &[LayoutState],
+    layout_states: &[LayoutRect],
     commands: &mut Vec<RenderCommand>,
 ) {
     if node_id == 0 || node_id > nodes.len() as u32 {
@@ -176,7 +820,7 @@ fn render_node(
     
     // Render based on node type
     match node_type {
-        NodeType::Rect | NodeType::Stack => {
+        NodeType::Rect | NodeType::Stack | NodeType::Grid | NodeType::Scroll => {
             // Draw background if fill color is set
             if props.fill_a[idx] > 0 {
                 commands.push(RenderCommand::FillRect {
@@ -212,7 +856,254 @@ fn render_node(
     
     // Render children
     let children = nodes.get_children(node_id);
+    if children.is_empty() {
+        return;
+    }
+    let clip = node_type == NodeType::Scroll;
+    if clip {
+        commands.push(RenderCommand::PushClip {
+            x: layout.x,
+            y: layout.y,
+            width: layout.width,
+            height: layout.height,
+        });
+    }
     for child_id in children {
         render_node(nodes, props, child_id, layout_states, commands);
     }
+    if clip {
+        commands.push(RenderCommand::PopClip);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, ContentBuilder};
+
+    fn fill(x: f32, y: f32, width: f32, height: f32, [r, g, b]: [u8; 3]) -> RenderCommand {
+        RenderCommand::FillRect { x, y, width, height, r, g, b, a: 255, border_radius: 0.0 }
+    }
+
+    #[test]
+    fn test_pack_align() {
+        // Two 20x20 rects in a 100x100 stack
+        let build = |direction: Direction, pack: Pack, align: Align| {
+            let mut builder = ContentBuilder::new();
+            builder.begin_stack().width(100.0).height(100.0).direction(direction).pack(pack).align(align);
+            builder.rect().fill(Color::new(255, 0, 0, 255));
+            builder.rect().fill(Color::new(0, 0, 255, 255));
+            builder.end();
+            let (nodes, mut props) = builder.build();
+            for idx in [2, 3] {
+                props.width[idx] = 20.0;
+                props.height[idx] = 20.0;
+            }
+            compute_layout(&nodes, &props, 100.0, 100.0)
+        };
+        let rect = |x: f32, y: f32| LayoutRect { x, y, width: 20.0, height: 20.0 };
+
+        let layout = build(Direction::Right, Pack::Center, Align::Center);
+        assert_eq!(layout[2..4], [rect(30.0, 40.0), rect(50.0, 40.0)]);
+        let layout = build(Direction::Down, Pack::SpaceBetween, Align::End);
+        assert_eq!(layout[2..4], [rect(80.0, 0.0), rect(80.0, 80.0)]);
+        let layout = build(Direction::Left, Pack::SpaceEvenly, Align::Start);
+        assert_eq!(layout[2..4], [rect(60.0, 0.0), rect(20.0, 0.0)]);
+        // Reverse directions stack from the far edge
+        let layout = build(Direction::Up, Pack::Start, Align::Stretch);
+        assert_eq!(layout[2..4], [rect(0.0, 80.0), rect(0.0, 60.0)]);
+        let layout = build(Direction::Up, Pack::End, Align::Center);
+        assert_eq!(layout[2..4], [rect(40.0, 20.0), rect(40.0, 0.0)]);
+        let layout = build(Direction::Left, Pack::Start, Align::End);
+        assert_eq!(layout[2..4], [rect(80.0, 80.0), rect(60.0, 80.0)]);
+    }
+
+    #[test]
+    fn test_intrinsic_size() {
+        // A padded button holding one label shrinks to the label; a row adds up its children
+        let build = |align: Align| {
+            let mut builder = ContentBuilder::new();
+            builder.align(align);
+            builder.begin_stack().inset(5.0);
+            builder.span("OK");
+            builder.end();
+            builder.begin_stack().direction(Direction::Right).gap(4.0);
+            builder.span("one two");
+            builder.span("three");
+            builder.end();
+            let (nodes, props) = builder.build();
+            let measure = |text: &str, font_size: f32| (text.chars().count() as f32 * 10.0, font_size);
+            compute_layout_with_measure(&nodes, &props, 200.0, 200.0, &measure)
+        };
+        let rect = |x: f32, y: f32, width: f32, height: f32| LayoutRect { x, y, width, height };
+
+        let layout = build(Align::Start);
+        assert_eq!(layout[1], rect(0.0, 0.0, 30.0, 26.0));
+        assert_eq!(layout[2], rect(5.0, 5.0, 20.0, 16.0));
+        assert_eq!(layout[3], rect(0.0, 26.0, 124.0, 16.0));
+        assert_eq!(layout[5], rect(74.0, 26.0, 50.0, 16.0));
+
+        // Stretch fills the cross axis
+        let layout = build(Align::Stretch);
+        assert_eq!(layout[1].width, 200.0);
+        assert_eq!(layout[3].width, 200.0);
+    }
+
+    #[test]
+    fn test_size_units() {
+        // A 200px row: a 25% column, then flex 1 and flex 3 splitting the rest around a 20px gap
+        let mut builder = ContentBuilder::new();
+        builder.begin_stack().direction(Direction::Right).width(200.0).height(50.0).gap(20.0);
+        builder.begin_stack().width_percent(25.0).height_percent(50.0).end();
+        builder.begin_stack().flex_grow(1.0).end();
+        builder.begin_stack().flex_grow(3.0).end();
+        builder.end();
+        let (nodes, props) = builder.build();
+
+        let layout = compute_layout(&nodes, &props, 400.0, 400.0);
+        assert_eq!((layout[2].x, layout[2].width, layout[2].height), (0.0, 50.0, 25.0));
+        assert_eq!((layout[3].x, layout[3].width), (70.0, 27.5));
+        assert_eq!((layout[4].x, layout[4].width), (117.5, 82.5));
+        // Flex on the cross axis fills the row like any empty node
+        assert_eq!(layout[4].height, 50.0);
+    }
+
+    #[test]
+    fn test_grid() {
+        // A 30px column and a flexible one with 10px gaps; the third cell spans
+        // both columns and the two rows share the 70px height
+        let mut builder = ContentBuilder::new();
+        builder.begin_grid().width(100.0).height(70.0).grid_columns(&[30.0, 0.0]).grid_gap(10.0, 10.0);
+        builder.rect().fill(Color::new(255, 0, 0, 255));
+        builder.rect().fill(Color::new(0, 0, 255, 255));
+        builder.rect().fill(Color::new(0, 255, 0, 255)).grid_span(2, 1);
+        builder.end();
+        let (nodes, props) = builder.build();
+
+        assert_eq!(
+            render(&nodes, &props, 100.0, 100.0),
+            [
+                fill(0.0, 0.0, 30.0, 30.0, [255, 0, 0]),
+                fill(40.0, 0.0, 60.0, 30.0, [0, 0, 255]),
+                fill(0.0, 40.0, 100.0, 30.0, [0, 255, 0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scroll() {
+        // A 50x50 scroll box holding two 40px rows, scrolled down by 30
+        let mut builder = ContentBuilder::new();
+        builder.begin_scroll().width(50.0).height(50.0);
+        builder.rect().fill(Color::new(255, 0, 0, 255));
+        builder.rect().fill(Color::new(0, 0, 255, 255));
+        builder.end();
+        builder.scroll_offset(2, 0.0, 30.0);
+        let (nodes, mut props) = builder.build();
+        props.height[2] = 40.0;
+        props.height[3] = 40.0;
+
+        // The rows are shifted up and clipped to the box
+        assert_eq!(
+            render(&nodes, &props, 100.0, 100.0),
+            [
+                RenderCommand::PushClip { x: 0.0, y: 0.0, width: 50.0, height: 50.0 },
+                fill(0.0, -30.0, 50.0, 40.0, [255, 0, 0]),
+                fill(0.0, 10.0, 50.0, 40.0, [0, 0, 255]),
+                RenderCommand::PopClip,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hit_test() {
+        // A 50x50 scroll box holding two 40px rows scrolled down by 20 (the
+        // second 80px wide), then a rounded 40px row below it
+        let mut builder = ContentBuilder::new();
+        builder.begin_scroll().width(50.0).height(50.0);
+        builder.rect();
+        builder.rect();
+        builder.end();
+        builder.rect().border_radius(20.0);
+        builder.scroll_offset(2, 0.0, 20.0);
+        let props = builder.tables_mut().1;
+        props.height[2] = 40.0;
+        props.height[3] = 40.0;
+        props.width[3] = 80.0;
+        props.height[4] = 40.0;
+        builder.compute_layout(100.0, 100.0);
+
+        assert_eq!(builder.hit_test(25.0, 5.0), 3);
+        assert_eq!(builder.hit_test(25.0, 30.0), 4);
+        // The second row reaches x = 80 but is clipped to the box
+        assert_eq!(builder.hit_test(75.0, 30.0), 1);
+        assert_eq!(builder.hit_test(50.0, 70.0), 5);
+        // Outside the rounded corner
+        assert_eq!(builder.hit_test(1.0, 51.0), 1);
+        assert_eq!(builder.hit_test(150.0, 20.0), 0);
+    }
+
+    #[test]
+    fn test_layout_rect_persists() {
+        let mut builder = ContentBuilder::new();
+        builder.inset(10.0).begin_stack().width(40.0).height(30.0);
+        builder.end();
+        builder.rect();
+        assert_eq!(builder.layout_rect(3), None);
+
+        let commands = builder.render(100.0, 80.0);
+        assert!(commands.is_empty());
+        assert_eq!(builder.layout_rect(1), Some(LayoutRect { x: 0.0, y: 0.0, width: 100.0, height: 80.0 }));
+        assert_eq!(builder.layout_rect(2), Some(LayoutRect { x: 10.0, y: 10.0, width: 40.0, height: 30.0 }));
+        assert_eq!(builder.layout_rect(3), Some(LayoutRect { x: 10.0, y: 40.0, width: 80.0, height: 60.0 }));
+        assert_eq!(builder.layout_rect(0), None);
+        assert_eq!(builder.layout_rect(4), None);
+    }
+
+    #[test]
+    fn test_render_with_layout() {
+        let mut builder = ContentBuilder::new();
+        builder.rect().fill(Color::new(255, 0, 0, 255));
+        let (nodes, props) = builder.build();
+        let rect = |x, y, width, height| LayoutRect { x, y, width, height };
+
+        // Positions from elsewhere are used as is
+        let positions = [rect(0.0, 0.0, 100.0, 100.0), rect(60.0, 20.0, 30.0, 10.0)];
+        assert_eq!(render_with_layout(&nodes, &props, &positions), [fill(60.0, 20.0, 30.0, 10.0, [255, 0, 0])]);
+        assert!(render_with_layout(&nodes, &props, &positions[..1]).is_empty());
+    }
+
+    #[test]
+    fn test_update_layout() {
+        // A fixed-size stack holding a span, then an auto-sized stack
+        let mut builder = ContentBuilder::new();
+        builder.begin_stack().width(100.0).height(50.0);
+        builder.span("hi");
+        builder.end();
+        builder.begin_stack();
+        builder.span("there");
+        builder.end();
+        builder.render(200.0, 200.0);
+        assert!(relayout_roots(builder.tables().0, builder.tables().1).is_empty());
+
+        // Text inside the fixed stack only relays out that stack
+        builder.tables_mut().1.set_text(2, "hello world");
+        let (nodes, props) = builder.tables();
+        assert_eq!(relayout_roots(nodes, props), vec![2]);
+        let commands = builder.update_layout();
+        assert_eq!(commands.len(), 1);
+        assert!(matches!(&commands[0], RenderCommand::DrawText { text, .. } if text == "hello world"));
+        let (nodes, props) = builder.tables();
+        assert_eq!(builder.layout(), compute_layout(nodes, props, 200.0, 200.0));
+
+        // Resizing the stack itself moves its sibling, so the root is redone
+        builder.tables_mut().1.set_size(1, 100.0, 80.0);
+        let (nodes, props) = builder.tables();
+        assert_eq!(relayout_roots(nodes, props), vec![1]);
+        builder.update_layout();
+        let (nodes, props) = builder.tables();
+        assert_eq!(builder.layout(), compute_layout(nodes, props, 200.0, 200.0));
+        assert_eq!(builder.layout_rect(4).map(|r| r.y), Some(80.0));
+    }
 }
@@ -0,0 +1,124 @@
+//! Tree Statistics
+//!
+//! This module summarizes the shape of a Content IR tree: node counts per
+//! type, maximum depth, and nodes that are unreachable from the root. It is
+//! used for performance budgeting and for catching builder bugs.
+
+use crate::primitives::{NodeTable, NodeType};
+
+/// Number of distinct node types
+pub const NODE_TYPE_COUNT: usize = 9;
+
+/// Summary statistics for a node table
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct NodeStats {
+    /// Node count per type, indexed by `NodeType as usize`
+    pub type_counts: [u32; NODE_TYPE_COUNT],
+    /// Total number of live nodes in the table
+    pub total: u32,
+    /// Maximum depth reachable from the root (root = 0)
+    pub max_depth: u32,
+    /// Number of nodes not reachable from the root
+    pub orphan_count: u32,
+}
+
+impl NodeStats {
+    /// Get the number of nodes of a given type
+    pub fn count(&self, node_type: NodeType) -> u32 {
+        self.type_counts[node_type as usize]
+    }
+}
+
+impl NodeTable {
+    /// Compute statistics for the tree rooted at node 1
+    pub fn stats(&self) -> NodeStats {
+        let free = &self.removed;
+        let mut stats = NodeStats {
+            total: (self.len() - self.free_slots.len()) as u32,
+            ..Default::default()
+        };
+        for (&node_type, _) in self.node_types.iter().zip(free).filter(|(_, &f)| !f) {
+            stats.type_counts[node_type as usize] += 1;
+        }
+
+        let reached = self.reachable();
+        stats.orphan_count = reached.iter().zip(free).filter(|(&r, &f)| !r && !f).count() as u32;
+
+        // Pre-order visits parents before children, so a parent's depth is always known
+        let mut depths = vec![0u32; self.len()];
+        for id in self.depth_first(1) {
+            let idx = id as usize - 1;
+            let parent = self.parents[idx];
+            if id != 1 && parent > 0 {
+                depths[idx] = depths[parent as usize - 1] + 1;
+                stats.max_depth = stats.max_depth.max(depths[idx]);
+            }
+        }
+
+        stats
+    }
+
+    /// Find nodes that cannot be reached from the root through child links
+    ///
+    /// Removed nodes waiting for reuse are not orphans.
+    pub fn find_orphans(&self) -> Vec<u32> {
+        self.reachable()
+            .iter()
+            .enumerate()
+            .filter(|&(idx, &r)| !r && !self.removed[idx])
+            .map(|(idx, _)| idx as u32 + 1)
+            .collect()
+    }
+
+    /// Mark every node reachable from the root
+    fn reachable(&self) -> Vec<bool> {
+        let mut reached = vec![false; self.len()];
+        for id in self.depth_first(1) {
+            reached[id as usize - 1] = true;
+        }
+        reached
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ContentBuilder;
+
+    #[test]
+    fn test_node_stats() {
+        // Root 1 holding stack 2 (paragraph 3 holding span 4) and rect 5
+        let mut builder = ContentBuilder::new();
+        builder.begin_stack().begin_paragraph().span("hi").end().end();
+        builder.rect();
+        let detached = builder.tables_mut().0.create_node(NodeType::Rect, 0, 0);
+
+        let stats = builder.tables().0.stats();
+        assert_eq!(stats.total, 6);
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(stats.orphan_count, 1);
+        for (node_type, count) in [
+            (NodeType::Root, 1),
+            (NodeType::Stack, 1),
+            (NodeType::Paragraph, 1),
+            (NodeType::Span, 1),
+            (NodeType::Rect, 2),
+            (NodeType::Grid, 0),
+        ] {
+            assert_eq!(stats.count(node_type), count, "{node_type:?}");
+        }
+        assert_eq!(builder.tables().0.find_orphans(), vec![detached]);
+
+        // Removed nodes leave the counts and are not orphans
+        builder.remove(2);
+        let stats = builder.tables().0.stats();
+        assert_eq!(stats.total, 3);
+        assert_eq!(stats.max_depth, 1);
+        assert_eq!(stats.orphan_count, 1);
+        assert_eq!(stats.count(NodeType::Stack), 0);
+        assert_eq!(stats.count(NodeType::Span), 0);
+        assert_eq!(stats.count(NodeType::Rect), 2);
+        assert_eq!(builder.tables().0.find_orphans(), vec![detached]);
+    }
+}
@@ -0,0 +1,341 @@
+//! Shared Styles
+//!
+//! This module stores flattened styles referenced by `NodeTable.style_ids`
+//! and resolves node properties on top of them. A property the node set
+//! itself (flagged in `PropertyTable.overrides`) wins, even if it was set
+//! back to its default; otherwise the value from its style is used. Style
+//! ID 0 means "no style".
+
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use crate::primitives::NodeTable;
+use crate::properties::{Align, Direction, Pack, PropertyTable, SizeUnit, DEFAULT_FONT_SIZE};
+
+/// Style-backed property groups, as bits of `PropertyTable.overrides`
+///
+/// A set bit means the node set that group itself and its style must not
+/// replace it. Width and height include their units; fill and text color
+/// cover all four channels.
+pub mod overrides {
+    pub const DIRECTION: u32 = 1 << 0;
+    pub const PACK: u32 = 1 << 1;
+    pub const ALIGN: u32 = 1 << 2;
+    pub const WIDTH: u32 = 1 << 3;
+    pub const HEIGHT: u32 = 1 << 4;
+    pub const GAP_ROW: u32 = 1 << 5;
+    pub const GAP_COL: u32 = 1 << 6;
+    pub const INSET_TOP: u32 = 1 << 7;
+    pub const INSET_RIGHT: u32 = 1 << 8;
+    pub const INSET_BOTTOM: u32 = 1 << 9;
+    pub const INSET_LEFT: u32 = 1 << 10;
+    pub const OFFSET_TOP: u32 = 1 << 11;
+    pub const OFFSET_RIGHT: u32 = 1 << 12;
+    pub const OFFSET_BOTTOM: u32 = 1 << 13;
+    pub const OFFSET_LEFT: u32 = 1 << 14;
+    pub const FILL: u32 = 1 << 15;
+    pub const BORDER_RADIUS: u32 = 1 << 16;
+    pub const FONT_SIZE: u32 = 1 << 17;
+    pub const TEXT_COLOR: u32 = 1 << 18;
+
+    pub const INSET: u32 = INSET_TOP | INSET_RIGHT | INSET_BOTTOM | INSET_LEFT;
+}
+
+/// Flattened style with all inheritance resolved
+///
+/// Layout-compatible with `FlatStyle` in dop-parser's compiler, so arrays
+/// produced by the AOT compiler can be passed through FFI unchanged.
+#[derive(Clone, Copy, Debug, Default, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C, packed)]
+pub struct FlatStyle {
+    pub direction: u8,
+    pub pack: u8,
+    pub align: u8,
+    pub _pad0: u8,
+
+    pub gap_row: f32,
+    pub gap_col: f32,
+
+    pub width: f32,
+    pub height: f32,
+    pub min_width: f32,
+    pub min_height: f32,
+    pub max_width: f32,
+    pub max_height: f32,
+
+    pub inset_top: f32,
+    pub inset_right: f32,
+    pub inset_bottom: f32,
+    pub inset_left: f32,
+
+    pub offset_top: f32,
+    pub offset_right: f32,
+    pub offset_bottom: f32,
+    pub offset_left: f32,
+
+    pub fill_r: u8,
+    pub fill_g: u8,
+    pub fill_b: u8,
+    pub fill_a: u8,
+
+    pub round: f32,
+
+    pub width_unit: u8,
+    pub height_unit: u8,
+    pub _pad1: [u8; 2],
+
+    /// Font size in pixels; 0 leaves the default
+    pub font_size: f32,
+
+    /// Text color; alpha 0 leaves the default
+    pub text_color_r: u8,
+    pub text_color_g: u8,
+    pub text_color_b: u8,
+    pub text_color_a: u8,
+
+    pub checksum: u64,
+}
+
+/// Table of flattened styles indexed by style ID (1-indexed)
+#[derive(Clone, Default, Debug)]
+pub struct StyleTable {
+    styles: Vec<FlatStyle>,
+}
+
+impl StyleTable {
+    /// Create a new empty style table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a style table from compiler output (entry `i` becomes style ID `i + 1`)
+    pub fn from_flat(styles: &[FlatStyle]) -> Self {
+        Self {
+            styles: styles.to_vec(),
+        }
+    }
+
+    /// Get the number of styles
+    pub fn len(&self) -> usize {
+        self.styles.len()
+    }
+
+    /// Check if the table is empty
+    pub fn is_empty(&self) -> bool {
+        self.styles.is_empty()
+    }
+
+    /// Append a style and return its ID
+    pub fn add_style(&mut self, style: FlatStyle) -> u32 {
+        self.styles.push(style);
+        self.styles.len() as u32
+    }
+
+    /// Replace all styles with compiler output
+    pub fn load_flat(&mut self, styles: &[FlatStyle]) {
+        self.styles.clear();
+        self.styles.extend_from_slice(styles);
+    }
+
+    /// Get all styles in ID order (entry `i` is style ID `i + 1`)
+    pub fn as_flat(&self) -> &[FlatStyle] {
+        &self.styles
+    }
+
+    /// Get a style by ID
+    pub fn get(&self, style_id: u32) -> Option<&FlatStyle> {
+        if style_id == 0 {
+            return None;
+        }
+        self.styles.get(style_id as usize - 1)
+    }
+}
+
+/// Resolve node properties against their referenced styles, in place
+///
+/// Every node with a style ID takes the style's value for each property
+/// group it has not set itself; an unknown style ID acts as an empty style.
+/// Nodes whose values change are marked dirty, so this is cheap to repeat
+/// before every layout pass.
+pub fn resolve_styles(nodes: &NodeTable, props: &mut PropertyTable, styles: &StyleTable) {
+    for (idx, &style_id) in nodes.style_ids.iter().enumerate().take(props.width.len()) {
+        if style_id != 0 {
+            let style = styles.get(style_id).copied().unwrap_or_default();
+            resolve_node(props, idx, &style);
+        }
+    }
+}
+
+/// Give one node the values of `style` for the groups it has not set itself
+///
+/// Pass `FlatStyle::default()` to drop a node's style and restore defaults.
+pub fn resolve_node(props: &mut PropertyTable, idx: usize, style: &FlatStyle) {
+    if idx >= props.width.len() {
+        return;
+    }
+    let set = props.overrides[idx];
+    let inherits = |group: u32| set & group == 0;
+    let mut layout = false;
+    let mut paint = false;
+
+    if inherits(overrides::DIRECTION) {
+        layout |= replace(&mut props.direction, idx, Direction::from_u8(style.direction));
+    }
+    if inherits(overrides::PACK) {
+        layout |= replace(&mut props.pack, idx, Pack::from_u8(style.pack));
+    }
+    if inherits(overrides::ALIGN) {
+        layout |= replace(&mut props.align, idx, Align::from_u8(style.align));
+    }
+    if inherits(overrides::WIDTH) {
+        layout |= replace(&mut props.width, idx, style.width);
+        layout |= replace(&mut props.width_unit, idx, SizeUnit::from_u8(style.width_unit));
+    }
+    if inherits(overrides::HEIGHT) {
+        layout |= replace(&mut props.height, idx, style.height);
+        layout |= replace(&mut props.height_unit, idx, SizeUnit::from_u8(style.height_unit));
+    }
+
+    for (group, column, value) in [
+        (overrides::GAP_ROW, &mut props.gap_row, style.gap_row),
+        (overrides::GAP_COL, &mut props.gap_col, style.gap_col),
+        (overrides::INSET_TOP, &mut props.inset_top, style.inset_top),
+        (overrides::INSET_RIGHT, &mut props.inset_right, style.inset_right),
+        (overrides::INSET_BOTTOM, &mut props.inset_bottom, style.inset_bottom),
+        (overrides::INSET_LEFT, &mut props.inset_left, style.inset_left),
+        (overrides::OFFSET_TOP, &mut props.offset_top, style.offset_top),
+        (overrides::OFFSET_RIGHT, &mut props.offset_right, style.offset_right),
+        (overrides::OFFSET_BOTTOM, &mut props.offset_bottom, style.offset_bottom),
+        (overrides::OFFSET_LEFT, &mut props.offset_left, style.offset_left),
+    ] {
+        if inherits(group) {
+            layout |= replace(column, idx, value);
+        }
+    }
+    if inherits(overrides::FONT_SIZE) {
+        let size = if style.font_size > 0.0 { style.font_size } else { DEFAULT_FONT_SIZE };
+        layout |= replace(&mut props.font_size, idx, size);
+    }
+
+    if inherits(overrides::FILL) {
+        for (column, value) in [
+            (&mut props.fill_r, style.fill_r),
+            (&mut props.fill_g, style.fill_g),
+            (&mut props.fill_b, style.fill_b),
+            (&mut props.fill_a, style.fill_a),
+        ] {
+            paint |= replace(column, idx, value);
+        }
+    }
+    if inherits(overrides::BORDER_RADIUS) {
+        paint |= replace(&mut props.border_radius, idx, style.round);
+    }
+    if inherits(overrides::TEXT_COLOR) {
+        // An unset (transparent) style color keeps the default opaque black
+        let color = if style.text_color_a > 0 {
+            [style.text_color_r, style.text_color_g, style.text_color_b, style.text_color_a]
+        } else {
+            [0, 0, 0, 255]
+        };
+        for (column, value) in [
+            &mut props.text_color_r,
+            &mut props.text_color_g,
+            &mut props.text_color_b,
+            &mut props.text_color_a,
+        ]
+        .into_iter()
+        .zip(color)
+        {
+            paint |= replace(column, idx, value);
+        }
+    }
+
+    if layout {
+        props.mark_layout_dirty(idx);
+    } else if paint {
+        props.mark_dirty(idx);
+    }
+}
+
+/// Store `value` at `idx`, returning whether it changed
+fn replace<T: PartialEq>(column: &mut [T], idx: usize, value: T) -> bool {
+    let changed = column[idx] != value;
+    column[idx] = value;
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, ContentBuilder};
+
+    fn blue_text_style() -> FlatStyle {
+        FlatStyle {
+            width: 50.0,
+            width_unit: SizeUnit::Percent as u8,
+            gap_row: 4.0,
+            fill_b: 255,
+            fill_a: 255,
+            font_size: 24.0,
+            text_color_r: 255,
+            text_color_a: 255,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_resolve_styles_inherit() {
+        let mut builder = ContentBuilder::new();
+        let style = builder.styles_mut().add_style(blue_text_style());
+        builder.begin_stack().style(style).end();
+        builder.compute_layout(200.0, 100.0);
+
+        let props = builder.tables().1;
+        assert_eq!((props.width[1], props.width_unit[1]), (50.0, SizeUnit::Percent));
+        assert_eq!(props.gap_row[1], 4.0);
+        assert_eq!((props.fill_b[1], props.fill_a[1]), (255, 255));
+        assert_eq!(props.font_size[1], 24.0);
+        assert_eq!((props.text_color_r[1], props.text_color_a[1]), (255, 255));
+        assert_eq!(builder.layout_rect(2).unwrap().width, 100.0);
+
+        // A replaced style applies at the next layout; what it leaves unset returns to defaults
+        builder.styles_mut().load_flat(&[FlatStyle { height: 30.0, ..Default::default() }]);
+        builder.compute_layout(200.0, 100.0);
+        let props = builder.tables().1;
+        assert_eq!((props.width[1], props.width_unit[1]), (0.0, SizeUnit::Px));
+        assert_eq!(props.height[1], 30.0);
+        assert_eq!(props.fill_a[1], 0);
+        assert_eq!(props.font_size[1], DEFAULT_FONT_SIZE);
+        assert_eq!((props.text_color_r[1], props.text_color_a[1]), (0, 255));
+    }
+
+    #[test]
+    fn test_resolve_styles_override() {
+        let mut builder = ContentBuilder::new();
+        let style = builder.styles_mut().add_style(blue_text_style());
+        // Overrides set back to the defaults still beat the style
+        builder
+            .begin_stack()
+            .style(style)
+            .width(0.0)
+            .gap(0.0)
+            .font_size(DEFAULT_FONT_SIZE)
+            .text_color(Color::black())
+            .fill(Color::transparent());
+        builder.end();
+        // Overriding before the style is set works the same
+        builder.begin_stack().height_percent(25.0).font_size(12.0).style(style).end();
+        builder.compute_layout(200.0, 100.0);
+
+        let props = builder.tables().1;
+        assert_eq!((props.width[1], props.width_unit[1]), (0.0, SizeUnit::Px));
+        assert_eq!(props.gap_row[1], 0.0);
+        assert_eq!(props.fill_a[1], 0);
+        assert_eq!(props.font_size[1], DEFAULT_FONT_SIZE);
+        assert_eq!((props.text_color_r[1], props.text_color_a[1]), (0, 255));
+
+        assert_eq!((props.height[2], props.height_unit[2]), (25.0, SizeUnit::Percent));
+        assert_eq!(props.font_size[2], 12.0);
+        assert_eq!((props.width[2], props.width_unit[2]), (50.0, SizeUnit::Percent));
+        assert_eq!(props.fill_b[2], 255);
+    }
+}
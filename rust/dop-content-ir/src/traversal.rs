@@ -0,0 +1,218 @@
+//! Subtree Traversal
+//!
+//! This module provides depth-first and breadth-first walks over a node's
+//! subtree. Traversal follows the first-child / next-sibling links directly,
+//! so no per-level child `Vec` is allocated as with `NodeTable::get_children`.
+
+use std::collections::VecDeque;
+
+use crate::primitives::NodeTable;
+
+/// Traversal order for a subtree walk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TraversalOrder {
+    DepthFirst = 0,
+    BreadthFirst = 1,
+}
+
+/// Owned traversal state for a subtree walk
+///
+/// The cursor does not borrow the node table, so it can be held across calls
+/// (for example behind an FFI handle) and advanced with `next_node`. Nodes are
+/// yielded starting with the subtree root; the table must not be modified
+/// while a walk is in progress.
+#[derive(Clone, Debug)]
+pub struct SubtreeCursor {
+    root: u32,
+    next: u32,
+    order: TraversalOrder,
+    queue: VecDeque<u32>,
+}
+
+impl SubtreeCursor {
+    /// Create a cursor positioned at `root` (0 yields nothing)
+    pub fn new(root: u32, order: TraversalOrder) -> Self {
+        Self {
+            root,
+            next: root,
+            order,
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Restart the walk from the subtree root
+    pub fn reset(&mut self) {
+        self.next = self.root;
+        self.queue.clear();
+    }
+
+    /// Advance the cursor and return the next node ID, or 0 when the walk is done
+    pub fn next_node(&mut self, nodes: &NodeTable) -> u32 {
+        let current = self.next;
+        if current == 0 || current > nodes.len() as u32 {
+            self.next = 0;
+            return 0;
+        }
+
+        self.next = match self.order {
+            TraversalOrder::DepthFirst => next_preorder(nodes, current, self.root),
+            TraversalOrder::BreadthFirst => {
+                let mut child = nodes.first_children[current as usize - 1];
+                while child != 0 {
+                    self.queue.push_back(child);
+                    child = nodes.next_siblings[child as usize - 1];
+                }
+                self.queue.pop_front().unwrap_or(0)
+            }
+        };
+        current
+    }
+}
+
+/// Find the pre-order successor of `current` without leaving the subtree of `root`
+fn next_preorder(nodes: &NodeTable, current: u32, root: u32) -> u32 {
+    let first_child = nodes.first_children[current as usize - 1];
+    if first_child != 0 {
+        return first_child;
+    }
+
+    // Climb until a node with a next sibling is found, stopping at the root
+    let mut node = current;
+    while node != root && node != 0 {
+        let sibling = nodes.next_siblings[node as usize - 1];
+        if sibling != 0 {
+            return sibling;
+        }
+        node = nodes.parents[node as usize - 1];
+    }
+    0
+}
+
+/// Depth-first (pre-order) iterator over a subtree
+pub struct DepthFirst<'a> {
+    nodes: &'a NodeTable,
+    cursor: SubtreeCursor,
+}
+
+impl Iterator for DepthFirst<'_> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        match self.cursor.next_node(self.nodes) {
+            0 => None,
+            id => Some(id),
+        }
+    }
+}
+
+/// Breadth-first (level-order) iterator over a subtree
+pub struct BreadthFirst<'a> {
+    nodes: &'a NodeTable,
+    cursor: SubtreeCursor,
+}
+
+impl Iterator for BreadthFirst<'_> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        match self.cursor.next_node(self.nodes) {
+            0 => None,
+            id => Some(id),
+        }
+    }
+}
+
+impl NodeTable {
+    /// Iterate over the subtree rooted at `node_id` in depth-first order, root included
+    pub fn depth_first(&self, node_id: u32) -> DepthFirst<'_> {
+        DepthFirst {
+            nodes: self,
+            cursor: SubtreeCursor::new(node_id, TraversalOrder::DepthFirst),
+        }
+    }
+
+    /// Iterate over the subtree rooted at `node_id` in breadth-first order, root included
+    pub fn breadth_first(&self, node_id: u32) -> BreadthFirst<'_> {
+        BreadthFirst {
+            nodes: self,
+            cursor: SubtreeCursor::new(node_id, TraversalOrder::BreadthFirst),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ContentBuilder;
+
+    /// Root 1 holding stack 2 (rect 3, stack 4 holding rect 5), rect 6 and stack 7 (rect 8)
+    fn nested() -> ContentBuilder {
+        let mut builder = ContentBuilder::new();
+        builder.begin_stack().rect().begin_stack().rect().end().end();
+        builder.rect();
+        builder.begin_stack().rect().end();
+        builder
+    }
+
+    #[test]
+    fn test_depth_first_order() {
+        let builder = nested();
+        let nodes = builder.tables().0;
+        assert_eq!(nodes.depth_first(1).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        // A subtree walk stops at the subtree's end instead of moving on to its siblings
+        assert_eq!(nodes.depth_first(2).collect::<Vec<_>>(), vec![2, 3, 4, 5]);
+        assert_eq!(nodes.depth_first(4).collect::<Vec<_>>(), vec![4, 5]);
+        assert_eq!(nodes.depth_first(6).collect::<Vec<_>>(), vec![6]);
+        assert_eq!(nodes.depth_first(5).collect::<Vec<_>>(), vec![5]);
+        assert_eq!(nodes.depth_first(0).count(), 0);
+        assert_eq!(nodes.depth_first(9).count(), 0);
+    }
+
+    #[test]
+    fn test_breadth_first_order() {
+        let builder = nested();
+        let nodes = builder.tables().0;
+        assert_eq!(nodes.breadth_first(1).collect::<Vec<_>>(), vec![1, 2, 6, 7, 3, 4, 8, 5]);
+        assert_eq!(nodes.breadth_first(2).collect::<Vec<_>>(), vec![2, 3, 4, 5]);
+        assert_eq!(nodes.breadth_first(7).collect::<Vec<_>>(), vec![7, 8]);
+        assert_eq!(nodes.breadth_first(6).collect::<Vec<_>>(), vec![6]);
+        assert_eq!(nodes.breadth_first(0).count(), 0);
+    }
+
+    #[test]
+    fn test_subtree_cursor_ffi() {
+        use crate::ffi::{
+            content_builder_begin_stack, content_builder_end, content_builder_free, content_builder_new,
+            content_builder_rect, content_subtree_cursor_free, content_subtree_cursor_new,
+            content_subtree_cursor_next, content_subtree_cursor_reset,
+        };
+
+        // Root 1 holding stack 2 (rects 3 and 4) and rect 5
+        let handle = content_builder_new();
+        content_builder_begin_stack(handle);
+        content_builder_rect(handle);
+        content_builder_rect(handle);
+        content_builder_end(handle);
+        content_builder_rect(handle);
+
+        let walk = |cursor| {
+            std::iter::from_fn(move || Some(content_subtree_cursor_next(cursor, handle)).filter(|&id| id != 0))
+                .collect::<Vec<_>>()
+        };
+        let depth_first = content_subtree_cursor_new(1, TraversalOrder::DepthFirst as u8);
+        assert_eq!(walk(depth_first), vec![1, 2, 3, 4, 5]);
+        assert_eq!(content_subtree_cursor_next(depth_first, handle), 0);
+        content_subtree_cursor_reset(depth_first);
+        assert_eq!(walk(depth_first), vec![1, 2, 3, 4, 5]);
+
+        let breadth_first = content_subtree_cursor_new(2, TraversalOrder::BreadthFirst as u8);
+        assert_eq!(walk(breadth_first), vec![2, 3, 4]);
+        assert_eq!(content_subtree_cursor_next(breadth_first, std::ptr::null()), 0);
+        assert_eq!(content_subtree_cursor_next(std::ptr::null_mut(), handle), 0);
+
+        content_subtree_cursor_free(depth_first);
+        content_subtree_cursor_free(breadth_first);
+        content_builder_free(handle);
+    }
+}
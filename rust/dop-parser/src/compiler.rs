@@ -10,12 +10,12 @@ use std::collections::HashMap;
 use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
 
 use crate::css_parser::Color;
-use crate::string_interner::StringId;
+use crate::string_interner::{StringId, StringPool};
 
 /// Content IR binary format magic number "CMMB"
 pub const MAGIC_NUMBER: u32 = 0x434D4D42;
 /// Current binary format version
-pub const FORMAT_VERSION: u32 = 1;
+pub const FORMAT_VERSION: u32 = 2;
 
 // ============================================================================
 // Node Types
@@ -36,6 +36,24 @@ pub enum NodeType {
     TextCluster = 8,
 }
 
+impl NodeType {
+    /// Convert a raw type tag into a NodeType (unknown values map to Root)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => NodeType::Root,
+            1 => NodeType::Stack,
+            2 => NodeType::Grid,
+            3 => NodeType::Scroll,
+            4 => NodeType::Rect,
+            5 => NodeType::Paragraph,
+            6 => NodeType::Span,
+            7 => NodeType::Link,
+            8 => NodeType::TextCluster,
+            _ => NodeType::Root,
+        }
+    }
+}
+
 // ============================================================================
 // Layout Properties
 // ============================================================================
@@ -111,8 +129,11 @@ impl NodeTable {
     }
     
     /// Create a new node and return its ID (1-indexed)
+    /// 
+    /// A parent that does not exist yet is stored as 0, leaving the node detached.
     pub fn create_node(&mut self, node_type: NodeType, parent: u32, style_id: u32) -> u32 {
         let id = self.node_types.len() as u32 + 1;
+        let parent = if parent < id { parent } else { 0 };
         
         self.node_types.push(node_type);
         self.parents.push(parent);
@@ -138,6 +159,73 @@ impl NodeTable {
         id
     }
     
+    /// Reserve capacity for at least `additional` more nodes
+    pub fn reserve(&mut self, additional: usize) {
+        self.node_types.reserve(additional);
+        self.parents.reserve(additional);
+        self.first_children.reserve(additional);
+        self.next_siblings.reserve(additional);
+        self.style_ids.reserve(additional);
+    }
+    
+    /// Create many nodes at once from parallel arrays and return the ID of the first one
+    /// 
+    /// Equivalent to calling `create_node` for each entry in order, but the last
+    /// child of every parent touched by the batch is remembered so appending
+    /// siblings does not rescan the child list. Parents may refer to nodes
+    /// created earlier in the same batch. Returns 0, creating nothing, if the
+    /// slices differ in length or a parent does not exist before its child.
+    pub fn create_nodes(&mut self, node_types: &[NodeType], parents: &[u32], style_ids: &[u32]) -> u32 {
+        if node_types.len() != parents.len() || node_types.len() != style_ids.len() {
+            return 0;
+        }
+        
+        let first_id = self.node_types.len() as u32 + 1;
+        // A node that is its own or a later node's child would make the tree cyclic
+        if parents.iter().enumerate().any(|(i, &parent)| parent >= first_id + i as u32) {
+            return 0;
+        }
+        self.reserve(node_types.len());
+        
+        let mut last_child: HashMap<u32, u32> = HashMap::new();
+        for i in 0..node_types.len() {
+            let id = self.node_types.len() as u32 + 1;
+            let parent = parents[i];
+            
+            self.node_types.push(node_types[i]);
+            self.parents.push(parent);
+            self.first_children.push(0);
+            self.next_siblings.push(0);
+            self.style_ids.push(style_ids[i]);
+            
+            if parent > 0 {
+                let parent_idx = parent as usize - 1;
+                let last = match last_child.get(&parent) {
+                    Some(&last) => last,
+                    None => {
+                        // First time this parent is seen in the batch: find its current last child
+                        let mut sibling = self.first_children[parent_idx];
+                        if sibling != 0 {
+                            while self.next_siblings[sibling as usize - 1] != 0 {
+                                sibling = self.next_siblings[sibling as usize - 1];
+                            }
+                        }
+                        sibling
+                    }
+                };
+                
+                if last == 0 {
+                    self.first_children[parent_idx] = id;
+                } else {
+                    self.next_siblings[last as usize - 1] = id;
+                }
+                last_child.insert(parent, id);
+            }
+        }
+        
+        first_id
+    }
+    
     /// Get children of a node
     pub fn get_children(&self, node_id: u32) -> Vec<u32> {
         if node_id == 0 || node_id > self.node_types.len() as u32 {
@@ -203,6 +291,39 @@ impl PropertyTable {
         Self::default()
     }
     
+    /// Reserve capacity for at least `additional` more nodes
+    pub fn reserve(&mut self, additional: usize) {
+        self.direction.reserve(additional);
+        self.pack.reserve(additional);
+        self.align.reserve(additional);
+        self.width.reserve(additional);
+        self.height.reserve(additional);
+        self.gap_row.reserve(additional);
+        self.gap_col.reserve(additional);
+        
+        self.inset_top.reserve(additional);
+        self.inset_right.reserve(additional);
+        self.inset_bottom.reserve(additional);
+        self.inset_left.reserve(additional);
+        
+        self.offset_top.reserve(additional);
+        self.offset_right.reserve(additional);
+        self.offset_bottom.reserve(additional);
+        self.offset_left.reserve(additional);
+        
+        self.fill_r.reserve(additional);
+        self.fill_g.reserve(additional);
+        self.fill_b.reserve(additional);
+        self.fill_a.reserve(additional);
+        
+        self.text_id.reserve(additional);
+        self.font_size.reserve(additional);
+        self.color_r.reserve(additional);
+        self.color_g.reserve(additional);
+        self.color_b.reserve(additional);
+        self.color_a.reserve(additional);
+    }
+    
     /// Resize all arrays to accommodate n nodes
     pub fn resize(&mut self, n: usize) {
         self.direction.resize(n, Direction::Down);
@@ -277,6 +398,19 @@ pub struct FlatStyle {
     
     pub round: f32,
     
+    pub width_unit: u8,
+    pub height_unit: u8,
+    pub _pad1: [u8; 2],
+    
+    /// Font size in pixels; 0 leaves the default
+    pub font_size: f32,
+    
+    /// Text color; alpha 0 leaves the default
+    pub text_color_r: u8,
+    pub text_color_g: u8,
+    pub text_color_b: u8,
+    pub text_color_a: u8,
+    
     pub checksum: u64,
 }
 
@@ -388,6 +522,15 @@ impl StyleTable {
                         flat.fill_a = c.a;
                     }
                     ("round", PropertyValue::Float(v)) => flat.round = *v,
+                    ("width_unit", PropertyValue::Int(u)) => flat.width_unit = *u as u8,
+                    ("height_unit", PropertyValue::Int(u)) => flat.height_unit = *u as u8,
+                    ("font_size", PropertyValue::Float(v)) => flat.font_size = *v,
+                    ("text_color", PropertyValue::Color(c)) => {
+                        flat.text_color_r = c.r;
+                        flat.text_color_g = c.g;
+                        flat.text_color_b = c.b;
+                        flat.text_color_a = c.a;
+                    }
                     _ => {}
                 }
             }
@@ -426,6 +569,8 @@ pub struct CompiledUnit {
     pub nodes: NodeTable,
     pub properties: PropertyTable,
     pub styles: Vec<FlatStyle>,
+    /// Strings referenced by `properties.text_id` (not part of the binary format)
+    pub strings: StringPool,
     pub environment_id: u32,
     pub version: u32,
     pub checksum: u64,
@@ -529,18 +674,7 @@ impl CompiledUnit {
                 return None;
             }
             
-            let node_type = match data[offset] {
-                0 => NodeType::Root,
-                1 => NodeType::Stack,
-                2 => NodeType::Grid,
-                3 => NodeType::Scroll,
-                4 => NodeType::Rect,
-                5 => NodeType::Paragraph,
-                6 => NodeType::Span,
-                7 => NodeType::Link,
-                8 => NodeType::TextCluster,
-                _ => NodeType::Root,
-            };
+            let node_type = NodeType::from_u8(data[offset]);
             offset += 1;
             
             let parent = u32::from_le_bytes(data[offset..offset+4].try_into().ok()?);
@@ -867,6 +1001,37 @@ mod tests {
         assert_eq!(children, vec![stack]);
     }
     
+    #[test]
+    fn test_create_nodes_bulk() {
+        let mut table = NodeTable::new();
+        let root = table.create_node(NodeType::Root, 0, 0);
+        table.create_node(NodeType::Rect, root, 0);
+        
+        // Two more root children plus a grandchild under the first new node
+        let first = table.create_nodes(
+            &[NodeType::Stack, NodeType::Stack, NodeType::Span],
+            &[root, root, 3],
+            &[1, 2, 3],
+        );
+        assert_eq!(first, 3);
+        assert_eq!(table.len(), 5);
+        assert_eq!(table.get_children(root), vec![2, 3, 4]);
+        assert_eq!(table.get_children(3), vec![5]);
+        assert_eq!(table.style_ids[4], 3);
+        
+        // Mismatched slices are rejected
+        assert_eq!(table.create_nodes(&[NodeType::Rect], &[], &[]), 0);
+        assert_eq!(table.len(), 5);
+        
+        // Parents must exist before their children, so no node becomes its own child
+        assert_eq!(table.create_nodes(&[NodeType::Rect, NodeType::Rect], &[root, 7], &[0, 0]), 0);
+        assert_eq!(table.create_nodes(&[NodeType::Rect], &[9], &[0]), 0);
+        assert_eq!(table.len(), 5);
+        let detached = table.create_node(NodeType::Rect, 6, 0);
+        assert_eq!(table.parents[detached as usize - 1], 0);
+        assert!(table.get_children(detached).is_empty());
+    }
+    
     #[test]
     fn test_binary_roundtrip() {
         let mut unit = CompiledUnit::new();
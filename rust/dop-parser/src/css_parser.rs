@@ -2,12 +2,14 @@
 //!
 //! Provides CSS parsing with support for:
 //! - Inline styles
-//! - Color parsing (hex, rgb, rgba, named colors)
-//! - Length parsing (px, %, em, mm, auto)
+//! - Color parsing (hex, rgb(a), hsl(a), CSS Color 4 named colors, system colors, currentColor)
+//! - Length parsing (absolute, font and viewport relative units, %, calc(), auto)
 //! - Comprehensive CSS property support
 
+use cssparser::color::{clamp_floor_256_f32, clamp_unit_f32, parse_hash_color};
 use cssparser::{Parser, ParserInput, Token as CssToken, ToCss};
 use std::collections::HashMap;
+use std::sync::RwLock;
 use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
 
 /// Position constants
@@ -24,6 +26,28 @@ pub const DISPLAY_TABLE: u8 = 3;
 pub const DISPLAY_TABLE_CELL: u8 = 4;
 pub const DISPLAY_TABLE_ROW: u8 = 5;
 pub const DISPLAY_INLINE_BLOCK: u8 = 6;
+pub const DISPLAY_FLEX: u8 = 7;
+pub const DISPLAY_INLINE_FLEX: u8 = 8;
+
+/// Flex direction constants
+pub const FLEX_DIRECTION_ROW: u8 = 0;
+pub const FLEX_DIRECTION_ROW_REVERSE: u8 = 1;
+pub const FLEX_DIRECTION_COLUMN: u8 = 2;
+pub const FLEX_DIRECTION_COLUMN_REVERSE: u8 = 3;
+
+/// Justify content constants
+pub const JUSTIFY_FLEX_START: u8 = 0;
+pub const JUSTIFY_FLEX_END: u8 = 1;
+pub const JUSTIFY_CENTER: u8 = 2;
+pub const JUSTIFY_SPACE_BETWEEN: u8 = 3;
+pub const JUSTIFY_SPACE_AROUND: u8 = 4;
+pub const JUSTIFY_SPACE_EVENLY: u8 = 5;
+
+/// Align items constants
+pub const ALIGN_FLEX_START: u8 = 0;
+pub const ALIGN_FLEX_END: u8 = 1;
+pub const ALIGN_CENTER: u8 = 2;
+pub const ALIGN_STRETCH: u8 = 3;
 
 /// Overflow constants
 pub const OVERFLOW_VISIBLE: u8 = 0;
@@ -132,6 +156,16 @@ pub struct CssStyles {
     pub line_height_normal: bool,
     pub font_size: f32,
     
+    // Flexbox
+    pub flex_direction: u8,
+    pub justify_content: u8,
+    pub align_items: u8,
+    pub row_gap: f32,
+    pub column_gap: f32,
+    pub flex_grow: f32,
+    pub flex_shrink: f32,
+    pub flex_basis: Length,
+    
     // Colors & content
     pub background_color: Color,
     pub color: Color,
@@ -185,6 +219,15 @@ impl Default for CssStyles {
             line_height_normal: true,
             font_size: 16.0,
             
+            flex_direction: FLEX_DIRECTION_ROW,
+            justify_content: JUSTIFY_FLEX_START,
+            align_items: ALIGN_STRETCH,
+            row_gap: 0.0,
+            column_gap: 0.0,
+            flex_grow: 0.0,
+            flex_shrink: 1.0,
+            flex_basis: Length::AUTO,
+            
             background_color: Color::TRANSPARENT,
             color: Color::BLACK,
             has_background: false,
@@ -192,153 +235,744 @@ impl Default for CssStyles {
     }
 }
 
-/// Named color lookup table
+/// CSS Color 4 named colors, sorted by name for binary search
+const NAMED_COLORS: &[(&str, [u8; 3])] = &[
+    ("aliceblue", [0xf0, 0xf8, 0xff]),
+    ("antiquewhite", [0xfa, 0xeb, 0xd7]),
+    ("aqua", [0x00, 0xff, 0xff]),
+    ("aquamarine", [0x7f, 0xff, 0xd4]),
+    ("azure", [0xf0, 0xff, 0xff]),
+    ("beige", [0xf5, 0xf5, 0xdc]),
+    ("bisque", [0xff, 0xe4, 0xc4]),
+    ("black", [0x00, 0x00, 0x00]),
+    ("blanchedalmond", [0xff, 0xeb, 0xcd]),
+    ("blue", [0x00, 0x00, 0xff]),
+    ("blueviolet", [0x8a, 0x2b, 0xe2]),
+    ("brown", [0xa5, 0x2a, 0x2a]),
+    ("burlywood", [0xde, 0xb8, 0x87]),
+    ("cadetblue", [0x5f, 0x9e, 0xa0]),
+    ("chartreuse", [0x7f, 0xff, 0x00]),
+    ("chocolate", [0xd2, 0x69, 0x1e]),
+    ("coral", [0xff, 0x7f, 0x50]),
+    ("cornflowerblue", [0x64, 0x95, 0xed]),
+    ("cornsilk", [0xff, 0xf8, 0xdc]),
+    ("crimson", [0xdc, 0x14, 0x3c]),
+    ("cyan", [0x00, 0xff, 0xff]),
+    ("darkblue", [0x00, 0x00, 0x8b]),
+    ("darkcyan", [0x00, 0x8b, 0x8b]),
+    ("darkgoldenrod", [0xb8, 0x86, 0x0b]),
+    ("darkgray", [0xa9, 0xa9, 0xa9]),
+    ("darkgreen", [0x00, 0x64, 0x00]),
+    ("darkgrey", [0xa9, 0xa9, 0xa9]),
+    ("darkkhaki", [0xbd, 0xb7, 0x6b]),
+    ("darkmagenta", [0x8b, 0x00, 0x8b]),
+    ("darkolivegreen", [0x55, 0x6b, 0x2f]),
+    ("darkorange", [0xff, 0x8c, 0x00]),
+    ("darkorchid", [0x99, 0x32, 0xcc]),
+    ("darkred", [0x8b, 0x00, 0x00]),
+    ("darksalmon", [0xe9, 0x96, 0x7a]),
+    ("darkseagreen", [0x8f, 0xbc, 0x8f]),
+    ("darkslateblue", [0x48, 0x3d, 0x8b]),
+    ("darkslategray", [0x2f, 0x4f, 0x4f]),
+    ("darkslategrey", [0x2f, 0x4f, 0x4f]),
+    ("darkturquoise", [0x00, 0xce, 0xd1]),
+    ("darkviolet", [0x94, 0x00, 0xd3]),
+    ("deeppink", [0xff, 0x14, 0x93]),
+    ("deepskyblue", [0x00, 0xbf, 0xff]),
+    ("dimgray", [0x69, 0x69, 0x69]),
+    ("dimgrey", [0x69, 0x69, 0x69]),
+    ("dodgerblue", [0x1e, 0x90, 0xff]),
+    ("firebrick", [0xb2, 0x22, 0x22]),
+    ("floralwhite", [0xff, 0xfa, 0xf0]),
+    ("forestgreen", [0x22, 0x8b, 0x22]),
+    ("fuchsia", [0xff, 0x00, 0xff]),
+    ("gainsboro", [0xdc, 0xdc, 0xdc]),
+    ("ghostwhite", [0xf8, 0xf8, 0xff]),
+    ("gold", [0xff, 0xd7, 0x00]),
+    ("goldenrod", [0xda, 0xa5, 0x20]),
+    ("gray", [0x80, 0x80, 0x80]),
+    ("green", [0x00, 0x80, 0x00]),
+    ("greenyellow", [0xad, 0xff, 0x2f]),
+    ("grey", [0x80, 0x80, 0x80]),
+    ("honeydew", [0xf0, 0xff, 0xf0]),
+    ("hotpink", [0xff, 0x69, 0xb4]),
+    ("indianred", [0xcd, 0x5c, 0x5c]),
+    ("indigo", [0x4b, 0x00, 0x82]),
+    ("ivory", [0xff, 0xff, 0xf0]),
+    ("khaki", [0xf0, 0xe6, 0x8c]),
+    ("lavender", [0xe6, 0xe6, 0xfa]),
+    ("lavenderblush", [0xff, 0xf0, 0xf5]),
+    ("lawngreen", [0x7c, 0xfc, 0x00]),
+    ("lemonchiffon", [0xff, 0xfa, 0xcd]),
+    ("lightblue", [0xad, 0xd8, 0xe6]),
+    ("lightcoral", [0xf0, 0x80, 0x80]),
+    ("lightcyan", [0xe0, 0xff, 0xff]),
+    ("lightgoldenrodyellow", [0xfa, 0xfa, 0xd2]),
+    ("lightgray", [0xd3, 0xd3, 0xd3]),
+    ("lightgreen", [0x90, 0xee, 0x90]),
+    ("lightgrey", [0xd3, 0xd3, 0xd3]),
+    ("lightpink", [0xff, 0xb6, 0xc1]),
+    ("lightsalmon", [0xff, 0xa0, 0x7a]),
+    ("lightseagreen", [0x20, 0xb2, 0xaa]),
+    ("lightskyblue", [0x87, 0xce, 0xfa]),
+    ("lightslategray", [0x77, 0x88, 0x99]),
+    ("lightslategrey", [0x77, 0x88, 0x99]),
+    ("lightsteelblue", [0xb0, 0xc4, 0xde]),
+    ("lightyellow", [0xff, 0xff, 0xe0]),
+    ("lime", [0x00, 0xff, 0x00]),
+    ("limegreen", [0x32, 0xcd, 0x32]),
+    ("linen", [0xfa, 0xf0, 0xe6]),
+    ("magenta", [0xff, 0x00, 0xff]),
+    ("maroon", [0x80, 0x00, 0x00]),
+    ("mediumaquamarine", [0x66, 0xcd, 0xaa]),
+    ("mediumblue", [0x00, 0x00, 0xcd]),
+    ("mediumorchid", [0xba, 0x55, 0xd3]),
+    ("mediumpurple", [0x93, 0x70, 0xdb]),
+    ("mediumseagreen", [0x3c, 0xb3, 0x71]),
+    ("mediumslateblue", [0x7b, 0x68, 0xee]),
+    ("mediumspringgreen", [0x00, 0xfa, 0x9a]),
+    ("mediumturquoise", [0x48, 0xd1, 0xcc]),
+    ("mediumvioletred", [0xc7, 0x15, 0x85]),
+    ("midnightblue", [0x19, 0x19, 0x70]),
+    ("mintcream", [0xf5, 0xff, 0xfa]),
+    ("mistyrose", [0xff, 0xe4, 0xe1]),
+    ("moccasin", [0xff, 0xe4, 0xb5]),
+    ("navajowhite", [0xff, 0xde, 0xad]),
+    ("navy", [0x00, 0x00, 0x80]),
+    ("oldlace", [0xfd, 0xf5, 0xe6]),
+    ("olive", [0x80, 0x80, 0x00]),
+    ("olivedrab", [0x6b, 0x8e, 0x23]),
+    ("orange", [0xff, 0xa5, 0x00]),
+    ("orangered", [0xff, 0x45, 0x00]),
+    ("orchid", [0xda, 0x70, 0xd6]),
+    ("palegoldenrod", [0xee, 0xe8, 0xaa]),
+    ("palegreen", [0x98, 0xfb, 0x98]),
+    ("paleturquoise", [0xaf, 0xee, 0xee]),
+    ("palevioletred", [0xdb, 0x70, 0x93]),
+    ("papayawhip", [0xff, 0xef, 0xd5]),
+    ("peachpuff", [0xff, 0xda, 0xb9]),
+    ("peru", [0xcd, 0x85, 0x3f]),
+    ("pink", [0xff, 0xc0, 0xcb]),
+    ("plum", [0xdd, 0xa0, 0xdd]),
+    ("powderblue", [0xb0, 0xe0, 0xe6]),
+    ("purple", [0x80, 0x00, 0x80]),
+    ("rebeccapurple", [0x66, 0x33, 0x99]),
+    ("red", [0xff, 0x00, 0x00]),
+    ("rosybrown", [0xbc, 0x8f, 0x8f]),
+    ("royalblue", [0x41, 0x69, 0xe1]),
+    ("saddlebrown", [0x8b, 0x45, 0x13]),
+    ("salmon", [0xfa, 0x80, 0x72]),
+    ("sandybrown", [0xf4, 0xa4, 0x60]),
+    ("seagreen", [0x2e, 0x8b, 0x57]),
+    ("seashell", [0xff, 0xf5, 0xee]),
+    ("sienna", [0xa0, 0x52, 0x2d]),
+    ("silver", [0xc0, 0xc0, 0xc0]),
+    ("skyblue", [0x87, 0xce, 0xeb]),
+    ("slateblue", [0x6a, 0x5a, 0xcd]),
+    ("slategray", [0x70, 0x80, 0x90]),
+    ("slategrey", [0x70, 0x80, 0x90]),
+    ("snow", [0xff, 0xfa, 0xfa]),
+    ("springgreen", [0x00, 0xff, 0x7f]),
+    ("steelblue", [0x46, 0x82, 0xb4]),
+    ("tan", [0xd2, 0xb4, 0x8c]),
+    ("teal", [0x00, 0x80, 0x80]),
+    ("thistle", [0xd8, 0xbf, 0xd8]),
+    ("tomato", [0xff, 0x63, 0x47]),
+    ("turquoise", [0x40, 0xe0, 0xd0]),
+    ("violet", [0xee, 0x82, 0xee]),
+    ("wheat", [0xf5, 0xde, 0xb3]),
+    ("white", [0xff, 0xff, 0xff]),
+    ("whitesmoke", [0xf5, 0xf5, 0xf5]),
+    ("yellow", [0xff, 0xff, 0x00]),
+    ("yellowgreen", [0x9a, 0xcd, 0x32]),
+];
+
+/// Colors used to resolve CSS system color keywords (`Canvas`, `LinkText`, ...)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColorTheme {
+    pub accent_color: Color,
+    pub accent_color_text: Color,
+    pub active_text: Color,
+    pub button_border: Color,
+    pub button_face: Color,
+    pub button_text: Color,
+    pub canvas: Color,
+    pub canvas_text: Color,
+    pub field: Color,
+    pub field_text: Color,
+    pub gray_text: Color,
+    pub highlight: Color,
+    pub highlight_text: Color,
+    pub link_text: Color,
+    pub mark: Color,
+    pub mark_text: Color,
+    pub selected_item: Color,
+    pub selected_item_text: Color,
+    pub visited_text: Color,
+}
+
+const fn rgb(hex: u32) -> Color {
+    Color { r: (hex >> 16) as u8, g: (hex >> 8) as u8, b: hex as u8, a: 255 }
+}
+
+impl ColorTheme {
+    pub const LIGHT: ColorTheme = ColorTheme {
+        accent_color: rgb(0x0075ff),
+        accent_color_text: rgb(0xffffff),
+        active_text: rgb(0xff0000),
+        button_border: rgb(0x767676),
+        button_face: rgb(0xefefef),
+        button_text: rgb(0x000000),
+        canvas: rgb(0xffffff),
+        canvas_text: rgb(0x000000),
+        field: rgb(0xffffff),
+        field_text: rgb(0x000000),
+        gray_text: rgb(0x6d6d6d),
+        highlight: rgb(0xb4d5fe),
+        highlight_text: rgb(0x000000),
+        link_text: rgb(0x0000ee),
+        mark: rgb(0xffff00),
+        mark_text: rgb(0x000000),
+        selected_item: rgb(0x0075ff),
+        selected_item_text: rgb(0xffffff),
+        visited_text: rgb(0x551a8b),
+    };
+
+    pub const DARK: ColorTheme = ColorTheme {
+        accent_color: rgb(0x99c8ff),
+        accent_color_text: rgb(0x000000),
+        active_text: rgb(0xff9e9e),
+        button_border: rgb(0x6b6b6b),
+        button_face: rgb(0x6b6b6b),
+        button_text: rgb(0xffffff),
+        canvas: rgb(0x121212),
+        canvas_text: rgb(0xffffff),
+        field: rgb(0x3b3b3b),
+        field_text: rgb(0xffffff),
+        gray_text: rgb(0x8e8e8e),
+        highlight: rgb(0x264f78),
+        highlight_text: rgb(0xffffff),
+        link_text: rgb(0x9e9eff),
+        mark: rgb(0x665c00),
+        mark_text: rgb(0xffffff),
+        selected_item: rgb(0x99c8ff),
+        selected_item_text: rgb(0x000000),
+        visited_text: rgb(0xd0adf0),
+    };
+
+    /// Look up a system color by its lowercased keyword
+    pub fn system_color(&self, name: &str) -> Option<Color> {
+        let color = match name {
+            "accentcolor" => self.accent_color,
+            "accentcolortext" => self.accent_color_text,
+            "activetext" => self.active_text,
+            "buttonborder" => self.button_border,
+            "buttonface" => self.button_face,
+            "buttontext" => self.button_text,
+            "canvas" => self.canvas,
+            "canvastext" => self.canvas_text,
+            "field" => self.field,
+            "fieldtext" => self.field_text,
+            "graytext" => self.gray_text,
+            "highlight" => self.highlight,
+            "highlighttext" => self.highlight_text,
+            "linktext" => self.link_text,
+            "mark" => self.mark,
+            "marktext" => self.mark_text,
+            "selecteditem" => self.selected_item,
+            "selecteditemtext" => self.selected_item_text,
+            "visitedtext" => self.visited_text,
+            _ => return None,
+        };
+        Some(color)
+    }
+
+    /// Mutable access to a system color by its lowercased keyword
+    pub fn system_color_mut(&mut self, name: &str) -> Option<&mut Color> {
+        let color = match name {
+            "accentcolor" => &mut self.accent_color,
+            "accentcolortext" => &mut self.accent_color_text,
+            "activetext" => &mut self.active_text,
+            "buttonborder" => &mut self.button_border,
+            "buttonface" => &mut self.button_face,
+            "buttontext" => &mut self.button_text,
+            "canvas" => &mut self.canvas,
+            "canvastext" => &mut self.canvas_text,
+            "field" => &mut self.field,
+            "fieldtext" => &mut self.field_text,
+            "graytext" => &mut self.gray_text,
+            "highlight" => &mut self.highlight,
+            "highlighttext" => &mut self.highlight_text,
+            "linktext" => &mut self.link_text,
+            "mark" => &mut self.mark,
+            "marktext" => &mut self.mark_text,
+            "selecteditem" => &mut self.selected_item,
+            "selecteditemtext" => &mut self.selected_item_text,
+            "visitedtext" => &mut self.visited_text,
+            _ => return None,
+        };
+        Some(color)
+    }
+}
+
+impl Default for ColorTheme {
+    fn default() -> Self {
+        Self::LIGHT
+    }
+}
+
+/// Theme used by `parse_color` for system colors
+static COLOR_THEME: RwLock<ColorTheme> = RwLock::new(ColorTheme::LIGHT);
+
+/// Get the theme system colors resolve against
+pub fn color_theme() -> ColorTheme {
+    *COLOR_THEME.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Replace the theme system colors resolve against
+pub fn set_color_theme(theme: ColorTheme) {
+    *COLOR_THEME.write().unwrap_or_else(|e| e.into_inner()) = theme;
+}
+
+/// Named and system color lookup (`name` must be lowercase)
 fn get_named_color(name: &str) -> Option<Color> {
-    match name.to_lowercase().as_str() {
-        "black" => Some(Color::new(0x00, 0x00, 0x00, 0xff)),
-        "white" => Some(Color::new(0xff, 0xff, 0xff, 0xff)),
-        "red" => Some(Color::new(0xff, 0x00, 0x00, 0xff)),
-        "green" => Some(Color::new(0x00, 0x80, 0x00, 0xff)),
-        "lime" => Some(Color::new(0x00, 0xff, 0x00, 0xff)),
-        "blue" => Some(Color::new(0x00, 0x00, 0xff, 0xff)),
-        "yellow" => Some(Color::new(0xff, 0xff, 0x00, 0xff)),
-        "cyan" | "aqua" => Some(Color::new(0x00, 0xff, 0xff, 0xff)),
-        "magenta" | "fuchsia" => Some(Color::new(0xff, 0x00, 0xff, 0xff)),
-        "gray" | "grey" => Some(Color::new(0x80, 0x80, 0x80, 0xff)),
-        "transparent" => Some(Color::TRANSPARENT),
-        "orange" => Some(Color::new(0xff, 0xa5, 0x00, 0xff)),
-        "purple" => Some(Color::new(0x80, 0x00, 0x80, 0xff)),
-        "navy" => Some(Color::new(0x00, 0x00, 0x80, 0xff)),
-        "maroon" => Some(Color::new(0x80, 0x00, 0x00, 0xff)),
-        "olive" => Some(Color::new(0x80, 0x80, 0x00, 0xff)),
-        "teal" => Some(Color::new(0x00, 0x80, 0x80, 0xff)),
-        "silver" => Some(Color::new(0xc0, 0xc0, 0xc0, 0xff)),
-        _ => None,
+    if name == "transparent" {
+        return Some(Color::TRANSPARENT);
+    }
+    match NAMED_COLORS.binary_search_by_key(&name, |(n, _)| n) {
+        Ok(idx) => {
+            let [r, g, b] = NAMED_COLORS[idx].1;
+            Some(Color::new(r, g, b, 0xff))
+        }
+        Err(_) => color_theme().system_color(name),
     }
 }
 
 /// Parse a CSS color value
+///
+/// `currentColor` resolves to the theme's `CanvasText`; use
+/// [`parse_color_with`] when the element's `color` is known.
 pub fn parse_color(value: &str) -> Color {
+    parse_color_with(value, color_theme().canvas_text)
+}
+
+/// Parse a CSS color value, resolving `currentColor` to `current`
+///
+/// Supports named, system and hex colors and the `rgb()`, `rgba()`, `hsl()`
+/// and `hsla()` functions in both comma-separated and space-separated
+/// (`rgb(255 0 0 / 50%)`) syntax. Invalid colors are transparent.
+pub fn parse_color_with(value: &str, current: Color) -> Color {
     let value = value.trim().to_lowercase();
+
+    if value == "currentcolor" {
+        return current;
+    }
     
-    // Named colors
+    // Named and system colors
     if let Some(color) = get_named_color(&value) {
         return color;
     }
     
-    // Hex colors
-    if value.starts_with('#') {
-        let hex = &value[1..];
-        if hex.len() == 3 {
-            // #rgb -> #rrggbb
-            let r = u8::from_str_radix(&hex[0..1], 16).unwrap_or(0) * 17;
-            let g = u8::from_str_radix(&hex[1..2], 16).unwrap_or(0) * 17;
-            let b = u8::from_str_radix(&hex[2..3], 16).unwrap_or(0) * 17;
-            return Color::new(r, g, b, 255);
-        } else if hex.len() == 6 {
-            let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or(0);
-            let g = u8::from_str_radix(&hex[2..4], 16).unwrap_or(0);
-            let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or(0);
-            return Color::new(r, g, b, 255);
-        } else if hex.len() == 8 {
-            let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or(0);
-            let g = u8::from_str_radix(&hex[2..4], 16).unwrap_or(0);
-            let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or(0);
-            let a = u8::from_str_radix(&hex[6..8], 16).unwrap_or(255);
-            return Color::new(r, g, b, a);
-        }
-    }
-    
-    // rgb() and rgba()
-    if value.starts_with("rgb") {
-        // Extract numbers using regex-like parsing
-        let numbers: Vec<&str> = value
-            .trim_start_matches("rgba")
-            .trim_start_matches("rgb")
-            .trim_start_matches('(')
-            .trim_end_matches(')')
-            .split(|c| c == ',' || c == ' ')
-            .filter(|s| !s.is_empty())
-            .collect();
-        
-        if numbers.len() >= 3 {
-            let r = numbers[0].trim().parse::<u8>().unwrap_or(0);
-            let g = numbers[1].trim().parse::<u8>().unwrap_or(0);
-            let b = numbers[2].trim().parse::<u8>().unwrap_or(0);
-            let a = if numbers.len() >= 4 {
-                let alpha = numbers[3].trim().parse::<f32>().unwrap_or(1.0);
-                (alpha * 255.0) as u8
-            } else {
-                255
-            };
-            return Color::new(r, g, b, a);
+    // Hex colors (#rgb, #rgba, #rrggbb, #rrggbbaa)
+    if let Some(hex) = value.strip_prefix('#') {
+        return match parse_hash_color(hex.as_bytes()) {
+            Ok((r, g, b, a)) => Color::new(r, g, b, clamp_unit_f32(a)),
+            Err(()) => Color::TRANSPARENT,
+        };
+    }
+    
+    parse_color_function(&value).unwrap_or(Color::TRANSPARENT)
+}
+
+/// A component of a color function
+#[derive(Clone, Copy)]
+enum ColorArg {
+    Number(f32),
+    /// Percentage as a fraction (50% is 0.5)
+    Percentage(f32),
+    /// Angle in degrees
+    Angle(f32),
+    /// The `none` keyword, which acts as zero
+    None,
+}
+
+/// Parse `rgb()`, `rgba()`, `hsl()` or `hsla()`
+fn parse_color_function(value: &str) -> Option<Color> {
+    let mut input = ParserInput::new(value);
+    let mut parser = Parser::new(&mut input);
+    let name = match parser.next() {
+        Ok(CssToken::Function(name)) => name.to_string(),
+        _ => return None,
+    };
+    
+    // Commas and the slash before alpha are optional separators here
+    let args: Result<Vec<ColorArg>, cssparser::ParseError<'_, ()>> = parser.parse_nested_block(|parser| {
+        let mut args = Vec::new();
+        while let Ok(token) = parser.next() {
+            args.push(match *token {
+                CssToken::Number { value, .. } => ColorArg::Number(value),
+                CssToken::Percentage { unit_value, .. } => ColorArg::Percentage(unit_value),
+                CssToken::Dimension { value, ref unit, .. } => match unit.to_ascii_lowercase().as_str() {
+                    "deg" => ColorArg::Angle(value),
+                    "rad" => ColorArg::Angle(value.to_degrees()),
+                    "grad" => ColorArg::Angle(value * 0.9),
+                    "turn" => ColorArg::Angle(value * 360.0),
+                    _ => return Err(parser.new_custom_error(())),
+                },
+                CssToken::Ident(ref ident) if ident.eq_ignore_ascii_case("none") => ColorArg::None,
+                CssToken::Comma | CssToken::Delim('/') => continue,
+                _ => return Err(parser.new_custom_error(())),
+            });
         }
+        Ok(args)
+    });
+    let args = args.ok()?;
+    if !parser.is_exhausted() {
+        return None;
     }
     
-    Color::TRANSPARENT
+    let (first, second, third, alpha) = match *args.as_slice() {
+        [a, b, c] => (a, b, c, None),
+        [a, b, c, d] => (a, b, c, Some(d)),
+        _ => return None,
+    };
+    let alpha = match alpha {
+        None => 255,
+        Some(ColorArg::Number(v) | ColorArg::Percentage(v)) => clamp_unit_f32(v),
+        Some(ColorArg::None) => 0,
+        Some(ColorArg::Angle(_)) => return None,
+    };
+    
+    match name.as_str() {
+        "rgb" | "rgba" => {
+            let channel = |arg| match arg {
+                ColorArg::Number(v) => Some(clamp_floor_256_f32(v)),
+                ColorArg::Percentage(v) => Some(clamp_unit_f32(v)),
+                ColorArg::None => Some(0),
+                ColorArg::Angle(_) => None,
+            };
+            Some(Color::new(channel(first)?, channel(second)?, channel(third)?, alpha))
+        }
+        "hsl" | "hsla" => {
+            let hue = match first {
+                ColorArg::Number(v) | ColorArg::Angle(v) => v,
+                ColorArg::None => 0.0,
+                ColorArg::Percentage(_) => return None,
+            };
+            // Saturation and lightness may be bare numbers in the modern syntax
+            let fraction = |arg| match arg {
+                ColorArg::Percentage(v) => Some(v.clamp(0.0, 1.0)),
+                ColorArg::Number(v) => Some((v / 100.0).clamp(0.0, 1.0)),
+                ColorArg::None => Some(0.0),
+                ColorArg::Angle(_) => None,
+            };
+            let (r, g, b) = hsl_to_rgb(hue, fraction(second)?, fraction(third)?);
+            Some(Color::new(clamp_unit_f32(r), clamp_unit_f32(g), clamp_unit_f32(b), alpha))
+        }
+        _ => None,
+    }
+}
+
+/// Convert HSL (hue in degrees, saturation and lightness in 0..1) to RGB in 0..1
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> (f32, f32, f32) {
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    (r + m, g + m, b + m)
+}
+
+/// Font and viewport sizes that relative lengths resolve against
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LengthContext {
+    /// Element font size, for `em`, `ex` and `ch`
+    pub font_size: f32,
+    /// Root element font size, for `rem`
+    pub root_font_size: f32,
+    /// Viewport size, for `vw`, `vh`, `vmin` and `vmax`
+    pub viewport_w: f32,
+    pub viewport_h: f32,
+}
+
+impl Default for LengthContext {
+    fn default() -> Self {
+        Self {
+            font_size: 16.0,
+            root_font_size: 16.0,
+            viewport_w: 800.0,
+            viewport_h: 600.0,
+        }
+    }
+}
+
+/// Parse a CSS length value against the default context (16px fonts, 800x600 viewport)
+pub fn parse_length(value: &str, container_size: f32) -> Length {
+    parse_length_ctx(value, container_size, &LengthContext::default())
 }
 
 /// Parse a CSS length value
-pub fn parse_length(value: &str, _container_size: f32) -> Length {
+///
+/// Percentages resolve against `container_size`; font and viewport relative
+/// units against `ctx`. Unitless numbers are pixels. Unknown units are auto.
+pub fn parse_length_ctx(value: &str, container_size: f32, ctx: &LengthContext) -> Length {
     let value = value.trim().to_lowercase();
     
     if value == "auto" {
         return Length::AUTO;
     }
     
-    // Percentage
-    if value.ends_with('%') {
-        if let Ok(num) = value[..value.len()-1].parse::<f32>() {
-            return Length::px(num / 100.0 * _container_size);
-        }
+    if value.starts_with("calc(") {
+        return parse_calc(&value, ctx)
+            .and_then(|expr| expr.resolve(container_size))
+            .map_or(Length::AUTO, Length::px);
     }
     
-    // Pixels (default unit)
-    let num_str = value.trim_end_matches("px");
-    if let Ok(num) = num_str.parse::<f32>() {
-        return Length::px(num);
+    let unit_start = value.trim_end_matches(|c: char| c.is_ascii_alphabetic() || c == '%').len();
+    let Ok(num) = value[..unit_start].parse::<f32>() else {
+        return Length::AUTO;
+    };
+    
+    match &value[unit_start..] {
+        "%" => Length::px(num * container_size / 100.0),
+        unit => unit_to_px(unit, ctx).map_or(Length::AUTO, |px| Length::px(num * px)),
+    }
+}
+
+/// Get the size of one `unit` in pixels (empty for unitless numbers)
+fn unit_to_px(unit: &str, ctx: &LengthContext) -> Option<f32> {
+    // Absolute units at 96 DPI
+    Some(match unit {
+        "" | "px" => 1.0,
+        "em" => ctx.font_size,
+        "rem" => ctx.root_font_size,
+        // Without font metrics, `ex` and `ch` use the usual half-em fallback
+        "ex" | "ch" => ctx.font_size * 0.5,
+        "vw" => ctx.viewport_w / 100.0,
+        "vh" => ctx.viewport_h / 100.0,
+        "vmin" => ctx.viewport_w.min(ctx.viewport_h) / 100.0,
+        "vmax" => ctx.viewport_w.max(ctx.viewport_h) / 100.0,
+        "in" => 96.0,
+        "cm" => 96.0 / 2.54,
+        "mm" => 96.0 / 25.4,
+        "q" => 96.0 / 101.6,
+        "pt" => 96.0 / 72.0,
+        "pc" => 16.0,
+        _ => return None,
+    })
+}
+
+/// A `calc()` expression
+///
+/// Font and viewport relative units are resolved to pixels when parsing;
+/// percentages stay symbolic until the container size is known.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CalcExpr {
+    Number(f32),
+    /// Length in pixels
+    Length(f32),
+    /// Percentage of the container size (50% is 50.0)
+    Percentage(f32),
+    Add(Box<CalcExpr>, Box<CalcExpr>),
+    Sub(Box<CalcExpr>, Box<CalcExpr>),
+    Mul(Box<CalcExpr>, Box<CalcExpr>),
+    Div(Box<CalcExpr>, Box<CalcExpr>),
+}
+
+impl CalcExpr {
+    /// Resolve to pixels against the container size
+    ///
+    /// Returns None for type errors (`10px * 2px`, `1 + 2px`) and division by
+    /// zero. A unitless result counts as pixels, like unitless lengths.
+    pub fn resolve(&self, container_size: f32) -> Option<f32> {
+        self.resolve_typed(container_size).map(|(value, _)| value)
     }
     
-    // em units (assume 16px base)
-    if value.ends_with("em") {
-        if let Ok(num) = value[..value.len()-2].parse::<f32>() {
-            return Length::px(num * 16.0);
+    /// Resolve to (value, is_length)
+    fn resolve_typed(&self, container_size: f32) -> Option<(f32, bool)> {
+        Some(match self {
+            CalcExpr::Number(v) => (*v, false),
+            CalcExpr::Length(v) => (*v, true),
+            CalcExpr::Percentage(v) => (v * container_size / 100.0, true),
+            CalcExpr::Add(a, b) | CalcExpr::Sub(a, b) => {
+                let (a, a_len) = a.resolve_typed(container_size)?;
+                let (b, b_len) = b.resolve_typed(container_size)?;
+                if a_len != b_len {
+                    return None;
+                }
+                (if matches!(self, CalcExpr::Add(..)) { a + b } else { a - b }, a_len)
+            }
+            CalcExpr::Mul(a, b) => {
+                let (a, a_len) = a.resolve_typed(container_size)?;
+                let (b, b_len) = b.resolve_typed(container_size)?;
+                if a_len && b_len {
+                    return None;
+                }
+                (a * b, a_len || b_len)
+            }
+            CalcExpr::Div(a, b) => {
+                let (a, a_len) = a.resolve_typed(container_size)?;
+                let (b, b_len) = b.resolve_typed(container_size)?;
+                if b_len || b == 0.0 {
+                    return None;
+                }
+                (a / b, a_len)
+            }
+        })
+    }
+}
+
+/// Parse a `calc()` expression, resolving relative units against `ctx`
+///
+/// Supports `+`, `-`, `*`, `/`, parentheses and nested `calc()`.
+pub fn parse_calc(value: &str, ctx: &LengthContext) -> Option<CalcExpr> {
+    let mut input = ParserInput::new(value);
+    let mut parser = Parser::new(&mut input);
+    match parser.next() {
+        Ok(CssToken::Function(name)) if name.eq_ignore_ascii_case("calc") => {}
+        _ => return None,
+    }
+    let expr: Result<CalcExpr, cssparser::ParseError<'_, ()>> = parser.parse_nested_block(|parser| {
+        let expr = parse_calc_sum(parser, ctx)?;
+        parser.expect_exhausted()?;
+        Ok(expr)
+    });
+    let expr = expr.ok()?;
+    parser.is_exhausted().then_some(expr)
+}
+
+fn parse_calc_sum<'i>(parser: &mut Parser<'i, '_>, ctx: &LengthContext) -> Result<CalcExpr, cssparser::ParseError<'i, ()>> {
+    let mut expr = parse_calc_product(parser, ctx)?;
+    loop {
+        let state = parser.state();
+        match parser.next() {
+            Ok(CssToken::Delim('+')) => expr = CalcExpr::Add(Box::new(expr), Box::new(parse_calc_product(parser, ctx)?)),
+            Ok(CssToken::Delim('-')) => expr = CalcExpr::Sub(Box::new(expr), Box::new(parse_calc_product(parser, ctx)?)),
+            _ => {
+                parser.reset(&state);
+                return Ok(expr);
+            }
         }
     }
-    
-    // mm units (1mm = 3.7795275591 pixels at 96 DPI)
-    if value.ends_with("mm") {
-        if let Ok(num) = value[..value.len()-2].parse::<f32>() {
-            return Length::px(num * 3.7795275591);
+}
+
+fn parse_calc_product<'i>(parser: &mut Parser<'i, '_>, ctx: &LengthContext) -> Result<CalcExpr, cssparser::ParseError<'i, ()>> {
+    let mut expr = parse_calc_value(parser, ctx)?;
+    loop {
+        let state = parser.state();
+        match parser.next() {
+            Ok(CssToken::Delim('*')) => expr = CalcExpr::Mul(Box::new(expr), Box::new(parse_calc_value(parser, ctx)?)),
+            Ok(CssToken::Delim('/')) => expr = CalcExpr::Div(Box::new(expr), Box::new(parse_calc_value(parser, ctx)?)),
+            _ => {
+                parser.reset(&state);
+                return Ok(expr);
+            }
         }
     }
-    
-    Length::AUTO
+}
+
+fn parse_calc_value<'i>(parser: &mut Parser<'i, '_>, ctx: &LengthContext) -> Result<CalcExpr, cssparser::ParseError<'i, ()>> {
+    let token = parser.next()?.clone();
+    match token {
+        CssToken::Number { value, .. } => Ok(CalcExpr::Number(value)),
+        CssToken::Percentage { unit_value, .. } => Ok(CalcExpr::Percentage(unit_value * 100.0)),
+        CssToken::Dimension { value, ref unit, .. } => match unit_to_px(&unit.to_ascii_lowercase(), ctx) {
+            Some(px) => Ok(CalcExpr::Length(value * px)),
+            None => Err(parser.new_custom_error(())),
+        },
+        CssToken::ParenthesisBlock => parser.parse_nested_block(|parser| {
+            let expr = parse_calc_sum(parser, ctx)?;
+            parser.expect_exhausted()?;
+            Ok(expr)
+        }),
+        CssToken::Function(ref name) if name.eq_ignore_ascii_case("calc") => parser.parse_nested_block(|parser| {
+            let expr = parse_calc_sum(parser, ctx)?;
+            parser.expect_exhausted()?;
+            Ok(expr)
+        }),
+        _ => Err(parser.new_custom_error(())),
+    }
+}
+
+/// Split a value on whitespace outside parentheses, so `calc(1px + 2px)` and
+/// `rgb(0, 0, 0)` stay whole
+fn split_values(val: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = None;
+    for (i, c) in val.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            c if c.is_whitespace() && depth == 0 => {
+                if let Some(s) = start.take() {
+                    parts.push(&val[s..i]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(s) = start {
+        parts.push(&val[s..]);
+    }
+    parts
 }
 
 /// Parse inline style string into CssStyles
 pub fn parse_inline_style(style_str: &str) -> CssStyles {
     let mut styles = CssStyles::default();
-    
-    // Split by semicolon and process each declaration
-    for decl in style_str.split(';') {
-        let decl = decl.trim();
-        if decl.is_empty() {
-            continue;
-        }
-        
-        if let Some(colon_idx) = decl.find(':') {
+    apply_declarations(&mut styles, &parse_declarations(style_str));
+    styles
+}
+
+/// Split a declaration list by semicolon into (lowercase property, value) pairs
+pub fn parse_declarations(style_str: &str) -> Vec<(String, &str)> {
+    style_str
+        .split(';')
+        .filter_map(|decl| {
+            let colon_idx = decl.find(':')?;
             let prop = decl[..colon_idx].trim().to_lowercase();
-            let val = decl[colon_idx + 1..].trim();
-            apply_property(&mut styles, &prop, val);
-        }
+            Some((prop, decl[colon_idx + 1..].trim()))
+        })
+        .collect()
+}
+
+/// Apply declarations in order, so later ones win
+pub fn apply_declarations(styles: &mut CssStyles, decls: &[(String, &str)]) {
+    apply_declarations_ctx(styles, decls, &LengthContext::default());
+}
+
+/// Apply declarations in order, resolving lengths against `ctx`
+///
+/// `ctx.font_size` is the parent's font size: `font-size` resolves against
+/// it, and other properties against the element's own font size.
+pub fn apply_declarations_ctx(styles: &mut CssStyles, decls: &[(String, &str)], ctx: &LengthContext) {
+    // `color` goes first so `currentColor` in other properties sees its final
+    // value, then `font-size` so `em` does
+    for (prop, val) in decls.iter().filter(|(p, _)| p == "color") {
+        apply_property(styles, prop, val, ctx);
+    }
+    let mut element_ctx = *ctx;
+    for (prop, val) in decls.iter().filter(|(p, _)| p == "font-size") {
+        apply_property(styles, prop, val, ctx);
+        element_ctx.font_size = styles.font_size;
+    }
+    for (prop, val) in decls.iter().filter(|(p, _)| p != "color" && p != "font-size") {
+        apply_property(styles, prop, val, &element_ctx);
     }
-    
-    styles
 }
 
 /// Apply a CSS property to styles
-fn apply_property(styles: &mut CssStyles, prop: &str, val: &str) {
+fn apply_property(styles: &mut CssStyles, prop: &str, val: &str, ctx: &LengthContext) {
     let val_lower = val.to_lowercase();
     
     match prop {
@@ -361,6 +995,8 @@ fn apply_property(styles: &mut CssStyles, prop: &str, val: &str) {
                 "table-cell" => DISPLAY_TABLE_CELL,
                 "table-row" => DISPLAY_TABLE_ROW,
                 "inline-block" => DISPLAY_INLINE_BLOCK,
+                "flex" => DISPLAY_FLEX,
+                "inline-flex" => DISPLAY_INLINE_FLEX,
                 _ => DISPLAY_BLOCK,
             };
         }
@@ -378,37 +1014,37 @@ fn apply_property(styles: &mut CssStyles, prop: &str, val: &str) {
         }
         
         "background-color" | "background" => {
-            let color = parse_color(val);
+            let color = parse_color_with(val, styles.color);
             styles.background_color = color;
             styles.has_background = color.a > 0;
         }
         
         "color" => {
-            styles.color = parse_color(val);
+            styles.color = parse_color_with(val, styles.color);
         }
         
         "width" => {
-            styles.width = parse_length(val, 0.0);
+            styles.width = parse_length_ctx(val, 0.0, ctx);
         }
         
         "height" => {
-            styles.height = parse_length(val, 0.0);
+            styles.height = parse_length_ctx(val, 0.0, ctx);
         }
         
         "top" => {
-            styles.top = parse_length(val, 0.0);
+            styles.top = parse_length_ctx(val, 0.0, ctx);
         }
         
         "right" => {
-            styles.right = parse_length(val, 0.0);
+            styles.right = parse_length_ctx(val, 0.0, ctx);
         }
         
         "bottom" => {
-            styles.bottom = parse_length(val, 0.0);
+            styles.bottom = parse_length_ctx(val, 0.0, ctx);
         }
         
         "left" => {
-            styles.left = parse_length(val, 0.0);
+            styles.left = parse_length_ctx(val, 0.0, ctx);
         }
         
         "z-index" => {
@@ -418,7 +1054,7 @@ fn apply_property(styles: &mut CssStyles, prop: &str, val: &str) {
         }
         
         "margin" => {
-            let values = parse_margin_shorthand(val);
+            let values = parse_margin_shorthand(val, ctx);
             styles.margin_top = values.0;
             styles.margin_right = values.1;
             styles.margin_bottom = values.2;
@@ -426,23 +1062,23 @@ fn apply_property(styles: &mut CssStyles, prop: &str, val: &str) {
         }
         
         "margin-top" => {
-            styles.margin_top = parse_length(val, 0.0).value;
+            styles.margin_top = parse_length_ctx(val, 0.0, ctx).value;
         }
         
         "margin-right" => {
-            styles.margin_right = parse_length(val, 0.0).value;
+            styles.margin_right = parse_length_ctx(val, 0.0, ctx).value;
         }
         
         "margin-bottom" => {
-            styles.margin_bottom = parse_length(val, 0.0).value;
+            styles.margin_bottom = parse_length_ctx(val, 0.0, ctx).value;
         }
         
         "margin-left" => {
-            styles.margin_left = parse_length(val, 0.0).value;
+            styles.margin_left = parse_length_ctx(val, 0.0, ctx).value;
         }
         
         "padding" => {
-            let values = parse_margin_shorthand(val);
+            let values = parse_margin_shorthand(val, ctx);
             styles.padding_top = values.0;
             styles.padding_right = values.1;
             styles.padding_bottom = values.2;
@@ -450,19 +1086,19 @@ fn apply_property(styles: &mut CssStyles, prop: &str, val: &str) {
         }
         
         "padding-top" => {
-            styles.padding_top = parse_length(val, 0.0).value;
+            styles.padding_top = parse_length_ctx(val, 0.0, ctx).value;
         }
         
         "padding-right" => {
-            styles.padding_right = parse_length(val, 0.0).value;
+            styles.padding_right = parse_length_ctx(val, 0.0, ctx).value;
         }
         
         "padding-bottom" => {
-            styles.padding_bottom = parse_length(val, 0.0).value;
+            styles.padding_bottom = parse_length_ctx(val, 0.0, ctx).value;
         }
         
         "padding-left" => {
-            styles.padding_left = parse_length(val, 0.0).value;
+            styles.padding_left = parse_length_ctx(val, 0.0, ctx).value;
         }
         
         "float" => {
@@ -483,39 +1119,39 @@ fn apply_property(styles: &mut CssStyles, prop: &str, val: &str) {
         }
         
         "min-width" => {
-            let len = parse_length(val, 0.0);
+            let len = parse_length_ctx(val, 0.0, ctx);
             if !len.is_auto {
                 styles.min_width = len;
             }
         }
         
         "max-width" => {
-            let len = parse_length(val, 0.0);
+            let len = parse_length_ctx(val, 0.0, ctx);
             if !len.is_auto {
                 styles.max_width = len;
             }
         }
         
         "min-height" => {
-            let len = parse_length(val, 0.0);
+            let len = parse_length_ctx(val, 0.0, ctx);
             if !len.is_auto {
                 styles.min_height = len;
             }
         }
         
         "max-height" => {
-            let len = parse_length(val, 0.0);
+            let len = parse_length_ctx(val, 0.0, ctx);
             if !len.is_auto {
                 styles.max_height = len;
             }
         }
         
         "border" => {
-            parse_border_shorthand(val, styles);
+            parse_border_shorthand(val, styles, ctx);
         }
         
         "border-width" => {
-            let values = parse_margin_shorthand(val);
+            let values = parse_margin_shorthand(val, ctx);
             styles.border_top_width = values.0;
             styles.border_right_width = values.1;
             styles.border_bottom_width = values.2;
@@ -531,7 +1167,7 @@ fn apply_property(styles: &mut CssStyles, prop: &str, val: &str) {
         }
         
         "border-color" => {
-            let color = parse_color(val);
+            let color = parse_color_with(val, styles.color);
             styles.border_top_color = color;
             styles.border_right_color = color;
             styles.border_bottom_color = color;
@@ -542,7 +1178,7 @@ fn apply_property(styles: &mut CssStyles, prop: &str, val: &str) {
             if val_lower == "normal" {
                 styles.line_height_normal = true;
             } else {
-                let len = parse_length(val, 0.0);
+                let len = parse_length_ctx(val, 0.0, ctx);
                 if !len.is_auto {
                     styles.line_height = len.value;
                     styles.line_height_normal = false;
@@ -551,22 +1187,90 @@ fn apply_property(styles: &mut CssStyles, prop: &str, val: &str) {
         }
         
         "font-size" => {
-            let len = parse_length(val, 0.0);
+            let len = parse_length_ctx(val, ctx.font_size, ctx);
             if !len.is_auto {
                 styles.font_size = len.value;
             }
         }
         
+        "flex-direction" => {
+            styles.flex_direction = match val_lower.as_str() {
+                "row-reverse" => FLEX_DIRECTION_ROW_REVERSE,
+                "column" => FLEX_DIRECTION_COLUMN,
+                "column-reverse" => FLEX_DIRECTION_COLUMN_REVERSE,
+                _ => FLEX_DIRECTION_ROW,
+            };
+        }
+        
+        "justify-content" => {
+            styles.justify_content = match val_lower.as_str() {
+                "flex-end" | "end" | "right" => JUSTIFY_FLEX_END,
+                "center" => JUSTIFY_CENTER,
+                "space-between" => JUSTIFY_SPACE_BETWEEN,
+                "space-around" => JUSTIFY_SPACE_AROUND,
+                "space-evenly" => JUSTIFY_SPACE_EVENLY,
+                _ => JUSTIFY_FLEX_START,
+            };
+        }
+        
+        "align-items" => {
+            styles.align_items = match val_lower.as_str() {
+                "flex-start" | "start" | "baseline" => ALIGN_FLEX_START,
+                "flex-end" | "end" => ALIGN_FLEX_END,
+                "center" => ALIGN_CENTER,
+                _ => ALIGN_STRETCH,
+            };
+        }
+        
+        "gap" | "grid-gap" => {
+            let values: Vec<f32> = split_values(val).into_iter().map(|v| parse_length_ctx(v, 0.0, ctx).value).collect();
+            if let [row, col] | [row @ col] = values.as_slice() {
+                styles.row_gap = *row;
+                styles.column_gap = *col;
+            }
+        }
+        
+        "row-gap" => {
+            styles.row_gap = parse_length_ctx(val, 0.0, ctx).value;
+        }
+        
+        "column-gap" => {
+            styles.column_gap = parse_length_ctx(val, 0.0, ctx).value;
+        }
+        
+        "flex" => {
+            let (grow, shrink, basis) = parse_flex_shorthand(&val_lower, ctx);
+            styles.flex_grow = grow;
+            styles.flex_shrink = shrink;
+            styles.flex_basis = basis;
+        }
+        
+        "flex-grow" => {
+            if let Ok(grow) = val.parse::<f32>() {
+                styles.flex_grow = grow.max(0.0);
+            }
+        }
+        
+        "flex-shrink" => {
+            if let Ok(shrink) = val.parse::<f32>() {
+                styles.flex_shrink = shrink.max(0.0);
+            }
+        }
+        
+        "flex-basis" => {
+            styles.flex_basis = parse_length_ctx(val, 0.0, ctx);
+        }
+        
         _ => {}
     }
 }
 
 /// Parse margin/padding shorthand (1-4 values) into top, right, bottom, left
-fn parse_margin_shorthand(val: &str) -> (f32, f32, f32, f32) {
-    let parts: Vec<&str> = val.split_whitespace().collect();
+fn parse_margin_shorthand(val: &str, ctx: &LengthContext) -> (f32, f32, f32, f32) {
+    let parts = split_values(val);
     let values: Vec<f32> = parts
         .iter()
-        .map(|p| parse_length(p, 0.0).value)
+        .map(|p| parse_length_ctx(p, 0.0, ctx).value)
         .collect();
     
     match values.len() {
@@ -578,6 +1282,33 @@ fn parse_margin_shorthand(val: &str) -> (f32, f32, f32, f32) {
     }
 }
 
+/// Parse flex shorthand into grow, shrink and basis
+///
+/// The first two unitless numbers are grow and shrink; anything else is the
+/// basis, which becomes 0 when omitted.
+fn parse_flex_shorthand(val: &str, ctx: &LengthContext) -> (f32, f32, Length) {
+    match val.trim() {
+        "none" => return (0.0, 0.0, Length::AUTO),
+        "auto" => return (1.0, 1.0, Length::AUTO),
+        "initial" => return (0.0, 1.0, Length::AUTO),
+        _ => {}
+    }
+    
+    let mut factors = Vec::new();
+    let mut basis = None;
+    for part in split_values(val) {
+        match part.parse::<f32>() {
+            Ok(factor) if factors.len() < 2 => factors.push(factor.max(0.0)),
+            _ => basis = Some(parse_length_ctx(part, 0.0, ctx)),
+        }
+    }
+    (
+        factors.first().copied().unwrap_or(1.0),
+        factors.get(1).copied().unwrap_or(1.0),
+        basis.unwrap_or(Length::px(0.0)),
+    )
+}
+
 /// Parse border style value
 fn parse_border_style(val: &str) -> u8 {
     match val.trim() {
@@ -589,15 +1320,15 @@ fn parse_border_style(val: &str) -> u8 {
 }
 
 /// Parse border shorthand (e.g., "1px solid black")
-fn parse_border_shorthand(val: &str, styles: &mut CssStyles) {
-    let parts: Vec<&str> = val.split_whitespace().collect();
+fn parse_border_shorthand(val: &str, styles: &mut CssStyles, ctx: &LengthContext) {
+    let parts = split_values(val);
     
     for part in parts {
         let part_lower = part.to_lowercase();
         
         // Check if it's a width
         if part.chars().next().map_or(false, |c| c.is_ascii_digit()) {
-            let len = parse_length(part, 0.0);
+            let len = parse_length_ctx(part, 0.0, ctx);
             styles.border_top_width = len.value;
             styles.border_right_width = len.value;
             styles.border_bottom_width = len.value;
@@ -613,7 +1344,7 @@ fn parse_border_shorthand(val: &str, styles: &mut CssStyles) {
         }
         // Otherwise it's a color
         else {
-            let color = parse_color(part);
+            let color = parse_color_with(part, styles.color);
             styles.border_top_color = color;
             styles.border_right_color = color;
             styles.border_bottom_color = color;
@@ -650,10 +1381,10 @@ pub fn parse_stylesheet(css: &str) -> Vec<CssRule> {
 
 /// Parse a single CSS rule
 fn parse_rule(parser: &mut Parser) -> Result<CssRule, ()> {
-    // Parse selector
+    // Parse selector, keeping whitespace as it is the descendant combinator
     let mut selector = String::new();
     loop {
-        let token = parser.next().map_err(|_| ())?;
+        let token = parser.next_including_whitespace().map_err(|_| ())?;
         match token {
             CssToken::CurlyBracketBlock => break,
             _ => {
@@ -679,19 +1410,19 @@ fn parse_rule(parser: &mut Parser) -> Result<CssRule, ()> {
                     _ => return Err(()),
                 }
                 
-                // Parse value until semicolon or end
-                let mut value = String::new();
+                // Take the source text up to the semicolon or end, which keeps
+                // whitespace and function arguments intact
+                let start = parser.position();
                 loop {
                     match parser.next() {
                         Ok(CssToken::Semicolon) => break,
                         Err(_) => break,
-                        Ok(token) => {
-                            value.push_str(&token.to_css_string());
-                        }
+                        Ok(_) => {}
                     }
                 }
+                let value = parser.slice_from(start).trim().trim_end_matches(';').trim_end();
                 
-                properties.insert(name, value.trim().to_string());
+                properties.insert(name, value.to_string());
                 Ok(())
             })();
             
@@ -722,6 +1453,18 @@ mod tests {
         assert_eq!(parse_color("transparent"), Color::TRANSPARENT);
     }
     
+    #[test]
+    fn test_parse_color_extended_and_system() {
+        assert_eq!(parse_color("RebeccaPurple"), Color::new(0x66, 0x33, 0x99, 255));
+        assert_eq!(parse_color("lightgoldenrodyellow"), Color::new(0xfa, 0xfa, 0xd2, 255));
+        assert_eq!(parse_color("notacolor"), Color::TRANSPARENT);
+        assert_eq!(parse_color("LinkText"), ColorTheme::LIGHT.link_text);
+
+        let styles = parse_inline_style("border: 1px solid currentColor; color: red");
+        assert_eq!(styles.border_top_color, Color::new(255, 0, 0, 255));
+        assert!(NAMED_COLORS.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+    
     #[test]
     fn test_parse_color_hex() {
         assert_eq!(parse_color("#fff"), Color::new(255, 255, 255, 255));
@@ -730,6 +1473,29 @@ mod tests {
         assert_eq!(parse_color("#00ff00"), Color::new(0, 255, 0, 255));
     }
     
+    #[test]
+    fn test_parse_color_functions() {
+        assert_eq!(parse_color("#f008"), Color::new(255, 0, 0, 0x88));
+        assert_eq!(parse_color("#12345"), Color::TRANSPARENT);
+        
+        assert_eq!(parse_color("rgb(255, 128, 0)"), Color::new(255, 128, 0, 255));
+        assert_eq!(parse_color("rgba(0, 0, 255, 0.5)"), Color::new(0, 0, 255, 128));
+        assert_eq!(parse_color("rgb(100%, 50%, 0%)"), Color::new(255, 128, 0, 255));
+        assert_eq!(parse_color("rgb(300 -20 12.6 / 25%)"), Color::new(255, 0, 13, 64));
+        assert_eq!(parse_color("RGB(none 255 0)"), Color::new(0, 255, 0, 255));
+        
+        assert_eq!(parse_color("hsl(0, 100%, 50%)"), Color::new(255, 0, 0, 255));
+        assert_eq!(parse_color("hsl(120deg 100% 25%)"), Color::new(0, 128, 0, 255));
+        assert_eq!(parse_color("hsla(240, 100%, 50%, 0.5)"), Color::new(0, 0, 255, 128));
+        assert_eq!(parse_color("hsl(0.5turn 100 50 / 1)"), Color::new(0, 255, 255, 255));
+        assert_eq!(parse_color("hsl(-60, 100%, 50%)"), Color::new(255, 0, 255, 255));
+        assert_eq!(parse_color("hsl(0, 0%, 50%)"), Color::new(128, 128, 128, 255));
+        
+        assert_eq!(parse_color("rgb(1, 2)"), Color::TRANSPARENT);
+        assert_eq!(parse_color("rgb(1, 2, 3) red"), Color::TRANSPARENT);
+        assert_eq!(parse_color("lab(50% 0 0)"), Color::TRANSPARENT);
+    }
+    
     #[test]
     fn test_parse_length() {
         let len = parse_length("100px", 0.0);
@@ -741,6 +1507,34 @@ mod tests {
         
         let no_unit = parse_length("50", 0.0);
         assert_eq!(no_unit.value, 50.0);
+        assert!(parse_length("12furlongs", 0.0).is_auto);
+    }
+    
+    #[test]
+    fn test_parse_length_relative_units() {
+        let ctx = LengthContext { font_size: 20.0, root_font_size: 10.0, viewport_w: 1000.0, viewport_h: 500.0 };
+        let px = |value| parse_length_ctx(value, 200.0, &ctx).value;
+        
+        assert_eq!(px("1.5em"), 30.0);
+        assert_eq!(px("2rem"), 20.0);
+        assert_eq!(px("2ch"), 20.0);
+        assert_eq!(px("10vw"), 100.0);
+        assert_eq!(px("10vh"), 50.0);
+        assert_eq!(px("10vmin"), 50.0);
+        assert_eq!(px("10VMAX"), 100.0);
+        assert_eq!(px("25%"), 50.0);
+        assert_eq!(px("12pt"), 16.0);
+        assert_eq!(px("1in"), 96.0);
+        assert_eq!(px("2.54cm"), 96.0);
+        assert_eq!(px("1pc"), 16.0);
+        
+        // `em` follows the element's own font size, which follows the parent's
+        let mut styles = CssStyles::default();
+        let decls = parse_declarations("padding: 1em; font-size: 150%; width: 50vw");
+        apply_declarations_ctx(&mut styles, &decls, &ctx);
+        assert_eq!(styles.font_size, 30.0);
+        assert_eq!(styles.padding_left, 30.0);
+        assert_eq!(styles.width.value, 500.0);
     }
     
     #[test]
@@ -764,15 +1558,70 @@ mod tests {
         assert_eq!(styles.left.value, 20.0);
     }
     
+    #[test]
+    fn test_parse_flexbox() {
+        let styles = parse_inline_style("display: flex; flex-direction: column; justify-content: space-around; align-items: flex-end; gap: 10px");
+        assert_eq!(styles.display, DISPLAY_FLEX);
+        assert_eq!(styles.flex_direction, FLEX_DIRECTION_COLUMN);
+        assert_eq!(styles.justify_content, JUSTIFY_SPACE_AROUND);
+        assert_eq!(styles.align_items, ALIGN_FLEX_END);
+        assert_eq!((styles.row_gap, styles.column_gap), (10.0, 10.0));
+        
+        let styles = parse_inline_style("row-gap: 2px; column-gap: 6px; flex: 2 3 50px");
+        assert_eq!((styles.row_gap, styles.column_gap), (2.0, 6.0));
+        assert_eq!((styles.flex_grow, styles.flex_shrink, styles.flex_basis.value), (2.0, 3.0, 50.0));
+        
+        let (grow, shrink, basis) = parse_flex_shorthand("1", &LengthContext::default());
+        assert_eq!((grow, shrink, basis.value, basis.is_auto), (1.0, 1.0, 0.0, false));
+        let (grow, shrink, basis) = parse_flex_shorthand("none", &LengthContext::default());
+        assert_eq!((grow, shrink, basis.is_auto), (0.0, 0.0, true));
+        let (grow, shrink, basis) = parse_flex_shorthand("100px 2", &LengthContext::default());
+        assert_eq!((grow, shrink, basis.value), (2.0, 1.0, 100.0));
+    }
+    
+    #[test]
+    fn test_parse_calc() {
+        let ctx = LengthContext { font_size: 10.0, ..LengthContext::default() };
+        let expr = parse_calc("calc(100% - 2 * (1em + 5px))", &ctx).unwrap();
+        assert_eq!(expr, CalcExpr::Sub(
+            Box::new(CalcExpr::Percentage(100.0)),
+            Box::new(CalcExpr::Mul(
+                Box::new(CalcExpr::Number(2.0)),
+                Box::new(CalcExpr::Add(Box::new(CalcExpr::Length(10.0)), Box::new(CalcExpr::Length(5.0)))),
+            )),
+        ));
+        assert_eq!(expr.resolve(200.0), Some(170.0));
+        
+        let px = |value| parse_length_ctx(value, 300.0, &ctx);
+        assert_eq!(px("calc(50% + 10px)").value, 160.0);
+        assert_eq!(px("CALC(100vw / 4 - calc(2rem))").value, 168.0);
+        assert!(!px("calc(0)").is_auto);
+        for invalid in ["calc(10px * 2px)", "calc(1 + 2px)", "calc(10px / 0)", "calc(10px / 2px)", "calc(10px +)", "calc(10px) 5px"] {
+            assert!(px(invalid).is_auto, "{}", invalid);
+        }
+        
+        let styles = parse_inline_style("width: calc(200px - 1em); margin: calc(4px * 2) 3px; padding: calc( 1px + 1px )");
+        assert_eq!(styles.width.value, 184.0);
+        assert_eq!((styles.margin_top, styles.margin_right), (8.0, 3.0));
+        assert_eq!(styles.padding_left, 2.0);
+    }
+    
+    #[test]
+    fn test_parse_stylesheet_values() {
+        let rules = parse_stylesheet("p { border: 1px solid rgb(0, 0, 255); width: calc(10px + 5px) }");
+        assert_eq!(rules[0].properties["border"], "1px solid rgb(0, 0, 255)");
+        assert_eq!(rules[0].properties["width"], "calc(10px + 5px)");
+    }
+    
     #[test]
     fn test_parse_margin_shorthand() {
-        let (t, r, b, l) = parse_margin_shorthand("10px");
+        let (t, r, b, l) = parse_margin_shorthand("10px", &LengthContext::default());
         assert_eq!((t, r, b, l), (10.0, 10.0, 10.0, 10.0));
         
-        let (t, r, b, l) = parse_margin_shorthand("10px 20px");
+        let (t, r, b, l) = parse_margin_shorthand("10px 20px", &LengthContext::default());
         assert_eq!((t, r, b, l), (10.0, 20.0, 10.0, 20.0));
         
-        let (t, r, b, l) = parse_margin_shorthand("10px 20px 30px 40px");
+        let (t, r, b, l) = parse_margin_shorthand("10px 20px 30px 40px", &LengthContext::default());
         assert_eq!((t, r, b, l), (10.0, 20.0, 30.0, 40.0));
     }
 }
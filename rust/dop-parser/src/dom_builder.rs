@@ -0,0 +1,379 @@
+//! HTML to Content IR tree construction
+//!
+//! Walks the token tape from `parse_html` and builds the node and property
+//! tables directly, so the IR no longer has to be assembled in Julia:
+//! - `p` becomes a Paragraph, `span` a Span and `a` a Link; other elements
+//!   become Stacks
+//! - `html` and `body` add no node of their own, and `head`, `script`,
+//!   `style`, `title` and `template` are dropped with their contents
+//! - Text becomes a Span (or fills an empty `span`)
+//! - `<style>` rules and inline `style` attributes (which win) set size,
+//!   padding (inset), margin (offset), background (fill), color and font
+//!   size; color and font size are inherited, and `display: none` drops the
+//!   element
+//! - Flex containers map `flex-direction`, `justify-content`, `align-items`
+//!   and `gap` onto the Stack's direction, pack, align and gaps
+//!
+//! End tags close the innermost open element with the same name, and stray
+//! end tags are ignored, so unbalanced markup still produces a tree.
+
+use crate::compiler::{Align, CompiledUnit, Direction, NodeType, Pack, PropertyTable};
+use crate::css_parser::*;
+use crate::html_parser::{parse_html, HtmlToken, TokenType};
+use crate::selector::{matched_declarations, Element};
+use crate::string_interner::{StringId, StringPool};
+
+/// Elements that never have children or an end tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+/// Elements whose contents are not rendered
+const SKIPPED_ELEMENTS: &[&str] = &["head", "script", "style", "template", "title"];
+
+/// Elements that add no node; their children go to the enclosing node
+const TRANSPARENT_ELEMENTS: &[&str] = &["html", "body"];
+
+/// An element still waiting for its end tag
+struct OpenElement {
+    tag: StringId,
+    id: StringId,
+    class: StringId,
+    /// The element's node, or the enclosing node for transparent elements
+    node: u32,
+    color: Color,
+    font_size: f32,
+}
+
+/// Parse HTML and build a Content IR unit from it
+pub fn html_to_content_ir(html: &str) -> CompiledUnit {
+    let result = parse_html(html);
+    build_content_ir(&result.tokens, &result.strings)
+}
+
+/// Build a Content IR unit from an HTML token tape
+///
+/// Node 1 is the Root. Text is interned into the unit's string pool and
+/// referenced from `properties.text_id`.
+pub fn build_content_ir(tokens: &[HtmlToken], strings: &StringPool) -> CompiledUnit {
+    let mut unit = CompiledUnit::new();
+    let root = unit.nodes.create_node(NodeType::Root, 0, 0);
+    unit.properties.resize(1);
+
+    let rules = collect_style_rules(tokens, strings);
+    let mut open = vec![OpenElement {
+        tag: StringId::NONE,
+        id: StringId::NONE,
+        class: StringId::NONE,
+        node: root,
+        color: Color::BLACK,
+        font_size: 16.0,
+    }];
+    // Name and nesting depth of the element being dropped, if any
+    let mut skipping: Option<(StringId, u32)> = None;
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = tokens[i];
+        i += 1;
+        let tag_name = strings.get(token.name_id).unwrap_or("");
+
+        if let Some((tag, depth)) = &mut skipping {
+            match token.token_type {
+                TokenType::StartTag if token.name_id == *tag => *depth += 1,
+                TokenType::EndTag if token.name_id == *tag => {
+                    *depth -= 1;
+                    if *depth == 0 {
+                        skipping = None;
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        match token.token_type {
+            TokenType::StartTag | TokenType::SelfClosing => {
+                // Attributes follow their start tag on the tape
+                let (mut style, mut id, mut class) = ("", StringId::NONE, StringId::NONE);
+                while let Some(attr) = tokens.get(i).filter(|t| t.token_type == TokenType::Attribute) {
+                    match strings.get(attr.name_id) {
+                        Some("style") => style = strings.get(attr.value_id).unwrap_or(""),
+                        Some("id") => id = attr.value_id,
+                        Some("class") => class = attr.value_id,
+                        _ => {}
+                    }
+                    i += 1;
+                }
+                let has_children = token.token_type == TokenType::StartTag && !VOID_ELEMENTS.contains(&tag_name);
+                if SKIPPED_ELEMENTS.contains(&tag_name) {
+                    if has_children {
+                        skipping = Some((token.name_id, 1));
+                    }
+                    continue;
+                }
+
+                // Stylesheet rules in cascade order, then the inline style
+                let element = |tag, id, class| Element {
+                    tag: strings.get(tag).unwrap_or(""),
+                    id: strings.get(id).unwrap_or(""),
+                    class: strings.get(class).unwrap_or(""),
+                };
+                let mut path: Vec<Element> = open[1..].iter().map(|e| element(e.tag, e.id, e.class)).collect();
+                path.push(element(token.name_id, id, class));
+                let mut decls = matched_declarations(&rules, &path);
+                decls.extend(parse_declarations(style));
+                let mut styles = CssStyles::default();
+                let ctx = LengthContext {
+                    font_size: open.last().expect("root stays open").font_size,
+                    ..LengthContext::default()
+                };
+                apply_declarations_ctx(&mut styles, &decls, &ctx);
+
+                if styles.display == DISPLAY_NONE {
+                    if has_children {
+                        skipping = Some((token.name_id, 1));
+                    }
+                    continue;
+                }
+                let parent = open.last().expect("root stays open");
+                let (parent_node, mut color, mut font_size) = (parent.node, parent.color, parent.font_size);
+                if VOID_ELEMENTS.contains(&tag_name) {
+                    continue;
+                }
+                if TRANSPARENT_ELEMENTS.contains(&tag_name) {
+                    if has_children {
+                        open.push(OpenElement { tag: token.name_id, id, class, node: parent_node, color, font_size });
+                    }
+                    continue;
+                }
+
+                let node_type = match tag_name {
+                    "p" => NodeType::Paragraph,
+                    "span" => NodeType::Span,
+                    "a" => NodeType::Link,
+                    _ => NodeType::Stack,
+                };
+                let node = unit.nodes.create_node(node_type, parent_node, 0);
+                unit.properties.resize(unit.nodes.len());
+                let idx = node as usize - 1;
+                if decls.iter().any(|(prop, _)| prop == "color") {
+                    color = styles.color;
+                }
+                if decls.iter().any(|(prop, _)| prop == "font-size") {
+                    font_size = styles.font_size;
+                }
+                apply_box_styles(&mut unit.properties, idx, &styles);
+                if matches!(styles.display, DISPLAY_FLEX | DISPLAY_INLINE_FLEX) {
+                    apply_flex_styles(&mut unit.properties, idx, &styles);
+                }
+                set_text_style(&mut unit.properties, idx, color, font_size);
+
+                if has_children {
+                    open.push(OpenElement { tag: token.name_id, id, class, node, color, font_size });
+                }
+            }
+            TokenType::EndTag => {
+                if let Some(pos) = open[1..].iter().rposition(|e| e.tag == token.name_id) {
+                    open.truncate(pos + 1);
+                }
+            }
+            TokenType::Text => {
+                let Some(text) = strings.get(token.value_id) else {
+                    continue;
+                };
+                let text_id = unit.strings.intern(text);
+                let current = open.last().expect("root stays open");
+                let idx = current.node as usize - 1;
+                let empty_span = unit.nodes.node_types[idx] == NodeType::Span
+                    && !unit.properties.text_id[idx].is_valid()
+                    && unit.nodes.first_children[idx] == 0;
+                if empty_span {
+                    unit.properties.text_id[idx] = text_id;
+                } else {
+                    let node = unit.nodes.create_node(NodeType::Span, current.node, 0);
+                    unit.properties.resize(unit.nodes.len());
+                    let idx = node as usize - 1;
+                    unit.properties.text_id[idx] = text_id;
+                    set_text_style(&mut unit.properties, idx, current.color, current.font_size);
+                }
+            }
+            TokenType::Comment | TokenType::Doctype | TokenType::Attribute => {}
+        }
+    }
+
+    unit.compute_checksum();
+    unit
+}
+
+/// Parse the contents of every `<style>` element, in document order
+fn collect_style_rules(tokens: &[HtmlToken], strings: &StringPool) -> Vec<CssRule> {
+    let mut css = String::new();
+    let mut in_style = false;
+    for token in tokens {
+        let is_style = strings.get(token.name_id) == Some("style");
+        match token.token_type {
+            TokenType::StartTag if is_style => in_style = true,
+            TokenType::EndTag if is_style => in_style = false,
+            TokenType::Text if in_style => {
+                css.push_str(strings.get(token.value_id).unwrap_or(""));
+                css.push('\n');
+            }
+            _ => {}
+        }
+    }
+    parse_stylesheet(&css)
+}
+
+/// Copy box model and background styles onto a node
+fn apply_box_styles(props: &mut PropertyTable, idx: usize, styles: &CssStyles) {
+    if !styles.width.is_auto {
+        props.width[idx] = styles.width.value;
+    }
+    if !styles.height.is_auto {
+        props.height[idx] = styles.height.value;
+    }
+    props.inset_top[idx] = styles.padding_top;
+    props.inset_right[idx] = styles.padding_right;
+    props.inset_bottom[idx] = styles.padding_bottom;
+    props.inset_left[idx] = styles.padding_left;
+    props.offset_top[idx] = styles.margin_top;
+    props.offset_right[idx] = styles.margin_right;
+    props.offset_bottom[idx] = styles.margin_bottom;
+    props.offset_left[idx] = styles.margin_left;
+    if styles.has_background {
+        let c = styles.background_color;
+        (props.fill_r[idx], props.fill_g[idx], props.fill_b[idx], props.fill_a[idx]) = (c.r, c.g, c.b, c.a);
+    }
+}
+
+/// Map flex container styles onto a Stack
+fn apply_flex_styles(props: &mut PropertyTable, idx: usize, styles: &CssStyles) {
+    props.direction[idx] = match styles.flex_direction {
+        FLEX_DIRECTION_ROW_REVERSE => Direction::Left,
+        FLEX_DIRECTION_COLUMN => Direction::Down,
+        FLEX_DIRECTION_COLUMN_REVERSE => Direction::Up,
+        _ => Direction::Right,
+    };
+    props.pack[idx] = match styles.justify_content {
+        JUSTIFY_FLEX_END => Pack::End,
+        JUSTIFY_CENTER => Pack::Center,
+        JUSTIFY_SPACE_BETWEEN => Pack::SpaceBetween,
+        JUSTIFY_SPACE_AROUND => Pack::SpaceAround,
+        JUSTIFY_SPACE_EVENLY => Pack::SpaceEvenly,
+        _ => Pack::Start,
+    };
+    props.align[idx] = match styles.align_items {
+        ALIGN_FLEX_END => Align::End,
+        ALIGN_CENTER => Align::Center,
+        ALIGN_STRETCH => Align::Stretch,
+        _ => Align::Start,
+    };
+    props.gap_row[idx] = styles.row_gap;
+    props.gap_col[idx] = styles.column_gap;
+}
+
+/// Set a node's (possibly inherited) text color and font size
+fn set_text_style(props: &mut PropertyTable, idx: usize, color: Color, font_size: f32) {
+    (props.color_r[idx], props.color_g[idx], props.color_b[idx], props.color_a[idx]) = (color.r, color.g, color.b, color.a);
+    props.font_size[idx] = font_size;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(unit: &CompiledUnit, node: u32) -> &str {
+        unit.strings.get(unit.properties.text_id[node as usize - 1]).unwrap_or("")
+    }
+
+    #[test]
+    fn test_element_mapping() {
+        let unit = html_to_content_ir(
+            "<!DOCTYPE html><html><head><title>Ignored</title></head>\
+             <body><div><p>Hello <span>big</span> <a href='#'>link</a></p></div></body></html>",
+        );
+
+        let types = &unit.nodes.node_types;
+        assert_eq!(types[0], NodeType::Root);
+        let div = unit.nodes.get_children(1);
+        assert_eq!(div.len(), 1);
+        assert_eq!(types[div[0] as usize - 1], NodeType::Stack);
+
+        let p = unit.nodes.get_children(div[0]);
+        assert_eq!(types[p[0] as usize - 1], NodeType::Paragraph);
+        let inline = unit.nodes.get_children(p[0]);
+        let inline_types: Vec<_> = inline.iter().map(|&n| types[n as usize - 1]).collect();
+        assert_eq!(inline_types, vec![NodeType::Span, NodeType::Span, NodeType::Link]);
+        assert_eq!(text(&unit, inline[0]), "Hello");
+        // The span's own text fills it instead of adding a child
+        assert_eq!(text(&unit, inline[1]), "big");
+        assert_eq!(text(&unit, unit.nodes.get_children(inline[2])[0]), "link");
+        assert!(!unit.strings.get_id("Ignored").is_some_and(|id| unit.properties.text_id.contains(&id)));
+    }
+
+    #[test]
+    fn test_inline_styles() {
+        let unit = html_to_content_ir(
+            "<div style='width: 200px; padding: 4px; margin-top: 8px; background: red; color: blue; font-size: 20px'>\
+             <p>Inherited</p><div style='display: none'><p>Hidden</p></div></div>",
+        );
+        let props = &unit.properties;
+
+        assert_eq!(props.width[1], 200.0);
+        assert_eq!(props.height[1], 0.0);
+        assert_eq!(props.inset_left[1], 4.0);
+        assert_eq!(props.offset_top[1], 8.0);
+        assert_eq!((props.fill_r[1], props.fill_a[1]), (255, 255));
+
+        // Only the paragraph and its text remain under the div
+        assert_eq!(unit.nodes.len(), 4);
+        assert_eq!(text(&unit, 4), "Inherited");
+        assert_eq!((props.color_b[3], props.font_size[3]), (255, 20.0));
+    }
+
+    #[test]
+    fn test_stylesheet_rules() {
+        let unit = html_to_content_ir(
+            "<html><head><style>body #main p { color: red } .wide { width: 300px; height: 10px }</style></head>\
+             <body><div id='main'><p class='wide' style='width: 50px'>A</p><p>B</p></div></body></html>",
+        );
+        let props = &unit.properties;
+
+        // Nodes: root, div, p, "A", p, "B"
+        assert_eq!(unit.nodes.len(), 6);
+        // The inline style beats the class rule
+        assert_eq!((props.width[2], props.height[2]), (50.0, 10.0));
+        assert_eq!((props.width[4], props.height[4]), (0.0, 0.0));
+        assert_eq!((props.color_r[3], props.color_r[5]), (255, 255));
+        assert_eq!(props.color_r[1], 0);
+    }
+
+    #[test]
+    fn test_flex_container() {
+        let unit = html_to_content_ir(
+            "<div style='display: flex; justify-content: space-between; align-items: center; gap: 4px 8px'>\
+             <div style='flex: 1'></div><div style='display: flex; flex-direction: column-reverse'></div></div>\
+             <div style='justify-content: center'></div>",
+        );
+        let props = &unit.properties;
+
+        assert_eq!((props.direction[1], props.pack[1], props.align[1]), (Direction::Right, Pack::SpaceBetween, Align::Center));
+        assert_eq!((props.gap_row[1], props.gap_col[1]), (4.0, 8.0));
+        assert_eq!((props.direction[3], props.align[3]), (Direction::Up, Align::Stretch));
+        // Flex properties only apply to flex containers
+        assert_eq!((props.direction[4], props.pack[4]), (Direction::Down, Pack::Start));
+    }
+
+    #[test]
+    fn test_unbalanced_markup() {
+        let unit = html_to_content_ir("<div><p>One</div></span><p>Two<br>Three");
+
+        // The stray </span> is ignored; </div> closes the open <p> too
+        let top = unit.nodes.get_children(1);
+        assert_eq!(top.len(), 2);
+        assert_eq!(unit.nodes.node_types[top[1] as usize - 1], NodeType::Paragraph);
+        let second: Vec<_> = unit.nodes.get_children(top[1]).iter().map(|&n| text(&unit, n).to_string()).collect();
+        assert_eq!(second, vec!["Two", "Three"]);
+    }
+}
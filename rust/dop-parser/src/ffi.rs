@@ -4,6 +4,7 @@
 //! using the `ccall` mechanism. The Rust library is built using the unified
 //! BinaryBuilder configuration for cross-platform distribution.
 
+use std::cell::OnceCell;
 use std::ffi::{c_char, c_float, c_int, c_uchar, CStr, CString};
 use std::ptr;
 use std::slice;
@@ -12,9 +13,16 @@ use crate::compiler::{
     CompiledUnit, CompilerContext,
     NodeTable, NodeType, PropertyTable, ShapedParagraph, TextShaper,
 };
-use crate::css_parser::{parse_color, parse_inline_style, parse_length, CssStyles};
-use crate::html_parser::{parse_html, HtmlToken};
-use crate::string_interner::{StringId, StringPool};
+use crate::css_parser::{
+    color_theme, parse_color, parse_inline_style, parse_length, parse_length_ctx, parse_stylesheet,
+    set_color_theme, Color, ColorTheme, CssRule, CssStyles, LengthContext,
+};
+use crate::dom_builder::html_to_content_ir;
+use crate::ffi_guard::ffi_guard;
+use crate::html_parser::{decode_character_references, parse_html, HtmlStreamTokenizer, HtmlToken, HtmlTokenFFI, LineIndex};
+use crate::selector::{match_rules, Element};
+use crate::string_interner::{FlatStrings, StringId, StringPool};
+use crate::tree_builder::{parse_html_tree, HtmlTree};
 
 // ============================================================================
 // Initialization
@@ -23,14 +31,38 @@ use crate::string_interner::{StringId, StringPool};
 /// Initialize the parser library
 #[no_mangle]
 pub extern "C" fn dop_parser_init() {
-    let _ = env_logger::try_init();
+    ffi_guard("dop_parser_init", || {
+        let _ = env_logger::try_init();
+    })
 }
 
 /// Get library version
 #[no_mangle]
 pub extern "C" fn dop_parser_version() -> *const c_char {
-    static VERSION: &[u8] = concat!(env!("CARGO_PKG_VERSION"), "\0").as_bytes();
-    VERSION.as_ptr() as *const c_char
+    ffi_guard("dop_parser_version", || {
+        static VERSION: &[u8] = concat!(env!("CARGO_PKG_VERSION"), "\0").as_bytes();
+        VERSION.as_ptr() as *const c_char
+    })
+}
+
+// ============================================================================
+// Error FFI
+// ============================================================================
+
+/// Get the message of the last panic caught on this thread (null if none)
+///
+/// Functions that panic return 0 or null and record the message here. The
+/// string is owned by the library and valid until the next error or
+/// `dop_parser_clear_last_error` on the same thread.
+#[no_mangle]
+pub extern "C" fn dop_parser_last_error() -> *const c_char {
+    crate::ffi_guard::last_error_ptr()
+}
+
+/// Clear the last error of this thread
+#[no_mangle]
+pub extern "C" fn dop_parser_clear_last_error() {
+    crate::ffi_guard::clear_last_error();
 }
 
 // ============================================================================
@@ -40,79 +72,93 @@ pub extern "C" fn dop_parser_version() -> *const c_char {
 /// Create a new string pool
 #[no_mangle]
 pub extern "C" fn dop_string_pool_new() -> *mut StringPool {
-    Box::into_raw(Box::new(StringPool::new()))
+    ffi_guard("dop_string_pool_new", || {
+        Box::into_raw(Box::new(StringPool::new()))
+    })
 }
 
 /// Free a string pool
 #[no_mangle]
 pub extern "C" fn dop_string_pool_free(pool: *mut StringPool) {
-    if !pool.is_null() {
-        unsafe {
-            drop(Box::from_raw(pool));
+    ffi_guard("dop_string_pool_free", || {
+        if !pool.is_null() {
+            unsafe {
+                drop(Box::from_raw(pool));
+            }
         }
-    }
+    })
 }
 
 /// Intern a string and return its ID
 #[no_mangle]
 pub extern "C" fn dop_string_pool_intern(pool: *mut StringPool, s: *const c_char) -> u32 {
-    if pool.is_null() || s.is_null() {
-        return 0;
-    }
-    unsafe {
-        let c_str = CStr::from_ptr(s);
-        if let Ok(str_slice) = c_str.to_str() {
-            (*pool).intern(str_slice).0
-        } else {
-            0
+    ffi_guard("dop_string_pool_intern", || {
+        if pool.is_null() || s.is_null() {
+            return 0;
         }
-    }
+        unsafe {
+            let c_str = CStr::from_ptr(s);
+            if let Ok(str_slice) = c_str.to_str() {
+                (*pool).intern(str_slice).0
+            } else {
+                0
+            }
+        }
+    })
 }
 
 /// Get a string by ID (returns pointer to internal string, valid until pool is modified)
 #[no_mangle]
 pub extern "C" fn dop_string_pool_get(pool: *const StringPool, id: u32) -> *const c_char {
-    if pool.is_null() {
-        return ptr::null();
-    }
-    unsafe {
-        if let Some(s) = (*pool).get(StringId(id)) {
-            // Create a null-terminated copy
-            if let Ok(c_string) = CString::new(s) {
-                return c_string.into_raw();
+    ffi_guard("dop_string_pool_get", || {
+        if pool.is_null() {
+            return ptr::null();
+        }
+        unsafe {
+            if let Some(s) = (*pool).get(StringId(id)) {
+                // Create a null-terminated copy
+                if let Ok(c_string) = CString::new(s) {
+                    return c_string.into_raw();
+                }
             }
         }
-    }
-    ptr::null()
+        ptr::null()
+    })
 }
 
 /// Free a string returned by dop_string_pool_get
 #[no_mangle]
 pub extern "C" fn dop_string_free(s: *mut c_char) {
-    if !s.is_null() {
-        unsafe {
-            drop(CString::from_raw(s));
+    ffi_guard("dop_string_free", || {
+        if !s.is_null() {
+            unsafe {
+                drop(CString::from_raw(s));
+            }
         }
-    }
+    })
 }
 
 /// Get the number of interned strings
 #[no_mangle]
 pub extern "C" fn dop_string_pool_len(pool: *const StringPool) -> u32 {
-    if pool.is_null() {
-        return 0;
-    }
-    unsafe { (*pool).len() as u32 }
+    ffi_guard("dop_string_pool_len", || {
+        if pool.is_null() {
+            return 0;
+        }
+        unsafe { (*pool).len() as u32 }
+    })
 }
 
 /// Clear the string pool
 #[no_mangle]
 pub extern "C" fn dop_string_pool_clear(pool: *mut StringPool) {
-    if !pool.is_null() {
-        unsafe {
-            (*pool).clear();
+    ffi_guard("dop_string_pool_clear", || {
+        if !pool.is_null() {
+            unsafe {
+                (*pool).clear();
+            }
         }
-    }
+    })
 }
 
 // ============================================================================
@@ -123,111 +169,551 @@ pub extern "C" fn dop_string_pool_clear(pool: *mut StringPool) {
 pub struct HtmlParseResult {
     tokens: Vec<HtmlToken>,
     strings: StringPool,
+    lines: LineIndex,
+    /// Bulk exports, built on first request
+    flat_tokens: OnceCell<Vec<HtmlTokenFFI>>,
+    flat_strings: OnceCell<FlatStrings>,
 }
 
 /// Parse HTML and return a result handle
 #[no_mangle]
 pub extern "C" fn dop_html_parse(html: *const c_char) -> *mut HtmlParseResult {
-    if html.is_null() {
-        return ptr::null_mut();
-    }
+    ffi_guard("dop_html_parse", || {
+        if html.is_null() {
+            return ptr::null_mut();
+        }
     
-    unsafe {
-        let c_str = CStr::from_ptr(html);
-        if let Ok(html_str) = c_str.to_str() {
-            let result = parse_html(html_str);
-            Box::into_raw(Box::new(HtmlParseResult {
-                tokens: result.tokens,
-                strings: result.strings,
-            }))
-        } else {
-            ptr::null_mut()
+        unsafe {
+            let c_str = CStr::from_ptr(html);
+            if let Ok(html_str) = c_str.to_str() {
+                let result = parse_html(html_str);
+                Box::into_raw(Box::new(HtmlParseResult {
+                    tokens: result.tokens,
+                    strings: result.strings,
+                    lines: result.lines,
+                    flat_tokens: OnceCell::new(),
+                    flat_strings: OnceCell::new(),
+                }))
+            } else {
+                ptr::null_mut()
+            }
         }
-    }
+    })
 }
 
 /// Free an HTML parse result
 #[no_mangle]
 pub extern "C" fn dop_html_result_free(result: *mut HtmlParseResult) {
-    if !result.is_null() {
-        unsafe {
-            drop(Box::from_raw(result));
+    ffi_guard("dop_html_result_free", || {
+        if !result.is_null() {
+            unsafe {
+                drop(Box::from_raw(result));
+            }
         }
-    }
+    })
 }
 
 /// Get the number of tokens
 #[no_mangle]
 pub extern "C" fn dop_html_result_token_count(result: *const HtmlParseResult) -> u32 {
-    if result.is_null() {
-        return 0;
-    }
-    unsafe { (*result).tokens.len() as u32 }
+    ffi_guard("dop_html_result_token_count", || {
+        if result.is_null() {
+            return 0;
+        }
+        unsafe { (*result).tokens.len() as u32 }
+    })
 }
 
 /// Get token type at index
 #[no_mangle]
 pub extern "C" fn dop_html_result_token_type(result: *const HtmlParseResult, index: u32) -> u8 {
-    if result.is_null() {
-        return 0;
-    }
-    unsafe {
-        let r = &*result;
-        if let Some(token) = r.tokens.get(index as usize) {
-            token.token_type as u8
-        } else {
-            0
+    ffi_guard("dop_html_result_token_type", || {
+        if result.is_null() {
+            return 0;
         }
-    }
+        unsafe {
+            let r = &*result;
+            if let Some(token) = r.tokens.get(index as usize) {
+                token.token_type as u8
+            } else {
+                0
+            }
+        }
+    })
 }
 
 /// Get token name ID at index
 #[no_mangle]
 pub extern "C" fn dop_html_result_token_name_id(result: *const HtmlParseResult, index: u32) -> u32 {
-    if result.is_null() {
-        return 0;
-    }
-    unsafe {
-        let r = &*result;
-        if let Some(token) = r.tokens.get(index as usize) {
-            token.name_id.0
-        } else {
-            0
+    ffi_guard("dop_html_result_token_name_id", || {
+        if result.is_null() {
+            return 0;
         }
-    }
+        unsafe {
+            let r = &*result;
+            if let Some(token) = r.tokens.get(index as usize) {
+                token.name_id.0
+            } else {
+                0
+            }
+        }
+    })
 }
 
 /// Get token value ID at index
 #[no_mangle]
 pub extern "C" fn dop_html_result_token_value_id(result: *const HtmlParseResult, index: u32) -> u32 {
-    if result.is_null() {
-        return 0;
-    }
-    unsafe {
-        let r = &*result;
-        if let Some(token) = r.tokens.get(index as usize) {
-            token.value_id.0
-        } else {
-            0
+    ffi_guard("dop_html_result_token_value_id", || {
+        if result.is_null() {
+            return 0;
         }
-    }
+        unsafe {
+            let r = &*result;
+            if let Some(token) = r.tokens.get(index as usize) {
+                token.value_id.0
+            } else {
+                0
+            }
+        }
+    })
+}
+
+/// Get token byte offset in the source at index
+#[no_mangle]
+pub extern "C" fn dop_html_result_token_offset(result: *const HtmlParseResult, index: u32) -> u32 {
+    ffi_guard("dop_html_result_token_offset", || {
+        if result.is_null() {
+            return 0;
+        }
+        unsafe {
+            let r = &*result;
+            r.tokens.get(index as usize).map_or(0, |t| t.source_offset)
+        }
+    })
+}
+
+/// Get token 1-based source line at index (0 if out of range)
+#[no_mangle]
+pub extern "C" fn dop_html_result_token_line(result: *const HtmlParseResult, index: u32) -> u32 {
+    ffi_guard("dop_html_result_token_line", || {
+        if result.is_null() {
+            return 0;
+        }
+        unsafe {
+            let r = &*result;
+            r.tokens.get(index as usize).map_or(0, |t| r.lines.line_col(t.source_offset).0)
+        }
+    })
+}
+
+/// Get token 1-based source column in bytes at index (0 if out of range)
+#[no_mangle]
+pub extern "C" fn dop_html_result_token_column(result: *const HtmlParseResult, index: u32) -> u32 {
+    ffi_guard("dop_html_result_token_column", || {
+        if result.is_null() {
+            return 0;
+        }
+        unsafe {
+            let r = &*result;
+            r.tokens.get(index as usize).map_or(0, |t| r.lines.line_col(t.source_offset).1)
+        }
+    })
+}
+
+/// Get a pointer to all tokens as a packed `HtmlTokenFFI` array
+///
+/// Writes the token count to `count`. The array lives as long as the result.
+#[no_mangle]
+pub extern "C" fn dop_html_result_tokens_ptr(result: *const HtmlParseResult, count: *mut u32) -> *const HtmlTokenFFI {
+    ffi_guard("dop_html_result_tokens_ptr", || {
+        if result.is_null() {
+            return ptr::null();
+        }
+        unsafe {
+            let r = &*result;
+            let tokens = r.flat_tokens.get_or_init(|| r.tokens.iter().map(|&t| t.into()).collect());
+            if !count.is_null() {
+                *count = tokens.len() as u32;
+            }
+            tokens.as_ptr()
+        }
+    })
+}
+
+/// Get a pointer to the result's strings concatenated as UTF-8
+///
+/// Writes the byte length to `len`. String `id` spans
+/// `[offsets[id], offsets[id + 1])`; see `dop_html_result_string_offsets_ptr`.
+#[no_mangle]
+pub extern "C" fn dop_html_result_strings_ptr(result: *const HtmlParseResult, len: *mut u32) -> *const u8 {
+    ffi_guard("dop_html_result_strings_ptr", || {
+        if result.is_null() {
+            return ptr::null();
+        }
+        unsafe {
+            let r = &*result;
+            let flat = r.flat_strings.get_or_init(|| r.strings.flatten());
+            if !len.is_null() {
+                *len = flat.bytes.len() as u32;
+            }
+            flat.bytes.as_ptr()
+        }
+    })
+}
+
+/// Get a pointer to the string offsets (string count + 2 entries, including ID 0)
+///
+/// Writes the number of offsets to `count`.
+#[no_mangle]
+pub extern "C" fn dop_html_result_string_offsets_ptr(result: *const HtmlParseResult, count: *mut u32) -> *const u32 {
+    ffi_guard("dop_html_result_string_offsets_ptr", || {
+        if result.is_null() {
+            return ptr::null();
+        }
+        unsafe {
+            let r = &*result;
+            let flat = r.flat_strings.get_or_init(|| r.strings.flatten());
+            if !count.is_null() {
+                *count = flat.offsets.len() as u32;
+            }
+            flat.offsets.as_ptr()
+        }
+    })
 }
 
 /// Get string from result's string pool
 #[no_mangle]
 pub extern "C" fn dop_html_result_get_string(result: *const HtmlParseResult, id: u32) -> *const c_char {
-    if result.is_null() {
-        return ptr::null();
-    }
-    unsafe {
-        let r = &*result;
-        if let Some(s) = r.strings.get(StringId(id)) {
-            if let Ok(c_string) = CString::new(s) {
-                return c_string.into_raw();
+    ffi_guard("dop_html_result_get_string", || {
+        if result.is_null() {
+            return ptr::null();
+        }
+        unsafe {
+            let r = &*result;
+            if let Some(s) = r.strings.get(StringId(id)) {
+                if let Ok(c_string) = CString::new(s) {
+                    return c_string.into_raw();
+                }
+            }
+        }
+        ptr::null()
+    })
+}
+
+/// Create a streaming HTML tokenizer
+#[no_mangle]
+pub extern "C" fn dop_html_stream_new() -> *mut HtmlStreamTokenizer {
+    ffi_guard("dop_html_stream_new", || {
+        Box::into_raw(Box::new(HtmlStreamTokenizer::new()))
+    })
+}
+
+/// Free a streaming HTML tokenizer
+#[no_mangle]
+pub extern "C" fn dop_html_stream_free(stream: *mut HtmlStreamTokenizer) {
+    ffi_guard("dop_html_stream_free", || {
+        if !stream.is_null() {
+            unsafe {
+                drop(Box::from_raw(stream));
+            }
+        }
+    })
+}
+
+/// Tokenize the next chunk of UTF-8 bytes
+///
+/// Returns 0 if the stream is null or already finished.
+#[no_mangle]
+pub extern "C" fn dop_html_stream_feed(stream: *mut HtmlStreamTokenizer, bytes: *const c_uchar, len: usize) -> c_int {
+    ffi_guard("dop_html_stream_feed", || {
+        if stream.is_null() || (bytes.is_null() && len > 0) {
+            return 0;
+        }
+        unsafe {
+            let s = &mut *stream;
+            if s.is_finished() {
+                return 0;
+            }
+            if len > 0 {
+                s.feed(slice::from_raw_parts(bytes, len));
+            }
+            1
+        }
+    })
+}
+
+/// Get the number of tokens waiting to be taken
+#[no_mangle]
+pub extern "C" fn dop_html_stream_pending_count(stream: *const HtmlStreamTokenizer) -> u32 {
+    ffi_guard("dop_html_stream_pending_count", || {
+        if stream.is_null() {
+            return 0;
+        }
+        unsafe { (*stream).pending_count() as u32 }
+    })
+}
+
+/// Move up to `capacity` pending tokens into `out`, returning how many
+///
+/// Each `HtmlToken` is 16 bytes: the type byte, 3 bytes of padding, then the
+/// name ID, value ID and source offset as u32.
+#[no_mangle]
+pub extern "C" fn dop_html_stream_take_tokens(
+    stream: *mut HtmlStreamTokenizer,
+    out: *mut HtmlToken,
+    capacity: u32,
+) -> u32 {
+    ffi_guard("dop_html_stream_take_tokens", || {
+        if stream.is_null() || out.is_null() {
+            return 0;
+        }
+        unsafe {
+            let tokens = (*stream).take_tokens(capacity as usize);
+            ptr::copy_nonoverlapping(tokens.as_ptr(), out, tokens.len());
+            tokens.len() as u32
+        }
+    })
+}
+
+/// Tokenize the remaining input; tokens are still taken afterwards
+#[no_mangle]
+pub extern "C" fn dop_html_stream_finish(stream: *mut HtmlStreamTokenizer) {
+    ffi_guard("dop_html_stream_finish", || {
+        if !stream.is_null() {
+            unsafe {
+                (*stream).finish();
+            }
+        }
+    })
+}
+
+/// Get the 1-based line of a taken token's `source_offset`
+#[no_mangle]
+pub extern "C" fn dop_html_stream_line(stream: *const HtmlStreamTokenizer, offset: u32) -> u32 {
+    ffi_guard("dop_html_stream_line", || {
+        if stream.is_null() {
+            return 0;
+        }
+        unsafe { (*stream).line_col(offset).0 }
+    })
+}
+
+/// Get the 1-based column in bytes of a taken token's `source_offset`
+#[no_mangle]
+pub extern "C" fn dop_html_stream_column(stream: *const HtmlStreamTokenizer, offset: u32) -> u32 {
+    ffi_guard("dop_html_stream_column", || {
+        if stream.is_null() {
+            return 0;
+        }
+        unsafe { (*stream).line_col(offset).1 }
+    })
+}
+
+/// Get string from the stream's string pool
+#[no_mangle]
+pub extern "C" fn dop_html_stream_get_string(stream: *const HtmlStreamTokenizer, id: u32) -> *const c_char {
+    ffi_guard("dop_html_stream_get_string", || {
+        if stream.is_null() {
+            return ptr::null();
+        }
+        unsafe {
+            let s = &*stream;
+            if let Some(text) = s.strings().get(StringId(id)) {
+                if let Ok(c_string) = CString::new(text) {
+                    return c_string.into_raw();
+                }
+            }
+        }
+        ptr::null()
+    })
+}
+
+/// HTML tree parse result handle
+pub struct HtmlTreeResult {
+    tree: HtmlTree,
+}
+
+/// Parse HTML with full HTML5 tree construction and return a result handle
+///
+/// Unlike `dop_html_parse`, misnested tags are repaired and implied elements
+/// (`html`, `head`, `body`, `tbody`) are created. Node IDs are 1-indexed in
+/// document order; 0 means "none".
+#[no_mangle]
+pub extern "C" fn dop_html_parse_tree(html: *const c_char) -> *mut HtmlTreeResult {
+    ffi_guard("dop_html_parse_tree", || {
+        if html.is_null() {
+            return ptr::null_mut();
+        }
+    
+        unsafe {
+            let c_str = CStr::from_ptr(html);
+            if let Ok(html_str) = c_str.to_str() {
+                Box::into_raw(Box::new(HtmlTreeResult {
+                    tree: parse_html_tree(html_str),
+                }))
+            } else {
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Free an HTML tree parse result
+#[no_mangle]
+pub extern "C" fn dop_html_tree_free(result: *mut HtmlTreeResult) {
+    ffi_guard("dop_html_tree_free", || {
+        if !result.is_null() {
+            unsafe {
+                drop(Box::from_raw(result));
             }
         }
+    })
+}
+
+/// Get the number of nodes in the tree
+#[no_mangle]
+pub extern "C" fn dop_html_tree_node_count(result: *const HtmlTreeResult) -> u32 {
+    ffi_guard("dop_html_tree_node_count", || {
+        if result.is_null() {
+            return 0;
+        }
+        unsafe { (*result).tree.len() as u32 }
+    })
+}
+
+/// Look up a field of node `id` (1-indexed)
+unsafe fn tree_node_field<T: Copy>(
+    result: *const HtmlTreeResult,
+    id: u32,
+    field: impl Fn(&HtmlTree) -> &Vec<T>,
+) -> Option<T> {
+    if result.is_null() || id == 0 {
+        return None;
     }
-    ptr::null()
+    field(&(*result).tree).get(id as usize - 1).copied()
+}
+
+/// Get node type (StartTag for elements, Text, Comment or Doctype; 0 if out of range)
+#[no_mangle]
+pub extern "C" fn dop_html_tree_node_type(result: *const HtmlTreeResult, id: u32) -> u8 {
+    ffi_guard("dop_html_tree_node_type", || {
+        unsafe { tree_node_field(result, id, |t| &t.node_types).map_or(0, |t| t as u8) }
+    })
+}
+
+/// Get node name ID (tag or doctype name)
+#[no_mangle]
+pub extern "C" fn dop_html_tree_node_name_id(result: *const HtmlTreeResult, id: u32) -> u32 {
+    ffi_guard("dop_html_tree_node_name_id", || {
+        unsafe { tree_node_field(result, id, |t| &t.name_ids).map_or(0, |s| s.0) }
+    })
+}
+
+/// Get node value ID (text or comment content)
+#[no_mangle]
+pub extern "C" fn dop_html_tree_node_value_id(result: *const HtmlTreeResult, id: u32) -> u32 {
+    ffi_guard("dop_html_tree_node_value_id", || {
+        unsafe { tree_node_field(result, id, |t| &t.value_ids).map_or(0, |s| s.0) }
+    })
+}
+
+/// Get node parent (0 for top-level nodes)
+#[no_mangle]
+pub extern "C" fn dop_html_tree_node_parent(result: *const HtmlTreeResult, id: u32) -> u32 {
+    ffi_guard("dop_html_tree_node_parent", || {
+        unsafe { tree_node_field(result, id, |t| &t.parents).unwrap_or(0) }
+    })
+}
+
+/// Get node first child (0 if none)
+#[no_mangle]
+pub extern "C" fn dop_html_tree_node_first_child(result: *const HtmlTreeResult, id: u32) -> u32 {
+    ffi_guard("dop_html_tree_node_first_child", || {
+        unsafe { tree_node_field(result, id, |t| &t.first_children).unwrap_or(0) }
+    })
+}
+
+/// Get node next sibling (0 if none)
+#[no_mangle]
+pub extern "C" fn dop_html_tree_node_next_sibling(result: *const HtmlTreeResult, id: u32) -> u32 {
+    ffi_guard("dop_html_tree_node_next_sibling", || {
+        unsafe { tree_node_field(result, id, |t| &t.next_siblings).unwrap_or(0) }
+    })
+}
+
+/// Get the number of attributes on a node
+#[no_mangle]
+pub extern "C" fn dop_html_tree_node_attr_count(result: *const HtmlTreeResult, id: u32) -> u32 {
+    ffi_guard("dop_html_tree_node_attr_count", || {
+        unsafe { tree_node_field(result, id, |t| &t.attr_counts).unwrap_or(0) }
+    })
+}
+
+/// Get the name ID of a node's `index`-th attribute
+#[no_mangle]
+pub extern "C" fn dop_html_tree_node_attr_name_id(result: *const HtmlTreeResult, id: u32, index: u32) -> u32 {
+    ffi_guard("dop_html_tree_node_attr_name_id", || {
+        if result.is_null() {
+            return 0;
+        }
+        unsafe {
+            (*result).tree.attributes_of(id).get(index as usize).map_or(0, |a| a.name_id.0)
+        }
+    })
+}
+
+/// Get the value ID of a node's `index`-th attribute
+#[no_mangle]
+pub extern "C" fn dop_html_tree_node_attr_value_id(result: *const HtmlTreeResult, id: u32, index: u32) -> u32 {
+    ffi_guard("dop_html_tree_node_attr_value_id", || {
+        if result.is_null() {
+            return 0;
+        }
+        unsafe {
+            (*result).tree.attributes_of(id).get(index as usize).map_or(0, |a| a.value_id.0)
+        }
+    })
+}
+
+/// Get string from the tree's string pool
+#[no_mangle]
+pub extern "C" fn dop_html_tree_get_string(result: *const HtmlTreeResult, id: u32) -> *const c_char {
+    ffi_guard("dop_html_tree_get_string", || {
+        if result.is_null() {
+            return ptr::null();
+        }
+        unsafe {
+            let r = &*result;
+            if let Some(s) = r.tree.strings.get(StringId(id)) {
+                if let Ok(c_string) = CString::new(s) {
+                    return c_string.into_raw();
+                }
+            }
+        }
+        ptr::null()
+    })
+}
+
+/// Decode character references in raw HTML text (free with dop_string_free)
+///
+/// For text that did not come through the tokenizer, which decodes
+/// references itself.
+#[no_mangle]
+pub extern "C" fn dop_html_decode_entities(text: *const c_char) -> *mut c_char {
+    ffi_guard("dop_html_decode_entities", || {
+        if text.is_null() {
+            return ptr::null_mut();
+        }
+        unsafe {
+            let c_str = CStr::from_ptr(text);
+            if let Ok(raw) = c_str.to_str() {
+                if let Ok(c_string) = CString::new(decode_character_references(raw).text) {
+                    return c_string.into_raw();
+                }
+            }
+        }
+        ptr::null_mut()
+    })
 }
 
 // ============================================================================
@@ -242,144 +728,258 @@ pub struct CssStylesHandle {
 /// Parse inline CSS style
 #[no_mangle]
 pub extern "C" fn dop_css_parse_inline(style_str: *const c_char) -> *mut CssStylesHandle {
-    if style_str.is_null() {
-        return ptr::null_mut();
-    }
+    ffi_guard("dop_css_parse_inline", || {
+        if style_str.is_null() {
+            return ptr::null_mut();
+        }
     
-    unsafe {
-        let c_str = CStr::from_ptr(style_str);
-        if let Ok(str_slice) = c_str.to_str() {
-            let styles = parse_inline_style(str_slice);
-            Box::into_raw(Box::new(CssStylesHandle { styles }))
-        } else {
-            ptr::null_mut()
+        unsafe {
+            let c_str = CStr::from_ptr(style_str);
+            if let Ok(str_slice) = c_str.to_str() {
+                let styles = parse_inline_style(str_slice);
+                Box::into_raw(Box::new(CssStylesHandle { styles }))
+            } else {
+                ptr::null_mut()
+            }
         }
-    }
+    })
 }
 
 /// Free CSS styles
 #[no_mangle]
 pub extern "C" fn dop_css_styles_free(handle: *mut CssStylesHandle) {
-    if !handle.is_null() {
-        unsafe {
-            drop(Box::from_raw(handle));
+    ffi_guard("dop_css_styles_free", || {
+        if !handle.is_null() {
+            unsafe {
+                drop(Box::from_raw(handle));
+            }
         }
-    }
+    })
 }
 
 // CSS style getters
 #[no_mangle]
 pub extern "C" fn dop_css_get_position(handle: *const CssStylesHandle) -> u8 {
-    if handle.is_null() { return 0; }
-    unsafe { (*handle).styles.position }
+    ffi_guard("dop_css_get_position", || {
+        if handle.is_null() { return 0; }
+        unsafe { (*handle).styles.position }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_display(handle: *const CssStylesHandle) -> u8 {
-    if handle.is_null() { return 1; }
-    unsafe { (*handle).styles.display }
+    ffi_guard("dop_css_get_display", || {
+        if handle.is_null() { return 1; }
+        unsafe { (*handle).styles.display }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_width(handle: *const CssStylesHandle) -> c_float {
-    if handle.is_null() { return 0.0; }
-    unsafe { (*handle).styles.width.value }
+    ffi_guard("dop_css_get_width", || {
+        if handle.is_null() { return 0.0; }
+        unsafe { (*handle).styles.width.value }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_width_is_auto(handle: *const CssStylesHandle) -> c_int {
-    if handle.is_null() { return 1; }
-    unsafe { if (*handle).styles.width.is_auto { 1 } else { 0 } }
+    ffi_guard("dop_css_get_width_is_auto", || {
+        if handle.is_null() { return 1; }
+        unsafe { if (*handle).styles.width.is_auto { 1 } else { 0 } }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_height(handle: *const CssStylesHandle) -> c_float {
-    if handle.is_null() { return 0.0; }
-    unsafe { (*handle).styles.height.value }
+    ffi_guard("dop_css_get_height", || {
+        if handle.is_null() { return 0.0; }
+        unsafe { (*handle).styles.height.value }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_height_is_auto(handle: *const CssStylesHandle) -> c_int {
-    if handle.is_null() { return 1; }
-    unsafe { if (*handle).styles.height.is_auto { 1 } else { 0 } }
+    ffi_guard("dop_css_get_height_is_auto", || {
+        if handle.is_null() { return 1; }
+        unsafe { if (*handle).styles.height.is_auto { 1 } else { 0 } }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_margin_top(handle: *const CssStylesHandle) -> c_float {
-    if handle.is_null() { return 0.0; }
-    unsafe { (*handle).styles.margin_top }
+    ffi_guard("dop_css_get_margin_top", || {
+        if handle.is_null() { return 0.0; }
+        unsafe { (*handle).styles.margin_top }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_margin_right(handle: *const CssStylesHandle) -> c_float {
-    if handle.is_null() { return 0.0; }
-    unsafe { (*handle).styles.margin_right }
+    ffi_guard("dop_css_get_margin_right", || {
+        if handle.is_null() { return 0.0; }
+        unsafe { (*handle).styles.margin_right }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_margin_bottom(handle: *const CssStylesHandle) -> c_float {
-    if handle.is_null() { return 0.0; }
-    unsafe { (*handle).styles.margin_bottom }
+    ffi_guard("dop_css_get_margin_bottom", || {
+        if handle.is_null() { return 0.0; }
+        unsafe { (*handle).styles.margin_bottom }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_margin_left(handle: *const CssStylesHandle) -> c_float {
-    if handle.is_null() { return 0.0; }
-    unsafe { (*handle).styles.margin_left }
+    ffi_guard("dop_css_get_margin_left", || {
+        if handle.is_null() { return 0.0; }
+        unsafe { (*handle).styles.margin_left }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_padding_top(handle: *const CssStylesHandle) -> c_float {
-    if handle.is_null() { return 0.0; }
-    unsafe { (*handle).styles.padding_top }
+    ffi_guard("dop_css_get_padding_top", || {
+        if handle.is_null() { return 0.0; }
+        unsafe { (*handle).styles.padding_top }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_padding_right(handle: *const CssStylesHandle) -> c_float {
-    if handle.is_null() { return 0.0; }
-    unsafe { (*handle).styles.padding_right }
+    ffi_guard("dop_css_get_padding_right", || {
+        if handle.is_null() { return 0.0; }
+        unsafe { (*handle).styles.padding_right }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_padding_bottom(handle: *const CssStylesHandle) -> c_float {
-    if handle.is_null() { return 0.0; }
-    unsafe { (*handle).styles.padding_bottom }
+    ffi_guard("dop_css_get_padding_bottom", || {
+        if handle.is_null() { return 0.0; }
+        unsafe { (*handle).styles.padding_bottom }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_padding_left(handle: *const CssStylesHandle) -> c_float {
-    if handle.is_null() { return 0.0; }
-    unsafe { (*handle).styles.padding_left }
+    ffi_guard("dop_css_get_padding_left", || {
+        if handle.is_null() { return 0.0; }
+        unsafe { (*handle).styles.padding_left }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_background_r(handle: *const CssStylesHandle) -> c_uchar {
-    if handle.is_null() { return 0; }
-    unsafe { (*handle).styles.background_color.r }
+    ffi_guard("dop_css_get_background_r", || {
+        if handle.is_null() { return 0; }
+        unsafe { (*handle).styles.background_color.r }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_background_g(handle: *const CssStylesHandle) -> c_uchar {
-    if handle.is_null() { return 0; }
-    unsafe { (*handle).styles.background_color.g }
+    ffi_guard("dop_css_get_background_g", || {
+        if handle.is_null() { return 0; }
+        unsafe { (*handle).styles.background_color.g }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_background_b(handle: *const CssStylesHandle) -> c_uchar {
-    if handle.is_null() { return 0; }
-    unsafe { (*handle).styles.background_color.b }
+    ffi_guard("dop_css_get_background_b", || {
+        if handle.is_null() { return 0; }
+        unsafe { (*handle).styles.background_color.b }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_background_a(handle: *const CssStylesHandle) -> c_uchar {
-    if handle.is_null() { return 0; }
-    unsafe { (*handle).styles.background_color.a }
+    ffi_guard("dop_css_get_background_a", || {
+        if handle.is_null() { return 0; }
+        unsafe { (*handle).styles.background_color.a }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn dop_css_get_has_background(handle: *const CssStylesHandle) -> c_int {
-    if handle.is_null() { return 0; }
-    unsafe { if (*handle).styles.has_background { 1 } else { 0 } }
+    ffi_guard("dop_css_get_has_background", || {
+        if handle.is_null() { return 0; }
+        unsafe { if (*handle).styles.has_background { 1 } else { 0 } }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn dop_css_get_flex_direction(handle: *const CssStylesHandle) -> u8 {
+    ffi_guard("dop_css_get_flex_direction", || {
+        if handle.is_null() { return 0; }
+        unsafe { (*handle).styles.flex_direction }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn dop_css_get_justify_content(handle: *const CssStylesHandle) -> u8 {
+    ffi_guard("dop_css_get_justify_content", || {
+        if handle.is_null() { return 0; }
+        unsafe { (*handle).styles.justify_content }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn dop_css_get_align_items(handle: *const CssStylesHandle) -> u8 {
+    ffi_guard("dop_css_get_align_items", || {
+        if handle.is_null() { return 0; }
+        unsafe { (*handle).styles.align_items }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn dop_css_get_row_gap(handle: *const CssStylesHandle) -> c_float {
+    ffi_guard("dop_css_get_row_gap", || {
+        if handle.is_null() { return 0.0; }
+        unsafe { (*handle).styles.row_gap }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn dop_css_get_column_gap(handle: *const CssStylesHandle) -> c_float {
+    ffi_guard("dop_css_get_column_gap", || {
+        if handle.is_null() { return 0.0; }
+        unsafe { (*handle).styles.column_gap }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn dop_css_get_flex_grow(handle: *const CssStylesHandle) -> c_float {
+    ffi_guard("dop_css_get_flex_grow", || {
+        if handle.is_null() { return 0.0; }
+        unsafe { (*handle).styles.flex_grow }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn dop_css_get_flex_shrink(handle: *const CssStylesHandle) -> c_float {
+    ffi_guard("dop_css_get_flex_shrink", || {
+        if handle.is_null() { return 0.0; }
+        unsafe { (*handle).styles.flex_shrink }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn dop_css_get_flex_basis(handle: *const CssStylesHandle) -> c_float {
+    ffi_guard("dop_css_get_flex_basis", || {
+        if handle.is_null() { return 0.0; }
+        unsafe { (*handle).styles.flex_basis.value }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn dop_css_get_flex_basis_is_auto(handle: *const CssStylesHandle) -> c_int {
+    ffi_guard("dop_css_get_flex_basis_is_auto", || {
+        if handle.is_null() { return 1; }
+        unsafe { if (*handle).styles.flex_basis.is_auto { 1 } else { 0 } }
+    })
 }
 
 /// Parse a color string and return RGBA values
@@ -391,20 +991,148 @@ pub extern "C" fn dop_css_parse_color(
     b: *mut c_uchar,
     a: *mut c_uchar,
 ) {
-    if color_str.is_null() || r.is_null() || g.is_null() || b.is_null() || a.is_null() {
-        return;
-    }
+    ffi_guard("dop_css_parse_color", || {
+        if color_str.is_null() || r.is_null() || g.is_null() || b.is_null() || a.is_null() {
+            return;
+        }
     
-    unsafe {
-        let c_str = CStr::from_ptr(color_str);
-        if let Ok(str_slice) = c_str.to_str() {
-            let color = parse_color(str_slice);
-            *r = color.r;
-            *g = color.g;
-            *b = color.b;
-            *a = color.a;
+        unsafe {
+            let c_str = CStr::from_ptr(color_str);
+            if let Ok(str_slice) = c_str.to_str() {
+                let color = parse_color(str_slice);
+                *r = color.r;
+                *g = color.g;
+                *b = color.b;
+                *a = color.a;
+            }
         }
-    }
+    })
+}
+
+/// Reset system colors to the built-in light (0) or dark (1) theme
+#[no_mangle]
+pub extern "C" fn dop_css_set_color_theme(dark: c_int) {
+    ffi_guard("dop_css_set_color_theme", || {
+        set_color_theme(if dark != 0 { ColorTheme::DARK } else { ColorTheme::LIGHT });
+    })
+}
+
+/// Override a single system color (e.g. "Canvas", "LinkText")
+/// Returns 1 on success, 0 if the name is not a system color
+#[no_mangle]
+pub extern "C" fn dop_css_set_system_color(
+    name: *const c_char,
+    r: c_uchar,
+    g: c_uchar,
+    b: c_uchar,
+    a: c_uchar,
+) -> c_int {
+    ffi_guard("dop_css_set_system_color", || {
+        if name.is_null() {
+            return 0;
+        }
+
+        let name = unsafe { CStr::from_ptr(name) };
+        let Ok(name) = name.to_str() else {
+            return 0;
+        };
+        let mut theme = color_theme();
+        match theme.system_color_mut(&name.to_lowercase()) {
+            Some(color) => {
+                *color = Color::new(r, g, b, a);
+                set_color_theme(theme);
+                1
+            }
+            None => 0,
+        }
+    })
+}
+
+/// Parse a stylesheet into rules for dop_css_match_rules
+///
+/// Returns null on invalid input. Free with dop_css_stylesheet_free.
+#[no_mangle]
+pub extern "C" fn dop_css_parse_stylesheet(css: *const c_char) -> *mut Vec<CssRule> {
+    ffi_guard("dop_css_parse_stylesheet", || {
+        if css.is_null() {
+            return ptr::null_mut();
+        }
+
+        unsafe {
+            let c_str = CStr::from_ptr(css);
+            match c_str.to_str() {
+                Ok(css_str) => Box::into_raw(Box::new(parse_stylesheet(css_str))),
+                Err(_) => ptr::null_mut(),
+            }
+        }
+    })
+}
+
+/// Free a parsed stylesheet
+#[no_mangle]
+pub extern "C" fn dop_css_stylesheet_free(rules: *mut Vec<CssRule>) {
+    ffi_guard("dop_css_stylesheet_free", || {
+        if !rules.is_null() {
+            unsafe {
+                drop(Box::from_raw(rules));
+            }
+        }
+    })
+}
+
+/// Get the number of rules in a stylesheet
+#[no_mangle]
+pub extern "C" fn dop_css_stylesheet_rule_count(rules: *const Vec<CssRule>) -> u32 {
+    ffi_guard("dop_css_stylesheet_rule_count", || {
+        if rules.is_null() { return 0; }
+        unsafe { (*rules).len() as u32 }
+    })
+}
+
+/// Compute the styles a stylesheet gives an element
+///
+/// `tags`, `ids` and `classes` each hold `count` strings describing the
+/// element path from the root to the styled element (last). `ids` and
+/// `classes` may be null, as may any of their entries. Free the result with
+/// dop_css_styles_free.
+#[no_mangle]
+pub extern "C" fn dop_css_match_rules(
+    rules: *const Vec<CssRule>,
+    tags: *const *const c_char,
+    ids: *const *const c_char,
+    classes: *const *const c_char,
+    count: u32,
+) -> *mut CssStylesHandle {
+    ffi_guard("dop_css_match_rules", || {
+        if rules.is_null() || tags.is_null() || count == 0 {
+            return ptr::null_mut();
+        }
+
+        unsafe {
+            let string_at = |array: *const *const c_char, i: usize| -> Option<&str> {
+                if array.is_null() {
+                    return Some("");
+                }
+                let s = *array.add(i);
+                if s.is_null() {
+                    Some("")
+                } else {
+                    CStr::from_ptr(s).to_str().ok()
+                }
+            };
+
+            let mut path = Vec::with_capacity(count as usize);
+            for i in 0..count as usize {
+                let tag = if (*tags.add(i)).is_null() { None } else { string_at(tags, i) };
+                match (tag, string_at(ids, i), string_at(classes, i)) {
+                    (Some(tag), Some(id), Some(class)) => path.push(Element { tag, id, class }),
+                    _ => return ptr::null_mut(),
+                }
+            }
+            let styles = match_rules(&*rules, &path);
+            Box::into_raw(Box::new(CssStylesHandle { styles }))
+        }
+    })
 }
 
 /// Parse a length string
@@ -415,18 +1143,49 @@ pub extern "C" fn dop_css_parse_length(
     value: *mut c_float,
     is_auto: *mut c_int,
 ) {
-    if length_str.is_null() || value.is_null() || is_auto.is_null() {
-        return;
-    }
+    ffi_guard("dop_css_parse_length", || {
+        if length_str.is_null() || value.is_null() || is_auto.is_null() {
+            return;
+        }
     
-    unsafe {
-        let c_str = CStr::from_ptr(length_str);
-        if let Ok(str_slice) = c_str.to_str() {
-            let len = parse_length(str_slice, container_size);
-            *value = len.value;
-            *is_auto = if len.is_auto { 1 } else { 0 };
+        unsafe {
+            let c_str = CStr::from_ptr(length_str);
+            if let Ok(str_slice) = c_str.to_str() {
+                let len = parse_length(str_slice, container_size);
+                *value = len.value;
+                *is_auto = if len.is_auto { 1 } else { 0 };
+            }
         }
-    }
+    })
+}
+
+/// Parse a length string, resolving font and viewport relative units
+#[no_mangle]
+pub extern "C" fn dop_css_parse_length_ctx(
+    length_str: *const c_char,
+    container_size: c_float,
+    font_size: c_float,
+    root_font_size: c_float,
+    viewport_w: c_float,
+    viewport_h: c_float,
+    value: *mut c_float,
+    is_auto: *mut c_int,
+) {
+    ffi_guard("dop_css_parse_length_ctx", || {
+        if length_str.is_null() || value.is_null() || is_auto.is_null() {
+            return;
+        }
+    
+        let ctx = LengthContext { font_size, root_font_size, viewport_w, viewport_h };
+        unsafe {
+            let c_str = CStr::from_ptr(length_str);
+            if let Ok(str_slice) = c_str.to_str() {
+                let len = parse_length_ctx(str_slice, container_size, &ctx);
+                *value = len.value;
+                *is_auto = if len.is_auto { 1 } else { 0 };
+            }
+        }
+    })
 }
 
 // ============================================================================
@@ -436,33 +1195,41 @@ pub extern "C" fn dop_css_parse_length(
 /// Create a new compiler context
 #[no_mangle]
 pub extern "C" fn dop_compiler_new() -> *mut CompilerContext {
-    Box::into_raw(Box::new(CompilerContext::new()))
+    ffi_guard("dop_compiler_new", || {
+        Box::into_raw(Box::new(CompilerContext::new()))
+    })
 }
 
 /// Free a compiler context
 #[no_mangle]
 pub extern "C" fn dop_compiler_free(ctx: *mut CompilerContext) {
-    if !ctx.is_null() {
-        unsafe {
-            drop(Box::from_raw(ctx));
+    ffi_guard("dop_compiler_free", || {
+        if !ctx.is_null() {
+            unsafe {
+                drop(Box::from_raw(ctx));
+            }
         }
-    }
+    })
 }
 
 /// Create a new node table
 #[no_mangle]
 pub extern "C" fn dop_node_table_new() -> *mut NodeTable {
-    Box::into_raw(Box::new(NodeTable::new()))
+    ffi_guard("dop_node_table_new", || {
+        Box::into_raw(Box::new(NodeTable::new()))
+    })
 }
 
 /// Free a node table
 #[no_mangle]
 pub extern "C" fn dop_node_table_free(table: *mut NodeTable) {
-    if !table.is_null() {
-        unsafe {
-            drop(Box::from_raw(table));
+    ffi_guard("dop_node_table_free", || {
+        if !table.is_null() {
+            unsafe {
+                drop(Box::from_raw(table));
+            }
         }
-    }
+    })
 }
 
 /// Create a node in the table
@@ -473,75 +1240,129 @@ pub extern "C" fn dop_node_table_create(
     parent: u32,
     style_id: u32,
 ) -> u32 {
-    if table.is_null() {
-        return 0;
-    }
-    unsafe {
-        let nt = match node_type {
-            0 => NodeType::Root,
-            1 => NodeType::Stack,
-            2 => NodeType::Grid,
-            3 => NodeType::Scroll,
-            4 => NodeType::Rect,
-            5 => NodeType::Paragraph,
-            6 => NodeType::Span,
-            7 => NodeType::Link,
-            8 => NodeType::TextCluster,
-            _ => NodeType::Root,
-        };
-        (*table).create_node(nt, parent, style_id)
-    }
+    ffi_guard("dop_node_table_create", || {
+        if table.is_null() {
+            return 0;
+        }
+        unsafe {
+            let nt = NodeType::from_u8(node_type);
+            (*table).create_node(nt, parent, style_id)
+        }
+    })
+}
+
+/// Create `count` nodes in one call from packed arrays
+/// Returns the ID of the first created node (IDs are consecutive), or 0 on failure
+#[no_mangle]
+pub extern "C" fn dop_node_table_create_bulk(
+    table: *mut NodeTable,
+    node_types: *const u8,
+    parents: *const u32,
+    style_ids: *const u32,
+    count: u32,
+) -> u32 {
+    ffi_guard("dop_node_table_create_bulk", || {
+        if table.is_null() || node_types.is_null() || parents.is_null() || style_ids.is_null() || count == 0 {
+            return 0;
+        }
+        unsafe {
+            let n = count as usize;
+            let types: Vec<NodeType> = slice::from_raw_parts(node_types, n)
+                .iter()
+                .map(|&t| NodeType::from_u8(t))
+                .collect();
+            let parents = slice::from_raw_parts(parents, n);
+            let style_ids = slice::from_raw_parts(style_ids, n);
+            (*table).create_nodes(&types, parents, style_ids)
+        }
+    })
+}
+
+/// Reserve capacity for additional nodes
+#[no_mangle]
+pub extern "C" fn dop_node_table_reserve(table: *mut NodeTable, additional: u32) {
+    ffi_guard("dop_node_table_reserve", || {
+        if !table.is_null() {
+            unsafe {
+                (*table).reserve(additional as usize);
+            }
+        }
+    })
 }
 
 /// Get node count
 #[no_mangle]
 pub extern "C" fn dop_node_table_len(table: *const NodeTable) -> u32 {
-    if table.is_null() {
-        return 0;
-    }
-    unsafe { (*table).len() as u32 }
+    ffi_guard("dop_node_table_len", || {
+        if table.is_null() {
+            return 0;
+        }
+        unsafe { (*table).len() as u32 }
+    })
 }
 
 /// Create a new property table
 #[no_mangle]
 pub extern "C" fn dop_property_table_new() -> *mut PropertyTable {
-    Box::into_raw(Box::new(PropertyTable::new()))
+    ffi_guard("dop_property_table_new", || {
+        Box::into_raw(Box::new(PropertyTable::new()))
+    })
 }
 
 /// Free a property table
 #[no_mangle]
 pub extern "C" fn dop_property_table_free(table: *mut PropertyTable) {
-    if !table.is_null() {
-        unsafe {
-            drop(Box::from_raw(table));
+    ffi_guard("dop_property_table_free", || {
+        if !table.is_null() {
+            unsafe {
+                drop(Box::from_raw(table));
+            }
         }
-    }
+    })
 }
 
 /// Resize property table
 #[no_mangle]
 pub extern "C" fn dop_property_table_resize(table: *mut PropertyTable, n: u32) {
-    if !table.is_null() {
-        unsafe {
-            (*table).resize(n as usize);
+    ffi_guard("dop_property_table_resize", || {
+        if !table.is_null() {
+            unsafe {
+                (*table).resize(n as usize);
+            }
         }
-    }
+    })
+}
+
+/// Reserve property table capacity for additional nodes
+#[no_mangle]
+pub extern "C" fn dop_property_table_reserve(table: *mut PropertyTable, additional: u32) {
+    ffi_guard("dop_property_table_reserve", || {
+        if !table.is_null() {
+            unsafe {
+                (*table).reserve(additional as usize);
+            }
+        }
+    })
 }
 
 /// Create a new text shaper
 #[no_mangle]
 pub extern "C" fn dop_text_shaper_new() -> *mut TextShaper {
-    Box::into_raw(Box::new(TextShaper::new()))
+    ffi_guard("dop_text_shaper_new", || {
+        Box::into_raw(Box::new(TextShaper::new()))
+    })
 }
 
 /// Free a text shaper
 #[no_mangle]
 pub extern "C" fn dop_text_shaper_free(shaper: *mut TextShaper) {
-    if !shaper.is_null() {
-        unsafe {
-            drop(Box::from_raw(shaper));
+    ffi_guard("dop_text_shaper_free", || {
+        if !shaper.is_null() {
+            unsafe {
+                drop(Box::from_raw(shaper));
+            }
         }
-    }
+    })
 }
 
 /// Shape paragraph result handle
@@ -556,57 +1377,69 @@ pub extern "C" fn dop_text_shaper_shape(
     text: *const c_char,
     max_width: c_float,
 ) -> *mut ShapedParagraphHandle {
-    if shaper.is_null() || text.is_null() {
-        return ptr::null_mut();
-    }
+    ffi_guard("dop_text_shaper_shape", || {
+        if shaper.is_null() || text.is_null() {
+            return ptr::null_mut();
+        }
     
-    unsafe {
-        let c_str = CStr::from_ptr(text);
-        if let Ok(text_str) = c_str.to_str() {
-            let result = (*shaper).shape_paragraph(text_str, max_width);
-            Box::into_raw(Box::new(ShapedParagraphHandle { result }))
-        } else {
-            ptr::null_mut()
+        unsafe {
+            let c_str = CStr::from_ptr(text);
+            if let Ok(text_str) = c_str.to_str() {
+                let result = (*shaper).shape_paragraph(text_str, max_width);
+                Box::into_raw(Box::new(ShapedParagraphHandle { result }))
+            } else {
+                ptr::null_mut()
+            }
         }
-    }
+    })
 }
 
 /// Free shaped paragraph
 #[no_mangle]
 pub extern "C" fn dop_shaped_paragraph_free(handle: *mut ShapedParagraphHandle) {
-    if !handle.is_null() {
-        unsafe {
-            drop(Box::from_raw(handle));
+    ffi_guard("dop_shaped_paragraph_free", || {
+        if !handle.is_null() {
+            unsafe {
+                drop(Box::from_raw(handle));
+            }
         }
-    }
+    })
 }
 
 /// Get shaped paragraph width
 #[no_mangle]
 pub extern "C" fn dop_shaped_paragraph_width(handle: *const ShapedParagraphHandle) -> c_float {
-    if handle.is_null() { return 0.0; }
-    unsafe { (*handle).result.width }
+    ffi_guard("dop_shaped_paragraph_width", || {
+        if handle.is_null() { return 0.0; }
+        unsafe { (*handle).result.width }
+    })
 }
 
 /// Get shaped paragraph height
 #[no_mangle]
 pub extern "C" fn dop_shaped_paragraph_height(handle: *const ShapedParagraphHandle) -> c_float {
-    if handle.is_null() { return 0.0; }
-    unsafe { (*handle).result.height }
+    ffi_guard("dop_shaped_paragraph_height", || {
+        if handle.is_null() { return 0.0; }
+        unsafe { (*handle).result.height }
+    })
 }
 
 /// Get shaped paragraph line count
 #[no_mangle]
 pub extern "C" fn dop_shaped_paragraph_line_count(handle: *const ShapedParagraphHandle) -> u32 {
-    if handle.is_null() { return 0; }
-    unsafe { (*handle).result.line_count }
+    ffi_guard("dop_shaped_paragraph_line_count", || {
+        if handle.is_null() { return 0; }
+        unsafe { (*handle).result.line_count }
+    })
 }
 
 /// Get shaped paragraph text hash
 #[no_mangle]
 pub extern "C" fn dop_shaped_paragraph_text_hash(handle: *const ShapedParagraphHandle) -> u64 {
-    if handle.is_null() { return 0; }
-    unsafe { (*handle).result.text_hash }
+    ffi_guard("dop_shaped_paragraph_text_hash", || {
+        if handle.is_null() { return 0; }
+        unsafe { (*handle).result.text_hash }
+    })
 }
 
 // ============================================================================
@@ -616,17 +1449,21 @@ pub extern "C" fn dop_shaped_paragraph_text_hash(handle: *const ShapedParagraphH
 /// Create a new compiled unit
 #[no_mangle]
 pub extern "C" fn dop_compiled_unit_new() -> *mut CompiledUnit {
-    Box::into_raw(Box::new(CompiledUnit::new()))
+    ffi_guard("dop_compiled_unit_new", || {
+        Box::into_raw(Box::new(CompiledUnit::new()))
+    })
 }
 
 /// Free a compiled unit
 #[no_mangle]
 pub extern "C" fn dop_compiled_unit_free(unit: *mut CompiledUnit) {
-    if !unit.is_null() {
-        unsafe {
-            drop(Box::from_raw(unit));
+    ffi_guard("dop_compiled_unit_free", || {
+        if !unit.is_null() {
+            unsafe {
+                drop(Box::from_raw(unit));
+            }
         }
-    }
+    })
 }
 
 /// Write compiled unit to binary buffer
@@ -636,24 +1473,26 @@ pub extern "C" fn dop_compiled_unit_write_binary(
     buffer: *mut *mut c_uchar,
     length: *mut u32,
 ) -> c_int {
-    if unit.is_null() || buffer.is_null() || length.is_null() {
-        return 0;
-    }
-    
-    unsafe {
-        let bytes = (*unit).write_binary();
-        *length = bytes.len() as u32;
-        
-        // Allocate buffer for the data
-        let ptr = libc::malloc(bytes.len()) as *mut c_uchar;
-        if ptr.is_null() {
+    ffi_guard("dop_compiled_unit_write_binary", || {
+        if unit.is_null() || buffer.is_null() || length.is_null() {
             return 0;
         }
+    
+        unsafe {
+            let bytes = (*unit).write_binary();
+            *length = bytes.len() as u32;
         
-        ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
-        *buffer = ptr;
-        1
-    }
+            // Allocate buffer for the data
+            let ptr = libc::malloc(bytes.len()) as *mut c_uchar;
+            if ptr.is_null() {
+                return 0;
+            }
+        
+            ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
+            *buffer = ptr;
+            1
+        }
+    })
 }
 
 /// Read compiled unit from binary buffer
@@ -662,47 +1501,121 @@ pub extern "C" fn dop_compiled_unit_read_binary(
     data: *const c_uchar,
     length: u32,
 ) -> *mut CompiledUnit {
-    if data.is_null() || length == 0 {
-        return ptr::null_mut();
-    }
+    ffi_guard("dop_compiled_unit_read_binary", || {
+        if data.is_null() || length == 0 {
+            return ptr::null_mut();
+        }
     
-    unsafe {
-        let slice = slice::from_raw_parts(data, length as usize);
-        if let Some(unit) = CompiledUnit::read_binary(slice) {
-            Box::into_raw(Box::new(unit))
-        } else {
-            ptr::null_mut()
+        unsafe {
+            let slice = slice::from_raw_parts(data, length as usize);
+            if let Some(unit) = CompiledUnit::read_binary(slice) {
+                Box::into_raw(Box::new(unit))
+            } else {
+                ptr::null_mut()
+            }
         }
-    }
+    })
 }
 
 /// Free binary buffer allocated by dop_compiled_unit_write_binary
 #[no_mangle]
 pub extern "C" fn dop_binary_buffer_free(buffer: *mut c_uchar) {
-    if !buffer.is_null() {
-        unsafe {
-            libc::free(buffer as *mut libc::c_void);
+    ffi_guard("dop_binary_buffer_free", || {
+        if !buffer.is_null() {
+            unsafe {
+                libc::free(buffer as *mut libc::c_void);
+            }
         }
-    }
+    })
 }
 
 /// Get compiled unit node count
 #[no_mangle]
 pub extern "C" fn dop_compiled_unit_node_count(unit: *const CompiledUnit) -> u32 {
-    if unit.is_null() { return 0; }
-    unsafe { (*unit).nodes.len() as u32 }
+    ffi_guard("dop_compiled_unit_node_count", || {
+        if unit.is_null() { return 0; }
+        unsafe { (*unit).nodes.len() as u32 }
+    })
 }
 
 /// Get compiled unit style count
 #[no_mangle]
 pub extern "C" fn dop_compiled_unit_style_count(unit: *const CompiledUnit) -> u32 {
-    if unit.is_null() { return 0; }
-    unsafe { (*unit).styles.len() as u32 }
+    ffi_guard("dop_compiled_unit_style_count", || {
+        if unit.is_null() { return 0; }
+        unsafe { (*unit).styles.len() as u32 }
+    })
 }
 
 /// Get compiled unit checksum
 #[no_mangle]
 pub extern "C" fn dop_compiled_unit_checksum(unit: *const CompiledUnit) -> u64 {
-    if unit.is_null() { return 0; }
-    unsafe { (*unit).checksum }
+    ffi_guard("dop_compiled_unit_checksum", || {
+        if unit.is_null() { return 0; }
+        unsafe { (*unit).checksum }
+    })
+}
+
+/// Build a Content IR unit from HTML
+///
+/// Returns null on invalid input. Free with dop_compiled_unit_free.
+#[no_mangle]
+pub extern "C" fn dop_html_to_content_ir(html: *const c_char) -> *mut CompiledUnit {
+    ffi_guard("dop_html_to_content_ir", || {
+        if html.is_null() {
+            return ptr::null_mut();
+        }
+
+        unsafe {
+            let c_str = CStr::from_ptr(html);
+            match c_str.to_str() {
+                Ok(html_str) => Box::into_raw(Box::new(html_to_content_ir(html_str))),
+                Err(_) => ptr::null_mut(),
+            }
+        }
+    })
+}
+
+/// Get the type of a node in a compiled unit (1-based ID)
+#[no_mangle]
+pub extern "C" fn dop_compiled_unit_node_type(unit: *const CompiledUnit, node_id: u32) -> u8 {
+    ffi_guard("dop_compiled_unit_node_type", || {
+        if unit.is_null() || node_id == 0 { return 0; }
+        unsafe {
+            let unit = &*unit;
+            unit.nodes.node_types.get(node_id as usize - 1).map_or(0, |&t| t as u8)
+        }
+    })
+}
+
+/// Get the parent of a node in a compiled unit (0 for the root)
+#[no_mangle]
+pub extern "C" fn dop_compiled_unit_node_parent(unit: *const CompiledUnit, node_id: u32) -> u32 {
+    ffi_guard("dop_compiled_unit_node_parent", || {
+        if unit.is_null() || node_id == 0 { return 0; }
+        unsafe {
+            let unit = &*unit;
+            unit.nodes.parents.get(node_id as usize - 1).copied().unwrap_or(0)
+        }
+    })
+}
+
+/// Get the text of a node in a compiled unit
+///
+/// Returns null if the node has no text. Free with dop_string_free.
+#[no_mangle]
+pub extern "C" fn dop_compiled_unit_node_text(unit: *const CompiledUnit, node_id: u32) -> *mut c_char {
+    ffi_guard("dop_compiled_unit_node_text", || {
+        if unit.is_null() || node_id == 0 {
+            return ptr::null_mut();
+        }
+
+        unsafe {
+            let unit = &*unit;
+            unit.properties.text_id.get(node_id as usize - 1)
+                .and_then(|&id| unit.strings.get(id))
+                .and_then(|text| CString::new(text).ok())
+                .map_or(ptr::null_mut(), CString::into_raw)
+        }
+    })
 }
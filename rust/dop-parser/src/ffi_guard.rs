@@ -0,0 +1,104 @@
+//! Panic boundary for the C API
+//!
+//! A panic unwinding out of an `extern "C"` function aborts the host process.
+//! Every exported function runs its body through `ffi_guard`, which catches
+//! the panic, records its message as the thread's last error and returns a
+//! neutral value instead (0 or null). Hosts read the message with
+//! `dop_parser_last_error`.
+//!
+//! Builds with `panic = "abort"` cannot catch panics; they still abort.
+
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::panic::{self, AssertUnwindSafe};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Record `message` as the calling thread's last error
+pub fn set_last_error(message: impl Into<String>) {
+    // Interior NULs would truncate the C string, so they are replaced
+    let message = message.into().replace('\0', " ");
+    let message = CString::new(message).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+/// Clear the calling thread's last error
+pub fn clear_last_error() {
+    LAST_ERROR.with(|e| *e.borrow_mut() = None);
+}
+
+/// Pointer to the calling thread's last error message, or null if none
+///
+/// The pointer stays valid until the next error is recorded or cleared on this thread.
+pub fn last_error_ptr() -> *const std::ffi::c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(std::ptr::null(), |s| s.as_ptr()))
+}
+
+/// Value an FFI function returns when its body panicked
+pub trait FfiDefault {
+    fn ffi_default() -> Self;
+}
+
+macro_rules! ffi_default_zero {
+    ($($ty:ty),*) => {
+        $(impl FfiDefault for $ty {
+            fn ffi_default() -> Self {
+                Self::default()
+            }
+        })*
+    };
+}
+
+ffi_default_zero!((), bool, u8, i8, u16, i16, u32, i32, u64, i64, usize, isize, f32, f64);
+
+impl<T> FfiDefault for *const T {
+    fn ffi_default() -> Self {
+        std::ptr::null()
+    }
+}
+
+impl<T> FfiDefault for *mut T {
+    fn ffi_default() -> Self {
+        std::ptr::null_mut()
+    }
+}
+
+/// Run an FFI function body, turning a panic into the last error and a neutral return value
+pub fn ffi_guard<R: FfiDefault>(name: &str, body: impl FnOnce() -> R) -> R {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(value) => value,
+        Err(payload) => {
+            let reason = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            log::error!("{} panicked: {}", name, reason);
+            set_last_error(format!("{} panicked: {}", name, reason));
+            R::ffi_default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::{dop_parser_clear_last_error, dop_parser_last_error};
+    use std::ffi::CStr;
+
+    #[test]
+    fn test_ffi_guard_recovers_from_panics() {
+        dop_parser_clear_last_error();
+        assert!(dop_parser_last_error().is_null());
+        let handle: *mut u8 = ffi_guard("make_handle", || panic!("out of nodes"));
+        assert!(handle.is_null());
+        assert_eq!(ffi_guard("count", || -> u32 { panic!("{}", 7) }), 0);
+        let message = unsafe { CStr::from_ptr(dop_parser_last_error()) }.to_str().unwrap();
+        assert_eq!(message, "count panicked: 7");
+        dop_parser_clear_last_error();
+        assert!(dop_parser_last_error().is_null());
+        assert_eq!(ffi_guard("ok", || 3u32), 3);
+    }
+}
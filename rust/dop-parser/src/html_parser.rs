@@ -4,12 +4,15 @@
 //! Generates a flat token tape for cache-efficient DOM construction.
 
 use std::cell::RefCell;
+use std::collections::VecDeque;
 
 use html5ever::tokenizer::{
-    BufferQueue, Tag, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts,
+    BufferQueue, Tag, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts, TokenizerResult,
 };
 use html5ever::Attribute;
+use markup5ever::data::{C1_REPLACEMENTS, NAMED_ENTITIES};
 use tendril::StrTendril;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
 
 use crate::string_interner::{StringId, StringPool};
 
@@ -39,9 +42,38 @@ pub struct HtmlToken {
     /// Interned string ID for value (text content, attribute value)
     pub value_id: StringId,
     /// Byte offset in original source (for error reporting)
+    ///
+    /// Markup tokens point at their `<`, text at its first non-whitespace
+    /// character; attributes share their tag's offset.
     pub source_offset: u32,
 }
 
+/// Plain-old-data copy of `HtmlToken` for bulk export over FFI
+///
+/// 16 bytes: the type byte, 3 zero bytes, then the name ID, value ID and
+/// source offset as u32, so an array can be wrapped without conversion.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+pub struct HtmlTokenFFI {
+    pub token_type: u8,
+    pub _padding: [u8; 3],
+    pub name_id: u32,
+    pub value_id: u32,
+    pub source_offset: u32,
+}
+
+impl From<HtmlToken> for HtmlTokenFFI {
+    fn from(token: HtmlToken) -> Self {
+        Self {
+            token_type: token.token_type as u8,
+            _padding: [0; 3],
+            name_id: token.name_id.0,
+            value_id: token.value_id.0,
+            source_offset: token.source_offset,
+        }
+    }
+}
+
 impl HtmlToken {
     pub fn new(token_type: TokenType, name_id: StringId, value_id: StringId, offset: u32) -> Self {
         Self {
@@ -61,6 +93,8 @@ pub struct HtmlTokenizer {
     strings: StringPool,
     /// Current source offset
     offset: u32,
+    /// Line starts of the last tokenized source
+    lines: LineIndex,
 }
 
 impl Default for HtmlTokenizer {
@@ -76,6 +110,7 @@ impl HtmlTokenizer {
             tokens: Vec::new(),
             strings: StringPool::new(),
             offset: 0,
+            lines: LineIndex::default(),
         }
     }
     
@@ -85,6 +120,7 @@ impl HtmlTokenizer {
             tokens: Vec::new(),
             strings: pool,
             offset: 0,
+            lines: LineIndex::default(),
         }
     }
     
@@ -92,6 +128,7 @@ impl HtmlTokenizer {
     pub fn reset(&mut self) {
         self.tokens.clear();
         self.offset = 0;
+        self.lines = LineIndex::default();
     }
     
     /// Get the token tape
@@ -109,6 +146,11 @@ impl HtmlTokenizer {
         &self.strings
     }
     
+    /// Get the line index for mapping token offsets to line and column
+    pub fn lines(&self) -> &LineIndex {
+        &self.lines
+    }
+    
     /// Take ownership of the tokens and string pool
     pub fn take(self) -> (Vec<HtmlToken>, StringPool) {
         (self.tokens, self.strings)
@@ -118,39 +160,48 @@ impl HtmlTokenizer {
     pub fn tokenize(&mut self, html: &str) {
         self.reset();
         
-        // Use RefCell to allow interior mutability for TokenSink
-        let tokens = RefCell::new(Vec::new());
-        let strings = RefCell::new(std::mem::take(&mut self.strings));
-        let offset = RefCell::new(0u32);
-        
-        {
-            let sink = TokenSinkWrapper {
-                tokens: &tokens,
-                strings: &strings,
-                offset: &offset,
-            };
-            
-            let tok = Tokenizer::new(sink, TokenizerOpts::default());
-            let mut buffer = BufferQueue::default();
-            buffer.push_back(StrTendril::from(html));
-            let _ = tok.feed(&mut buffer);
-            tok.end();
-        }
+        let sink = TokenSinkWrapper::new(std::mem::take(&mut self.strings));
+        sink.source.borrow_mut().scan(html);
+        sink.source.borrow_mut().finish();
+        let tok = Tokenizer::new(sink, TokenizerOpts::default());
+        let mut buffer = BufferQueue::default();
+        buffer.push_back(StrTendril::from(html));
+        let _ = tok.feed(&mut buffer);
+        tok.end();
         
-        self.tokens = tokens.into_inner();
-        self.strings = strings.into_inner();
-        self.offset = offset.into_inner();
+        self.tokens = tok.sink.tokens.into_inner();
+        self.strings = tok.sink.strings.into_inner();
+        let source = tok.sink.source.into_inner();
+        self.offset = source.len;
+        self.lines = source.lines;
     }
 }
 
 /// Wrapper to implement TokenSink trait
-struct TokenSinkWrapper<'a> {
-    tokens: &'a RefCell<Vec<HtmlToken>>,
-    strings: &'a RefCell<StringPool>,
-    offset: &'a RefCell<u32>,
+///
+/// Uses RefCell to allow interior mutability for TokenSink
+struct TokenSinkWrapper {
+    tokens: RefCell<Vec<HtmlToken>>,
+    strings: RefCell<StringPool>,
+    /// Token start offsets, scanned ahead of the tokenizer
+    source: RefCell<SourceScanner>,
+    /// Character tokens since the last non-text token
+    ///
+    /// The tokenizer emits each character reference as its own token, so
+    /// runs are joined before trimming to keep the spaces between them.
+    pending_text: RefCell<String>,
 }
 
-impl TokenSinkWrapper<'_> {
+impl TokenSinkWrapper {
+    fn new(strings: StringPool) -> Self {
+        Self {
+            tokens: RefCell::new(Vec::new()),
+            strings: RefCell::new(strings),
+            source: RefCell::new(SourceScanner::default()),
+            pending_text: RefCell::new(String::new()),
+        }
+    }
+    
     fn process_tag(&self, tag: Tag) {
         let is_self_closing = tag.self_closing;
         let tag_name = tag.name.as_ref().to_lowercase();
@@ -167,7 +218,7 @@ impl TokenSinkWrapper<'_> {
             TagKind::EndTag => TokenType::EndTag,
         };
         
-        let offset = *self.offset.borrow();
+        let offset = self.source.borrow_mut().next_markup();
         self.tokens.borrow_mut().push(HtmlToken::new(
             token_type,
             tag_name_id,
@@ -178,12 +229,12 @@ impl TokenSinkWrapper<'_> {
         // Emit attribute tokens for start tags
         if matches!(tag.kind, TagKind::StartTag) {
             for attr in tag.attrs {
-                self.process_attribute(attr);
+                self.process_attribute(attr, offset);
             }
         }
     }
     
-    fn process_attribute(&self, attr: Attribute) {
+    fn process_attribute(&self, attr: Attribute, offset: u32) {
         let name = attr.name.local.as_ref().to_lowercase();
         let value = attr.value.to_string();
         
@@ -194,7 +245,6 @@ impl TokenSinkWrapper<'_> {
             self.strings.borrow_mut().intern(&value)
         };
         
-        let offset = *self.offset.borrow();
         self.tokens.borrow_mut().push(HtmlToken::new(
             TokenType::Attribute,
             name_id,
@@ -204,10 +254,17 @@ impl TokenSinkWrapper<'_> {
     }
     
     fn process_text(&self, text: &str) {
-        let trimmed = text.trim();
+        self.pending_text.borrow_mut().push_str(text);
+    }
+    
+    /// Emit the buffered text run as one Text token
+    fn flush_text(&self) {
+        let text = std::mem::take(&mut *self.pending_text.borrow_mut());
+        // Character references arrive decoded; keep `&nbsp;` at the edges
+        let trimmed = trim_html_whitespace(&text);
         if !trimmed.is_empty() {
             let text_id = self.strings.borrow_mut().intern(trimmed);
-            let offset = *self.offset.borrow();
+            let offset = self.source.borrow_mut().next_text();
             self.tokens.borrow_mut().push(HtmlToken::new(
                 TokenType::Text,
                 StringId::NONE,
@@ -219,7 +276,7 @@ impl TokenSinkWrapper<'_> {
     
     fn process_comment(&self, comment: &str) {
         let comment_id = self.strings.borrow_mut().intern(comment);
-        let offset = *self.offset.borrow();
+        let offset = self.source.borrow_mut().next_markup();
         self.tokens.borrow_mut().push(HtmlToken::new(
             TokenType::Comment,
             StringId::NONE,
@@ -229,7 +286,7 @@ impl TokenSinkWrapper<'_> {
     }
     
     fn process_doctype(&self) {
-        let offset = *self.offset.borrow();
+        let offset = self.source.borrow_mut().next_markup();
         self.tokens.borrow_mut().push(HtmlToken::new(
             TokenType::Doctype,
             StringId::NONE,
@@ -239,10 +296,13 @@ impl TokenSinkWrapper<'_> {
     }
 }
 
-impl TokenSink for TokenSinkWrapper<'_> {
+impl TokenSink for TokenSinkWrapper {
     type Handle = ();
     
     fn process_token(&self, token: Token, _line_number: u64) -> TokenSinkResult<()> {
+        if !matches!(token, Token::CharacterTokens(_) | Token::NullCharacterToken | Token::ParseError(_)) {
+            self.flush_text();
+        }
         match token {
             Token::TagToken(tag) => {
                 self.process_tag(tag);
@@ -263,18 +323,460 @@ impl TokenSink for TokenSinkWrapper<'_> {
     }
 }
 
+/// Incremental HTML tokenizer for documents that arrive in chunks
+///
+/// Tokens become available as soon as the bytes that complete them are fed,
+/// and the string pool is shared across chunks, so IDs stay valid for the
+/// whole document. UTF-8 sequences split across chunks are reassembled;
+/// invalid bytes become U+FFFD, so offsets count bytes of the decoded text
+/// (the same as input offsets for valid UTF-8). A Text token is emitted once the next tag,
+/// comment or the end of input shows the text run is complete.
+pub struct HtmlStreamTokenizer {
+    tokenizer: Tokenizer<TokenSinkWrapper>,
+    buffer: BufferQueue,
+    /// Bytes of an incomplete UTF-8 sequence at the end of the last chunk
+    partial: Vec<u8>,
+    /// Tokens already handed out by `take_tokens`
+    taken: usize,
+    finished: bool,
+}
+
+impl Default for HtmlStreamTokenizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HtmlStreamTokenizer {
+    /// Create a streaming tokenizer with a fresh string pool
+    pub fn new() -> Self {
+        Self {
+            tokenizer: Tokenizer::new(TokenSinkWrapper::new(StringPool::new()), TokenizerOpts::default()),
+            buffer: BufferQueue::default(),
+            partial: Vec::new(),
+            taken: 0,
+            finished: false,
+        }
+    }
+    
+    /// Tokenize the next chunk of the document
+    ///
+    /// Ignored after `finish`.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        if self.finished {
+            return;
+        }
+        self.partial.extend_from_slice(bytes);
+        
+        // Decode up to the last complete character, keeping the rest
+        let mut text = String::new();
+        let mut rest = self.partial.as_slice();
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    rest = &[];
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    text.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            rest = &after[len..];
+                        }
+                        None => {
+                            rest = after;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.partial = rest.to_vec();
+        
+        if !text.is_empty() {
+            self.tokenizer.sink.source.borrow_mut().scan(&text);
+            self.buffer.push_back(StrTendril::from(text));
+            self.run();
+        }
+    }
+    
+    /// Tokenize whatever input is left and close open constructs
+    pub fn finish(&mut self) {
+        if self.finished {
+            return;
+        }
+        if !self.partial.is_empty() {
+            let rest = String::from_utf8_lossy(&self.partial).into_owned();
+            self.partial.clear();
+            self.tokenizer.sink.source.borrow_mut().scan(&rest);
+            self.buffer.push_back(StrTendril::from(rest));
+            self.run();
+        }
+        self.tokenizer.sink.source.borrow_mut().finish();
+        self.tokenizer.end();
+        self.finished = true;
+    }
+    
+    /// Check if `finish` has been called
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+    
+    /// Get the number of tokens not yet taken
+    pub fn pending_count(&self) -> usize {
+        self.tokenizer.sink.tokens.borrow().len() - self.taken
+    }
+    
+    /// Take up to `max` of the tokens produced since the last call, in order
+    pub fn take_tokens(&mut self, max: usize) -> Vec<HtmlToken> {
+        let mut tokens = self.tokenizer.sink.tokens.borrow_mut();
+        let end = tokens.len().min(self.taken + max);
+        let batch = tokens[self.taken..end].to_vec();
+        self.taken = end;
+        
+        // Drop handed-out tokens once everything has been taken
+        if self.taken == tokens.len() {
+            tokens.clear();
+            self.taken = 0;
+        }
+        batch
+    }
+    
+    /// Get the string pool shared by all tokens
+    pub fn strings(&self) -> std::cell::Ref<'_, StringPool> {
+        self.tokenizer.sink.strings.borrow()
+    }
+    
+    /// Map a token offset to a 1-based (line, column) in the input so far
+    pub fn line_col(&self, offset: u32) -> (u32, u32) {
+        self.tokenizer.sink.source.borrow().lines.line_col(offset)
+    }
+    
+    fn run(&mut self) {
+        // Script results only pause tokenization; there is no script engine
+        while !self.buffer.is_empty() {
+            if let TokenizerResult::Done = self.tokenizer.feed(&self.buffer) {
+                break;
+            }
+        }
+    }
+}
+
+/// Line starts of a source document
+#[derive(Clone, Debug, Default)]
+pub struct LineIndex {
+    /// Byte offset where each line after the first begins
+    line_starts: Vec<u32>,
+}
+
+impl LineIndex {
+    /// Map a byte offset to a 1-based (line, column); columns count bytes
+    pub fn line_col(&self, offset: u32) -> (u32, u32) {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let start = if line == 0 { 0 } else { self.line_starts[line - 1] };
+        (line as u32 + 1, offset - start + 1)
+    }
+}
+
+/// Where the scanner found the start of a token
+#[derive(Clone, Copy, Debug)]
+enum TokenStart {
+    Markup(u32),
+    Text(u32),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ScanState {
+    Data,
+    /// After `<` at the given offset
+    TagOpen(u32),
+    /// After `</` at the given offset
+    EndTagOpen(u32),
+    /// Inside a tag; a quote only opens a value right after `=`
+    Tag { after_equals: bool, quote: Option<char> },
+    /// After `<!` and this many dashes
+    Bang(u8),
+    /// Inside a comment, with the last three characters seen
+    Comment([char; 3]),
+    /// Doctype or bogus comment, closed by the next `>`
+    UntilGt,
+}
+
+/// Finds where tokens start, since html5ever only reports line numbers
+///
+/// Mirrors just enough of the tokenizer's data-state rules (which is all
+/// the standalone tokenizer uses) to tell markup from text. Starts are
+/// queued in document order and matched to tokens as the sink emits them.
+#[derive(Debug)]
+struct SourceScanner {
+    state: ScanState,
+    starts: VecDeque<TokenStart>,
+    /// Whether the current text run already has its start queued
+    text_started: bool,
+    /// Bytes scanned so far
+    len: u32,
+    lines: LineIndex,
+}
+
+impl Default for SourceScanner {
+    fn default() -> Self {
+        Self {
+            state: ScanState::Data,
+            starts: VecDeque::new(),
+            text_started: false,
+            len: 0,
+            lines: LineIndex::default(),
+        }
+    }
+}
+
+impl SourceScanner {
+    /// Scan the next piece of input
+    fn scan(&mut self, text: &str) {
+        for (i, c) in text.char_indices() {
+            let pos = self.len + i as u32;
+            if c == '\n' {
+                self.lines.line_starts.push(pos + 1);
+            }
+            // The tokenizer drops a leading byte order mark
+            if pos == 0 && c == '\u{feff}' {
+                continue;
+            }
+            self.step(c, pos);
+        }
+        self.len += text.len() as u32;
+    }
+    
+    /// Handle the end of input: a dangling `<` or `</` is text
+    fn finish(&mut self) {
+        if let ScanState::TagOpen(start) | ScanState::EndTagOpen(start) = self.state {
+            self.text(start);
+        }
+        self.state = ScanState::Data;
+    }
+    
+    fn step(&mut self, c: char, pos: u32) {
+        use ScanState::*;
+        self.state = match self.state {
+            Data => return self.data(c, pos),
+            TagOpen(start) => match c {
+                c if c.is_ascii_alphabetic() => self.markup(start, Tag { after_equals: false, quote: None }),
+                '!' => self.markup(start, Bang(0)),
+                '?' => self.markup(start, UntilGt),
+                '/' => EndTagOpen(start),
+                _ => {
+                    self.text(start);
+                    self.state = Data;
+                    return self.data(c, pos);
+                }
+            },
+            EndTagOpen(start) => match c {
+                c if c.is_ascii_alphabetic() => self.markup(start, Tag { after_equals: false, quote: None }),
+                // `</>` produces no token
+                '>' => Data,
+                _ => self.markup(start, UntilGt),
+            },
+            Tag { quote: Some(q), after_equals } => {
+                if c == q {
+                    Tag { after_equals: false, quote: None }
+                } else {
+                    Tag { after_equals, quote: Some(q) }
+                }
+            }
+            Tag { after_equals, quote: None } => match c {
+                '>' => Data,
+                '=' => Tag { after_equals: true, quote: None },
+                '"' | '\'' if after_equals => Tag { after_equals: false, quote: Some(c) },
+                c if is_html_whitespace(c) => Tag { after_equals, quote: None },
+                _ => Tag { after_equals: false, quote: None },
+            },
+            Bang(dashes) => match c {
+                '-' if dashes == 0 => Bang(1),
+                '-' => Comment(['!', '-', '-']),
+                '>' => Data,
+                _ => UntilGt,
+            },
+            Comment(last) => {
+                if c == '>' && (last[1..] == ['-', '-'] || last == ['-', '-', '!']) {
+                    Data
+                } else {
+                    Comment([last[1], last[2], c])
+                }
+            }
+            UntilGt => if c == '>' { Data } else { UntilGt },
+        };
+    }
+    
+    fn data(&mut self, c: char, pos: u32) {
+        if c == '<' {
+            self.state = ScanState::TagOpen(pos);
+        } else if !is_html_whitespace(c) {
+            self.text(pos);
+        }
+    }
+    
+    fn markup(&mut self, start: u32, next: ScanState) -> ScanState {
+        self.starts.push_back(TokenStart::Markup(start));
+        self.text_started = false;
+        next
+    }
+    
+    fn text(&mut self, start: u32) {
+        if !self.text_started {
+            self.starts.push_back(TokenStart::Text(start));
+            self.text_started = true;
+        }
+    }
+    
+    /// Take the start of the next tag, comment or doctype
+    fn next_markup(&mut self) -> u32 {
+        // Skip text runs that produced no token, such as lone NULs
+        while let Some(start) = self.starts.pop_front() {
+            if let TokenStart::Markup(offset) = start {
+                return offset;
+            }
+        }
+        self.len
+    }
+    
+    /// Take the start of the text run being emitted
+    fn next_text(&mut self) -> u32 {
+        match self.starts.front() {
+            Some(&TokenStart::Text(offset)) => {
+                self.starts.pop_front();
+                offset
+            }
+            _ => self.len,
+        }
+    }
+}
+
 /// Parse result containing tokens and string pool
 pub struct ParseResult {
     pub tokens: Vec<HtmlToken>,
     pub strings: StringPool,
+    pub lines: LineIndex,
 }
 
 /// Convenience function to parse HTML and get results
 pub fn parse_html(html: &str) -> ParseResult {
     let mut tokenizer = HtmlTokenizer::new();
     tokenizer.tokenize(html);
+    let lines = tokenizer.lines().clone();
     let (tokens, strings) = tokenizer.take();
-    ParseResult { tokens, strings }
+    ParseResult { tokens, strings, lines }
+}
+
+/// Check for HTML whitespace (space, tab, LF, FF, CR)
+///
+/// Unlike `char::is_whitespace`, U+00A0 is not whitespace, so text made of
+/// `&nbsp;` is not trimmed away.
+pub fn is_html_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\x0C' | '\r')
+}
+
+/// Trim leading and trailing HTML whitespace, keeping non-breaking spaces
+pub fn trim_html_whitespace(text: &str) -> &str {
+    text.trim_matches(is_html_whitespace)
+}
+
+/// Text with its character references decoded
+pub struct DecodedText {
+    pub text: String,
+    /// (decoded, raw) byte offsets at the start and end of each reference
+    offsets: Vec<(u32, u32)>,
+}
+
+impl DecodedText {
+    /// Map a byte offset in the decoded text back to the raw source
+    ///
+    /// Offsets inside a reference's expansion map into the reference itself.
+    pub fn raw_offset(&self, decoded: usize) -> usize {
+        match self.offsets.partition_point(|&(d, _)| d as usize <= decoded) {
+            0 => decoded,
+            i => {
+                let (d, r) = self.offsets[i - 1];
+                r as usize + (decoded - d as usize)
+            }
+        }
+    }
+}
+
+/// Decode numeric (`&#169;`, `&#xA9;`) and named (`&copy;`) character references
+///
+/// For text that bypassed the tokenizer, which already decodes references
+/// in text and attribute values. Follows the HTML5 rules: the longest known
+/// name wins, legacy names may omit the `;`, and invalid code points become
+/// U+FFFD. Unknown references are kept as written.
+pub fn decode_character_references(raw: &str) -> DecodedText {
+    let mut text = String::with_capacity(raw.len());
+    let mut offsets = Vec::new();
+    let mut i = 0;
+    while let Some(amp) = raw[i..].find('&') {
+        let start = i + amp;
+        text.push_str(&raw[i..start]);
+        i = start + 1;
+        
+        let decoded_start = text.len() as u32;
+        let rest = &raw[i..];
+        let consumed = if let Some(numeric) = rest.strip_prefix('#') {
+            decode_numeric_reference(numeric, &mut text).map(|n| n + 1)
+        } else {
+            decode_named_reference(rest, &mut text)
+        };
+        match consumed {
+            Some(n) => {
+                offsets.push((decoded_start, start as u32));
+                i += n;
+                offsets.push((text.len() as u32, i as u32));
+            }
+            None => text.push('&'),
+        }
+    }
+    text.push_str(&raw[i..]);
+    DecodedText { text, offsets }
+}
+
+/// Decode the digits after `&#`, returning the bytes consumed
+fn decode_numeric_reference(rest: &str, out: &mut String) -> Option<usize> {
+    let (digits, radix, prefix) = match rest.strip_prefix(['x', 'X']) {
+        Some(hex) => (hex, 16, 1),
+        None => (rest, 10, 0),
+    };
+    let len = digits.bytes().take_while(|b| (*b as char).is_digit(radix)).count();
+    if len == 0 {
+        return None;
+    }
+    let value = digits[..len]
+        .chars()
+        .fold(0u32, |acc, c| acc.saturating_mul(radix).saturating_add(c.to_digit(radix).unwrap_or(0)));
+    let c = match value {
+        0x80..=0x9F => C1_REPLACEMENTS[(value - 0x80) as usize].unwrap_or(char::REPLACEMENT_CHARACTER),
+        0 => char::REPLACEMENT_CHARACTER,
+        _ => char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER),
+    };
+    out.push(c);
+    let semicolon = digits[len..].starts_with(';') as usize;
+    Some(prefix + len + semicolon)
+}
+
+/// Decode the longest known entity name after `&`, returning the bytes consumed
+fn decode_named_reference(rest: &str, out: &mut String) -> Option<usize> {
+    // The longest entity name is 32 characters plus the `;`
+    let name_len = rest.bytes().take(32).take_while(u8::is_ascii_alphanumeric).count();
+    let max = name_len + rest[name_len..].starts_with(';') as usize;
+    (1..=max).rev().find_map(|len| {
+        let &(first, second) = NAMED_ENTITIES.get(&rest[..len])?;
+        let first = char::from_u32(first).filter(|_| first != 0)?;
+        out.push(first);
+        if let Some(second) = char::from_u32(second).filter(|_| second != 0) {
+            out.push(second);
+        }
+        Some(len)
+    })
 }
 
 #[cfg(test)]
@@ -329,10 +831,179 @@ mod tests {
         assert_eq!(self_closing.len(), 2);
     }
     
+    #[test]
+    fn test_streaming_matches_whole_document() {
+        let html = "<!DOCTYPE html><div id=\"main\" class=\"a b\"><p>caf\u{e9} <b>bold</b></p><!-- note --><br/></div>";
+        let whole = parse_html(html);
+        
+        // Feed one byte at a time, splitting tags, attributes and the é
+        let mut stream = HtmlStreamTokenizer::new();
+        let mut tokens = Vec::new();
+        for byte in html.as_bytes() {
+            stream.feed(std::slice::from_ref(byte));
+            tokens.extend(stream.take_tokens(usize::MAX));
+        }
+        stream.finish();
+        tokens.extend(stream.take_tokens(usize::MAX));
+        
+        let strings = stream.strings();
+        let describe = |tokens: &[HtmlToken], pool: &StringPool| -> Vec<(TokenType, String, String)> {
+            tokens
+                .iter()
+                .filter(|t| t.token_type != TokenType::Text)
+                .map(|t| (t.token_type, pool.get(t.name_id).unwrap_or("").to_string(), pool.get(t.value_id).unwrap_or("").to_string()))
+                .collect()
+        };
+        assert_eq!(describe(&tokens, &strings), describe(&whole.tokens, &whole.strings));
+        
+        let text: String = tokens
+            .iter()
+            .filter(|t| t.token_type == TokenType::Text)
+            .map(|t| strings.get(t.value_id).unwrap())
+            .collect();
+        assert_eq!(text, "caf\u{e9}bold");
+    }
+    
+    #[test]
+    fn test_streaming_batches() {
+        let mut stream = HtmlStreamTokenizer::new();
+        stream.feed(b"<div><span>x</span></d");
+        // The unfinished end tag is not emitted yet
+        assert_eq!(stream.pending_count(), 4);
+        assert_eq!(stream.take_tokens(3).len(), 3);
+        assert_eq!(stream.take_tokens(3).len(), 1);
+        
+        stream.feed(b"iv>\xff");
+        stream.finish();
+        let tokens = stream.take_tokens(10);
+        assert_eq!(tokens[0].token_type, TokenType::EndTag);
+        assert_eq!(stream.strings().get(tokens[1].value_id), Some("\u{fffd}"));
+        assert!(stream.is_finished());
+    }
+    
     #[test]
     fn test_comment() {
         let result = parse_html("<!-- This is a comment --><div></div>");
         
         assert!(result.tokens.iter().any(|t| t.token_type == TokenType::Comment));
     }
+    
+    #[test]
+    fn test_character_references_in_text() {
+        let result = parse_html("<p>&amp; &#169; &#xA9;&#X2014; &copy &lt;b&gt;</p><p>&nbsp;</p>");
+        let texts: Vec<_> = result.tokens.iter()
+            .filter(|t| t.token_type == TokenType::Text)
+            .map(|t| result.strings.get(t.value_id).unwrap())
+            .collect();
+        assert_eq!(texts, vec!["& \u{a9} \u{a9}\u{2014} \u{a9} <b>", "\u{a0}"]);
+        
+        let result = parse_html(r#"<a title="Q&amp;A &quot;x&quot;"></a>"#);
+        let attr = result.tokens.iter().find(|t| t.token_type == TokenType::Attribute).unwrap();
+        assert_eq!(result.strings.get(attr.value_id), Some("Q&A \"x\""));
+    }
+    
+    #[test]
+    fn test_character_reference_split_across_chunks() {
+        let mut stream = HtmlStreamTokenizer::new();
+        stream.feed(b"<p>a &am");
+        stream.feed(b"p; &#x");
+        stream.feed(b"41;</p>");
+        stream.finish();
+        let tokens = stream.take_tokens(usize::MAX);
+        let strings = stream.strings();
+        let text: Vec<_> = tokens.iter()
+            .filter(|t| t.token_type == TokenType::Text)
+            .map(|t| strings.get(t.value_id).unwrap())
+            .collect();
+        assert_eq!(text, vec!["a & A"]);
+    }
+    
+    #[test]
+    fn test_nbsp_aware_trimming() {
+        assert_eq!(trim_html_whitespace(" \t\u{a0}x\u{a0}\n"), "\u{a0}x\u{a0}");
+        assert_eq!(trim_html_whitespace("\r\x0C "), "");
+        assert!(!is_html_whitespace('\u{a0}'));
+    }
+    
+    #[test]
+    fn test_decode_character_references() {
+        // Named, with and without `;`, and the longest match wins
+        assert_eq!(decode_character_references("&amp;&copy &notin;&notit;").text, "&\u{a9} \u{2209}\u{ac}it;");
+        // Decimal and hex, including C1, NUL and out-of-range replacements
+        assert_eq!(decode_character_references("&#65;&#x42&#X43;&#128;&#0;&#x110000;").text, "ABC\u{20ac}\u{fffd}\u{fffd}");
+        // Two-code-point entities
+        assert_eq!(decode_character_references("&NotEqualTilde;").text, "\u{2242}\u{338}");
+        // Unknown and malformed references are kept
+        assert_eq!(decode_character_references("a & b &#; &bogus; &#xZ").text, "a & b &#; &bogus; &#xZ");
+    }
+    
+    #[test]
+    fn test_decoded_raw_offsets() {
+        let decoded = decode_character_references("a&amp;b&#169;c");
+        assert_eq!(decoded.text, "a&b\u{a9}c");
+        assert_eq!(decoded.raw_offset(0), 0);
+        assert_eq!(decoded.raw_offset(1), 1);
+        assert_eq!(decoded.raw_offset(2), 6);
+        assert_eq!(decoded.raw_offset(3), 7);
+        assert_eq!(decoded.raw_offset(5), 13);
+    }
+    
+    #[test]
+    fn test_source_offsets() {
+        let html = "<!DOCTYPE html>\n<div title=\"a>b\" id=x>\n  a < b</>c\n<!-- <p> --><p>&amp;x</p></div>";
+        let result = parse_html(html);
+        let starts: Vec<_> = result.tokens.iter()
+            .map(|t| (t.token_type, &html[t.source_offset as usize..]))
+            .map(|(ty, rest)| (ty, rest.chars().take(4).collect::<String>()))
+            .collect();
+        let expected = [
+            (TokenType::Doctype, "<!DO"),
+            (TokenType::StartTag, "<div"),
+            (TokenType::Attribute, "<div"),
+            (TokenType::Attribute, "<div"),
+            (TokenType::Text, "a < "),
+            (TokenType::Comment, "<!--"),
+            (TokenType::StartTag, "<p>&"),
+            (TokenType::Text, "&amp"),
+            (TokenType::EndTag, "</p>"),
+            (TokenType::EndTag, "</di"),
+        ];
+        let expected: Vec<_> = expected.iter().map(|(ty, s)| (*ty, s.to_string())).collect();
+        assert_eq!(starts, expected);
+        
+        let text = &result.tokens[4];
+        assert_eq!(result.lines.line_col(text.source_offset), (3, 3));
+        assert_eq!(result.lines.line_col(0), (1, 1));
+        assert_eq!(result.lines.line_col(result.tokens[5].source_offset), (4, 1));
+    }
+    
+    #[test]
+    fn test_streaming_source_offsets() {
+        let html = "<p class='a'>one</p>\n<!-- x -- y -->two<br/>";
+        let whole = parse_html(html);
+        
+        let mut stream = HtmlStreamTokenizer::new();
+        for chunk in html.as_bytes().chunks(3) {
+            stream.feed(chunk);
+        }
+        stream.finish();
+        let tokens = stream.take_tokens(usize::MAX);
+        let offsets: Vec<_> = tokens.iter().map(|t| t.source_offset).collect();
+        let expected: Vec<_> = whole.tokens.iter().map(|t| t.source_offset).collect();
+        assert_eq!(offsets, expected);
+        assert_eq!(offsets, vec![0, 0, 13, 16, 21, 36, 39]);
+        assert_eq!(stream.line_col(36), (2, 16));
+    }
+    
+    #[test]
+    fn test_ffi_token_layout() {
+        assert_eq!(std::mem::size_of::<HtmlTokenFFI>(), 16);
+        let result = parse_html("<b>x</b>");
+        let flat = HtmlTokenFFI::from(result.tokens[1]);
+        let bytes = flat.as_bytes();
+        assert_eq!(bytes[0], TokenType::Text as u8);
+        assert_eq!(&bytes[1..4], &[0, 0, 0]);
+        assert_eq!(u32::from_ne_bytes(bytes[8..12].try_into().unwrap()), result.tokens[1].value_id.0);
+        assert_eq!(u32::from_ne_bytes(bytes[12..16].try_into().unwrap()), 3);
+    }
 }
@@ -1,20 +1,27 @@
 //! DOP Parser - HTML/CSS parsers and Content IR compiler
 //!
 //! This crate provides:
-//! - HTML parsing using html5ever
+//! - HTML parsing using html5ever (token tape or full tree construction)
 //! - CSS parsing using cssparser
+//! - CSS selector matching
 //! - Content IR compiler with zerocopy binary format
+//! - HTML to Content IR tree construction
 //! - JIT text shaping infrastructure
 //!
 //! All modules expose FFI functions for Julia integration.
 
 pub mod html_parser;
+pub mod tree_builder;
 pub mod css_parser;
+pub mod selector;
 pub mod compiler;
+pub mod dom_builder;
 pub mod string_interner;
 pub mod ffi;
+pub mod ffi_guard;
 
 pub use html_parser::*;
+pub use tree_builder::*;
 pub use css_parser::*;
 pub use compiler::*;
 pub use string_interner::*;
@@ -0,0 +1,258 @@
+//! CSS selector matching
+//!
+//! Matches the raw selector strings of `parse_stylesheet` rules against an
+//! element and its ancestors. Supported selectors:
+//! - Type (`div`), universal (`*`), ID (`#main`) and class (`.note`)
+//!   selectors, alone or compounded (`p.note.wide`)
+//! - Descendant (`nav a`) and child (`ul > li`) combinators
+//! - Selector lists (`h1, h2`)
+//!
+//! Selectors using anything else (attributes, pseudo-classes, sibling
+//! combinators) never match. Matching rules cascade by specificity, then
+//! source order.
+
+use crate::css_parser::{apply_declarations, CssRule, CssStyles};
+
+/// An element as seen by selectors
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Element<'a> {
+    /// Lowercase tag name
+    pub tag: &'a str,
+    /// `id` attribute (empty if none)
+    pub id: &'a str,
+    /// `class` attribute, whitespace-separated (empty if none)
+    pub class: &'a str,
+}
+
+/// Selector specificity as (IDs, classes, types)
+pub type Specificity = (u32, u32, u32);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+/// Type, ID and class selectors that must all match one element
+#[derive(Clone, Debug, Default)]
+struct Compound {
+    tag: Option<String>,
+    ids: Vec<String>,
+    classes: Vec<String>,
+}
+
+impl Compound {
+    fn matches(&self, element: &Element) -> bool {
+        self.tag.as_ref().is_none_or(|tag| tag.eq_ignore_ascii_case(element.tag))
+            && self.ids.iter().all(|id| id == element.id)
+            && self.classes.iter().all(|class| element.class.split_ascii_whitespace().any(|c| c == class))
+    }
+}
+
+/// A complex selector such as `nav > ul li.active`
+#[derive(Clone, Debug)]
+pub struct Selector {
+    compounds: Vec<Compound>,
+    /// `combinators[i]` joins `compounds[i]` and `compounds[i + 1]`
+    combinators: Vec<Combinator>,
+}
+
+impl Selector {
+    /// Parse a single complex selector (no commas)
+    ///
+    /// Returns None for empty or unsupported selectors.
+    pub fn parse(selector: &str) -> Option<Selector> {
+        let mut compounds = Vec::new();
+        let mut combinators = Vec::new();
+        let mut current: Option<Compound> = None;
+        let mut combinator = None;
+
+        let mut chars = selector.trim().chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                c if c.is_whitespace() || c == '>' => {
+                    if let Some(compound) = current.take() {
+                        compounds.push(compound);
+                        combinator = Some(Combinator::Descendant);
+                    }
+                    if c == '>' {
+                        // A leading `>` has nothing to join
+                        combinator.replace(Combinator::Child)?;
+                    }
+                }
+                '*' if current.is_some() => return None,
+                '*' | '#' | '.' => {
+                    let name = if c == '*' { String::new() } else { take_ident(&mut chars)? };
+                    if current.is_none() {
+                        if let Some(combinator) = combinator.take() {
+                            combinators.push(combinator);
+                        } else if !compounds.is_empty() {
+                            return None;
+                        }
+                    }
+                    let compound = current.get_or_insert_with(Compound::default);
+                    match c {
+                        '#' => compound.ids.push(name),
+                        '.' => compound.classes.push(name),
+                        _ => {}
+                    }
+                }
+                c if is_ident_char(c) => {
+                    // A type selector starts a compound
+                    if current.is_some() {
+                        return None;
+                    }
+                    if let Some(combinator) = combinator.take() {
+                        combinators.push(combinator);
+                    }
+                    let mut name = c.to_string();
+                    name.push_str(&take_ident(&mut chars).unwrap_or_default());
+                    current = Some(Compound { tag: Some(name), ..Compound::default() });
+                }
+                _ => return None,
+            }
+        }
+
+        // A dangling combinator has nothing on its right
+        compounds.push(current?);
+        Some(Selector { compounds, combinators })
+    }
+
+    /// Get the specificity of the selector
+    pub fn specificity(&self) -> Specificity {
+        self.compounds.iter().fold((0, 0, 0), |(a, b, c), compound| {
+            (
+                a + compound.ids.len() as u32,
+                b + compound.classes.len() as u32,
+                c + compound.tag.is_some() as u32,
+            )
+        })
+    }
+
+    /// Check if the last element of `path` (root first) matches
+    pub fn matches(&self, path: &[Element]) -> bool {
+        self.matches_at(self.compounds.len() - 1, path)
+    }
+
+    fn matches_at(&self, index: usize, path: &[Element]) -> bool {
+        let Some((element, ancestors)) = path.split_last() else {
+            return false;
+        };
+        if !self.compounds[index].matches(element) {
+            return false;
+        }
+        if index == 0 {
+            return true;
+        }
+        match self.combinators[index - 1] {
+            Combinator::Child => self.matches_at(index - 1, ancestors),
+            Combinator::Descendant => (1..=ancestors.len()).rev().any(|end| self.matches_at(index - 1, &ancestors[..end])),
+        }
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_' || !c.is_ascii()
+}
+
+fn take_ident(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<String> {
+    let mut ident = String::new();
+    while let Some(&c) = chars.peek() {
+        if !is_ident_char(c) {
+            break;
+        }
+        ident.push(c);
+        chars.next();
+    }
+    (!ident.is_empty()).then_some(ident)
+}
+
+/// Get the specificity of the most specific selector in a list that matches
+pub fn match_selector_list(selectors: &str, path: &[Element]) -> Option<Specificity> {
+    selectors
+        .split(',')
+        .filter_map(Selector::parse)
+        .filter(|selector| selector.matches(path))
+        .map(|selector| selector.specificity())
+        .max()
+}
+
+/// Get the declarations of matching rules in cascade order
+///
+/// Later declarations win: rules are ordered by specificity, then by their
+/// position in `rules`. Property names are lowercased.
+pub fn matched_declarations<'r>(rules: &'r [CssRule], path: &[Element]) -> Vec<(String, &'r str)> {
+    let mut matched: Vec<(Specificity, usize)> = rules
+        .iter()
+        .enumerate()
+        .filter_map(|(i, rule)| Some((match_selector_list(&rule.selector, path)?, i)))
+        .collect();
+    matched.sort_unstable();
+
+    matched
+        .into_iter()
+        .flat_map(|(_, i)| rules[i].properties.iter())
+        .map(|(prop, val)| (prop.to_lowercase(), val.as_str()))
+        .collect()
+}
+
+/// Compute the styles that `rules` give the last element of `path`
+///
+/// `path` runs from the root element to the element being styled.
+pub fn match_rules(rules: &[CssRule], path: &[Element]) -> CssStyles {
+    let mut styles = CssStyles::default();
+    apply_declarations(&mut styles, &matched_declarations(rules, path));
+    styles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css_parser::{parse_stylesheet, Color};
+
+    fn el<'a>(tag: &'a str, id: &'a str, class: &'a str) -> Element<'a> {
+        Element { tag, id, class }
+    }
+
+    #[test]
+    fn test_selector_matching() {
+        let path = [el("body", "", ""), el("nav", "menu", "top"), el("ul", "", ""), el("li", "", "item active")];
+
+        for selector in ["li", "*", ".active", "li.item.active", "nav li", "#menu li", "ul > li", "nav > ul > .item", "body li"] {
+            assert!(Selector::parse(selector).unwrap().matches(&path), "{}", selector);
+        }
+        for selector in ["p", ".hidden", "nav > li", "#main li", "li ul", "div li"] {
+            assert!(!Selector::parse(selector).unwrap().matches(&path), "{}", selector);
+        }
+        for selector in ["", "a:hover", "a[href]", "> li", "li >", "li + li"] {
+            assert!(Selector::parse(selector).is_none(), "{}", selector);
+        }
+
+        assert_eq!(Selector::parse("#menu li.item.active").unwrap().specificity(), (1, 2, 1));
+        // One class outweighs any number of types
+        assert_eq!(match_selector_list("p, .item, nav > ul li", &path), Some((0, 1, 0)));
+        assert_eq!(match_selector_list("p, nav > ul li", &path), Some((0, 0, 3)));
+    }
+
+    #[test]
+    fn test_match_rules_cascade() {
+        let rules = parse_stylesheet(
+            "#main p { color: red } \
+             .note { color: blue; width: 100px } \
+             p { color: green; height: 20px } \
+             div p { color: black }",
+        );
+        let path = [el("div", "main", ""), el("p", "", "note")];
+
+        // The ID rule wins over the later class and type rules
+        let styles = match_rules(&rules, &path);
+        assert_eq!(styles.color, Color::new(255, 0, 0, 255));
+        assert_eq!(styles.width.value, 100.0);
+        assert_eq!(styles.height.value, 20.0);
+
+        // Equal specificity falls back to source order
+        let rules = parse_stylesheet(".a { color: red } .b { color: blue }");
+        let styles = match_rules(&rules, &[el("p", "", "b a")]);
+        assert_eq!(styles.color, Color::new(0, 0, 255, 255));
+    }
+}
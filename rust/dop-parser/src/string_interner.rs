@@ -94,6 +94,27 @@ impl StringPool {
         self.strings.truncate(1);
         self.lookup.clear();
     }
+    
+    /// Concatenate all strings into one buffer for bulk export
+    pub fn flatten(&self) -> FlatStrings {
+        let mut bytes = Vec::with_capacity(self.strings.iter().map(String::len).sum());
+        let mut offsets = Vec::with_capacity(self.strings.len() + 1);
+        for s in &self.strings {
+            offsets.push(bytes.len() as u32);
+            bytes.extend_from_slice(s.as_bytes());
+        }
+        offsets.push(bytes.len() as u32);
+        FlatStrings { bytes, offsets }
+    }
+}
+
+/// A string pool flattened into one UTF-8 buffer
+///
+/// String `id` spans `bytes[offsets[id]..offsets[id + 1]]`; ID 0 is empty.
+#[derive(Clone, Debug, Default)]
+pub struct FlatStrings {
+    pub bytes: Vec<u8>,
+    pub offsets: Vec<u32>,
 }
 
 #[cfg(test)]
@@ -129,4 +150,17 @@ mod tests {
         assert_eq!(pool.get(StringId::NONE), None);
         assert_eq!(pool.get(StringId(999)), None);
     }
+    
+    #[test]
+    fn test_flatten() {
+        let mut pool = StringPool::new();
+        pool.intern("div");
+        pool.intern("caf\u{e9}");
+        let flat = pool.flatten();
+        
+        assert_eq!(flat.offsets, vec![0, 0, 3, 8]);
+        let get = |id: usize| std::str::from_utf8(&flat.bytes[flat.offsets[id] as usize..flat.offsets[id + 1] as usize]).unwrap();
+        assert_eq!(get(0), "");
+        assert_eq!(get(2), "caf\u{e9}");
+    }
 }
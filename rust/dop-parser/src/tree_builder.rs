@@ -0,0 +1,439 @@
+//! Tree-building HTML parser using html5ever's TreeBuilder
+//!
+//! The token tape from `HtmlTokenizer` follows the markup as written, so
+//! misnested tags, implied `<tbody>` and auto-closed `<p>` elements come out
+//! wrong. This mode runs the full HTML5 tree-construction algorithm and
+//! flattens the resulting document into a node tape laid out like
+//! `NodeTable`: parallel arrays, 1-indexed IDs, 0 meaning "none".
+//!
+//! Nodes are numbered in document order, so a parent always precedes its
+//! children. Top-level nodes (doctype, comments and `html`) have parent 0
+//! and are chained from node 1 through `next_siblings`. Text is trimmed and
+//! whitespace-only text is dropped, as in the token tape.
+
+use std::borrow::Cow;
+use std::cell::{Ref, RefCell};
+
+use html5ever::tendril::TendrilSink;
+use html5ever::tree_builder::{ElementFlags, NodeOrText, QuirksMode, TreeSink};
+use html5ever::{parse_document, Attribute, ParseOpts, QualName};
+use tendril::StrTendril;
+
+use crate::html_parser::{trim_html_whitespace, HtmlToken, TokenType};
+use crate::string_interner::{StringId, StringPool};
+
+/// HTML document as a flat node tape
+#[derive(Default)]
+pub struct HtmlTree {
+    /// Node kinds: StartTag for elements, Text, Comment or Doctype
+    pub node_types: Vec<TokenType>,
+    /// Interned tag name (elements) or doctype name
+    pub name_ids: Vec<StringId>,
+    /// Interned text or comment content
+    pub value_ids: Vec<StringId>,
+    /// Parent node indices (0 = top level)
+    pub parents: Vec<u32>,
+    /// First child node indices (0 = no children)
+    pub first_children: Vec<u32>,
+    /// Next sibling node indices (0 = no sibling)
+    pub next_siblings: Vec<u32>,
+    /// Index of each node's first entry in `attributes`
+    pub attr_starts: Vec<u32>,
+    /// Number of attributes on each node
+    pub attr_counts: Vec<u32>,
+    /// Attribute tokens of all elements, grouped by node
+    pub attributes: Vec<HtmlToken>,
+    /// Strings referenced by the node and attribute IDs
+    pub strings: StringPool,
+}
+
+impl HtmlTree {
+    /// Get the number of nodes
+    pub fn len(&self) -> usize {
+        self.node_types.len()
+    }
+
+    /// Check if the tree is empty
+    pub fn is_empty(&self) -> bool {
+        self.node_types.is_empty()
+    }
+
+    /// Get the attribute tokens of a node (1-indexed)
+    pub fn attributes_of(&self, id: u32) -> &[HtmlToken] {
+        if id == 0 || id as usize > self.len() {
+            return &[];
+        }
+        let start = self.attr_starts[id as usize - 1] as usize;
+        let count = self.attr_counts[id as usize - 1] as usize;
+        &self.attributes[start..start + count]
+    }
+
+    /// Iterate over the children of a node (1-indexed)
+    pub fn children(&self, id: u32) -> impl Iterator<Item = u32> + '_ {
+        let first = if id == 0 || id as usize > self.len() {
+            0
+        } else {
+            self.first_children[id as usize - 1]
+        };
+        std::iter::successors(Some(first).filter(|&c| c != 0), move |&c| {
+            Some(self.next_siblings[c as usize - 1]).filter(|&n| n != 0)
+        })
+    }
+
+    /// Append a node in document order and link it into its parent
+    fn push(&mut self, token_type: TokenType, name_id: StringId, value_id: StringId, parent: u32, prev_sibling: u32) -> u32 {
+        let id = self.node_types.len() as u32 + 1;
+        self.node_types.push(token_type);
+        self.name_ids.push(name_id);
+        self.value_ids.push(value_id);
+        self.parents.push(parent);
+        self.first_children.push(0);
+        self.next_siblings.push(0);
+        self.attr_starts.push(self.attributes.len() as u32);
+        self.attr_counts.push(0);
+
+        if prev_sibling != 0 {
+            self.next_siblings[prev_sibling as usize - 1] = id;
+        } else if parent != 0 {
+            self.first_children[parent as usize - 1] = id;
+        }
+        id
+    }
+}
+
+/// Parse HTML with the HTML5 tree-construction algorithm
+pub fn parse_html_tree(html: &str) -> HtmlTree {
+    let sink = parse_document(TreeBuilderSink::new(), ParseOpts::default()).one(StrTendril::from(html));
+    sink.into_tree()
+}
+
+/// A node in the sink's arena while the tree is being built
+struct SinkNode {
+    kind: TokenType,
+    /// Element name; `None` for every other kind
+    name: Option<QualName>,
+    /// Text, comment content or doctype name
+    text: String,
+    attrs: Vec<Attribute>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    /// Fragment holding a `<template>`'s contents
+    template_contents: Option<usize>,
+}
+
+impl SinkNode {
+    fn new(kind: TokenType) -> Self {
+        Self {
+            kind,
+            name: None,
+            text: String::new(),
+            attrs: Vec::new(),
+            parent: None,
+            children: Vec::new(),
+            template_contents: None,
+        }
+    }
+}
+
+/// TreeSink that keeps the DOM in an arena indexed by handle
+///
+/// Handle 0 is the document. Uses RefCell because TreeSink takes `&self`.
+struct TreeBuilderSink {
+    nodes: RefCell<Vec<SinkNode>>,
+}
+
+impl TreeBuilderSink {
+    fn new() -> Self {
+        // The document's kind is never emitted
+        Self {
+            nodes: RefCell::new(vec![SinkNode::new(TokenType::StartTag)]),
+        }
+    }
+
+    fn add(&self, node: SinkNode) -> usize {
+        let mut nodes = self.nodes.borrow_mut();
+        nodes.push(node);
+        nodes.len() - 1
+    }
+
+    fn detach(nodes: &mut [SinkNode], child: usize) {
+        if let Some(parent) = nodes[child].parent.take() {
+            nodes[parent].children.retain(|&c| c != child);
+        }
+    }
+
+    /// Insert a node or text into `parent` at `index`, merging adjacent text
+    fn insert(&self, parent: usize, index: usize, child: NodeOrText<usize>) {
+        let mut nodes = self.nodes.borrow_mut();
+        match child {
+            NodeOrText::AppendNode(node) => {
+                Self::detach(&mut nodes, node);
+                nodes[node].parent = Some(parent);
+                let index = index.min(nodes[parent].children.len());
+                nodes[parent].children.insert(index, node);
+            }
+            NodeOrText::AppendText(text) => {
+                let previous = index.checked_sub(1).and_then(|i| nodes[parent].children.get(i).copied());
+                if let Some(prev) = previous.filter(|&p| nodes[p].kind == TokenType::Text) {
+                    nodes[prev].text.push_str(&text);
+                    return;
+                }
+                let mut node = SinkNode::new(TokenType::Text);
+                node.text = text.to_string();
+                node.parent = Some(parent);
+                nodes.push(node);
+                let id = nodes.len() - 1;
+                nodes[parent].children.insert(index, id);
+            }
+        }
+    }
+
+    /// Flatten the arena into a node tape in document order
+    fn into_tree(self) -> HtmlTree {
+        let nodes = self.nodes.into_inner();
+        let mut tree = HtmlTree::default();
+
+        // (arena node, tape parent); the stack holds children in reverse
+        let mut stack: Vec<(usize, u32)> = nodes[0].children.iter().rev().map(|&c| (c, 0)).collect();
+        // Last tape child emitted for each tape parent, indexed by parent ID
+        let mut last_child: Vec<u32> = vec![0];
+
+        while let Some((index, parent)) = stack.pop() {
+            let node = &nodes[index];
+            let (name_id, value_id) = match node.kind {
+                TokenType::Text => {
+                    let trimmed = trim_html_whitespace(&node.text);
+                    if trimmed.is_empty() {
+                        continue;
+                    }
+                    (StringId::NONE, tree.strings.intern(trimmed))
+                }
+                TokenType::Comment => (StringId::NONE, tree.strings.intern(&node.text)),
+                TokenType::Doctype => (tree.strings.intern(&node.text), StringId::NONE),
+                _ => {
+                    let name = node.name.as_ref().map(|n| n.local.as_ref()).unwrap_or("");
+                    (tree.strings.intern(&name.to_lowercase()), StringId::NONE)
+                }
+            };
+
+            let prev = last_child[parent as usize];
+            let id = tree.push(node.kind, name_id, value_id, parent, prev);
+            last_child[parent as usize] = id;
+            last_child.push(0);
+
+            for attr in &node.attrs {
+                let name_id = tree.strings.intern(&attr.name.local.as_ref().to_lowercase());
+                let value_id = if attr.value.is_empty() {
+                    StringId::NONE
+                } else {
+                    tree.strings.intern(&attr.value)
+                };
+                tree.attributes.push(HtmlToken::new(TokenType::Attribute, name_id, value_id, 0));
+            }
+            tree.attr_counts[id as usize - 1] = node.attrs.len() as u32;
+
+            // A template's contents are emitted as its children
+            let children = node.template_contents.map_or(&node.children, |t| &nodes[t].children);
+            stack.extend(children.iter().rev().map(|&c| (c, id)));
+        }
+        tree
+    }
+}
+
+impl TreeSink for TreeBuilderSink {
+    type Handle = usize;
+    type Output = Self;
+    type ElemName<'a> = Ref<'a, QualName>;
+
+    fn finish(self) -> Self {
+        self
+    }
+
+    fn parse_error(&self, msg: Cow<'static, str>) {
+        log::debug!("HTML parse error: {}", msg);
+    }
+
+    fn get_document(&self) -> usize {
+        0
+    }
+
+    fn elem_name<'a>(&'a self, target: &'a usize) -> Ref<'a, QualName> {
+        Ref::map(self.nodes.borrow(), |nodes| nodes[*target].name.as_ref().expect("not an element"))
+    }
+
+    fn create_element(&self, name: QualName, attrs: Vec<Attribute>, flags: ElementFlags) -> usize {
+        let contents = flags.template.then(|| self.add(SinkNode::new(TokenType::StartTag)));
+        let mut node = SinkNode::new(TokenType::StartTag);
+        node.name = Some(name);
+        node.attrs = attrs;
+        node.template_contents = contents;
+        self.add(node)
+    }
+
+    fn create_comment(&self, text: StrTendril) -> usize {
+        let mut node = SinkNode::new(TokenType::Comment);
+        node.text = text.to_string();
+        self.add(node)
+    }
+
+    fn create_pi(&self, _target: StrTendril, data: StrTendril) -> usize {
+        // Processing instructions only occur in XML; keep them as comments
+        self.create_comment(data)
+    }
+
+    fn append(&self, parent: &usize, child: NodeOrText<usize>) {
+        let index = self.nodes.borrow()[*parent].children.len();
+        self.insert(*parent, index, child);
+    }
+
+    fn append_based_on_parent_node(&self, element: &usize, prev_element: &usize, child: NodeOrText<usize>) {
+        if self.nodes.borrow()[*element].parent.is_some() {
+            self.append_before_sibling(element, child);
+        } else {
+            self.append(prev_element, child);
+        }
+    }
+
+    fn append_doctype_to_document(&self, name: StrTendril, _public_id: StrTendril, _system_id: StrTendril) {
+        let mut node = SinkNode::new(TokenType::Doctype);
+        node.text = name.to_string();
+        let id = self.add(node);
+        self.append(&0, NodeOrText::AppendNode(id));
+    }
+
+    fn get_template_contents(&self, target: &usize) -> usize {
+        self.nodes.borrow()[*target].template_contents.expect("not a template element")
+    }
+
+    fn same_node(&self, x: &usize, y: &usize) -> bool {
+        x == y
+    }
+
+    fn set_quirks_mode(&self, _mode: QuirksMode) {}
+
+    fn append_before_sibling(&self, sibling: &usize, new_node: NodeOrText<usize>) {
+        let (parent, index) = {
+            let nodes = self.nodes.borrow();
+            let parent = nodes[*sibling].parent.expect("sibling has no parent");
+            let index = nodes[parent].children.iter().position(|c| c == sibling).unwrap_or(0);
+            (parent, index)
+        };
+        // Detaching a node that comes earlier in the same parent shifts the sibling
+        let index = match &new_node {
+            NodeOrText::AppendNode(node) => {
+                let nodes = self.nodes.borrow();
+                match nodes[parent].children.iter().position(|c| c == node) {
+                    Some(old) if old < index => index - 1,
+                    _ => index,
+                }
+            }
+            NodeOrText::AppendText(_) => index,
+        };
+        self.insert(parent, index, new_node);
+    }
+
+    fn add_attrs_if_missing(&self, target: &usize, attrs: Vec<Attribute>) {
+        let mut nodes = self.nodes.borrow_mut();
+        let existing = &mut nodes[*target].attrs;
+        for attr in attrs {
+            if !existing.iter().any(|a| a.name == attr.name) {
+                existing.push(attr);
+            }
+        }
+    }
+
+    fn remove_from_parent(&self, target: &usize) {
+        Self::detach(&mut self.nodes.borrow_mut(), *target);
+    }
+
+    fn reparent_children(&self, node: &usize, new_parent: &usize) {
+        let mut nodes = self.nodes.borrow_mut();
+        let children = std::mem::take(&mut nodes[*node].children);
+        for &child in &children {
+            nodes[child].parent = Some(*new_parent);
+        }
+        nodes[*new_parent].children.extend(children);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Render the tree as nested tag names, e.g. `html(head,body(p(#text)))`
+    fn outline(tree: &HtmlTree, parent: u32) -> String {
+        let ids: Vec<u32> = if parent == 0 {
+            std::iter::successors(Some(1).filter(|_| !tree.is_empty()), |&c| {
+                Some(tree.next_siblings[c as usize - 1]).filter(|&n| n != 0)
+            })
+            .collect()
+        } else {
+            tree.children(parent).collect()
+        };
+        ids.iter()
+            .map(|&id| {
+                let i = id as usize - 1;
+                let name = match tree.node_types[i] {
+                    TokenType::Text => "#text".to_string(),
+                    TokenType::Comment => "#comment".to_string(),
+                    TokenType::Doctype => "!doctype".to_string(),
+                    _ => tree.strings.get(tree.name_ids[i]).unwrap_or("").to_string(),
+                };
+                let inner = outline(tree, id);
+                if inner.is_empty() { name } else { format!("{}({})", name, inner) }
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    #[test]
+    fn test_implied_elements() {
+        let tree = parse_html_tree("<!DOCTYPE html><p>Hello");
+        assert_eq!(outline(&tree, 0), "!doctype,html(head,body(p(#text)))");
+        assert_eq!(tree.parents[0], 0);
+        let text = tree.node_types.iter().position(|&t| t == TokenType::Text).unwrap();
+        assert_eq!(tree.strings.get(tree.value_ids[text]), Some("Hello"));
+    }
+
+    #[test]
+    fn test_auto_closed_paragraph() {
+        let tree = parse_html_tree("<p>one<p>two<div>three</div>");
+        assert_eq!(outline(&tree, 0), "html(head,body(p(#text),p(#text),div(#text)))");
+    }
+
+    #[test]
+    fn test_implied_tbody() {
+        let tree = parse_html_tree("<table><tr><td>cell</td></tr></table>");
+        assert_eq!(outline(&tree, 0), "html(head,body(table(tbody(tr(td(#text))))))");
+    }
+
+    #[test]
+    fn test_misnested_formatting() {
+        // The adoption agency algorithm reopens <b> inside the <p>
+        let tree = parse_html_tree("<b>1<p>2</b>3</p>");
+        assert_eq!(outline(&tree, 0), "html(head,body(b(#text),p(b(#text),#text)))");
+    }
+
+    #[test]
+    fn test_attributes_and_document_order() {
+        let tree = parse_html_tree(r#"<div id="main" class="a b"><span hidden>x</span></div>"#);
+        let div = tree.name_ids.iter().position(|&n| tree.strings.get(n) == Some("div")).unwrap() as u32 + 1;
+        let attrs = tree.attributes_of(div);
+        assert_eq!(attrs.len(), 2);
+        assert_eq!(tree.strings.get(attrs[0].name_id), Some("id"));
+        assert_eq!(tree.strings.get(attrs[0].value_id), Some("main"));
+
+        let span = tree.children(div).next().unwrap();
+        assert_eq!(tree.attributes_of(span)[0].value_id, StringId::NONE);
+
+        for (i, &parent) in tree.parents.iter().enumerate() {
+            assert!((parent as usize) < i + 1);
+        }
+    }
+
+    #[test]
+    fn test_template_contents() {
+        let tree = parse_html_tree("<template><i>t</i></template>");
+        assert_eq!(outline(&tree, 0), "html(head(template(i(#text))),body)");
+    }
+}
@@ -0,0 +1,81 @@
+//! Throughput of the fixed-point row blenders against the float per-pixel loop they replaced
+//!
+//! Run with `cargo bench --bench blend`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use dop_renderer::blend;
+
+const WIDTH: usize = 1920;
+const ROWS: usize = 1080;
+
+/// Straight-alpha text-like row: runs of transparent, partial and opaque pixels
+fn source_row() -> Vec<u8> {
+    (0..WIDTH)
+        .flat_map(|x| {
+            let a = match x % 24 {
+                0..=7 => 0,
+                8..=11 => ((x * 37) % 254 + 1) as u8,
+                _ => 255,
+            };
+            [(x % 251) as u8, (x % 241) as u8, (x % 239) as u8, a]
+        })
+        .collect()
+}
+
+/// The float blend the software renderer used per pixel before the row blenders
+fn blend_row_float(dst: &mut [u8], src: &[u8]) {
+    for (d, s) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+        let a = s[3] as f32 / 255.0;
+        if a > 0.0 {
+            let inv = 1.0 - a;
+            for c in 0..3 {
+                d[c] = (s[c] as f32 * a + d[c] as f32 * inv) as u8;
+            }
+            d[3] = (a * 255.0 + d[3] as f32 * inv) as u8;
+        }
+    }
+}
+
+/// Time `frame` over a 1080p framebuffer and print megapixels per second
+fn bench(name: &str, mut frame: impl FnMut(&mut [u8])) {
+    let mut framebuffer = vec![128u8; WIDTH * ROWS * 4];
+    frame(&mut framebuffer);
+    let mut frames = 0u32;
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(2) {
+        frame(black_box(&mut framebuffer));
+        frames += 1;
+    }
+    let elapsed = start.elapsed();
+    let mpix = (WIDTH * ROWS) as f64 * frames as f64 / elapsed.as_secs_f64() / 1e6;
+    println!("{name:<24} {:>8.2} ms/frame {mpix:>10.1} Mpix/s", elapsed.as_secs_f64() * 1e3 / frames as f64);
+}
+
+fn main() {
+    let src = source_row();
+    let coverage: Vec<u8> = src.chunks_exact(4).map(|p| p[3]).collect();
+    let subpixel: Vec<u8> = coverage.iter().flat_map(|&c| [c, c / 2, c / 3]).collect();
+
+    bench("float per pixel", |fb| {
+        for row in fb.chunks_exact_mut(WIDTH * 4) {
+            blend_row_float(row, black_box(&src));
+        }
+    });
+    bench("blend_row", |fb| {
+        for row in fb.chunks_exact_mut(WIDTH * 4) {
+            blend::blend_row(row, black_box(&src), None);
+        }
+    });
+    bench("blend_color_row", |fb| {
+        for row in fb.chunks_exact_mut(WIDTH * 4) {
+            blend::blend_color_row(row, [20, 40, 200, 230], black_box(&coverage));
+        }
+    });
+    bench("blend_subpixel_row", |fb| {
+        for row in fb.chunks_exact_mut(WIDTH * 4) {
+            blend::blend_subpixel_row(row, [20, 40, 200], 230, black_box(&subpixel), None);
+        }
+    });
+}
@@ -0,0 +1,255 @@
+//! Window-level accessibility adapter
+//!
+//! Publishes an accesskit tree for the window through `accesskit_winit` so
+//! screen readers can read the rendered page. The host supplies the tree
+//! (built by the Content IR exporter or node by node over FFI), and action
+//! requests from assistive technology come back as `DopEvent`s.
+//!
+//! Platform adapters call the handlers on arbitrary threads, so all state is
+//! shared through an [`AccessibilityBridge`] behind a mutex. The event loop
+//! applies pending tree updates and drains queued actions each iteration.
+
+use std::sync::{Arc, Mutex};
+
+use accesskit::{
+    Action, ActionData, ActionHandler, ActionRequest, ActivationHandler, DeactivationHandler,
+    Node, NodeId, Rect, Tree, TreeUpdate,
+};
+
+use crate::window::DopEvent;
+
+/// Action codes carried in the `key` field of accessibility action events
+pub mod actions {
+    pub const CLICK: i32 = 1;
+    pub const FOCUS: i32 = 2;
+    pub const BLUR: i32 = 3;
+    pub const COLLAPSE: i32 = 4;
+    pub const EXPAND: i32 = 5;
+    pub const INCREMENT: i32 = 6;
+    pub const DECREMENT: i32 = 7;
+    pub const SCROLL_INTO_VIEW: i32 = 8;
+    pub const SCROLL_TO_POINT: i32 = 9;
+    pub const SET_SCROLL_OFFSET: i32 = 10;
+    pub const SET_VALUE: i32 = 11;
+    pub const SHOW_CONTEXT_MENU: i32 = 12;
+    pub const SCROLL_UP: i32 = 13;
+    pub const SCROLL_DOWN: i32 = 14;
+    pub const SCROLL_LEFT: i32 = 15;
+    pub const SCROLL_RIGHT: i32 = 16;
+}
+
+/// Map an accesskit action onto its FFI code (None for unsupported actions)
+fn action_code(action: Action) -> Option<i32> {
+    let code = match action {
+        Action::Click => actions::CLICK,
+        Action::Focus => actions::FOCUS,
+        Action::Blur => actions::BLUR,
+        Action::Collapse => actions::COLLAPSE,
+        Action::Expand => actions::EXPAND,
+        Action::Increment => actions::INCREMENT,
+        Action::Decrement => actions::DECREMENT,
+        Action::ScrollIntoView => actions::SCROLL_INTO_VIEW,
+        Action::ScrollToPoint => actions::SCROLL_TO_POINT,
+        Action::SetScrollOffset => actions::SET_SCROLL_OFFSET,
+        Action::SetValue => actions::SET_VALUE,
+        Action::ShowContextMenu => actions::SHOW_CONTEXT_MENU,
+        Action::ScrollUp => actions::SCROLL_UP,
+        Action::ScrollDown => actions::SCROLL_DOWN,
+        Action::ScrollLeft => actions::SCROLL_LEFT,
+        Action::ScrollRight => actions::SCROLL_RIGHT,
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Convert an accesskit action request into a `DopEvent`
+///
+/// The target node ID is stored in `char_code`, points (scroll targets) in
+/// `x`/`y` and numeric values in `scroll_x`. String values cannot be carried
+/// by the event and are dropped.
+pub fn action_event(request: &ActionRequest) -> Option<DopEvent> {
+    let action = action_code(request.action)?;
+    let mut event = DopEvent::accessibility_action(action, request.target.0 as u32);
+    match &request.data {
+        Some(ActionData::ScrollToPoint(p)) | Some(ActionData::SetScrollOffset(p)) => {
+            event.x = p.x;
+            event.y = p.y;
+        }
+        Some(ActionData::NumericValue(v)) => event.scroll_x = *v,
+        _ => {}
+    }
+    Some(event)
+}
+
+/// Map a Content IR role code onto an accesskit role
+///
+/// Codes match `dop_content_ir::properties::Role` so Julia can use the same
+/// constants for both paths.
+pub fn role_from_u8(value: u8) -> accesskit::Role {
+    match value {
+        3 => accesskit::Role::Document,
+        4 => accesskit::Role::Heading,
+        5 => accesskit::Role::Paragraph,
+        6 => accesskit::Role::Label,
+        7 => accesskit::Role::Link,
+        8 => accesskit::Role::Button,
+        9 => accesskit::Role::Image,
+        10 => accesskit::Role::List,
+        11 => accesskit::Role::ListItem,
+        12 => accesskit::Role::TextInput,
+        13 => accesskit::Role::CheckBox,
+        14 => accesskit::Role::Navigation,
+        15 => accesskit::Role::Main,
+        _ => accesskit::Role::GenericContainer,
+    }
+}
+
+/// A flat accessibility node description supplied over FFI
+#[derive(Debug, Clone)]
+pub struct AccessNode {
+    pub id: u32,
+    /// Parent node ID (0 for the root)
+    pub parent: u32,
+    pub role: u8,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub name: String,
+}
+
+/// Build a full tree update from flat node descriptions
+///
+/// Children are attached in input order. The first node with parent 0 is the
+/// root; returns None when there is no root or an ID is 0.
+pub fn build_tree_update(nodes: &[AccessNode], focus: u32) -> Option<TreeUpdate> {
+    let root = nodes.iter().find(|n| n.parent == 0)?.id;
+    if nodes.iter().any(|n| n.id == 0) {
+        return None;
+    }
+
+    let mut update_nodes = Vec::with_capacity(nodes.len());
+    for desc in nodes {
+        let mut node = Node::new(role_from_u8(desc.role));
+        node.set_bounds(Rect::new(
+            desc.x as f64,
+            desc.y as f64,
+            (desc.x + desc.width) as f64,
+            (desc.y + desc.height) as f64,
+        ));
+        if !desc.name.is_empty() {
+            node.set_label(desc.name.clone());
+        }
+        let children: Vec<NodeId> = nodes
+            .iter()
+            .filter(|child| child.parent == desc.id)
+            .map(|child| NodeId(child.id as u64))
+            .collect();
+        if !children.is_empty() {
+            node.set_children(children);
+        }
+        update_nodes.push((NodeId(desc.id as u64), node));
+    }
+
+    let focus = if nodes.iter().any(|n| n.id == focus) {
+        NodeId(focus as u64)
+    } else {
+        NodeId(root as u64)
+    };
+
+    Some(TreeUpdate {
+        nodes: update_nodes,
+        tree: Some(Tree::new(NodeId(root as u64))),
+        focus,
+    })
+}
+
+/// State shared between the host, the event loop and the platform adapter
+#[derive(Default)]
+pub struct AccessibilityBridge {
+    tree: Option<TreeUpdate>,
+    pending: bool,
+    active: bool,
+    events: Vec<DopEvent>,
+}
+
+/// Shared handle to an [`AccessibilityBridge`]
+pub type SharedAccessibility = Arc<Mutex<AccessibilityBridge>>;
+
+impl AccessibilityBridge {
+    pub fn new_shared() -> SharedAccessibility {
+        Arc::new(Mutex::new(Self::default()))
+    }
+
+    /// Replace the published tree; it is pushed to the platform on the next event loop iteration
+    pub fn publish(&mut self, update: TreeUpdate) {
+        self.tree = Some(update);
+        self.pending = true;
+    }
+
+    /// Take the tree if it changed since the last call
+    pub fn take_pending(&mut self) -> Option<TreeUpdate> {
+        if !self.pending {
+            return None;
+        }
+        self.pending = false;
+        self.tree.clone()
+    }
+
+    /// Check if assistive technology is currently attached
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Drain accessibility events queued by the platform handlers
+    pub fn take_events(&mut self) -> Vec<DopEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+/// Placeholder tree used until the host publishes one
+fn placeholder_tree() -> TreeUpdate {
+    let root = NodeId(1);
+    TreeUpdate {
+        nodes: vec![(root, Node::new(accesskit::Role::Window))],
+        tree: Some(Tree::new(root)),
+        focus: root,
+    }
+}
+
+/// Platform callbacks forwarding into the shared bridge
+pub struct BridgeHandler {
+    bridge: SharedAccessibility,
+}
+
+impl BridgeHandler {
+    pub fn new(bridge: SharedAccessibility) -> Self {
+        Self { bridge }
+    }
+}
+
+impl ActivationHandler for BridgeHandler {
+    fn request_initial_tree(&mut self) -> Option<TreeUpdate> {
+        let mut bridge = self.bridge.lock().ok()?;
+        bridge.active = true;
+        bridge.pending = false;
+        bridge.events.push(DopEvent::accessibility_activated());
+        Some(bridge.tree.clone().unwrap_or_else(placeholder_tree))
+    }
+}
+
+impl ActionHandler for BridgeHandler {
+    fn do_action(&mut self, request: ActionRequest) {
+        if let (Some(event), Ok(mut bridge)) = (action_event(&request), self.bridge.lock()) {
+            bridge.events.push(event);
+        }
+    }
+}
+
+impl DeactivationHandler for BridgeHandler {
+    fn deactivate_accessibility(&mut self) {
+        if let Ok(mut bridge) = self.bridge.lock() {
+            bridge.active = false;
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Fixed-point source-over blending of pixel rows
+//!
+//! Text blits onto the framebuffer go through these row functions. Channels
+//! are blended as 8-bit integers, `(src * a + dst * (255 - a)) / 255` with
+//! exact rounding, four RGBA pixels at a time in 16-bit lanes (`wide` maps
+//! them to SSE2, AVX2 or NEON, or to scalar code elsewhere). The alpha
+//! channel blends 255 over the destination alpha, matching the straight
+//! source colors the text rasterizer produces.
+//!
+//! Linear-light blending (`color::blend_linear`) needs per-channel transfer
+//! curves and stays scalar.
+
+use wide::u16x16;
+
+/// Pixels per SIMD step: four RGBA pixels in sixteen 16-bit lanes
+const STEP: usize = 4;
+
+/// `x / 255` rounded to nearest, exact for `x <= 255 * 255`
+#[inline]
+fn div_255(x: u16) -> u16 {
+    let x = x + 128;
+    (x + (x >> 8)) >> 8
+}
+
+/// `a * b / 255` rounded to nearest, e.g. coverage scaled by an alpha
+#[inline]
+pub fn mul_255(a: u8, b: u8) -> u8 {
+    div_255(a as u16 * b as u16) as u8
+}
+
+/// `div_255` on every lane
+#[inline]
+fn div_255_x16(x: u16x16) -> u16x16 {
+    let x = x + u16x16::splat(128);
+    (x + (x >> 8u16)) >> 8u16
+}
+
+/// Blend one pixel: `src` colors at coverage `a`
+#[inline]
+pub fn blend_pixel(dst: &mut [u8], src: [u8; 3], a: u8) {
+    let a = a as u16;
+    let inv = 255 - a;
+    for c in 0..3 {
+        dst[c] = div_255(src[c] as u16 * a + dst[c] as u16 * inv) as u8;
+    }
+    dst[3] = div_255(255 * a + dst[3] as u16 * inv) as u8;
+}
+
+/// Blend four pixels whose colors and per-lane coverage are already laid out in lanes
+#[inline]
+fn blend_step(dst: &mut [u8], src: [u16; 16], a: [u16; 16]) {
+    let a = u16x16::new(a);
+    let d = u16x16::new(std::array::from_fn(|i| dst[i] as u16));
+    let out = div_255_x16(u16x16::new(src) * a + d * (u16x16::splat(255) - a)).to_array();
+    for (d, o) in dst.iter_mut().zip(out) {
+        *d = o as u8;
+    }
+}
+
+/// Blend a row of straight-alpha RGBA pixels over `dst`
+///
+/// `src` and `dst` hold the same number of pixels. Pixels whose `mask` byte
+/// is 0 are left alone. Returns the number of pixels with nonzero alpha that
+/// passed the mask.
+pub fn blend_row(dst: &mut [u8], src: &[u8], mask: Option<&[u8]>) -> usize {
+    let alpha = |i: usize| if mask.is_some_and(|m| m[i] == 0) { 0 } else { src[i * 4 + 3] };
+    let pixels = dst.len().min(src.len()) / 4;
+    let mut touched = 0;
+    let mut i = 0;
+    while i + STEP <= pixels {
+        let a: [u8; STEP] = std::array::from_fn(|p| alpha(i + p));
+        touched += a.iter().filter(|&&a| a > 0).count();
+        let dst = &mut dst[i * 4..(i + STEP) * 4];
+        let src = &src[i * 4..(i + STEP) * 4];
+        if a == [255; STEP] {
+            for (d, s) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+                d[..3].copy_from_slice(&s[..3]);
+                d[3] = 255;
+            }
+        } else if a != [0; STEP] {
+            let lanes = std::array::from_fn(|l| if l % 4 == 3 { 255 } else { src[l] as u16 });
+            blend_step(dst, lanes, std::array::from_fn(|l| a[l / 4] as u16));
+        }
+        i += STEP;
+    }
+    for i in i..pixels {
+        let a = alpha(i);
+        if a > 0 {
+            touched += 1;
+            let s = &src[i * 4..i * 4 + 3];
+            blend_pixel(&mut dst[i * 4..i * 4 + 4], [s[0], s[1], s[2]], a);
+        }
+    }
+    touched
+}
+
+/// Blend a solid color over `dst` with one coverage byte per pixel
+///
+/// Each pixel's coverage is scaled by the color's alpha. Returns the number
+/// of pixels changed.
+pub fn blend_color_row(dst: &mut [u8], color: [u8; 4], coverage: &[u8]) -> usize {
+    let pixels = (dst.len() / 4).min(coverage.len());
+    let scale = color[3] as u16;
+    let lanes: [u16; 16] = std::array::from_fn(|l| if l % 4 == 3 { 255 } else { color[l % 4] as u16 });
+    let mut touched = 0;
+    let mut i = 0;
+    while i + STEP <= pixels {
+        let cov = &coverage[i..i + STEP];
+        if cov.iter().any(|&c| c > 0) {
+            let a: [u16; STEP] = std::array::from_fn(|p| div_255(cov[p] as u16 * scale));
+            touched += a.iter().filter(|&&a| a > 0).count();
+            blend_step(&mut dst[i * 4..(i + STEP) * 4], lanes, std::array::from_fn(|l| a[l / 4]));
+        }
+        i += STEP;
+    }
+    for i in i..pixels {
+        let a = div_255(coverage[i] as u16 * scale) as u8;
+        if a > 0 {
+            touched += 1;
+            blend_pixel(&mut dst[i * 4..i * 4 + 4], [color[0], color[1], color[2]], a);
+        }
+    }
+    touched
+}
+
+/// Blend a solid color over `dst` with per-channel (subpixel) coverage, 3 bytes per pixel
+///
+/// Each channel blends by its own coverage scaled by `alpha`; the alpha
+/// channel takes the largest of the three. Pixels whose `mask` byte is 0 are
+/// left alone. Returns the number of pixels changed.
+pub fn blend_subpixel_row(dst: &mut [u8], color: [u8; 3], alpha: u8, coverage: &[u8], mask: Option<&[u8]>) -> usize {
+    let pixels = (dst.len() / 4).min(coverage.len() / 3);
+    let scale = alpha as u16;
+    let lanes: [u16; 16] = std::array::from_fn(|l| if l % 4 == 3 { 255 } else { color[l % 4] as u16 });
+    // Coverage of one pixel as [r, g, b, max], before scaling by `alpha`
+    let raw = |i: usize| -> [u16; 4] {
+        if mask.is_some_and(|m| m[i] == 0) {
+            return [0; 4];
+        }
+        let [r, g, b] = [0, 1, 2].map(|c| coverage[i * 3 + c] as u16);
+        [r, g, b, r.max(g).max(b)]
+    };
+    let mut touched = 0;
+    let mut i = 0;
+    while i + STEP <= pixels {
+        let cov = &coverage[i * 3..(i + STEP) * 3];
+        if cov.iter().any(|&c| c > 0) {
+            let raw: [[u16; 4]; STEP] = std::array::from_fn(|p| raw(i + p));
+            let a = div_255_x16(u16x16::new(std::array::from_fn(|l| raw[l / 4][l % 4])) * u16x16::splat(scale)).to_array();
+            let changed = (0..STEP).filter(|p| a[p * 4 + 3] > 0).count();
+            if changed > 0 {
+                touched += changed;
+                blend_step(&mut dst[i * 4..(i + STEP) * 4], lanes, a);
+            }
+        }
+        i += STEP;
+    }
+    for i in i..pixels {
+        let a = raw(i).map(|c| div_255(c * scale));
+        if a[3] > 0 {
+            touched += 1;
+            let dst = &mut dst[i * 4..i * 4 + 4];
+            for c in 0..4 {
+                let src = if c == 3 { 255 } else { color[c] as u16 };
+                dst[c] = div_255(src * a[c] + dst[c] as u16 * (255 - a[c])) as u8;
+            }
+        }
+    }
+    touched
+}
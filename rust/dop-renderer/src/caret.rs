@@ -0,0 +1,172 @@
+//! Blinking text caret and focus rings for editable fields
+//!
+//! The caret blinks on a clock kept by the renderer, so hosts do not need a
+//! timer of their own racing the frame loop. Setting the caret again at the
+//! same place keeps its phase; moving it shows it again at once, like a
+//! native caret while typing. A caret attached to a threaded window is shared
+//! with the window thread (`SharedCaret`), which blends it over the presented
+//! frame and presents again on every toggle.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::path::Path;
+
+/// Width of the caret bar in logical pixels
+pub const CARET_WIDTH: f32 = 1.0;
+
+/// Caret handed to a window thread, in device pixels (None = no caret)
+pub type SharedCaret = Arc<Mutex<Option<Caret>>>;
+
+/// A vertical caret bar with its top-left corner at (x, y)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Caret {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// RGBA in sRGB
+    pub color: [f32; 4],
+    /// Time shown, then time hidden, per blink (None = always shown)
+    pub blink: Option<Duration>,
+    /// When the caret was placed; it is shown for the first `blink` from here
+    pub since: Instant,
+}
+
+impl Caret {
+    /// Caret `CARET_WIDTH` wide placed now; `blink_ms` of 0 keeps it shown
+    pub fn new(x: f32, y: f32, height: f32, color: [f32; 4], blink_ms: u32) -> Self {
+        Self {
+            x,
+            y,
+            width: CARET_WIDTH,
+            height,
+            color,
+            blink: (blink_ms > 0).then(|| Duration::from_millis(blink_ms as u64)),
+            since: Instant::now(),
+        }
+    }
+
+    /// Check if two carets differ only in blink phase
+    pub fn same_place(&self, other: &Caret) -> bool {
+        Caret { since: other.since, ..*self } == *other
+    }
+
+    /// Number of blink half-periods elapsed at `now`
+    fn phase(&self, now: Instant) -> Option<u128> {
+        let blink = self.blink?.as_nanos().max(1);
+        Some(now.saturating_duration_since(self.since).as_nanos() / blink)
+    }
+
+    /// Check if the caret is shown at `now`
+    pub fn is_visible_at(&self, now: Instant) -> bool {
+        self.phase(now).is_none_or(|phase| phase % 2 == 0)
+    }
+
+    /// When the caret next appears or disappears (None if it does not blink)
+    pub fn next_toggle(&self, now: Instant) -> Option<Instant> {
+        let phase = self.phase(now)?;
+        let blink = self.blink?;
+        Some(self.since + blink * (phase as u32 + 1))
+    }
+
+    /// Bar as (x, y, width, height)
+    pub fn rect(&self) -> [f32; 4] {
+        [self.x, self.y, self.width, self.height]
+    }
+
+    /// Copy with the bar multiplied by `scale`, e.g. into device pixels
+    pub fn scaled(&self, scale: f32) -> Caret {
+        Caret {
+            x: self.x * scale,
+            y: self.y * scale,
+            width: self.width * scale,
+            height: self.height * scale,
+            ..*self
+        }
+    }
+}
+
+/// Dashed rounded outline drawn just outside a focused element's box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FocusRing {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// Corner radius of the element; the ring's corners grow with the offset
+    pub radius: f32,
+    /// Stroke width
+    pub line_width: f32,
+    /// Lengths of dashes and of the gaps between them (a gap of 0 draws a solid ring)
+    pub dash: f32,
+    pub gap: f32,
+    /// RGBA in sRGB
+    pub color: [f32; 4],
+}
+
+impl FocusRing {
+    /// Ring around a box with a 2px stroke in 4px dashes and 2px gaps
+    pub fn new(x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            radius: 0.0,
+            line_width: 2.0,
+            dash: 4.0,
+            gap: 2.0,
+            color,
+        }
+    }
+
+    /// Center line of the stroke: the box grown by half the line width, split into dashes
+    pub fn outline(&self) -> Path {
+        let half = self.line_width / 2.0;
+        let (width, height) = (self.width + self.line_width, self.height + self.line_width);
+        let radius = (self.radius.max(0.0) + half).min(width / 2.0).min(height / 2.0);
+        Path::rounded_rect(self.x - half, self.y - half, width, height, [radius; 4]).dashed(self.dash, self.gap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::PathSegment;
+
+    #[test]
+    fn test_caret_blink() {
+        // Shown for the first half period, hidden for the second
+        let caret = Caret::new(0.0, 0.0, 10.0, [0.0; 4], 500);
+        let at = |ms: u64| caret.since + Duration::from_millis(ms);
+        assert!(caret.is_visible_at(at(100)));
+        assert!(!caret.is_visible_at(at(600)));
+        assert!(caret.is_visible_at(at(1100)));
+        assert_eq!(caret.next_toggle(at(600)), Some(at(1000)));
+        assert!(Caret::new(0.0, 0.0, 10.0, [0.0; 4], 0).next_toggle(at(600)).is_none());
+
+        // Moving it is a change of place, blinking is not
+        let later = Caret { since: at(300), ..caret };
+        assert!(caret.same_place(&later));
+        assert!(!caret.same_place(&Caret { x: 1.0, ..caret }));
+        assert_eq!(caret.scaled(2.0).rect(), [0.0, 0.0, 2.0, 20.0]);
+    }
+
+    #[test]
+    fn test_focus_ring_outline() {
+        // A line split into 4px dashes and 2px gaps
+        let mut line = Path::new();
+        line.move_to(0.0, 0.0);
+        line.line_to(20.0, 0.0);
+        let dashes = line.dashed(4.0, 2.0);
+        let starts = dashes.segments().iter().filter(|s| matches!(s, PathSegment::MoveTo(..))).count();
+        assert_eq!(starts, 4);
+
+        // The ring is dashed; without a gap it is one closed outline
+        let ring = FocusRing::new(10.0, 10.0, 40.0, 20.0, [0.0, 0.0, 1.0, 1.0]);
+        let count = |path: &Path| path.segments().iter().filter(|s| matches!(s, PathSegment::MoveTo(..))).count();
+        assert!(count(&ring.outline()) > 1);
+        assert_eq!(count(&FocusRing { gap: 0.0, ..ring }.outline()), 1);
+    }
+}
@@ -0,0 +1,372 @@
+//! Color management
+//!
+//! Converts colors and decoded image pixels from their source space (sRGB,
+//! Display-P3) into the output surface space. The output space is either one
+//! of the built-in spaces, handled with exact matrix conversions, or (with
+//! the `icc` feature) an arbitrary display ICC profile handled by qcms.
+//!
+//! Conversions are skipped entirely when source and output match, which is
+//! the default sRGB-to-sRGB case.
+//!
+//! The module also provides linear-light blending helpers used by the
+//! renderer's gamma-correct blending mode.
+
+use std::sync::OnceLock;
+
+/// Color space of a color or image
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    #[default]
+    Srgb = 0,
+    DisplayP3 = 1,
+}
+
+impl ColorSpace {
+    /// Convert a raw FFI value (unknown values map to sRGB)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => ColorSpace::DisplayP3,
+            _ => ColorSpace::Srgb,
+        }
+    }
+
+    /// Linear RGB to CIE XYZ (D65) matrix
+    fn rgb_to_xyz(self) -> [[f32; 3]; 3] {
+        match self {
+            ColorSpace::Srgb => [
+                [0.412_390_8, 0.357_584_3, 0.180_480_8],
+                [0.212_639, 0.715_168_7, 0.072_192_3],
+                [0.019_330_8, 0.119_194_8, 0.950_532_1],
+            ],
+            ColorSpace::DisplayP3 => [
+                [0.486_570_9, 0.265_667_7, 0.198_217_3],
+                [0.228_974_6, 0.691_738_5, 0.079_286_9],
+                [0.0, 0.045_113_4, 1.043_944_4],
+            ],
+        }
+    }
+
+    /// CIE XYZ (D65) to linear RGB matrix
+    fn xyz_to_rgb(self) -> [[f32; 3]; 3] {
+        match self {
+            ColorSpace::Srgb => [
+                [3.240_97, -1.537_383, -0.498_610_8],
+                [-0.969_243_6, 1.875_967_5, 0.041_555_1],
+                [0.055_630_1, -0.203_977, 1.056_971_5],
+            ],
+            ColorSpace::DisplayP3 => [
+                [2.493_497, -0.931_383_6, -0.402_710_8],
+                [-0.829_489, 1.762_664_1, 0.023_624_7],
+                [0.035_845_8, -0.076_172_4, 0.956_884_5],
+            ],
+        }
+    }
+}
+
+/// sRGB transfer function (shared by sRGB and Display-P3): encoded to linear
+pub fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.040_45 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// sRGB transfer function: linear to encoded
+pub fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Lookup table decoding 8-bit sRGB values to linear light
+fn decode_table() -> &'static [f32; 256] {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| std::array::from_fn(|i| srgb_to_linear(i as f32 / 255.0)))
+}
+
+/// Number of entries in the linear-to-sRGB encode table
+const ENCODE_STEPS: usize = 4096;
+
+/// Lookup table encoding linear light to 8-bit sRGB
+fn encode_table() -> &'static [u8; ENCODE_STEPS] {
+    static TABLE: OnceLock<[u8; ENCODE_STEPS]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        std::array::from_fn(|i| (linear_to_srgb(i as f32 / (ENCODE_STEPS - 1) as f32) * 255.0).round() as u8)
+    })
+}
+
+/// Decode an 8-bit sRGB channel to linear light
+pub fn decode_u8(v: u8) -> f32 {
+    decode_table()[v as usize]
+}
+
+/// Encode a linear-light channel to 8-bit sRGB
+pub fn encode_u8(v: f32) -> u8 {
+    encode_table()[(v.clamp(0.0, 1.0) * (ENCODE_STEPS - 1) as f32).round() as usize]
+}
+
+/// Blend a straight-alpha sRGB color over a premultiplied RGBA8 pixel in linear light
+pub fn blend_linear(dst: &mut [u8], src: [u8; 3], src_a: f32) {
+    if src_a <= 0.0 {
+        return;
+    }
+    let dst_a = dst[3] as f32 / 255.0;
+    let out_a = src_a + dst_a * (1.0 - src_a);
+    if out_a <= 0.0 {
+        return;
+    }
+
+    for c in 0..3 {
+        // Un-premultiply the destination before decoding it
+        let dst_c = if dst[3] > 0 {
+            ((dst[c] as f32 * 255.0 / dst[3] as f32).round() as u32).min(255) as u8
+        } else {
+            0
+        };
+        let linear = (decode_u8(src[c]) * src_a + decode_u8(dst_c) * dst_a * (1.0 - src_a)) / out_a;
+        dst[c] = (encode_u8(linear) as f32 * out_a).round() as u8;
+    }
+    dst[3] = (out_a * 255.0).round() as u8;
+}
+
+/// Like `blend_linear`, with a separate coverage for each color channel (subpixel text)
+pub fn blend_linear_subpixel(dst: &mut [u8], src: [u8; 3], src_a: [f32; 3]) {
+    let max_a = src_a[0].max(src_a[1]).max(src_a[2]);
+    if max_a <= 0.0 {
+        return;
+    }
+    let dst_a = dst[3] as f32 / 255.0;
+    let out_a = max_a + dst_a * (1.0 - max_a);
+
+    for c in 0..3 {
+        let dst_c = if dst[3] > 0 {
+            ((dst[c] as f32 * 255.0 / dst[3] as f32).round() as u32).min(255) as u8
+        } else {
+            0
+        };
+        let linear = (decode_u8(src[c]) * src_a[c] + decode_u8(dst_c) * dst_a * (1.0 - src_a[c])) / out_a;
+        dst[c] = (encode_u8(linear) as f32 * out_a).round() as u8;
+    }
+    dst[3] = (out_a * 255.0).round() as u8;
+}
+
+/// Interpolate between two straight-alpha sRGB colors in linear light
+///
+/// Used for gradient stops so midpoints don't darken the way byte-space
+/// interpolation does.
+pub fn mix_linear(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    let t = t.clamp(0.0, 1.0);
+    let mix = |x: f32, y: f32| {
+        let lx = srgb_to_linear(x);
+        let ly = srgb_to_linear(y);
+        linear_to_srgb(lx + (ly - lx) * t)
+    };
+    [
+        mix(a[0], b[0]),
+        mix(a[1], b[1]),
+        mix(a[2], b[2]),
+        a[3] + (b[3] - a[3]) * t,
+    ]
+}
+
+/// Convert straight-alpha RGBA8 to premultiplied alpha in place
+pub fn premultiply(pixels: &mut [u8]) {
+    for px in pixels.chunks_exact_mut(4) {
+        let a = px[3] as u16;
+        if a < 255 {
+            px[0] = ((px[0] as u16 * a + 127) / 255) as u8;
+            px[1] = ((px[1] as u16 * a + 127) / 255) as u8;
+            px[2] = ((px[2] as u16 * a + 127) / 255) as u8;
+        }
+    }
+}
+
+/// Convert premultiplied RGBA8 back to straight alpha in place
+pub fn unpremultiply(pixels: &mut [u8]) {
+    for px in pixels.chunks_exact_mut(4) {
+        let a = px[3] as u16;
+        if a > 0 && a < 255 {
+            px[0] = ((px[0] as u16 * 255 + a / 2) / a).min(255) as u8;
+            px[1] = ((px[1] as u16 * 255 + a / 2) / a).min(255) as u8;
+            px[2] = ((px[2] as u16 * 255 + a / 2) / a).min(255) as u8;
+        }
+    }
+}
+
+fn mul(m: &[[f32; 3]; 3], v: [f32; 3]) -> [f32; 3] {
+    [
+        m[0][0] * v[0] + m[0][1] * v[1] + m[0][2] * v[2],
+        m[1][0] * v[0] + m[1][1] * v[1] + m[1][2] * v[2],
+        m[2][0] * v[0] + m[2][1] * v[1] + m[2][2] * v[2],
+    ]
+}
+
+/// Converts colors into the output surface space
+#[derive(Default)]
+pub struct ColorManager {
+    output: ColorSpace,
+    #[cfg(feature = "icc")]
+    display: Option<IccOutput>,
+}
+
+/// Transforms into a display ICC profile, one per supported source space
+#[cfg(feature = "icc")]
+struct IccOutput {
+    from_srgb: qcms::Transform,
+    from_p3: qcms::Transform,
+}
+
+impl ColorManager {
+    /// Create a manager that outputs sRGB
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Output to a built-in color space (clears any display ICC profile)
+    pub fn set_output_space(&mut self, space: ColorSpace) {
+        self.output = space;
+        #[cfg(feature = "icc")]
+        {
+            self.display = None;
+        }
+    }
+
+    /// Current built-in output space
+    pub fn output_space(&self) -> ColorSpace {
+        self.output
+    }
+
+    /// Output to a display described by an ICC profile
+    ///
+    /// Returns false (leaving the current output unchanged) if the profile
+    /// cannot be parsed or used as a transform target.
+    #[cfg(feature = "icc")]
+    pub fn set_display_profile(&mut self, icc: &[u8]) -> bool {
+        use qcms::{CIE_xyY, CIE_xyYTRIPLE, DataType, Intent, Profile, Transform};
+
+        let Some(mut display) = Profile::new_from_slice(icc, false) else {
+            return false;
+        };
+        display.precache_output_transform();
+        let srgb = Profile::new_sRGB();
+
+        // Display-P3: DCI-P3 primaries, D65 white point, sRGB transfer curve
+        let xy = |x: f64, y: f64| CIE_xyY { x, y, Y: 1.0 };
+        let curve: Vec<u16> = (0..1024)
+            .map(|i| (srgb_to_linear(i as f32 / 1023.0) * 65535.0).round() as u16)
+            .collect();
+        let Some(p3) = Profile::new_rgb_with_table(
+            xy(0.3127, 0.3290),
+            CIE_xyYTRIPLE {
+                red: xy(0.680, 0.320),
+                green: xy(0.265, 0.690),
+                blue: xy(0.150, 0.060),
+            },
+            &curve,
+        ) else {
+            return false;
+        };
+        let from_srgb = Transform::new(&srgb, &display, DataType::RGBA8, Intent::Perceptual);
+        let from_p3 = Transform::new(&p3, &display, DataType::RGBA8, Intent::Perceptual);
+        match (from_srgb, from_p3) {
+            (Some(from_srgb), Some(from_p3)) => {
+                self.display = Some(IccOutput { from_srgb, from_p3 });
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Check if colors in `source` pass through unchanged
+    pub fn is_identity(&self, source: ColorSpace) -> bool {
+        #[cfg(feature = "icc")]
+        if self.display.is_some() {
+            return false;
+        }
+        source == self.output
+    }
+
+    /// Convert a normalized straight-alpha RGBA color into the output space
+    pub fn convert_color(&self, rgba: [f32; 4], source: ColorSpace) -> [f32; 4] {
+        if self.is_identity(source) {
+            return rgba;
+        }
+
+        #[cfg(feature = "icc")]
+        if self.display.is_some() {
+            let mut px = [
+                (rgba[0].clamp(0.0, 1.0) * 255.0).round() as u8,
+                (rgba[1].clamp(0.0, 1.0) * 255.0).round() as u8,
+                (rgba[2].clamp(0.0, 1.0) * 255.0).round() as u8,
+                255,
+            ];
+            self.convert_pixels(&mut px, source);
+            return [
+                px[0] as f32 / 255.0,
+                px[1] as f32 / 255.0,
+                px[2] as f32 / 255.0,
+                rgba[3],
+            ];
+        }
+
+        let linear = [
+            srgb_to_linear(rgba[0]),
+            srgb_to_linear(rgba[1]),
+            srgb_to_linear(rgba[2]),
+        ];
+        let out = mul(&self.output.xyz_to_rgb(), mul(&source.rgb_to_xyz(), linear));
+        // Out-of-gamut colors are clipped to the output gamut
+        [
+            linear_to_srgb(out[0].clamp(0.0, 1.0)),
+            linear_to_srgb(out[1].clamp(0.0, 1.0)),
+            linear_to_srgb(out[2].clamp(0.0, 1.0)),
+            rgba[3],
+        ]
+    }
+
+    /// Convert straight-alpha RGBA8 pixels into the output space in place
+    pub fn convert_pixels(&self, pixels: &mut [u8], source: ColorSpace) {
+        if self.is_identity(source) {
+            return;
+        }
+
+        #[cfg(feature = "icc")]
+        if let Some(display) = &self.display {
+            match source {
+                ColorSpace::Srgb => display.from_srgb.apply(pixels),
+                ColorSpace::DisplayP3 => display.from_p3.apply(pixels),
+            }
+            return;
+        }
+
+        // Per-channel lookup for decoding; the matrix runs once per pixel
+        let decode = decode_table();
+        let matrix = {
+            let to = source.rgb_to_xyz();
+            let from = self.output.xyz_to_rgb();
+            let mut m = [[0.0f32; 3]; 3];
+            for (i, row) in m.iter_mut().enumerate() {
+                for (j, cell) in row.iter_mut().enumerate() {
+                    *cell = (0..3).map(|k| from[i][k] * to[k][j]).sum();
+                }
+            }
+            m
+        };
+
+        for px in pixels.chunks_exact_mut(4) {
+            let linear = [
+                decode[px[0] as usize],
+                decode[px[1] as usize],
+                decode[px[2] as usize],
+            ];
+            let out = mul(&matrix, linear);
+            px[0] = (linear_to_srgb(out[0].clamp(0.0, 1.0)) * 255.0).round() as u8;
+            px[1] = (linear_to_srgb(out[1].clamp(0.0, 1.0)) * 255.0).round() as u8;
+            px[2] = (linear_to_srgb(out[2].clamp(0.0, 1.0)) * 255.0).round() as u8;
+        }
+    }
+}
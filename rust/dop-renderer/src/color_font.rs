@@ -0,0 +1,200 @@
+//! Color glyphs from emoji and other color fonts
+//!
+//! Bitmap strikes (CBDT, sbix) are decoded from their embedded PNGs and
+//! scaled to the requested size. COLR glyphs are composited from their
+//! layers, each an outline filled with one palette color; COLRv1 gradients
+//! and transforms are not supported, so such layers are left out.
+//!
+//! Glyphs come out as straight-alpha RGBA bitmaps with fontdue metrics, so
+//! they are placed exactly like coverage bitmaps.
+
+use fontdue::{Font, Metrics};
+use ttf_parser::{colr, GlyphId, RasterImageFormat, RgbaColor};
+
+/// Check if a face has any color glyph tables
+pub fn has_color_tables(data: &[u8], index: u32) -> bool {
+    ttf_parser::Face::parse(data, index).is_ok_and(|face| {
+        let tables = face.tables();
+        tables.cbdt.is_some() || tables.sbix.is_some() || tables.colr.is_some()
+    })
+}
+
+/// Rasterize the color version of a glyph to RGBA, None if it has none
+///
+/// `font` must be the face parsed from `data`; outlines of COLR layers are
+/// rasterized with it.
+pub fn rasterize(font: &Font, data: &[u8], index: u32, glyph_index: u16, font_size: f32) -> Option<(Metrics, Vec<u8>)> {
+    let face = ttf_parser::Face::parse(data, index).ok()?;
+    let glyph = GlyphId(glyph_index);
+    let advance_width = font.metrics_indexed(glyph_index, font_size).advance_width;
+    let (mut metrics, pixels) = if face.is_color_glyph(glyph) {
+        rasterize_layers(font, &face, glyph, font_size)?
+    } else {
+        rasterize_strike(&face, glyph, font_size)?
+    };
+    metrics.advance_width = advance_width;
+    Some((metrics, pixels))
+}
+
+/// Decode the PNG of the bitmap strike closest to `font_size` and scale it to size
+fn rasterize_strike(face: &ttf_parser::Face, glyph: GlyphId, font_size: f32) -> Option<(Metrics, Vec<u8>)> {
+    let ppem = font_size.round().clamp(1.0, u16::MAX as f32) as u16;
+    let image = face.glyph_raster_image(glyph, ppem)?;
+    if image.format != RasterImageFormat::PNG {
+        return None;
+    }
+    let (width, height, pixels) = decode_png(image.data)?;
+    let scale = font_size / image.pixels_per_em.max(1) as f32;
+    let scaled_width = ((width as f32 * scale).round() as usize).max(1);
+    let scaled_height = ((height as f32 * scale).round() as usize).max(1);
+    let metrics = Metrics {
+        // Offsets are to the image's bottom-left corner, y up, like fontdue's
+        xmin: (image.x as f32 * scale).round() as i32,
+        ymin: (image.y as f32 * scale).round() as i32,
+        width: scaled_width,
+        height: scaled_height,
+        ..Metrics::default()
+    };
+    Some((metrics, resample(&pixels, width, height, scaled_width, scaled_height)))
+}
+
+/// Decode a PNG to 8-bit straight-alpha RGBA
+fn decode_png(data: &[u8]) -> Option<(usize, usize, Vec<u8>)> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().ok()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).ok()?;
+    let pixels = &buffer[..info.buffer_size()];
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => pixels.to_vec(),
+        png::ColorType::Rgb => pixels.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => pixels.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        png::ColorType::Indexed => return None,
+    };
+    Some((info.width as usize, info.height as usize, rgba))
+}
+
+/// Scale RGBA pixels by averaging the source pixels under each output pixel
+///
+/// Colors are averaged premultiplied so transparent pixels don't darken edges.
+fn resample(src: &[u8], width: usize, height: usize, new_width: usize, new_height: usize) -> Vec<u8> {
+    if (width, height) == (new_width, new_height) {
+        return src.to_vec();
+    }
+    let span = |i: usize, from: usize, to: usize| {
+        let start = i * from / to;
+        start..((i + 1) * from).div_ceil(to).max(start + 1)
+    };
+    let mut out = vec![0u8; new_width * new_height * 4];
+    for y in 0..new_height {
+        let rows = span(y, height, new_height);
+        for x in 0..new_width {
+            let cols = span(x, width, new_width);
+            let mut sum = [0u32; 4];
+            let mut count = 0;
+            for sy in rows.clone() {
+                for sx in cols.clone() {
+                    let p = &src[(sy * width + sx) * 4..][..4];
+                    let a = p[3] as u32;
+                    sum[0] += p[0] as u32 * a;
+                    sum[1] += p[1] as u32 * a;
+                    sum[2] += p[2] as u32 * a;
+                    sum[3] += a;
+                    count += 1;
+                }
+            }
+            let dst = &mut out[(y * new_width + x) * 4..][..4];
+            for c in 0..3 {
+                dst[c] = sum[c].checked_div(sum[3]).unwrap_or(0) as u8;
+            }
+            dst[3] = (sum[3] / count) as u8;
+        }
+    }
+    out
+}
+
+/// Collects the solid-filled layers of a COLR glyph
+#[derive(Default)]
+struct LayerCollector {
+    outline: Option<GlyphId>,
+    layers: Vec<(GlyphId, RgbaColor)>,
+}
+
+impl<'a> colr::Painter<'a> for LayerCollector {
+    fn outline_glyph(&mut self, glyph_id: GlyphId) {
+        self.outline = Some(glyph_id);
+    }
+
+    fn paint(&mut self, paint: colr::Paint<'a>) {
+        if let (Some(glyph), colr::Paint::Solid(color)) = (self.outline, paint) {
+            self.layers.push((glyph, color));
+        }
+    }
+
+    fn push_clip(&mut self) {}
+    fn push_clip_box(&mut self, _clipbox: colr::ClipBox) {}
+    fn pop_clip(&mut self) {}
+    fn push_layer(&mut self, _mode: colr::CompositeMode) {}
+    fn pop_layer(&mut self) {}
+    fn push_translate(&mut self, _tx: f32, _ty: f32) {}
+    fn push_scale(&mut self, _sx: f32, _sy: f32) {}
+    fn push_rotate(&mut self, _angle: f32) {}
+    fn push_skew(&mut self, _skew_x: f32, _skew_y: f32) {}
+    fn push_transform(&mut self, _transform: ttf_parser::Transform) {}
+    fn pop_transform(&mut self) {}
+}
+
+/// Composite the layers of a COLR glyph, bottom layer first
+///
+/// Layers drawn in the foreground color use black, since glyphs are cached
+/// independently of the text color.
+fn rasterize_layers(font: &Font, face: &ttf_parser::Face, glyph: GlyphId, font_size: f32) -> Option<(Metrics, Vec<u8>)> {
+    let mut collector = LayerCollector::default();
+    face.paint_color_glyph(glyph, 0, RgbaColor::new(0, 0, 0, 255), &mut collector)?;
+    let layers: Vec<(Metrics, Vec<u8>, RgbaColor)> = collector
+        .layers
+        .into_iter()
+        .map(|(layer, color)| {
+            let (metrics, coverage) = font.rasterize_indexed(layer.0, font_size);
+            (metrics, coverage, color)
+        })
+        .filter(|(metrics, _, _)| metrics.width > 0 && metrics.height > 0)
+        .collect();
+
+    let left = layers.iter().map(|(m, _, _)| m.xmin).min()?;
+    let bottom = layers.iter().map(|(m, _, _)| m.ymin).min()?;
+    let right = layers.iter().map(|(m, _, _)| m.xmin + m.width as i32).max()?;
+    let top = layers.iter().map(|(m, _, _)| m.ymin + m.height as i32).max()?;
+    let (width, height) = ((right - left) as usize, (top - bottom) as usize);
+
+    let mut pixels = vec![0u8; width * height * 4];
+    for (metrics, coverage, color) in &layers {
+        let (ox, oy) = ((metrics.xmin - left) as usize, (top - metrics.ymin - metrics.height as i32) as usize);
+        for gy in 0..metrics.height {
+            for gx in 0..metrics.width {
+                let a = coverage[gy * metrics.width + gx] as f32 / 255.0 * color.alpha as f32 / 255.0;
+                if a <= 0.0 {
+                    continue;
+                }
+                let dst = &mut pixels[((oy + gy) * width + ox + gx) * 4..][..4];
+                let dst_a = dst[3] as f32 / 255.0;
+                let out_a = a + dst_a * (1.0 - a);
+                for (c, src) in [color.red, color.green, color.blue].into_iter().enumerate() {
+                    dst[c] = ((src as f32 * a + dst[c] as f32 * dst_a * (1.0 - a)) / out_a) as u8;
+                }
+                dst[3] = (out_a * 255.0) as u8;
+            }
+        }
+    }
+
+    let metrics = Metrics {
+        xmin: left,
+        ymin: bottom,
+        width,
+        height,
+        ..Metrics::default()
+    };
+    Some((metrics, pixels))
+}
@@ -0,0 +1,140 @@
+//! Automatic damage tracking for the software renderer
+//!
+//! Each frame records a fingerprint of every draw: its device-space bounds
+//! and a hash of everything else that decides its pixels. Diffing two
+//! frames' fingerprints yields the areas that changed, so a frame that only
+//! recolors a button repaints just that button, and moving one repaints its
+//! old and new position.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::state::ClipBounds;
+
+/// Above this many changed areas the damage is merged into their bounding box
+pub const MAX_DAMAGE_RECTS: usize = 16;
+
+/// Fingerprint of one draw
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct DrawEntry {
+    key: u64,
+    bounds: [u32; 4],
+}
+
+/// Fingerprints of every draw in a frame
+#[derive(Debug, Clone, Default)]
+pub struct FrameSnapshot {
+    entries: Vec<DrawEntry>,
+    /// Anything that repaints the whole frame when it changes (e.g. the clear color)
+    background: u64,
+}
+
+impl FrameSnapshot {
+    /// Create a snapshot for a frame with the given background fingerprint
+    pub fn new(background: impl Hash) -> Self {
+        Self {
+            entries: Vec::new(),
+            background: hash_of(background),
+        }
+    }
+
+    /// Record a draw covering `bounds` whose pixels depend only on `key`
+    pub fn push(&mut self, bounds: ClipBounds, key: impl Hash) {
+        if bounds[2] <= bounds[0] || bounds[3] <= bounds[1] {
+            return;
+        }
+        self.entries.push(DrawEntry {
+            key: hash_of(key),
+            bounds: bounds.map(f32::to_bits),
+        });
+    }
+
+    /// Number of recorded draws
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if no draws were recorded
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Areas that differ from `previous`, or None if the whole frame must be repainted
+    ///
+    /// Draws present in only one of the frames contribute their bounds;
+    /// identical draws that merely changed order within a z-index are not detected.
+    pub fn diff(&self, previous: &FrameSnapshot) -> Option<Vec<ClipBounds>> {
+        if self.background != previous.background {
+            return None;
+        }
+        let mut current = self.entries.clone();
+        let mut before = previous.entries.clone();
+        current.sort_unstable();
+        before.sort_unstable();
+
+        // Walk both sorted lists, keeping entries found in only one of them
+        let mut changed = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < current.len() || j < before.len() {
+            match (current.get(i), before.get(j)) {
+                (Some(a), Some(b)) if a == b => {
+                    i += 1;
+                    j += 1;
+                }
+                (Some(a), Some(b)) if a < b => {
+                    changed.push(a.bounds);
+                    i += 1;
+                }
+                (Some(_), Some(b)) => {
+                    changed.push(b.bounds);
+                    j += 1;
+                }
+                (Some(a), None) => {
+                    changed.push(a.bounds);
+                    i += 1;
+                }
+                (None, Some(b)) => {
+                    changed.push(b.bounds);
+                    j += 1;
+                }
+                (None, None) => break,
+            }
+        }
+        Some(coalesce(
+            changed.into_iter().map(|b| b.map(f32::from_bits)).collect(),
+            MAX_DAMAGE_RECTS,
+        ))
+    }
+}
+
+/// Drop rectangles contained in another and merge everything once there are more than `max`
+pub fn coalesce(mut rects: Vec<ClipBounds>, max: usize) -> Vec<ClipBounds> {
+    let contains = |outer: &ClipBounds, inner: &ClipBounds| {
+        outer[0] <= inner[0] && outer[1] <= inner[1] && outer[2] >= inner[2] && outer[3] >= inner[3]
+    };
+    let mut kept: Vec<ClipBounds> = Vec::with_capacity(rects.len());
+    // Larger rectangles first so contained ones are dropped in a single pass
+    rects.sort_by(|a, b| area(b).total_cmp(&area(a)));
+    for rect in rects {
+        if !kept.iter().any(|k| contains(k, &rect)) {
+            kept.push(rect);
+        }
+    }
+    if kept.len() > max {
+        let union = kept.iter().skip(1).fold(kept[0], |acc, r| {
+            [acc[0].min(r[0]), acc[1].min(r[1]), acc[2].max(r[2]), acc[3].max(r[3])]
+        });
+        return vec![union];
+    }
+    kept
+}
+
+fn area(r: &ClipBounds) -> f32 {
+    (r[2] - r[0]) * (r[3] - r[1])
+}
+
+fn hash_of(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
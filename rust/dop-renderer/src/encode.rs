@@ -0,0 +1,175 @@
+//! In-memory image encoding
+//!
+//! Encodes RGBA8 framebuffers into PNG, BMP, QOI or JPEG bytes so frames
+//! can be handed to the host without going through the filesystem. BMP and
+//! QOI are written here directly; JPEG needs the `images` feature.
+
+/// Output format of `encode_rgba`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExportFormat {
+    Png,
+    /// 32-bit top-down BMP with an alpha channel
+    Bmp,
+    /// Quite OK Image format, 4 channels, sRGB
+    Qoi,
+    /// Baseline JPEG at `JPEG_QUALITY`; alpha is dropped
+    Jpeg,
+}
+
+impl ExportFormat {
+    /// Map an FFI value (0 = PNG, 1 = BMP, 2 = QOI, 3 = JPEG)
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Png),
+            1 => Some(Self::Bmp),
+            2 => Some(Self::Qoi),
+            3 => Some(Self::Jpeg),
+            _ => None,
+        }
+    }
+}
+
+/// Quality used for JPEG output (1-100)
+pub const JPEG_QUALITY: u8 = 90;
+
+/// Encode a `width` x `height` RGBA8 buffer
+pub fn encode_rgba(pixels: &[u8], width: u32, height: u32, format: ExportFormat) -> Result<Vec<u8>, String> {
+    let len = width as usize * height as usize * 4;
+    if width == 0 || height == 0 || pixels.len() < len {
+        return Err(format!("{} bytes is not a {}x{} RGBA buffer", pixels.len(), width, height));
+    }
+    let pixels = &pixels[..len];
+    match format {
+        ExportFormat::Png => encode_png(pixels, width, height),
+        ExportFormat::Bmp => Ok(encode_bmp(pixels, width, height)),
+        ExportFormat::Qoi => Ok(encode_qoi(pixels, width, height)),
+        ExportFormat::Jpeg => encode_jpeg(pixels, width, height),
+    }
+}
+
+fn encode_png(pixels: &[u8], width: u32, height: u32) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(pixels).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+fn encode_bmp(pixels: &[u8], width: u32, height: u32) -> Vec<u8> {
+    const FILE_HEADER: u32 = 14;
+    // BITMAPV4HEADER, which carries the channel masks including alpha
+    const INFO_HEADER: u32 = 108;
+    let image_size = pixels.len() as u32;
+
+    let mut out = Vec::with_capacity((FILE_HEADER + INFO_HEADER) as usize + pixels.len());
+    out.extend_from_slice(b"BM");
+    out.extend_from_slice(&(FILE_HEADER + INFO_HEADER + image_size).to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&(FILE_HEADER + INFO_HEADER).to_le_bytes());
+
+    out.extend_from_slice(&INFO_HEADER.to_le_bytes());
+    out.extend_from_slice(&(width as i32).to_le_bytes());
+    // Negative height stores rows top-down
+    out.extend_from_slice(&(-(height as i32)).to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&32u16.to_le_bytes());
+    // BI_BITFIELDS
+    out.extend_from_slice(&3u32.to_le_bytes());
+    out.extend_from_slice(&image_size.to_le_bytes());
+    // 72 DPI in pixels per meter
+    out.extend_from_slice(&2835u32.to_le_bytes());
+    out.extend_from_slice(&2835u32.to_le_bytes());
+    out.extend_from_slice(&[0; 8]);
+    for mask in [0x00FF_0000u32, 0x0000_FF00, 0x0000_00FF, 0xFF00_0000] {
+        out.extend_from_slice(&mask.to_le_bytes());
+    }
+    out.extend_from_slice(b"BGRs");
+    // Endpoints and gamma, unused for sRGB
+    out.extend_from_slice(&[0; 48]);
+
+    for px in pixels.chunks_exact(4) {
+        out.extend_from_slice(&[px[2], px[1], px[0], px[3]]);
+    }
+    out
+}
+
+fn encode_qoi(pixels: &[u8], width: u32, height: u32) -> Vec<u8> {
+    const OP_INDEX: u8 = 0x00;
+    const OP_DIFF: u8 = 0x40;
+    const OP_LUMA: u8 = 0x80;
+    const OP_RUN: u8 = 0xC0;
+    const OP_RGB: u8 = 0xFE;
+    const OP_RGBA: u8 = 0xFF;
+
+    let mut out = Vec::with_capacity(14 + pixels.len() / 2 + 8);
+    out.extend_from_slice(b"qoif");
+    out.extend_from_slice(&width.to_be_bytes());
+    out.extend_from_slice(&height.to_be_bytes());
+    // 4 channels, sRGB with linear alpha
+    out.extend_from_slice(&[4, 0]);
+
+    let mut index = [[0u8; 4]; 64];
+    let mut prev = [0u8, 0, 0, 255];
+    let mut run = 0u8;
+    let count = pixels.len() / 4;
+    for (i, px) in pixels.chunks_exact(4).enumerate() {
+        let px = [px[0], px[1], px[2], px[3]];
+        if px == prev {
+            run += 1;
+            if run == 62 || i + 1 == count {
+                out.push(OP_RUN | (run - 1));
+                run = 0;
+            }
+            continue;
+        }
+        if run > 0 {
+            out.push(OP_RUN | (run - 1));
+            run = 0;
+        }
+
+        let hash = (px[0] as usize * 3 + px[1] as usize * 5 + px[2] as usize * 7 + px[3] as usize * 11) % 64;
+        if index[hash] == px {
+            out.push(OP_INDEX | hash as u8);
+        } else {
+            index[hash] = px;
+            if px[3] == prev[3] {
+                let dr = px[0].wrapping_sub(prev[0]) as i8;
+                let dg = px[1].wrapping_sub(prev[1]) as i8;
+                let db = px[2].wrapping_sub(prev[2]) as i8;
+                let (dr_dg, db_dg) = (dr.wrapping_sub(dg), db.wrapping_sub(dg));
+                if (-2..=1).contains(&dr) && (-2..=1).contains(&dg) && (-2..=1).contains(&db) {
+                    out.push(OP_DIFF | ((dr + 2) as u8) << 4 | ((dg + 2) as u8) << 2 | (db + 2) as u8);
+                } else if (-32..=31).contains(&dg) && (-8..=7).contains(&dr_dg) && (-8..=7).contains(&db_dg) {
+                    out.push(OP_LUMA | (dg + 32) as u8);
+                    out.push(((dr_dg + 8) as u8) << 4 | (db_dg + 8) as u8);
+                } else {
+                    out.extend_from_slice(&[OP_RGB, px[0], px[1], px[2]]);
+                }
+            } else {
+                out.extend_from_slice(&[OP_RGBA, px[0], px[1], px[2], px[3]]);
+            }
+        }
+        prev = px;
+    }
+
+    out.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+    out
+}
+
+#[cfg(feature = "images")]
+fn encode_jpeg(pixels: &[u8], width: u32, height: u32) -> Result<Vec<u8>, String> {
+    let rgb: Vec<u8> = pixels.chunks_exact(4).flat_map(|px| [px[0], px[1], px[2]]).collect();
+    let mut out = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY)
+        .encode(&rgb, width, height, image::ExtendedColorType::Rgb8)
+        .map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+#[cfg(not(feature = "images"))]
+fn encode_jpeg(_pixels: &[u8], _width: u32, _height: u32) -> Result<Vec<u8>, String> {
+    Err("JPEG export requires the images feature".to_string())
+}
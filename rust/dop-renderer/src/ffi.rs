@@ -4,55 +4,111 @@
 //! using the `ccall` mechanism. The Rust library is built using the unified
 //! BinaryBuilder configuration for cross-platform distribution.
 
-use std::ffi::{c_char, c_float, c_int, CStr};
+#[cfg(feature = "software")]
+use std::collections::HashMap;
+use std::ffi::{c_char, c_double, c_float, c_int, CStr};
 use std::ptr;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 use winit::event_loop::EventLoopProxy;
 
-use crate::renderer::RenderCommand;
 #[cfg(feature = "software")]
-use crate::software::{SoftwareRenderer, TextCommand};
+use crate::optimize::OptimizeStats;
+#[cfg(feature = "software")]
+use crate::color::ColorSpace;
+#[cfg(not(feature = "software"))]
+use crate::blend;
+#[cfg(feature = "software")]
+use crate::caret::FocusRing;
+use crate::caret::SharedCaret;
+use crate::encode::ExportFormat;
+use crate::ffi_guard::{ffi_guard, FfiDefault, LockExt};
+use crate::handles::{DopHandle, HandleRegistry};
+#[cfg(feature = "software")]
+use crate::path::{FillRule, LineCap, LineJoin, Path, StrokeStyle};
+use crate::renderer::{GpuFrame, RenderCommand};
+use crate::scroll::{ScrollArea, ScrollbarStyle};
+#[cfg(feature = "software")]
+use crate::software::{BitmapCommand, RasterStats, SoftwareRenderer, TextCommand};
+#[cfg(all(feature = "software", feature = "images"))]
+use crate::software::ImageCommand;
 #[cfg(not(feature = "software"))]
 use crate::text::FontManager;
-use crate::text::TextShaper;
-use crate::window::{DopEvent, MouseButtonId, WindowConfig, WindowHandle};
+#[cfg(feature = "software")]
+use crate::text::{TextAnchor, TextAntialias, TextDecoration, TextSpan};
+use crate::text::{ShapedText, ShapingBackend, TextShaper, TextSpacing, WrapMode, WrapOptions};
+use crate::window::{
+    DopEvent, ExternalFrame, MouseButtonId, SharedFramebuffer, SharedImeText, WindowCommand, WindowConfig, WindowHandle,
+};
+use crate::virtual_window::VirtualWindow;
+use crate::window_manager::WindowManager;
+#[cfg(feature = "accessibility")]
+use crate::accessibility::{AccessNode, AccessibilityBridge, SharedAccessibility};
+
+// ============================================================================
+// Error FFI
+// ============================================================================
+
+/// Get the message of the last panic caught on this thread (null if none)
+///
+/// Functions that panic return 0, null or an empty value and record the
+/// message here. The string is owned by the library and valid until the
+/// next error or `dop_clear_last_error` on the same thread.
+#[no_mangle]
+pub extern "C" fn dop_last_error() -> *const c_char {
+    crate::ffi_guard::last_error_ptr()
+}
+
+/// Clear the last error of this thread
+#[no_mangle]
+pub extern "C" fn dop_clear_last_error() {
+    crate::ffi_guard::clear_last_error();
+}
 
 /// Initialize the rendering engine
 #[no_mangle]
 pub extern "C" fn dop_init() {
-    let _ = env_logger::try_init();
+    ffi_guard("dop_init", || {
+        let _ = env_logger::try_init();
+    })
 }
 
 /// Create a window configuration
 #[no_mangle]
 pub extern "C" fn dop_window_config_new() -> *mut WindowConfig {
-    Box::into_raw(Box::new(WindowConfig::default()))
+    ffi_guard("dop_window_config_new", || {
+        Box::into_raw(Box::new(WindowConfig::default()))
+    })
 }
 
 /// Free a window configuration
 #[no_mangle]
 pub extern "C" fn dop_window_config_free(config: *mut WindowConfig) {
-    if !config.is_null() {
-        unsafe {
-            drop(Box::from_raw(config));
+    ffi_guard("dop_window_config_free", || {
+        if !config.is_null() {
+            unsafe {
+                drop(Box::from_raw(config));
+            }
         }
-    }
+    })
 }
 
 /// Set window title
 #[no_mangle]
 pub extern "C" fn dop_window_config_set_title(config: *mut WindowConfig, title: *const c_char) {
-    if config.is_null() || title.is_null() {
-        return;
-    }
-    unsafe {
-        let c_str = CStr::from_ptr(title);
-        if let Ok(s) = c_str.to_str() {
-            (*config).title = s.to_string();
+    ffi_guard("dop_window_config_set_title", || {
+        if config.is_null() || title.is_null() {
+            return;
         }
-    }
+        unsafe {
+            let c_str = CStr::from_ptr(title);
+            if let Ok(s) = c_str.to_str() {
+                (*config).title = s.to_string();
+            }
+        }
+    })
 }
 
 /// Set window size
@@ -62,110 +118,156 @@ pub extern "C" fn dop_window_config_set_size(
     width: c_int,
     height: c_int,
 ) {
-    if config.is_null() {
-        return;
-    }
-    unsafe {
-        (*config).width = width as u32;
-        (*config).height = height as u32;
-    }
+    ffi_guard("dop_window_config_set_size", || {
+        if config.is_null() {
+            return;
+        }
+        unsafe {
+            (*config).width = width as u32;
+            (*config).height = height as u32;
+        }
+    })
 }
 
 /// Set window resizable flag
 #[no_mangle]
 pub extern "C" fn dop_window_config_set_resizable(config: *mut WindowConfig, resizable: c_int) {
-    if config.is_null() {
-        return;
-    }
-    unsafe {
-        (*config).resizable = resizable != 0;
-    }
+    ffi_guard("dop_window_config_set_resizable", || {
+        if config.is_null() {
+            return;
+        }
+        unsafe {
+            (*config).resizable = resizable != 0;
+        }
+    })
 }
 
 /// Set window decorated flag
 #[no_mangle]
 pub extern "C" fn dop_window_config_set_decorated(config: *mut WindowConfig, decorated: c_int) {
-    if config.is_null() {
-        return;
-    }
-    unsafe {
-        (*config).decorated = decorated != 0;
-    }
+    ffi_guard("dop_window_config_set_decorated", || {
+        if config.is_null() {
+            return;
+        }
+        unsafe {
+            (*config).decorated = decorated != 0;
+        }
+    })
+}
+
+/// Enable synthesized momentum scrolling after trackpad gestures
+#[no_mangle]
+pub extern "C" fn dop_window_config_set_kinetic_scrolling(config: *mut WindowConfig, enabled: c_int) {
+    ffi_guard("dop_window_config_set_kinetic_scrolling", || {
+        if config.is_null() {
+            return;
+        }
+        unsafe {
+            (*config).kinetic_scrolling = enabled != 0;
+        }
+    })
+}
+
+/// Interpret the configured sizes as physical pixels instead of logical pixels
+#[no_mangle]
+pub extern "C" fn dop_window_config_set_physical_size(config: *mut WindowConfig, enabled: c_int) {
+    ffi_guard("dop_window_config_set_physical_size", || {
+        if config.is_null() {
+            return;
+        }
+        unsafe {
+            (*config).physical_size = enabled != 0;
+        }
+    })
 }
 
 /// Create a window handle (for headless mode without actual window)
 #[no_mangle]
 pub extern "C" fn dop_window_create_headless(width: c_int, height: c_int) -> *mut WindowHandle {
-    let config = WindowConfig {
-        width: width as u32,
-        height: height as u32,
-        ..Default::default()
-    };
-    Box::into_raw(Box::new(WindowHandle::new(config)))
+    ffi_guard("dop_window_create_headless", || {
+        let config = WindowConfig {
+            width: width as u32,
+            height: height as u32,
+            ..Default::default()
+        };
+        Box::into_raw(Box::new(WindowHandle::new(config)))
+    })
 }
 
 /// Free a window handle
 #[no_mangle]
 pub extern "C" fn dop_window_free(handle: *mut WindowHandle) {
-    if !handle.is_null() {
-        unsafe {
-            drop(Box::from_raw(handle));
+    ffi_guard("dop_window_free", || {
+        if !handle.is_null() {
+            unsafe {
+                drop(Box::from_raw(handle));
+            }
         }
-    }
+    })
 }
 
 /// Check if window is open
 #[no_mangle]
 pub extern "C" fn dop_window_is_open(handle: *const WindowHandle) -> c_int {
-    if handle.is_null() {
-        return 0;
-    }
-    unsafe {
-        if (*handle).is_open() {
-            1
-        } else {
-            0
+    ffi_guard("dop_window_is_open", || {
+        if handle.is_null() {
+            return 0;
         }
-    }
+        unsafe {
+            if (*handle).is_open() {
+                1
+            } else {
+                0
+            }
+        }
+    })
 }
 
 /// Close the window
 #[no_mangle]
 pub extern "C" fn dop_window_close(handle: *mut WindowHandle) {
-    if !handle.is_null() {
-        unsafe {
-            (*handle).close();
+    ffi_guard("dop_window_close", || {
+        if !handle.is_null() {
+            unsafe {
+                (*handle).close();
+            }
         }
-    }
+    })
 }
 
 /// Get window width
 #[no_mangle]
 pub extern "C" fn dop_window_get_width(handle: *const WindowHandle) -> c_int {
-    if handle.is_null() {
-        return 0;
-    }
-    unsafe { (*handle).get_size().0 as c_int }
+    ffi_guard("dop_window_get_width", || {
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).get_size().0 as c_int }
+    })
 }
 
 /// Get window height
 #[no_mangle]
 pub extern "C" fn dop_window_get_height(handle: *const WindowHandle) -> c_int {
-    if handle.is_null() {
-        return 0;
-    }
-    unsafe { (*handle).get_size().1 as c_int }
+    ffi_guard("dop_window_get_height", || {
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).get_size().1 as c_int }
+    })
 }
 
 /// Push an event to the window's event queue
 #[no_mangle]
 pub extern "C" fn dop_window_push_event(handle: *mut WindowHandle, event: *const DopEvent) {
-    if handle.is_null() || event.is_null() {
-        return;
-    }
-    unsafe {
-        (*handle).push_event(*event);
-    }
+    ffi_guard("dop_window_push_event", || {
+        if handle.is_null() || event.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).push_event(*event);
+        }
+    })
 }
 
 /// Poll events from the window
@@ -176,35 +278,54 @@ pub extern "C" fn dop_window_poll_events(
     events: *mut DopEvent,
     max_events: c_int,
 ) -> c_int {
-    if handle.is_null() || events.is_null() || max_events <= 0 {
-        return 0;
-    }
-    unsafe {
-        let polled = (*handle).poll_events();
-        let count = polled.len().min(max_events as usize);
-        for (i, event) in polled.into_iter().take(count).enumerate() {
-            *events.add(i) = event;
+    ffi_guard("dop_window_poll_events", || {
+        if handle.is_null() || events.is_null() || max_events <= 0 {
+            return 0;
         }
-        count as c_int
-    }
+        unsafe {
+            let polled = (*handle).poll_events();
+            let count = polled.len().min(max_events as usize);
+            for (i, event) in polled.into_iter().take(count).enumerate() {
+                *events.add(i) = event;
+            }
+            count as c_int
+        }
+    })
+}
+
+/// Place the IME candidate window at the caret (bottom-left, physical pixels)
+#[no_mangle]
+pub extern "C" fn dop_window_set_ime_position(handle: *mut WindowHandle, x: c_float, y: c_float) {
+    ffi_guard("dop_window_set_ime_position", || {
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).set_ime_position(x as f64, y as f64);
+        }
+    })
 }
 
 /// Get mouse X position
 #[no_mangle]
 pub extern "C" fn dop_window_get_mouse_x(handle: *const WindowHandle) -> c_float {
-    if handle.is_null() {
-        return 0.0;
-    }
-    unsafe { (*handle).mouse_position().0 as c_float }
+    ffi_guard("dop_window_get_mouse_x", || {
+        if handle.is_null() {
+            return 0.0;
+        }
+        unsafe { (*handle).mouse_position().0 as c_float }
+    })
 }
 
 /// Get mouse Y position
 #[no_mangle]
 pub extern "C" fn dop_window_get_mouse_y(handle: *const WindowHandle) -> c_float {
-    if handle.is_null() {
-        return 0.0;
-    }
-    unsafe { (*handle).mouse_position().1 as c_float }
+    ffi_guard("dop_window_get_mouse_y", || {
+        if handle.is_null() {
+            return 0.0;
+        }
+        unsafe { (*handle).mouse_position().1 as c_float }
+    })
 }
 
 // ============================================================================
@@ -216,41 +337,191 @@ pub struct ThreadedWindowHandle {
     events: Arc<Mutex<Vec<DopEvent>>>,
     is_open: Arc<Mutex<bool>>,
     size: Arc<Mutex<(u32, u32)>>,
-    external_framebuffer: Arc<Mutex<Option<(Vec<u8>, u32, u32)>>>,
+    external_framebuffer: SharedFramebuffer,
+    // Host-side write target of the double-buffered framebuffer; swapped with
+    // `external_framebuffer` instead of copied
+    back_framebuffer: Mutex<Option<ExternalFrame>>,
+    gpu_frame: Arc<Mutex<Option<GpuFrame>>>,
+    // Copy of the last frame submitted in GPU mode, which the window thread consumes
+    last_gpu_frame: Mutex<Option<GpuFrame>>,
+    gpu_native: bool,
     event_proxy: Arc<Mutex<Option<EventLoopProxy<()>>>>,
     thread_handle: Option<thread::JoinHandle<()>>,
+    kinetic_scrolling: Arc<AtomicBool>,
+    scale_factor: Arc<Mutex<f64>>,
+    ime_text: SharedImeText,
+    commands: mpsc::Sender<WindowCommand>,
+    // Caret of an attached renderer, blinked by the window thread
+    caret: SharedCaret,
+    #[cfg(feature = "accessibility")]
+    accessibility: SharedAccessibility,
 }
 
+/// Live threaded windows, addressed by the handles returned to the host
+static THREADED_WINDOWS: HandleRegistry<ThreadedWindowHandle> = HandleRegistry::new("window");
+
 impl ThreadedWindowHandle {
     pub fn is_open(&self) -> bool {
-        *self.is_open.lock().unwrap()
+        *self.is_open.lock_or_recover()
     }
 
     pub fn poll_events(&self) -> Vec<DopEvent> {
-        let mut events = self.events.lock().unwrap();
+        let mut events = self.events.lock_or_recover();
         std::mem::take(&mut *events)
     }
 
     pub fn get_size(&self) -> (u32, u32) {
-        *self.size.lock().unwrap()
+        *self.size.lock_or_recover()
     }
-}
 
-/// Request the threaded window to close (sets closed flag and wakes event loop)
-#[no_mangle]
-pub extern "C" fn dop_window_request_close_threaded(handle: *mut ThreadedWindowHandle) {
-    if handle.is_null() {
-        return;
+    /// Display scale factor of the window (1.0 until the window opens)
+    pub fn scale_factor(&self) -> f64 {
+        *self.scale_factor.lock_or_recover()
+    }
+
+    /// Caret the window thread blinks over presented frames (shared with an attached renderer)
+    pub fn caret(&self) -> &SharedCaret {
+        &self.caret
+    }
+
+    /// Send a command to the window and wake the event loop to apply it
+    /// Returns false if the window is already closed
+    pub fn send_command(&self, command: WindowCommand) -> bool {
+        if !self.is_open() || self.commands.send(command).is_err() {
+            return false;
+        }
+        if let Ok(proxy_lock) = self.event_proxy.lock() {
+            if let Some(proxy) = &*proxy_lock {
+                let _ = proxy.send_event(());
+            }
+        }
+        true
+    }
+
+    /// Get the back buffer to draw the next frame into: (pixels, width, height)
+    ///
+    /// The buffer matches the current window size and is reallocated (cleared)
+    /// when the size changed. The pointer stays valid until the next swap.
+    pub fn map_framebuffer(&self) -> Option<(*mut u8, u32, u32)> {
+        if self.gpu_native || !self.is_open() {
+            return None;
+        }
+        let (width, height) = self.get_size();
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let mut back = self.back_framebuffer.lock_or_recover();
+        let len = width as usize * height as usize * 4;
+        match &mut *back {
+            Some(frame) if frame.width == width && frame.height == height && frame.data.len() == len => {}
+            _ => *back = Some(ExternalFrame::new(vec![0; len], width, height)),
+        }
+        back.as_mut().map(|frame| (frame.data.as_mut_ptr(), frame.width, frame.height))
+    }
+
+    /// Present the back buffer by swapping it with the front buffer and wake
+    /// the event loop; the previous front buffer becomes the new back buffer
+    pub fn swap_framebuffer(&self) -> bool {
+        if self.gpu_native || !self.is_open() {
+            return false;
+        }
+        let (Ok(mut back), Ok(mut front)) = (self.back_framebuffer.lock(), self.external_framebuffer.lock()) else {
+            return false;
+        };
+        if back.is_none() {
+            return false;
+        }
+        std::mem::swap(&mut *back, &mut *front);
+        if let Some(frame) = front.as_mut() {
+            frame.damage = Some((0, 0, frame.width, frame.height));
+        }
+        drop(front);
+        if let Ok(proxy_lock) = self.event_proxy.lock() {
+            if let Some(proxy) = &*proxy_lock {
+                let _ = proxy.send_event(());
+            }
+        }
+        true
+    }
+
+    /// Ask for a redraw; a `Redraw` event follows once the frame is presented
+    pub fn request_redraw(&self) -> bool {
+        if !self.is_open() {
+            return false;
+        }
+        // Waking the event loop requests a redraw
+        if let Ok(proxy_lock) = self.event_proxy.lock() {
+            if let Some(proxy) = &*proxy_lock {
+                return proxy.send_event(()).is_ok();
+            }
+        }
+        false
     }
 
-    unsafe {
-        // Set closed flag
-        if let Ok(mut is_open) = (*handle).is_open.lock() {
-            *is_open = false;
+    /// Queue a frame for a GPU-mode window and wake the event loop to draw it
+    /// Returns false if the window is not in GPU mode or already closed
+    pub fn submit_gpu_frame(&self, frame: GpuFrame) -> bool {
+        if !self.gpu_native || !self.is_open() {
+            return false;
+        }
+        if let Ok(mut last) = self.last_gpu_frame.lock() {
+            *last = Some(frame.clone());
+        }
+        match self.gpu_frame.lock() {
+            Ok(mut guard) => *guard = Some(frame),
+            Err(_) => return false,
+        }
+        if let Ok(proxy_lock) = self.event_proxy.lock() {
+            if let Some(proxy) = &*proxy_lock {
+                let _ = proxy.send_event(());
+            }
         }
+        true
+    }
+
+    /// Write the last presented frame to a PNG file
+    ///
+    /// Framebuffer windows encode the front buffer as presented. GPU windows
+    /// draw the last submitted frame again on a headless wgpu renderer at the
+    /// window size, since a presented surface texture cannot be read back.
+    pub fn capture_png(&self, path: &str) -> Result<(), String> {
+        let (data, width, height) = if self.gpu_native {
+            let frame = self
+                .last_gpu_frame
+                .lock()
+                .map_err(|_| "GPU frame lock poisoned".to_string())?
+                .clone()
+                .ok_or("no frame has been submitted")?;
+            let (width, height) = self.get_size();
+            let mut renderer = crate::renderer::WgpuHeadlessRenderer::new(width, height)?;
+            renderer.submit_frame(frame);
+            renderer.render().map_err(|e| e.to_string())?;
+            let (width, height) = renderer.size();
+            (renderer.get_framebuffer().to_vec(), width, height)
+        } else {
+            let front = self
+                .external_framebuffer
+                .lock()
+                .map_err(|_| "framebuffer lock poisoned".to_string())?;
+            let frame = front.as_ref().ok_or("no frame has been presented")?;
+            (frame.data.clone(), frame.width, frame.height)
+        };
 
-        // Try to wake the event loop so it can exit promptly
-        if let Ok(proxy_lock) = (*handle).event_proxy.lock() {
+        let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+        let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        writer.write_image_data(&data).map_err(|e| e.to_string())
+    }
+
+    /// Publish a full accessibility tree and wake the event loop to apply it
+    #[cfg(feature = "accessibility")]
+    pub fn publish_accessibility(&self, update: accesskit::TreeUpdate) {
+        if let Ok(mut bridge) = self.accessibility.lock() {
+            bridge.publish(update);
+        }
+        if let Ok(proxy_lock) = self.event_proxy.lock() {
             if let Some(proxy) = &*proxy_lock {
                 let _ = proxy.send_event(());
             }
@@ -258,59 +529,87 @@ pub extern "C" fn dop_window_request_close_threaded(handle: *mut ThreadedWindowH
     }
 }
 
+/// Request the threaded window to close (sets closed flag and wakes event loop)
+#[no_mangle]
+pub extern "C" fn dop_window_request_close_threaded(handle: DopHandle) {
+    ffi_guard("dop_window_request_close_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+
+        unsafe {
+            // Set closed flag
+            if let Ok(mut is_open) = (*handle).is_open.lock() {
+                *is_open = false;
+            }
+
+            // Try to wake the event loop so it can exit promptly
+            if let Ok(proxy_lock) = (*handle).event_proxy.lock() {
+                if let Some(proxy) = &*proxy_lock {
+                    let _ = proxy.send_event(());
+                }
+            }
+        }
+    })
+}
+
 /// Join the threaded window thread, waiting up to `timeout_ms` milliseconds.
 /// Returns 1 on success (thread joined or already gone), 0 on timeout/failure.
 #[no_mangle]
 pub extern "C" fn dop_window_join_threaded_timeout(
-    handle: *mut ThreadedWindowHandle,
+    handle: DopHandle,
     timeout_ms: c_int,
 ) -> c_int {
-    if handle.is_null() {
-        return 0;
-    }
-
-    // Convert timeout to Duration; negative timeout means wait indefinitely
-    let timeout = if timeout_ms < 0 {
-        None
-    } else {
-        Some(Duration::from_millis(timeout_ms as u64))
-    };
+    ffi_guard("dop_window_join_threaded_timeout", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
 
-    unsafe {
-        let start = Instant::now();
+        // Convert timeout to Duration; negative timeout means wait indefinitely
+        let timeout = if timeout_ms < 0 {
+            None
+        } else {
+            Some(Duration::from_millis(timeout_ms as u64))
+        };
 
-        // Wait for the thread to observe the closed flag (polling). This
-        // avoids joining while the thread is still in platform code. If the
-        // caller provided a timeout, honor it.
-        loop {
-            if let Ok(is_open_lock) = (*handle).is_open.lock() {
-                if !*is_open_lock {
+        unsafe {
+            let start = Instant::now();
+
+            // Wait for the thread to observe the closed flag (polling). This
+            // avoids joining while the thread is still in platform code. If the
+            // caller provided a timeout, honor it.
+            loop {
+                if let Ok(is_open_lock) = (*handle).is_open.lock() {
+                    if !*is_open_lock {
+                        break;
+                    }
+                } else {
+                    // Couldn't lock; break and try to join as best-effort
                     break;
                 }
-            } else {
-                // Couldn't lock; break and try to join as best-effort
-                break;
-            }
 
-            if let Some(t) = timeout {
-                if start.elapsed() >= t {
-                    return 0;
+                if let Some(t) = timeout {
+                    if start.elapsed() >= t {
+                        return 0;
+                    }
                 }
-            }
 
-            // Sleep a bit before re-checking
-            std::thread::sleep(Duration::from_millis(5));
-        }
+                // Sleep a bit before re-checking
+                std::thread::sleep(Duration::from_millis(5));
+            }
 
-        // Take the join handle and join it. If it's already None, return success.
-        if let Some(jh) = (*handle).thread_handle.take() {
-            let _ = jh.join();
-            1
-        } else {
-            // Nothing to join; treat as success
-            1
+            // Take the join handle and join it. If it's already None, return success.
+            if let Some(jh) = (*handle).thread_handle.take() {
+                let _ = jh.join();
+                1
+            } else {
+                // Nothing to join; treat as success
+                1
+            }
         }
-    }
+    })
 }
 
 impl Drop for ThreadedWindowHandle {
@@ -324,7 +623,7 @@ impl Drop for ThreadedWindowHandle {
         // loop (best-effort) and *detach* the thread by dropping the
         // JoinHandle. The event-loop thread still holds its own clone(s) of
         // the shared Arcs and will exit on its own when appropriate.
-        *self.is_open.lock().unwrap() = false;
+        *self.is_open.lock_or_recover() = false;
 
         // Try to wake the event loop so it can notice the closed flag and exit.
         if let Ok(proxy_lock) = self.event_proxy.lock() {
@@ -341,6 +640,11 @@ impl Drop for ThreadedWindowHandle {
     }
 }
 
+/// Onscreen mode: the host rasterizes on the CPU and uploads a framebuffer each frame
+pub const ONSCREEN_MODE_FRAMEBUFFER: c_int = 0;
+/// Onscreen mode: rect and text commands are drawn by the window's wgpu renderer
+pub const ONSCREEN_MODE_GPU: c_int = 1;
+
 /// Create an onscreen window (runs in a separate thread)
 /// Returns a handle that can be used to poll events
 #[no_mangle]
@@ -348,1199 +652,5601 @@ pub extern "C" fn dop_window_create_onscreen(
     width: c_int,
     height: c_int,
     title: *const c_char,
-) -> *mut ThreadedWindowHandle {
-    let title = if title.is_null() {
-        "DOP Browser".to_string()
-    } else {
-        unsafe {
-            CStr::from_ptr(title)
-                .to_str()
-                .unwrap_or("DOP Browser")
-                .to_string()
-        }
-    };
-
-    let config = WindowConfig {
-        title,
-        width: width as u32,
-        height: height as u32,
-        ..Default::default()
-    };
-
-    let events = Arc::new(Mutex::new(Vec::new()));
-    let is_open = Arc::new(Mutex::new(true));
-    let size = Arc::new(Mutex::new((width as u32, height as u32)));
-    let external_framebuffer = Arc::new(Mutex::new(None));
-    let event_proxy = Arc::new(Mutex::new(None));
-
-    let events_clone = events.clone();
-    let is_open_clone = is_open.clone();
-    let size_clone = size.clone();
-    let external_framebuffer_clone = external_framebuffer.clone();
-    let event_proxy_clone = event_proxy.clone();
-
-    // Spawn a thread to run the event loop
-    // We'll send the EventLoop proxy back to the creator thread via a channel
-    let (proxy_tx, proxy_rx) = std::sync::mpsc::channel();
-
-    let thread_handle = thread::spawn(move || {
-        use crate::window::DopApp;
-        use winit::event_loop::{ControlFlow, EventLoop, EventLoopBuilder};
-
-        // Create event loop - use builder to enable any_thread on Unix platforms
-        // We'll use unit `()` as the user event type so we can receive proxy wakeups.
-        let event_loop_result = {
-            #[cfg(any(
-                target_os = "linux",
-                target_os = "dragonfly",
-                target_os = "freebsd",
-                target_os = "netbsd",
-                target_os = "openbsd"
-            ))]
-            {
-                use winit::platform::x11::EventLoopBuilderExtX11;
-
-                let mut builder = EventLoopBuilder::new();
-                // Enable any_thread to allow event loop creation on non-main thread
-                // Build with user event type = () so we can create a proxy
-                builder.with_any_thread(true).build()
-            }
+) -> DopHandle {
+    ffi_guard("dop_window_create_onscreen", || {
+        dop_window_create_onscreen_with_mode(width, height, title, ONSCREEN_MODE_FRAMEBUFFER)
+    })
+}
 
-            #[cfg(not(any(
-                target_os = "linux",
-                target_os = "dragonfly",
-                target_os = "freebsd",
-                target_os = "netbsd",
-                target_os = "openbsd"
-            )))]
-            {
-                EventLoop::new()
+/// Create an onscreen window presenting either host framebuffers or GPU-drawn commands
+///
+/// `ONSCREEN_MODE_GPU` requires the `gpu` feature and falls back to
+/// framebuffer mode without it.
+#[no_mangle]
+pub extern "C" fn dop_window_create_onscreen_with_mode(
+    width: c_int,
+    height: c_int,
+    title: *const c_char,
+    mode: c_int,
+) -> DopHandle {
+    ffi_guard("dop_window_create_onscreen_with_mode", || {
+        let gpu_native = mode == ONSCREEN_MODE_GPU && cfg!(feature = "gpu");
+        if mode == ONSCREEN_MODE_GPU && !gpu_native {
+            log::warn!("GPU onscreen mode requires the `gpu` feature; using framebuffer mode");
+        }
+        let title = if title.is_null() {
+            "DOP Browser".to_string()
+        } else {
+            unsafe {
+                CStr::from_ptr(title)
+                    .to_str()
+                    .unwrap_or("DOP Browser")
+                    .to_string()
             }
         };
 
-        let event_loop = match event_loop_result {
-            Ok(el) => el,
-            Err(e) => {
-                log::error!("Failed to create event loop: {:?}", e);
-                *is_open_clone.lock().unwrap() = false;
-                return;
-            }
+        let config = WindowConfig {
+            title,
+            width: width as u32,
+            height: height as u32,
+            ..Default::default()
         };
 
-        // Send the proxy back to the creator thread so it can request redraws
-        let proxy = event_loop.create_proxy();
-        let _ = proxy_tx.send(proxy);
-
-        event_loop.set_control_flow(ControlFlow::Poll);
-
-        // Create app with shared event queue and external framebuffer
-        let mut app = crate::window::DopApp::new_with_shared_events(
-            config,
-            events_clone.clone(),
-            Some(external_framebuffer_clone.clone()),
-        );
-
-        // (The event loop host will keep its own copy of the proxy; the creator
-        // thread will receive the proxy from the channel and store it into the
-        // shared `event_proxy` Arc so it can wake the event loop.)
-
-        // Run the event loop
-        let result = event_loop.run_app(&mut app);
-
-        if let Err(e) = result {
-            log::error!("Event loop error: {:?}", e);
-        }
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let is_open = Arc::new(Mutex::new(true));
+        let size = Arc::new(Mutex::new((width as u32, height as u32)));
+        let external_framebuffer = Arc::new(Mutex::new(None));
+        let gpu_frame = Arc::new(Mutex::new(None));
+        let event_proxy = Arc::new(Mutex::new(None));
+        let kinetic_scrolling = Arc::new(AtomicBool::new(false));
+        let scale_factor = Arc::new(Mutex::new(1.0));
+        let ime_text = SharedImeText::default();
+        let (commands, command_rx) = mpsc::channel();
+        let caret: SharedCaret = Arc::new(Mutex::new(None));
+        #[cfg(feature = "accessibility")]
+        let accessibility = AccessibilityBridge::new_shared();
+
+        let events_clone = events.clone();
+        let is_open_clone = is_open.clone();
+        let size_clone = size.clone();
+        let external_framebuffer_clone = external_framebuffer.clone();
+        let gpu_frame_clone = gpu_frame.clone();
+        let event_proxy_clone = event_proxy.clone();
+        let kinetic_scrolling_clone = kinetic_scrolling.clone();
+        let scale_factor_clone = scale_factor.clone();
+        let ime_text_clone = ime_text.clone();
+        let caret_clone = caret.clone();
+        #[cfg(feature = "accessibility")]
+        let accessibility_clone = accessibility.clone();
+
+        // Spawn a thread to run the event loop
+        // We'll send the EventLoop proxy back to the creator thread via a channel
+        let (proxy_tx, proxy_rx) = std::sync::mpsc::channel();
+
+        let thread_handle = thread::spawn(move || {
+            use winit::event_loop::ControlFlow;
+
+            // Create event loop - enable any_thread where the platform allows it.
+            // The user event type is unit `()` so we can receive proxy wakeups.
+            let event_loop_result = crate::window::any_thread_event_loop();
+
+            let event_loop = match event_loop_result {
+                Ok(el) => el,
+                Err(e) => {
+                    log::error!("Failed to create event loop: {:?}", e);
+                    *is_open_clone.lock_or_recover() = false;
+                    return;
+                }
+            };
+
+            // Send the proxy back to the creator thread so it can request redraws
+            let proxy = event_loop.create_proxy();
+            let _ = proxy_tx.send(proxy);
+
+            event_loop.set_control_flow(ControlFlow::Poll);
+
+            // Create app with shared event queue and either an external framebuffer or GPU frames
+            let mut app = crate::window::DopApp::new_with_shared_events(
+                config,
+                events_clone.clone(),
+                (!gpu_native).then(|| external_framebuffer_clone.clone()),
+            );
+            if gpu_native {
+                app.set_gpu_frame_source(gpu_frame_clone);
+            }
+            app.set_kinetic_scrolling_flag(kinetic_scrolling_clone);
+            app.set_scale_factor_source(scale_factor_clone);
+            app.set_ime_text_store(ime_text_clone);
+            app.set_command_source(command_rx);
+            app.set_caret_source(caret_clone);
+            #[cfg(feature = "accessibility")]
+            app.set_accessibility(accessibility_clone);
+
+            // (The event loop host will keep its own copy of the proxy; the creator
+            // thread will receive the proxy from the channel and store it into the
+            // shared `event_proxy` Arc so it can wake the event loop.)
+
+            // Run the event loop
+            let result = event_loop.run_app(&mut app);
+
+            if let Err(e) = result {
+                log::error!("Event loop error: {:?}", e);
+            }
 
-        // Get the final state from the app
-        if let Some(handle) = app.take_handle() {
-            // Update size
-            let final_size = handle.get_size();
-            *size_clone.lock().unwrap() = final_size;
-        }
+            // Get the final state from the app
+            if let Some(handle) = app.take_handle() {
+                // Update size
+                let final_size = handle.get_size();
+                *size_clone.lock_or_recover() = final_size;
+            }
 
-        // Mark as closed
-        *is_open_clone.lock().unwrap() = false;
-    });
+            // Mark as closed
+            *is_open_clone.lock_or_recover() = false;
+        });
 
-    // Receive the EventLoopProxy from the spawned thread (with timeout)
-    use std::time::Duration;
-    if let Ok(proxy) = proxy_rx.recv_timeout(Duration::from_millis(5000)) {
-        if let Ok(mut p) = event_proxy.lock() {
-            *p = Some(proxy);
+        // Receive the EventLoopProxy from the spawned thread (with timeout)
+        use std::time::Duration;
+        if let Ok(proxy) = proxy_rx.recv_timeout(Duration::from_millis(5000)) {
+            if let Ok(mut p) = event_proxy.lock() {
+                *p = Some(proxy);
+            }
+        } else {
+            log::warn!("Failed to receive EventLoopProxy from window thread within timeout");
         }
-    } else {
-        log::warn!("Failed to receive EventLoopProxy from window thread within timeout");
-    }
 
-    Box::into_raw(Box::new(ThreadedWindowHandle {
-        events,
-        is_open,
-        size,
-        external_framebuffer,
-        event_proxy,
-        thread_handle: Some(thread_handle),
-    }))
+        THREADED_WINDOWS.insert(Box::new(ThreadedWindowHandle {
+            events,
+            is_open,
+            size,
+            external_framebuffer,
+            back_framebuffer: Mutex::new(None),
+            gpu_frame,
+            last_gpu_frame: Mutex::new(None),
+            gpu_native,
+            event_proxy,
+            thread_handle: Some(thread_handle),
+            kinetic_scrolling,
+            scale_factor,
+            ime_text,
+            commands,
+            caret,
+            #[cfg(feature = "accessibility")]
+            accessibility,
+        }))
+    })
 }
 
 /// Update the threaded window external framebuffer with an RGBA buffer (copied).
 #[no_mangle]
 pub extern "C" fn dop_window_update_framebuffer_threaded(
-    handle: *mut ThreadedWindowHandle,
+    handle: DopHandle,
     data: *const u8,
     size: c_int,
     width: c_int,
     height: c_int,
 ) {
-    if handle.is_null() || data.is_null() || size <= 0 || width <= 0 || height <= 0 {
-        return;
-    }
-    unsafe {
-        log::debug!(
-            "ffi: dop_window_update_framebuffer_threaded called (data_len={} width={} height={})",
-            size,
-            width,
-            height
-        );
-
-        // If the window has been closed, skip updating the framebuffer
-        if let Ok(is_open) = (*handle).is_open.lock() {
-            if !*is_open {
-                log::debug!("ffi: window handle not open; skipping framebuffer update");
-                return;
-            }
-        }
-
-        let slice = std::slice::from_raw_parts(data, size as usize);
-        // Copy the provided data into the shared external_framebuffer
-        if let Ok(mut guard) = (*handle).external_framebuffer.lock() {
-            *guard = Some((slice.to_vec(), width as u32, height as u32));
-        } else {
-            log::warn!("ffi: failed to lock external_framebuffer mutex");
+    ffi_guard("dop_window_update_framebuffer_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        if handle.is_null() || data.is_null() || size <= 0 || width <= 0 || height <= 0 {
             return;
         }
+        unsafe {
+            log::debug!(
+                "ffi: dop_window_update_framebuffer_threaded called (data_len={} width={} height={})",
+                size,
+                width,
+                height
+            );
+
+            // If the window has been closed, skip updating the framebuffer
+            if let Ok(is_open) = (*handle).is_open.lock() {
+                if !*is_open {
+                    log::debug!("ffi: window handle not open; skipping framebuffer update");
+                    return;
+                }
+            }
 
-        // Notify event loop to present the new framebuffer (best-effort).
-        // Clone the proxy out of the mutex so we don't hold the lock while sending.
-        if let Ok(proxy_lock) = (*handle).event_proxy.lock() {
-            if let Some(proxy) = &*proxy_lock {
-                match proxy.send_event(()) {
-                    Ok(_) => log::debug!("ffi: sent user event to event loop proxy"),
-                    Err(e) => log::debug!("ffi: failed to send user event to proxy: {:?}", e),
+            let slice = std::slice::from_raw_parts(data, size as usize);
+            // Copy the provided data into the shared external_framebuffer
+            if let Ok(mut guard) = (*handle).external_framebuffer.lock() {
+                *guard = Some(ExternalFrame::new(slice.to_vec(), width as u32, height as u32));
+            } else {
+                log::warn!("ffi: failed to lock external_framebuffer mutex");
+                return;
+            }
+
+            // Notify event loop to present the new framebuffer (best-effort).
+            // Clone the proxy out of the mutex so we don't hold the lock while sending.
+            if let Ok(proxy_lock) = (*handle).event_proxy.lock() {
+                if let Some(proxy) = &*proxy_lock {
+                    match proxy.send_event(()) {
+                        Ok(_) => log::debug!("ffi: sent user event to event loop proxy"),
+                        Err(e) => log::debug!("ffi: failed to send user event to proxy: {:?}", e),
+                    }
+                } else {
+                    log::debug!("ffi: event_proxy is None; cannot wake event loop");
                 }
             } else {
-                log::debug!("ffi: event_proxy is None; cannot wake event loop");
+                log::warn!("ffi: failed to lock event_proxy mutex");
             }
-        } else {
-            log::warn!("ffi: failed to lock event_proxy mutex");
+            log::debug!("ffi: dop_window_update_framebuffer_threaded returning");
         }
-        log::debug!("ffi: dop_window_update_framebuffer_threaded returning");
-    }
+    })
 }
 
-/// Free a threaded window handle
+/// Map the threaded window's back buffer for drawing without a copy
+///
+/// Writes a pointer to a `width` x `height` RGBA buffer sized to the window and
+/// its row stride in bytes; `width` and `height` may be null. Draw the frame,
+/// then call `dop_window_swap_framebuffer_threaded`. The pointer is valid until
+/// the next swap, after which the buffer holds an older frame and must be
+/// mapped again. Returns 1 on success, 0 if the window is closed or in GPU mode.
 #[no_mangle]
-pub extern "C" fn dop_window_free_threaded(handle: *mut ThreadedWindowHandle) {
-    if !handle.is_null() {
+pub extern "C" fn dop_window_map_framebuffer_threaded(
+    handle: DopHandle,
+    ptr: *mut *mut u8,
+    stride: *mut c_int,
+    width: *mut c_int,
+    height: *mut c_int,
+) -> c_int {
+    ffi_guard("dop_window_map_framebuffer_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        if handle.is_null() || ptr.is_null() || stride.is_null() {
+            return 0;
+        }
         unsafe {
-            drop(Box::from_raw(handle));
+            let Some((data, w, h)) = (*handle).map_framebuffer() else {
+                return 0;
+            };
+            *ptr = data;
+            *stride = (w * 4) as c_int;
+            if !width.is_null() {
+                *width = w as c_int;
+            }
+            if !height.is_null() {
+                *height = h as c_int;
+            }
+            1
         }
-    }
+    })
 }
 
-/// Check if threaded window is open
+/// Present the mapped back buffer by swapping it with the front buffer
+///
+/// Only pointers are exchanged, so the frame is not copied. Returns 1 on
+/// success, 0 if nothing was mapped or the window is closed.
 #[no_mangle]
-pub extern "C" fn dop_window_is_open_threaded(handle: *const ThreadedWindowHandle) -> c_int {
-    if handle.is_null() {
-        return 0;
-    }
-    unsafe {
-        if (*handle).is_open() {
-            1
-        } else {
-            0
+pub extern "C" fn dop_window_swap_framebuffer_threaded(handle: DopHandle) -> c_int {
+    ffi_guard("dop_window_swap_framebuffer_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        if handle.is_null() {
+            return 0;
         }
-    }
+        unsafe {
+            if (*handle).swap_framebuffer() { 1 } else { 0 }
+        }
+    })
+}
+
+/// Copy only a region of an RGBA buffer into the threaded window framebuffer
+///
+/// `data` is the full `width` x `height` frame; only the rows and columns of
+/// the region are copied, and only they are uploaded to the GPU when the frame
+/// is presented. Falls back to copying the whole buffer when the window holds
+/// no frame of the same size yet.
+#[no_mangle]
+pub extern "C" fn dop_window_update_framebuffer_region_threaded(
+    handle: DopHandle,
+    data: *const u8,
+    size: c_int,
+    width: c_int,
+    height: c_int,
+    region_x: c_int,
+    region_y: c_int,
+    region_width: c_int,
+    region_height: c_int,
+) {
+    ffi_guard("dop_window_update_framebuffer_region_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        if handle.is_null() || data.is_null() || size <= 0 || width <= 0 || height <= 0 {
+            return;
+        }
+        if (width as usize * height as usize * 4) > size as usize {
+            return;
+        }
+        unsafe {
+            if !(*handle).is_open() {
+                return;
+            }
+
+            let slice = std::slice::from_raw_parts(data, size as usize);
+            let (w, h) = (width as u32, height as u32);
+            // Clamp the region to the frame; an empty region has nothing to present
+            let x0 = region_x.clamp(0, width) as usize;
+            let y0 = region_y.clamp(0, height) as usize;
+            let x1 = region_x.saturating_add(region_width).clamp(0, width) as usize;
+            let y1 = region_y.saturating_add(region_height).clamp(0, height) as usize;
+            if x1 <= x0 || y1 <= y0 {
+                return;
+            }
+
+            match (*handle).external_framebuffer.lock() {
+                Ok(mut guard) => match &mut *guard {
+                    Some(frame) if frame.width == w && frame.height == h && frame.data.len() == slice.len() => {
+                        let stride = width as usize * 4;
+                        for row in y0..y1 {
+                            let start = row * stride + x0 * 4;
+                            let end = row * stride + x1 * 4;
+                            frame.data[start..end].copy_from_slice(&slice[start..end]);
+                        }
+                        // Only this area is uploaded to the GPU on the next present
+                        frame.add_damage(x0 as u32, y0 as u32, (x1 - x0) as u32, (y1 - y0) as u32);
+                    }
+                    _ => *guard = Some(ExternalFrame::new(slice.to_vec(), w, h)),
+                },
+                Err(_) => {
+                    log::warn!("ffi: failed to lock external_framebuffer mutex");
+                    return;
+                }
+            }
+
+            if let Ok(proxy_lock) = (*handle).event_proxy.lock() {
+                if let Some(proxy) = &*proxy_lock {
+                    let _ = proxy.send_event(());
+                }
+            }
+        }
+    })
+}
+
+/// Hand the renderer's recorded rects and text to a GPU-mode window for drawing
+///
+/// Replaces `dop_renderer_render` + `dop_window_update_framebuffer_threaded`
+/// for windows created with `ONSCREEN_MODE_GPU`. The renderer's commands are
+/// left in place. Returns 1 on success, 0 if the window is not in GPU mode.
+#[cfg(all(feature = "gpu", feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_window_submit_renderer_threaded(
+    handle: DopHandle,
+    renderer: DopHandle,
+) -> c_int {
+    ffi_guard("dop_window_submit_renderer_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        let renderer = RENDERERS.get(renderer) as *const RendererHandle;
+        if handle.is_null() || renderer.is_null() {
+            return 0;
+        }
+        unsafe {
+            let frame = (*renderer).renderer.gpu_frame();
+            if (*handle).submit_gpu_frame(frame) { 1 } else { 0 }
+        }
+    })
+}
+
+/// Write what a threaded window last presented to a PNG file
+///
+/// Framebuffer windows save the front buffer; GPU windows redraw the last
+/// submitted frame offscreen. Returns 1 on success, 0 if nothing was
+/// presented yet or the file could not be written.
+#[no_mangle]
+pub extern "C" fn dop_window_capture_threaded(handle: DopHandle, path: *const c_char) -> c_int {
+    ffi_guard("dop_window_capture_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle) as *const ThreadedWindowHandle;
+        if handle.is_null() || path.is_null() {
+            return 0;
+        }
+        unsafe {
+            let Ok(path) = CStr::from_ptr(path).to_str() else {
+                return 0;
+            };
+            match (*handle).capture_png(path) {
+                Ok(()) => 1,
+                Err(e) => {
+                    log::warn!("Failed to capture window: {}", e);
+                    0
+                }
+            }
+        }
+    })
+}
+
+/// Free a threaded window handle
+#[no_mangle]
+pub extern "C" fn dop_window_free_threaded(handle: DopHandle) {
+    ffi_guard("dop_window_free_threaded", || {
+        THREADED_WINDOWS.remove(handle);
+    })
+}
+
+/// Check if threaded window is open
+#[no_mangle]
+pub extern "C" fn dop_window_is_open_threaded(handle: DopHandle) -> c_int {
+    ffi_guard("dop_window_is_open_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle) as *const ThreadedWindowHandle;
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*handle).is_open() {
+                1
+            } else {
+                0
+            }
+        }
+    })
 }
 
 /// Poll events from threaded window
 #[no_mangle]
 pub extern "C" fn dop_window_poll_events_threaded(
-    handle: *mut ThreadedWindowHandle,
+    handle: DopHandle,
     events: *mut DopEvent,
     max_events: c_int,
 ) -> c_int {
-    if handle.is_null() || events.is_null() || max_events <= 0 {
-        return 0;
-    }
-    unsafe {
-        let polled = (*handle).poll_events();
-        let count = polled.len().min(max_events as usize);
-        for (i, event) in polled.into_iter().take(count).enumerate() {
-            *events.add(i) = event;
+    ffi_guard("dop_window_poll_events_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        if handle.is_null() || events.is_null() || max_events <= 0 {
+            return 0;
         }
-        count as c_int
-    }
+        unsafe {
+            let polled = (*handle).poll_events();
+            let count = polled.len().min(max_events as usize);
+            for (i, event) in polled.into_iter().take(count).enumerate() {
+                *events.add(i) = event;
+            }
+            count as c_int
+        }
+    })
 }
 
 /// Get threaded window width
 #[no_mangle]
-pub extern "C" fn dop_window_get_width_threaded(handle: *const ThreadedWindowHandle) -> c_int {
-    if handle.is_null() {
-        return 0;
-    }
-    unsafe { (*handle).get_size().0 as c_int }
+pub extern "C" fn dop_window_get_width_threaded(handle: DopHandle) -> c_int {
+    ffi_guard("dop_window_get_width_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle) as *const ThreadedWindowHandle;
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).get_size().0 as c_int }
+    })
 }
 
 /// Get threaded window height
 #[no_mangle]
-pub extern "C" fn dop_window_get_height_threaded(handle: *const ThreadedWindowHandle) -> c_int {
-    if handle.is_null() {
-        return 0;
-    }
-    unsafe { (*handle).get_size().1 as c_int }
+pub extern "C" fn dop_window_get_height_threaded(handle: DopHandle) -> c_int {
+    ffi_guard("dop_window_get_height_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle) as *const ThreadedWindowHandle;
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).get_size().1 as c_int }
+    })
 }
 
-// ============================================================================
-// Renderer FFI
-// ============================================================================
+/// Request a redraw of the threaded window
+///
+/// An `EventType::Redraw` event is queued after the next frame is presented,
+/// so a host that produces a frame per Redraw event runs at the display rate.
+/// Returns 1 if the request was sent, 0 if the window is closed.
+#[no_mangle]
+pub extern "C" fn dop_window_request_redraw_threaded(handle: DopHandle) -> c_int {
+    ffi_guard("dop_window_request_redraw_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*handle).request_redraw() { 1 } else { 0 }
+        }
+    })
+}
 
-/// Renderer handle for FFI - uses software rendering by default
-#[cfg(feature = "software")]
-pub struct RendererHandle {
-    renderer: SoftwareRenderer,
+/// Change the threaded window's title
+#[no_mangle]
+pub extern "C" fn dop_window_set_title_threaded(handle: DopHandle, title: *const c_char) -> c_int {
+    ffi_guard("dop_window_set_title_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        if handle.is_null() || title.is_null() {
+            return 0;
+        }
+        unsafe {
+            let Ok(title) = CStr::from_ptr(title).to_str() else {
+                return 0;
+            };
+            if (*handle).send_command(WindowCommand::SetTitle(title.to_string())) { 1 } else { 0 }
+        }
+    })
 }
 
-/// Renderer handle for FFI - fallback when software feature is disabled
-#[cfg(not(feature = "software"))]
-#[allow(dead_code)]
-pub struct RendererHandle {
-    commands: Vec<RenderCommand>,
-    text_commands: Vec<TextCommandFFI>,
-    framebuffer: Vec<u8>,
-    width: u32,
-    height: u32,
-    font_manager: FontManager,
+/// Request a new inner size for the threaded window in physical pixels
+///
+/// The platform may adjust or refuse it; a resize event reports the actual size.
+#[no_mangle]
+pub extern "C" fn dop_window_set_size_threaded(handle: DopHandle, width: c_int, height: c_int) -> c_int {
+    ffi_guard("dop_window_set_size_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        if handle.is_null() || width <= 0 || height <= 0 {
+            return 0;
+        }
+        unsafe {
+            if (*handle).send_command(WindowCommand::SetSize(width as u32, height as u32)) { 1 } else { 0 }
+        }
+    })
 }
 
-/// Text command for FFI (used when software feature is disabled)
-#[cfg(not(feature = "software"))]
-#[derive(Debug, Clone)]
-struct TextCommandFFI {
-    text: String,
-    x: f32,
-    y: f32,
-    font_size: f32,
-    color_r: f32,
-    color_g: f32,
-    color_b: f32,
-    color_a: f32,
-    font_id: u32,
+/// Switch the threaded window to borderless fullscreen or back to windowed
+#[no_mangle]
+pub extern "C" fn dop_window_set_fullscreen_threaded(handle: DopHandle, fullscreen: c_int) -> c_int {
+    ffi_guard("dop_window_set_fullscreen_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*handle).send_command(WindowCommand::SetFullscreen(fullscreen != 0)) { 1 } else { 0 }
+        }
+    })
 }
 
-/// Create a headless renderer using software rendering (tiny-skia)
-#[cfg(feature = "software")]
+/// Minimize the threaded window
 #[no_mangle]
-pub extern "C" fn dop_renderer_create_headless(width: c_int, height: c_int) -> *mut RendererHandle {
-    let renderer = SoftwareRenderer::new(width as u32, height as u32);
-    Box::into_raw(Box::new(RendererHandle { renderer }))
+pub extern "C" fn dop_window_minimize_threaded(handle: DopHandle) -> c_int {
+    ffi_guard("dop_window_minimize_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*handle).send_command(WindowCommand::SetMinimized(true)) { 1 } else { 0 }
+        }
+    })
 }
 
-/// Create a headless renderer (fallback implementation)
-#[cfg(not(feature = "software"))]
+/// Maximize or restore the threaded window
 #[no_mangle]
-pub extern "C" fn dop_renderer_create_headless(width: c_int, height: c_int) -> *mut RendererHandle {
-    let w = width as u32;
-    let h = height as u32;
-    let framebuffer = vec![255u8; (w * h * 4) as usize]; // White background
+pub extern "C" fn dop_window_set_maximized_threaded(handle: DopHandle, maximized: c_int) -> c_int {
+    ffi_guard("dop_window_set_maximized_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*handle).send_command(WindowCommand::SetMaximized(maximized != 0)) { 1 } else { 0 }
+        }
+    })
+}
 
-    Box::into_raw(Box::new(RendererHandle {
-        commands: Vec::new(),
-        text_commands: Vec::new(),
-        framebuffer,
-        width: w,
-        height: h,
-        font_manager: FontManager::new(),
-    }))
+/// Get the threaded window's display scale factor (physical pixels per logical pixel)
+#[no_mangle]
+pub extern "C" fn dop_window_get_scale_factor_threaded(handle: DopHandle) -> c_double {
+    ffi_guard("dop_window_get_scale_factor_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle) as *const ThreadedWindowHandle;
+        if handle.is_null() {
+            return 1.0;
+        }
+        unsafe { (*handle).scale_factor() }
+    })
 }
 
-/// Free a renderer
+/// Place the threaded window's IME candidate window at the caret (bottom-left, physical pixels)
 #[no_mangle]
-pub extern "C" fn dop_renderer_free(handle: *mut RendererHandle) {
-    if !handle.is_null() {
+pub extern "C" fn dop_window_set_ime_position_threaded(handle: DopHandle, x: c_float, y: c_float) {
+    ffi_guard("dop_window_set_ime_position_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        if handle.is_null() {
+            return;
+        }
         unsafe {
-            drop(Box::from_raw(handle));
+            (*handle).send_command(WindowCommand::SetImePosition(x as f64, y as f64));
         }
-    }
+    })
 }
 
-/// Clear the renderer
-#[cfg(feature = "software")]
+/// Copy the UTF-8 text of an IME event (`char_code` of `ImePreedit`/`ImeCommit`)
+///
+/// Writes at most `capacity` bytes to `buffer` (not NUL-terminated) and
+/// returns the full length in bytes, or -1 if the text is no longer stored.
 #[no_mangle]
-pub extern "C" fn dop_renderer_clear(handle: *mut RendererHandle) {
-    if handle.is_null() {
-        return;
-    }
-    unsafe {
-        (*handle).renderer.clear();
-    }
+pub extern "C" fn dop_window_get_ime_text_threaded(
+    handle: DopHandle,
+    text_id: u32,
+    buffer: *mut u8,
+    capacity: c_int,
+) -> c_int {
+    ffi_guard("dop_window_get_ime_text_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle) as *const ThreadedWindowHandle;
+        if handle.is_null() {
+            return -1;
+        }
+        unsafe { copy_ime_text(&(*handle).ime_text, text_id, buffer, capacity) }
+    })
 }
 
-/// Clear the renderer (fallback)
-#[cfg(not(feature = "software"))]
-#[no_mangle]
-pub extern "C" fn dop_renderer_clear(handle: *mut RendererHandle) {
-    if handle.is_null() {
-        return;
-    }
-    unsafe {
-        (*handle).commands.clear();
-        (*handle).text_commands.clear();
+/// Copy stored IME text into a caller buffer, returning its full length or -1
+unsafe fn copy_ime_text(store: &SharedImeText, text_id: u32, buffer: *mut u8, capacity: c_int) -> c_int {
+    let Ok(store) = store.lock() else {
+        return -1;
+    };
+    let Some(text) = store.get(text_id) else {
+        return -1;
+    };
+    if !buffer.is_null() && capacity > 0 {
+        let n = text.len().min(capacity as usize);
+        ptr::copy_nonoverlapping(text.as_ptr(), buffer, n);
     }
+    text.len() as c_int
 }
 
-/// Set clear color
-#[cfg(feature = "software")]
+/// Enable or disable momentum scroll events on the threaded window
 #[no_mangle]
-pub extern "C" fn dop_renderer_set_clear_color(
-    handle: *mut RendererHandle,
-    r: c_float,
-    g: c_float,
-    b: c_float,
-    a: c_float,
+pub extern "C" fn dop_window_set_kinetic_scrolling_threaded(
+    handle: DopHandle,
+    enabled: c_int,
 ) {
-    if handle.is_null() {
-        return;
-    }
-    unsafe {
-        (*handle).renderer.set_clear_color(r, g, b, a);
-    }
+    ffi_guard("dop_window_set_kinetic_scrolling_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).kinetic_scrolling.store(enabled != 0, Ordering::Relaxed);
+        }
+    })
 }
 
-/// Set clear color (fallback)
-#[cfg(not(feature = "software"))]
+// ============================================================================
+// Window Manager (several windows on one event loop)
+// ============================================================================
+
+/// Start a window manager running a single event loop thread
+/// Returns null if the event loop could not be created
+///
+/// Not supported on macOS; use `dop_window_manager_create_main_thread` there.
 #[no_mangle]
-pub extern "C" fn dop_renderer_set_clear_color(
-    handle: *mut RendererHandle,
-    r: c_float,
-    g: c_float,
-    b: c_float,
-    a: c_float,
-) {
-    if handle.is_null() {
-        return;
-    }
-    let handle = unsafe { &mut *handle };
-
-    // Fill framebuffer with clear color
-    let w = handle.width;
-    let h = handle.height;
-    let rb = (r * 255.0) as u8;
-    let gb = (g * 255.0) as u8;
-    let bb = (b * 255.0) as u8;
-    let ab = (a * 255.0) as u8;
-
-    for i in 0..(w * h) as usize {
-        let idx = i * 4;
-        handle.framebuffer[idx] = rb;
-        handle.framebuffer[idx + 1] = gb;
-        handle.framebuffer[idx + 2] = bb;
-        handle.framebuffer[idx + 3] = ab;
-    }
+pub extern "C" fn dop_window_manager_create() -> *mut WindowManager {
+    ffi_guard("dop_window_manager_create", || {
+        match WindowManager::new() {
+            Some(manager) => Box::into_raw(Box::new(manager)),
+            None => ptr::null_mut(),
+        }
+    })
 }
 
-/// Add a rectangle render command
-#[cfg(feature = "software")]
+/// Create a window manager whose event loop runs on the calling thread
+///
+/// Call this from the main thread on macOS and drive the loop with
+/// `dop_window_manager_pump`. Returns null if the event loop could not be created.
 #[no_mangle]
-pub extern "C" fn dop_renderer_add_rect(
-    handle: *mut RendererHandle,
-    x: c_float,
-    y: c_float,
-    width: c_float,
-    height: c_float,
-    r: c_float,
-    g: c_float,
-    b: c_float,
-    a: c_float,
-    z_index: c_int,
-) {
-    if handle.is_null() {
-        return;
-    }
-    unsafe {
-        (*handle).renderer.add_rect(RenderCommand {
-            x,
-            y,
-            width,
-            height,
-            color_r: r,
-            color_g: g,
-            color_b: b,
-            color_a: a,
-            texture_id: 0,
-            z_index,
-        });
-    }
+pub extern "C" fn dop_window_manager_create_main_thread() -> *mut WindowManager {
+    ffi_guard("dop_window_manager_create_main_thread", || {
+        match WindowManager::new_main_thread() {
+            Some(manager) => Box::into_raw(Box::new(manager)),
+            None => ptr::null_mut(),
+        }
+    })
 }
 
-/// Add a rectangle render command (fallback)
-#[cfg(not(feature = "software"))]
+/// Dispatch pending events of a main-thread window manager, waiting up to `timeout_ms` for one
+/// Returns 1 while the event loop runs, 0 once it has stopped or if it runs on its own thread
 #[no_mangle]
-pub extern "C" fn dop_renderer_add_rect(
-    handle: *mut RendererHandle,
-    x: c_float,
-    y: c_float,
-    width: c_float,
-    height: c_float,
-    r: c_float,
-    g: c_float,
-    b: c_float,
-    a: c_float,
-    z_index: c_int,
-) {
-    if handle.is_null() {
-        return;
-    }
-    unsafe {
-        (*handle).commands.push(RenderCommand {
-            x,
-            y,
-            width,
-            height,
-            color_r: r,
-            color_g: g,
-            color_b: b,
-            color_a: a,
-            texture_id: 0,
-            z_index,
-        });
-    }
+pub extern "C" fn dop_window_manager_pump(manager: *mut WindowManager, timeout_ms: c_int) -> c_int {
+    ffi_guard("dop_window_manager_pump", || {
+        if manager.is_null() {
+            return 0;
+        }
+        let timeout = Duration::from_millis(timeout_ms.max(0) as u64);
+        unsafe {
+            if (*manager).pump(timeout) { 1 } else { 0 }
+        }
+    })
 }
 
-/// Render the frame using software rendering (tiny-skia)
-#[cfg(feature = "software")]
+/// Stop the window manager's event loop, closing its windows
 #[no_mangle]
-pub extern "C" fn dop_renderer_render(handle: *mut RendererHandle) {
-    if handle.is_null() {
-        return;
-    }
-    unsafe {
-        (*handle).renderer.render();
-    }
+pub extern "C" fn dop_window_manager_free(manager: *mut WindowManager) {
+    ffi_guard("dop_window_manager_free", || {
+        if !manager.is_null() {
+            unsafe {
+                drop(Box::from_raw(manager));
+            }
+        }
+    })
+}
+
+/// Open a window on the manager's event loop
+///
+/// `mode` is `ONSCREEN_MODE_FRAMEBUFFER` or `ONSCREEN_MODE_GPU`, as for
+/// `dop_window_create_onscreen_with_mode`. Returns the window ID, or 0 on failure.
+#[no_mangle]
+pub extern "C" fn dop_window_manager_create_window(
+    manager: *mut WindowManager,
+    width: c_int,
+    height: c_int,
+    title: *const c_char,
+    mode: c_int,
+) -> u32 {
+    ffi_guard("dop_window_manager_create_window", || {
+        if manager.is_null() || width <= 0 || height <= 0 {
+            return 0;
+        }
+        let gpu_native = mode == ONSCREEN_MODE_GPU && cfg!(feature = "gpu");
+        if mode == ONSCREEN_MODE_GPU && !gpu_native {
+            log::warn!("GPU onscreen mode requires the `gpu` feature; using framebuffer mode");
+        }
+        let mut config = WindowConfig {
+            width: width as u32,
+            height: height as u32,
+            ..Default::default()
+        };
+        if !title.is_null() {
+            if let Ok(title) = unsafe { CStr::from_ptr(title) }.to_str() {
+                config.title = title.to_string();
+            }
+        }
+        unsafe { (*manager).create_window(config, gpu_native) }
+    })
+}
+
+/// Close a managed window
+#[no_mangle]
+pub extern "C" fn dop_window_manager_close_window(manager: *mut WindowManager, window_id: u32) -> c_int {
+    ffi_guard("dop_window_manager_close_window", || {
+        if manager.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*manager).close_window(window_id) { 1 } else { 0 }
+        }
+    })
+}
+
+/// Check if a managed window is open
+#[no_mangle]
+pub extern "C" fn dop_window_manager_is_open(manager: *const WindowManager, window_id: u32) -> c_int {
+    ffi_guard("dop_window_manager_is_open", || {
+        if manager.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*manager).window(window_id).is_some_and(|w| w.is_open()) {
+                1
+            } else {
+                0
+            }
+        }
+    })
+}
+
+/// Poll events from a managed window
+#[no_mangle]
+pub extern "C" fn dop_window_manager_poll_events(
+    manager: *const WindowManager,
+    window_id: u32,
+    events: *mut DopEvent,
+    max_events: c_int,
+) -> c_int {
+    ffi_guard("dop_window_manager_poll_events", || {
+        if manager.is_null() || events.is_null() || max_events <= 0 {
+            return 0;
+        }
+        unsafe {
+            let Some(window) = (*manager).window(window_id) else {
+                return 0;
+            };
+            let polled = window.poll_events();
+            let count = polled.len().min(max_events as usize);
+            for (i, event) in polled.into_iter().take(count).enumerate() {
+                *events.add(i) = event;
+            }
+            count as c_int
+        }
+    })
+}
+
+/// Get a managed window's size
+/// Returns 1 on success, 0 if the window is unknown
+#[no_mangle]
+pub extern "C" fn dop_window_manager_get_size(
+    manager: *const WindowManager,
+    window_id: u32,
+    width: *mut c_int,
+    height: *mut c_int,
+) -> c_int {
+    ffi_guard("dop_window_manager_get_size", || {
+        if manager.is_null() || width.is_null() || height.is_null() {
+            return 0;
+        }
+        unsafe {
+            let Some(window) = (*manager).window(window_id) else {
+                return 0;
+            };
+            let (w, h) = window.get_size();
+            *width = w as c_int;
+            *height = h as c_int;
+            1
+        }
+    })
+}
+
+/// Request a redraw of a managed window; a Redraw event follows the next presented frame
+#[no_mangle]
+pub extern "C" fn dop_window_manager_request_redraw(manager: *const WindowManager, window_id: u32) -> c_int {
+    ffi_guard("dop_window_manager_request_redraw", || {
+        if manager.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*manager).request_redraw(window_id) { 1 } else { 0 }
+        }
+    })
+}
+
+/// Change a managed window's title
+#[no_mangle]
+pub extern "C" fn dop_window_manager_set_title(manager: *const WindowManager, window_id: u32, title: *const c_char) -> c_int {
+    ffi_guard("dop_window_manager_set_title", || {
+        if manager.is_null() || title.is_null() {
+            return 0;
+        }
+        unsafe {
+            let Ok(title) = CStr::from_ptr(title).to_str() else {
+                return 0;
+            };
+            if (*manager).send_command(window_id, WindowCommand::SetTitle(title.to_string())) { 1 } else { 0 }
+        }
+    })
+}
+
+/// Request a new inner size for a managed window in physical pixels
+#[no_mangle]
+pub extern "C" fn dop_window_manager_set_size(
+    manager: *const WindowManager,
+    window_id: u32,
+    width: c_int,
+    height: c_int,
+) -> c_int {
+    ffi_guard("dop_window_manager_set_size", || {
+        if manager.is_null() || width <= 0 || height <= 0 {
+            return 0;
+        }
+        unsafe {
+            if (*manager).send_command(window_id, WindowCommand::SetSize(width as u32, height as u32)) { 1 } else { 0 }
+        }
+    })
+}
+
+/// Switch a managed window to borderless fullscreen or back to windowed
+#[no_mangle]
+pub extern "C" fn dop_window_manager_set_fullscreen(manager: *const WindowManager, window_id: u32, fullscreen: c_int) -> c_int {
+    ffi_guard("dop_window_manager_set_fullscreen", || {
+        if manager.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*manager).send_command(window_id, WindowCommand::SetFullscreen(fullscreen != 0)) { 1 } else { 0 }
+        }
+    })
+}
+
+/// Minimize a managed window
+#[no_mangle]
+pub extern "C" fn dop_window_manager_minimize(manager: *const WindowManager, window_id: u32) -> c_int {
+    ffi_guard("dop_window_manager_minimize", || {
+        if manager.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*manager).send_command(window_id, WindowCommand::SetMinimized(true)) { 1 } else { 0 }
+        }
+    })
+}
+
+/// Maximize or restore a managed window
+#[no_mangle]
+pub extern "C" fn dop_window_manager_set_maximized(manager: *const WindowManager, window_id: u32, maximized: c_int) -> c_int {
+    ffi_guard("dop_window_manager_set_maximized", || {
+        if manager.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*manager).send_command(window_id, WindowCommand::SetMaximized(maximized != 0)) { 1 } else { 0 }
+        }
+    })
+}
+
+/// Get a managed window's display scale factor (1.0 if the window is unknown)
+#[no_mangle]
+pub extern "C" fn dop_window_manager_get_scale_factor(manager: *const WindowManager, window_id: u32) -> c_double {
+    ffi_guard("dop_window_manager_get_scale_factor", || {
+        if manager.is_null() {
+            return 1.0;
+        }
+        unsafe { (*manager).window(window_id).map_or(1.0, |w| w.scale_factor()) }
+    })
+}
+
+/// Place a managed window's IME candidate window at the caret (bottom-left, physical pixels)
+#[no_mangle]
+pub extern "C" fn dop_window_manager_set_ime_position(
+    manager: *const WindowManager,
+    window_id: u32,
+    x: c_float,
+    y: c_float,
+) {
+    ffi_guard("dop_window_manager_set_ime_position", || {
+        if manager.is_null() {
+            return;
+        }
+        unsafe {
+            (*manager).send_command(window_id, WindowCommand::SetImePosition(x as f64, y as f64));
+        }
+    })
+}
+
+/// Copy the UTF-8 text of a managed window's IME event, as `dop_window_get_ime_text_threaded`
+#[no_mangle]
+pub extern "C" fn dop_window_manager_get_ime_text(
+    manager: *const WindowManager,
+    window_id: u32,
+    text_id: u32,
+    buffer: *mut u8,
+    capacity: c_int,
+) -> c_int {
+    ffi_guard("dop_window_manager_get_ime_text", || {
+        if manager.is_null() {
+            return -1;
+        }
+        unsafe {
+            match (*manager).window(window_id) {
+                Some(window) => copy_ime_text(window.ime_text(), text_id, buffer, capacity),
+                None => -1,
+            }
+        }
+    })
+}
+
+/// Copy an RGBA framebuffer into a managed window for presentation
+/// Returns 1 on success, 0 if the window is unknown, closed or in GPU mode
+#[no_mangle]
+pub extern "C" fn dop_window_manager_update_framebuffer(
+    manager: *const WindowManager,
+    window_id: u32,
+    data: *const u8,
+    size: c_int,
+    width: c_int,
+    height: c_int,
+) -> c_int {
+    ffi_guard("dop_window_manager_update_framebuffer", || {
+        if manager.is_null() || data.is_null() || size <= 0 || width <= 0 || height <= 0 {
+            return 0;
+        }
+        unsafe {
+            let slice = std::slice::from_raw_parts(data, size as usize);
+            if (*manager).update_framebuffer(window_id, slice, width as u32, height as u32) {
+                1
+            } else {
+                0
+            }
+        }
+    })
+}
+
+/// Hand the renderer's recorded rects and text to a GPU-mode managed window
+/// Returns 1 on success, 0 if the window is unknown, closed or not in GPU mode
+#[cfg(all(feature = "gpu", feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_window_manager_submit_renderer(
+    manager: *const WindowManager,
+    window_id: u32,
+    renderer: DopHandle,
+) -> c_int {
+    ffi_guard("dop_window_manager_submit_renderer", || {
+        let renderer = RENDERERS.get(renderer) as *const RendererHandle;
+        if manager.is_null() || renderer.is_null() {
+            return 0;
+        }
+        unsafe {
+            let frame = (*renderer).renderer.gpu_frame();
+            if (*manager).submit_gpu_frame(window_id, frame) { 1 } else { 0 }
+        }
+    })
+}
+
+/// Enable or disable momentum scroll events on a managed window
+#[no_mangle]
+pub extern "C" fn dop_window_manager_set_kinetic_scrolling(
+    manager: *const WindowManager,
+    window_id: u32,
+    enabled: c_int,
+) {
+    ffi_guard("dop_window_manager_set_kinetic_scrolling", || {
+        if manager.is_null() {
+            return;
+        }
+        unsafe {
+            if let Some(window) = (*manager).window(window_id) {
+                window.set_kinetic_scrolling(enabled != 0);
+            }
+        }
+    })
+}
+
+// ============================================================================
+// Virtual Window (scripted headless input)
+// ============================================================================
+
+/// Create a virtual window driven by injected events and a stepped clock
+#[no_mangle]
+pub extern "C" fn dop_window_create_virtual(width: c_int, height: c_int) -> *mut VirtualWindow {
+    ffi_guard("dop_window_create_virtual", || {
+        let config = WindowConfig {
+            width: width as u32,
+            height: height as u32,
+            ..Default::default()
+        };
+        Box::into_raw(Box::new(VirtualWindow::new(config)))
+    })
+}
+
+/// Free a virtual window
+#[no_mangle]
+pub extern "C" fn dop_window_free_virtual(handle: *mut VirtualWindow) {
+    ffi_guard("dop_window_free_virtual", || {
+        if !handle.is_null() {
+            unsafe {
+                drop(Box::from_raw(handle));
+            }
+        }
+    })
+}
+
+/// Schedule an event at `at` seconds of virtual time
+#[no_mangle]
+pub extern "C" fn dop_window_inject_event(handle: *mut VirtualWindow, event: *const DopEvent, at: f64) {
+    ffi_guard("dop_window_inject_event", || {
+        if handle.is_null() || event.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).inject(*event, at);
+        }
+    })
+}
+
+/// Advance the virtual clock by `dt` seconds and deliver due events
+/// Returns the number of events produced by the step
+#[no_mangle]
+pub extern "C" fn dop_window_step(handle: *mut VirtualWindow, dt: f64) -> c_int {
+    ffi_guard("dop_window_step", || {
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).step(dt) as c_int }
+    })
+}
+
+/// End the current scripted scroll gesture (starts momentum when kinetic scrolling is on)
+#[no_mangle]
+pub extern "C" fn dop_window_release_scroll_virtual(handle: *mut VirtualWindow) {
+    ffi_guard("dop_window_release_scroll_virtual", || {
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).release_scroll();
+        }
+    })
+}
+
+/// Enable or disable momentum scroll events on the virtual window
+#[no_mangle]
+pub extern "C" fn dop_window_set_kinetic_scrolling_virtual(handle: *mut VirtualWindow, enabled: c_int) {
+    ffi_guard("dop_window_set_kinetic_scrolling_virtual", || {
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle)
+                .scroll_processor_mut()
+                .kinetic_flag()
+                .store(enabled != 0, Ordering::Relaxed);
+        }
+    })
+}
+
+/// Poll events produced by previous steps
+#[no_mangle]
+pub extern "C" fn dop_window_poll_events_virtual(
+    handle: *mut VirtualWindow,
+    events: *mut DopEvent,
+    max_events: c_int,
+) -> c_int {
+    ffi_guard("dop_window_poll_events_virtual", || {
+        if handle.is_null() || events.is_null() || max_events <= 0 {
+            return 0;
+        }
+        unsafe {
+            let polled = (*handle).poll_events();
+            let count = polled.len().min(max_events as usize);
+            for (i, event) in polled.into_iter().take(count).enumerate() {
+                *events.add(i) = event;
+            }
+            count as c_int
+        }
+    })
+}
+
+/// Get the virtual clock in seconds
+#[no_mangle]
+pub extern "C" fn dop_window_virtual_time(handle: *const VirtualWindow) -> f64 {
+    ffi_guard("dop_window_virtual_time", || {
+        if handle.is_null() {
+            return 0.0;
+        }
+        unsafe { (*handle).time() }
+    })
+}
+
+/// Check if the virtual window is open (a scripted Close event closes it)
+#[no_mangle]
+pub extern "C" fn dop_window_is_open_virtual(handle: *const VirtualWindow) -> c_int {
+    ffi_guard("dop_window_is_open_virtual", || {
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).is_open() as c_int }
+    })
+}
+
+// ============================================================================
+// Accessibility FFI
+// ============================================================================
+
+/// Flat accessibility node passed from Julia
+///
+/// `role` uses the Content IR role codes; `parent` is 0 for the root node.
+#[cfg(feature = "accessibility")]
+#[repr(C)]
+pub struct DopAccessNode {
+    pub id: u32,
+    pub parent: u32,
+    pub role: u8,
+    pub x: c_float,
+    pub y: c_float,
+    pub width: c_float,
+    pub height: c_float,
+    pub name: *const c_char,
+}
+
+/// Publish an accessibility tree built from flat node descriptions
+/// Returns 1 on success, 0 if the nodes don't form a tree with a root
+#[cfg(feature = "accessibility")]
+#[no_mangle]
+pub extern "C" fn dop_window_publish_accessibility_threaded(
+    handle: DopHandle,
+    nodes: *const DopAccessNode,
+    count: c_int,
+    focus: u32,
+) -> c_int {
+    ffi_guard("dop_window_publish_accessibility_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        if handle.is_null() || nodes.is_null() || count <= 0 {
+            return 0;
+        }
+        unsafe {
+            let descs: Vec<AccessNode> = std::slice::from_raw_parts(nodes, count as usize)
+                .iter()
+                .map(|n| AccessNode {
+                    id: n.id,
+                    parent: n.parent,
+                    role: n.role,
+                    x: n.x,
+                    y: n.y,
+                    width: n.width,
+                    height: n.height,
+                    name: if n.name.is_null() {
+                        String::new()
+                    } else {
+                        CStr::from_ptr(n.name).to_string_lossy().into_owned()
+                    },
+                })
+                .collect();
+            match crate::accessibility::build_tree_update(&descs, focus) {
+                Some(update) => {
+                    (*handle).publish_accessibility(update);
+                    1
+                }
+                None => 0,
+            }
+        }
+    })
+}
+
+/// Publish the accessibility tree exported from a Content IR builder
+/// Returns the number of nodes published, or -1 on failure
+#[cfg(all(feature = "accessibility", feature = "content-ir"))]
+#[no_mangle]
+pub extern "C" fn dop_window_publish_content_accessibility_threaded(
+    handle: DopHandle,
+    builder: *const dop_content_ir::ffi::BuilderHandle,
+    viewport_width: c_float,
+    viewport_height: c_float,
+) -> c_int {
+    ffi_guard("dop_window_publish_content_accessibility_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle);
+        if handle.is_null() || builder.is_null() {
+            return -1;
+        }
+        unsafe {
+            let (nodes, props) = (*builder).builder().tables();
+            if nodes.is_empty() {
+                return -1;
+            }
+            let update = dop_content_ir::accessibility::export_tree(nodes, props, viewport_width, viewport_height);
+            let count = update.nodes.len() as c_int;
+            (*handle).publish_accessibility(update);
+            count
+        }
+    })
+}
+
+/// Check if assistive technology is attached to the threaded window
+#[cfg(feature = "accessibility")]
+#[no_mangle]
+pub extern "C" fn dop_window_accessibility_active_threaded(handle: DopHandle) -> c_int {
+    ffi_guard("dop_window_accessibility_active_threaded", || {
+        let handle = THREADED_WINDOWS.get(handle) as *const ThreadedWindowHandle;
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            (*handle)
+                .accessibility
+                .lock()
+                .map(|bridge| bridge.is_active() as c_int)
+                .unwrap_or(0)
+        }
+    })
+}
+
+// ============================================================================
+// Renderer FFI
+// ============================================================================
+
+/// Renderer handle for FFI - uses software rendering by default
+///
+/// A handle created with `dop_renderer_create_headless_gpu` still records
+/// commands in the software renderer, but rasterizes them with wgpu.
+#[cfg(feature = "software")]
+pub struct RendererHandle {
+    renderer: SoftwareRenderer,
+    /// Path being built by the `dop_renderer_path_*` calls
+    path: Path,
+    fill_rule: FillRule,
+    line_cap: LineCap,
+    line_join: LineJoin,
+    /// Spans collected by `dop_text_add_run` for the next paragraph
+    paragraph: Vec<TextSpan>,
+    /// Strings interned by `dop_renderer_intern_string` (1-indexed, index 0 unused)
+    strings: Vec<String>,
+    string_ids: HashMap<String, u32>,
+    #[cfg(feature = "gpu")]
+    gpu: Option<crate::renderer::WgpuHeadlessRenderer>,
+}
+
+/// Renderer handle for FFI - fallback when software feature is disabled
+#[cfg(not(feature = "software"))]
+#[allow(dead_code)]
+pub struct RendererHandle {
+    commands: Vec<RenderCommand>,
+    text_commands: Vec<TextCommandFFI>,
+    framebuffer: Vec<u8>,
+    width: u32,
+    height: u32,
+    font_manager: FontManager,
+}
+
+/// Live renderers, addressed by the handles returned to the host
+///
+/// A renderer is not `Sync` (its font manager caches glyphs in `RefCell`s),
+/// so each one is bound to the thread that created it; see `dop_renderer_adopt`.
+static RENDERERS: HandleRegistry<RendererHandle> = HandleRegistry::thread_bound("renderer");
+
+/// Text command for FFI (used when software feature is disabled)
+#[cfg(not(feature = "software"))]
+#[derive(Debug, Clone)]
+struct TextCommandFFI {
+    text: String,
+    x: f32,
+    y: f32,
+    font_size: f32,
+    color_r: f32,
+    color_g: f32,
+    color_b: f32,
+    color_a: f32,
+    font_id: u32,
+}
+
+/// Create a headless renderer using software rendering (tiny-skia)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_create_headless(width: c_int, height: c_int) -> DopHandle {
+    ffi_guard("dop_renderer_create_headless", || {
+        let renderer = SoftwareRenderer::new(width as u32, height as u32);
+        RENDERERS.insert(Box::new(RendererHandle {
+            renderer,
+            path: Path::new(),
+            fill_rule: FillRule::default(),
+            line_cap: LineCap::default(),
+            line_join: LineJoin::default(),
+            paragraph: Vec::new(),
+            strings: vec![String::new()],
+            string_ids: HashMap::new(),
+            #[cfg(feature = "gpu")]
+            gpu: None,
+        }))
+    })
+}
+
+/// Create a headless renderer that rasterizes on the GPU into an offscreen texture
+///
+/// Accepts the same `dop_renderer_*` calls as a software headless renderer;
+/// `dop_renderer_render` reads the GPU frame back so the framebuffer and
+/// PNG export work unchanged. Returns null if no GPU adapter is available.
+#[cfg(all(feature = "gpu", feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_create_headless_gpu(width: c_int, height: c_int) -> DopHandle {
+    ffi_guard("dop_renderer_create_headless_gpu", || {
+        let (width, height) = (width.max(1) as u32, height.max(1) as u32);
+        match crate::renderer::WgpuHeadlessRenderer::new(width, height) {
+            Ok(gpu) => RENDERERS.insert(Box::new(RendererHandle {
+                renderer: SoftwareRenderer::new(width, height),
+                path: Path::new(),
+                fill_rule: FillRule::default(),
+                line_cap: LineCap::default(),
+                line_join: LineJoin::default(),
+                paragraph: Vec::new(),
+                strings: vec![String::new()],
+                string_ids: HashMap::new(),
+                gpu: Some(gpu),
+            })),
+            Err(e) => {
+                log::warn!("Failed to create headless GPU renderer: {}", e);
+                0
+            }
+        }
+    })
+}
+
+/// Check if a renderer rasterizes on the GPU (1) or in software (0)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_is_gpu(handle: DopHandle) -> c_int {
+    ffi_guard("dop_renderer_is_gpu", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() {
+            return 0;
+        }
+        #[cfg(feature = "gpu")]
+        unsafe {
+            (*handle).gpu.is_some() as c_int
+        }
+        #[cfg(not(feature = "gpu"))]
+        0
+    })
+}
+
+/// Create a headless renderer (fallback implementation)
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_create_headless(width: c_int, height: c_int) -> DopHandle {
+    ffi_guard("dop_renderer_create_headless", || {
+        let w = width as u32;
+        let h = height as u32;
+        let framebuffer = vec![255u8; (w * h * 4) as usize]; // White background
+
+        RENDERERS.insert(Box::new(RendererHandle {
+            commands: Vec::new(),
+            text_commands: Vec::new(),
+            framebuffer,
+            width: w,
+            height: h,
+            font_manager: FontManager::new(),
+        }))
+    })
+}
+
+/// Create a headless renderer that shapes text with the given backend
+///
+/// `shaping` is 0 for fontdue's layout and 1 for HarfBuzz (rustybuzz), which
+/// falls back to fontdue in builds without the `harfbuzz` feature.
+#[no_mangle]
+pub extern "C" fn dop_renderer_create_headless_with_shaping(
+    width: c_int,
+    height: c_int,
+    shaping: c_int,
+) -> DopHandle {
+    ffi_guard("dop_renderer_create_headless_with_shaping", || {
+        let id = dop_renderer_create_headless(width, height);
+        let handle = RENDERERS.get(id);
+        if handle.is_null() {
+            return 0;
+        }
+        let backend = ShapingBackend::from_u8(shaping as u8);
+        unsafe {
+            #[cfg(feature = "software")]
+            (*handle).renderer.font_manager_mut().set_shaping_backend(backend);
+            #[cfg(not(feature = "software"))]
+            (*handle).font_manager.set_shaping_backend(backend);
+        }
+        id
+    })
+}
+
+/// Free a renderer
+///
+/// Unlike other calls this may come from any thread, so hosts can free
+/// renderers from finalizers.
+#[no_mangle]
+pub extern "C" fn dop_renderer_free(handle: DopHandle) {
+    ffi_guard("dop_renderer_free", || {
+        RENDERERS.remove(handle);
+    })
+}
+
+/// Move a renderer to the calling thread
+///
+/// Renderers only accept calls from the thread that owns them; calls from
+/// other threads fail and set the last error. The host must stop using the
+/// renderer on its previous thread before adopting it.
+/// Returns 1 on success, 0 if the handle is invalid.
+#[no_mangle]
+pub extern "C" fn dop_renderer_adopt(handle: DopHandle) -> c_int {
+    ffi_guard("dop_renderer_adopt", || RENDERERS.adopt(handle) as c_int)
+}
+
+/// Clear the renderer
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_clear(handle: DopHandle) {
+    ffi_guard("dop_renderer_clear", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.clear();
+        }
+    })
+}
+
+/// Clear the renderer (fallback)
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_clear(handle: DopHandle) {
+    ffi_guard("dop_renderer_clear", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).commands.clear();
+            (*handle).text_commands.clear();
+        }
+    })
+}
+
+/// Set clear color
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_set_clear_color(
+    handle: DopHandle,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+) {
+    ffi_guard("dop_renderer_set_clear_color", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.set_clear_color(r, g, b, a);
+        }
+    })
+}
+
+/// Set clear color (fallback)
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_set_clear_color(
+    handle: DopHandle,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+) {
+    ffi_guard("dop_renderer_set_clear_color", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        let handle = unsafe { &mut *handle };
+
+        // Fill framebuffer with clear color
+        let w = handle.width;
+        let h = handle.height;
+        let rb = (r * 255.0) as u8;
+        let gb = (g * 255.0) as u8;
+        let bb = (b * 255.0) as u8;
+        let ab = (a * 255.0) as u8;
+
+        for i in 0..(w * h) as usize {
+            let idx = i * 4;
+            handle.framebuffer[idx] = rb;
+            handle.framebuffer[idx + 1] = gb;
+            handle.framebuffer[idx + 2] = bb;
+            handle.framebuffer[idx + 3] = ab;
+        }
+    })
+}
+
+/// Add a rectangle render command
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_add_rect(
+    handle: DopHandle,
+    x: c_float,
+    y: c_float,
+    width: c_float,
+    height: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+    z_index: c_int,
+) {
+    ffi_guard("dop_renderer_add_rect", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.add_rect(RenderCommand {
+                x,
+                y,
+                width,
+                height,
+                color_r: r,
+                color_g: g,
+                color_b: b,
+                color_a: a,
+                texture_id: 0,
+                z_index,
+                corner_radii: [0.0; 4],
+                stacking_context: 0,
+            });
+        }
+    })
+}
+
+/// Add a rectangle render command (fallback)
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_add_rect(
+    handle: DopHandle,
+    x: c_float,
+    y: c_float,
+    width: c_float,
+    height: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+    z_index: c_int,
+) {
+    ffi_guard("dop_renderer_add_rect", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).commands.push(RenderCommand {
+                x,
+                y,
+                width,
+                height,
+                color_r: r,
+                color_g: g,
+                color_b: b,
+                color_a: a,
+                texture_id: 0,
+                z_index,
+                corner_radii: [0.0; 4],
+                stacking_context: 0,
+            });
+        }
+    })
+}
+
+/// Add a rectangle with rounded corners
+/// Radii are in pixels, clockwise from the top-left corner
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_add_rounded_rect(
+    handle: DopHandle,
+    x: c_float,
+    y: c_float,
+    width: c_float,
+    height: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+    radius_tl: c_float,
+    radius_tr: c_float,
+    radius_br: c_float,
+    radius_bl: c_float,
+    z_index: c_int,
+) {
+    ffi_guard("dop_renderer_add_rounded_rect", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.add_rect(RenderCommand {
+                x,
+                y,
+                width,
+                height,
+                color_r: r,
+                color_g: g,
+                color_b: b,
+                color_a: a,
+                texture_id: 0,
+                z_index,
+                corner_radii: [radius_tl, radius_tr, radius_br, radius_bl],
+                stacking_context: 0,
+            });
+        }
+    })
+}
+
+/// Add a rectangle with rounded corners (fallback, corners are drawn square)
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_add_rounded_rect(
+    handle: DopHandle,
+    x: c_float,
+    y: c_float,
+    width: c_float,
+    height: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+    radius_tl: c_float,
+    radius_tr: c_float,
+    radius_br: c_float,
+    radius_bl: c_float,
+    z_index: c_int,
+) {
+    ffi_guard("dop_renderer_add_rounded_rect", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).commands.push(RenderCommand {
+                x,
+                y,
+                width,
+                height,
+                color_r: r,
+                color_g: g,
+                color_b: b,
+                color_a: a,
+                texture_id: 0,
+                z_index,
+                corner_radii: [radius_tl, radius_tr, radius_br, radius_bl],
+                stacking_context: 0,
+            });
+        }
+    })
+}
+
+/// Render the frame using software rendering (tiny-skia)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_render(handle: DopHandle) {
+    ffi_guard("dop_renderer_render", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            let handle = &mut *handle;
+            #[cfg(feature = "gpu")]
+            if let Some(gpu) = &mut handle.gpu {
+                gpu.submit_frame(handle.renderer.gpu_frame());
+                match gpu.render() {
+                    Ok(()) => {
+                        handle.renderer.write_framebuffer(gpu.get_framebuffer());
+                    }
+                    Err(e) => log::warn!("GPU headless render failed: {:?}", e),
+                }
+                return;
+            }
+            handle.renderer.render();
+        }
+    })
+}
+
+/// Render the frame (fallback software rasterization)
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_render(handle: DopHandle) {
+    ffi_guard("dop_renderer_render", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        let handle = unsafe { &mut *handle };
+
+        let w = handle.width;
+        let h = handle.height;
+
+        // Sort commands by z-index
+        handle.commands.sort_by_key(|c| c.z_index);
+
+        // Clone commands to iterate over them
+        let commands: Vec<RenderCommand> = handle.commands.clone();
+
+        // Software rasterize each rectangle command
+        for cmd in &commands {
+            // Calculate rectangle bounds
+            let x0 = (cmd.x.max(0.0) as u32).min(w);
+            let y0 = (cmd.y.max(0.0) as u32).min(h);
+            let x1 = ((cmd.x + cmd.width).ceil() as u32).min(w);
+            let y1 = ((cmd.y + cmd.height).ceil() as u32).min(h);
+
+            if x0 >= x1 {
+                continue;
+            }
+            let color = [
+                (cmd.color_r * 255.0) as u8,
+                (cmd.color_g * 255.0) as u8,
+                (cmd.color_b * 255.0) as u8,
+                (cmd.color_a * 255.0) as u8,
+            ];
+            let coverage = vec![255u8; (x1 - x0) as usize];
+
+            // Fill the rectangle
+            for y in y0..y1 {
+                let row = (y * w) as usize;
+                let dst = &mut handle.framebuffer[(row + x0 as usize) * 4..(row + x1 as usize) * 4];
+                blend::blend_color_row(dst, color, &coverage);
+            }
+        }
+
+        // Render text commands
+        let text_commands: Vec<TextCommandFFI> = handle.text_commands.clone();
+        for text_cmd in &text_commands {
+            let color = (
+                (text_cmd.color_r * 255.0) as u8,
+                (text_cmd.color_g * 255.0) as u8,
+                (text_cmd.color_b * 255.0) as u8,
+                (text_cmd.color_a * 255.0) as u8,
+            );
+
+            let (text_buffer, text_w, text_h) = handle.font_manager.rasterize_text(
+                &text_cmd.text,
+                text_cmd.font_size,
+                text_cmd.font_id,
+                color,
+                TextSpacing::default(),
+            );
+
+            if text_buffer.is_empty() || text_w == 0 || text_h == 0 {
+                continue;
+            }
+
+            // Blit text to framebuffer
+            let tx = text_cmd.x as i32;
+            let ty = text_cmd.y as i32;
+
+            let (cx0, cx1) = (tx.max(0), (tx + text_w as i32).min(w as i32));
+            if cx0 >= cx1 {
+                continue;
+            }
+            for py in ty.max(0)..(ty + text_h as i32).min(h as i32) {
+                let src_row = ((py - ty) as u32 * text_w) as usize;
+                let src = &text_buffer[(src_row + (cx0 - tx) as usize) * 4..(src_row + (cx1 - tx) as usize) * 4];
+                let dst_row = (py as u32 * w) as usize;
+                let dst = &mut handle.framebuffer[(dst_row + cx0 as usize) * 4..(dst_row + cx1 as usize) * 4];
+                blend::blend_row(dst, src, None);
+            }
+        }
+    })
+}
+
+/// Get the number of rectangle commands culled as occluded in the last render
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_get_culled_count(handle: DopHandle) -> c_int {
+    ffi_guard("dop_renderer_get_culled_count", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).renderer.culled_count() as c_int }
+    })
+}
+
+/// Limit the next render to a damaged area (call once per rectangle)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_add_damage_rect(
+    handle: DopHandle,
+    x: c_float,
+    y: c_float,
+    width: c_float,
+    height: c_float,
+) {
+    ffi_guard("dop_renderer_add_damage_rect", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.add_damage_rect(x, y, width, height);
+        }
+    })
+}
+
+/// Enable (1) or disable (0) deriving damage by diffing each frame against the previous one
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_set_auto_damage(handle: DopHandle, enabled: c_int) {
+    ffi_guard("dop_renderer_set_auto_damage", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.set_auto_damage(enabled != 0);
+        }
+    })
+}
+
+/// Get the area repainted by the last render in whole pixels
+/// Returns 1 and fills the outputs if anything was repainted, 0 otherwise
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_get_dirty_rect(
+    handle: DopHandle,
+    out_x: *mut c_int,
+    out_y: *mut c_int,
+    out_width: *mut c_int,
+    out_height: *mut c_int,
+) -> c_int {
+    ffi_guard("dop_renderer_get_dirty_rect", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() || out_x.is_null() || out_y.is_null() || out_width.is_null() || out_height.is_null() {
+            return 0;
+        }
+        unsafe {
+            match (*handle).renderer.dirty_rect() {
+                Some((x, y, w, h)) => {
+                    *out_x = x as c_int;
+                    *out_y = y as c_int;
+                    *out_width = w as c_int;
+                    *out_height = h as c_int;
+                    1
+                }
+                None => 0,
+            }
+        }
+    })
+}
+
+/// Force the next render to repaint the whole framebuffer
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_invalidate(handle: DopHandle) {
+    ffi_guard("dop_renderer_invalidate", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.invalidate_all();
+        }
+    })
+}
+
+/// Get the counters from the command optimization pass of the last render
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_get_optimize_stats(handle: DopHandle, out: *mut OptimizeStats) -> c_int {
+    ffi_guard("dop_renderer_get_optimize_stats", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() || out.is_null() {
+            return 0;
+        }
+        unsafe {
+            *out = (*handle).renderer.optimize_stats();
+        }
+        1
+    })
+}
+
+/// Copy the rasterization counters from the last render into `out`
+/// Returns 1 on success, 0 on failure
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_get_raster_stats(handle: DopHandle, out: *mut RasterStats) -> c_int {
+    ffi_guard("dop_renderer_get_raster_stats", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() || out.is_null() {
+            return 0;
+        }
+        unsafe {
+            *out = (*handle).renderer.raster_stats();
+        }
+        1
+    })
+}
+
+/// Get the number of culled commands (fallback - culling is not performed)
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_get_culled_count(_handle: DopHandle) -> c_int {
+    ffi_guard("dop_renderer_get_culled_count", || {
+        0
+    })
+}
+
+/// Get framebuffer pointer
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_get_framebuffer(handle: DopHandle) -> *const u8 {
+    ffi_guard("dop_renderer_get_framebuffer", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() {
+            return ptr::null();
+        }
+        unsafe { (*handle).renderer.get_framebuffer().as_ptr() }
+    })
+}
+
+/// Get framebuffer pointer (fallback)
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_get_framebuffer(handle: DopHandle) -> *const u8 {
+    ffi_guard("dop_renderer_get_framebuffer", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() {
+            return ptr::null();
+        }
+        unsafe { (*handle).framebuffer.as_ptr() }
+    })
+}
+
+/// Get framebuffer size
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_get_framebuffer_size(handle: DopHandle) -> c_int {
+    ffi_guard("dop_renderer_get_framebuffer_size", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).renderer.get_framebuffer_size() as c_int }
+    })
+}
+
+/// Get framebuffer size (fallback)
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_get_framebuffer_size(handle: DopHandle) -> c_int {
+    ffi_guard("dop_renderer_get_framebuffer_size", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).framebuffer.len() as c_int }
+    })
+}
+
+/// Resize the renderer
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_resize(handle: DopHandle, width: c_int, height: c_int) {
+    ffi_guard("dop_renderer_resize", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.resize(width as u32, height as u32);
+            #[cfg(feature = "gpu")]
+            if let Some(gpu) = &mut (*handle).gpu {
+                gpu.resize(width as u32, height as u32);
+            }
+        }
+    })
+}
+
+/// Resize the renderer (fallback)
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_resize(handle: DopHandle, width: c_int, height: c_int) {
+    ffi_guard("dop_renderer_resize", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            let w = width as u32;
+            let h = height as u32;
+            (*handle).width = w;
+            (*handle).height = h;
+            (*handle).framebuffer = vec![255u8; (w * h * 4) as usize];
+        }
+    })
+}
+
+// ============================================================================
+// Event creation helpers
+// ============================================================================
+
+/// Create a close event
+#[no_mangle]
+pub extern "C" fn dop_event_close() -> DopEvent {
+    ffi_guard("dop_event_close", || {
+        DopEvent::close()
+    })
+}
+
+/// Create a resize event
+#[no_mangle]
+pub extern "C" fn dop_event_resize(width: c_int, height: c_int) -> DopEvent {
+    ffi_guard("dop_event_resize", || {
+        DopEvent::resize(width as u32, height as u32)
+    })
+}
+
+/// Create a key down event
+#[no_mangle]
+pub extern "C" fn dop_event_key_down(key: c_int, modifiers: u8) -> DopEvent {
+    ffi_guard("dop_event_key_down", || {
+        DopEvent::key_down(key, modifiers)
+    })
+}
+
+/// Create a key up event
+#[no_mangle]
+pub extern "C" fn dop_event_key_up(key: c_int, modifiers: u8) -> DopEvent {
+    ffi_guard("dop_event_key_up", || {
+        DopEvent::key_up(key, modifiers)
+    })
+}
+
+/// Create a mouse down event
+#[no_mangle]
+pub extern "C" fn dop_event_mouse_down(button: u8, x: c_float, y: c_float) -> DopEvent {
+    ffi_guard("dop_event_mouse_down", || {
+        let btn = match button {
+            0 => MouseButtonId::Left,
+            1 => MouseButtonId::Right,
+            2 => MouseButtonId::Middle,
+            3 => MouseButtonId::X1,
+            4 => MouseButtonId::X2,
+            _ => MouseButtonId::Left,
+        };
+        DopEvent::mouse_down(btn, x as f64, y as f64)
+    })
+}
+
+/// Create a mouse up event
+#[no_mangle]
+pub extern "C" fn dop_event_mouse_up(button: u8, x: c_float, y: c_float) -> DopEvent {
+    ffi_guard("dop_event_mouse_up", || {
+        let btn = match button {
+            0 => MouseButtonId::Left,
+            1 => MouseButtonId::Right,
+            2 => MouseButtonId::Middle,
+            3 => MouseButtonId::X1,
+            4 => MouseButtonId::X2,
+            _ => MouseButtonId::Left,
+        };
+        DopEvent::mouse_up(btn, x as f64, y as f64)
+    })
+}
+
+/// Create a mouse move event
+#[no_mangle]
+pub extern "C" fn dop_event_mouse_move(x: c_float, y: c_float) -> DopEvent {
+    ffi_guard("dop_event_mouse_move", || {
+        DopEvent::mouse_move(x as f64, y as f64)
+    })
+}
+
+/// Create a mouse scroll event
+#[no_mangle]
+pub extern "C" fn dop_event_mouse_scroll(
+    x: c_float,
+    y: c_float,
+    scroll_x: c_float,
+    scroll_y: c_float,
+) -> DopEvent {
+    ffi_guard("dop_event_mouse_scroll", || {
+        DopEvent::mouse_scroll(x as f64, y as f64, scroll_x as f64, scroll_y as f64)
+    })
+}
+
+/// Create a raw (unaccelerated) mouse motion event
+#[no_mangle]
+pub extern "C" fn dop_event_raw_mouse_motion(dx: c_float, dy: c_float) -> DopEvent {
+    ffi_guard("dop_event_raw_mouse_motion", || {
+        DopEvent::raw_mouse_motion(dx as f64, dy as f64)
+    })
+}
+
+/// Create a pen event
+#[no_mangle]
+pub extern "C" fn dop_event_pen(
+    x: c_float,
+    y: c_float,
+    pressure: c_float,
+    tilt_x: c_float,
+    tilt_y: c_float,
+    pen_flags: u32,
+) -> DopEvent {
+    ffi_guard("dop_event_pen", || {
+        DopEvent::pen(x as f64, y as f64, pressure, tilt_x, tilt_y, pen_flags)
+    })
+}
+
+// ============================================================================
+// Utility functions
+// ============================================================================
+
+/// Get the size of DopEvent struct for Julia
+#[no_mangle]
+pub extern "C" fn dop_event_size() -> c_int {
+    ffi_guard("dop_event_size", || {
+        std::mem::size_of::<DopEvent>() as c_int
+    })
+}
+
+/// Get the size of RenderCommand struct for Julia
+#[no_mangle]
+pub extern "C" fn dop_render_command_size() -> c_int {
+    ffi_guard("dop_render_command_size", || {
+        std::mem::size_of::<RenderCommand>() as c_int
+    })
+}
+
+/// Get library version
+#[no_mangle]
+pub extern "C" fn dop_version() -> *const c_char {
+    ffi_guard("dop_version", || {
+        static VERSION: &[u8] = concat!(env!("CARGO_PKG_VERSION"), "\0").as_bytes();
+        VERSION.as_ptr() as *const c_char
+    })
+}
+
+// ============================================================================
+// Color management FFI
+// ============================================================================
+
+/// Set the output color space (0 = sRGB, 1 = Display-P3), clearing any display profile
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_set_output_color_space(handle: DopHandle, space: u8) {
+    ffi_guard("dop_renderer_set_output_color_space", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.color_manager_mut().set_output_space(ColorSpace::from_u8(space));
+        }
+    })
+}
+
+/// Set the display ICC profile used as the output space
+/// Returns 1 on success, 0 if the profile is invalid or ICC support is not built in
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_set_display_profile(handle: DopHandle, data: *const u8, len: usize) -> c_int {
+    ffi_guard("dop_renderer_set_display_profile", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || data.is_null() {
+            return 0;
+        }
+        #[cfg(feature = "icc")]
+        unsafe {
+            let icc = std::slice::from_raw_parts(data, len);
+            (*handle).renderer.color_manager_mut().set_display_profile(icc) as c_int
+        }
+        #[cfg(not(feature = "icc"))]
+        {
+            let _ = len;
+            log::warn!("dop_renderer_set_display_profile: built without the icc feature");
+            0
+        }
+    })
+}
+
+/// Enable or disable linear-light (gamma-correct) blending
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_set_linear_blending(handle: DopHandle, enabled: c_int) {
+    ffi_guard("dop_renderer_set_linear_blending", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.set_linear_blending(enabled != 0);
+        }
+    })
+}
+
+/// Set text antialiasing (0 = grayscale, 1 = subpixel RGB, 2 = subpixel BGR)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_set_text_aa_mode(handle: DopHandle, mode: c_int) {
+    ffi_guard("dop_renderer_set_text_aa_mode", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.set_text_antialias(TextAntialias::from_u8(mode as u8));
+        }
+    })
+}
+
+/// Add a rectangle whose color is given in a specific color space (0 = sRGB, 1 = Display-P3)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_add_rect_in_space(
+    handle: DopHandle,
+    x: c_float,
+    y: c_float,
+    width: c_float,
+    height: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+    z_index: c_int,
+    space: u8,
+) {
+    ffi_guard("dop_renderer_add_rect_in_space", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.add_rect_in_space(
+                RenderCommand {
+                    x,
+                    y,
+                    width,
+                    height,
+                    color_r: r,
+                    color_g: g,
+                    color_b: b,
+                    color_a: a,
+                    texture_id: 0,
+                    z_index,
+                    corner_radii: [0.0; 4],
+                    stacking_context: 0,
+                },
+                ColorSpace::from_u8(space),
+            );
+        }
+    })
+}
+
+// ============================================================================
+// Retained command FFI
+// ============================================================================
+
+/// Retain a rectangle across frames (device pixels, sRGB color); `dop_renderer_clear` keeps it
+/// Returns the command ID
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_command_create_rect(
+    handle: DopHandle,
+    x: c_float,
+    y: c_float,
+    width: c_float,
+    height: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+    z_index: c_int,
+) -> u32 {
+    ffi_guard("dop_renderer_command_create_rect", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            (*handle).renderer.create_retained_rect(RenderCommand {
+                x,
+                y,
+                width,
+                height,
+                color_r: r,
+                color_g: g,
+                color_b: b,
+                color_a: a,
+                texture_id: 0,
+                z_index,
+                corner_radii: [0.0; 4],
+                stacking_context: 0,
+            })
+        }
+    })
+}
+
+/// Move a retained rectangle (returns 1 if it exists, 0 otherwise)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_command_set_position(
+    handle: DopHandle,
+    cmd_id: u32,
+    x: c_float,
+    y: c_float,
+) -> c_int {
+    ffi_guard("dop_renderer_command_set_position", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            match (*handle).renderer.retained_mut().get_mut(cmd_id) {
+                Some(cmd) => {
+                    cmd.x = x;
+                    cmd.y = y;
+                    1
+                }
+                None => 0,
+            }
+        }
+    })
+}
+
+/// Resize a retained rectangle (returns 1 if it exists, 0 otherwise)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_command_set_size(
+    handle: DopHandle,
+    cmd_id: u32,
+    width: c_float,
+    height: c_float,
+) -> c_int {
+    ffi_guard("dop_renderer_command_set_size", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            match (*handle).renderer.retained_mut().get_mut(cmd_id) {
+                Some(cmd) => {
+                    cmd.width = width;
+                    cmd.height = height;
+                    1
+                }
+                None => 0,
+            }
+        }
+    })
+}
+
+/// Recolor a retained rectangle (sRGB; returns 1 if it exists, 0 otherwise)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_command_set_color(
+    handle: DopHandle,
+    cmd_id: u32,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+) -> c_int {
+    ffi_guard("dop_renderer_command_set_color", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            match (*handle).renderer.retained_mut().get_mut(cmd_id) {
+                Some(cmd) => {
+                    cmd.color_r = r;
+                    cmd.color_g = g;
+                    cmd.color_b = b;
+                    cmd.color_a = a;
+                    1
+                }
+                None => 0,
+            }
+        }
+    })
+}
+
+/// Change a retained rectangle's z-index (returns 1 if it exists, 0 otherwise)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_command_set_z_index(handle: DopHandle, cmd_id: u32, z_index: c_int) -> c_int {
+    ffi_guard("dop_renderer_command_set_z_index", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            match (*handle).renderer.retained_mut().get_mut(cmd_id) {
+                Some(cmd) => {
+                    cmd.z_index = z_index;
+                    1
+                }
+                None => 0,
+            }
+        }
+    })
+}
+
+/// Remove a retained rectangle (returns 1 if it existed, 0 otherwise)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_command_remove(handle: DopHandle, cmd_id: u32) -> c_int {
+    ffi_guard("dop_renderer_command_remove", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).renderer.remove_retained(cmd_id) as c_int }
+    })
+}
+
+// ============================================================================
+// Compositor layer FFI
+// ============================================================================
+
+/// Create a retained compositor layer (returns layer ID, 0 on failure)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_create_layer(handle: DopHandle, width: c_int, height: c_int) -> u32 {
+    ffi_guard("dop_renderer_create_layer", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || width <= 0 || height <= 0 {
+            return 0;
+        }
+        unsafe { (*handle).renderer.create_layer(width as u32, height as u32) }
+    })
+}
+
+/// Remove a compositor layer
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_remove_layer(handle: DopHandle, layer_id: u32) {
+    ffi_guard("dop_renderer_remove_layer", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.layers_mut().remove(layer_id);
+        }
+    })
+}
+
+/// Clear a layer's content (it is re-rasterized on the next render)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_layer_clear(handle: DopHandle, layer_id: u32) {
+    ffi_guard("dop_renderer_layer_clear", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            if let Some(layer) = (*handle).renderer.layers_mut().get_mut(layer_id) {
+                layer.clear();
+            }
+        }
+    })
+}
+
+/// Add a rectangle to a layer (coordinates are in layer space)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_layer_add_rect(
+    handle: DopHandle,
+    layer_id: u32,
+    x: c_float,
+    y: c_float,
+    width: c_float,
+    height: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+    z_index: c_int,
+) {
+    ffi_guard("dop_renderer_layer_add_rect", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            if let Some(layer) = (*handle).renderer.layers_mut().get_mut(layer_id) {
+                layer.add_rect(RenderCommand {
+                    x,
+                    y,
+                    width,
+                    height,
+                    color_r: r,
+                    color_g: g,
+                    color_b: b,
+                    color_a: a,
+                    texture_id: 0,
+                    z_index,
+                    corner_radii: [0.0; 4],
+                    stacking_context: 0,
+                });
+            }
+        }
+    })
+}
+
+/// Add text to a layer (coordinates are in layer space)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_layer_add_text(
+    handle: DopHandle,
+    layer_id: u32,
+    text: *const c_char,
+    x: c_float,
+    y: c_float,
+    font_size: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+    font_id: c_int,
+) {
+    ffi_guard("dop_renderer_layer_add_text", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || text.is_null() {
+            return;
+        }
+
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s.to_string(),
+                Err(_) => return,
+            }
+        };
+
+        unsafe {
+            if let Some(layer) = (*handle).renderer.layers_mut().get_mut(layer_id) {
+                layer.add_text(TextCommand {
+                    text: text_str,
+                    x,
+                    y,
+                    font_size,
+                    color_r: r,
+                    color_g: g,
+                    color_b: b,
+                    color_a: a,
+                    font_id: font_id as u32,
+                    decoration: TextDecoration::NONE,
+                    decoration_color: None,
+                    anchor: TextAnchor::Top,
+                    spacing: TextSpacing::default(),
+                });
+            }
+        }
+    })
+}
+
+/// Move a layer without re-rasterizing its content
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_set_layer_position(handle: DopHandle, layer_id: u32, x: c_float, y: c_float) {
+    ffi_guard("dop_renderer_set_layer_position", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            if let Some(layer) = (*handle).renderer.layers_mut().get_mut(layer_id) {
+                layer.x = x;
+                layer.y = y;
+            }
+        }
+    })
+}
+
+/// Set a layer's compositing order
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_set_layer_z_index(handle: DopHandle, layer_id: u32, z_index: c_int) {
+    ffi_guard("dop_renderer_set_layer_z_index", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            if let Some(layer) = (*handle).renderer.layers_mut().get_mut(layer_id) {
+                layer.z_index = z_index;
+            }
+        }
+    })
+}
+
+// ============================================================================
+// Image FFI
+// ============================================================================
+
+/// Decode an encoded image (PNG/JPEG/GIF/WebP) into the renderer's image cache
+/// Returns the image handle, or 0 on failure
+#[cfg(all(feature = "software", feature = "images"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_load_image(
+    handle: DopHandle,
+    key: *const c_char,
+    data: *const u8,
+    len: usize,
+) -> u32 {
+    ffi_guard("dop_renderer_load_image", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || key.is_null() || data.is_null() {
+            return 0;
+        }
+        let key = unsafe {
+            match CStr::from_ptr(key).to_str() {
+                Ok(s) => s,
+                Err(_) => return 0,
+            }
+        };
+        let bytes = unsafe { std::slice::from_raw_parts(data, len) };
+        match unsafe { (*handle).renderer.load_image(key, bytes) } {
+            Ok(id) => id,
+            Err(e) => {
+                log::warn!("Failed to decode image '{}': {}", key, e);
+                0
+            }
+        }
+    })
+}
+
+/// Cache a straight-alpha sRGB RGBA8 buffer (width * height * 4 bytes) as an image
+/// Returns the image handle for `dop_renderer_add_image`, or 0 on failure
+#[cfg(all(feature = "software", feature = "images"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_load_image_rgba(
+    handle: DopHandle,
+    data: *const u8,
+    width: c_int,
+    height: c_int,
+) -> u32 {
+    ffi_guard("dop_renderer_load_image_rgba", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || data.is_null() || width <= 0 || height <= 0 {
+            return 0;
+        }
+        unsafe {
+            let len = width as usize * height as usize * 4;
+            let pixels = std::slice::from_raw_parts(data, len);
+            (*handle)
+                .renderer
+                .load_image_rgba(pixels, width as u32, height as u32)
+                .unwrap_or(0)
+        }
+    })
+}
+
+/// Decode a PNG/JPEG/GIF/WebP image without a cache key (GIFs keep their first frame)
+/// Each call returns a new image handle for `dop_renderer_add_image`, or 0 on failure
+#[cfg(all(feature = "software", feature = "images"))]
+#[no_mangle]
+pub extern "C" fn dop_image_decode(handle: DopHandle, data: *const u8, len: usize) -> u32 {
+    ffi_guard("dop_image_decode", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || data.is_null() || len == 0 {
+            return 0;
+        }
+        let bytes = unsafe { std::slice::from_raw_parts(data, len) };
+        match unsafe { (*handle).renderer.decode_image(bytes) } {
+            Ok(id) => id,
+            Err(e) => {
+                log::warn!("Failed to decode image: {}", e);
+                0
+            }
+        }
+    })
+}
+
+/// Get the width of a cached image in pixels (0 if not cached)
+#[cfg(all(feature = "software", feature = "images"))]
+#[no_mangle]
+pub extern "C" fn dop_image_width(handle: DopHandle, image_id: u32) -> c_int {
+    ffi_guard("dop_image_width", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).renderer.images().peek(image_id).map_or(0, |image| image.width as c_int) }
+    })
+}
+
+/// Get the height of a cached image in pixels (0 if not cached)
+#[cfg(all(feature = "software", feature = "images"))]
+#[no_mangle]
+pub extern "C" fn dop_image_height(handle: DopHandle, image_id: u32) -> c_int {
+    ffi_guard("dop_image_height", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).renderer.images().peek(image_id).map_or(0, |image| image.height as c_int) }
+    })
+}
+
+/// Get the size of a cached image (returns 1 if found, 0 otherwise)
+#[cfg(all(feature = "software", feature = "images"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_image_size(
+    handle: DopHandle,
+    image_id: u32,
+    out_width: *mut c_int,
+    out_height: *mut c_int,
+) -> c_int {
+    ffi_guard("dop_renderer_image_size", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() || out_width.is_null() || out_height.is_null() {
+            return 0;
+        }
+        unsafe {
+            match (*handle).renderer.images().peek(image_id) {
+                Some(image) => {
+                    *out_width = image.width as c_int;
+                    *out_height = image.height as c_int;
+                    1
+                }
+                None => 0,
+            }
+        }
+    })
+}
+
+/// Get the premultiplied RGBA pixels of a cached image (null if not cached)
+/// The pointer is valid until the image is evicted or removed
+#[cfg(all(feature = "software", feature = "images"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_image_pixels(handle: DopHandle, image_id: u32) -> *const u8 {
+    ffi_guard("dop_renderer_image_pixels", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() {
+            return ptr::null();
+        }
+        unsafe {
+            match (*handle).renderer.images().peek(image_id) {
+                Some(image) => image.pixels.as_ptr(),
+                None => ptr::null(),
+            }
+        }
+    })
+}
+
+/// Get the number of frames in a cached image (1 for still images, 0 if not cached)
+#[cfg(all(feature = "software", feature = "images"))]
+#[no_mangle]
+pub extern "C" fn dop_image_frame_count(handle: DopHandle, image_id: u32) -> c_int {
+    ffi_guard("dop_image_frame_count", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).renderer.images().frame_count(image_id) as c_int }
+    })
+}
+
+/// Get the premultiplied RGBA pixels of one frame (null if out of range)
+/// Writes the frame delay in milliseconds to `out_delay_ms` when non-null.
+/// Frames of animated images are decoded on first access.
+#[cfg(all(feature = "software", feature = "images"))]
+#[no_mangle]
+pub extern "C" fn dop_image_get_frame(
+    handle: DopHandle,
+    image_id: u32,
+    index: c_int,
+    out_delay_ms: *mut c_int,
+) -> *const u8 {
+    ffi_guard("dop_image_get_frame", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || index < 0 {
+            return ptr::null();
+        }
+        unsafe {
+            match (*handle).renderer.image_frame(image_id, index as usize) {
+                Some((image, delay_ms)) => {
+                    if !out_delay_ms.is_null() {
+                        *out_delay_ms = delay_ms as c_int;
+                    }
+                    image.pixels.as_ptr()
+                }
+                None => ptr::null(),
+            }
+        }
+    })
+}
+
+/// Get the delay of one frame in milliseconds, decoding it if needed (-1 if out of range)
+#[cfg(all(feature = "software", feature = "images"))]
+#[no_mangle]
+pub extern "C" fn dop_image_frame_delay(handle: DopHandle, image_id: u32, index: c_int) -> c_int {
+    ffi_guard("dop_image_frame_delay", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || index < 0 {
+            return -1;
+        }
+        unsafe {
+            (*handle)
+                .renderer
+                .image_frame(image_id, index as usize)
+                .map_or(-1, |(_, delay_ms)| delay_ms as c_int)
+        }
+    })
+}
+
+/// Show one frame of an animated image, for embedders that drive animation timing themselves
+/// Returns 1 on success, 0 if the image or frame doesn't exist
+#[cfg(all(feature = "software", feature = "images"))]
+#[no_mangle]
+pub extern "C" fn dop_image_set_frame(handle: DopHandle, image_id: u32, index: c_int) -> c_int {
+    ffi_guard("dop_image_set_frame", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || index < 0 {
+            return 0;
+        }
+        unsafe { (*handle).renderer.set_image_frame(image_id, index as usize) as c_int }
+    })
+}
+
+/// Advance animated image playback by `dt` seconds
+/// Returns 1 if any visible frame changed (a redraw is needed), 0 otherwise
+#[cfg(all(feature = "software", feature = "images"))]
+#[no_mangle]
+pub extern "C" fn dop_image_advance(handle: DopHandle, dt: c_float) -> c_int {
+    ffi_guard("dop_image_advance", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).renderer.advance_images(dt) as c_int }
+    })
+}
+
+/// Remove an image from the cache
+#[cfg(all(feature = "software", feature = "images"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_free_image(handle: DopHandle, image_id: u32) {
+    ffi_guard("dop_renderer_free_image", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.images_mut().remove(image_id);
+        }
+    })
+}
+
+/// Set the decoded image memory budget in bytes
+#[cfg(all(feature = "software", feature = "images"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_set_image_budget(handle: DopHandle, bytes: usize) {
+    ffi_guard("dop_renderer_set_image_budget", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.images_mut().set_budget(bytes);
+        }
+    })
+}
+
+/// Draw a cached image scaled to the given rectangle
+#[cfg(all(feature = "software", feature = "images"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_add_image(
+    handle: DopHandle,
+    image_id: u32,
+    x: c_float,
+    y: c_float,
+    width: c_float,
+    height: c_float,
+    z_index: c_int,
+) {
+    ffi_guard("dop_renderer_add_image", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.add_image(ImageCommand {
+                image_id,
+                x,
+                y,
+                width,
+                height,
+                z_index,
+            });
+        }
+    })
+}
+
+/// Store a straight-alpha RGBA8 bitmap in the renderer under `id` so later draws don't re-send pixels
+/// Returns 1 on success, 0 on failure
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_register_bitmap(
+    handle: DopHandle,
+    id: u32,
+    data: *const u8,
+    width: c_int,
+    height: c_int,
+) -> c_int {
+    ffi_guard("dop_renderer_register_bitmap", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || data.is_null() || width <= 0 || height <= 0 {
+            return 0;
+        }
+        unsafe {
+            let len = width as usize * height as usize * 4;
+            let pixels = std::slice::from_raw_parts(data, len);
+            (*handle).renderer.register_bitmap(id, pixels, width as u32, height as u32) as c_int
+        }
+    })
+}
+
+/// Remove a registered bitmap
+/// Returns 1 if it existed, 0 otherwise
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_unregister_bitmap(handle: DopHandle, id: u32) -> c_int {
+    ffi_guard("dop_renderer_unregister_bitmap", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).renderer.unregister_bitmap(id) as c_int }
+    })
+}
+
+/// Draw a registered bitmap scaled to the given rectangle
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_add_bitmap(
+    handle: DopHandle,
+    id: u32,
+    x: c_float,
+    y: c_float,
+    width: c_float,
+    height: c_float,
+    z_index: c_int,
+) {
+    ffi_guard("dop_renderer_add_bitmap", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.add_bitmap(BitmapCommand {
+                bitmap_id: id,
+                x,
+                y,
+                width,
+                height,
+                z_index,
+            });
+        }
+    })
+}
+
+// ============================================================================
+// Path FFI
+// ============================================================================
+
+/// Start a new path, discarding the previous one and resetting the fill rule and stroke style
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_path_begin(handle: DopHandle) {
+    ffi_guard("dop_renderer_path_begin", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            let handle = &mut *handle;
+            handle.path.clear();
+            handle.fill_rule = FillRule::NonZero;
+            handle.line_cap = LineCap::Butt;
+            handle.line_join = LineJoin::Miter;
+        }
+    })
+}
+
+/// Start a new subpath at (x, y)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_path_move_to(handle: DopHandle, x: c_float, y: c_float) {
+    ffi_guard("dop_renderer_path_move_to", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).path.move_to(x, y);
+        }
+    })
+}
+
+/// Add a line to (x, y)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_path_line_to(handle: DopHandle, x: c_float, y: c_float) {
+    ffi_guard("dop_renderer_path_line_to", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).path.line_to(x, y);
+        }
+    })
+}
+
+/// Add a quadratic Bezier curve through control point (cx, cy) to (x, y)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_path_quad_to(
+    handle: DopHandle,
+    cx: c_float,
+    cy: c_float,
+    x: c_float,
+    y: c_float,
+) {
+    ffi_guard("dop_renderer_path_quad_to", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).path.quad_to(cx, cy, x, y);
+        }
+    })
+}
+
+/// Add a cubic Bezier curve through control points (c1x, c1y) and (c2x, c2y) to (x, y)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_path_cubic_to(
+    handle: DopHandle,
+    c1x: c_float,
+    c1y: c_float,
+    c2x: c_float,
+    c2y: c_float,
+    x: c_float,
+    y: c_float,
+) {
+    ffi_guard("dop_renderer_path_cubic_to", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).path.cubic_to(c1x, c1y, c2x, c2y, x, y);
+        }
+    })
+}
+
+/// Close the current subpath
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_path_close(handle: DopHandle) {
+    ffi_guard("dop_renderer_path_close", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).path.close();
+        }
+    })
+}
+
+/// Set the fill rule of the current path (0 = non-zero, 1 = even-odd)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_path_set_fill_rule(handle: DopHandle, rule: c_int) {
+    ffi_guard("dop_renderer_path_set_fill_rule", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).fill_rule = FillRule::from_u8(rule as u8);
+        }
+    })
+}
+
+/// Set how the current path is stroked
+/// cap: 0 = butt, 1 = round, 2 = square; join: 0 = miter, 1 = round
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_path_set_stroke_style(handle: DopHandle, cap: c_int, join: c_int) {
+    ffi_guard("dop_renderer_path_set_stroke_style", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).line_cap = LineCap::from_u8(cap as u8);
+            (*handle).line_join = LineJoin::from_u8(join as u8);
+        }
+    })
+}
+
+/// Fill the current path (color in sRGB)
+///
+/// The path is kept, so it can also be stroked; the graphics state applies.
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_path_fill(handle: DopHandle, r: c_float, g: c_float, b: c_float, a: c_float) {
+    ffi_guard("dop_renderer_path_fill", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            let handle = &mut *handle;
+            handle.renderer.fill_path(&handle.path, handle.fill_rule, [r, g, b, a]);
+        }
+    })
+}
+
+/// Stroke the current path with a line `width` pixels wide (color in sRGB)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_path_stroke(
+    handle: DopHandle,
+    width: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+) {
+    ffi_guard("dop_renderer_path_stroke", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            let handle = &mut *handle;
+            let style = StrokeStyle {
+                width,
+                cap: handle.line_cap,
+                join: handle.line_join,
+            };
+            handle.renderer.stroke_path(&handle.path, style, [r, g, b, a]);
+        }
+    })
+}
+
+/// Draw a line from (x0, y0) to (x1, y1), `width` pixels wide (color in sRGB)
+/// cap: 0 = butt, 1 = round, 2 = square
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_add_line(
+    handle: DopHandle,
+    x0: c_float,
+    y0: c_float,
+    x1: c_float,
+    y1: c_float,
+    width: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+    cap: c_int,
+) {
+    ffi_guard("dop_renderer_add_line", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle)
+                .renderer
+                .add_line((x0, y0), (x1, y1), width, [r, g, b, a], LineCap::from_u8(cap as u8));
+        }
+    })
+}
+
+/// Draw connected lines through `count` points stored as x, y pairs (color in sRGB)
+/// cap: 0 = butt, 1 = round, 2 = square; join: 0 = miter, 1 = round
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_add_polyline(
+    handle: DopHandle,
+    points: *const c_float,
+    count: c_int,
+    width: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+    cap: c_int,
+    join: c_int,
+) {
+    ffi_guard("dop_renderer_add_polyline", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || points.is_null() || count < 2 {
+            return;
+        }
+        unsafe {
+            let points: Vec<(f32, f32)> = std::slice::from_raw_parts(points, count as usize * 2)
+                .chunks_exact(2)
+                .map(|p| (p[0], p[1]))
+                .collect();
+            let style = StrokeStyle {
+                width,
+                cap: LineCap::from_u8(cap as u8),
+                join: LineJoin::from_u8(join as u8),
+            };
+            (*handle).renderer.add_polyline(&points, style, [r, g, b, a]);
+        }
+    })
+}
+
+/// Draw an SVG document (null-terminated UTF-8 XML) fitted into a rectangle
+/// Returns 1 on success, 0 if the document could not be parsed
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_add_svg(
+    handle: DopHandle,
+    xml: *const c_char,
+    x: c_float,
+    y: c_float,
+    width: c_float,
+    height: c_float,
+) -> c_int {
+    ffi_guard("dop_renderer_add_svg", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || xml.is_null() {
+            return 0;
+        }
+        unsafe {
+            let Ok(xml) = CStr::from_ptr(xml).to_str() else {
+                return 0;
+            };
+            match crate::svg::SvgDocument::parse(xml) {
+                Ok(svg) => {
+                    (*handle).renderer.add_svg(&svg, x, y, width, height);
+                    1
+                }
+                Err(e) => {
+                    log::warn!("Failed to parse SVG: {}", e);
+                    0
+                }
+            }
+        }
+    })
+}
+
+// ============================================================================
+// Graphics State FFI
+// ============================================================================
+
+/// Set the device pixel ratio: draws are given in logical pixels and scaled by `ratio`
+///
+/// Size the renderer in physical pixels (logical size times the ratio) so
+/// text is rasterized at full resolution on HiDPI displays.
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_set_device_pixel_ratio(handle: DopHandle, ratio: c_float) {
+    ffi_guard("dop_renderer_set_device_pixel_ratio", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.set_device_pixel_ratio(ratio);
+        }
+    })
+}
+
+/// Save the renderer's transform, clip, opacity and blend mode
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_save(handle: DopHandle) {
+    ffi_guard("dop_renderer_save", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.save();
+        }
+    })
+}
+
+/// Restore the state from the matching save
+/// Returns 1 on success, 0 if nothing was saved
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_restore(handle: DopHandle) -> c_int {
+    ffi_guard("dop_renderer_restore", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*handle).renderer.restore() { 1 } else { 0 }
+        }
+    })
+}
+
+/// Open a stacking context inside the current one, painted as a unit at `z_index` among its
+/// siblings; rectangles added until the matching pop are ordered inside it
+/// Paths, images, bitmaps and text are not ordered by contexts and still paint above all rectangles
+/// Returns the context ID, or 0 on error
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_push_stacking_context(handle: DopHandle, z_index: c_int) -> u32 {
+    ffi_guard("dop_renderer_push_stacking_context", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).renderer.push_stacking_context(z_index) }
+    })
+}
+
+/// Close the innermost stacking context
+/// Returns 1 on success, 0 if only the root context is open
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_pop_stacking_context(handle: DopHandle) -> c_int {
+    ffi_guard("dop_renderer_pop_stacking_context", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*handle).renderer.pop_stacking_context() { 1 } else { 0 }
+        }
+    })
+}
+
+/// Offset subsequent draws
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_translate(handle: DopHandle, dx: c_float, dy: c_float) {
+    ffi_guard("dop_renderer_translate", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.translate(dx, dy);
+        }
+    })
+}
+
+/// Scale subsequent draws around the current origin
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_scale(handle: DopHandle, sx: c_float, sy: c_float) {
+    ffi_guard("dop_renderer_scale", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.scale(sx, sy);
+        }
+    })
+}
+
+/// Rotate subsequent draws clockwise by `degrees` around the current origin
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_rotate(handle: DopHandle, degrees: c_float) {
+    ffi_guard("dop_renderer_rotate", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.rotate(degrees);
+        }
+    })
+}
+
+/// Multiply the transform of subsequent draws by the matrix (a, b, c, d, e, f),
+/// mapping (x, y) to (a*x + c*y + e, b*x + d*y + f)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_transform(
+    handle: DopHandle,
+    a: c_float,
+    b: c_float,
+    c: c_float,
+    d: c_float,
+    e: c_float,
+    f: c_float,
+) {
+    ffi_guard("dop_renderer_transform", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.transform(a, b, c, d, e, f);
+        }
+    })
+}
+
+/// Transform subsequent draws (e.g. for scrolling or pinch-zoom) until the
+/// matching `dop_renderer_pop_transform`
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_push_transform(
+    handle: DopHandle,
+    a: c_float,
+    b: c_float,
+    c: c_float,
+    d: c_float,
+    e: c_float,
+    f: c_float,
+) {
+    ffi_guard("dop_renderer_push_transform", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.push_transform(a, b, c, d, e, f);
+        }
+    })
+}
+
+/// Pop the transform pushed by `dop_renderer_push_transform`
+/// Returns 1 on success, 0 if no transform was pushed
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_pop_transform(handle: DopHandle) -> c_int {
+    ffi_guard("dop_renderer_pop_transform", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*handle).renderer.pop_transform() { 1 } else { 0 }
+        }
+    })
+}
+
+/// Intersect the clip with a rectangle in the current coordinate space
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_clip_rect(
+    handle: DopHandle,
+    x: c_float,
+    y: c_float,
+    width: c_float,
+    height: c_float,
+) {
+    ffi_guard("dop_renderer_clip_rect", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.clip_rect(x, y, width, height);
+        }
+    })
+}
+
+/// Clip subsequent rect and text commands (e.g. for scroll containers or `overflow: hidden`)
+/// until the matching `dop_renderer_pop_clip`
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_push_clip(
+    handle: DopHandle,
+    x: c_float,
+    y: c_float,
+    width: c_float,
+    height: c_float,
+) {
+    ffi_guard("dop_renderer_push_clip", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.push_clip_rect(x, y, width, height);
+        }
+    })
+}
+
+/// Pop the clip pushed by `dop_renderer_push_clip`
+/// Returns 1 on success, 0 if no clip was pushed
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_pop_clip(handle: DopHandle) -> c_int {
+    ffi_guard("dop_renderer_pop_clip", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*handle).renderer.pop_clip_rect() { 1 } else { 0 }
+        }
+    })
+}
+
+/// Multiply the opacity of subsequent draws
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_set_opacity(handle: DopHandle, opacity: c_float) {
+    ffi_guard("dop_renderer_set_opacity", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.set_opacity(opacity);
+        }
+    })
+}
+
+/// Set the blend mode for subsequent rectangles, images and bitmaps
+/// (0 = normal, 1 = multiply, 2 = screen, 3 = overlay, 4 = darken, 5 = lighten,
+/// 6 = difference, 7 = exclusion, 8 = plus)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_set_blend_mode(handle: DopHandle, mode: u8) {
+    ffi_guard("dop_renderer_set_blend_mode", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle)
+                .renderer
+                .set_blend_mode(crate::state::BlendMode::from_u8(mode));
+        }
+    })
+}
+
+/// Set how subsequent images and bitmaps are sampled when scaled (0 = bilinear, 1 = nearest)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_set_image_filter(handle: DopHandle, filter: u8) {
+    ffi_guard("dop_renderer_set_image_filter", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle)
+                .renderer
+                .set_image_filter(crate::renderer::ImageFilter::from_u8(filter));
+        }
+    })
+}
+
+// ============================================================================
+// Content IR FFI
+// ============================================================================
+
+/// Run Content IR rendering for a builder's tree (resolving its shared styles) and queue the result on the renderer
+/// Text is sized with the builder's measure callback if set, else with the renderer's default font
+/// The layout is kept on the builder for `content_layout_get_rect` and hit testing
+/// Returns the number of commands queued, or -1 on failure
+#[cfg(feature = "content-ir")]
+#[no_mangle]
+pub extern "C" fn dop_render_content(
+    handle: DopHandle,
+    builder: *mut dop_content_ir::ffi::BuilderHandle,
+    viewport_width: c_float,
+    viewport_height: c_float,
+) -> c_int {
+    ffi_guard("dop_render_content", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || builder.is_null() {
+            return -1;
+        }
+        unsafe {
+            let content = (*builder).builder_mut();
+            let commands = match content.text_measure() {
+                Some(_) => content.render(viewport_width, viewport_height),
+                None => {
+                    let fonts = (*handle).renderer.font_manager();
+                    let font_measure =
+                        |text: &str, font_size: f32| fonts.measure_text(text, font_size, 0, TextSpacing::default());
+                    content.render_with_measure(viewport_width, viewport_height, &font_measure)
+                }
+            };
+            (*handle).renderer.add_content_commands(&commands);
+            commands.len() as c_int
+        }
+    })
+}
+
+/// Queue a builder's tree and limit the next render to the areas changed since the last call
+/// Returns the number of damage rectangles (0 means nothing changed), or -1 on failure
+#[cfg(feature = "content-ir")]
+#[no_mangle]
+pub extern "C" fn dop_render_content_incremental(
+    handle: DopHandle,
+    builder: *mut dop_content_ir::ffi::BuilderHandle,
+    viewport_width: c_float,
+    viewport_height: c_float,
+) -> c_int {
+    ffi_guard("dop_render_content_incremental", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || builder.is_null() {
+            return -1;
+        }
+        unsafe {
+            let content = (*builder).builder_mut();
+            let damage = match content.text_measure() {
+                Some(_) => content.collect_damage(viewport_width, viewport_height),
+                None => {
+                    let fonts = (*handle).renderer.font_manager();
+                    let font_measure =
+                        |text: &str, font_size: f32| fonts.measure_text(text, font_size, 0, TextSpacing::default());
+                    content.collect_damage_with_measure(viewport_width, viewport_height, &font_measure)
+                }
+            };
+            for rect in &damage {
+                (*handle).renderer.add_damage_rect(rect.x, rect.y, rect.width, rect.height);
+            }
+            // Damage collection already laid the tree out
+            (*handle).renderer.add_content_commands(&content.render_layout());
+            damage.len() as c_int
+        }
+    })
+}
+
+/// Queue a builder's tree at its current layout without laying it out
+/// Use after `content_builder_set_layout` when the layout was computed elsewhere
+/// Returns the number of commands queued, or -1 on failure
+#[cfg(feature = "content-ir")]
+#[no_mangle]
+pub extern "C" fn dop_render_content_layout(
+    handle: DopHandle,
+    builder: *const dop_content_ir::ffi::BuilderHandle,
+) -> c_int {
+    ffi_guard("dop_render_content_layout", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || builder.is_null() {
+            return -1;
+        }
+        unsafe {
+            let commands = (*builder).builder().render_layout();
+            (*handle).renderer.add_content_commands(&commands);
+            commands.len() as c_int
+        }
+    })
+}
+
+/// Lay out again only the parts of a builder's tree changed since its last layout and queue it
+/// The next render is limited to the areas that changed; the builder must have been rendered before
+/// Returns the number of damage rectangles (0 means nothing changed), or -1 on failure
+#[cfg(feature = "content-ir")]
+#[no_mangle]
+pub extern "C" fn dop_render_content_update(
+    handle: DopHandle,
+    builder: *mut dop_content_ir::ffi::BuilderHandle,
+) -> c_int {
+    ffi_guard("dop_render_content_update", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || builder.is_null() {
+            return -1;
+        }
+        unsafe {
+            let content = (*builder).builder_mut();
+            if content.text_measure().is_some() {
+                content.update_layout();
+            } else {
+                let fonts = (*handle).renderer.font_manager();
+                let font_measure = |text: &str, font_size: f32| fonts.measure_text(text, font_size, 0, TextSpacing::default());
+                content.update_layout_with_measure(&font_measure);
+            }
+            let damage = content.collect_layout_damage();
+            for rect in &damage {
+                (*handle).renderer.add_damage_rect(rect.x, rect.y, rect.width, rect.height);
+            }
+            (*handle).renderer.add_content_commands(&content.render_layout());
+            damage.len() as c_int
+        }
+    })
+}
+
+/// Deserialize a compiled unit (CMMB binary), lay it out and queue it on the renderer in one call
+/// Text is sized with the renderer's default font; no builder handle is needed
+/// Returns the number of commands queued, or -1 if the data is not a valid CMMB binary
+#[cfg(feature = "content-ir")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_render_compiled_unit(
+    handle: DopHandle,
+    unit_bytes: *const u8,
+    len: usize,
+    viewport_width: c_float,
+    viewport_height: c_float,
+) -> c_int {
+    ffi_guard("dop_renderer_render_compiled_unit", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || unit_bytes.is_null() {
+            return -1;
+        }
+        let data = unsafe { std::slice::from_raw_parts(unit_bytes, len) };
+        let Some(mut content) = dop_content_ir::ContentBuilder::from_binary(data) else {
+            crate::ffi_guard::set_last_error("not a valid CMMB compiled unit");
+            return -1;
+        };
+        unsafe {
+            let fonts = (*handle).renderer.font_manager();
+            let font_measure = |text: &str, font_size: f32| fonts.measure_text(text, font_size, 0, TextSpacing::default());
+            let commands = content.render_with_measure(viewport_width, viewport_height, &font_measure);
+            (*handle).renderer.add_content_commands(&commands);
+            commands.len() as c_int
+        }
+    })
+}
+
+// ============================================================================
+// Text rendering FFI
+// ============================================================================
+
+/// Add a text render command (software)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_add_text(
+    handle: DopHandle,
+    text: *const c_char,
+    x: c_float,
+    y: c_float,
+    font_size: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+    _font_id: c_int,
+) {
+    ffi_guard("dop_renderer_add_text", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || text.is_null() {
+            return;
+        }
+
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s.to_string(),
+                Err(_) => return,
+            }
+        };
+
+        unsafe {
+            (*handle).renderer.add_text(TextCommand {
+                text: text_str,
+                x,
+                y,
+                font_size,
+                color_r: r,
+                color_g: g,
+                color_b: b,
+                color_a: a,
+                font_id: _font_id as u32,
+                decoration: TextDecoration::NONE,
+                decoration_color: None,
+                anchor: TextAnchor::Top,
+                spacing: TextSpacing::default(),
+            });
+        }
+    })
+}
+
+/// Add a text render command with decoration lines and an anchor (software)
+///
+/// `decoration` combines 1 = underline, 2 = overline and 4 = line-through;
+/// the lines are drawn in (dr, dg, db, da), in sRGB like the text color.
+/// `anchor` selects what `y` refers to: 0 = top, 1 = first baseline, 2 = bottom.
+/// `letter_spacing` and `word_spacing` widen characters and spaces, and a
+/// positive `line_height` replaces the default of 1.2 times the font size.
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_add_text_ex(
+    handle: DopHandle,
+    text: *const c_char,
+    x: c_float,
+    y: c_float,
+    font_size: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+    font_id: c_int,
+    decoration: c_int,
+    dr: c_float,
+    dg: c_float,
+    db: c_float,
+    da: c_float,
+    anchor: c_int,
+    letter_spacing: c_float,
+    word_spacing: c_float,
+    line_height: c_float,
+) {
+    ffi_guard("dop_renderer_add_text_ex", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || text.is_null() {
+            return;
+        }
+
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s.to_string(),
+                Err(_) => return,
+            }
+        };
+
+        unsafe {
+            (*handle).renderer.add_text(TextCommand {
+                text: text_str,
+                x,
+                y,
+                font_size,
+                color_r: r,
+                color_g: g,
+                color_b: b,
+                color_a: a,
+                font_id: font_id as u32,
+                decoration: TextDecoration::from_bits(decoration as u8),
+                decoration_color: Some([dr, dg, db, da]),
+                anchor: TextAnchor::from_u8(anchor as u8),
+                spacing: TextSpacing {
+                    letter: letter_spacing,
+                    word: word_spacing,
+                    line_height: (line_height > 0.0).then_some(line_height),
+                },
+            });
+        }
+    })
+}
+
+/// Start a rich-text paragraph, discarding runs added since the last one
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_text_begin_paragraph(handle: DopHandle) {
+    ffi_guard("dop_text_begin_paragraph", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).paragraph.clear();
+        }
+    })
+}
+
+/// Append a styled run to the current paragraph
+///
+/// `flags` takes the text decoration bits of `dop_renderer_add_text_ex`
+/// (1 = underline, 2 = overline, 4 = line-through).
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_text_add_run(
+    handle: DopHandle,
+    text: *const c_char,
+    font_id: c_int,
+    font_size: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+    flags: c_int,
+) {
+    ffi_guard("dop_text_add_run", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || text.is_null() {
+            return;
+        }
+
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s.to_string(),
+                Err(_) => return,
+            }
+        };
+
+        unsafe {
+            (*handle).paragraph.push(TextSpan {
+                text: text_str,
+                font_id: font_id as u32,
+                font_size,
+                color: [r, g, b, a],
+                decoration: TextDecoration::from_bits(flags as u8),
+            });
+        }
+    })
+}
+
+/// Shape the current paragraph's runs together and draw it with its top-left at (x, y)
+///
+/// Lines wrap at `max_width` (no wrapping if 0 or less). Returns the
+/// paragraph's size and line count; the runs stay until the next
+/// `dop_text_begin_paragraph`.
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_text_shape_and_draw(
+    handle: DopHandle,
+    x: c_float,
+    y: c_float,
+    max_width: c_float,
+) -> ShapedTextFFI {
+    ffi_guard("dop_text_shape_and_draw", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return ShapedTextFFI {
+                width: 0.0,
+                height: 0.0,
+                line_count: 0,
+            };
+        }
+
+        unsafe {
+            let handle = &mut *handle;
+            let layout = handle
+                .renderer
+                .add_paragraph(&handle.paragraph, x, y, (max_width > 0.0).then_some(max_width));
+            ShapedTextFFI {
+                width: layout.width,
+                height: layout.height,
+                line_count: layout.line_count as c_int,
+            }
+        }
+    })
+}
+
+/// Add a text render command (fallback)
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_add_text(
+    handle: DopHandle,
+    text: *const c_char,
+    x: c_float,
+    y: c_float,
+    font_size: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+    font_id: c_int,
+) {
+    ffi_guard("dop_renderer_add_text", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || text.is_null() {
+            return;
+        }
+
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s.to_string(),
+                Err(_) => return,
+            }
+        };
+
+        unsafe {
+            (*handle).text_commands.push(TextCommandFFI {
+                text: text_str,
+                x,
+                y,
+                font_size,
+                color_r: r,
+                color_g: g,
+                color_b: b,
+                color_a: a,
+                font_id: font_id as u32,
+            });
+        }
+    })
+}
+
+/// Measure text width and height (software)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_measure_text(
+    handle: DopHandle,
+    text: *const c_char,
+    font_size: c_float,
+    font_id: c_int,
+    out_width: *mut c_float,
+    out_height: *mut c_float,
+) {
+    ffi_guard("dop_renderer_measure_text", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() || text.is_null() || out_width.is_null() || out_height.is_null() {
+            return;
+        }
+
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s,
+                Err(_) => {
+                    *out_width = 0.0;
+                    *out_height = 0.0;
+                    return;
+                }
+            }
+        };
+
+        unsafe {
+            let (w, h) =
+                (*handle)
+                    .renderer
+                    .font_manager()
+                    .measure_text(text_str, font_size, font_id as u32, TextSpacing::default());
+            *out_width = w;
+            *out_height = h;
+        }
+    })
+}
+
+/// Measure text width and height (fallback)
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_measure_text(
+    handle: DopHandle,
+    text: *const c_char,
+    font_size: c_float,
+    font_id: c_int,
+    out_width: *mut c_float,
+    out_height: *mut c_float,
+) {
+    ffi_guard("dop_renderer_measure_text", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() || text.is_null() || out_width.is_null() || out_height.is_null() {
+            return;
+        }
+
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s,
+                Err(_) => {
+                    *out_width = 0.0;
+                    *out_height = 0.0;
+                    return;
+                }
+            }
+        };
+
+        unsafe {
+            let (w, h) = (*handle)
+                .font_manager
+                .measure_text(text_str, font_size, font_id as u32, TextSpacing::default());
+            *out_width = w;
+            *out_height = h;
+        }
+    })
+}
+
+/// Get a font's ascent, descent (positive, below the baseline) and line gap in pixels at `font_size` (software)
+///
+/// Returns 1 on success, 0 if the font is not loaded.
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_get_font_metrics(
+    handle: DopHandle,
+    font_id: c_int,
+    font_size: c_float,
+    out_ascent: *mut c_float,
+    out_descent: *mut c_float,
+    out_line_gap: *mut c_float,
+) -> c_int {
+    ffi_guard("dop_renderer_get_font_metrics", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() || out_ascent.is_null() || out_descent.is_null() || out_line_gap.is_null() {
+            return 0;
+        }
+
+        unsafe {
+            match (*handle).renderer.font_manager().font_metrics(font_id as u32, font_size) {
+                Some(metrics) => {
+                    *out_ascent = metrics.ascent;
+                    *out_descent = metrics.descent;
+                    *out_line_gap = metrics.line_gap;
+                    1
+                }
+                None => 0,
+            }
+        }
+    })
+}
+
+/// Get a font's ascent, descent (positive, below the baseline) and line gap in pixels at `font_size` (fallback)
+///
+/// Returns 1 on success, 0 if the font is not loaded.
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_get_font_metrics(
+    handle: DopHandle,
+    font_id: c_int,
+    font_size: c_float,
+    out_ascent: *mut c_float,
+    out_descent: *mut c_float,
+    out_line_gap: *mut c_float,
+) -> c_int {
+    ffi_guard("dop_renderer_get_font_metrics", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() || out_ascent.is_null() || out_descent.is_null() || out_line_gap.is_null() {
+            return 0;
+        }
+
+        unsafe {
+            match (*handle).font_manager.font_metrics(font_id as u32, font_size) {
+                Some(metrics) => {
+                    *out_ascent = metrics.ascent;
+                    *out_descent = metrics.descent;
+                    *out_line_gap = metrics.line_gap;
+                    1
+                }
+                None => 0,
+            }
+        }
+    })
+}
+
+/// Load a font from file, returns font ID or -1 on failure (software)
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_load_font(
+    handle: DopHandle,
+    path: *const c_char,
+) -> c_int {
+    ffi_guard("dop_renderer_load_font", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || path.is_null() {
+            return -1;
+        }
+
+        let path_str = unsafe {
+            match CStr::from_ptr(path).to_str() {
+                Ok(s) => s,
+                Err(_) => return -1,
+            }
+        };
+
+        unsafe {
+            match (*handle).renderer.font_manager_mut().load_font(path_str) {
+                Some(id) => id as c_int,
+                None => -1,
+            }
+        }
+    })
+}
+
+/// Load a font from file, returns font ID or -1 on failure (fallback)
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_load_font(
+    handle: DopHandle,
+    path: *const c_char,
+) -> c_int {
+    ffi_guard("dop_renderer_load_font", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || path.is_null() {
+            return -1;
+        }
+
+        let path_str = unsafe {
+            match CStr::from_ptr(path).to_str() {
+                Ok(s) => s,
+                Err(_) => return -1,
+            }
+        };
+
+        unsafe {
+            match (*handle).font_manager.load_font(path_str) {
+                Some(id) => id as c_int,
+                None => -1,
+            }
+        }
+    })
+}
+
+/// Load a font from memory (TrueType, OpenType or WOFF), returns font ID or -1 on failure (software)
+/// The bytes are copied; the caller keeps ownership of `data`
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_load_font_from_memory(
+    handle: DopHandle,
+    data: *const u8,
+    len: usize,
+) -> c_int {
+    ffi_guard("dop_renderer_load_font_from_memory", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || data.is_null() {
+            return -1;
+        }
+
+        unsafe {
+            let bytes = std::slice::from_raw_parts(data, len);
+            match (*handle).renderer.font_manager_mut().load_font_from_bytes(bytes) {
+                Some(id) => id as c_int,
+                None => -1,
+            }
+        }
+    })
+}
+
+/// Load a font from memory (TrueType, OpenType or WOFF), returns font ID or -1 on failure (fallback)
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_load_font_from_memory(
+    handle: DopHandle,
+    data: *const u8,
+    len: usize,
+) -> c_int {
+    ffi_guard("dop_renderer_load_font_from_memory", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || data.is_null() {
+            return -1;
+        }
+
+        unsafe {
+            let bytes = std::slice::from_raw_parts(data, len);
+            match (*handle).font_manager.load_font_from_bytes(bytes) {
+                Some(id) => id as c_int,
+                None => -1,
+            }
+        }
+    })
+}
+
+/// Load a font file as one face of a family, returns font ID or -1 on failure (software)
+/// weight: 100-900 (400 = regular, 700 = bold); italic: 1 for an italic face
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_load_font_family(
+    handle: DopHandle,
+    family: *const c_char,
+    weight: c_int,
+    italic: c_int,
+    path: *const c_char,
+) -> c_int {
+    ffi_guard("dop_renderer_load_font_family", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || family.is_null() || path.is_null() {
+            return -1;
+        }
+
+        let (family_str, path_str) = unsafe {
+            match (CStr::from_ptr(family).to_str(), CStr::from_ptr(path).to_str()) {
+                (Ok(f), Ok(p)) => (f, p),
+                _ => return -1,
+            }
+        };
+
+        unsafe {
+            match (*handle)
+                .renderer
+                .font_manager_mut()
+                .load_font_family(family_str, weight.clamp(1, 1000) as u16, italic != 0, path_str)
+            {
+                Some(id) => id as c_int,
+                None => -1,
+            }
+        }
+    })
+}
+
+/// Find the font ID for a family at a weight and style, returns -1 if the family is unknown (software)
+///
+/// Missing bold or italic faces are synthesized from the closest face.
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_resolve_font(
+    handle: DopHandle,
+    family: *const c_char,
+    weight: c_int,
+    italic: c_int,
+) -> c_int {
+    ffi_guard("dop_renderer_resolve_font", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || family.is_null() {
+            return -1;
+        }
+
+        let family_str = unsafe {
+            match CStr::from_ptr(family).to_str() {
+                Ok(s) => s,
+                Err(_) => return -1,
+            }
+        };
+
+        unsafe {
+            match (*handle)
+                .renderer
+                .font_manager_mut()
+                .resolve_font(family_str, weight.clamp(1, 1000) as u16, italic != 0)
+            {
+                Some(id) => id as c_int,
+                None => -1,
+            }
+        }
+    })
+}
+
+/// Load a font file as one face of a family, returns font ID or -1 on failure (fallback)
+/// weight: 100-900 (400 = regular, 700 = bold); italic: 1 for an italic face
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_load_font_family(
+    handle: DopHandle,
+    family: *const c_char,
+    weight: c_int,
+    italic: c_int,
+    path: *const c_char,
+) -> c_int {
+    ffi_guard("dop_renderer_load_font_family", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || family.is_null() || path.is_null() {
+            return -1;
+        }
+
+        let (family_str, path_str) = unsafe {
+            match (CStr::from_ptr(family).to_str(), CStr::from_ptr(path).to_str()) {
+                (Ok(f), Ok(p)) => (f, p),
+                _ => return -1,
+            }
+        };
+
+        unsafe {
+            match (*handle)
+                .font_manager
+                .load_font_family(family_str, weight.clamp(1, 1000) as u16, italic != 0, path_str)
+            {
+                Some(id) => id as c_int,
+                None => -1,
+            }
+        }
+    })
+}
+
+/// Find the font ID for a family at a weight and style, returns -1 if the family is unknown (fallback)
+///
+/// Missing bold or italic faces are synthesized from the closest face.
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_resolve_font(
+    handle: DopHandle,
+    family: *const c_char,
+    weight: c_int,
+    italic: c_int,
+) -> c_int {
+    ffi_guard("dop_renderer_resolve_font", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || family.is_null() {
+            return -1;
+        }
+
+        let family_str = unsafe {
+            match CStr::from_ptr(family).to_str() {
+                Ok(s) => s,
+                Err(_) => return -1,
+            }
+        };
+
+        unsafe {
+            match (*handle)
+                .font_manager
+                .resolve_font(family_str, weight.clamp(1, 1000) as u16, italic != 0)
+            {
+                Some(id) => id as c_int,
+                None => -1,
+            }
+        }
+    })
+}
+
+/// Copy the family names of installed system fonts, separated by '\n'
+///
+/// Writes at most `capacity` bytes to `buffer` (not NUL-terminated) and
+/// returns the full length in bytes; call with a null buffer to query the size.
+#[no_mangle]
+pub extern "C" fn dop_font_enumerate(buffer: *mut u8, capacity: c_int) -> c_int {
+    ffi_guard("dop_font_enumerate", || {
+        let names = crate::text::system_font_families().join("\n");
+        if !buffer.is_null() && capacity > 0 {
+            let n = names.len().min(capacity as usize);
+            unsafe { ptr::copy_nonoverlapping(names.as_ptr(), buffer, n) };
+        }
+        names.len() as c_int
+    })
+}
+
+/// Load an installed font by family name, e.g. "Noto Sans" (software)
+///
+/// Returns the font ID of the regular face, or -1 if no such family is
+/// installed. The family is also registered for `dop_renderer_resolve_font`.
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_load_font_by_name(handle: DopHandle, name: *const c_char) -> c_int {
+    ffi_guard("dop_renderer_load_font_by_name", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || name.is_null() {
+            return -1;
+        }
+
+        let name_str = unsafe {
+            match CStr::from_ptr(name).to_str() {
+                Ok(s) => s,
+                Err(_) => return -1,
+            }
+        };
+
+        unsafe {
+            match (*handle).renderer.font_manager_mut().load_system_font(name_str, 400, false) {
+                Some(id) => id as c_int,
+                None => -1,
+            }
+        }
+    })
+}
+
+/// Load an installed font by family name, e.g. "Noto Sans" (fallback)
+///
+/// Returns the font ID of the regular face, or -1 if no such family is
+/// installed. The family is also registered for `dop_renderer_resolve_font`.
+#[cfg(not(feature = "software"))]
+#[no_mangle]
+pub extern "C" fn dop_renderer_load_font_by_name(handle: DopHandle, name: *const c_char) -> c_int {
+    ffi_guard("dop_renderer_load_font_by_name", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || name.is_null() {
+            return -1;
+        }
+
+        let name_str = unsafe {
+            match CStr::from_ptr(name).to_str() {
+                Ok(s) => s,
+                Err(_) => return -1,
+            }
+        };
+
+        unsafe {
+            match (*handle).font_manager.load_system_font(name_str, 400, false) {
+                Some(id) => id as c_int,
+                None => -1,
+            }
+        }
+    })
+}
+
+/// Set the glyph bitmap cache budget in bytes (software)
+///
+/// Least-recently-used glyphs are evicted past the budget; 0 disables the cache.
+#[cfg(feature = "software")]
+#[no_mangle]
+pub extern "C" fn dop_renderer_set_glyph_cache_limit(handle: DopHandle, bytes: usize) {
+    ffi_guard("dop_renderer_set_glyph_cache_limit", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.font_manager_mut().set_glyph_cache_limit(bytes);
+        }
+    })
 }
 
-/// Render the frame (fallback software rasterization)
+/// Set the glyph bitmap cache budget in bytes (fallback)
+///
+/// Least-recently-used glyphs are evicted past the budget; 0 disables the cache.
 #[cfg(not(feature = "software"))]
 #[no_mangle]
-pub extern "C" fn dop_renderer_render(handle: *mut RendererHandle) {
-    if handle.is_null() {
-        return;
-    }
-    let handle = unsafe { &mut *handle };
-
-    let w = handle.width;
-    let h = handle.height;
-
-    // Sort commands by z-index
-    handle.commands.sort_by_key(|c| c.z_index);
-
-    // Clone commands to iterate over them
-    let commands: Vec<RenderCommand> = handle.commands.clone();
-
-    // Software rasterize each rectangle command
-    for cmd in &commands {
-        // Calculate rectangle bounds
-        let x0 = (cmd.x.max(0.0) as u32).min(w);
-        let y0 = (cmd.y.max(0.0) as u32).min(h);
-        let x1 = ((cmd.x + cmd.width).ceil() as u32).min(w);
-        let y1 = ((cmd.y + cmd.height).ceil() as u32).min(h);
-
-        let rb = (cmd.color_r * 255.0) as u8;
-        let gb = (cmd.color_g * 255.0) as u8;
-        let bb = (cmd.color_b * 255.0) as u8;
-        let ab = (cmd.color_a * 255.0) as u8;
-        let alpha = cmd.color_a;
-        let inv_alpha = 1.0 - alpha;
-
-        // Fill the rectangle
-        for y in y0..y1 {
-            for x in x0..x1 {
-                let idx = ((y * w + x) * 4) as usize;
-                if idx + 3 < handle.framebuffer.len() {
-                    // Alpha blend
-                    let dst_r = handle.framebuffer[idx] as f32;
-                    let dst_g = handle.framebuffer[idx + 1] as f32;
-                    let dst_b = handle.framebuffer[idx + 2] as f32;
-                    let dst_a = handle.framebuffer[idx + 3];
-
-                    handle.framebuffer[idx] =
-                        ((rb as f32 * alpha + dst_r * inv_alpha) as u8).min(255);
-                    handle.framebuffer[idx + 1] =
-                        ((gb as f32 * alpha + dst_g * inv_alpha) as u8).min(255);
-                    handle.framebuffer[idx + 2] =
-                        ((bb as f32 * alpha + dst_b * inv_alpha) as u8).min(255);
-                    handle.framebuffer[idx + 3] = (dst_a as u16 + ab as u16).min(255) as u8;
-                }
-            }
+pub extern "C" fn dop_renderer_set_glyph_cache_limit(handle: DopHandle, bytes: usize) {
+    ffi_guard("dop_renderer_set_glyph_cache_limit", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
         }
-    }
-
-    // Render text commands
-    let text_commands: Vec<TextCommandFFI> = handle.text_commands.clone();
-    for text_cmd in &text_commands {
-        let color = (
-            (text_cmd.color_r * 255.0) as u8,
-            (text_cmd.color_g * 255.0) as u8,
-            (text_cmd.color_b * 255.0) as u8,
-            (text_cmd.color_a * 255.0) as u8,
-        );
-
-        let (text_buffer, text_w, text_h) = handle.font_manager.rasterize_text(
-            &text_cmd.text,
-            text_cmd.font_size,
-            text_cmd.font_id,
-            color,
-        );
-
-        if text_buffer.is_empty() || text_w == 0 || text_h == 0 {
-            continue;
-        }
-
-        // Blit text to framebuffer
-        let tx = text_cmd.x as i32;
-        let ty = text_cmd.y as i32;
-
-        for ty_off in 0..text_h as i32 {
-            for tx_off in 0..text_w as i32 {
-                let px = tx + tx_off;
-                let py = ty + ty_off;
-
-                if px >= 0 && py >= 0 && (px as u32) < w && (py as u32) < h {
-                    let src_idx = ((ty_off as u32 * text_w + tx_off as u32) * 4) as usize;
-                    let dst_idx = ((py as u32 * w + px as u32) * 4) as usize;
-
-                    if src_idx + 3 < text_buffer.len() && dst_idx + 3 < handle.framebuffer.len() {
-                        let src_a = text_buffer[src_idx + 3] as f32 / 255.0;
-                        if src_a > 0.0 {
-                            let inv_a = 1.0 - src_a;
-                            handle.framebuffer[dst_idx] = ((text_buffer[src_idx] as f32 * src_a
-                                + handle.framebuffer[dst_idx] as f32 * inv_a)
-                                as u8)
-                                .min(255);
-                            handle.framebuffer[dst_idx + 1] = ((text_buffer[src_idx + 1] as f32
-                                * src_a
-                                + handle.framebuffer[dst_idx + 1] as f32 * inv_a)
-                                as u8)
-                                .min(255);
-                            handle.framebuffer[dst_idx + 2] = ((text_buffer[src_idx + 2] as f32
-                                * src_a
-                                + handle.framebuffer[dst_idx + 2] as f32 * inv_a)
-                                as u8)
-                                .min(255);
-                            handle.framebuffer[dst_idx + 3] = ((src_a * 255.0
-                                + handle.framebuffer[dst_idx + 3] as f32 * inv_a)
-                                as u8)
-                                .min(255);
-                        }
-                    }
-                }
-            }
+        unsafe {
+            (*handle).font_manager.set_glyph_cache_limit(bytes);
         }
-    }
+    })
 }
 
-/// Get framebuffer pointer
+/// Check if a default font is available (software)
 #[cfg(feature = "software")]
 #[no_mangle]
-pub extern "C" fn dop_renderer_get_framebuffer(handle: *const RendererHandle) -> *const u8 {
-    if handle.is_null() {
-        return ptr::null();
-    }
-    unsafe { (*handle).renderer.get_framebuffer().as_ptr() }
+pub extern "C" fn dop_renderer_has_default_font(handle: DopHandle) -> c_int {
+    ffi_guard("dop_renderer_has_default_font", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*handle).renderer.font_manager().get_font(0).is_some() {
+                1
+            } else {
+                0
+            }
+        }
+    })
 }
 
-/// Get framebuffer pointer (fallback)
+/// Check if a default font is available (fallback)
 #[cfg(not(feature = "software"))]
 #[no_mangle]
-pub extern "C" fn dop_renderer_get_framebuffer(handle: *const RendererHandle) -> *const u8 {
-    if handle.is_null() {
-        return ptr::null();
-    }
-    unsafe { (*handle).framebuffer.as_ptr() }
+pub extern "C" fn dop_renderer_has_default_font(handle: DopHandle) -> c_int {
+    ffi_guard("dop_renderer_has_default_font", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*handle).font_manager.get_font(0).is_some() {
+                1
+            } else {
+                0
+            }
+        }
+    })
 }
 
-/// Get framebuffer size
-#[cfg(feature = "software")]
-#[no_mangle]
-pub extern "C" fn dop_renderer_get_framebuffer_size(handle: *const RendererHandle) -> c_int {
-    if handle.is_null() {
-        return 0;
-    }
-    unsafe { (*handle).renderer.get_framebuffer_size() as c_int }
+// ============================================================================
+// Batch submission FFI
+// ============================================================================
+
+/// A text command in a packed array for `dop_renderer_submit_text_commands`
+///
+/// The text is a string interned with `dop_renderer_intern_string`, so a
+/// frame of text crosses the FFI boundary without any C strings.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PackedTextCommand {
+    pub string_id: u32,
+    pub font_id: u32,
+    pub x: f32,
+    pub y: f32,
+    pub font_size: f32,
+    pub color_r: f32,
+    pub color_g: f32,
+    pub color_b: f32,
+    pub color_a: f32,
+    /// `TextDecoration` bits
+    pub decoration: u32,
+    /// `TextAnchor` value
+    pub anchor: u32,
 }
 
-/// Get framebuffer size (fallback)
-#[cfg(not(feature = "software"))]
+/// Add `count` rectangle commands from a packed array in one call
+///
+/// Returns the number of commands added.
 #[no_mangle]
-pub extern "C" fn dop_renderer_get_framebuffer_size(handle: *const RendererHandle) -> c_int {
-    if handle.is_null() {
-        return 0;
-    }
-    unsafe { (*handle).framebuffer.len() as c_int }
+pub extern "C" fn dop_renderer_submit_commands(handle: DopHandle, commands: *const RenderCommand, count: usize) -> c_int {
+    ffi_guard("dop_renderer_submit_commands", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || commands.is_null() {
+            return 0;
+        }
+        unsafe {
+            let commands = std::slice::from_raw_parts(commands, count);
+            #[cfg(feature = "software")]
+            for cmd in commands {
+                (*handle).renderer.add_rect(*cmd);
+            }
+            #[cfg(not(feature = "software"))]
+            (*handle).commands.extend_from_slice(commands);
+            commands.len() as c_int
+        }
+    })
 }
 
-/// Resize the renderer
+/// Intern a UTF-8 string for use in packed text commands
+///
+/// Interning the same string again returns the same ID. Strings live as long
+/// as the renderer. Returns 0 on error.
 #[cfg(feature = "software")]
 #[no_mangle]
-pub extern "C" fn dop_renderer_resize(handle: *mut RendererHandle, width: c_int, height: c_int) {
-    if handle.is_null() {
-        return;
-    }
-    unsafe {
-        (*handle).renderer.resize(width as u32, height as u32);
-    }
+pub extern "C" fn dop_renderer_intern_string(handle: DopHandle, text: *const c_char) -> u32 {
+    ffi_guard("dop_renderer_intern_string", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || text.is_null() {
+            return 0;
+        }
+        unsafe {
+            let Ok(text) = CStr::from_ptr(text).to_str() else {
+                return 0;
+            };
+            let handle = &mut *handle;
+            if let Some(&id) = handle.string_ids.get(text) {
+                return id;
+            }
+            let id = handle.strings.len() as u32;
+            handle.strings.push(text.to_string());
+            handle.string_ids.insert(text.to_string(), id);
+            id
+        }
+    })
 }
 
-/// Resize the renderer (fallback)
-#[cfg(not(feature = "software"))]
+/// Add `count` text commands from a packed array in one call
+///
+/// Commands with an unknown string ID are skipped. Returns the number of
+/// commands added.
+#[cfg(feature = "software")]
 #[no_mangle]
-pub extern "C" fn dop_renderer_resize(handle: *mut RendererHandle, width: c_int, height: c_int) {
-    if handle.is_null() {
-        return;
-    }
-    unsafe {
-        let w = width as u32;
-        let h = height as u32;
-        (*handle).width = w;
-        (*handle).height = h;
-        (*handle).framebuffer = vec![255u8; (w * h * 4) as usize];
-    }
+pub extern "C" fn dop_renderer_submit_text_commands(
+    handle: DopHandle,
+    commands: *const PackedTextCommand,
+    count: usize,
+) -> c_int {
+    ffi_guard("dop_renderer_submit_text_commands", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || commands.is_null() {
+            return 0;
+        }
+        let handle = unsafe { &mut *handle };
+        let commands = unsafe { std::slice::from_raw_parts(commands, count) };
+        let mut added = 0;
+        for cmd in commands {
+            let Some(text) = handle.strings.get(cmd.string_id as usize).filter(|_| cmd.string_id != 0) else {
+                continue;
+            };
+            handle.renderer.add_text(TextCommand {
+                text: text.clone(),
+                x: cmd.x,
+                y: cmd.y,
+                font_size: cmd.font_size,
+                color_r: cmd.color_r,
+                color_g: cmd.color_g,
+                color_b: cmd.color_b,
+                color_a: cmd.color_a,
+                font_id: cmd.font_id,
+                decoration: TextDecoration::from_bits(cmd.decoration as u8),
+                decoration_color: None,
+                anchor: TextAnchor::from_u8(cmd.anchor as u8),
+                spacing: TextSpacing::default(),
+            });
+            added += 1;
+        }
+        added
+    })
 }
 
 // ============================================================================
-// Event creation helpers
+// Text shaper FFI
 // ============================================================================
 
-/// Create a close event
-#[no_mangle]
-pub extern "C" fn dop_event_close() -> DopEvent {
-    DopEvent::close()
+/// Text shaper handle for paragraph layout
+pub struct TextShaperHandle {
+    shaper: TextShaper,
+    /// Result of the last `dop_text_shaper_shape`, queried by line and glyph
+    shaped: Option<ShapedText>,
 }
 
-/// Create a resize event
+/// Create a text shaper
 #[no_mangle]
-pub extern "C" fn dop_event_resize(width: c_int, height: c_int) -> DopEvent {
-    DopEvent::resize(width as u32, height as u32)
+pub extern "C" fn dop_text_shaper_create() -> *mut TextShaperHandle {
+    ffi_guard("dop_text_shaper_create", || {
+        Box::into_raw(Box::new(TextShaperHandle {
+            shaper: TextShaper::new(),
+            shaped: None,
+        }))
+    })
 }
 
-/// Create a key down event
+/// Free a text shaper
 #[no_mangle]
-pub extern "C" fn dop_event_key_down(key: c_int, modifiers: u8) -> DopEvent {
-    DopEvent::key_down(key, modifiers)
+pub extern "C" fn dop_text_shaper_free(handle: *mut TextShaperHandle) {
+    ffi_guard("dop_text_shaper_free", || {
+        if !handle.is_null() {
+            unsafe {
+                drop(Box::from_raw(handle));
+            }
+        }
+    })
 }
 
-/// Create a key up event
+/// Set the letter spacing, word spacing and line height of paragraphs shaped from now on
+///
+/// A `line_height` of 0 or less restores the default of 1.2 times the font size.
 #[no_mangle]
-pub extern "C" fn dop_event_key_up(key: c_int, modifiers: u8) -> DopEvent {
-    DopEvent::key_up(key, modifiers)
+pub extern "C" fn dop_text_shaper_set_spacing(
+    handle: *mut TextShaperHandle,
+    letter_spacing: c_float,
+    word_spacing: c_float,
+    line_height: c_float,
+) {
+    ffi_guard("dop_text_shaper_set_spacing", || {
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).shaper.set_spacing(TextSpacing {
+                letter: letter_spacing,
+                word: word_spacing,
+                line_height: (line_height > 0.0).then_some(line_height),
+            });
+        }
+    })
 }
 
-/// Create a mouse down event
-#[no_mangle]
-pub extern "C" fn dop_event_mouse_down(button: u8, x: c_float, y: c_float) -> DopEvent {
-    let btn = match button {
-        0 => MouseButtonId::Left,
-        1 => MouseButtonId::Right,
-        2 => MouseButtonId::Middle,
-        3 => MouseButtonId::X1,
-        4 => MouseButtonId::X2,
-        _ => MouseButtonId::Left,
-    };
-    DopEvent::mouse_down(btn, x as f64, y as f64)
+/// Shaped text result for FFI
+#[repr(C)]
+#[derive(Default)]
+pub struct ShapedTextFFI {
+    pub width: c_float,
+    pub height: c_float,
+    pub line_count: c_int,
 }
 
-/// Create a mouse up event
+impl FfiDefault for ShapedTextFFI {
+    fn ffi_default() -> Self {
+        Self::default()
+    }
+}
+
+/// Shape a paragraph
 #[no_mangle]
-pub extern "C" fn dop_event_mouse_up(button: u8, x: c_float, y: c_float) -> DopEvent {
-    let btn = match button {
-        0 => MouseButtonId::Left,
-        1 => MouseButtonId::Right,
-        2 => MouseButtonId::Middle,
-        3 => MouseButtonId::X1,
-        4 => MouseButtonId::X2,
-        _ => MouseButtonId::Left,
-    };
-    DopEvent::mouse_up(btn, x as f64, y as f64)
+pub extern "C" fn dop_text_shaper_shape(
+    handle: *mut TextShaperHandle,
+    text: *const c_char,
+    max_width: c_float,
+    font_size: c_float,
+) -> ShapedTextFFI {
+    ffi_guard("dop_text_shaper_shape", || {
+        dop_text_shaper_shape_ex(handle, text, max_width, font_size, 0, 0, 0)
+    })
 }
 
-/// Create a mouse move event
+/// Shape a paragraph with a wrap mode and line limit
+///
+/// `wrap_mode` is 0 = word, 1 = char, 2 = break-word, 3 = no-wrap.
+/// A nonzero `hyphens` also breaks at soft hyphens. With `max_lines` > 0,
+/// text past that many lines is cut off and the last line ends in an ellipsis.
 #[no_mangle]
-pub extern "C" fn dop_event_mouse_move(x: c_float, y: c_float) -> DopEvent {
-    DopEvent::mouse_move(x as f64, y as f64)
+pub extern "C" fn dop_text_shaper_shape_ex(
+    handle: *mut TextShaperHandle,
+    text: *const c_char,
+    max_width: c_float,
+    font_size: c_float,
+    wrap_mode: c_int,
+    hyphens: c_int,
+    max_lines: c_int,
+) -> ShapedTextFFI {
+    ffi_guard("dop_text_shaper_shape_ex", || {
+        if handle.is_null() || text.is_null() {
+            return ShapedTextFFI {
+                width: 0.0,
+                height: 0.0,
+                line_count: 0,
+            };
+        }
+
+        let text_str = unsafe {
+            match CStr::from_ptr(text).to_str() {
+                Ok(s) => s,
+                Err(_) => {
+                    return ShapedTextFFI {
+                        width: 0.0,
+                        height: 0.0,
+                        line_count: 0,
+                    }
+                }
+            }
+        };
+
+        unsafe {
+            let wrap = WrapOptions {
+                mode: WrapMode::from_u8(wrap_mode as u8),
+                hyphens: hyphens != 0,
+                max_lines: max_lines.max(0) as u32,
+            };
+            let shaped = (*handle)
+                .shaper
+                .shape_paragraph_with(text_str, max_width, font_size, wrap);
+            let result = ShapedTextFFI {
+                width: shaped.width,
+                height: shaped.height,
+                line_count: shaped.line_count as c_int,
+            };
+            (*handle).shaped = Some(shaped);
+            result
+        }
+    })
 }
 
-/// Create a mouse scroll event
+/// A line of the last shaped paragraph for FFI
+#[repr(C)]
+pub struct ShapedLineFFI {
+    /// Byte range of the line in the paragraph text
+    pub start: c_int,
+    pub end: c_int,
+    /// Top of the line relative to the paragraph
+    pub y: c_float,
+    pub height: c_float,
+    pub width: c_float,
+    /// Index of the line's first glyph for `dop_text_shaper_get_glyph`
+    pub first_glyph: c_int,
+    pub glyph_count: c_int,
+    /// Mark drawn after the line: 0 = none, 1 = hyphen, 2 = ellipsis
+    pub suffix: c_int,
+}
+
+/// A glyph of the last shaped paragraph for FFI
+#[repr(C)]
+pub struct ShapedGlyphFFI {
+    /// Bytes of paragraph text the glyph was shaped from
+    pub byte_offset: c_int,
+    pub byte_len: c_int,
+    pub line: c_int,
+    /// Pen position relative to the line's left edge
+    pub x: c_float,
+    pub advance: c_float,
+    /// 1 if the glyph is part of a right-to-left run
+    pub rtl: c_int,
+}
+
+/// Get a line of the last shaped paragraph, returns 1 on success or 0 if out of range
 #[no_mangle]
-pub extern "C" fn dop_event_mouse_scroll(
-    x: c_float,
-    y: c_float,
-    scroll_x: c_float,
-    scroll_y: c_float,
-) -> DopEvent {
-    DopEvent::mouse_scroll(x as f64, y as f64, scroll_x as f64, scroll_y as f64)
+pub extern "C" fn dop_text_shaper_get_line(
+    handle: *const TextShaperHandle,
+    index: c_int,
+    out: *mut ShapedLineFFI,
+) -> c_int {
+    ffi_guard("dop_text_shaper_get_line", || {
+        if handle.is_null() || out.is_null() || index < 0 {
+            return 0;
+        }
+        unsafe {
+            let Some(line) = (*handle).shaped.as_ref().and_then(|s| s.lines.get(index as usize)) else {
+                return 0;
+            };
+            *out = ShapedLineFFI {
+                start: line.start as c_int,
+                end: line.end as c_int,
+                y: line.y,
+                height: line.height,
+                width: line.width,
+                first_glyph: line.first_cluster as c_int,
+                glyph_count: line.cluster_count as c_int,
+                suffix: line.suffix as c_int,
+            };
+            1
+        }
+    })
 }
 
-// ============================================================================
-// Utility functions
-// ============================================================================
+/// Number of glyphs in the last shaped paragraph
+#[no_mangle]
+pub extern "C" fn dop_text_shaper_glyph_count(handle: *const TextShaperHandle) -> c_int {
+    ffi_guard("dop_text_shaper_glyph_count", || {
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).shaped.as_ref().map_or(0, |s| s.clusters.len() as c_int) }
+    })
+}
 
-/// Get the size of DopEvent struct for Julia
+/// Get a glyph of the last shaped paragraph, returns 1 on success or 0 if out of range
 #[no_mangle]
-pub extern "C" fn dop_event_size() -> c_int {
-    std::mem::size_of::<DopEvent>() as c_int
+pub extern "C" fn dop_text_shaper_get_glyph(
+    handle: *const TextShaperHandle,
+    index: c_int,
+    out: *mut ShapedGlyphFFI,
+) -> c_int {
+    ffi_guard("dop_text_shaper_get_glyph", || {
+        if handle.is_null() || out.is_null() || index < 0 {
+            return 0;
+        }
+        unsafe {
+            let Some(glyph) = (*handle).shaped.as_ref().and_then(|s| s.clusters.get(index as usize)) else {
+                return 0;
+            };
+            *out = ShapedGlyphFFI {
+                byte_offset: glyph.byte_offset as c_int,
+                byte_len: glyph.byte_len as c_int,
+                line: glyph.line as c_int,
+                x: glyph.x,
+                advance: glyph.advance,
+                rtl: glyph.rtl as c_int,
+            };
+            1
+        }
+    })
 }
 
-/// Get the size of RenderCommand struct for Julia
+/// Base direction of the last shaped paragraph: 0 = left to right, 1 = right to left
 #[no_mangle]
-pub extern "C" fn dop_render_command_size() -> c_int {
-    std::mem::size_of::<RenderCommand>() as c_int
+pub extern "C" fn dop_text_shaper_get_direction(handle: *const TextShaperHandle) -> c_int {
+    ffi_guard("dop_text_shaper_get_direction", || {
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).shaped.as_ref().map_or(0, |s| s.direction as c_int) }
+    })
 }
 
-/// Get library version
+/// Byte offset of the caret nearest to (x, y) in the last shaped paragraph
+///
+/// Returns 0 if nothing has been shaped.
 #[no_mangle]
-pub extern "C" fn dop_version() -> *const c_char {
-    static VERSION: &[u8] = concat!(env!("CARGO_PKG_VERSION"), "\0").as_bytes();
-    VERSION.as_ptr() as *const c_char
+pub extern "C" fn dop_text_hit_test(handle: *const TextShaperHandle, x: c_float, y: c_float) -> c_int {
+    ffi_guard("dop_text_hit_test", || {
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe { (*handle).shaped.as_ref().map_or(0, |s| s.hit_test(x, y) as c_int) }
+    })
 }
 
-// ============================================================================
-// Text rendering FFI
-// ============================================================================
+/// Caret position for a byte offset in the last shaped paragraph
+///
+/// Writes the caret's x, top and height; an offset where a line wraps
+/// stays at the end of the earlier line. Returns 1 on success, 0 if
+/// nothing has been shaped.
+#[no_mangle]
+pub extern "C" fn dop_text_offset_to_position(
+    handle: *const TextShaperHandle,
+    byte_offset: c_int,
+    out_x: *mut c_float,
+    out_y: *mut c_float,
+    out_height: *mut c_float,
+) -> c_int {
+    ffi_guard("dop_text_offset_to_position", || {
+        if handle.is_null() || out_x.is_null() || out_y.is_null() || out_height.is_null() {
+            return 0;
+        }
+        unsafe {
+            let Some(shaped) = (*handle).shaped.as_ref() else {
+                return 0;
+            };
+            let (x, y, height) = shaped.offset_to_position(byte_offset.max(0) as usize);
+            *out_x = x;
+            *out_y = y;
+            *out_height = height;
+            1
+        }
+    })
+}
 
-/// Add a text render command (software)
+/// Highlight bytes `start_byte..end_byte` of the shaper's last paragraph on a renderer, with the
+/// paragraph's top-left corner at (x, y)
+///
+/// Adds one rectangle per line (more where the selection crosses a bidi
+/// boundary). Rectangles paint beneath all text, so the glyphs stay on
+/// top. Returns the number of rectangles added, 0 if nothing was shaped.
 #[cfg(feature = "software")]
 #[no_mangle]
-pub extern "C" fn dop_renderer_add_text(
-    handle: *mut RendererHandle,
-    text: *const c_char,
+pub extern "C" fn dop_renderer_add_selection(
+    handle: DopHandle,
+    shaper: *const TextShaperHandle,
     x: c_float,
     y: c_float,
-    font_size: c_float,
+    start_byte: c_int,
+    end_byte: c_int,
     r: c_float,
     g: c_float,
     b: c_float,
     a: c_float,
-    _font_id: c_int,
-) {
-    if handle.is_null() || text.is_null() {
-        return;
-    }
+    z_index: c_int,
+) -> c_int {
+    ffi_guard("dop_renderer_add_selection", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() || shaper.is_null() {
+            return 0;
+        }
+        unsafe {
+            let Some(shaped) = (*shaper).shaped.as_ref() else {
+                return 0;
+            };
+            let bytes = start_byte.max(0) as usize..end_byte.max(0) as usize;
+            (*handle).renderer.add_selection(shaped, x, y, bytes, [r, g, b, a], z_index) as c_int
+        }
+    })
+}
 
-    let text_str = unsafe {
-        match CStr::from_ptr(text).to_str() {
-            Ok(s) => s.to_string(),
-            Err(_) => return,
+/// Load font into shaper
+#[no_mangle]
+pub extern "C" fn dop_text_shaper_load_font(
+    handle: *mut TextShaperHandle,
+    path: *const c_char,
+) -> c_int {
+    ffi_guard("dop_text_shaper_load_font", || {
+        if handle.is_null() || path.is_null() {
+            return -1;
         }
-    };
 
-    unsafe {
-        (*handle).renderer.add_text(TextCommand {
-            text: text_str,
-            x,
-            y,
-            font_size,
-            color_r: r,
-            color_g: g,
-            color_b: b,
-            color_a: a,
-            font_id: _font_id as u32,
-        });
-    }
+        let path_str = unsafe {
+            match CStr::from_ptr(path).to_str() {
+                Ok(s) => s,
+                Err(_) => return -1,
+            }
+        };
+
+        unsafe {
+            match (*handle).shaper.font_manager_mut().load_font(path_str) {
+                Some(id) => id as c_int,
+                None => -1,
+            }
+        }
+    })
 }
 
-/// Add a text render command (fallback)
-#[cfg(not(feature = "software"))]
+/// Load font from memory (TrueType, OpenType or WOFF) into shaper
 #[no_mangle]
-pub extern "C" fn dop_renderer_add_text(
-    handle: *mut RendererHandle,
-    text: *const c_char,
-    x: c_float,
-    y: c_float,
-    font_size: c_float,
-    r: c_float,
-    g: c_float,
-    b: c_float,
-    a: c_float,
-    font_id: c_int,
-) {
-    if handle.is_null() || text.is_null() {
-        return;
-    }
+pub extern "C" fn dop_text_shaper_load_font_from_memory(
+    handle: *mut TextShaperHandle,
+    data: *const u8,
+    len: usize,
+) -> c_int {
+    ffi_guard("dop_text_shaper_load_font_from_memory", || {
+        if handle.is_null() || data.is_null() {
+            return -1;
+        }
 
-    let text_str = unsafe {
-        match CStr::from_ptr(text).to_str() {
-            Ok(s) => s.to_string(),
-            Err(_) => return,
+        unsafe {
+            let bytes = std::slice::from_raw_parts(data, len);
+            match (*handle).shaper.font_manager_mut().load_font_from_bytes(bytes) {
+                Some(id) => id as c_int,
+                None => -1,
+            }
         }
-    };
+    })
+}
+
+/// Check if shaper has default font
+#[no_mangle]
+pub extern "C" fn dop_text_shaper_has_font(handle: *const TextShaperHandle) -> c_int {
+    ffi_guard("dop_text_shaper_has_font", || {
+        if handle.is_null() {
+            return 0;
+        }
+        unsafe {
+            if (*handle).shaper.font_manager().get_font(0).is_some() {
+                1
+            } else {
+                0
+            }
+        }
+    })
+}
+
+// ============================================================================
+// Scroll area FFI
+// ============================================================================
+
+/// Live scroll areas; like renderers, each is bound to the thread that created it
+static SCROLL_AREAS: HandleRegistry<ScrollArea> = HandleRegistry::thread_bound("scroll area");
+
+/// Create a scroll area with a viewport of `width` x `height` over content of the given size
+///
+/// Kinetic scrolling starts disabled. Returns 0 on failure.
+#[no_mangle]
+pub extern "C" fn dop_scroll_area_create(
+    width: c_float,
+    height: c_float,
+    content_width: c_float,
+    content_height: c_float,
+) -> DopHandle {
+    ffi_guard("dop_scroll_area_create", || {
+        let area = ScrollArea::new(width as f64, height as f64, content_width as f64, content_height as f64);
+        SCROLL_AREAS.insert(Box::new(area))
+    })
+}
 
-    unsafe {
-        (*handle).text_commands.push(TextCommandFFI {
-            text: text_str,
-            x,
-            y,
-            font_size,
-            color_r: r,
-            color_g: g,
-            color_b: b,
-            color_a: a,
-            font_id: font_id as u32,
-        });
-    }
+/// Free a scroll area
+#[no_mangle]
+pub extern "C" fn dop_scroll_area_free(handle: DopHandle) {
+    ffi_guard("dop_scroll_area_free", || {
+        SCROLL_AREAS.remove(handle);
+    })
 }
 
-/// Measure text width and height (software)
-#[cfg(feature = "software")]
+/// Resize the viewport, keeping the offset in range
 #[no_mangle]
-pub extern "C" fn dop_renderer_measure_text(
-    handle: *const RendererHandle,
-    text: *const c_char,
-    font_size: c_float,
-    font_id: c_int,
-    out_width: *mut c_float,
-    out_height: *mut c_float,
-) {
-    if handle.is_null() || text.is_null() || out_width.is_null() || out_height.is_null() {
-        return;
-    }
+pub extern "C" fn dop_scroll_area_set_viewport_size(handle: DopHandle, width: c_float, height: c_float) {
+    ffi_guard("dop_scroll_area_set_viewport_size", || {
+        let area = SCROLL_AREAS.get(handle);
+        if area.is_null() {
+            return;
+        }
+        unsafe {
+            (*area).set_viewport_size(width as f64, height as f64);
+        }
+    })
+}
 
-    let text_str = unsafe {
-        match CStr::from_ptr(text).to_str() {
-            Ok(s) => s,
-            Err(_) => {
-                *out_width = 0.0;
-                *out_height = 0.0;
-                return;
-            }
+/// Resize the content, keeping the offset in range
+#[no_mangle]
+pub extern "C" fn dop_scroll_area_set_content_size(handle: DopHandle, width: c_float, height: c_float) {
+    ffi_guard("dop_scroll_area_set_content_size", || {
+        let area = SCROLL_AREAS.get(handle);
+        if area.is_null() {
+            return;
         }
-    };
+        unsafe {
+            (*area).set_content_size(width as f64, height as f64);
+        }
+    })
+}
 
-    unsafe {
-        let (w, h) =
-            (*handle)
-                .renderer
-                .font_manager()
-                .measure_text(text_str, font_size, font_id as u32);
-        *out_width = w;
-        *out_height = h;
-    }
+/// Enable or disable momentum after trackpad gestures and drags
+#[no_mangle]
+pub extern "C" fn dop_scroll_area_set_kinetic(handle: DopHandle, enabled: c_int) {
+    ffi_guard("dop_scroll_area_set_kinetic", || {
+        let area = SCROLL_AREAS.get(handle);
+        if area.is_null() {
+            return;
+        }
+        unsafe {
+            (*area).set_kinetic(enabled != 0);
+        }
+    })
 }
 
-/// Measure text width and height (fallback)
-#[cfg(not(feature = "software"))]
+/// Set the scrollbar thumb thickness, edge margin and minimum length in pixels
 #[no_mangle]
-pub extern "C" fn dop_renderer_measure_text(
-    handle: *const RendererHandle,
-    text: *const c_char,
-    font_size: c_float,
-    font_id: c_int,
-    out_width: *mut c_float,
-    out_height: *mut c_float,
+pub extern "C" fn dop_scroll_area_set_scrollbar_style(
+    handle: DopHandle,
+    thickness: c_float,
+    margin: c_float,
+    min_length: c_float,
 ) {
-    if handle.is_null() || text.is_null() || out_width.is_null() || out_height.is_null() {
-        return;
-    }
-
-    let text_str = unsafe {
-        match CStr::from_ptr(text).to_str() {
-            Ok(s) => s,
-            Err(_) => {
-                *out_width = 0.0;
-                *out_height = 0.0;
-                return;
-            }
+    ffi_guard("dop_scroll_area_set_scrollbar_style", || {
+        let area = SCROLL_AREAS.get(handle);
+        if area.is_null() {
+            return;
         }
-    };
+        unsafe {
+            (*area).set_scrollbar_style(ScrollbarStyle {
+                thickness: thickness.max(0.0),
+                margin: margin.max(0.0),
+                min_length: min_length.max(0.0),
+            });
+        }
+    })
+}
 
-    unsafe {
-        let (w, h) = (*handle)
-            .font_manager
-            .measure_text(text_str, font_size, font_id as u32);
-        *out_width = w;
-        *out_height = h;
-    }
+/// Scroll by a wheel delta, as carried by `MouseScroll` events
+///
+/// `precise` is nonzero for pixel deltas from trackpads; otherwise the
+/// deltas count lines. Returns 1 if the offset changed, 0 otherwise.
+#[no_mangle]
+pub extern "C" fn dop_scroll_area_scroll(handle: DopHandle, dx: c_double, dy: c_double, precise: c_int) -> c_int {
+    ffi_guard("dop_scroll_area_scroll", || {
+        let area = SCROLL_AREAS.get(handle);
+        if area.is_null() {
+            return 0;
+        }
+        unsafe { (*area).scroll(dx, dy, precise != 0, Instant::now()) as c_int }
+    })
 }
 
-/// Load a font from file, returns font ID or -1 on failure (software)
-#[cfg(feature = "software")]
+/// End a trackpad gesture, starting momentum when kinetic scrolling is enabled
 #[no_mangle]
-pub extern "C" fn dop_renderer_load_font(
-    handle: *mut RendererHandle,
-    path: *const c_char,
-) -> c_int {
-    if handle.is_null() || path.is_null() {
-        return -1;
-    }
+pub extern "C" fn dop_scroll_area_release(handle: DopHandle) {
+    ffi_guard("dop_scroll_area_release", || {
+        let area = SCROLL_AREAS.get(handle);
+        if area.is_null() {
+            return;
+        }
+        unsafe {
+            (*area).release(Instant::now());
+        }
+    })
+}
 
-    let path_str = unsafe {
-        match CStr::from_ptr(path).to_str() {
-            Ok(s) => s,
-            Err(_) => return -1,
+/// Start dragging the content with the pointer at (x, y)
+#[no_mangle]
+pub extern "C" fn dop_scroll_area_drag_begin(handle: DopHandle, x: c_double, y: c_double) {
+    ffi_guard("dop_scroll_area_drag_begin", || {
+        let area = SCROLL_AREAS.get(handle);
+        if area.is_null() {
+            return;
         }
-    };
+        unsafe {
+            (*area).drag_begin(x, y);
+        }
+    })
+}
 
-    unsafe {
-        match (*handle).renderer.font_manager_mut().load_font(path_str) {
-            Some(id) => id as c_int,
-            None => -1,
+/// Move the pointer of a drag in progress; the content follows it
+/// Returns 1 if the offset changed, 0 otherwise
+#[no_mangle]
+pub extern "C" fn dop_scroll_area_drag_move(handle: DopHandle, x: c_double, y: c_double) -> c_int {
+    ffi_guard("dop_scroll_area_drag_move", || {
+        let area = SCROLL_AREAS.get(handle);
+        if area.is_null() {
+            return 0;
         }
-    }
+        unsafe { (*area).drag_move(x, y, Instant::now()) as c_int }
+    })
 }
 
-/// Load a font from file, returns font ID or -1 on failure (fallback)
-#[cfg(not(feature = "software"))]
+/// End a drag, flinging the content when kinetic scrolling is enabled
 #[no_mangle]
-pub extern "C" fn dop_renderer_load_font(
-    handle: *mut RendererHandle,
-    path: *const c_char,
-) -> c_int {
-    if handle.is_null() || path.is_null() {
-        return -1;
-    }
+pub extern "C" fn dop_scroll_area_drag_end(handle: DopHandle) {
+    ffi_guard("dop_scroll_area_drag_end", || {
+        let area = SCROLL_AREAS.get(handle);
+        if area.is_null() {
+            return;
+        }
+        unsafe {
+            (*area).drag_end(Instant::now());
+        }
+    })
+}
 
-    let path_str = unsafe {
-        match CStr::from_ptr(path).to_str() {
-            Ok(s) => s,
-            Err(_) => return -1,
+/// Jump to an offset (clamped), stopping any momentum
+#[no_mangle]
+pub extern "C" fn dop_scroll_area_scroll_to(handle: DopHandle, x: c_float, y: c_float) {
+    ffi_guard("dop_scroll_area_scroll_to", || {
+        let area = SCROLL_AREAS.get(handle);
+        if area.is_null() {
+            return;
         }
-    };
+        unsafe {
+            (*area).scroll_to(x as f64, y as f64);
+        }
+    })
+}
 
-    unsafe {
-        match (*handle).font_manager.load_font(path_str) {
-            Some(id) => id as c_int,
-            None => -1,
+/// Advance momentum to the current time; call once per frame
+/// Returns 1 while momentum is still running, 0 once the area is at rest
+#[no_mangle]
+pub extern "C" fn dop_scroll_area_tick(handle: DopHandle) -> c_int {
+    ffi_guard("dop_scroll_area_tick", || {
+        let area = SCROLL_AREAS.get(handle);
+        if area.is_null() {
+            return 0;
         }
-    }
+        unsafe {
+            (*area).tick(Instant::now());
+            (*area).is_animating() as c_int
+        }
+    })
 }
 
-/// Check if a default font is available (software)
-#[cfg(feature = "software")]
+/// Get the current scroll offset
 #[no_mangle]
-pub extern "C" fn dop_renderer_has_default_font(handle: *const RendererHandle) -> c_int {
-    if handle.is_null() {
-        return 0;
-    }
-    unsafe {
-        if (*handle).renderer.font_manager().get_font(0).is_some() {
-            1
-        } else {
-            0
+pub extern "C" fn dop_scroll_area_get_offset(handle: DopHandle, out_x: *mut c_float, out_y: *mut c_float) {
+    ffi_guard("dop_scroll_area_get_offset", || {
+        let area = SCROLL_AREAS.get(handle) as *const ScrollArea;
+        if area.is_null() || out_x.is_null() || out_y.is_null() {
+            return;
         }
-    }
+        unsafe {
+            let (x, y) = (*area).offset();
+            *out_x = x as c_float;
+            *out_y = y as c_float;
+        }
+    })
 }
 
-/// Check if a default font is available (fallback)
-#[cfg(not(feature = "software"))]
+/// Add the scroll area's scrollbar thumbs to a renderer, with the viewport's top-left corner at (x, y)
+/// Returns the number of thumbs added (one per overflowing axis)
+#[cfg(feature = "software")]
 #[no_mangle]
-pub extern "C" fn dop_renderer_has_default_font(handle: *const RendererHandle) -> c_int {
-    if handle.is_null() {
-        return 0;
-    }
-    unsafe {
-        if (*handle).font_manager.get_font(0).is_some() {
-            1
-        } else {
-            0
+pub extern "C" fn dop_scroll_area_add_scrollbars(
+    handle: DopHandle,
+    renderer: DopHandle,
+    x: c_float,
+    y: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+    z_index: c_int,
+) -> c_int {
+    ffi_guard("dop_scroll_area_add_scrollbars", || {
+        let area = SCROLL_AREAS.get(handle) as *const ScrollArea;
+        let renderer = RENDERERS.get(renderer);
+        if area.is_null() || renderer.is_null() {
+            return 0;
         }
-    }
+        unsafe { (*renderer).renderer.add_scrollbars(&*area, x, y, [r, g, b, a], z_index) as c_int }
+    })
 }
 
 // ============================================================================
-// Text shaper FFI
+// Caret and focus ring FFI
 // ============================================================================
 
-/// Text shaper handle for paragraph layout
-pub struct TextShaperHandle {
-    shaper: TextShaper,
-}
-
-/// Create a text shaper
+/// Show a caret bar at (x, y) in logical pixels, blinking every `blink_ms` (0 = steady)
+///
+/// The caret stays across frames until `dop_renderer_clear_caret` and is
+/// drawn above everything. Setting it again unchanged keeps the blink phase;
+/// moving it shows it again at once.
+#[cfg(feature = "software")]
 #[no_mangle]
-pub extern "C" fn dop_text_shaper_create() -> *mut TextShaperHandle {
-    Box::into_raw(Box::new(TextShaperHandle {
-        shaper: TextShaper::new(),
-    }))
+pub extern "C" fn dop_renderer_set_caret(
+    handle: DopHandle,
+    x: c_float,
+    y: c_float,
+    height: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+    blink_ms: c_int,
+) {
+    ffi_guard("dop_renderer_set_caret", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
+        unsafe {
+            (*handle).renderer.set_caret(x, y, height, [r, g, b, a], blink_ms.max(0) as u32);
+        }
+    })
 }
 
-/// Free a text shaper
+/// Remove the caret
+#[cfg(feature = "software")]
 #[no_mangle]
-pub extern "C" fn dop_text_shaper_free(handle: *mut TextShaperHandle) {
-    if !handle.is_null() {
+pub extern "C" fn dop_renderer_clear_caret(handle: DopHandle) {
+    ffi_guard("dop_renderer_clear_caret", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
+        }
         unsafe {
-            drop(Box::from_raw(handle));
+            (*handle).renderer.clear_caret();
         }
-    }
-}
-
-/// Shaped text result for FFI
-#[repr(C)]
-pub struct ShapedTextFFI {
-    pub width: c_float,
-    pub height: c_float,
-    pub line_count: c_int,
+    })
 }
 
-/// Shape a paragraph
+/// Milliseconds until the caret next appears or disappears in the renderer's framebuffer
+///
+/// Hosts that render on demand render again after this long. Returns -1 if
+/// there is no blinking caret or it is attached to a window.
+#[cfg(feature = "software")]
 #[no_mangle]
-pub extern "C" fn dop_text_shaper_shape(
-    handle: *mut TextShaperHandle,
-    text: *const c_char,
-    max_width: c_float,
-    font_size: c_float,
-) -> ShapedTextFFI {
-    if handle.is_null() || text.is_null() {
-        return ShapedTextFFI {
-            width: 0.0,
-            height: 0.0,
-            line_count: 0,
-        };
-    }
-
-    let text_str = unsafe {
-        match CStr::from_ptr(text).to_str() {
-            Ok(s) => s,
-            Err(_) => {
-                return ShapedTextFFI {
-                    width: 0.0,
-                    height: 0.0,
-                    line_count: 0,
-                }
-            }
+pub extern "C" fn dop_renderer_caret_next_toggle_ms(handle: DopHandle) -> c_int {
+    ffi_guard("dop_renderer_caret_next_toggle_ms", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() {
+            return -1;
         }
-    };
-
-    unsafe {
-        let shaped = (*handle)
-            .shaper
-            .shape_paragraph(text_str, max_width, font_size);
-        ShapedTextFFI {
-            width: shaped.width,
-            height: shaped.height,
-            line_count: shaped.line_count as c_int,
+        let now = Instant::now();
+        unsafe {
+            match (*handle).renderer.next_caret_toggle(now) {
+                Some(at) => at.saturating_duration_since(now).as_millis().min(c_int::MAX as u128) as c_int,
+                None => -1,
+            }
         }
-    }
+    })
 }
 
-/// Load font into shaper
+/// Let a threaded window draw the renderer's caret, or take it back with window 0
+///
+/// The window thread blends the caret over each presented framebuffer and
+/// presents again on every blink, so the host does not render for blinks.
+/// While attached, `dop_renderer_render` leaves the caret out. Returns 1 if
+/// attached to the window, 0 if detached.
+#[cfg(feature = "software")]
 #[no_mangle]
-pub extern "C" fn dop_text_shaper_load_font(
-    handle: *mut TextShaperHandle,
-    path: *const c_char,
-) -> c_int {
-    if handle.is_null() || path.is_null() {
-        return -1;
-    }
-
-    let path_str = unsafe {
-        match CStr::from_ptr(path).to_str() {
-            Ok(s) => s,
-            Err(_) => return -1,
+pub extern "C" fn dop_renderer_attach_caret_window(handle: DopHandle, window: DopHandle) -> c_int {
+    ffi_guard("dop_renderer_attach_caret_window", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return 0;
         }
-    };
-
-    unsafe {
-        match (*handle).shaper.font_manager_mut().load_font(path_str) {
-            Some(id) => id as c_int,
-            None => -1,
+        let window = THREADED_WINDOWS.get(window) as *const ThreadedWindowHandle;
+        unsafe {
+            let caret = (!window.is_null()).then(|| (*window).caret().clone());
+            let attached = caret.is_some();
+            (*handle).renderer.attach_caret_window(caret);
+            attached as c_int
         }
-    }
+    })
 }
 
-/// Check if shaper has default font
+/// Draw a dashed rounded focus ring just outside the box (x, y, width, height)
+///
+/// `radius` is the box's corner radius. Dashes are `dash` long with `gap`
+/// between them; a gap of 0 draws a solid ring.
+#[cfg(feature = "software")]
 #[no_mangle]
-pub extern "C" fn dop_text_shaper_has_font(handle: *const TextShaperHandle) -> c_int {
-    if handle.is_null() {
-        return 0;
-    }
-    unsafe {
-        if (*handle).shaper.font_manager().get_font(0).is_some() {
-            1
-        } else {
-            0
+pub extern "C" fn dop_renderer_add_focus_ring(
+    handle: DopHandle,
+    x: c_float,
+    y: c_float,
+    width: c_float,
+    height: c_float,
+    radius: c_float,
+    line_width: c_float,
+    dash: c_float,
+    gap: c_float,
+    r: c_float,
+    g: c_float,
+    b: c_float,
+    a: c_float,
+) {
+    ffi_guard("dop_renderer_add_focus_ring", || {
+        let handle = RENDERERS.get(handle);
+        if handle.is_null() {
+            return;
         }
-    }
+        let ring = FocusRing {
+            radius,
+            line_width,
+            dash,
+            gap,
+            ..FocusRing::new(x, y, width, height, [r, g, b, a])
+        };
+        unsafe {
+            (*handle).renderer.add_focus_ring(&ring);
+        }
+    })
 }
 
 // ============================================================================
-// PNG export FFI
+// Export FFI
 // ============================================================================
 
 /// Export framebuffer to PNG file (software)
 #[cfg(feature = "software")]
 #[no_mangle]
 pub extern "C" fn dop_renderer_export_png(
-    handle: *const RendererHandle,
+    handle: DopHandle,
     path: *const c_char,
 ) -> c_int {
-    if handle.is_null() || path.is_null() {
-        return 0;
-    }
-
-    let path_str = unsafe {
-        match CStr::from_ptr(path).to_str() {
-            Ok(s) => s,
-            Err(_) => return 0,
+    ffi_guard("dop_renderer_export_png", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() || path.is_null() {
+            return 0;
         }
-    };
 
-    unsafe {
-        match (*handle).renderer.export_png(path_str) {
-            Ok(_) => 1,
-            Err(_) => 0,
+        let path_str = unsafe {
+            match CStr::from_ptr(path).to_str() {
+                Ok(s) => s,
+                Err(_) => return 0,
+            }
+        };
+
+        unsafe {
+            match (*handle).renderer.export_png(path_str) {
+                Ok(_) => 1,
+                Err(_) => 0,
+            }
         }
-    }
+    })
 }
 
 /// Export framebuffer to PNG file (fallback)
 #[cfg(not(feature = "software"))]
 #[no_mangle]
 pub extern "C" fn dop_renderer_export_png(
-    handle: *const RendererHandle,
+    handle: DopHandle,
     path: *const c_char,
 ) -> c_int {
-    if handle.is_null() || path.is_null() {
-        return 0;
-    }
-
-    let path_str = unsafe {
-        match CStr::from_ptr(path).to_str() {
-            Ok(s) => s,
-            Err(_) => return 0,
+    ffi_guard("dop_renderer_export_png", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() || path.is_null() {
+            return 0;
         }
-    };
 
-    unsafe {
-        let h = &*handle;
-
-        // Use the png crate to write the file
-        let file = match std::fs::File::create(path_str) {
-            Ok(f) => f,
-            Err(_) => return 0,
+        let path_str = unsafe {
+            match CStr::from_ptr(path).to_str() {
+                Ok(s) => s,
+                Err(_) => return 0,
+            }
         };
 
-        let w = std::io::BufWriter::new(file);
-        let mut encoder = png::Encoder::new(w, h.width, h.height);
-        encoder.set_color(png::ColorType::Rgba);
-        encoder.set_depth(png::BitDepth::Eight);
+        unsafe {
+            let h = &*handle;
+
+            // Use the png crate to write the file
+            let file = match std::fs::File::create(path_str) {
+                Ok(f) => f,
+                Err(_) => return 0,
+            };
+
+            let w = std::io::BufWriter::new(file);
+            let mut encoder = png::Encoder::new(w, h.width, h.height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+
+            let mut writer = match encoder.write_header() {
+                Ok(w) => w,
+                Err(_) => return 0,
+            };
+
+            if writer.write_image_data(&h.framebuffer).is_err() {
+                return 0;
+            }
 
-        let mut writer = match encoder.write_header() {
-            Ok(w) => w,
-            Err(_) => return 0,
-        };
+            1
+        }
+    })
+}
 
-        if writer.write_image_data(&h.framebuffer).is_err() {
+/// Encode the framebuffer in memory
+/// format: 0 = PNG, 1 = BMP, 2 = QOI, 3 = JPEG (needs the images feature)
+/// On success writes the buffer to `out_ptr` and its length to `out_len` and
+/// returns 1; free the buffer with `dop_buffer_free`. Returns 0 on failure.
+#[no_mangle]
+pub extern "C" fn dop_renderer_export_to_buffer(
+    handle: DopHandle,
+    format: c_int,
+    out_ptr: *mut *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    ffi_guard("dop_renderer_export_to_buffer", || {
+        let handle = RENDERERS.get(handle) as *const RendererHandle;
+        if handle.is_null() || out_ptr.is_null() || out_len.is_null() {
             return 0;
         }
+        let Some(format) = u8::try_from(format).ok().and_then(ExportFormat::from_u8) else {
+            return 0;
+        };
 
-        1
-    }
+        unsafe {
+            #[cfg(feature = "software")]
+            let encoded = (*handle).renderer.export_to_buffer(format);
+            #[cfg(not(feature = "software"))]
+            let encoded = {
+                let h = &*handle;
+                crate::encode::encode_rgba(&h.framebuffer, h.width, h.height, format)
+            };
+            match encoded {
+                Ok(bytes) => {
+                    let bytes = bytes.into_boxed_slice();
+                    *out_len = bytes.len();
+                    *out_ptr = Box::into_raw(bytes) as *mut u8;
+                    1
+                }
+                Err(e) => {
+                    log::warn!("Failed to encode framebuffer: {}", e);
+                    0
+                }
+            }
+        }
+    })
+}
+
+/// Free a buffer returned by `dop_renderer_export_to_buffer`
+#[no_mangle]
+pub extern "C" fn dop_buffer_free(ptr: *mut u8, len: usize) {
+    ffi_guard("dop_buffer_free", || {
+        if !ptr.is_null() {
+            unsafe {
+                drop(Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, len)));
+            }
+        }
+    })
 }
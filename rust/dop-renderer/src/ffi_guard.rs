@@ -0,0 +1,141 @@
+//! Panic boundary for the C API
+//!
+//! A panic unwinding out of an `extern "C"` function aborts the host process.
+//! Every exported function runs its body through `ffi_guard`, which catches
+//! the panic, records its message as the thread's last error and returns a
+//! neutral value instead (0, null, or an empty struct). Hosts read the message
+//! with `dop_last_error`.
+//!
+//! Builds with `panic = "abort"` (the `release-small` profile) cannot catch
+//! panics; they still abort.
+
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Mutex, MutexGuard};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Record `message` as the calling thread's last error
+pub fn set_last_error(message: impl Into<String>) {
+    // Interior NULs would truncate the C string, so they are replaced
+    let message = message.into().replace('\0', " ");
+    let message = CString::new(message).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+/// Clear the calling thread's last error
+pub fn clear_last_error() {
+    LAST_ERROR.with(|e| *e.borrow_mut() = None);
+}
+
+/// Pointer to the calling thread's last error message, or null if none
+///
+/// The pointer stays valid until the next error is recorded or cleared on this thread.
+pub fn last_error_ptr() -> *const std::ffi::c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(std::ptr::null(), |s| s.as_ptr()))
+}
+
+/// Value an FFI function returns when its body panicked
+pub trait FfiDefault {
+    fn ffi_default() -> Self;
+}
+
+macro_rules! ffi_default_zero {
+    ($($ty:ty),*) => {
+        $(impl FfiDefault for $ty {
+            fn ffi_default() -> Self {
+                Self::default()
+            }
+        })*
+    };
+}
+
+ffi_default_zero!((), bool, u8, i8, u16, i16, u32, i32, u64, i64, usize, isize, f32, f64);
+
+impl<T> FfiDefault for *const T {
+    fn ffi_default() -> Self {
+        std::ptr::null()
+    }
+}
+
+impl<T> FfiDefault for *mut T {
+    fn ffi_default() -> Self {
+        std::ptr::null_mut()
+    }
+}
+
+impl FfiDefault for crate::window::DopEvent {
+    fn ffi_default() -> Self {
+        Self::default()
+    }
+}
+
+/// Run an FFI function body, turning a panic into the last error and a neutral return value
+///
+/// Handles looked up during the body stay valid until it returns (see `handles::pin_scope`).
+pub fn ffi_guard<R: FfiDefault>(name: &str, body: impl FnOnce() -> R) -> R {
+    match panic::catch_unwind(AssertUnwindSafe(|| crate::handles::pin_scope(body))) {
+        Ok(value) => value,
+        Err(payload) => {
+            let reason = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            log::error!("{} panicked: {}", name, reason);
+            set_last_error(format!("{} panicked: {}", name, reason));
+            R::ffi_default()
+        }
+    }
+}
+
+/// Locking that survives a panic in another holder of the lock
+pub trait LockExt<T> {
+    /// Lock the mutex, taking over the data and clearing the poison flag if a
+    /// previous holder panicked
+    fn lock_or_recover(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> LockExt<T> for Mutex<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(|poisoned| {
+            log::warn!("recovering a mutex poisoned by a panic");
+            self.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "software")]
+    #[test]
+    fn test_ffi_guard_recovers_from_panics() {
+        use crate::ffi::{dop_clear_last_error, dop_last_error, dop_renderer_create_headless};
+        use std::ffi::CStr;
+
+        // An impossible pixmap size panics inside the renderer; the caller gets no handle and a message
+        dop_clear_last_error();
+        assert!(dop_last_error().is_null());
+        assert_eq!(dop_renderer_create_headless(-1, -1), 0);
+        let message = unsafe { CStr::from_ptr(dop_last_error()) }.to_str().unwrap();
+        assert!(message.starts_with("dop_renderer_create_headless panicked"), "{}", message);
+        dop_clear_last_error();
+        assert!(dop_last_error().is_null());
+
+        let lock = Mutex::new(1);
+        let _ = std::panic::catch_unwind(|| {
+            let _guard = lock.lock().unwrap();
+            panic!("poison");
+        });
+        assert!(lock.is_poisoned());
+        *lock.lock_or_recover() += 1;
+        assert!(!lock.is_poisoned());
+        assert_eq!(*lock.lock().unwrap(), 2);
+    }
+}
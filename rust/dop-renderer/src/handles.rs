@@ -0,0 +1,356 @@
+//! Generational handles for FFI objects
+//!
+//! Objects handed to the host are kept in a `HandleRegistry` and addressed by
+//! an opaque `DopHandle` instead of a raw pointer. A handle packs a slot index
+//! with the slot's generation, and freeing an object bumps the generation, so
+//! a handle used after `free` (or a made-up value) is rejected instead of
+//! dereferencing freed memory.
+//!
+//! A registry can also bind each object to the thread that created it. Calls
+//! from any other thread are then rejected until that thread adopts the
+//! handle, which keeps objects with unsynchronized interior state (such as
+//! the `RefCell` caches in `FontManager`) on one thread at a time.
+//!
+//! Inside an FFI call (`pin_scope`, entered by `ffi_guard`), `get` pins the
+//! object until the call returns. Freeing a pinned object from another thread
+//! only retires its handle; the object is dropped once the last call using it
+//! returns, so a pointer from `get` never dangles mid-call.
+
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+
+use crate::ffi_guard::{set_last_error, LockExt};
+
+/// Opaque FFI handle: generation in the high 32 bits, slot index + 1 in the low 32 (0 = none)
+pub type DopHandle = u64;
+
+struct Slot {
+    generation: u32,
+    /// Address of the boxed object, 0 when the slot is free
+    address: usize,
+    /// Thread allowed to use the object, for thread-bound registries
+    owner: Option<ThreadId>,
+    /// Number of running FFI calls using the object
+    pins: u32,
+    /// The handle was removed while pinned; the last unpin drops the object
+    retired: bool,
+}
+
+thread_local! {
+    /// Slots pinned by this thread's running FFI calls, innermost last
+    static PINNED: RefCell<Vec<(&'static dyn PinRelease, usize)>> = const { RefCell::new(Vec::new()) };
+    /// Number of `pin_scope`s this thread is inside
+    static SCOPE_DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Registry side of releasing a pin
+trait PinRelease {
+    fn unpin(&self, index: usize);
+}
+
+/// Releases the pins taken inside a scope, also when it unwinds
+struct ScopeGuard {
+    mark: usize,
+}
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        SCOPE_DEPTH.with(|depth| depth.set(depth.get() - 1));
+        let pins = PINNED.with(|pinned| pinned.borrow_mut().split_off(self.mark));
+        for (registry, index) in pins {
+            registry.unpin(index);
+        }
+    }
+}
+
+/// Run `body` as one FFI call: objects returned by `get` inside it stay alive until it returns
+pub fn pin_scope<R>(body: impl FnOnce() -> R) -> R {
+    SCOPE_DEPTH.with(|depth| depth.set(depth.get() + 1));
+    let _guard = ScopeGuard {
+        mark: PINNED.with(|pinned| pinned.borrow().len()),
+    };
+    body()
+}
+
+/// Owns boxed objects and maps generational handles to them
+pub struct HandleRegistry<T> {
+    name: &'static str,
+    thread_bound: bool,
+    slots: Mutex<Vec<Slot>>,
+    free: Mutex<Vec<u32>>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Send> HandleRegistry<T> {
+    /// Create an empty registry; `name` is used in error messages
+    pub const fn new(name: &'static str) -> Self {
+        Self::with_binding(name, false)
+    }
+
+    /// Create an empty registry whose objects may only be used from the thread that created them
+    pub const fn thread_bound(name: &'static str) -> Self {
+        Self::with_binding(name, true)
+    }
+
+    const fn with_binding(name: &'static str, thread_bound: bool) -> Self {
+        Self {
+            name,
+            thread_bound,
+            slots: Mutex::new(Vec::new()),
+            free: Mutex::new(Vec::new()),
+            _marker: PhantomData,
+        }
+    }
+
+    fn pack(index: usize, generation: u32) -> DopHandle {
+        ((generation as u64) << 32) | (index as u64 + 1)
+    }
+
+    fn unpack(handle: DopHandle) -> Option<(usize, u32)> {
+        let index = (handle & 0xFFFF_FFFF) as usize;
+        (index > 0).then(|| (index - 1, (handle >> 32) as u32))
+    }
+
+    /// Take ownership of `value` and return its handle
+    pub fn insert(&self, value: Box<T>) -> DopHandle {
+        let address = Box::into_raw(value) as usize;
+        let owner = self.thread_bound.then(|| thread::current().id());
+        let mut slots = self.slots.lock_or_recover();
+        match self.free.lock_or_recover().pop() {
+            Some(index) => {
+                let slot = &mut slots[index as usize];
+                slot.address = address;
+                slot.owner = owner;
+                slot.retired = false;
+                Self::pack(index as usize, slot.generation)
+            }
+            None => {
+                slots.push(Slot {
+                    generation: 1,
+                    address,
+                    owner,
+                    pins: 0,
+                    retired: false,
+                });
+                Self::pack(slots.len() - 1, 1)
+            }
+        }
+    }
+
+    /// Get the object behind `handle`, or null (recording the last error) if it is stale or
+    /// invalid, or belongs to another thread
+    ///
+    /// Inside a `pin_scope` the pointer is valid until the scope ends, even if
+    /// another thread removes the handle meanwhile. Outside one it is valid
+    /// until the handle is removed.
+    pub fn get(&'static self, handle: DopHandle) -> *mut T {
+        let mut slots = self.slots.lock_or_recover();
+        let slot = Self::unpack(handle).and_then(|(index, generation)| {
+            slots.get_mut(index).filter(|s| s.generation == generation).map(|s| (index, s))
+        });
+        match slot {
+            Some((index, slot)) if slot.address != 0 => {
+                if slot.owner.is_some_and(|owner| owner != thread::current().id()) {
+                    set_last_error(format!(
+                        "{} handle {:#x} is bound to another thread; adopt it before use",
+                        self.name, handle
+                    ));
+                    return std::ptr::null_mut();
+                }
+                if SCOPE_DEPTH.with(Cell::get) > 0 {
+                    slot.pins += 1;
+                    PINNED.with(|pinned| pinned.borrow_mut().push((self, index)));
+                }
+                slot.address as *mut T
+            }
+            _ => {
+                self.reject(handle);
+                std::ptr::null_mut()
+            }
+        }
+    }
+
+    /// Bind the object behind `handle` to the calling thread
+    ///
+    /// The caller must make sure the previous thread no longer uses the handle.
+    /// Returns false if the handle is stale or invalid.
+    pub fn adopt(&self, handle: DopHandle) -> bool {
+        let mut slots = self.slots.lock_or_recover();
+        let slot = Self::unpack(handle).and_then(|(index, generation)| {
+            slots.get_mut(index).filter(|s| s.generation == generation && s.address != 0)
+        });
+        match slot {
+            Some(slot) => {
+                if self.thread_bound {
+                    slot.owner = Some(thread::current().id());
+                }
+                true
+            }
+            None => {
+                self.reject(handle);
+                false
+            }
+        }
+    }
+
+    fn reject(&self, handle: DopHandle) {
+        // 0 is the host's "no handle" and is not worth an error
+        if handle != 0 {
+            set_last_error(format!("invalid or freed {} handle {:#x}", self.name, handle));
+        }
+    }
+
+    /// Drop the object behind `handle`; later uses of the handle are rejected
+    ///
+    /// Removal is allowed from any thread, so hosts can free handles from
+    /// finalizers; the object must be `Send` for that to be sound. An object
+    /// pinned by a running call is dropped when the last such call returns.
+    /// Returns false if the handle is stale or invalid.
+    pub fn remove(&self, handle: DopHandle) -> bool {
+        let mut slots = self.slots.lock_or_recover();
+        let live = Self::unpack(handle).filter(|&(index, generation)| {
+            slots.get(index).is_some_and(|s| s.generation == generation && s.address != 0 && !s.retired)
+        });
+        let Some((index, _)) = live else {
+            self.reject(handle);
+            return false;
+        };
+        let slot = &mut slots[index];
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.owner = None;
+        if slot.pins > 0 {
+            slot.retired = true;
+            return true;
+        }
+        let address = self.release(slot, index);
+        drop(slots);
+        // SAFETY: the address came from Box::into_raw in insert and is released only once
+        drop(unsafe { Box::from_raw(address as *mut T) });
+        true
+    }
+
+    /// Empty a slot and put it on the free list, returning the object's address
+    fn release(&self, slot: &mut Slot, index: usize) -> usize {
+        slot.retired = false;
+        self.free.lock_or_recover().push(index as u32);
+        std::mem::replace(&mut slot.address, 0)
+    }
+}
+
+impl<T: Send> PinRelease for HandleRegistry<T> {
+    fn unpin(&self, index: usize) {
+        let mut slots = self.slots.lock_or_recover();
+        let Some(slot) = slots.get_mut(index) else {
+            return;
+        };
+        slot.pins -= 1;
+        if slot.pins > 0 || !slot.retired {
+            return;
+        }
+        let address = self.release(slot, index);
+        drop(slots);
+        // SAFETY: the address came from Box::into_raw in insert; the handle was already removed
+        drop(unsafe { Box::from_raw(address as *mut T) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static DROPPED: AtomicBool = AtomicBool::new(false);
+
+    struct Tracked(u32);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            DROPPED.store(true, Ordering::SeqCst);
+        }
+    }
+
+    static TRACKED: HandleRegistry<Tracked> = HandleRegistry::new("tracked");
+
+    #[test]
+    fn test_handle_registry_defers_free_while_pinned() {
+        let handle = TRACKED.insert(Box::new(Tracked(7)));
+        pin_scope(|| {
+            let tracked = TRACKED.get(handle);
+            assert!(!tracked.is_null());
+            // Freed from another thread mid-call: the handle is retired, the object kept
+            assert!(thread::spawn(move || TRACKED.remove(handle)).join().unwrap());
+            assert!(TRACKED.get(handle).is_null());
+            assert!(!TRACKED.remove(handle));
+            assert!(!DROPPED.load(Ordering::SeqCst));
+            assert_eq!(unsafe { (*tracked).0 }, 7);
+        });
+        assert!(DROPPED.load(Ordering::SeqCst));
+
+        // The slot is reused under a new generation once released
+        let reused = TRACKED.insert(Box::new(Tracked(8)));
+        assert_eq!(reused & 0xFFFF_FFFF, handle & 0xFFFF_FFFF);
+        assert_ne!(reused, handle);
+        assert!(TRACKED.remove(reused));
+    }
+
+    #[cfg(feature = "software")]
+    #[test]
+    fn test_stale_handle_rejected() {
+        use crate::ffi::{
+            dop_clear_last_error, dop_last_error, dop_renderer_create_headless, dop_renderer_free,
+            dop_renderer_get_framebuffer_size,
+        };
+
+        let handle = dop_renderer_create_headless(8, 4);
+        assert_ne!(handle, 0);
+        assert_eq!(dop_renderer_get_framebuffer_size(handle), 8 * 4 * 4);
+        dop_renderer_free(handle);
+
+        // The freed handle is rejected even after its slot is reused
+        let reused = dop_renderer_create_headless(2, 2);
+        assert_ne!(reused, handle);
+        dop_clear_last_error();
+        assert_eq!(dop_renderer_get_framebuffer_size(handle), 0);
+        assert!(!dop_last_error().is_null());
+        assert_eq!(dop_renderer_get_framebuffer_size(reused), 2 * 2 * 4);
+
+        // Double free and made-up handles are ignored
+        dop_renderer_free(handle);
+        assert_eq!(dop_renderer_get_framebuffer_size(0xDEAD_BEEF), 0);
+        dop_renderer_free(reused);
+        dop_clear_last_error();
+    }
+
+    #[cfg(feature = "software")]
+    #[test]
+    fn test_handle_bound_to_thread() {
+        use crate::ffi::{
+            dop_clear_last_error, dop_last_error, dop_renderer_adopt, dop_renderer_create_headless,
+            dop_renderer_free, dop_renderer_get_framebuffer_size,
+        };
+
+        let handle = dop_renderer_create_headless(4, 4);
+        assert_eq!(dop_renderer_get_framebuffer_size(handle), 64);
+
+        // Another thread is refused until it adopts the renderer, which then refuses this one
+        std::thread::spawn(move || {
+            dop_clear_last_error();
+            assert_eq!(dop_renderer_get_framebuffer_size(handle), 0);
+            assert!(!dop_last_error().is_null());
+            assert_eq!(dop_renderer_adopt(handle), 1);
+            assert_eq!(dop_renderer_get_framebuffer_size(handle), 64);
+        })
+        .join()
+        .unwrap();
+        assert_eq!(dop_renderer_get_framebuffer_size(handle), 0);
+        assert_eq!(dop_renderer_adopt(handle), 1);
+        assert_eq!(dop_renderer_get_framebuffer_size(handle), 64);
+
+        // Freeing works from any thread, and a freed handle cannot be adopted
+        std::thread::spawn(move || dop_renderer_free(handle)).join().unwrap();
+        assert_eq!(dop_renderer_adopt(handle), 0);
+        dop_clear_last_error();
+    }
+}
@@ -0,0 +1,622 @@
+//! Image decoding and caching
+//!
+//! Decodes PNG, JPEG, GIF and WebP data into RGBA bitmaps and keeps them in
+//! a keyed cache with a memory budget. Cached images are addressed by an
+//! `ImageId`, which is used both as the image handle for the software blit
+//! path and as the `texture_id` of GPU render commands.
+//!
+//! Animated GIF, APNG and WebP images are decoded lazily: the frame count is
+//! read from the container up front, and each frame is decoded the first
+//! time playback or `ImageCache::frame` reaches it, then kept with its delay.
+//! `ImageCache::advance` steps playback so `get` returns the frame that
+//! should currently be shown.
+//!
+//! Pixels are stored as premultiplied RGBA8 so the software renderer can blit
+//! them without conversion; GPU uploads should use a premultiplied-alpha blend.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::Arc;
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, Frames, ImageFormat};
+
+use crate::color::{ColorManager, ColorSpace};
+
+/// Handle to a cached image (0 = no image)
+pub type ImageId = u32;
+
+/// Default cache budget (64 MiB of decoded pixels)
+pub const DEFAULT_IMAGE_BUDGET: usize = 64 * 1024 * 1024;
+
+/// A decoded image
+#[derive(Debug, Clone)]
+pub struct DecodedImage {
+    pub width: u32,
+    pub height: u32,
+    /// Premultiplied RGBA8 pixels, row-major
+    pub pixels: Arc<[u8]>,
+}
+
+impl DecodedImage {
+    /// Decode an encoded image (format is detected from the data)
+    pub fn decode(data: &[u8]) -> Result<Self, image::ImageError> {
+        Self::decode_managed(data, &ColorManager::new())
+    }
+
+    /// Decode an encoded sRGB image and convert it into the output space of `color`
+    pub fn decode_managed(data: &[u8], color: &ColorManager) -> Result<Self, image::ImageError> {
+        Ok(Self::from_rgba(image::load_from_memory(data)?.to_rgba8(), color))
+    }
+
+    /// Build an image from a straight-alpha sRGB RGBA buffer
+    fn from_rgba(rgba: image::RgbaImage, color: &ColorManager) -> Self {
+        let (width, height) = rgba.dimensions();
+        let mut pixels = rgba.into_raw();
+        color.convert_pixels(&mut pixels, ColorSpace::Srgb);
+        crate::color::premultiply(&mut pixels);
+        Self {
+            width,
+            height,
+            pixels: pixels.into(),
+        }
+    }
+
+    /// Size of the decoded pixels in bytes
+    pub fn byte_size(&self) -> usize {
+        self.pixels.len()
+    }
+}
+
+/// A single frame of an animated image
+#[derive(Debug, Clone)]
+pub struct ImageFrame {
+    pub image: DecodedImage,
+    /// How long the frame is shown, in milliseconds
+    pub delay_ms: u32,
+}
+
+/// Decode every frame of an image
+///
+/// Animated GIF, APNG and WebP data yields all composited frames; anything
+/// else yields a single frame with zero delay.
+pub fn decode_frames(data: &[u8]) -> Result<Vec<ImageFrame>, image::ImageError> {
+    decode_frames_managed(data, &ColorManager::new())
+}
+
+/// Decode every frame of an sRGB image and convert it into the output space of `color`
+pub fn decode_frames_managed(data: &[u8], color: &ColorManager) -> Result<Vec<ImageFrame>, image::ImageError> {
+    let frames = match image::guess_format(data)? {
+        ImageFormat::Gif => GifDecoder::new(Cursor::new(data))?.into_frames().collect_frames()?,
+        ImageFormat::Png => {
+            let decoder = PngDecoder::new(Cursor::new(data))?;
+            if decoder.is_apng()? {
+                decoder.apng()?.into_frames().collect_frames()?
+            } else {
+                Vec::new()
+            }
+        }
+        ImageFormat::WebP => {
+            let decoder = WebPDecoder::new(Cursor::new(data))?;
+            if decoder.has_animation() {
+                decoder.into_frames().collect_frames()?
+            } else {
+                Vec::new()
+            }
+        }
+        _ => Vec::new(),
+    };
+
+    if frames.is_empty() {
+        return Ok(vec![ImageFrame {
+            image: DecodedImage::decode_managed(data, color)?,
+            delay_ms: 0,
+        }]);
+    }
+
+    Ok(frames.into_iter().map(|frame| image_frame(frame, color)).collect())
+}
+
+fn image_frame(frame: image::Frame, color: &ColorManager) -> ImageFrame {
+    let (numer, denom) = frame.delay().numer_denom_ms();
+    ImageFrame {
+        delay_ms: numer.checked_div(denom).unwrap_or(0),
+        image: DecodedImage::from_rgba(frame.into_buffer(), color),
+    }
+}
+
+/// Number of frames in an animated GIF, APNG or WebP (1 for anything else)
+///
+/// Only the container structure is read; no pixel data is decoded.
+pub fn frame_count(data: &[u8]) -> usize {
+    match image::guess_format(data) {
+        Ok(ImageFormat::Gif) => gif_frame_count(data),
+        Ok(ImageFormat::Png) => png::Decoder::new(Cursor::new(data))
+            .read_info()
+            .ok()
+            .and_then(|reader| reader.info().animation_control.map(|a| a.num_frames as usize))
+            .unwrap_or(1),
+        Ok(ImageFormat::WebP) => webp_frame_count(data),
+        _ => 1,
+    }
+    .max(1)
+}
+
+/// Count the image descriptors of a GIF by walking its blocks
+fn gif_frame_count(data: &[u8]) -> usize {
+    // Size of a color table from the packed flags of a descriptor
+    let color_table = |flags: u8| if flags & 0x80 != 0 { 3 << ((flags & 7) + 1) } else { 0 };
+    // Skip a run of data sub-blocks, returning the position after its terminator
+    let skip_sub_blocks = |mut pos: usize| -> Option<usize> {
+        loop {
+            let len = *data.get(pos)? as usize;
+            pos += 1 + len;
+            if len == 0 {
+                return Some(pos);
+            }
+        }
+    };
+
+    // Header and logical screen descriptor, then the global color table
+    let Some(&flags) = data.get(10) else {
+        return 0;
+    };
+    let mut pos = 13 + color_table(flags);
+    let mut count = 0;
+    loop {
+        let next = match data.get(pos) {
+            // Extension: introducer, label, sub-blocks
+            Some(0x21) => skip_sub_blocks(pos + 2),
+            // Image descriptor, local color table, LZW code size, sub-blocks
+            Some(0x2C) => {
+                count += 1;
+                data.get(pos + 9)
+                    .and_then(|&flags| skip_sub_blocks(pos + 11 + color_table(flags)))
+            }
+            // Trailer, or data we don't understand
+            _ => None,
+        };
+        match next {
+            Some(next) => pos = next,
+            None => return count,
+        }
+    }
+}
+
+/// Count the ANMF chunks of an animated WebP
+fn webp_frame_count(data: &[u8]) -> usize {
+    let mut pos = 12;
+    let mut count = 0;
+    while let Some(header) = data.get(pos..pos + 8) {
+        if &header[..4] == b"ANMF" {
+            count += 1;
+        }
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        // Chunks are padded to an even size
+        pos = pos.saturating_add(8 + size + (size & 1));
+    }
+    count
+}
+
+/// Frame iterator of a decoder that owns its input
+///
+/// `Frames` boxes its iterator without a `Send` bound, but every iterator
+/// built in `Playback::open` wraps a GIF, PNG or WebP decoder over an owned
+/// `Cursor<Vec<u8>>`, all of which are `Send`.
+struct PendingFrames(Frames<'static>);
+
+// SAFETY: only constructed from the owned, Send decoders listed above
+unsafe impl Send for PendingFrames {}
+
+/// Playback state of an animated image
+struct Playback {
+    /// Frames decoded so far, in order
+    frames: Vec<ImageFrame>,
+    /// Decoder for the remaining frames, None once all are decoded
+    pending: Option<PendingFrames>,
+    /// Total number of frames, including those not decoded yet
+    frame_count: usize,
+    current: usize,
+    elapsed_ms: f32,
+}
+
+impl Playback {
+    /// Start playback of an animated image by decoding its first frame
+    ///
+    /// Returns None for still images, or if the first frame cannot be decoded.
+    fn open(data: &[u8], color: &ColorManager) -> Option<Self> {
+        let frame_count = frame_count(data);
+        if frame_count < 2 {
+            return None;
+        }
+        // The decoder owns a copy of the data so frames can be decoded later
+        let cursor = Cursor::new(data.to_vec());
+        let frames = match image::guess_format(data).ok()? {
+            ImageFormat::Gif => GifDecoder::new(cursor).ok()?.into_frames(),
+            ImageFormat::Png => PngDecoder::new(cursor).ok()?.apng().ok()?.into_frames(),
+            ImageFormat::WebP => WebPDecoder::new(cursor).ok()?.into_frames(),
+            _ => return None,
+        };
+        let mut playback = Self {
+            frames: Vec::new(),
+            pending: Some(PendingFrames(frames)),
+            frame_count,
+            current: 0,
+            elapsed_ms: 0.0,
+        };
+        playback.decode_to(0, color).then_some(playback)
+    }
+
+    /// Decode frames up to and including `index`, returning false if it doesn't exist
+    ///
+    /// A decode error ends the animation at the last good frame.
+    fn decode_to(&mut self, index: usize, color: &ColorManager) -> bool {
+        while self.frames.len() <= index && self.frames.len() < self.frame_count {
+            match self.pending.as_mut().and_then(|frames| frames.0.next()) {
+                Some(Ok(frame)) => self.frames.push(image_frame(frame, color)),
+                Some(Err(e)) => {
+                    log::warn!("image cache: stopping animation at frame {}: {}", self.frames.len(), e);
+                    self.frame_count = self.frames.len();
+                }
+                None => self.frame_count = self.frames.len(),
+            }
+        }
+        if self.frames.len() >= self.frame_count {
+            self.pending = None;
+        }
+        index < self.frames.len()
+    }
+
+    /// Advance by `dt_ms`, returning true if the visible frame changed
+    fn advance(&mut self, dt_ms: f32, color: &ColorManager) -> bool {
+        let start = self.current;
+        self.elapsed_ms += dt_ms;
+        // Browsers clamp very small delays so zero-delay GIFs don't spin
+        loop {
+            let delay = self.frames[self.current].delay_ms.max(MIN_FRAME_DELAY_MS) as f32;
+            if self.elapsed_ms < delay {
+                break;
+            }
+            self.elapsed_ms -= delay;
+            let next = (self.current + 1) % self.frame_count;
+            self.current = if self.decode_to(next, color) { next } else { 0 };
+        }
+        self.current != start
+    }
+
+    fn byte_size(&self) -> usize {
+        self.frames.iter().map(|f| f.image.byte_size()).sum()
+    }
+}
+
+/// Minimum frame delay used during playback, in milliseconds
+const MIN_FRAME_DELAY_MS: u32 = 20;
+
+struct CacheEntry {
+    key: String,
+    image: DecodedImage,
+    animation: Option<Playback>,
+    last_used: u64,
+}
+
+impl CacheEntry {
+    fn byte_size(&self) -> usize {
+        match &self.animation {
+            Some(anim) => anim.byte_size(),
+            None => self.image.byte_size(),
+        }
+    }
+}
+
+/// Keyed cache of decoded images with least-recently-used eviction
+pub struct ImageCache {
+    entries: HashMap<ImageId, CacheEntry>,
+    keys: HashMap<String, ImageId>,
+    next_id: ImageId,
+    clock: u64,
+    used_bytes: usize,
+    budget_bytes: usize,
+}
+
+impl Default for ImageCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_IMAGE_BUDGET)
+    }
+}
+
+impl ImageCache {
+    /// Create a cache that holds at most `budget_bytes` of decoded pixels
+    pub fn new(budget_bytes: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            keys: HashMap::new(),
+            next_id: 0,
+            clock: 0,
+            used_bytes: 0,
+            budget_bytes,
+        }
+    }
+
+    /// Decode and cache an image under `key`, returning its handle
+    ///
+    /// If `key` is already cached the existing handle is returned without
+    /// decoding again. Animated images only decode their first frame here;
+    /// the rest are decoded as playback reaches them.
+    pub fn load(&mut self, key: &str, data: &[u8]) -> Result<ImageId, image::ImageError> {
+        self.load_managed(key, data, &ColorManager::new())
+    }
+
+    /// Like `load`, converting pixels into the output space of `color`
+    ///
+    /// Images already cached are not converted again when the output space changes.
+    pub fn load_managed(&mut self, key: &str, data: &[u8], color: &ColorManager) -> Result<ImageId, image::ImageError> {
+        if let Some(&id) = self.keys.get(key) {
+            self.touch(id);
+            return Ok(id);
+        }
+        match Playback::open(data, color) {
+            Some(playback) => {
+                let first = playback.frames[0].image.clone();
+                Ok(self.insert_entry(key, first, Some(playback)))
+            }
+            None => Ok(self.insert(key, DecodedImage::decode_managed(data, color)?)),
+        }
+    }
+
+    /// Decode a still image and cache it under a fresh handle
+    ///
+    /// Unlike `load`, each call decodes again and gets its own handle.
+    /// Animated images keep only their first frame.
+    pub fn decode(&mut self, data: &[u8], color: &ColorManager) -> Result<ImageId, image::ImageError> {
+        let image = DecodedImage::decode_managed(data, color)?;
+        let key = format!("decoded:{}", self.next_id + 1);
+        Ok(self.insert(&key, image))
+    }
+
+    /// Insert an already decoded image under `key`, replacing any previous entry
+    pub fn insert(&mut self, key: &str, image: DecodedImage) -> ImageId {
+        self.insert_entry(key, image, None)
+    }
+
+    /// Cache a straight-alpha sRGB RGBA8 buffer, converting it into the output space of `color`
+    ///
+    /// Each call gets a fresh handle. Returns None if `pixels` is smaller than
+    /// `width * height * 4` or either dimension is zero.
+    pub fn insert_rgba(&mut self, pixels: &[u8], width: u32, height: u32, color: &ColorManager) -> Option<ImageId> {
+        let len = width as usize * height as usize * 4;
+        if width == 0 || height == 0 || pixels.len() < len {
+            return None;
+        }
+        let rgba = image::RgbaImage::from_raw(width, height, pixels[..len].to_vec())?;
+        let key = format!("rgba:{}", self.next_id + 1);
+        Some(self.insert(&key, DecodedImage::from_rgba(rgba, color)))
+    }
+
+    /// Insert decoded frames under `key`; more than one frame makes the image animated
+    pub fn insert_frames(&mut self, key: &str, mut frames: Vec<ImageFrame>) -> ImageId {
+        if frames.len() > 1 {
+            let first = frames[0].image.clone();
+            self.insert_entry(
+                key,
+                first,
+                Some(Playback {
+                    frame_count: frames.len(),
+                    frames,
+                    pending: None,
+                    current: 0,
+                    elapsed_ms: 0.0,
+                }),
+            )
+        } else {
+            let image = match frames.pop() {
+                Some(frame) => frame.image,
+                None => DecodedImage {
+                    width: 0,
+                    height: 0,
+                    pixels: Arc::from(Vec::new()),
+                },
+            };
+            self.insert_entry(key, image, None)
+        }
+    }
+
+    fn insert_entry(&mut self, key: &str, image: DecodedImage, animation: Option<Playback>) -> ImageId {
+        if let Some(id) = self.keys.get(key).copied() {
+            self.remove(id);
+        }
+
+        self.next_id += 1;
+        let id = self.next_id;
+        self.clock += 1;
+        let entry = CacheEntry {
+            key: key.to_string(),
+            image,
+            animation,
+            last_used: self.clock,
+        };
+        self.used_bytes += entry.byte_size();
+        self.keys.insert(key.to_string(), id);
+        self.entries.insert(id, entry);
+
+        self.evict(id);
+        id
+    }
+
+    /// Number of frames in an image (1 for still images, 0 if not cached)
+    pub fn frame_count(&self, id: ImageId) -> usize {
+        match self.entries.get(&id) {
+            Some(entry) => entry.animation.as_ref().map_or(1, |a| a.frame_count),
+            None => 0,
+        }
+    }
+
+    /// Get a frame by index along with its delay in milliseconds, decoding it if needed
+    pub fn frame(&mut self, id: ImageId, index: usize) -> Option<(&DecodedImage, u32)> {
+        self.frame_managed(id, index, &ColorManager::new())
+    }
+
+    /// Like `frame`, converting newly decoded frames into the output space of `color`
+    pub fn frame_managed(&mut self, id: ImageId, index: usize, color: &ColorManager) -> Option<(&DecodedImage, u32)> {
+        let entry = self.entries.get_mut(&id)?;
+        match &mut entry.animation {
+            Some(anim) => {
+                let before = anim.byte_size();
+                let decoded = anim.decode_to(index, color);
+                self.used_bytes += anim.byte_size() - before;
+                decoded.then(|| (&anim.frames[index].image, anim.frames[index].delay_ms))
+            }
+            None if index == 0 => Some((&entry.image, 0)),
+            None => None,
+        }
+    }
+
+    /// Show frame `index` of an animated image and restart its delay
+    ///
+    /// Lets the embedder drive timing instead of calling `advance`.
+    /// Returns false if the image or frame doesn't exist.
+    pub fn set_frame(&mut self, id: ImageId, index: usize) -> bool {
+        self.set_frame_managed(id, index, &ColorManager::new())
+    }
+
+    /// Like `set_frame`, converting newly decoded frames into the output space of `color`
+    pub fn set_frame_managed(&mut self, id: ImageId, index: usize, color: &ColorManager) -> bool {
+        let Some(entry) = self.entries.get_mut(&id) else {
+            return false;
+        };
+        let Some(anim) = &mut entry.animation else {
+            return index == 0;
+        };
+        let before = anim.byte_size();
+        let decoded = anim.decode_to(index, color);
+        self.used_bytes += anim.byte_size() - before;
+        if decoded {
+            anim.current = index;
+            anim.elapsed_ms = 0.0;
+            entry.image = anim.frames[index].image.clone();
+        }
+        decoded
+    }
+
+    /// Index of the frame currently shown for an image
+    pub fn current_frame(&self, id: ImageId) -> usize {
+        self.entries
+            .get(&id)
+            .and_then(|e| e.animation.as_ref())
+            .map_or(0, |a| a.current)
+    }
+
+    /// Advance playback of every animated image by `dt` seconds
+    ///
+    /// Returns true if any visible frame changed (i.e. a redraw is needed).
+    pub fn advance(&mut self, dt: f32) -> bool {
+        self.advance_managed(dt, &ColorManager::new())
+    }
+
+    /// Like `advance`, converting newly decoded frames into the output space of `color`
+    ///
+    /// Frames decoded here count towards the budget from the next insertion.
+    pub fn advance_managed(&mut self, dt: f32, color: &ColorManager) -> bool {
+        let dt_ms = dt.max(0.0) * 1000.0;
+        let mut changed = false;
+        for entry in self.entries.values_mut() {
+            if let Some(anim) = &mut entry.animation {
+                let before = anim.byte_size();
+                if anim.advance(dt_ms, color) {
+                    entry.image = anim.frames[anim.current].image.clone();
+                    changed = true;
+                }
+                self.used_bytes += anim.byte_size() - before;
+            }
+        }
+        changed
+    }
+
+    /// Get a cached image and mark it as recently used
+    pub fn get(&mut self, id: ImageId) -> Option<&DecodedImage> {
+        self.touch(id);
+        self.entries.get(&id).map(|e| &e.image)
+    }
+
+    /// Get a cached image without updating its recency
+    pub fn peek(&self, id: ImageId) -> Option<&DecodedImage> {
+        self.entries.get(&id).map(|e| &e.image)
+    }
+
+    /// Look up the handle cached under `key`
+    pub fn lookup(&self, key: &str) -> Option<ImageId> {
+        self.keys.get(key).copied()
+    }
+
+    /// Remove an image from the cache
+    pub fn remove(&mut self, id: ImageId) -> bool {
+        match self.entries.remove(&id) {
+            Some(entry) => {
+                self.keys.remove(&entry.key);
+                self.used_bytes -= entry.byte_size();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove all images
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.keys.clear();
+        self.used_bytes = 0;
+    }
+
+    /// Number of cached images
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Bytes of decoded pixels currently held
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes
+    }
+
+    /// Current memory budget in bytes
+    pub fn budget(&self) -> usize {
+        self.budget_bytes
+    }
+
+    /// Change the memory budget, evicting images if the cache is now over it
+    pub fn set_budget(&mut self, budget_bytes: usize) {
+        self.budget_bytes = budget_bytes;
+        self.evict(0);
+    }
+
+    fn touch(&mut self, id: ImageId) {
+        if let Some(entry) = self.entries.get_mut(&id) {
+            self.clock += 1;
+            entry.last_used = self.clock;
+        }
+    }
+
+    /// Evict least-recently-used images until within budget, never evicting `keep`
+    fn evict(&mut self, keep: ImageId) {
+        while self.used_bytes > self.budget_bytes {
+            let victim = self
+                .entries
+                .iter()
+                .filter(|(&id, _)| id != keep)
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(&id, _)| id);
+            match victim {
+                Some(id) => {
+                    log::debug!("image cache: evicting image {}", id);
+                    self.remove(id);
+                }
+                None => break,
+            }
+        }
+    }
+}
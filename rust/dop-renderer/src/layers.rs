@@ -0,0 +1,146 @@
+//! Compositor layers
+//!
+//! A layer holds the commands of one subtree (for example a scrolled
+//! article) and a retained pixmap with their rasterized result. Each frame the
+//! software renderer composites layer pixmaps at their current position;
+//! commands are only re-rasterized after the layer's content changes, so
+//! moving or scrolling a layer is a blit.
+
+use std::collections::HashMap;
+
+use tiny_skia::Pixmap;
+
+use crate::renderer::RenderCommand;
+use crate::software::TextCommand;
+
+/// Handle to a compositor layer (0 = no layer)
+pub type LayerId = u32;
+
+/// A retained compositor layer
+///
+/// Command coordinates are in layer space; `x`/`y` place the layer's
+/// origin in the framebuffer.
+pub struct Layer {
+    pub x: f32,
+    pub y: f32,
+    pub width: u32,
+    pub height: u32,
+    pub z_index: i32,
+    pub(crate) commands: Vec<RenderCommand>,
+    pub(crate) text_commands: Vec<TextCommand>,
+    pub(crate) pixmap: Option<Pixmap>,
+    pub(crate) dirty: bool,
+    /// Number of times the layer content has been rasterized
+    pub raster_count: u32,
+}
+
+impl Layer {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            width: width.max(1),
+            height: height.max(1),
+            z_index: 0,
+            commands: Vec::new(),
+            text_commands: Vec::new(),
+            pixmap: None,
+            dirty: true,
+            raster_count: 0,
+        }
+    }
+
+    /// Check if the layer needs to be rasterized again
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Force re-rasterization on the next frame
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Add a rectangle in layer space
+    pub fn add_rect(&mut self, cmd: RenderCommand) {
+        self.commands.push(cmd);
+        self.dirty = true;
+    }
+
+    /// Add text in layer space
+    pub fn add_text(&mut self, cmd: TextCommand) {
+        self.text_commands.push(cmd);
+        self.dirty = true;
+    }
+
+    /// Remove all layer content
+    pub fn clear(&mut self) {
+        self.commands.clear();
+        self.text_commands.clear();
+        self.dirty = true;
+    }
+
+    /// Resize the layer's backing pixmap
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let width = width.max(1);
+        let height = height.max(1);
+        if width != self.width || height != self.height {
+            self.width = width;
+            self.height = height;
+            self.pixmap = None;
+            self.dirty = true;
+        }
+    }
+}
+
+/// Collection of compositor layers
+#[derive(Default)]
+pub struct LayerStore {
+    layers: HashMap<LayerId, Layer>,
+    next_id: LayerId,
+}
+
+impl LayerStore {
+    /// Create an empty layer store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a layer with the given pixmap size and return its handle
+    pub fn create(&mut self, width: u32, height: u32) -> LayerId {
+        self.next_id += 1;
+        self.layers.insert(self.next_id, Layer::new(width, height));
+        self.next_id
+    }
+
+    /// Remove a layer
+    pub fn remove(&mut self, id: LayerId) -> bool {
+        self.layers.remove(&id).is_some()
+    }
+
+    /// Get a layer
+    pub fn get(&self, id: LayerId) -> Option<&Layer> {
+        self.layers.get(&id)
+    }
+
+    /// Get a layer mutably
+    pub fn get_mut(&mut self, id: LayerId) -> Option<&mut Layer> {
+        self.layers.get_mut(&id)
+    }
+
+    /// Number of layers
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Check if there are no layers
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Layer handles in compositing order (z-index, then creation order)
+    pub(crate) fn draw_order(&self) -> Vec<LayerId> {
+        let mut ids: Vec<LayerId> = self.layers.keys().copied().collect();
+        ids.sort_by_key(|id| (self.layers[id].z_index, *id));
+        ids
+    }
+}
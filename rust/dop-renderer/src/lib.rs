@@ -6,13 +6,46 @@
 //! ## Features
 //!
 //! - **software** (default): CPU-based rendering using tiny-skia and softbuffer
-//! - **gpu**: Hardware-accelerated rendering using wgpu
+//! - **gpu**: Hardware-accelerated rendering using wgpu, with lyon path tessellation
+//! - **images**: PNG/JPEG/GIF/WebP decoding with a shared image cache
+//! - **icc**: Color-manage output to a display ICC profile
+//! - **content-ir**: Render dop-content-ir trees directly on the software renderer
+//! - **accessibility**: Publish an accesskit tree for the window to screen readers
 
 pub mod window;
+pub mod window_manager;
+pub mod scroll;
+pub mod virtual_window;
 pub mod renderer;
 pub mod text;
+pub mod color_font;
+pub mod woff;
+pub mod optimize;
+pub mod stacking;
+pub mod caret;
+pub mod color;
+pub mod blend;
+pub mod encode;
+pub mod path;
+pub mod svg;
 #[cfg(feature = "software")]
 pub mod software;
+#[cfg(feature = "software")]
+pub mod layers;
+#[cfg(feature = "software")]
+pub mod state;
+#[cfg(feature = "software")]
+pub mod damage;
+#[cfg(feature = "software")]
+pub mod retained;
+#[cfg(feature = "software")]
+pub mod testing;
+#[cfg(feature = "images")]
+pub mod images;
+#[cfg(feature = "accessibility")]
+pub mod accessibility;
+pub mod ffi_guard;
+pub mod handles;
 pub mod ffi;
 
 pub use window::*;
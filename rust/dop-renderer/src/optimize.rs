@@ -0,0 +1,224 @@
+//! Frame command optimization passes
+//!
+//! These passes run over a z-sorted list of rectangle commands before
+//! rasterization and remove work that cannot affect the final image:
+//! `optimize_commands` drops invisible commands, deduplicates repeats and
+//! merges adjacent rectangles; `cull_outside` removes commands outside the
+//! viewport and `cull_occluded` removes hidden ones.
+
+use crate::renderer::RenderCommand;
+
+/// Maximum number of occluders tracked by the culling pass
+///
+/// Only the largest opaque rectangles are kept, which bounds the pass to
+/// O(n * MAX_OCCLUDERS) while still catching page backgrounds and modals.
+const MAX_OCCLUDERS: usize = 32;
+
+/// Integer pixel bounds (x0, y0, x1, y1), exclusive on the right and bottom
+pub type PixelBounds = (i32, i32, i32, i32);
+
+/// Bounds that contain every command
+pub const UNBOUNDED: PixelBounds = (i32::MIN, i32::MIN, i32::MAX, i32::MAX);
+
+/// Pixels touched by a command, including partially covered edges
+fn outer_bounds(cmd: &RenderCommand) -> PixelBounds {
+    (
+        cmd.x.floor() as i32,
+        cmd.y.floor() as i32,
+        (cmd.x + cmd.width).ceil() as i32,
+        (cmd.y + cmd.height).ceil() as i32,
+    )
+}
+
+/// Pixels fully covered by a command (anti-aliased edges excluded)
+fn inner_bounds(cmd: &RenderCommand) -> PixelBounds {
+    (
+        cmd.x.ceil() as i32,
+        cmd.y.ceil() as i32,
+        (cmd.x + cmd.width).floor() as i32,
+        (cmd.y + cmd.height).floor() as i32,
+    )
+}
+
+fn area(b: &PixelBounds) -> i64 {
+    (b.2 - b.0).max(0) as i64 * (b.3 - b.1).max(0) as i64
+}
+
+fn contains(outer: &PixelBounds, inner: &PixelBounds) -> bool {
+    outer.0 <= inner.0 && outer.1 <= inner.1 && outer.2 >= inner.2 && outer.3 >= inner.3
+}
+
+fn intersect(a: &PixelBounds, b: &PixelBounds) -> PixelBounds {
+    (a.0.max(b.0), a.1.max(b.1), a.2.min(b.2), a.3.min(b.3))
+}
+
+/// Check if a command fully hides everything beneath it
+///
+/// Rounded rectangles leave their corners uncovered, so they never occlude.
+fn is_opaque(cmd: &RenderCommand) -> bool {
+    cmd.color_a >= 1.0 && cmd.texture_id == 0 && cmd.width > 0.0 && cmd.height > 0.0 && !cmd.is_rounded()
+}
+
+/// Remove commands that touch no pixel inside `viewport`
+///
+/// Returns the number of commands removed.
+pub fn cull_outside(commands: &mut Vec<RenderCommand>, viewport: PixelBounds) -> usize {
+    let before = commands.len();
+    commands.retain(|cmd| area(&intersect(&outer_bounds(cmd), &viewport)) > 0);
+    before - commands.len()
+}
+
+/// Remove commands completely covered by a later opaque command
+///
+/// `commands` must already be in draw order. Returns the number of commands
+/// removed.
+pub fn cull_occluded(commands: &mut Vec<RenderCommand>) -> usize {
+    cull_occluded_in(commands, UNBOUNDED)
+}
+
+/// Remove commands whose pixels inside `viewport` are all covered by a later opaque command
+///
+/// Only the visible part of a command has to be covered, so a small damaged
+/// area under an opaque rectangle culls everything beneath it. `commands`
+/// must already be in draw order. Returns the number of commands removed.
+pub fn cull_occluded_in(commands: &mut Vec<RenderCommand>, viewport: PixelBounds) -> usize {
+    if commands.len() < 2 {
+        return 0;
+    }
+
+    let mut occluders: Vec<PixelBounds> = Vec::new();
+    let mut keep = vec![true; commands.len()];
+
+    // Walk back to front so every occluder seen so far is drawn later
+    for (i, cmd) in commands.iter().enumerate().rev() {
+        let bounds = intersect(&outer_bounds(cmd), &viewport);
+        if occluders.iter().any(|o| contains(o, &bounds)) {
+            keep[i] = false;
+            continue;
+        }
+
+        if is_opaque(cmd) {
+            let inner = intersect(&inner_bounds(cmd), &viewport);
+            if area(&inner) == 0 {
+                continue;
+            }
+            if occluders.len() < MAX_OCCLUDERS {
+                occluders.push(inner);
+            } else if let Some((smallest, _)) = occluders
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, o)| area(o))
+                .filter(|(_, o)| area(o) < area(&inner))
+            {
+                occluders[smallest] = inner;
+            }
+        }
+    }
+
+    let before = commands.len();
+    let mut flags = keep.into_iter();
+    commands.retain(|_| flags.next().unwrap_or(true));
+    before - commands.len()
+}
+
+/// Counters reported by `optimize_commands`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OptimizeStats {
+    /// Zero-area or fully transparent commands removed
+    pub dropped: u32,
+    /// Opaque commands removed because they repeat the previous command
+    pub deduplicated: u32,
+    /// Commands folded into an adjacent same-color rectangle
+    pub merged: u32,
+}
+
+/// Check if two commands draw with the same paint
+fn same_paint(a: &RenderCommand, b: &RenderCommand) -> bool {
+    a.color_r == b.color_r
+        && a.color_g == b.color_g
+        && a.color_b == b.color_b
+        && a.color_a == b.color_a
+        && a.texture_id == b.texture_id
+        && a.z_index == b.z_index
+        && a.corner_radii == b.corner_radii
+}
+
+/// Check if two commands are identical
+fn same_command(a: &RenderCommand, b: &RenderCommand) -> bool {
+    same_paint(a, b) && a.x == b.x && a.y == b.y && a.width == b.width && a.height == b.height
+}
+
+/// Merge `next` into `prev` if they share a full edge without overlapping
+///
+/// The shared edge must lie on a pixel boundary: on a fractional edge both
+/// rectangles anti-alias the same pixels, and blending the two partial
+/// coverages differs from one rectangle covering them fully.
+fn try_merge(prev: &mut RenderCommand, next: &RenderCommand) -> bool {
+    if !same_paint(prev, next) || prev.texture_id != 0 || prev.is_rounded() {
+        return false;
+    }
+    let on_pixel = |edge: f32| edge.fract() == 0.0;
+
+    // Side by side on the same row
+    if prev.y == next.y && prev.height == next.height {
+        if prev.x + prev.width == next.x && on_pixel(next.x) {
+            prev.width += next.width;
+            return true;
+        }
+        if next.x + next.width == prev.x && on_pixel(prev.x) {
+            prev.x = next.x;
+            prev.width += next.width;
+            return true;
+        }
+    }
+
+    // Stacked in the same column
+    if prev.x == next.x && prev.width == next.width {
+        if prev.y + prev.height == next.y && on_pixel(next.y) {
+            prev.height += next.height;
+            return true;
+        }
+        if next.y + next.height == prev.y && on_pixel(prev.y) {
+            prev.y = next.y;
+            prev.height += next.height;
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Drop invisible commands, deduplicate repeats and merge adjacent rectangles
+///
+/// Only neighbours in draw order are deduplicated or merged, and merges need
+/// a shared edge on a pixel boundary, so the result paints exactly the same
+/// pixels. Translucent repeats are kept because drawing them twice changes
+/// the blended color.
+pub fn optimize_commands(commands: &mut Vec<RenderCommand>) -> OptimizeStats {
+    let mut stats = OptimizeStats::default();
+    let mut out: Vec<RenderCommand> = Vec::with_capacity(commands.len());
+
+    for cmd in commands.drain(..) {
+        if cmd.width <= 0.0 || cmd.height <= 0.0 || (cmd.color_a <= 0.0 && cmd.texture_id == 0) {
+            stats.dropped += 1;
+            continue;
+        }
+
+        if let Some(prev) = out.last_mut() {
+            if same_command(prev, &cmd) && is_opaque(&cmd) {
+                stats.deduplicated += 1;
+                continue;
+            }
+            if try_merge(prev, &cmd) {
+                stats.merged += 1;
+                continue;
+            }
+        }
+
+        out.push(cmd);
+    }
+
+    *commands = out;
+    stats
+}
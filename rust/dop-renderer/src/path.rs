@@ -0,0 +1,402 @@
+//! Vector paths for non-rectangular shapes
+//!
+//! A path is recorded as move/line/curve segments and then filled or
+//! stroked. The software renderer rasterizes it with tiny-skia; the wgpu
+//! renderer tessellates it into triangles with lyon.
+//!
+//! Like other commands, path geometry is mapped to device pixels when the
+//! path is added, so renderers only ever see device-space points.
+
+use std::hash::{Hash, Hasher};
+
+/// One segment of a path
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathSegment {
+    MoveTo(f32, f32),
+    LineTo(f32, f32),
+    /// Control point, then end point
+    QuadTo(f32, f32, f32, f32),
+    /// Two control points, then end point
+    CubicTo(f32, f32, f32, f32, f32, f32),
+    Close,
+}
+
+impl Hash for PathSegment {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let (tag, points): (u8, &[f32]) = match self {
+            PathSegment::MoveTo(x, y) => (0, &[*x, *y]),
+            PathSegment::LineTo(x, y) => (1, &[*x, *y]),
+            PathSegment::QuadTo(cx, cy, x, y) => (2, &[*cx, *cy, *x, *y]),
+            PathSegment::CubicTo(c1x, c1y, c2x, c2y, x, y) => (3, &[*c1x, *c1y, *c2x, *c2y, *x, *y]),
+            PathSegment::Close => (4, &[]),
+        };
+        tag.hash(state);
+        for p in points {
+            p.to_bits().hash(state);
+        }
+    }
+}
+
+/// Rule deciding which areas of a self-overlapping path are inside
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum FillRule {
+    #[default]
+    NonZero = 0,
+    EvenOdd = 1,
+}
+
+impl FillRule {
+    /// Convert a raw FFI value into a FillRule (unknown values map to NonZero)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => FillRule::EvenOdd,
+            _ => FillRule::NonZero,
+        }
+    }
+}
+
+/// Shape drawn at the open ends of a stroke
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LineCap {
+    /// Ends flat at the end point
+    #[default]
+    Butt = 0,
+    /// Half circle around the end point
+    Round = 1,
+    /// Extends half the width past the end point
+    Square = 2,
+}
+
+impl LineCap {
+    /// Convert a raw FFI value into a LineCap (unknown values map to Butt)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => LineCap::Round,
+            2 => LineCap::Square,
+            _ => LineCap::Butt,
+        }
+    }
+}
+
+/// Shape drawn where two stroked segments meet
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LineJoin {
+    /// Sharp corner (beveled past the default miter limit of 4)
+    #[default]
+    Miter = 0,
+    Round = 1,
+}
+
+impl LineJoin {
+    /// Convert a raw FFI value into a LineJoin (unknown values map to Miter)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => LineJoin::Round,
+            _ => LineJoin::Miter,
+        }
+    }
+}
+
+/// Width and shape of a stroke
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrokeStyle {
+    pub width: f32,
+    pub cap: LineCap,
+    pub join: LineJoin,
+}
+
+impl StrokeStyle {
+    /// Stroke with butt caps and miter joins
+    pub fn new(width: f32) -> Self {
+        Self {
+            width,
+            cap: LineCap::default(),
+            join: LineJoin::default(),
+        }
+    }
+}
+
+/// A sequence of subpaths
+#[derive(Debug, Clone, Default, PartialEq, Hash)]
+pub struct Path {
+    segments: Vec<PathSegment>,
+}
+
+impl Path {
+    /// Create an empty path
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Closed outline of a rectangle with circular arcs for rounded corners
+    ///
+    /// Radii are top-left, top-right, bottom-right, bottom-left and must not
+    /// overlap (see `RenderCommand::clamped_radii`).
+    pub fn rounded_rect(x: f32, y: f32, width: f32, height: f32, radii: [f32; 4]) -> Self {
+        // Cubic Bezier control point distance approximating a quarter circle
+        const K: f32 = 0.552_284_8;
+        let [tl, tr, br, bl] = radii;
+        let (x0, y0, x1, y1) = (x, y, x + width, y + height);
+        let mut path = Path::new();
+        path.move_to(x0 + tl, y0);
+        path.line_to(x1 - tr, y0);
+        if tr > 0.0 {
+            path.cubic_to(x1 - tr * (1.0 - K), y0, x1, y0 + tr * (1.0 - K), x1, y0 + tr);
+        }
+        path.line_to(x1, y1 - br);
+        if br > 0.0 {
+            path.cubic_to(x1, y1 - br * (1.0 - K), x1 - br * (1.0 - K), y1, x1 - br, y1);
+        }
+        path.line_to(x0 + bl, y1);
+        if bl > 0.0 {
+            path.cubic_to(x0 + bl * (1.0 - K), y1, x0, y1 - bl * (1.0 - K), x0, y1 - bl);
+        }
+        path.line_to(x0, y0 + tl);
+        if tl > 0.0 {
+            path.cubic_to(x0, y0 + tl * (1.0 - K), x0 + tl * (1.0 - K), y0, x0 + tl, y0);
+        }
+        path.close();
+        path
+    }
+
+    /// Start a new subpath
+    pub fn move_to(&mut self, x: f32, y: f32) {
+        self.segments.push(PathSegment::MoveTo(x, y));
+    }
+
+    /// Add a straight line (starts a subpath if there is none)
+    pub fn line_to(&mut self, x: f32, y: f32) {
+        self.segments.push(PathSegment::LineTo(x, y));
+    }
+
+    /// Add a quadratic Bezier curve
+    pub fn quad_to(&mut self, cx: f32, cy: f32, x: f32, y: f32) {
+        self.segments.push(PathSegment::QuadTo(cx, cy, x, y));
+    }
+
+    /// Add a cubic Bezier curve
+    pub fn cubic_to(&mut self, c1x: f32, c1y: f32, c2x: f32, c2y: f32, x: f32, y: f32) {
+        self.segments.push(PathSegment::CubicTo(c1x, c1y, c2x, c2y, x, y));
+    }
+
+    /// Close the current subpath with a line back to its start
+    pub fn close(&mut self) {
+        self.segments.push(PathSegment::Close);
+    }
+
+    /// Remove all segments
+    pub fn clear(&mut self) {
+        self.segments.clear();
+    }
+
+    /// Recorded segments in order
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+
+    /// Check if the path has no segments
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Bounds of all points, control points included, as left, top, right, bottom
+    pub fn bounds(&self) -> Option<[f32; 4]> {
+        let mut bounds: Option<[f32; 4]> = None;
+        let mut add = |x: f32, y: f32| {
+            bounds = Some(match bounds {
+                Some([l, t, r, b]) => [l.min(x), t.min(y), r.max(x), b.max(y)],
+                None => [x, y, x, y],
+            });
+        };
+        for segment in &self.segments {
+            match *segment {
+                PathSegment::MoveTo(x, y) | PathSegment::LineTo(x, y) => add(x, y),
+                PathSegment::QuadTo(cx, cy, x, y) => {
+                    add(cx, cy);
+                    add(x, y);
+                }
+                PathSegment::CubicTo(c1x, c1y, c2x, c2y, x, y) => {
+                    add(c1x, c1y);
+                    add(c2x, c2y);
+                    add(x, y);
+                }
+                PathSegment::Close => {}
+            }
+        }
+        bounds
+    }
+
+    /// Subpaths as polylines, with curves split into short lines
+    fn polylines(&self) -> Vec<Vec<(f32, f32)>> {
+        // Chords per curve: about one per two pixels of control polygon, at most 64
+        fn steps(points: &[(f32, f32)]) -> usize {
+            let length: f32 = points.windows(2).map(|p| (p[1].0 - p[0].0).hypot(p[1].1 - p[0].1)).sum();
+            ((length / 2.0).ceil() as usize).clamp(1, 64)
+        }
+        let mut polylines: Vec<Vec<(f32, f32)>> = Vec::new();
+        let mut current = (0.0, 0.0);
+        let mut start = (0.0, 0.0);
+        let mut open = false;
+        for segment in &self.segments {
+            if !matches!(segment, PathSegment::MoveTo(..)) && !open {
+                polylines.push(vec![current]);
+                open = true;
+            }
+            match *segment {
+                PathSegment::MoveTo(x, y) => {
+                    polylines.push(vec![(x, y)]);
+                    start = (x, y);
+                    current = (x, y);
+                    open = true;
+                    continue;
+                }
+                PathSegment::LineTo(x, y) => current = (x, y),
+                PathSegment::QuadTo(cx, cy, x, y) => {
+                    let (x0, y0) = current;
+                    let n = steps(&[current, (cx, cy), (x, y)]);
+                    let line = polylines.last_mut().expect("subpath opened above");
+                    for i in 1..n {
+                        let t = i as f32 / n as f32;
+                        let u = 1.0 - t;
+                        line.push((u * u * x0 + 2.0 * u * t * cx + t * t * x, u * u * y0 + 2.0 * u * t * cy + t * t * y));
+                    }
+                    current = (x, y);
+                }
+                PathSegment::CubicTo(c1x, c1y, c2x, c2y, x, y) => {
+                    let (x0, y0) = current;
+                    let n = steps(&[current, (c1x, c1y), (c2x, c2y), (x, y)]);
+                    let line = polylines.last_mut().expect("subpath opened above");
+                    for i in 1..n {
+                        let t = i as f32 / n as f32;
+                        let u = 1.0 - t;
+                        let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                        line.push((a * x0 + b * c1x + c * c2x + d * x, a * y0 + b * c1y + c * c2y + d * y));
+                    }
+                    current = (x, y);
+                }
+                PathSegment::Close => {
+                    current = start;
+                    open = false;
+                }
+            }
+            if let Some(line) = polylines.last_mut() {
+                line.push(current);
+            }
+        }
+        polylines
+    }
+
+    /// Split the outline into dashes `dash` long separated by `gap`
+    ///
+    /// Curves are approximated by lines, so the result holds only move and
+    /// line segments. The pattern restarts at every subpath. Returns a copy of
+    /// the path if `dash` or `gap` is not positive.
+    pub fn dashed(&self, dash: f32, gap: f32) -> Path {
+        if !(dash > 0.0 && gap > 0.0) {
+            return self.clone();
+        }
+        let mut out = Path::new();
+        for line in self.polylines() {
+            let (mut on, mut remaining, mut pen_down) = (true, dash, false);
+            for pair in line.windows(2) {
+                let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+                let length = (x1 - x0).hypot(y1 - y0);
+                let at = |t: f32| (x0 + (x1 - x0) * t / length, y0 + (y1 - y0) * t / length);
+                let mut t = 0.0;
+                while t < length {
+                    let step = remaining.min(length - t);
+                    if on {
+                        if !pen_down {
+                            let (x, y) = at(t);
+                            out.move_to(x, y);
+                            pen_down = true;
+                        }
+                        let (x, y) = at(t + step);
+                        out.line_to(x, y);
+                    }
+                    t += step;
+                    remaining -= step;
+                    if remaining <= 0.0 {
+                        on = !on;
+                        remaining = if on { dash } else { gap };
+                        pen_down = false;
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Copy of the path with every point passed through `map`
+    pub fn map_points(&self, map: impl Fn(f32, f32) -> (f32, f32)) -> Path {
+        let segments = self
+            .segments
+            .iter()
+            .map(|segment| match *segment {
+                PathSegment::MoveTo(x, y) => {
+                    let (x, y) = map(x, y);
+                    PathSegment::MoveTo(x, y)
+                }
+                PathSegment::LineTo(x, y) => {
+                    let (x, y) = map(x, y);
+                    PathSegment::LineTo(x, y)
+                }
+                PathSegment::QuadTo(cx, cy, x, y) => {
+                    let (cx, cy) = map(cx, cy);
+                    let (x, y) = map(x, y);
+                    PathSegment::QuadTo(cx, cy, x, y)
+                }
+                PathSegment::CubicTo(c1x, c1y, c2x, c2y, x, y) => {
+                    let (c1x, c1y) = map(c1x, c1y);
+                    let (c2x, c2y) = map(c2x, c2y);
+                    let (x, y) = map(x, y);
+                    PathSegment::CubicTo(c1x, c1y, c2x, c2y, x, y)
+                }
+                PathSegment::Close => PathSegment::Close,
+            })
+            .collect();
+        Path { segments }
+    }
+}
+
+/// How a path is painted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathPaint {
+    Fill(FillRule),
+    /// Outline centered on the path
+    Stroke(StrokeStyle),
+}
+
+impl Hash for PathPaint {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            PathPaint::Fill(rule) => (0u8, *rule as u8).hash(state),
+            PathPaint::Stroke(style) => (1u8, style.width.to_bits(), style.cap as u8, style.join as u8).hash(state),
+        }
+    }
+}
+
+/// A filled or stroked path in device pixels
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathCommand {
+    pub path: Path,
+    pub paint: PathPaint,
+    /// RGBA in the output color space, opacity already applied
+    pub color: [f32; 4],
+}
+
+impl PathCommand {
+    /// Area the command can touch, as left, top, right, bottom
+    pub fn bounds(&self) -> Option<[f32; 4]> {
+        let [l, t, r, b] = self.path.bounds()?;
+        // Miter joins reach up to twice the stroke width past a corner; one more pixel for anti-aliasing
+        let pad = match self.paint {
+            PathPaint::Fill(_) => 1.0,
+            PathPaint::Stroke(style) => style.width * 2.0 + 1.0,
+        };
+        Some([l - pad, t - pad, r + pad, b + pad])
+    }
+}
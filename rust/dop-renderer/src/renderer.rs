@@ -2,22 +2,42 @@
 //!
 //! Provides hardware-accelerated 2D rendering for the browser.
 
+use std::collections::HashMap;
 use std::sync::Arc;
-use wgpu::util::DeviceExt;
+use wgpu::util::{DeviceExt, StagingBelt};
 use winit::window::Window;
 
+use crate::color::{ColorManager, ColorSpace};
+use crate::path::PathCommand;
+use crate::text::{FontManager, GlyphKey, RunGlyph, TextCommand};
+
+/// Image atlas width in pixels (a multiple of 64 keeps RGBA rows 256-byte aligned)
+const IMAGE_ATLAS_WIDTH: u32 = 2048;
+
+/// Glyph atlas width and height in pixels
+const GLYPH_ATLAS_SIZE: u32 = 1024;
+
 /// A vertex for 2D rendering
 #[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
     pub position: [f32; 2],
     pub tex_coords: [f32; 2],
     pub color: [f32; 4],
+    /// Size of the rectangle this vertex belongs to (for rounded corners)
+    pub rect_size: [f32; 2],
+    /// Corner radii: top-left, top-right, bottom-right, bottom-left
+    pub radii: [f32; 4],
 }
 
 impl Vertex {
-    const ATTRIBS: [wgpu::VertexAttribute; 3] =
-        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+    const ATTRIBS: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x4,
+        3 => Float32x2,
+        4 => Float32x4
+    ];
 
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
@@ -42,6 +62,31 @@ pub struct RenderCommand {
     pub color_a: f32,
     pub texture_id: u32,
     pub z_index: i32,
+    /// Corner radii in pixels: top-left, top-right, bottom-right, bottom-left
+    pub corner_radii: [f32; 4],
+    /// Stacking context the rectangle paints in (0 = the renderer's current one when added)
+    pub stacking_context: u32,
+}
+
+impl RenderCommand {
+    /// Check if any corner is rounded
+    pub fn is_rounded(&self) -> bool {
+        self.corner_radii.iter().any(|r| *r > 0.0)
+    }
+
+    /// Corner radii clamped so adjacent corners never overlap (CSS scaling rule)
+    pub fn clamped_radii(&self) -> [f32; 4] {
+        let [tl, tr, br, bl] = self.corner_radii.map(|r| r.max(0.0));
+        let w = self.width.max(0.0);
+        let h = self.height.max(0.0);
+        let mut f: f32 = 1.0;
+        for (sum, side) in [(tl + tr, w), (bl + br, w), (tl + bl, h), (tr + br, h)] {
+            if sum > side {
+                f = f.min(side / sum);
+            }
+        }
+        [tl * f, tr * f, br * f, bl * f]
+    }
 }
 
 impl Default for RenderCommand {
@@ -57,10 +102,379 @@ impl Default for RenderCommand {
             color_a: 1.0,
             texture_id: 0,
             z_index: 0,
+            corner_radii: [0.0; 4],
+            stacking_context: 0,
+        }
+    }
+}
+
+/// Sampling used when an image is drawn at a size other than its own
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImageFilter {
+    #[default]
+    Bilinear = 0,
+    Nearest = 1,
+}
+
+impl ImageFilter {
+    /// Convert a raw FFI value into an ImageFilter (unknown values map to Bilinear)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => ImageFilter::Nearest,
+            _ => ImageFilter::Bilinear,
+        }
+    }
+}
+
+/// Premultiplied RGBA8 pixels shared with the image atlas
+#[derive(Debug, Clone)]
+pub struct GpuImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Arc<[u8]>,
+}
+
+/// An image scaled into a destination rectangle
+#[derive(Debug, Clone)]
+pub struct ImageDraw {
+    pub image: GpuImage,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub opacity: f32,
+    pub filter: ImageFilter,
+    pub z_index: i32,
+}
+
+/// Rectangles, paths, images and text recorded by a host renderer, drawn natively by an onscreen window
+///
+/// Colors are already converted to the output color space.
+#[derive(Clone, Default)]
+pub struct GpuFrame {
+    pub clear_color: [f32; 4],
+    pub commands: Vec<RenderCommand>,
+    pub paths: Vec<PathCommand>,
+    pub images: Vec<ImageDraw>,
+    pub text_commands: Vec<TextCommand>,
+    /// Fonts referenced by `text_commands`, registered with the window's font manager
+    pub fonts: Vec<(u32, Arc<fontdue::Font>)>,
+}
+
+/// Identity of an atlas image: pixel buffer address and size
+type ImageKey = (usize, u32, u32);
+
+fn image_key(image: &GpuImage) -> ImageKey {
+    (image.pixels.as_ptr() as usize, image.width, image.height)
+}
+
+/// Images packed into a single texture, with a bind group per filter
+struct ImageAtlas {
+    /// Distinct images in first-use order
+    keys: Vec<ImageKey>,
+    /// Atlas position (x, y) per key, None if the image did not fit
+    placements: Vec<Option<(u32, u32)>>,
+    width: u32,
+    height: u32,
+    bilinear: wgpu::BindGroup,
+    nearest: wgpu::BindGroup,
+}
+
+/// Glyph coverage packed into a persistent texture, kept across frames
+///
+/// Glyphs are stored white with their coverage in alpha so one texel serves
+/// every text color; the texture pipeline tints them by the vertex color.
+struct GlyphAtlas {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+    /// Atlas position (x, y) per glyph
+    glyphs: HashMap<GlyphKey, (u32, u32)>,
+    /// Next free position on the current shelf
+    cursor: (u32, u32),
+    shelf: u32,
+}
+
+impl GlyphAtlas {
+    fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, sampler: &wgpu::Sampler) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Glyph Atlas"),
+            size: wgpu::Extent3d {
+                width: GLYPH_ATLAS_SIZE,
+                height: GLYPH_ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+            label: Some("glyph_atlas_bind_group"),
+        });
+        Self {
+            texture,
+            bind_group,
+            glyphs: HashMap::new(),
+            cursor: (0, 0),
+            shelf: 0,
+        }
+    }
+
+    /// Atlas position of a glyph, uploading it first if needed; None if the atlas is full
+    fn insert(&mut self, queue: &wgpu::Queue, glyph: &RunGlyph) -> Option<(u32, u32)> {
+        if let Some(&pos) = self.glyphs.get(&glyph.key) {
+            return Some(pos);
+        }
+
+        // Shelf-pack with a 1px gutter so bilinear sampling never reaches a neighbour
+        let (w, h) = (glyph.width, glyph.height);
+        let (mut x, mut y) = self.cursor;
+        if x + w + 1 > GLYPH_ATLAS_SIZE {
+            x = 0;
+            y += self.shelf;
+            self.shelf = 0;
+        }
+        if y + h + 1 > GLYPH_ATLAS_SIZE {
+            return None;
+        }
+        self.cursor = (x + w + 1, y);
+        self.shelf = self.shelf.max(h + 1);
+
+        let pixels: Vec<u8> = glyph.coverage().iter().flat_map(|&c| [255, 255, 255, c]).collect();
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(w * 4),
+                rows_per_image: Some(h),
+            },
+            wgpu::Extent3d {
+                width: w,
+                height: h,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.glyphs.insert(glyph.key, (x, y));
+        Some((x, y))
+    }
+
+    /// Forget every glyph; their texels are overwritten as new glyphs arrive
+    fn reset(&mut self) {
+        self.glyphs.clear();
+        self.cursor = (0, 0);
+        self.shelf = 0;
+    }
+}
+
+/// Chunk size of the staging belt used for framebuffer uploads
+const PRESENT_STAGING_CHUNK: u64 = 1 << 20;
+
+/// Padded bytes per row for buffer->texture copies of `width` RGBA pixels
+fn padded_bytes_per_row(width: u32) -> u32 {
+    (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+}
+
+/// Maximum distance in pixels between a curve and its tessellated polyline
+#[cfg(feature = "gpu")]
+const PATH_TOLERANCE: f32 = 0.1;
+
+/// Convert a path into a lyon path for tessellation
+#[cfg(feature = "gpu")]
+fn lyon_path(path: &crate::path::Path) -> lyon_tessellation::path::Path {
+    use crate::path::PathSegment;
+    use lyon_tessellation::math::point;
+
+    let mut builder = lyon_tessellation::path::Path::builder();
+    let mut open = false;
+    let mut start = point(0.0, 0.0);
+    let mut current = start;
+    for segment in path.segments() {
+        match *segment {
+            PathSegment::MoveTo(x, y) => {
+                if open {
+                    builder.end(false);
+                }
+                start = point(x, y);
+                current = start;
+                builder.begin(current);
+                open = true;
+                continue;
+            }
+            PathSegment::Close => {
+                if open {
+                    builder.end(true);
+                    open = false;
+                }
+                current = start;
+                continue;
+            }
+            _ => {}
+        }
+        // Drawing without a current subpath starts one at the last point, like tiny-skia
+        if !open {
+            builder.begin(current);
+            open = true;
         }
+        current = match *segment {
+            PathSegment::LineTo(x, y) => {
+                builder.line_to(point(x, y));
+                point(x, y)
+            }
+            PathSegment::QuadTo(cx, cy, x, y) => {
+                builder.quadratic_bezier_to(point(cx, cy), point(x, y));
+                point(x, y)
+            }
+            PathSegment::CubicTo(c1x, c1y, c2x, c2y, x, y) => {
+                builder.cubic_bezier_to(point(c1x, c1y), point(c2x, c2y), point(x, y));
+                point(x, y)
+            }
+            PathSegment::MoveTo(..) | PathSegment::Close => unreachable!(),
+        };
+    }
+    if open {
+        builder.end(false);
     }
+    builder.build()
 }
 
+/// Texture holding the last presented host framebuffer, updated in place
+struct PresentTexture {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+    /// Row-padded copy source for the texture, filled through the staging belt
+    upload: wgpu::Buffer,
+    size: (u32, u32),
+}
+
+impl PresentTexture {
+    fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Present Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+            label: Some("present_bind_group"),
+        });
+        let upload = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Present Upload Buffer"),
+            size: padded_bytes_per_row(width) as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        Self {
+            texture,
+            bind_group,
+            upload,
+            size: (width, height),
+        }
+    }
+
+    /// Record an upload of the (x, y, width, height) region of a full-size RGBA frame
+    ///
+    /// Buffer->texture copies need rows aligned to
+    /// wgpu::COPY_BYTES_PER_ROW_ALIGNMENT (256), so the rows are padded while
+    /// being written into staging memory, which the belt reuses across frames.
+    fn write_region(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        belt: &mut StagingBelt,
+        device: &wgpu::Device,
+        data: &[u8],
+        (x, y, w, h): (u32, u32, u32, u32),
+    ) {
+        let src_stride = self.size.0 as usize * 4;
+        let row_bytes = w as usize * 4;
+        let padded_row = padded_bytes_per_row(w);
+        let Some(size) = wgpu::BufferSize::new(padded_row as u64 * h as u64) else {
+            return;
+        };
+        log::debug!(
+            "present_rgba: staging upload (rows={} padded_row_bytes={})",
+            h,
+            padded_row
+        );
+        {
+            let mut staging = belt.write_buffer(encoder, &self.upload, 0, size, device);
+            for row in 0..h as usize {
+                let src = (y as usize + row) * src_stride + x as usize * 4;
+                let dst = row * padded_row as usize;
+                staging[dst..dst + row_bytes].copy_from_slice(&data[src..src + row_bytes]);
+            }
+        }
+        encoder.copy_buffer_to_texture(
+            wgpu::ImageCopyBuffer {
+                buffer: &self.upload,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(h),
+                },
+            },
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::Extent3d {
+                width: w,
+                height: h,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+
 /// GPU uniform buffer for view projection
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -82,18 +496,65 @@ impl Uniforms {
     }
 }
 
+/// Where a renderer draws its frames
+enum RenderTarget {
+    /// A window surface, presented after each frame
+    Surface {
+        surface: wgpu::Surface<'static>,
+        config: wgpu::SurfaceConfiguration,
+    },
+    /// An offscreen texture that can be read back
+    Offscreen { texture: wgpu::Texture },
+}
+
+/// Format of offscreen render targets (matches the sRGB surface formats preferred onscreen)
+const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+fn create_offscreen_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Offscreen Target"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: OFFSCREEN_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    })
+}
+
+/// A frame being drawn into the render target
+struct TargetFrame {
+    surface: Option<wgpu::SurfaceTexture>,
+    view: wgpu::TextureView,
+}
+
+impl TargetFrame {
+    /// Show the frame (a no-op for offscreen targets)
+    fn present(self) {
+        if let Some(output) = self.surface {
+            output.present();
+        }
+    }
+}
+
 /// The main wgpu renderer
 #[allow(dead_code)]
 pub struct WgpuRenderer {
-    surface: wgpu::Surface<'static>,
+    target: RenderTarget,
     device: wgpu::Device,
     queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration,
+    format: wgpu::TextureFormat,
     size: (u32, u32),
     render_pipeline: wgpu::RenderPipeline,
     texture_pipeline: wgpu::RenderPipeline,
     texture_bind_group_layout: wgpu::BindGroupLayout,
     sampler: wgpu::Sampler,
+    nearest_sampler: wgpu::Sampler,
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     uniform_buffer: wgpu::Buffer,
@@ -101,7 +562,25 @@ pub struct WgpuRenderer {
     vertices: Vec<Vertex>,
     indices: Vec<u32>,
     commands: Vec<RenderCommand>,
+    /// Paths, tessellated after the rectangles each frame
+    path_commands: Vec<PathCommand>,
+    text_commands: Vec<TextCommand>,
+    image_commands: Vec<ImageDraw>,
+    font_manager: FontManager,
+    glyph_atlas: GlyphAtlas,
+    /// Host framebuffer of `present_rgba`, kept so unchanged areas are not uploaded again
+    present_texture: Option<PresentTexture>,
+    /// Reused staging memory for framebuffer uploads
+    staging_belt: StagingBelt,
+    image_atlas: Option<ImageAtlas>,
+    /// Index ranges of the image quads, split where the filter changes
+    image_batches: Vec<(std::ops::Range<u32>, ImageFilter)>,
+    /// First index of the image quads in `indices`
+    image_index_start: usize,
+    /// First index of the text quads in `indices`
+    text_index_start: usize,
     clear_color: wgpu::Color,
+    color: ColorManager,
     max_vertices: usize,
     max_indices: usize,
 }
@@ -173,6 +652,64 @@ impl WgpuRenderer {
         };
         surface.configure(&device, &config);
 
+        Ok(Self::with_device(
+            device,
+            queue,
+            surface_format,
+            (width, height),
+            RenderTarget::Surface { surface, config },
+        ))
+    }
+
+    /// Create a renderer that draws into an offscreen texture (no window or surface needed)
+    /// Returns Err(String) when no adapter or device is available
+    pub async fn new_headless(width: u32, height: u32) -> Result<Self, String> {
+        let width = width.max(1);
+        let height = height.max(1);
+
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            })
+            .await
+            .ok_or_else(|| "Failed to find a suitable GPU adapter".to_string())?;
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    required_features: wgpu::Features::empty(),
+                    required_limits: wgpu::Limits::default(),
+                    label: None,
+                    memory_hints: Default::default(),
+                },
+                None,
+            )
+            .await
+            .map_err(|e| format!("Failed to create device: {:?}", e))?;
+
+        let texture = create_offscreen_texture(&device, width, height);
+        Ok(Self::with_device(
+            device,
+            queue,
+            OFFSCREEN_FORMAT,
+            (width, height),
+            RenderTarget::Offscreen { texture },
+        ))
+    }
+
+    /// Build pipelines and buffers for a device drawing into `target`
+    fn with_device(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        format: wgpu::TextureFormat,
+        (width, height): (u32, u32),
+        target: RenderTarget,
+    ) -> Self {
         // Create shader module
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Shader"),
@@ -253,6 +790,19 @@ impl WgpuRenderer {
             mipmap_filter: wgpu::FilterMode::Nearest,
             ..Default::default()
         });
+        let nearest_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("nearest_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        // Glyph quads are pixel-aligned, so nearest sampling keeps them crisp
+        let glyph_atlas = GlyphAtlas::new(&device, &texture_bind_group_layout, &nearest_sampler);
 
         // Create render pipeline (vertex color)
         let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
@@ -268,7 +818,7 @@ impl WgpuRenderer {
                 module: &shader,
                 entry_point: Some("fs_color"),
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
+                    format,
                     blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -332,7 +882,7 @@ impl WgpuRenderer {
                     module: &shader,
                     entry_point: Some("fs_texture"),
                     targets: &[Some(wgpu::ColorTargetState {
-                        format: config.format,
+                        format,
                         blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                         write_mask: wgpu::ColorWrites::ALL,
                     })],
@@ -358,16 +908,17 @@ impl WgpuRenderer {
             })
         };
 
-        Ok(Self {
-            surface,
+        Self {
+            target,
             device,
             queue,
-            config,
+            format,
             size: (width, height),
             render_pipeline,
             texture_pipeline,
             texture_bind_group_layout,
             sampler,
+            nearest_sampler,
             vertex_buffer,
             index_buffer,
             uniform_buffer,
@@ -375,22 +926,68 @@ impl WgpuRenderer {
             vertices: Vec::with_capacity(max_vertices),
             indices: Vec::with_capacity(max_indices),
             commands: Vec::new(),
+            path_commands: Vec::new(),
+            text_commands: Vec::new(),
+            image_commands: Vec::new(),
+            font_manager: FontManager::new(),
+            glyph_atlas,
+            present_texture: None,
+            staging_belt: StagingBelt::new(PRESENT_STAGING_CHUNK),
+            image_atlas: None,
+            image_batches: Vec::new(),
+            image_index_start: 0,
+            text_index_start: 0,
             clear_color: wgpu::Color::WHITE,
+            color: ColorManager::new(),
             max_vertices,
             max_indices,
-        })
+        }
+    }
+
+    /// Get the texture view to draw the next frame into
+    fn acquire_frame(&self) -> Result<TargetFrame, wgpu::SurfaceError> {
+        match &self.target {
+            RenderTarget::Surface { surface, .. } => {
+                let output = surface.get_current_texture()?;
+                let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+                Ok(TargetFrame {
+                    surface: Some(output),
+                    view,
+                })
+            }
+            RenderTarget::Offscreen { texture } => Ok(TargetFrame {
+                surface: None,
+                view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            }),
+        }
     }
 
     /// Present an RGBA8888 CPU buffer to the surface by uploading it as a texture
     pub fn present_rgba(&mut self, data: &[u8], src_w: u32, src_h: u32) -> Result<(), wgpu::SurfaceError> {
+        self.present_rgba_region(data, src_w, src_h, Some((0, 0, src_w, src_h)))
+    }
+
+    /// Present an RGBA8888 CPU buffer, uploading only the damaged area
+    ///
+    /// `damage` is the (x, y, width, height) area that changed since the last
+    /// present; None presents the previous upload again. The whole buffer is
+    /// uploaded when its size changed.
+    pub fn present_rgba_region(
+        &mut self,
+        data: &[u8],
+        src_w: u32,
+        src_h: u32,
+        damage: Option<(u32, u32, u32, u32)>,
+    ) -> Result<(), wgpu::SurfaceError> {
         // Basic sanity checks and debug logging to help track intermittent crashes
         log::debug!(
-            "present_rgba: requested present {}x{} (renderer size {}x{}), data_len={}",
+            "present_rgba: requested present {}x{} (renderer size {}x{}), data_len={}, damage={:?}",
             src_w,
             src_h,
             self.size.0,
             self.size.1,
-            data.len()
+            data.len(),
+            damage
         );
 
         // Validate input buffer length
@@ -407,119 +1004,53 @@ impl WgpuRenderer {
             return Ok(());
         }
 
-        // Create texture from data
-        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Present Texture"),
-            size: wgpu::Extent3d {
-                width: src_w,
-                height: src_h,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[],
-        });
-        // Write data into the texture. Some backends (Vulkan) require the bytes
-        // per row (pitch) used for buffer->texture copies to be aligned to
-        // wgpu::COPY_BYTES_PER_ROW_ALIGNMENT (256). To be robust we pad each
-        // row to that alignment when needed.
-        let bytes_per_row_unpadded = 4u32.checked_mul(src_w).unwrap_or(0);
-        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u32;
-        let padded_bytes_per_row = if bytes_per_row_unpadded % align == 0 {
-            bytes_per_row_unpadded
-        } else {
-            ((bytes_per_row_unpadded + align - 1) / align) * align
+        // The texture is only recreated when the frame size changes
+        let mut damage = damage;
+        if self.present_texture.as_ref().map(|t| t.size) != Some((src_w, src_h)) {
+            self.present_texture = Some(PresentTexture::new(
+                &self.device,
+                &self.texture_bind_group_layout,
+                &self.sampler,
+                src_w,
+                src_h,
+            ));
+            damage = Some((0, 0, src_w, src_h));
+        }
+        let Some(present) = &self.present_texture else {
+            return Ok(());
         };
-        // Log chosen upload path
-        log::debug!(
-            "present_rgba: bytes_per_row_unpadded={} padded_bytes_per_row={}",
-            bytes_per_row_unpadded,
-            padded_bytes_per_row
-        );
 
-        if padded_bytes_per_row == bytes_per_row_unpadded {
-            // Fast path: no padding required
-            log::debug!("present_rgba: using fast path upload");
-            self.queue.write_texture(
-                wgpu::ImageCopyTexture {
-                    texture: &texture,
-                    mip_level: 0,
-                    origin: wgpu::Origin3d::ZERO,
-                    aspect: wgpu::TextureAspect::All,
-                },
-                data,
-                wgpu::ImageDataLayout {
-                    offset: 0,
-                    bytes_per_row: Some(bytes_per_row_unpadded),
-                    rows_per_image: Some(src_h),
-                },
-                wgpu::Extent3d {
-                    width: src_w,
-                    height: src_h,
-                    depth_or_array_layers: 1,
-                },
-            );
-        } else {
-            // Create a padded staging buffer and copy rows into it
-            log::debug!("present_rgba: creating padded staging buffer (rows={} padded_row_bytes={})", src_h, padded_bytes_per_row);
-            let mut padded: Vec<u8> = vec![0u8; (padded_bytes_per_row * src_h) as usize];
-            for row in 0..src_h as usize {
-                let src_offset = row * (bytes_per_row_unpadded as usize);
-                let dst_offset = row * (padded_bytes_per_row as usize);
-                padded[dst_offset..dst_offset + (bytes_per_row_unpadded as usize)]
-                    .copy_from_slice(&data[src_offset..src_offset + (bytes_per_row_unpadded as usize)]);
+        // Clamp the damage to the frame; an empty area has nothing to upload
+        if let Some((x, y, w, h)) = damage {
+            let (x0, y0) = (x.min(src_w), y.min(src_h));
+            let x1 = x.saturating_add(w).min(src_w);
+            let y1 = y.saturating_add(h).min(src_h);
+            if x1 > x0 && y1 > y0 {
+                // Submitted on its own so the upload lands even if acquiring the frame fails
+                let mut encoder = self
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Present Upload Encoder") });
+                present.write_region(
+                    &mut encoder,
+                    &mut self.staging_belt,
+                    &self.device,
+                    data,
+                    (x0, y0, x1 - x0, y1 - y0),
+                );
+                self.staging_belt.finish();
+                self.queue.submit(std::iter::once(encoder.finish()));
+                self.staging_belt.recall();
             }
-
-            self.queue.write_texture(
-                wgpu::ImageCopyTexture {
-                    texture: &texture,
-                    mip_level: 0,
-                    origin: wgpu::Origin3d::ZERO,
-                    aspect: wgpu::TextureAspect::All,
-                },
-                &padded,
-                wgpu::ImageDataLayout {
-                    offset: 0,
-                    bytes_per_row: Some(padded_bytes_per_row),
-                    rows_per_image: Some(src_h),
-                },
-                wgpu::Extent3d {
-                    width: src_w,
-                    height: src_h,
-                    depth_or_array_layers: 1,
-                },
-            );
         }
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-
-        // Create bind group for this texture
-        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &self.texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&self.sampler),
-                },
-            ],
-            label: Some("present_bind_group"),
-        });
-
         // Build a fullscreen quad
         let w = self.size.0 as f32;
         let h = self.size.1 as f32;
         let vertices = vec![
-            Vertex { position: [0.0, 0.0], tex_coords: [0.0, 0.0], color: [1.0, 1.0, 1.0, 1.0] },
-            Vertex { position: [w, 0.0], tex_coords: [1.0, 0.0], color: [1.0, 1.0, 1.0, 1.0] },
-            Vertex { position: [w, h], tex_coords: [1.0, 1.0], color: [1.0, 1.0, 1.0, 1.0] },
-            Vertex { position: [0.0, h], tex_coords: [0.0, 1.0], color: [1.0, 1.0, 1.0, 1.0] },
+            Vertex { position: [0.0, 0.0], tex_coords: [0.0, 0.0], color: [1.0, 1.0, 1.0, 1.0], ..Default::default() },
+            Vertex { position: [w, 0.0], tex_coords: [1.0, 0.0], color: [1.0, 1.0, 1.0, 1.0], ..Default::default() },
+            Vertex { position: [w, h], tex_coords: [1.0, 1.0], color: [1.0, 1.0, 1.0, 1.0], ..Default::default() },
+            Vertex { position: [0.0, h], tex_coords: [0.0, 1.0], color: [1.0, 1.0, 1.0, 1.0], ..Default::default() },
         ];
         let indices: Vec<u32> = vec![0, 1, 2, 0, 2, 3];
 
@@ -529,14 +1060,13 @@ impl WgpuRenderer {
 
         // Acquire surface texture
         log::debug!("present_rgba: acquiring current surface texture");
-        let output = match self.surface.get_current_texture() {
+        let output = match self.acquire_frame() {
             Ok(o) => o,
             Err(e) => {
                 log::warn!("present_rgba: get_current_texture failed: {:?}", e);
                 return Err(e);
             }
         };
-        let view_out = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
 
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Present Encoder") });
 
@@ -544,7 +1074,7 @@ impl WgpuRenderer {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Present Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view_out,
+                    view: &output.view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(self.clear_color),
@@ -558,7 +1088,7 @@ impl WgpuRenderer {
 
             render_pass.set_pipeline(&self.texture_pipeline);
             render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
-            render_pass.set_bind_group(1, &bind_group, &[]);
+            render_pass.set_bind_group(1, &present.bind_group, &[]);
             render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
             render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
             render_pass.draw_indexed(0..6, 0, 0..1);
@@ -576,9 +1106,16 @@ impl WgpuRenderer {
     pub fn resize(&mut self, width: u32, height: u32) {
         if width > 0 && height > 0 {
             self.size = (width, height);
-            self.config.width = width;
-            self.config.height = height;
-            self.surface.configure(&self.device, &self.config);
+            match &mut self.target {
+                RenderTarget::Surface { surface, config } => {
+                    config.width = width;
+                    config.height = height;
+                    surface.configure(&self.device, config);
+                }
+                RenderTarget::Offscreen { texture } => {
+                    *texture = create_offscreen_texture(&self.device, width, height);
+                }
+            }
 
             // Update uniforms
             let uniforms = Uniforms::new(width as f32, height as f32);
@@ -589,6 +1126,7 @@ impl WgpuRenderer {
 
     /// Set the clear color
     pub fn set_clear_color(&mut self, r: f32, g: f32, b: f32, a: f32) {
+        let [r, g, b, a] = self.color.convert_color([r, g, b, a], ColorSpace::Srgb);
         self.clear_color = wgpu::Color {
             r: r as f64,
             g: g as f64,
@@ -600,24 +1138,109 @@ impl WgpuRenderer {
     /// Clear all render commands
     pub fn clear(&mut self) {
         self.commands.clear();
+        self.path_commands.clear();
+        self.text_commands.clear();
+        self.image_commands.clear();
         self.vertices.clear();
         self.indices.clear();
     }
 
-    /// Add a rectangle render command
+    /// Add a text render command (color in sRGB)
+    pub fn add_text(&mut self, mut cmd: TextCommand) {
+        let [r, g, b, a] = self
+            .color
+            .convert_color([cmd.color_r, cmd.color_g, cmd.color_b, cmd.color_a], ColorSpace::Srgb);
+        cmd.color_r = r;
+        cmd.color_g = g;
+        cmd.color_b = b;
+        cmd.color_a = a;
+        self.text_commands.push(cmd);
+    }
+
+    /// Add a path in device pixels (color in sRGB)
+    ///
+    /// Paths are tessellated into triangles without anti-aliasing and need the
+    /// `gpu` feature; without it they are skipped.
+    pub fn add_path(&mut self, mut cmd: PathCommand) {
+        cmd.color = self.color.convert_color(cmd.color, ColorSpace::Srgb);
+        self.path_commands.push(cmd);
+    }
+
+    /// Add an image draw (pixels already in the output color space)
+    pub fn add_image(&mut self, draw: ImageDraw) {
+        self.image_commands.push(draw);
+    }
+
+    /// Replace the frame's commands with ones recorded by a host renderer
+    pub fn submit_frame(&mut self, frame: GpuFrame) {
+        let [r, g, b, a] = frame.clear_color;
+        self.clear_color = wgpu::Color {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+            a: a as f64,
+        };
+        self.commands = frame.commands;
+        self.path_commands = frame.paths;
+        self.image_commands = frame.images;
+        self.text_commands = frame.text_commands;
+        for (id, font) in frame.fonts {
+            self.font_manager.insert_font(id, font);
+        }
+    }
+
+    /// Get a reference to the font manager used for text
+    pub fn font_manager(&self) -> &FontManager {
+        &self.font_manager
+    }
+
+    /// Get a mutable reference to the font manager used for text
+    pub fn font_manager_mut(&mut self) -> &mut FontManager {
+        &mut self.font_manager
+    }
+
+    /// Add a rectangle render command (color in sRGB)
     pub fn add_rect(&mut self, cmd: RenderCommand) {
+        self.add_rect_in_space(cmd, ColorSpace::Srgb);
+    }
+
+    /// Add a rectangle render command whose color is in `space`
+    pub fn add_rect_in_space(&mut self, mut cmd: RenderCommand, space: ColorSpace) {
+        let [r, g, b, a] = self
+            .color
+            .convert_color([cmd.color_r, cmd.color_g, cmd.color_b, cmd.color_a], space);
+        cmd.color_r = r;
+        cmd.color_g = g;
+        cmd.color_b = b;
+        cmd.color_a = a;
         self.commands.push(cmd);
     }
 
+    /// Get a reference to the color manager
+    pub fn color_manager(&self) -> &ColorManager {
+        &self.color
+    }
+
+    /// Get a mutable reference to the color manager
+    pub fn color_manager_mut(&mut self) -> &mut ColorManager {
+        &mut self.color
+    }
+
     /// Build vertex and index buffers from commands
     fn build_buffers(&mut self) {
         self.vertices.clear();
         self.indices.clear();
 
-        // Sort commands by z-index
+        // Sort commands by z-index and drop work that cannot change the frame
         self.commands.sort_by_key(|c| c.z_index);
+        crate::optimize::optimize_commands(&mut self.commands);
+        crate::optimize::cull_occluded(&mut self.commands);
 
         for cmd in &self.commands {
+            if self.vertices.len() + 4 > self.max_vertices || self.indices.len() + 6 > self.max_indices {
+                log::warn!("renderer: vertex buffer full, dropping {} rects", self.commands.len());
+                break;
+            }
             let base_index = self.vertices.len() as u32;
 
             let x = cmd.x;
@@ -625,27 +1248,37 @@ impl WgpuRenderer {
             let w = cmd.width;
             let h = cmd.height;
             let color = [cmd.color_r, cmd.color_g, cmd.color_b, cmd.color_a];
+            let rect_size = [w, h];
+            let radii = cmd.clamped_radii();
 
             // Add 4 vertices for the quad
             self.vertices.push(Vertex {
                 position: [x, y],
                 tex_coords: [0.0, 0.0],
                 color,
+                rect_size,
+                radii,
             });
             self.vertices.push(Vertex {
                 position: [x + w, y],
                 tex_coords: [1.0, 0.0],
                 color,
+                rect_size,
+                radii,
             });
             self.vertices.push(Vertex {
                 position: [x + w, y + h],
                 tex_coords: [1.0, 1.0],
                 color,
+                rect_size,
+                radii,
             });
             self.vertices.push(Vertex {
                 position: [x, y + h],
                 tex_coords: [0.0, 1.0],
                 color,
+                rect_size,
+                radii,
             });
 
             // Add 6 indices for 2 triangles
@@ -656,18 +1289,323 @@ impl WgpuRenderer {
             self.indices.push(base_index + 2);
             self.indices.push(base_index + 3);
         }
+
+        // Paths follow the rects and share their pipeline (square-cornered vertices)
+        #[cfg(feature = "gpu")]
+        self.build_path_buffers();
+    }
+
+    /// Tessellate every path into colored triangles
+    #[cfg(feature = "gpu")]
+    fn build_path_buffers(&mut self) {
+        use lyon_tessellation::{
+            BuffersBuilder, FillOptions, FillTessellator, FillVertex, StrokeOptions, StrokeTessellator, StrokeVertex,
+            VertexBuffers,
+        };
+
+        let mut fill = FillTessellator::new();
+        let mut stroke = StrokeTessellator::new();
+        for cmd in &self.path_commands {
+            let path = lyon_path(&cmd.path);
+            let color = cmd.color;
+            let mut geometry: VertexBuffers<Vertex, u32> = VertexBuffers::new();
+            let result = match cmd.paint {
+                crate::path::PathPaint::Fill(rule) => {
+                    let rule = match rule {
+                        crate::path::FillRule::NonZero => lyon_tessellation::FillRule::NonZero,
+                        crate::path::FillRule::EvenOdd => lyon_tessellation::FillRule::EvenOdd,
+                    };
+                    fill.tessellate_path(
+                        &path,
+                        &FillOptions::tolerance(PATH_TOLERANCE).with_fill_rule(rule),
+                        &mut BuffersBuilder::new(&mut geometry, |v: FillVertex| Vertex {
+                            position: v.position().to_array(),
+                            color,
+                            ..Default::default()
+                        }),
+                    )
+                }
+                crate::path::PathPaint::Stroke(style) => stroke.tessellate_path(
+                    &path,
+                    &StrokeOptions::tolerance(PATH_TOLERANCE)
+                        .with_line_width(style.width)
+                        .with_line_cap(match style.cap {
+                            crate::path::LineCap::Butt => lyon_tessellation::LineCap::Butt,
+                            crate::path::LineCap::Round => lyon_tessellation::LineCap::Round,
+                            crate::path::LineCap::Square => lyon_tessellation::LineCap::Square,
+                        })
+                        .with_line_join(match style.join {
+                            crate::path::LineJoin::Miter => lyon_tessellation::LineJoin::Miter,
+                            crate::path::LineJoin::Round => lyon_tessellation::LineJoin::Round,
+                        }),
+                    &mut BuffersBuilder::new(&mut geometry, |v: StrokeVertex| Vertex {
+                        position: v.position().to_array(),
+                        color,
+                        ..Default::default()
+                    }),
+                ),
+            };
+            if let Err(e) = result {
+                log::warn!("renderer: failed to tessellate path: {:?}", e);
+                continue;
+            }
+            if self.vertices.len() + geometry.vertices.len() > self.max_vertices
+                || self.indices.len() + geometry.indices.len() > self.max_indices
+            {
+                log::warn!("renderer: vertex buffer full, dropping paths");
+                break;
+            }
+            let base_index = self.vertices.len() as u32;
+            self.vertices.extend_from_slice(&geometry.vertices);
+            self.indices.extend(geometry.indices.iter().map(|i| base_index + i));
+        }
+    }
+
+    /// Pack the frame's images into the atlas unless the previous atlas holds the same images
+    fn update_image_atlas(&mut self) {
+        let mut keys: Vec<ImageKey> = Vec::new();
+        let mut images: Vec<&GpuImage> = Vec::new();
+        for draw in &self.image_commands {
+            let key = image_key(&draw.image);
+            if !keys.contains(&key) {
+                keys.push(key);
+                images.push(&draw.image);
+            }
+        }
+        if keys.is_empty() || self.image_atlas.as_ref().is_some_and(|a| a.keys == keys) {
+            return;
+        }
+
+        // Shelf-pack with a one pixel gutter so bilinear sampling never reads a neighbour
+        let max_height = self.device.limits().max_texture_dimension_2d;
+        let mut placements = Vec::with_capacity(keys.len());
+        let (mut x, mut y, mut shelf) = (0u32, 0u32, 0u32);
+        for image in &images {
+            let (w, h) = (image.width, image.height);
+            let len = w as usize * h as usize * 4;
+            if w == 0 || h == 0 || w > IMAGE_ATLAS_WIDTH || image.pixels.len() < len {
+                placements.push(None);
+                continue;
+            }
+            if x + w > IMAGE_ATLAS_WIDTH {
+                x = 0;
+                y += shelf;
+                shelf = 0;
+            }
+            if y + h > max_height {
+                log::warn!("renderer: image atlas full, dropping {}x{} image", w, h);
+                placements.push(None);
+                continue;
+            }
+            placements.push(Some((x, y)));
+            x += w + 1;
+            shelf = shelf.max(h + 1);
+        }
+        let height = (y + shelf).max(1);
+
+        // The texture pipeline blends straight alpha
+        let mut pixels = vec![0u8; (IMAGE_ATLAS_WIDTH * height * 4) as usize];
+        for (placement, image) in placements.iter().zip(&images) {
+            let Some((px, py)) = *placement else {
+                continue;
+            };
+            let row_len = (image.width * 4) as usize;
+            for row in 0..image.height {
+                let src = row as usize * row_len;
+                let dst = (((py + row) * IMAGE_ATLAS_WIDTH + px) * 4) as usize;
+                pixels[dst..dst + row_len].copy_from_slice(&image.pixels[src..src + row_len]);
+                crate::color::unpremultiply(&mut pixels[dst..dst + row_len]);
+            }
+        }
+
+        let size = wgpu::Extent3d {
+            width: IMAGE_ATLAS_WIDTH,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Image Atlas"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(IMAGE_ATLAS_WIDTH * 4),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = |sampler: &wgpu::Sampler, label| {
+            self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.texture_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                ],
+                label: Some(label),
+            })
+        };
+        let bilinear = bind_group(&self.sampler, "image_atlas_bilinear_bind_group");
+        let nearest = bind_group(&self.nearest_sampler, "image_atlas_nearest_bind_group");
+
+        self.image_atlas = Some(ImageAtlas {
+            keys,
+            placements,
+            width: IMAGE_ATLAS_WIDTH,
+            height,
+            bilinear,
+            nearest,
+        });
+    }
+
+    /// Append one textured quad per placed image after the rect quads
+    fn build_image_buffers(&mut self) {
+        self.image_index_start = self.indices.len();
+        self.image_batches.clear();
+        let Some(atlas) = &self.image_atlas else {
+            return;
+        };
+
+        self.image_commands.sort_by_key(|d| d.z_index);
+        let (aw, ah) = (atlas.width as f32, atlas.height as f32);
+        for draw in &self.image_commands {
+            let key = image_key(&draw.image);
+            let Some(slot) = atlas.keys.iter().position(|k| *k == key) else {
+                continue;
+            };
+            let Some((px, py)) = atlas.placements[slot] else {
+                continue;
+            };
+            if draw.width <= 0.0 || draw.height <= 0.0 || draw.opacity <= 0.0 {
+                continue;
+            }
+            if self.vertices.len() + 4 > self.max_vertices || self.indices.len() + 6 > self.max_indices {
+                log::warn!("renderer: vertex buffer full, dropping images");
+                break;
+            }
+            let base_index = self.vertices.len() as u32;
+            let (x, y, w, h) = (draw.x, draw.y, draw.width, draw.height);
+            // Bilinear samples stay half a texel inside the image so edges don't fade into the gutter
+            let inset = match draw.filter {
+                ImageFilter::Bilinear => 0.5,
+                ImageFilter::Nearest => 0.0,
+            };
+            let (u0, v0) = ((px as f32 + inset) / aw, (py as f32 + inset) / ah);
+            let (u1, v1) = (
+                (px as f32 + draw.image.width as f32 - inset) / aw,
+                (py as f32 + draw.image.height as f32 - inset) / ah,
+            );
+            let color = [1.0, 1.0, 1.0, draw.opacity.min(1.0)];
+            self.vertices.push(Vertex { position: [x, y], tex_coords: [u0, v0], color, ..Default::default() });
+            self.vertices.push(Vertex { position: [x + w, y], tex_coords: [u1, v0], color, ..Default::default() });
+            self.vertices.push(Vertex { position: [x + w, y + h], tex_coords: [u1, v1], color, ..Default::default() });
+            self.vertices.push(Vertex { position: [x, y + h], tex_coords: [u0, v1], color, ..Default::default() });
+            self.indices.extend_from_slice(&[
+                base_index,
+                base_index + 1,
+                base_index + 2,
+                base_index,
+                base_index + 2,
+                base_index + 3,
+            ]);
+
+            let end = self.indices.len() as u32;
+            match self.image_batches.last_mut() {
+                Some((range, filter)) if *filter == draw.filter => range.end = end,
+                _ => self.image_batches.push((end - 6..end, draw.filter)),
+            }
+        }
+    }
+
+    /// Append one textured quad per glyph after the image quads
+    fn build_text_buffers(&mut self) {
+        self.text_index_start = self.indices.len();
+        let vertex_start = self.vertices.len();
+        for attempt in 0..2 {
+            if self.push_glyph_quads() {
+                return;
+            }
+            if attempt == 0 {
+                // The atlas filled up: start over holding only this frame's glyphs
+                self.vertices.truncate(vertex_start);
+                self.indices.truncate(self.text_index_start);
+                self.glyph_atlas.reset();
+            }
+        }
+        log::warn!("renderer: glyph atlas full, dropping glyphs");
+    }
+
+    /// Push glyph quads for every text command; false if the glyph atlas ran out of space
+    fn push_glyph_quads(&mut self) -> bool {
+        let size = GLYPH_ATLAS_SIZE as f32;
+        for cmd in &self.text_commands {
+            let run = self.font_manager.layout_run(&cmd.text, cmd.font_size, cmd.font_id, cmd.spacing);
+            // Runs are blitted at integer positions like the software renderer
+            let (ox, oy) = (cmd.x.trunc(), cmd.y.trunc());
+            let color = [cmd.color_r, cmd.color_g, cmd.color_b, cmd.color_a];
+            for glyph in &run.glyphs {
+                if glyph.width >= GLYPH_ATLAS_SIZE || glyph.height >= GLYPH_ATLAS_SIZE {
+                    continue;
+                }
+                let Some((px, py)) = self.glyph_atlas.insert(&self.queue, glyph) else {
+                    return false;
+                };
+                if self.vertices.len() + 4 > self.max_vertices || self.indices.len() + 6 > self.max_indices {
+                    log::warn!("renderer: vertex buffer full, dropping glyphs");
+                    return true;
+                }
+                let base_index = self.vertices.len() as u32;
+                let (x, y) = (ox + glyph.x.trunc(), oy + glyph.y.trunc());
+                let (w, h) = (glyph.width as f32, glyph.height as f32);
+                let (u0, v0) = (px as f32 / size, py as f32 / size);
+                let (u1, v1) = ((px as f32 + w) / size, (py as f32 + h) / size);
+                self.vertices.push(Vertex { position: [x, y], tex_coords: [u0, v0], color, ..Default::default() });
+                self.vertices.push(Vertex { position: [x + w, y], tex_coords: [u1, v0], color, ..Default::default() });
+                self.vertices.push(Vertex { position: [x + w, y + h], tex_coords: [u1, v1], color, ..Default::default() });
+                self.vertices.push(Vertex { position: [x, y + h], tex_coords: [u0, v1], color, ..Default::default() });
+                self.indices.extend_from_slice(&[
+                    base_index,
+                    base_index + 1,
+                    base_index + 2,
+                    base_index,
+                    base_index + 2,
+                    base_index + 3,
+                ]);
+            }
+        }
+        true
     }
 
     /// Render the current frame
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        // Build buffers from commands
+        // Build buffers from commands; images follow the rects and text follows the images
         self.build_buffers();
+        self.update_image_atlas();
+        self.build_image_buffers();
+        self.build_text_buffers();
 
-        // Get surface texture
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        // Get the target texture
+        let output = self.acquire_frame()?;
 
         // Upload vertex data
         if !self.vertices.is_empty() {
@@ -693,7 +1631,7 @@ impl WgpuRenderer {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view: &output.view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(self.clear_color),
@@ -706,11 +1644,29 @@ impl WgpuRenderer {
             });
 
             if !self.indices.is_empty() {
-                render_pass.set_pipeline(&self.render_pipeline);
                 render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
                 render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
                 render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-                render_pass.draw_indexed(0..self.indices.len() as u32, 0, 0..1);
+                if self.image_index_start > 0 {
+                    render_pass.set_pipeline(&self.render_pipeline);
+                    render_pass.draw_indexed(0..self.image_index_start as u32, 0, 0..1);
+                }
+                if let Some(atlas) = &self.image_atlas {
+                    render_pass.set_pipeline(&self.texture_pipeline);
+                    for (range, filter) in &self.image_batches {
+                        let bind_group = match filter {
+                            ImageFilter::Bilinear => &atlas.bilinear,
+                            ImageFilter::Nearest => &atlas.nearest,
+                        };
+                        render_pass.set_bind_group(1, bind_group, &[]);
+                        render_pass.draw_indexed(range.clone(), 0, 0..1);
+                    }
+                }
+                if self.indices.len() > self.text_index_start {
+                    render_pass.set_pipeline(&self.texture_pipeline);
+                    render_pass.set_bind_group(1, &self.glyph_atlas.bind_group, &[]);
+                    render_pass.draw_indexed(self.text_index_start as u32..self.indices.len() as u32, 0, 0..1);
+                }
             }
         }
 
@@ -732,21 +1688,29 @@ impl WgpuRenderer {
         let size = (width * height * 4) as usize;
         let mut pixels = vec![0u8; size];
 
-        // Create a texture to copy into
-        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Copy Texture"),
-            size: wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: self.config.format,
-            usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[],
-        });
+        // Offscreen targets are read directly; surface textures can't be copied
+        // after presenting, so those read back a fresh texture
+        let copy_texture;
+        let texture = match &self.target {
+            RenderTarget::Offscreen { texture } => texture,
+            RenderTarget::Surface { .. } => {
+                copy_texture = self.device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("Copy Texture"),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: self.format,
+                    usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                });
+                &copy_texture
+            }
+        };
 
         // Create a buffer to copy texture data into
         let bytes_per_row = (width * 4 + 255) & !255; // Align to 256 bytes
@@ -766,7 +1730,7 @@ impl WgpuRenderer {
 
         encoder.copy_texture_to_buffer(
             wgpu::ImageCopyTexture {
-                texture: &texture,
+                texture,
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All,
@@ -809,3 +1773,110 @@ impl WgpuRenderer {
         pixels
     }
 }
+
+/// wgpu renderer drawing into an offscreen texture, with the software renderer's headless API
+///
+/// `render()` draws the frame on the GPU and reads it back, so the
+/// framebuffer can be inspected or exported like a software frame. Pixels
+/// are RGBA8 in the output color space.
+pub struct WgpuHeadlessRenderer {
+    renderer: WgpuRenderer,
+    framebuffer: Vec<u8>,
+}
+
+impl WgpuHeadlessRenderer {
+    /// Create a headless GPU renderer (blocks until the device is ready)
+    /// Returns Err(String) when no adapter or device is available
+    pub fn new(width: u32, height: u32) -> Result<Self, String> {
+        let renderer = pollster::block_on(WgpuRenderer::new_headless(width, height))?;
+        let (w, h) = renderer.size();
+        Ok(Self {
+            renderer,
+            framebuffer: vec![0; (w * h * 4) as usize],
+        })
+    }
+
+    /// Get the current size
+    pub fn size(&self) -> (u32, u32) {
+        self.renderer.size()
+    }
+
+    /// Resize the offscreen target (the framebuffer is cleared until the next render)
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.renderer.resize(width.max(1), height.max(1));
+        let (w, h) = self.renderer.size();
+        self.framebuffer = vec![0; (w * h * 4) as usize];
+    }
+
+    /// Set the clear color (sRGB)
+    pub fn set_clear_color(&mut self, r: f32, g: f32, b: f32, a: f32) {
+        self.renderer.set_clear_color(r, g, b, a);
+    }
+
+    /// Clear all render commands
+    pub fn clear(&mut self) {
+        self.renderer.clear();
+    }
+
+    /// Add a rectangle render command (color in sRGB)
+    pub fn add_rect(&mut self, cmd: RenderCommand) {
+        self.renderer.add_rect(cmd);
+    }
+
+    /// Add a text render command (color in sRGB)
+    pub fn add_text(&mut self, cmd: TextCommand) {
+        self.renderer.add_text(cmd);
+    }
+
+    /// Add a path in device pixels (color in sRGB)
+    pub fn add_path(&mut self, cmd: PathCommand) {
+        self.renderer.add_path(cmd);
+    }
+
+    /// Add an image draw (pixels already in the output color space)
+    pub fn add_image(&mut self, draw: ImageDraw) {
+        self.renderer.add_image(draw);
+    }
+
+    /// Replace the frame's commands with ones recorded by a host renderer
+    pub fn submit_frame(&mut self, frame: GpuFrame) {
+        self.renderer.submit_frame(frame);
+    }
+
+    /// Get a mutable reference to the font manager used for text
+    pub fn font_manager_mut(&mut self) -> &mut FontManager {
+        self.renderer.font_manager_mut()
+    }
+
+    /// Get a mutable reference to the color manager
+    pub fn color_manager_mut(&mut self) -> &mut ColorManager {
+        self.renderer.color_manager_mut()
+    }
+
+    /// Draw the frame and read it back into the framebuffer
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        self.renderer.render()?;
+        self.framebuffer = self.renderer.read_pixels();
+        Ok(())
+    }
+
+    /// Get the framebuffer read back by the last `render()`
+    pub fn get_framebuffer(&self) -> &[u8] {
+        &self.framebuffer
+    }
+
+    /// Export the framebuffer to a PNG file
+    pub fn export_png(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let (width, height) = self.size();
+        let file = std::fs::File::create(path)?;
+        let w = std::io::BufWriter::new(file);
+        let mut encoder = png::Encoder::new(w, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.framebuffer)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+//! Retained rectangle commands
+//!
+//! Immediate-mode commands are cleared and re-added every frame. Retained
+//! commands instead live in the renderer until removed and are addressed by
+//! a handle, so the host can move or recolor one rectangle without sending
+//! the rest of the frame again. They are drawn together with the immediate
+//! rectangles, ordered by z-index.
+//!
+//! Geometry is in device pixels and colors are sRGB; the graphics state of
+//! the software renderer does not apply.
+
+use std::collections::BTreeMap;
+
+use crate::renderer::RenderCommand;
+
+/// Handle to a retained command (0 = no command)
+pub type CommandId = u32;
+
+/// Collection of retained rectangle commands
+#[derive(Default)]
+pub struct RetainedCommands {
+    commands: BTreeMap<CommandId, RenderCommand>,
+    next_id: CommandId,
+}
+
+impl RetainedCommands {
+    /// Create an empty collection
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Retain a command and return its handle
+    pub fn create(&mut self, cmd: RenderCommand) -> CommandId {
+        self.next_id += 1;
+        self.commands.insert(self.next_id, cmd);
+        self.next_id
+    }
+
+    /// Remove a command
+    pub fn remove(&mut self, id: CommandId) -> bool {
+        self.commands.remove(&id).is_some()
+    }
+
+    /// Get a command
+    pub fn get(&self, id: CommandId) -> Option<&RenderCommand> {
+        self.commands.get(&id)
+    }
+
+    /// Get a command mutably
+    pub fn get_mut(&mut self, id: CommandId) -> Option<&mut RenderCommand> {
+        self.commands.get_mut(&id)
+    }
+
+    /// Remove all commands
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    /// Number of retained commands
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Check if there are no retained commands
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Commands in creation order
+    pub fn iter(&self) -> impl Iterator<Item = (CommandId, &RenderCommand)> {
+        self.commands.iter().map(|(id, cmd)| (*id, cmd))
+    }
+}
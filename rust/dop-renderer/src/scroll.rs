@@ -0,0 +1,473 @@
+//! Scroll input processing
+//!
+//! Trackpads and high-resolution wheels can deliver dozens of scroll events
+//! per frame. `ScrollProcessor` sums them into a single `MouseScroll` event
+//! per event loop iteration and, when kinetic scrolling is enabled, keeps
+//! producing `MomentumScroll` events with a decaying velocity after the
+//! fingers lift off a trackpad.
+//!
+//! `ScrollArea` builds on it for embedders: it owns a viewport over larger
+//! content, turns wheel, trackpad and drag input into clamped offsets with
+//! optional momentum, and lays out overlay scrollbar thumbs.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::window::{DopEvent, EventType};
+
+/// Only samples this recent contribute to the release velocity
+const VELOCITY_WINDOW: Duration = Duration::from_millis(100);
+
+/// Pixels scrolled per line of a notched wheel
+const LINE_HEIGHT: f64 = 40.0;
+
+/// Deceleration parameters for synthesized momentum
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MomentumCurve {
+    /// Fraction of velocity kept per 1/60 s
+    pub decay_per_frame: f64,
+    /// Momentum stops below this speed (pixels per second)
+    pub min_velocity: f64,
+    /// Release speeds are clamped to this (pixels per second)
+    pub max_velocity: f64,
+}
+
+impl MomentumCurve {
+    /// Curve approximating the host platform's native scrolling
+    pub fn platform_default() -> Self {
+        if cfg!(target_os = "macos") {
+            Self {
+                decay_per_frame: 0.95,
+                min_velocity: 20.0,
+                max_velocity: 8000.0,
+            }
+        } else if cfg!(target_os = "windows") {
+            Self {
+                decay_per_frame: 0.92,
+                min_velocity: 30.0,
+                max_velocity: 6000.0,
+            }
+        } else {
+            Self {
+                decay_per_frame: 0.93,
+                min_velocity: 25.0,
+                max_velocity: 6000.0,
+            }
+        }
+    }
+}
+
+impl Default for MomentumCurve {
+    fn default() -> Self {
+        Self::platform_default()
+    }
+}
+
+/// Coalesces scroll bursts and synthesizes kinetic momentum
+pub struct ScrollProcessor {
+    pending: Option<(f64, f64)>,
+    samples: VecDeque<(Instant, f64, f64)>,
+    velocity: (f64, f64),
+    last_tick: Option<Instant>,
+    curve: MomentumCurve,
+    kinetic: Arc<AtomicBool>,
+}
+
+impl Default for ScrollProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScrollProcessor {
+    pub fn new() -> Self {
+        Self {
+            pending: None,
+            samples: VecDeque::new(),
+            velocity: (0.0, 0.0),
+            last_tick: None,
+            curve: MomentumCurve::platform_default(),
+            kinetic: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn set_curve(&mut self, curve: MomentumCurve) {
+        self.curve = curve;
+    }
+
+    pub fn curve(&self) -> MomentumCurve {
+        self.curve
+    }
+
+    /// Shared flag enabling kinetic scrolling (can be toggled from another thread)
+    pub fn kinetic_flag(&self) -> Arc<AtomicBool> {
+        self.kinetic.clone()
+    }
+
+    /// Replace the kinetic flag with one owned by the host
+    pub fn set_kinetic_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.kinetic = flag;
+    }
+
+    /// Check if momentum is currently being generated
+    pub fn is_coasting(&self) -> bool {
+        self.velocity != (0.0, 0.0)
+    }
+
+    /// Accumulate a scroll delta; any new input cancels running momentum
+    ///
+    /// `precise` marks pixel deltas from trackpads, the only source that
+    /// feeds the release velocity.
+    pub fn push(&mut self, dx: f64, dy: f64, precise: bool, now: Instant) {
+        let (px, py) = self.pending.unwrap_or((0.0, 0.0));
+        self.pending = Some((px + dx, py + dy));
+        self.velocity = (0.0, 0.0);
+
+        if precise {
+            self.samples.push_back((now, dx, dy));
+            while self
+                .samples
+                .front()
+                .is_some_and(|(t, _, _)| now.duration_since(*t) > VELOCITY_WINDOW)
+            {
+                self.samples.pop_front();
+            }
+        } else {
+            self.samples.clear();
+        }
+    }
+
+    /// The scroll gesture ended; start momentum from the recent samples
+    pub fn release(&mut self, now: Instant) {
+        let samples = std::mem::take(&mut self.samples);
+        if !self.kinetic.load(Ordering::Relaxed) {
+            return;
+        }
+        let Some((first, _, _)) = samples.front() else {
+            return;
+        };
+
+        // The first sample's delta happened before its timestamp, so the
+        // span covers one frame more than the sample times
+        let span = now.duration_since(*first).as_secs_f64() + 1.0 / 60.0;
+        let (sx, sy) = samples
+            .iter()
+            .fold((0.0, 0.0), |(sx, sy), (_, dx, dy)| (sx + dx, sy + dy));
+        let max = self.curve.max_velocity;
+        let velocity = ((sx / span).clamp(-max, max), (sy / span).clamp(-max, max));
+
+        if velocity.0.hypot(velocity.1) >= self.curve.min_velocity {
+            self.velocity = velocity;
+            self.last_tick = Some(now);
+        }
+    }
+
+    /// Stop momentum immediately
+    pub fn cancel(&mut self) {
+        self.velocity = (0.0, 0.0);
+        self.samples.clear();
+    }
+
+    /// Produce at most one coalesced scroll and one momentum event for this frame
+    pub fn tick(&mut self, x: f64, y: f64, now: Instant) -> Vec<DopEvent> {
+        let mut events = Vec::new();
+
+        if let Some((dx, dy)) = self.pending.take() {
+            events.push(DopEvent::mouse_scroll(x, y, dx, dy));
+        }
+
+        if self.is_coasting() {
+            if !self.kinetic.load(Ordering::Relaxed) {
+                self.cancel();
+                return events;
+            }
+            let dt = self
+                .last_tick
+                .map_or(0.0, |t| now.duration_since(t).as_secs_f64());
+            self.last_tick = Some(now);
+            if dt > 0.0 {
+                let (vx, vy) = self.velocity;
+                events.push(DopEvent::momentum_scroll(x, y, vx * dt, vy * dt));
+
+                let decay = self.curve.decay_per_frame.powf(dt * 60.0);
+                self.velocity = (vx * decay, vy * decay);
+                if self.velocity.0.hypot(self.velocity.1) < self.curve.min_velocity {
+                    self.velocity = (0.0, 0.0);
+                }
+            }
+        }
+
+        events
+    }
+}
+
+/// Size of the overlay scrollbar thumbs laid out by `ScrollArea`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScrollbarStyle {
+    /// Thumb width across the scrolling direction
+    pub thickness: f32,
+    /// Gap between the thumb and the viewport edges
+    pub margin: f32,
+    /// Thumbs never get shorter than this, however long the content
+    pub min_length: f32,
+}
+
+impl Default for ScrollbarStyle {
+    fn default() -> Self {
+        Self {
+            thickness: 6.0,
+            margin: 2.0,
+            min_length: 24.0,
+        }
+    }
+}
+
+/// A viewport scrolled over larger content
+///
+/// Deltas follow `MouseScroll` events: positive values move the content
+/// right and down, toward the start, so the offset shrinks. Offsets are
+/// always clamped to `0..=content - viewport` on each axis.
+pub struct ScrollArea {
+    viewport: (f64, f64),
+    content: (f64, f64),
+    offset: (f64, f64),
+    /// Last pointer position of a drag in progress
+    drag: Option<(f64, f64)>,
+    momentum: ScrollProcessor,
+    scrollbar: ScrollbarStyle,
+}
+
+impl ScrollArea {
+    /// Create a scroll area at offset (0, 0); kinetic scrolling starts disabled
+    pub fn new(width: f64, height: f64, content_width: f64, content_height: f64) -> Self {
+        Self {
+            viewport: (width.max(0.0), height.max(0.0)),
+            content: (content_width.max(0.0), content_height.max(0.0)),
+            offset: (0.0, 0.0),
+            drag: None,
+            momentum: ScrollProcessor::new(),
+            scrollbar: ScrollbarStyle::default(),
+        }
+    }
+
+    pub fn viewport_size(&self) -> (f64, f64) {
+        self.viewport
+    }
+
+    pub fn content_size(&self) -> (f64, f64) {
+        self.content
+    }
+
+    /// Resize the viewport, keeping the offset in range
+    pub fn set_viewport_size(&mut self, width: f64, height: f64) {
+        self.viewport = (width.max(0.0), height.max(0.0));
+        self.offset = self.clamp(self.offset);
+    }
+
+    /// Resize the content, keeping the offset in range
+    pub fn set_content_size(&mut self, width: f64, height: f64) {
+        self.content = (width.max(0.0), height.max(0.0));
+        self.offset = self.clamp(self.offset);
+    }
+
+    /// Current scroll offset of the viewport's top-left corner into the content
+    pub fn offset(&self) -> (f64, f64) {
+        self.offset
+    }
+
+    /// Largest offset on each axis (0 where the content fits)
+    pub fn max_offset(&self) -> (f64, f64) {
+        (
+            (self.content.0 - self.viewport.0).max(0.0),
+            (self.content.1 - self.viewport.1).max(0.0),
+        )
+    }
+
+    fn clamp(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let (mx, my) = self.max_offset();
+        (x.clamp(0.0, mx), y.clamp(0.0, my))
+    }
+
+    /// Jump to an offset, stopping any momentum
+    pub fn scroll_to(&mut self, x: f64, y: f64) {
+        self.momentum.cancel();
+        self.offset = self.clamp((x, y));
+    }
+
+    /// Enable or disable momentum after trackpad gestures and drags
+    pub fn set_kinetic(&mut self, enabled: bool) {
+        self.momentum.kinetic_flag().store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn set_curve(&mut self, curve: MomentumCurve) {
+        self.momentum.set_curve(curve);
+    }
+
+    pub fn scrollbar_style(&self) -> ScrollbarStyle {
+        self.scrollbar
+    }
+
+    pub fn set_scrollbar_style(&mut self, style: ScrollbarStyle) {
+        self.scrollbar = style;
+    }
+
+    /// Check if momentum is still moving the offset
+    pub fn is_animating(&self) -> bool {
+        self.momentum.is_coasting()
+    }
+
+    /// Move the offset by the deltas of this frame's events
+    ///
+    /// Returns true if the offset changed. Momentum that runs into the edge
+    /// on every axis it moves along is stopped.
+    fn apply(&mut self, events: Vec<DopEvent>) -> bool {
+        let mut changed = false;
+        for event in events {
+            let target = (self.offset.0 - event.scroll_x, self.offset.1 - event.scroll_y);
+            let clamped = self.clamp(target);
+            changed |= clamped != self.offset;
+            if event.event_type == EventType::MomentumScroll && clamped == self.offset {
+                self.momentum.cancel();
+            }
+            self.offset = clamped;
+        }
+        changed
+    }
+
+    /// Scroll by a wheel or trackpad delta; `precise` marks pixel deltas, otherwise they count lines
+    ///
+    /// Returns true if the offset changed.
+    pub fn scroll(&mut self, dx: f64, dy: f64, precise: bool, now: Instant) -> bool {
+        let scale = if precise { 1.0 } else { LINE_HEIGHT };
+        self.momentum.push(dx * scale, dy * scale, precise, now);
+        let events = self.momentum.tick(0.0, 0.0, now);
+        self.apply(events)
+    }
+
+    /// The trackpad gesture ended; start momentum if kinetic scrolling is enabled
+    pub fn release(&mut self, now: Instant) {
+        self.momentum.release(now);
+    }
+
+    /// Start dragging the content with the pointer at (x, y), stopping any momentum
+    pub fn drag_begin(&mut self, x: f64, y: f64) {
+        self.momentum.cancel();
+        self.drag = Some((x, y));
+    }
+
+    /// Move a drag in progress; the content follows the pointer
+    ///
+    /// Returns true if the offset changed.
+    pub fn drag_move(&mut self, x: f64, y: f64, now: Instant) -> bool {
+        let Some((last_x, last_y)) = self.drag.replace((x, y)) else {
+            return false;
+        };
+        self.scroll(x - last_x, y - last_y, true, now)
+    }
+
+    /// End a drag, flinging the content if kinetic scrolling is enabled
+    pub fn drag_end(&mut self, now: Instant) {
+        if self.drag.take().is_some() {
+            self.momentum.release(now);
+        }
+    }
+
+    /// Advance momentum to `now`; returns true if the offset changed
+    pub fn tick(&mut self, now: Instant) -> bool {
+        let events = self.momentum.tick(0.0, 0.0, now);
+        self.apply(events)
+    }
+
+    /// Thumb rectangles as `[x, y, width, height]` relative to the viewport, vertical first
+    ///
+    /// Only axes whose content overflows get a thumb. When both do, each
+    /// track stops short of the corner the other one occupies.
+    pub fn scrollbar_thumbs(&self) -> Vec<[f32; 4]> {
+        let ScrollbarStyle {
+            thickness,
+            margin,
+            min_length,
+        } = self.scrollbar;
+        let (vw, vh) = (self.viewport.0 as f32, self.viewport.1 as f32);
+        let (max_x, max_y) = self.max_offset();
+        let (vertical, horizontal) = (max_y > 0.0, max_x > 0.0);
+        let corner = thickness + margin;
+        // Thumb start and length along a track, given the axis' viewport, content and offset
+        let thumb = |track: f32, viewport: f64, content: f64, offset: f64, max: f64| {
+            let length = (track * (viewport / content) as f32).max(min_length).min(track);
+            (margin + (track - length) * (offset / max) as f32, length)
+        };
+
+        let mut thumbs = Vec::new();
+        if vertical {
+            let track = vh - 2.0 * margin - if horizontal { corner } else { 0.0 };
+            if track > 0.0 {
+                let (y, length) = thumb(track, self.viewport.1, self.content.1, self.offset.1, max_y);
+                thumbs.push([vw - corner, y, thickness, length]);
+            }
+        }
+        if horizontal {
+            let track = vw - 2.0 * margin - if vertical { corner } else { 0.0 };
+            if track > 0.0 {
+                let (x, length) = thumb(track, self.viewport.0, self.content.0, self.offset.0, max_x);
+                thumbs.push([x, vh - corner, length, thickness]);
+            }
+        }
+        thumbs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scroll_area() {
+        let mut area = ScrollArea::new(100.0, 100.0, 100.0, 400.0);
+        let t0 = Instant::now();
+        // Three wheel lines down, then far past the end
+        assert!(area.scroll(0.0, -3.0, false, t0));
+        assert_eq!(area.offset(), (0.0, 120.0));
+        area.scroll(0.0, -1000.0, true, t0);
+        assert_eq!(area.offset(), (0.0, 300.0));
+        assert!(!area.scroll(0.0, -10.0, true, t0));
+        area.scroll_to(0.0, 0.0);
+
+        // Without kinetic scrolling a fling stops where the pointer let go
+        let fling = |area: &mut ScrollArea| {
+            area.drag_begin(50.0, 90.0);
+            for i in 1..=4 {
+                area.drag_move(50.0, 90.0 - 10.0 * i as f64, t0 + Duration::from_millis(16 * i));
+            }
+            area.drag_end(t0 + Duration::from_millis(64));
+        };
+        fling(&mut area);
+        assert_eq!(area.offset(), (0.0, 40.0));
+        assert!(!area.is_animating());
+
+        // With it the content keeps moving, then comes to rest at the end
+        area.scroll_to(0.0, 0.0);
+        area.set_kinetic(true);
+        fling(&mut area);
+        assert!(area.is_animating());
+        assert!(area.tick(t0 + Duration::from_millis(80)));
+        assert!(area.offset().1 > 40.0);
+        let mut t = t0 + Duration::from_millis(80);
+        while area.is_animating() {
+            t += Duration::from_millis(16);
+            area.tick(t);
+            assert!(area.offset().1 <= 300.0);
+        }
+
+        // One thumb for the vertical overflow, at the bottom of its track at the end
+        area.scroll_to(0.0, 300.0);
+        assert_eq!(area.scrollbar_thumbs(), vec![[92.0, 74.0, 6.0, 24.0]]);
+
+        // Content that fits gets no scrollbars
+        area.set_content_size(100.0, 50.0);
+        assert_eq!(area.offset(), (0.0, 0.0));
+        assert!(area.scrollbar_thumbs().is_empty());
+    }
+}
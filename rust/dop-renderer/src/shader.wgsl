@@ -11,12 +11,16 @@ struct VertexInput {
     @location(0) position: vec2<f32>,
     @location(1) tex_coords: vec2<f32>,
     @location(2) color: vec4<f32>,
+    @location(3) rect_size: vec2<f32>,
+    @location(4) radii: vec4<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
     @location(1) color: vec4<f32>,
+    @location(2) rect_size: vec2<f32>,
+    @location(3) radii: vec4<f32>,
 }
 
 @vertex
@@ -25,6 +29,8 @@ fn vs_main(input: VertexInput) -> VertexOutput {
     output.clip_position = uniforms.view_proj * vec4<f32>(input.position, 0.0, 1.0);
     output.tex_coords = input.tex_coords;
     output.color = input.color;
+    output.rect_size = input.rect_size;
+    output.radii = input.radii;
     return output;
 }
 
@@ -32,8 +38,38 @@ fn vs_main(input: VertexInput) -> VertexOutput {
 
 @fragment
 fn fs_color(input: VertexOutput) -> @location(0) vec4<f32> {
-    // Output the vertex color
-    return input.color;
+    let r = input.radii;
+    if (max(max(r.x, r.y), max(r.z, r.w)) <= 0.0) {
+        // Square corners: output the vertex color
+        return input.color;
+    }
+
+    // Pick the radius of the nearest corner (top-left, top-right, bottom-right, bottom-left)
+    let size = input.rect_size;
+    let p = input.tex_coords * size;
+    let right = p.x > size.x * 0.5;
+    let bottom = p.y > size.y * 0.5;
+    var radius = r.x;
+    if (right && !bottom) {
+        radius = r.y;
+    } else if (right && bottom) {
+        radius = r.z;
+    } else if (!right && bottom) {
+        radius = r.w;
+    }
+
+    // Distance outside the corner arc, antialiased over one pixel
+    let center = vec2<f32>(
+        select(radius, size.x - radius, right),
+        select(radius, size.y - radius, bottom),
+    );
+    let d = abs(p - center);
+    let in_corner = (p.x < center.x) != right && (p.y < center.y) != bottom;
+    var coverage = 1.0;
+    if (in_corner) {
+        coverage = clamp(radius - length(d) + 0.5, 0.0, 1.0);
+    }
+    return vec4<f32>(input.color.rgb, input.color.a * coverage);
 }
 
 // Fragment shader (texture sampling)
@@ -45,6 +81,7 @@ var samp: sampler;
 @fragment
 fn fs_texture(input: VertexOutput) -> @location(0) vec4<f32> {
     // Sample the provided texture using the vertex tex_coords
+    // and apply the vertex color as a tint (white for text and presents)
     let c = textureSample(tex, samp, input.tex_coords);
-    return c;
+    return c * input.color;
 }
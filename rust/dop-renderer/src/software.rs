@@ -2,11 +2,40 @@
 //!
 //! Provides CPU-based 2D rendering for headless and fallback scenarios.
 
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Instant;
+
 #[cfg(feature = "software")]
-use tiny_skia::{Color, Paint, PathBuilder, Pixmap, Rect, Transform};
+use tiny_skia::{Color, Mask, Paint, PathBuilder, Pixmap, PixmapMut, Rect, Transform};
+
+#[cfg(feature = "images")]
+use crate::images::{DecodedImage, ImageCache, ImageId};
+use crate::layers::{LayerId, LayerStore};
+use crate::blend;
+use crate::caret::{Caret, FocusRing, SharedCaret};
+use crate::color::{self, ColorManager, ColorSpace};
+use crate::damage::FrameSnapshot;
+use crate::encode::{self, ExportFormat};
+use crate::ffi_guard::LockExt;
+use crate::optimize;
+use crate::path::{FillRule, LineCap, LineJoin, Path, PathCommand, PathPaint, PathSegment, StrokeStyle};
+use crate::renderer::{ImageFilter, RenderCommand};
+use crate::retained::{CommandId, RetainedCommands};
+use crate::scroll::ScrollArea;
+use crate::stacking::{StackingContextId, StackingContexts, ROOT_STACKING_CONTEXT};
+use crate::state::{BlendMode, ClipBounds, GraphicsState, PaintState};
+use crate::svg::SvgDocument;
+use crate::text::{FontManager, ParagraphLayout, ShapedText, TextAnchor, TextAntialias, TextDecoration, TextSpacing, TextSpan};
 
-use crate::renderer::RenderCommand;
-use crate::text::FontManager;
+/// Smallest band height worth giving its own task in the banded rectangle pass
+#[cfg(feature = "parallel")]
+const MIN_BAND_HEIGHT: u32 = 32;
+
+/// Fewest rectangles in a frame for the banded pass to pay off
+#[cfg(feature = "parallel")]
+const PARALLEL_MIN_COMMANDS: usize = 32;
 
 /// Software renderer using tiny-skia for CPU-based 2D rendering.
 ///
@@ -30,10 +59,57 @@ pub struct SoftwareRenderer {
     pixmap: Pixmap,
     width: u32,
     height: u32,
-    commands: Vec<RenderCommand>,
+    /// Rectangles of this frame with their insertion sequence, shared with `stateful_commands`
+    commands: Vec<(RenderCommand, u32)>,
+    /// Rectangles kept across frames until removed
+    retained: RetainedCommands,
+    /// Immediate and retained rectangles of the frame being rendered
+    frame_commands: Vec<RenderCommand>,
+    /// Rectangles needing a blend mode or their own clip (kept out of merging and culling),
+    /// with their insertion sequence
+    stateful_commands: Vec<(RenderCommand, PaintState, u32)>,
+    /// Filled and stroked paths, drawn above rectangles in the order added
+    path_commands: Vec<(PathCommand, PaintState)>,
     text_commands: Vec<TextCommand>,
+    /// Clip for each entry of `text_commands`
+    text_clips: Vec<Option<ClipBounds>>,
     clear_color: (u8, u8, u8, u8),
     font_manager: FontManager,
+    culled_count: usize,
+    color: ColorManager,
+    linear_blending: bool,
+    text_antialias: TextAntialias,
+    damage: Vec<Rect>,
+    full_redraw: bool,
+    /// Derive damage by diffing each frame's draws against the previous frame
+    auto_damage: bool,
+    previous_frame: Option<FrameSnapshot>,
+    /// Area repainted by the last `render()`
+    last_dirty: Option<(u32, u32, u32, u32)>,
+    optimize_stats: optimize::OptimizeStats,
+    raster_stats: RasterStats,
+    #[cfg(feature = "images")]
+    image_commands: Vec<(ImageCommand, PaintState)>,
+    #[cfg(feature = "images")]
+    images: ImageCache,
+    layers: LayerStore,
+    bitmaps: HashMap<u32, Bitmap>,
+    bitmap_commands: Vec<(BitmapCommand, PaintState)>,
+    state: GraphicsState,
+    state_stack: Vec<GraphicsState>,
+    /// Stacking contexts opened this frame, which order rectangles before their z-index
+    stacking: StackingContexts,
+    /// Blinking caret, kept across frames until cleared
+    caret: Option<Caret>,
+    /// Window thread that draws the caret instead of `render()`
+    caret_window: Option<SharedCaret>,
+    /// Caret bar drawn by the last `render()`, in device pixels
+    drawn_caret: Option<[f32; 4]>,
+    /// Framebuffer pixels per logical pixel, the base scale of the graphics state
+    device_pixel_ratio: f32,
+    /// Fill rectangles of large frames in parallel bands
+    #[cfg(feature = "parallel")]
+    parallel: bool,
 }
 
 /// Text command for software rendering
@@ -48,6 +124,80 @@ pub struct TextCommand {
     pub color_b: f32,
     pub color_a: f32,
     pub font_id: u32,
+    /// Underline, overline and line-through flags
+    pub decoration: TextDecoration,
+    /// Color of the decoration lines, None for the text color
+    pub decoration_color: Option<[f32; 4]>,
+    /// Point of the run placed at `y`; resolved to `Top` by `add_text`
+    pub anchor: TextAnchor,
+    /// Letter spacing, word spacing and line height
+    pub spacing: TextSpacing,
+}
+
+/// Per-frame rasterization counters reported by `SoftwareRenderer::raster_stats`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RasterStats {
+    /// Destination pixels covered by draws (bounding-box estimate for rects and images)
+    pub pixels_touched: u64,
+    pub rects_filled: u32,
+    pub images_drawn: u32,
+    pub text_runs: u32,
+    pub glyphs_rasterized: u32,
+    pub glyph_metrics_hits: u32,
+    pub glyph_metrics_misses: u32,
+    pub image_cache_hits: u32,
+    pub image_cache_misses: u32,
+    /// Layers composited from their retained pixmap
+    pub layer_cache_hits: u32,
+    /// Layers re-rasterized because they were dirty
+    pub layer_cache_misses: u32,
+    /// Draws skipped because they fall outside the viewport or damaged area
+    pub clip_rejections: u32,
+    /// Glyph bitmaps evicted from the glyph cache to stay within its budget
+    pub glyphs_evicted: u32,
+}
+
+/// Pixels of the `x, y, width, height` box inside `region` (0 if disjoint)
+fn covered_pixels(region: &Rect, x: f32, y: f32, width: f32, height: f32) -> u64 {
+    let w = (x + width).min(region.right()) - x.max(region.left());
+    let h = (y + height).min(region.bottom()) - y.max(region.top());
+    if w <= 0.0 || h <= 0.0 {
+        0
+    } else {
+        (w * h).round() as u64
+    }
+}
+
+/// Host-supplied bitmap retained by the renderer (premultiplied RGBA8)
+#[derive(Debug, Clone)]
+pub struct Bitmap {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Arc<[u8]>,
+}
+
+/// Draw command referencing a registered bitmap
+#[derive(Debug, Clone, Copy)]
+pub struct BitmapCommand {
+    pub bitmap_id: u32,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub z_index: i32,
+}
+
+/// Image draw command for software rendering
+#[cfg(feature = "images")]
+#[derive(Debug, Clone, Copy)]
+pub struct ImageCommand {
+    pub image_id: ImageId,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub z_index: i32,
 }
 
 impl SoftwareRenderer {
@@ -70,9 +220,41 @@ impl SoftwareRenderer {
             width: w,
             height: h,
             commands: Vec::new(),
+            retained: RetainedCommands::new(),
+            frame_commands: Vec::new(),
+            stateful_commands: Vec::new(),
+            path_commands: Vec::new(),
             text_commands: Vec::new(),
+            text_clips: Vec::new(),
             clear_color: (255, 255, 255, 255), // White by default
             font_manager: FontManager::new(),
+            culled_count: 0,
+            color: ColorManager::new(),
+            linear_blending: false,
+            text_antialias: TextAntialias::Grayscale,
+            damage: Vec::new(),
+            full_redraw: true,
+            auto_damage: false,
+            previous_frame: None,
+            last_dirty: None,
+            optimize_stats: optimize::OptimizeStats::default(),
+            raster_stats: RasterStats::default(),
+            #[cfg(feature = "images")]
+            image_commands: Vec::new(),
+            #[cfg(feature = "images")]
+            images: ImageCache::default(),
+            layers: LayerStore::new(),
+            bitmaps: HashMap::new(),
+            bitmap_commands: Vec::new(),
+            state: GraphicsState::default(),
+            state_stack: Vec::new(),
+            stacking: StackingContexts::new(),
+            caret: None,
+            caret_window: None,
+            drawn_caret: None,
+            device_pixel_ratio: 1.0,
+            #[cfg(feature = "parallel")]
+            parallel: true,
         }
     }
 
@@ -89,6 +271,7 @@ impl SoftwareRenderer {
             self.width = w;
             self.height = h;
             self.pixmap = Pixmap::new(w, h).expect("Failed to create pixmap");
+            self.full_redraw = true;
         }
     }
 
@@ -102,240 +285,3358 @@ impl SoftwareRenderer {
         );
     }
 
-    /// Clear all render commands
+    /// Clear all render commands and reset the graphics state
     pub fn clear(&mut self) {
         self.commands.clear();
+        self.stateful_commands.clear();
+        self.path_commands.clear();
         self.text_commands.clear();
+        self.text_clips.clear();
+        #[cfg(feature = "images")]
+        self.image_commands.clear();
+        self.bitmap_commands.clear();
+        self.state = self.base_state();
+        self.state_stack.clear();
+        self.stacking.clear();
+    }
+
+    /// Set the device pixel ratio: draws are given in logical pixels and scaled by `ratio`
+    ///
+    /// Text is rasterized at the scaled font size, so it stays crisp on HiDPI
+    /// displays. The framebuffer keeps its size; resize it to the logical size
+    /// times the ratio. Resets the graphics state like `clear()`.
+    pub fn set_device_pixel_ratio(&mut self, ratio: f32) {
+        if !ratio.is_finite() || ratio <= 0.0 {
+            return;
+        }
+        self.device_pixel_ratio = ratio;
+        self.state = self.base_state();
+        self.state_stack.clear();
+        self.full_redraw = true;
+        self.publish_caret();
+    }
+
+    /// Get the device pixel ratio
+    pub fn device_pixel_ratio(&self) -> f32 {
+        self.device_pixel_ratio
+    }
+
+    /// Graphics state at the start of a frame: identity scaled by the device pixel ratio
+    fn base_state(&self) -> GraphicsState {
+        let mut state = GraphicsState::default();
+        state.scale(self.device_pixel_ratio, self.device_pixel_ratio);
+        state
+    }
+
+    /// Push the current transform, clip, opacity and blend mode
+    pub fn save(&mut self) {
+        self.state_stack.push(self.state);
+    }
+
+    /// Pop the state pushed by the matching `save()`
+    ///
+    /// Returns false (leaving the state unchanged) if the stack is empty.
+    pub fn restore(&mut self) -> bool {
+        match self.state_stack.pop() {
+            Some(state) => {
+                self.state = state;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Number of saved states
+    pub fn save_depth(&self) -> usize {
+        self.state_stack.len()
+    }
+
+    /// Open a stacking context inside the current one, painted as a unit at `z_index` among its
+    /// siblings
+    ///
+    /// Rectangles added until the matching `pop_stacking_context()` belong to
+    /// it and are ordered by z-index inside it. Contexts last until `clear()`.
+    ///
+    /// Only axis-aligned rectangles are ordered this way. Paths (including
+    /// rotated rectangles), images, bitmaps and text still paint in their own
+    /// stages above all rectangles, whatever context they were added in.
+    pub fn push_stacking_context(&mut self, z_index: i32) -> StackingContextId {
+        self.stacking.push(z_index, self.next_sequence())
+    }
+
+    /// Insertion sequence of the next rectangle, counting plain and stateful ones alike
+    fn next_sequence(&self) -> u32 {
+        (self.commands.len() + self.stateful_commands.len()) as u32
+    }
+
+    /// Close the innermost stacking context
+    ///
+    /// Returns false if only the root context is open.
+    pub fn pop_stacking_context(&mut self) -> bool {
+        self.stacking.pop()
+    }
+
+    /// Get the current graphics state
+    pub fn graphics_state(&self) -> &GraphicsState {
+        &self.state
+    }
+
+    /// Offset subsequent draws
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        self.state.translate(dx, dy);
+    }
+
+    /// Scale subsequent draws (text scales by `sy`)
+    pub fn scale(&mut self, sx: f32, sy: f32) {
+        self.state.scale(sx, sy);
+    }
+
+    /// Rotate subsequent draws clockwise by `degrees` around the current origin
+    pub fn rotate(&mut self, degrees: f32) {
+        self.state.concat(Transform::from_rotate(degrees));
+    }
+
+    /// Multiply the transform of subsequent draws by the matrix (a, b, c, d, e, f)
+    ///
+    /// A point (x, y) maps to (a*x + c*y + e, b*x + d*y + f), as in the canvas
+    /// `transform()` method.
+    pub fn transform(&mut self, a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) {
+        self.state.concat(Transform::from_row(a, b, c, d, e, f));
+    }
+
+    /// Transform subsequent draws until the matching `pop_transform()`
+    ///
+    /// Shorthand for `save()` + `transform()`, like `push_clip_rect()`.
+    pub fn push_transform(&mut self, a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) {
+        self.save();
+        self.transform(a, b, c, d, e, f);
+    }
+
+    /// Remove the transform pushed by the matching `push_transform()`
+    pub fn pop_transform(&mut self) -> bool {
+        self.restore()
+    }
+
+    /// Intersect the clip with a rectangle in the current coordinate space
+    pub fn clip_rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.state.clip(x, y, width, height);
+    }
+
+    /// Clip subsequent draws to a rectangle until the matching `pop_clip_rect()`
+    ///
+    /// Shorthand for `save()` + `clip_rect()`, so clips nest by intersection
+    /// and popping also restores any state changed since the push.
+    pub fn push_clip_rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.save();
+        self.clip_rect(x, y, width, height);
+    }
+
+    /// Remove the clip pushed by the matching `push_clip_rect()`
+    pub fn pop_clip_rect(&mut self) -> bool {
+        self.restore()
+    }
+
+    /// Multiply the opacity of subsequent draws by `opacity`
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.state.paint.opacity *= opacity.clamp(0.0, 1.0);
+    }
+
+    /// Set the blend mode for subsequent rectangles, images and bitmaps
+    pub fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.state.paint.blend_mode = mode;
+    }
+
+    /// Set how subsequent images and bitmaps are sampled when scaled
+    pub fn set_image_filter(&mut self, filter: ImageFilter) {
+        self.state.paint.image_filter = filter;
+    }
+
+    /// Map a rectangle through the current state, None if it is clipped away
+    fn apply_state(&self, x: f32, y: f32, width: f32, height: f32) -> Option<(f32, f32, f32, f32)> {
+        if self.state.paint.opacity <= 0.0 {
+            return None;
+        }
+        self.state.clip_mapped(x, y, width, height)
     }
 
-    /// Add a rectangle render command
+    /// Add a rectangle render command (color in sRGB)
     pub fn add_rect(&mut self, cmd: RenderCommand) {
-        self.commands.push(cmd);
+        self.add_rect_in_space(cmd, ColorSpace::Srgb);
     }
 
-    /// Add a text render command
-    pub fn add_text(&mut self, text_cmd: TextCommand) {
-        self.text_commands.push(text_cmd);
+    /// Add a rectangle render command whose color is in `space`
+    pub fn add_rect_in_space(&mut self, mut cmd: RenderCommand, space: ColorSpace) {
+        if !self.state.is_axis_aligned() {
+            // Rotated or skewed rectangles are drawn as paths, above the axis-aligned ones
+            if cmd.width > 0.0 && cmd.height > 0.0 {
+                let path = Path::rounded_rect(cmd.x, cmd.y, cmd.width, cmd.height, cmd.clamped_radii());
+                let color = [cmd.color_r, cmd.color_g, cmd.color_b, cmd.color_a];
+                self.add_path(&path, PathPaint::Fill(FillRule::NonZero), color, space);
+            }
+            return;
+        }
+        let [r, g, b, a] = self
+            .color
+            .convert_color([cmd.color_r, cmd.color_g, cmd.color_b, cmd.color_a], space);
+        cmd.color_r = r;
+        cmd.color_g = g;
+        cmd.color_b = b;
+        cmd.color_a = a * self.state.paint.opacity;
+        if cmd.stacking_context == ROOT_STACKING_CONTEXT {
+            cmd.stacking_context = self.stacking.current();
+        }
+
+        let Some(clipped) = self.apply_state(cmd.x, cmd.y, cmd.width, cmd.height) else {
+            return;
+        };
+
+        // Square rectangles are clipped geometrically; rounded ones keep their
+        // shape and carry the clip unless they lie entirely inside it
+        let mut clip = None;
+        if cmd.is_rounded() {
+            let mapped = self.state.map_rect(cmd.x, cmd.y, cmd.width, cmd.height);
+            if mapped != clipped {
+                clip = self.state.paint.clip;
+            }
+            let scale = self.state.average_scale();
+            cmd.corner_radii = cmd.corner_radii.map(|r| r * scale);
+            (cmd.x, cmd.y, cmd.width, cmd.height) = mapped;
+        } else {
+            (cmd.x, cmd.y, cmd.width, cmd.height) = clipped;
+        }
+
+        let blend_mode = self.state.paint.blend_mode;
+        let sequence = self.next_sequence();
+        if blend_mode == BlendMode::Normal && clip.is_none() {
+            self.commands.push((cmd, sequence));
+        } else {
+            let paint = PaintState {
+                clip,
+                opacity: 1.0,
+                blend_mode,
+                ..PaintState::default()
+            };
+            self.stateful_commands.push((cmd, paint, sequence));
+        }
     }
 
-    /// Get a reference to the font manager
-    pub fn font_manager(&self) -> &FontManager {
-        &self.font_manager
+    /// Add the scrollbar thumbs of a scroll area whose viewport's top-left corner is at (x, y)
+    ///
+    /// Thumbs are fully rounded rectangles in `color` (sRGB). Returns the
+    /// number added: one per overflowing axis.
+    pub fn add_scrollbars(&mut self, area: &ScrollArea, x: f32, y: f32, color: [f32; 4], z_index: i32) -> usize {
+        let thumbs = area.scrollbar_thumbs();
+        for &[tx, ty, width, height] in &thumbs {
+            self.add_rect(RenderCommand {
+                x: x + tx,
+                y: y + ty,
+                width,
+                height,
+                color_r: color[0],
+                color_g: color[1],
+                color_b: color[2],
+                color_a: color[3],
+                z_index,
+                corner_radii: [width.min(height) / 2.0; 4],
+                ..RenderCommand::default()
+            });
+        }
+        thumbs.len()
     }
 
-    /// Get a mutable reference to the font manager
-    pub fn font_manager_mut(&mut self) -> &mut FontManager {
-        &mut self.font_manager
+    /// Add selection highlights for a byte range of a paragraph drawn with its top-left corner at (x, y)
+    ///
+    /// The highlights are rectangles in `color` (sRGB), so they paint beneath
+    /// all text. Returns the number added.
+    pub fn add_selection(&mut self, shaped: &ShapedText, x: f32, y: f32, bytes: Range<usize>, color: [f32; 4], z_index: i32) -> usize {
+        let rects = shaped.selection_rects(bytes);
+        for &[rx, ry, width, height] in &rects {
+            self.add_rect(RenderCommand {
+                x: x + rx,
+                y: y + ry,
+                width,
+                height,
+                color_r: color[0],
+                color_g: color[1],
+                color_b: color[2],
+                color_a: color[3],
+                z_index,
+                ..RenderCommand::default()
+            });
+        }
+        rects.len()
     }
 
-    /// Render all commands to the pixmap
-    pub fn render(&mut self) {
-        // Clear pixmap with clear color
-        let (r, g, b, a) = self.clear_color;
-        self.pixmap.fill(Color::from_rgba8(r, g, b, a));
+    /// Show a caret bar at (x, y) in logical pixels, blinking every `blink_ms` (0 = steady)
+    ///
+    /// The caret stays until `clear_caret()` and is drawn above everything by
+    /// `render()`, which also repaints its area when it toggles under damage
+    /// tracking. Setting it again unchanged keeps the blink phase, so hosts may
+    /// set it every frame; moving it shows it again at once.
+    pub fn set_caret(&mut self, x: f32, y: f32, height: f32, color: [f32; 4], blink_ms: u32) {
+        let caret = Caret::new(x, y, height, color, blink_ms);
+        if self.caret.is_some_and(|current| current.same_place(&caret)) {
+            return;
+        }
+        self.caret = Some(caret);
+        self.publish_caret();
+    }
 
-        // Sort commands by z-index
-        self.commands.sort_by_key(|c| c.z_index);
+    /// Remove the caret
+    pub fn clear_caret(&mut self) {
+        self.caret = None;
+        self.publish_caret();
+    }
 
-        // Render rectangles - iterate by index to avoid borrow conflicts
-        // Each iteration clones a single command (small struct) instead of the whole vector
-        for i in 0..self.commands.len() {
-            let cmd = self.commands[i].clone();
-            Self::render_rect_to_pixmap(&mut self.pixmap, &cmd);
+    /// Current caret, if one is set
+    pub fn caret(&self) -> Option<&Caret> {
+        self.caret.as_ref()
+    }
+
+    /// Hand the caret to a window thread that blends it over presented frames and
+    /// presents again on every toggle, or take it back with None
+    ///
+    /// While attached, `render()` leaves the caret out of the framebuffer.
+    pub fn attach_caret_window(&mut self, window: Option<SharedCaret>) {
+        if let Some(previous) = &self.caret_window {
+            *previous.lock_or_recover() = None;
         }
+        self.caret_window = window;
+        self.publish_caret();
+    }
 
-        // Render text commands
-        for i in 0..self.text_commands.len() {
-            let text_cmd = self.text_commands[i].clone();
-            Self::render_text_to_pixmap(
-                &mut self.pixmap,
-                &mut self.font_manager,
-                self.width,
-                self.height,
-                &text_cmd,
-            );
+    /// Copy the caret in device pixels to the attached window
+    fn publish_caret(&self) {
+        if let Some(window) = &self.caret_window {
+            let caret = self.caret.map(|c| c.scaled(self.device_pixel_ratio));
+            *window.lock_or_recover() = caret;
         }
     }
 
-    /// Render a rectangle to the pixmap (static method to avoid borrow conflicts)
-    fn render_rect_to_pixmap(pixmap: &mut Pixmap, cmd: &RenderCommand) {
-        if cmd.width <= 0.0 || cmd.height <= 0.0 {
+    /// Caret bar to draw at `now` in device pixels, if it is shown and not drawn by a window
+    fn caret_rect_at(&self, now: Instant) -> Option<[f32; 4]> {
+        let caret = self.caret.filter(|c| self.caret_window.is_none() && c.is_visible_at(now))?;
+        Some(caret.scaled(self.device_pixel_ratio).rect())
+    }
+
+    /// When the caret next appears or disappears in the framebuffer, so hosts rendering on
+    /// demand know when to render again (None if nothing will change)
+    pub fn next_caret_toggle(&self, now: Instant) -> Option<Instant> {
+        self.caret.filter(|_| self.caret_window.is_none())?.next_toggle(now)
+    }
+
+    /// Draw a dashed rounded focus ring around a box in the current coordinate space
+    pub fn add_focus_ring(&mut self, ring: &FocusRing) {
+        self.stroke_path(&ring.outline(), StrokeStyle::new(ring.line_width), ring.color);
+    }
+
+    /// Fill a path (points in the current coordinate space, color in sRGB)
+    pub fn fill_path(&mut self, path: &Path, rule: FillRule, color: [f32; 4]) {
+        self.add_path(path, PathPaint::Fill(rule), color, ColorSpace::Srgb);
+    }
+
+    /// Stroke a path (width in the current coordinate space, color in sRGB)
+    pub fn stroke_path(&mut self, path: &Path, style: StrokeStyle, color: [f32; 4]) {
+        if style.width.is_nan() || style.width <= 0.0 {
             return;
         }
+        let style = StrokeStyle {
+            width: style.width * self.state.average_scale(),
+            ..style
+        };
+        self.add_path(path, PathPaint::Stroke(style), color, ColorSpace::Srgb);
+    }
 
-        let rect = match Rect::from_xywh(cmd.x, cmd.y, cmd.width, cmd.height) {
-            Some(r) => r,
-            None => return,
+    /// Draw a straight line between two points (color in sRGB)
+    ///
+    /// Unlike a thin rect, the line is anti-aliased along its own direction,
+    /// so it stays crisp at fractional coordinates and any angle.
+    pub fn add_line(&mut self, from: (f32, f32), to: (f32, f32), width: f32, color: [f32; 4], cap: LineCap) {
+        let mut path = Path::new();
+        path.move_to(from.0, from.1);
+        path.line_to(to.0, to.1);
+        let style = StrokeStyle {
+            cap,
+            ..StrokeStyle::new(width)
         };
+        self.stroke_path(&path, style, color);
+    }
 
-        let mut paint = Paint::default();
-        paint.set_color(Color::from_rgba(
-            cmd.color_r,
-            cmd.color_g,
-            cmd.color_b,
-            cmd.color_a,
-        ).unwrap_or(Color::BLACK));
-        paint.anti_alias = true;
+    /// Draw connected line segments through `points` (color in sRGB)
+    pub fn add_polyline(&mut self, points: &[(f32, f32)], style: StrokeStyle, color: [f32; 4]) {
+        let Some((&(x, y), rest)) = points.split_first() else {
+            return;
+        };
+        let mut path = Path::new();
+        path.move_to(x, y);
+        for &(x, y) in rest {
+            path.line_to(x, y);
+        }
+        self.stroke_path(&path, style, color);
+    }
 
-        // Create a filled rectangle path
-        let path = PathBuilder::from_rect(rect);
-        
-        pixmap.fill_path(
-            &path,
-            &paint,
-            tiny_skia::FillRule::Winding,
-            Transform::identity(),
-            None,
-        );
+    /// Draw an SVG document fitted into a rectangle in the current coordinate space
+    ///
+    /// The viewBox is mapped into the rectangle per the document's
+    /// `preserveAspectRatio`, and drawing is clipped to the rectangle.
+    pub fn add_svg(&mut self, svg: &SvgDocument, x: f32, y: f32, width: f32, height: f32) {
+        if width <= 0.0 || height <= 0.0 {
+            return;
+        }
+        self.push_clip_rect(x, y, width, height);
+        let [a, b, c, d, e, f] = svg.viewport_transform(x, y, width, height);
+        self.transform(a, b, c, d, e, f);
+        for shape in &svg.shapes {
+            self.save();
+            let [a, b, c, d, e, f] = shape.transform;
+            self.transform(a, b, c, d, e, f);
+            self.set_opacity(shape.opacity);
+            if let Some(fill) = shape.fill {
+                self.fill_path(&shape.path, shape.fill_rule, fill);
+            }
+            if let Some(stroke) = shape.stroke {
+                self.stroke_path(&shape.path, shape.stroke_style, stroke);
+            }
+            self.restore();
+        }
+        self.pop_clip_rect();
     }
 
-    /// Render text to the pixmap (static method to avoid borrow conflicts)
-    fn render_text_to_pixmap(
-        pixmap: &mut Pixmap,
-        font_manager: &mut FontManager,
-        width: u32,
-        height: u32,
-        cmd: &TextCommand,
-    ) {
-        if cmd.text.is_empty() {
+    fn add_path(&mut self, path: &Path, paint: PathPaint, color: [f32; 4], space: ColorSpace) {
+        let [r, g, b, a] = self.color.convert_color(color, space);
+        let a = a * self.state.paint.opacity;
+        if path.is_empty() || a <= 0.0 || self.state.paint.is_clipped_out() {
             return;
         }
+        let state = self.state;
+        let cmd = PathCommand {
+            path: path.map_points(|x, y| state.map_point(x, y)),
+            paint,
+            color: [r, g, b, a],
+        };
+        let Some([l, t, r, b]) = cmd.bounds() else {
+            return;
+        };
 
-        let color = (
-            (cmd.color_r * 255.0) as u8,
-            (cmd.color_g * 255.0) as u8,
-            (cmd.color_b * 255.0) as u8,
-            (cmd.color_a * 255.0) as u8,
-        );
+        // Paths keep their shape and carry the clip unless they lie entirely inside it
+        let mut clip = None;
+        if let Some([cl, ct, cr, cb]) = state.paint.clip {
+            if r <= cl || b <= ct || l >= cr || t >= cb {
+                return;
+            }
+            if l < cl || t < ct || r > cr || b > cb {
+                clip = state.paint.clip;
+            }
+        }
+        let paint = PaintState {
+            clip,
+            opacity: 1.0,
+            blend_mode: state.paint.blend_mode,
+            ..PaintState::default()
+        };
+        self.path_commands.push((cmd, paint));
+    }
 
-        let (text_buffer, text_w, text_h) = font_manager.rasterize_text(
-            &cmd.text,
-            cmd.font_size,
-            cmd.font_id,
-            color,
+    /// Add a text render command (color in sRGB)
+    pub fn add_text(&mut self, mut text_cmd: TextCommand) {
+        let [r, g, b, a] = self.color.convert_color(
+            [text_cmd.color_r, text_cmd.color_g, text_cmd.color_b, text_cmd.color_a],
+            ColorSpace::Srgb,
         );
-
-        if text_buffer.is_empty() || text_w == 0 || text_h == 0 {
+        text_cmd.color_r = r;
+        text_cmd.color_g = g;
+        text_cmd.color_b = b;
+        text_cmd.color_a = a * self.state.paint.opacity;
+        if text_cmd.color_a <= 0.0 || self.state.paint.is_clipped_out() {
             return;
         }
+        if let Some(color) = text_cmd.decoration_color {
+            let [r, g, b, a] = self.color.convert_color(color, ColorSpace::Srgb);
+            text_cmd.decoration_color = Some([r, g, b, a * self.state.paint.opacity]);
+        }
 
-        // Blit text to pixmap
-        let tx = cmd.x as i32;
-        let ty = cmd.y as i32;
-        let pixmap_data = pixmap.data_mut();
-        let w = width as i32;
-        let h = height as i32;
-
-        for ty_off in 0..text_h as i32 {
-            for tx_off in 0..text_w as i32 {
-                let px = tx + tx_off;
-                let py = ty + ty_off;
-
-                if px >= 0 && py >= 0 && px < w && py < h {
-                    let src_idx = ((ty_off as u32 * text_w + tx_off as u32) * 4) as usize;
-                    let dst_idx = ((py * w + px) * 4) as usize;
-
-                    if src_idx + 3 < text_buffer.len() && dst_idx + 3 < pixmap_data.len() {
-                        let src_a = text_buffer[src_idx + 3] as f32 / 255.0;
-                        if src_a > 0.0 {
-                            let inv_a = 1.0 - src_a;
-                            pixmap_data[dst_idx] = ((text_buffer[src_idx] as f32 * src_a
-                                + pixmap_data[dst_idx] as f32 * inv_a) as u8)
-                                .min(255);
-                            pixmap_data[dst_idx + 1] = ((text_buffer[src_idx + 1] as f32 * src_a
-                                + pixmap_data[dst_idx + 1] as f32 * inv_a) as u8)
-                                .min(255);
-                            pixmap_data[dst_idx + 2] = ((text_buffer[src_idx + 2] as f32 * src_a
-                                + pixmap_data[dst_idx + 2] as f32 * inv_a) as u8)
-                                .min(255);
-                            pixmap_data[dst_idx + 3] = ((src_a * 255.0
-                                + pixmap_data[dst_idx + 3] as f32 * inv_a) as u8)
-                                .min(255);
-                        }
-                    }
-                }
-            }
+        // Renderers draw runs from their top edge
+        text_cmd.y -= self
+            .font_manager
+            .anchor_offset(&text_cmd.text, text_cmd.font_size, text_cmd.font_id, text_cmd.anchor, text_cmd.spacing);
+        text_cmd.anchor = TextAnchor::Top;
+
+        // Text is not rotated: only its origin follows the transform
+        let state = &self.state;
+        (text_cmd.x, text_cmd.y) = state.map_point(text_cmd.x, text_cmd.y);
+        let scale = state.transform.get_scale().1;
+        text_cmd.font_size *= scale;
+        text_cmd.spacing = text_cmd.spacing.scaled(scale);
+        self.text_commands.push(text_cmd);
+        self.text_clips.push(state.paint.clip);
+    }
+
+    /// Shape styled spans as one paragraph and draw it with its top-left at (x, y)
+    ///
+    /// Lines wrap at `max_width` when given. Each span fragment becomes a
+    /// text command placed on its line's baseline. Returns the layout.
+    pub fn add_paragraph(&mut self, spans: &[TextSpan], x: f32, y: f32, max_width: Option<f32>) -> ParagraphLayout {
+        let layout = self.font_manager.layout_paragraph(spans, max_width);
+        for fragment in &layout.fragments {
+            let span = &spans[fragment.span];
+            let [r, g, b, a] = span.color;
+            self.add_text(TextCommand {
+                text: span.text[fragment.range.clone()].to_string(),
+                x: x + fragment.x,
+                y: y + fragment.baseline,
+                font_size: span.font_size,
+                color_r: r,
+                color_g: g,
+                color_b: b,
+                color_a: a,
+                font_id: span.font_id,
+                decoration: span.decoration,
+                decoration_color: None,
+                anchor: TextAnchor::Baseline,
+                spacing: TextSpacing::default(),
+            });
         }
+        layout
     }
 
-    /// Get the framebuffer as raw RGBA bytes
-    pub fn get_framebuffer(&self) -> &[u8] {
-        self.pixmap.data()
+    /// Get a reference to the color manager
+    pub fn color_manager(&self) -> &ColorManager {
+        &self.color
     }
 
-    /// Get a copy of the framebuffer
-    pub fn get_framebuffer_copy(&self) -> Vec<u8> {
-        self.pixmap.data().to_vec()
+    /// Get a mutable reference to the color manager
+    ///
+    /// Changing the output space forces a full repaint; images already in
+    /// the cache keep the space they were decoded for.
+    pub fn color_manager_mut(&mut self) -> &mut ColorManager {
+        self.full_redraw = true;
+        &mut self.color
     }
 
-    /// Get the framebuffer size in bytes
-    pub fn get_framebuffer_size(&self) -> usize {
-        self.pixmap.data().len()
+    /// Blend rectangles and text in linear light instead of on sRGB bytes
+    ///
+    /// Improves antialiased edges and translucent overlays at some CPU cost.
+    /// Images and layer compositing still blend in sRGB.
+    pub fn set_linear_blending(&mut self, enabled: bool) {
+        if self.linear_blending != enabled {
+            self.linear_blending = enabled;
+            self.full_redraw = true;
+        }
     }
 
-    /// Export the framebuffer to a PNG file
-    pub fn export_png(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let file = std::fs::File::create(path)?;
-        let w = std::io::BufWriter::new(file);
-        let mut encoder = png::Encoder::new(w, self.width, self.height);
-        encoder.set_color(png::ColorType::Rgba);
-        encoder.set_depth(png::BitDepth::Eight);
+    /// Check if linear-light blending is enabled
+    pub fn linear_blending(&self) -> bool {
+        self.linear_blending
+    }
 
-        let mut writer = encoder.write_header()?;
-        writer.write_image_data(self.pixmap.data())?;
+    /// Fill the rectangles of large full redraws in horizontal bands on the rayon pool (on by default)
+    ///
+    /// The output matches single-threaded rendering, except that rounded
+    /// corners crossing a band edge may differ by a rounding step of
+    /// antialiasing, since the rasterizer splits curves at the band edge.
+    #[cfg(feature = "parallel")]
+    pub fn set_parallel(&mut self, enabled: bool) {
+        self.parallel = enabled;
+    }
 
-        Ok(())
+    /// Choose grayscale or LCD subpixel antialiasing for text
+    ///
+    /// Subpixel coverage only suits opaque backgrounds, so text inside
+    /// layers stays grayscale. The GPU renderer always draws grayscale text.
+    pub fn set_text_antialias(&mut self, mode: TextAntialias) {
+        if self.text_antialias != mode {
+            self.text_antialias = mode;
+            self.full_redraw = true;
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Current text antialiasing mode
+    pub fn text_antialias(&self) -> TextAntialias {
+        self.text_antialias
+    }
 
-    #[test]
-    fn test_software_renderer_creation() {
-        let renderer = SoftwareRenderer::new(100, 100);
-        assert_eq!(renderer.size(), (100, 100));
+    /// Decode an sRGB image into the cache, converting it to the output space
+    #[cfg(feature = "images")]
+    pub fn load_image(&mut self, key: &str, data: &[u8]) -> Result<ImageId, image::ImageError> {
+        self.images.load_managed(key, data, &self.color)
     }
 
-    #[test]
-    fn test_software_renderer_clear_color() {
-        let mut renderer = SoftwareRenderer::new(100, 100);
-        renderer.set_clear_color(1.0, 0.0, 0.0, 1.0);
-        renderer.render();
+    /// Decode a still image (first frame of animations) under a fresh handle
+    #[cfg(feature = "images")]
+    pub fn decode_image(&mut self, data: &[u8]) -> Result<ImageId, image::ImageError> {
+        self.images.decode(data, &self.color)
+    }
 
-        let data = renderer.get_framebuffer();
-        // First pixel should be red
-        assert_eq!(data[0], 255); // R
-        assert_eq!(data[1], 0);   // G
-        assert_eq!(data[2], 0);   // B
-        assert_eq!(data[3], 255); // A
+    /// Get frame `index` of a cached image with its delay in milliseconds, decoding it if needed
+    #[cfg(feature = "images")]
+    pub fn image_frame(&mut self, id: ImageId, index: usize) -> Option<(&DecodedImage, u32)> {
+        self.images.frame_managed(id, index, &self.color)
     }
 
-    #[test]
-    fn test_software_renderer_add_rect() {
-        let mut renderer = SoftwareRenderer::new(100, 100);
-        renderer.set_clear_color(1.0, 1.0, 1.0, 1.0);
-        renderer.add_rect(RenderCommand {
-            x: 10.0,
-            y: 10.0,
-            width: 50.0,
-            height: 50.0,
-            color_r: 0.0,
-            color_g: 0.0,
-            color_b: 1.0,
-            color_a: 1.0,
-            texture_id: 0,
-            z_index: 0,
-        });
-        renderer.render();
+    /// Show frame `index` of an animated image (see `ImageCache::set_frame`)
+    #[cfg(feature = "images")]
+    pub fn set_image_frame(&mut self, id: ImageId, index: usize) -> bool {
+        self.images.set_frame_managed(id, index, &self.color)
+    }
 
-        let data = renderer.get_framebuffer();
-        // Check a pixel inside the rectangle (at 25, 25)
-        let idx = ((25 * 100) + 25) * 4;
-        assert_eq!(data[idx], 0);     // R
-        assert_eq!(data[idx + 1], 0); // G
-        assert_eq!(data[idx + 2], 255); // B
-        assert_eq!(data[idx + 3], 255); // A
+    /// Advance animated image playback by `dt` seconds, returning true if a redraw is needed
+    #[cfg(feature = "images")]
+    pub fn advance_images(&mut self, dt: f32) -> bool {
+        self.images.advance_managed(dt, &self.color)
+    }
+
+    /// Cache a straight-alpha sRGB RGBA8 buffer as an image, converting it to the output space
+    ///
+    /// Returns None if `pixels` is smaller than `width * height * 4`.
+    #[cfg(feature = "images")]
+    pub fn load_image_rgba(&mut self, pixels: &[u8], width: u32, height: u32) -> Option<ImageId> {
+        self.images.insert_rgba(pixels, width, height, &self.color)
+    }
+
+    /// Add the commands produced by dop-content-ir's `render()`
+    ///
+    /// Commands keep their submission order; Content IR colors are converted
+    /// from 0-255 to normalized floats. Clips map to `push_clip_rect()` and
+    /// `pop_clip_rect()`.
+    #[cfg(feature = "content-ir")]
+    pub fn add_content_commands(&mut self, commands: &[dop_content_ir::render::RenderCommand]) {
+        use dop_content_ir::render::RenderCommand as ContentCommand;
+
+        for cmd in commands {
+            match cmd {
+                ContentCommand::FillRect { x, y, width, height, r, g, b, a, border_radius } => {
+                    self.add_rect(RenderCommand {
+                        x: *x,
+                        y: *y,
+                        width: *width,
+                        height: *height,
+                        color_r: *r as f32 / 255.0,
+                        color_g: *g as f32 / 255.0,
+                        color_b: *b as f32 / 255.0,
+                        color_a: *a as f32 / 255.0,
+                        texture_id: 0,
+                        z_index: 0,
+                        corner_radii: [*border_radius; 4],
+                        stacking_context: 0,
+                    });
+                }
+                ContentCommand::DrawText { x, y, text, font_size, r, g, b, a } => {
+                    self.add_text(TextCommand {
+                        text: text.clone(),
+                        x: *x,
+                        y: *y,
+                        font_size: *font_size,
+                        color_r: *r as f32 / 255.0,
+                        color_g: *g as f32 / 255.0,
+                        color_b: *b as f32 / 255.0,
+                        color_a: *a as f32 / 255.0,
+                        font_id: 0,
+                        decoration: TextDecoration::NONE,
+                        decoration_color: None,
+                        anchor: TextAnchor::Top,
+                        spacing: TextSpacing::default(),
+                    });
+                }
+                ContentCommand::PushClip { x, y, width, height } => {
+                    self.push_clip_rect(*x, *y, *width, *height);
+                }
+                ContentCommand::PopClip => {
+                    self.pop_clip_rect();
+                }
+            }
+        }
+    }
+
+    /// Add an image draw command (the image is scaled to the given rectangle)
+    #[cfg(feature = "images")]
+    pub fn add_image(&mut self, mut cmd: ImageCommand) {
+        if self.apply_state(cmd.x, cmd.y, cmd.width, cmd.height).is_none() {
+            return;
+        }
+        (cmd.x, cmd.y, cmd.width, cmd.height) = self.state.map_rect(cmd.x, cmd.y, cmd.width, cmd.height);
+        self.image_commands.push((cmd, self.state.paint));
+    }
+
+    /// Get a reference to the image cache
+    #[cfg(feature = "images")]
+    pub fn images(&self) -> &ImageCache {
+        &self.images
+    }
+
+    /// Get a mutable reference to the image cache
+    #[cfg(feature = "images")]
+    pub fn images_mut(&mut self) -> &mut ImageCache {
+        &mut self.images
+    }
+
+    /// Retain a rectangle (device pixels, sRGB color) across frames and return its handle
+    ///
+    /// `clear()` leaves retained rectangles in place; remove them with `remove_retained`.
+    pub fn create_retained_rect(&mut self, cmd: RenderCommand) -> CommandId {
+        self.retained.create(cmd)
+    }
+
+    /// Remove a retained rectangle
+    pub fn remove_retained(&mut self, id: CommandId) -> bool {
+        self.retained.remove(id)
+    }
+
+    /// Get a reference to the retained rectangles
+    pub fn retained(&self) -> &RetainedCommands {
+        &self.retained
+    }
+
+    /// Get a mutable reference to the retained rectangles
+    pub fn retained_mut(&mut self) -> &mut RetainedCommands {
+        &mut self.retained
+    }
+
+    /// Retained rectangles with their color converted into the output space
+    fn managed_retained(&self) -> impl Iterator<Item = RenderCommand> + '_ {
+        self.retained.iter().map(|(_, cmd)| {
+            let [r, g, b, a] = self
+                .color
+                .convert_color([cmd.color_r, cmd.color_g, cmd.color_b, cmd.color_a], ColorSpace::Srgb);
+            RenderCommand {
+                color_r: r,
+                color_g: g,
+                color_b: b,
+                color_a: a,
+                ..*cmd
+            }
+        })
+    }
+
+    /// Create a retained compositor layer of the given size
+    pub fn create_layer(&mut self, width: u32, height: u32) -> LayerId {
+        self.layers.create(width, height)
+    }
+
+    /// Get a reference to the compositor layers
+    pub fn layers(&self) -> &LayerStore {
+        &self.layers
+    }
+
+    /// Get a mutable reference to the compositor layers
+    pub fn layers_mut(&mut self) -> &mut LayerStore {
+        &mut self.layers
+    }
+
+    /// Limit the next `render()` to the given area (may be called several times)
+    ///
+    /// Pixels outside all damage rectangles keep their previous contents. The
+    /// full command list must still be supplied; it is only clipped.
+    pub fn add_damage_rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        if let Some(rect) = Rect::from_xywh(x, y, width, height) {
+            self.damage.push(rect);
+        }
+    }
+
+    /// Force the next `render()` to repaint the whole framebuffer
+    pub fn invalidate_all(&mut self) {
+        self.full_redraw = true;
+    }
+
+    /// Derive damage automatically by diffing each frame's commands against the previous frame
+    ///
+    /// The full command list is still supplied every frame. A frame whose
+    /// draws are unchanged is not rasterized at all; otherwise only the areas
+    /// of added, removed or changed draws (plus any `add_damage_rect` areas)
+    /// are repainted.
+    pub fn set_auto_damage(&mut self, enabled: bool) {
+        self.auto_damage = enabled;
+        self.previous_frame = None;
+    }
+
+    /// Check if automatic damage tracking is enabled
+    pub fn auto_damage(&self) -> bool {
+        self.auto_damage
+    }
+
+    /// Area repainted by the last `render()` as (x, y, width, height) in whole pixels
+    ///
+    /// None if the last frame repainted nothing (or nothing was rendered yet).
+    pub fn dirty_rect(&self) -> Option<(u32, u32, u32, u32)> {
+        self.last_dirty
+    }
+
+    /// Fingerprint every recorded draw for automatic damage tracking
+    fn snapshot(&self) -> FrameSnapshot {
+        fn bits<const N: usize>(values: [f32; N]) -> [u32; N] {
+            values.map(f32::to_bits)
+        }
+        let rect_bounds = |cmd: &RenderCommand| [cmd.x, cmd.y, cmd.x + cmd.width, cmd.y + cmd.height];
+        let rect_key = |cmd: &RenderCommand| {
+            (
+                bits([cmd.color_r, cmd.color_g, cmd.color_b, cmd.color_a]),
+                bits(cmd.corner_radii),
+                cmd.texture_id,
+                cmd.z_index,
+            )
+        };
+        let paint_key = |paint: &PaintState| {
+            (
+                paint.clip.map(bits),
+                paint.opacity.to_bits(),
+                paint.blend_mode as u8,
+                paint.image_filter as u8,
+            )
+        };
+
+        let mut snapshot = FrameSnapshot::new(self.clear_color);
+        for cmd in self.commands.iter().map(|(cmd, _)| *cmd).chain(self.managed_retained()) {
+            snapshot.push(rect_bounds(&cmd), (0u8, rect_key(&cmd)));
+        }
+        for (cmd, paint, _) in &self.stateful_commands {
+            snapshot.push(rect_bounds(cmd), (1u8, rect_key(cmd), paint_key(paint)));
+        }
+        for (cmd, paint) in &self.path_commands {
+            if let Some(bounds) = cmd.bounds() {
+                snapshot.push(bounds, (6u8, &cmd.path, cmd.paint, bits(cmd.color), paint_key(paint)));
+            }
+        }
+        #[cfg(feature = "images")]
+        for (cmd, paint) in &self.image_commands {
+            // The pixel address changes when an animation advances or the image is replaced
+            let pixels = self.images.peek(cmd.image_id).map(|i| i.pixels.as_ptr() as usize);
+            let bounds = [cmd.x, cmd.y, cmd.x + cmd.width, cmd.y + cmd.height];
+            snapshot.push(bounds, (2u8, cmd.image_id, pixels, cmd.z_index, paint_key(paint)));
+        }
+        for (cmd, paint) in &self.bitmap_commands {
+            let pixels = self.bitmaps.get(&cmd.bitmap_id).map(|b| b.pixels.as_ptr() as usize);
+            let bounds = [cmd.x, cmd.y, cmd.x + cmd.width, cmd.y + cmd.height];
+            snapshot.push(bounds, (3u8, cmd.bitmap_id, pixels, cmd.z_index, paint_key(paint)));
+        }
+        for (cmd, clip) in self.text_commands.iter().zip(&self.text_clips) {
+            // Measured extents plus a margin for glyph overhang
+            let (w, h) = self.font_manager.measure_text(&cmd.text, cmd.font_size, cmd.font_id, cmd.spacing);
+            let pad = cmd.font_size * 0.25 + 1.0;
+            let bounds = [cmd.x - pad, cmd.y - pad, cmd.x + w + pad, cmd.y + h + pad];
+            let color = bits([cmd.color_r, cmd.color_g, cmd.color_b, cmd.color_a]);
+            let decoration = (cmd.decoration, cmd.decoration_color.map(bits));
+            let key = (4u8, &cmd.text, cmd.font_size.to_bits(), cmd.font_id, color, clip.map(bits), decoration, cmd.spacing);
+            snapshot.push(bounds, key);
+        }
+        for id in self.layers.draw_order() {
+            if let Some(layer) = self.layers.get(id) {
+                let (x, y) = (layer.x.round(), layer.y.round());
+                let bounds = [x, y, x + layer.width as f32, y + layer.height as f32];
+                snapshot.push(bounds, (5u8, id, layer.z_index));
+            }
+        }
+        snapshot
+    }
+
+    /// Turn the difference from the previous frame into damage rectangles
+    ///
+    /// Returns false if nothing changed and the frame can be skipped.
+    fn collect_auto_damage(&mut self) -> bool {
+        let snapshot = self.snapshot();
+        let changed = match &self.previous_frame {
+            Some(previous) if !self.full_redraw => snapshot.diff(previous),
+            _ => None,
+        };
+        self.previous_frame = Some(snapshot);
+
+        let Some(mut rects) = changed else {
+            self.full_redraw = true;
+            return true;
+        };
+        // Dirty layers repaint in place even if they did not move
+        for id in self.layers.draw_order() {
+            if let Some(layer) = self.layers.get(id).filter(|l| l.is_dirty()) {
+                let (x, y) = (layer.x.round(), layer.y.round());
+                rects.push([x, y, x + layer.width as f32, y + layer.height as f32]);
+            }
+        }
+        if rects.is_empty() && self.damage.is_empty() {
+            return false;
+        }
+        for [l, t, r, b] in rects {
+            self.add_damage_rect(l, t, r - l, b - t);
+        }
+        true
+    }
+
+    /// Clear color converted into the output space
+    fn managed_clear_color(&self) -> (u8, u8, u8, u8) {
+        let (r, g, b, a) = self.clear_color;
+        let [r, g, b, a] = self.color.convert_color(
+            [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, a as f32 / 255.0],
+            ColorSpace::Srgb,
+        );
+        (
+            (r * 255.0).round() as u8,
+            (g * 255.0).round() as u8,
+            (b * 255.0).round() as u8,
+            (a * 255.0).round() as u8,
+        )
+    }
+
+    /// Build the clip mask for a damage-limited frame (None = repaint everything)
+    fn damage_mask(&self) -> Option<Mask> {
+        if self.full_redraw || self.damage.is_empty() {
+            return None;
+        }
+        let mut builder = PathBuilder::new();
+        for rect in &self.damage {
+            // Snap outward so anti-aliased edges are repainted completely
+            let x0 = rect.left().floor();
+            let y0 = rect.top().floor();
+            let x1 = rect.right().ceil();
+            let y1 = rect.bottom().ceil();
+            if let Some(snapped) = Rect::from_ltrb(x0, y0, x1, y1) {
+                builder.push_rect(snapped);
+            }
+        }
+        let path = builder.finish()?;
+        let mut mask = Mask::new(self.width, self.height)?;
+        mask.fill_path(&path, tiny_skia::FillRule::Winding, false, Transform::identity());
+        Some(mask)
+    }
+
+    /// Number of rectangle commands culled as fully occluded in the last `render()`
+    pub fn culled_count(&self) -> usize {
+        self.culled_count
+    }
+
+    /// Counters from the command optimization pass of the last `render()`
+    pub fn optimize_stats(&self) -> optimize::OptimizeStats {
+        self.optimize_stats
+    }
+
+    /// Rasterization counters from the last `render()`
+    pub fn raster_stats(&self) -> RasterStats {
+        self.raster_stats
+    }
+
+    /// Bounding box of the area redrawn by the next `render()`
+    fn clip_region(&self) -> Rect {
+        let full = Rect::from_xywh(0.0, 0.0, self.width as f32, self.height as f32)
+            .expect("renderer size is at least 1x1");
+        if self.full_redraw || self.damage.is_empty() {
+            return full;
+        }
+        let bounds = self.damage.iter().skip(1).fold(self.damage[0], |acc, r| {
+            Rect::from_ltrb(
+                acc.left().min(r.left()),
+                acc.top().min(r.top()),
+                acc.right().max(r.right()),
+                acc.bottom().max(r.bottom()),
+            )
+            .unwrap_or(acc)
+        });
+        bounds.intersect(&full).unwrap_or(full)
+    }
+
+    /// Get a reference to the font manager
+    pub fn font_manager(&self) -> &FontManager {
+        &self.font_manager
+    }
+
+    /// Store a straight-alpha RGBA8 bitmap under a caller-chosen ID, replacing any previous one
+    ///
+    /// Returns false if `pixels` is smaller than `width * height * 4`.
+    pub fn register_bitmap(&mut self, id: u32, pixels: &[u8], width: u32, height: u32) -> bool {
+        let len = width as usize * height as usize * 4;
+        if width == 0 || height == 0 || pixels.len() < len {
+            return false;
+        }
+        let mut pixels = pixels[..len].to_vec();
+        color::premultiply(&mut pixels);
+        self.bitmaps.insert(id, Bitmap { width, height, pixels: pixels.into() });
+        true
+    }
+
+    /// Drop a registered bitmap
+    pub fn unregister_bitmap(&mut self, id: u32) -> bool {
+        self.bitmaps.remove(&id).is_some()
+    }
+
+    /// Get a registered bitmap
+    pub fn bitmap(&self, id: u32) -> Option<&Bitmap> {
+        self.bitmaps.get(&id)
+    }
+
+    /// Draw a registered bitmap scaled to the given rectangle
+    pub fn add_bitmap(&mut self, mut cmd: BitmapCommand) {
+        if self.apply_state(cmd.x, cmd.y, cmd.width, cmd.height).is_none() {
+            return;
+        }
+        (cmd.x, cmd.y, cmd.width, cmd.height) = self.state.map_rect(cmd.x, cmd.y, cmd.width, cmd.height);
+        self.bitmap_commands.push((cmd, self.state.paint));
+    }
+
+    /// Snapshot the recorded rectangles, images and text for a GPU-native onscreen window
+    ///
+    /// Blended rectangles, paths and images are drawn with normal blending, and
+    /// path, image and text clips are ignored; layers stay on the software path.
+    #[cfg(feature = "gpu")]
+    pub fn gpu_frame(&self) -> crate::renderer::GpuFrame {
+        let (r, g, b, a) = self.managed_clear_color();
+        let mut commands = self.commands.clone();
+        commands.extend(self.stateful_commands.iter().map(|(cmd, _, sequence)| (*cmd, *sequence)));
+        commands.extend(self.managed_retained().zip(self.next_sequence()..));
+        // The GPU renderer sorts by z-index only, so stacking contexts are baked into it
+        self.stacking.flatten(&mut commands);
+        let commands = commands.into_iter().map(|(cmd, _)| cmd).collect();
+        let text_commands = self
+            .text_commands
+            .iter()
+            .map(|t| crate::text::TextCommand {
+                text: t.text.clone(),
+                x: t.x,
+                y: t.y,
+                font_size: t.font_size,
+                color_r: t.color_r,
+                color_g: t.color_g,
+                color_b: t.color_b,
+                color_a: t.color_a,
+                font_id: t.font_id,
+                spacing: t.spacing,
+            })
+            .collect();
+        let image_draw = |image: crate::renderer::GpuImage, (x, y, width, height), z_index, paint: &PaintState| {
+            crate::renderer::ImageDraw {
+                image,
+                x,
+                y,
+                width,
+                height,
+                opacity: paint.opacity,
+                filter: paint.image_filter,
+                z_index,
+            }
+        };
+        let mut images = Vec::new();
+        #[cfg(feature = "images")]
+        for (cmd, paint) in &self.image_commands {
+            if let Some(image) = self.images.peek(cmd.image_id) {
+                let image = crate::renderer::GpuImage {
+                    width: image.width,
+                    height: image.height,
+                    pixels: image.pixels.clone(),
+                };
+                images.push(image_draw(image, (cmd.x, cmd.y, cmd.width, cmd.height), cmd.z_index, paint));
+            }
+        }
+        for (cmd, paint) in &self.bitmap_commands {
+            if let Some(bitmap) = self.bitmaps.get(&cmd.bitmap_id) {
+                let image = crate::renderer::GpuImage {
+                    width: bitmap.width,
+                    height: bitmap.height,
+                    pixels: bitmap.pixels.clone(),
+                };
+                images.push(image_draw(image, (cmd.x, cmd.y, cmd.width, cmd.height), cmd.z_index, paint));
+            }
+        }
+        crate::renderer::GpuFrame {
+            clear_color: [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, a as f32 / 255.0],
+            commands,
+            paths: self.path_commands.iter().map(|(cmd, _)| cmd.clone()).collect(),
+            images,
+            text_commands,
+            fonts: self.font_manager.shared_fonts(),
+        }
+    }
+
+    /// Get a mutable reference to the font manager
+    pub fn font_manager_mut(&mut self) -> &mut FontManager {
+        &mut self.font_manager
+    }
+
+    /// Render all commands to the pixmap
+    pub fn render(&mut self) {
+        // A damage-limited frame must also repaint where the caret appeared or disappeared
+        let caret = self.caret_rect_at(Instant::now());
+        if caret != self.drawn_caret && (self.auto_damage || !self.damage.is_empty()) {
+            for [x, y, width, height] in [self.drawn_caret, caret].into_iter().flatten() {
+                self.add_damage_rect(x.floor(), y.floor(), width.ceil() + 1.0, height.ceil() + 1.0);
+            }
+        }
+
+        if self.auto_damage && !self.collect_auto_damage() {
+            self.last_dirty = None;
+            self.raster_stats = RasterStats::default();
+            return;
+        }
+
+        // Clear pixmap with clear color, only inside the damaged area if one was given
+        let (r, g, b, a) = self.managed_clear_color();
+        let clip = self.damage_mask();
+        let region = self.clip_region();
+        let x0 = region.left().floor().max(0.0) as u32;
+        let y0 = region.top().floor().max(0.0) as u32;
+        let x1 = (region.right().ceil() as u32).min(self.width);
+        let y1 = (region.bottom().ceil() as u32).min(self.height);
+        self.last_dirty = (x1 > x0 && y1 > y0).then_some((x0, y0, x1 - x0, y1 - y0));
+        let glyphs_before = self.font_manager.glyph_counters();
+        let mut stats = RasterStats::default();
+        match &clip {
+            Some(mask) => {
+                let paint = Paint {
+                    shader: tiny_skia::Shader::SolidColor(Color::from_rgba8(r, g, b, a)),
+                    blend_mode: tiny_skia::BlendMode::Source,
+                    ..Paint::default()
+                };
+                let full = Rect::from_xywh(0.0, 0.0, self.width as f32, self.height as f32);
+                if let Some(full) = full {
+                    self.pixmap.fill_rect(full, &paint, Transform::identity(), Some(mask));
+                }
+            }
+            None => self.pixmap.fill(Color::from_rgba8(r, g, b, a)),
+        }
+        self.damage.clear();
+        self.full_redraw = false;
+
+        // Sort commands by z-index, simplify the list, drop rectangles outside the redrawn area,
+        // then drop rectangles whose visible part is hidden by later opaque ones
+        // Retained rectangles join the frame's immediate ones; the recorded list is left as added.
+        // Plain rectangles are simplified in runs between stateful ones, so none merges across one.
+        let viewport = (x0 as i32, y0 as i32, x1 as i32, y1 as i32);
+        let mut sorted = self.commands.clone();
+        sorted.extend(self.managed_retained().zip(self.next_sequence()..));
+        self.stacking.sort(&mut sorted);
+        self.stacking.sort_by(&mut self.stateful_commands, |(cmd, _, sequence)| (cmd, *sequence));
+        let mut frame = std::mem::take(&mut self.frame_commands);
+        frame.clear();
+        self.optimize_stats = optimize::OptimizeStats::default();
+        self.culled_count = 0;
+        // Number of plain rectangles painted before each stateful one
+        let mut stateful_at = Vec::with_capacity(self.stateful_commands.len());
+        let mut rest = &sorted[..];
+        for run in 0..=self.stateful_commands.len() {
+            let len = match self.stateful_commands.get(run) {
+                Some((b, _, sequence)) => {
+                    rest.partition_point(|(a, position)| self.stacking.compare((a, *position), (b, *sequence)).is_lt())
+                }
+                None => rest.len(),
+            };
+            let mut commands: Vec<RenderCommand> = rest[..len].iter().map(|(cmd, _)| *cmd).collect();
+            rest = &rest[len..];
+            let optimized = optimize::optimize_commands(&mut commands);
+            self.optimize_stats.dropped += optimized.dropped;
+            self.optimize_stats.deduplicated += optimized.deduplicated;
+            self.optimize_stats.merged += optimized.merged;
+            stats.clip_rejections += optimize::cull_outside(&mut commands, viewport) as u32;
+            self.culled_count += optimize::cull_occluded_in(&mut commands, viewport);
+            frame.append(&mut commands);
+            stateful_at.push(frame.len());
+        }
+        self.frame_commands = frame;
+
+        // Render rectangles - iterate by index to avoid borrow conflicts
+        // Each iteration clones a single command (small struct) instead of the whole vector.
+        // Stateful rectangles are interleaved in paint order.
+        // Large full redraws of plain rectangles are split into bands across cores instead.
+        let banded = clip.is_none() && self.stateful_commands.is_empty() && self.render_rects_banded(&region, &mut stats);
+        if !banded {
+            let (mut i, mut j) = (0, 0);
+            while i < self.frame_commands.len() || j < self.stateful_commands.len() {
+                let take_stateful = j < self.stateful_commands.len() && stateful_at[j] <= i;
+                let (cmd, state) = if take_stateful {
+                    j += 1;
+                    let (cmd, state, _) = self.stateful_commands[j - 1];
+                    (cmd, Some(state))
+                } else {
+                    i += 1;
+                    (self.frame_commands[i - 1], None)
+                };
+                let pixels = covered_pixels(&region, cmd.x, cmd.y, cmd.width, cmd.height);
+                if pixels == 0 {
+                    stats.clip_rejections += 1;
+                    continue;
+                }
+                match state {
+                    None => Self::render_rect_to_pixmap(&mut self.pixmap.as_mut(), &cmd, clip.as_ref(), self.linear_blending),
+                    Some(state) => Self::render_rect_with_state(&mut self.pixmap, &cmd, &state, clip.as_ref()),
+                }
+                stats.rects_filled += 1;
+                stats.pixels_touched += pixels;
+            }
+        }
+
+        // Paths draw above rectangles and below images, in the order added
+        for (cmd, paint) in &self.path_commands {
+            let Some([l, t, r, b]) = cmd.bounds() else {
+                continue;
+            };
+            let pixels = covered_pixels(&region, l, t, r - l, b - t);
+            if pixels == 0 {
+                stats.clip_rejections += 1;
+                continue;
+            }
+            Self::render_path_to_pixmap(&mut self.pixmap, cmd, paint, clip.as_ref());
+            stats.pixels_touched += pixels;
+        }
+
+        // Render images above rectangles and below text
+        #[cfg(feature = "images")]
+        {
+            self.image_commands.sort_by_key(|(c, _)| c.z_index);
+            for i in 0..self.image_commands.len() {
+                let (cmd, paint) = self.image_commands[i];
+                let pixels = covered_pixels(&region, cmd.x, cmd.y, cmd.width, cmd.height);
+                if pixels == 0 {
+                    stats.clip_rejections += 1;
+                    continue;
+                }
+                if Self::render_image_to_pixmap(&mut self.pixmap, &mut self.images, &cmd, &paint, clip.as_ref()) {
+                    stats.image_cache_hits += 1;
+                    stats.images_drawn += 1;
+                    stats.pixels_touched += pixels;
+                } else {
+                    stats.image_cache_misses += 1;
+                }
+            }
+        }
+
+        // Registered bitmaps share the image stage
+        self.bitmap_commands.sort_by_key(|(c, _)| c.z_index);
+        for i in 0..self.bitmap_commands.len() {
+            let (cmd, paint) = self.bitmap_commands[i];
+            let pixels = covered_pixels(&region, cmd.x, cmd.y, cmd.width, cmd.height);
+            if pixels == 0 {
+                stats.clip_rejections += 1;
+                continue;
+            }
+            match self.bitmaps.get(&cmd.bitmap_id) {
+                Some(bitmap) => {
+                    Self::blit_premultiplied(
+                        &mut self.pixmap,
+                        &bitmap.pixels,
+                        bitmap.width,
+                        bitmap.height,
+                        (cmd.x, cmd.y, cmd.width, cmd.height),
+                        &paint,
+                        clip.as_ref(),
+                    );
+                    stats.image_cache_hits += 1;
+                    stats.images_drawn += 1;
+                    stats.pixels_touched += pixels;
+                }
+                None => stats.image_cache_misses += 1,
+            }
+        }
+
+        // Rasterize the frame's missing glyphs up front (in parallel for large batches)
+        self.font_manager.prepare_glyphs(
+            self.text_commands
+                .iter()
+                .filter(|t| t.x < region.right() && t.y < region.bottom())
+                .map(|t| (t.text.as_str(), t.font_size, t.font_id)),
+        );
+
+        // Render text commands
+        for i in 0..self.text_commands.len() {
+            let text_cmd = self.text_commands[i].clone();
+            // Text extents are unknown before shaping, so only reject runs starting past the region
+            if text_cmd.x >= region.right() || text_cmd.y >= region.bottom() {
+                stats.clip_rejections += 1;
+                continue;
+            }
+            stats.text_runs += 1;
+            stats.pixels_touched += Self::render_text_to_pixmap(
+                &mut self.pixmap,
+                &mut self.font_manager,
+                &text_cmd,
+                self.text_clips.get(i).copied().flatten(),
+                clip.as_ref(),
+                self.linear_blending,
+                self.text_antialias,
+            );
+        }
+
+        // Composite layers on top of the base content
+        self.composite_layers(clip.as_ref(), &region, &mut stats);
+
+        // The caret goes above everything
+        if let Some([x, y, width, height]) = caret {
+            let [r, g, b, a] = self.caret.map_or([0.0; 4], |c| self.color.convert_color(c.color, ColorSpace::Srgb));
+            let paint = Paint {
+                shader: tiny_skia::Shader::SolidColor(Color::from_rgba(r, g, b, a).unwrap_or(Color::BLACK)),
+                anti_alias: false,
+                ..Paint::default()
+            };
+            if let Some(rect) = Rect::from_xywh(x.round(), y.round(), width.round().max(1.0), height.round()) {
+                self.pixmap.fill_rect(rect, &paint, Transform::identity(), clip.as_ref());
+            }
+        }
+        self.drawn_caret = caret;
+
+        let glyphs = self.font_manager.glyph_counters();
+        stats.glyphs_rasterized = (glyphs.rasterized - glyphs_before.rasterized) as u32;
+        stats.glyph_metrics_hits = (glyphs.metrics_hits - glyphs_before.metrics_hits) as u32;
+        stats.glyph_metrics_misses = (glyphs.metrics_misses - glyphs_before.metrics_misses) as u32;
+        stats.glyphs_evicted = (glyphs.evicted - glyphs_before.evicted) as u32;
+        self.raster_stats = stats;
+    }
+
+    /// Fill the frame's rectangles in horizontal bands on the rayon pool
+    ///
+    /// Each band is a strip of the pixmap that draws, in order, every
+    /// rectangle overlapping it, shifted into band coordinates; a rectangle
+    /// spanning several bands is drawn once per band and cut at the band
+    /// edges. Overlapping rectangles therefore composite in the same order as
+    /// on one core. Returns false, drawing nothing, if the frame is too small
+    /// to be worth splitting.
+    #[cfg(feature = "parallel")]
+    fn render_rects_banded(&mut self, region: &Rect, stats: &mut RasterStats) -> bool {
+        use rayon::prelude::*;
+
+        let threads = rayon::current_num_threads() as u32;
+        if !self.parallel
+            || threads < 2
+            || self.frame_commands.len() < PARALLEL_MIN_COMMANDS
+            || self.height < 2 * MIN_BAND_HEIGHT
+        {
+            return false;
+        }
+
+        let mut visible = Vec::with_capacity(self.frame_commands.len());
+        for cmd in &self.frame_commands {
+            let pixels = covered_pixels(region, cmd.x, cmd.y, cmd.width, cmd.height);
+            if pixels == 0 {
+                stats.clip_rejections += 1;
+                continue;
+            }
+            stats.rects_filled += 1;
+            stats.pixels_touched += pixels;
+            visible.push(*cmd);
+        }
+
+        let width = self.width;
+        let band_height = self.height.div_ceil(threads).max(MIN_BAND_HEIGHT);
+        let stride = width as usize * 4;
+        let linear = self.linear_blending;
+        self.pixmap
+            .data_mut()
+            .par_chunks_mut(stride * band_height as usize)
+            .enumerate()
+            .for_each(|(band, data)| {
+                let rows = (data.len() / stride) as u32;
+                let top = (band as u32 * band_height) as f32;
+                let bottom = top + rows as f32;
+                let Some(mut pixmap) = PixmapMut::from_bytes(data, width, rows) else {
+                    return;
+                };
+                for cmd in visible.iter().filter(|c| c.y < bottom && c.y + c.height > top) {
+                    let shifted = RenderCommand { y: cmd.y - top, ..*cmd };
+                    Self::render_rect_to_pixmap(&mut pixmap, &shifted, None, linear);
+                }
+            });
+        true
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn render_rects_banded(&mut self, _region: &Rect, _stats: &mut RasterStats) -> bool {
+        false
+    }
+
+    /// Rasterize dirty layers and blit every layer at its current position
+    fn composite_layers(&mut self, clip: Option<&Mask>, region: &Rect, stats: &mut RasterStats) {
+        for id in self.layers.draw_order() {
+            let Some(layer) = self.layers.get_mut(id) else {
+                continue;
+            };
+
+            let pixels = covered_pixels(
+                region,
+                layer.x.round(),
+                layer.y.round(),
+                layer.width as f32,
+                layer.height as f32,
+            );
+            if pixels == 0 {
+                stats.clip_rejections += 1;
+                continue;
+            }
+
+            if layer.dirty || layer.pixmap.is_none() {
+                stats.layer_cache_misses += 1;
+                let mut pixmap = match layer.pixmap.take() {
+                    Some(p) => p,
+                    None => match Pixmap::new(layer.width, layer.height) {
+                        Some(p) => p,
+                        None => continue,
+                    },
+                };
+                pixmap.fill(Color::TRANSPARENT);
+
+                layer.commands.sort_by_key(|c| c.z_index);
+                for cmd in &layer.commands {
+                    Self::render_rect_to_pixmap(&mut pixmap.as_mut(), cmd, None, self.linear_blending);
+                }
+                for cmd in &layer.text_commands {
+                    stats.text_runs += 1;
+                    Self::render_text_to_pixmap(
+                        &mut pixmap,
+                        &mut self.font_manager,
+                        cmd,
+                        None,
+                        None,
+                        self.linear_blending,
+                        TextAntialias::Grayscale,
+                    );
+                }
+
+                layer.pixmap = Some(pixmap);
+                layer.dirty = false;
+                layer.raster_count += 1;
+            } else {
+                stats.layer_cache_hits += 1;
+            }
+
+            if let Some(pixmap) = &layer.pixmap {
+                stats.pixels_touched += pixels;
+                self.pixmap.draw_pixmap(
+                    layer.x.round() as i32,
+                    layer.y.round() as i32,
+                    pixmap.as_ref(),
+                    &tiny_skia::PixmapPaint::default(),
+                    Transform::identity(),
+                    clip,
+                );
+            }
+        }
+    }
+
+    /// Render a rectangle to the pixmap (static method to avoid borrow conflicts)
+    fn render_rect_to_pixmap(pixmap: &mut PixmapMut, cmd: &RenderCommand, clip: Option<&Mask>, linear: bool) {
+        if cmd.width <= 0.0 || cmd.height <= 0.0 {
+            return;
+        }
+
+        // Linear blending uses exact box coverage, so rounded corners take the path fill
+        if linear && !cmd.is_rounded() {
+            Self::render_rect_linear(pixmap, cmd, clip);
+            return;
+        }
+
+        let Some(path) = Self::rect_path(cmd) else {
+            return;
+        };
+
+        let mut paint = Paint::default();
+        paint.set_color(Color::from_rgba(
+            cmd.color_r,
+            cmd.color_g,
+            cmd.color_b,
+            cmd.color_a,
+        ).unwrap_or(Color::BLACK));
+        paint.anti_alias = true;
+
+        pixmap.fill_path(
+            &path,
+            &paint,
+            tiny_skia::FillRule::Winding,
+            Transform::identity(),
+            clip,
+        );
+    }
+
+    /// Outline of a rectangle command, with circular arcs for rounded corners
+    fn rect_path(cmd: &RenderCommand) -> Option<tiny_skia::Path> {
+        let rect = Rect::from_xywh(cmd.x, cmd.y, cmd.width, cmd.height)?;
+        if !cmd.is_rounded() {
+            return Some(PathBuilder::from_rect(rect));
+        }
+
+        Self::skia_path(&Path::rounded_rect(
+            rect.x(),
+            rect.y(),
+            rect.width(),
+            rect.height(),
+            cmd.clamped_radii(),
+        ))
+    }
+
+    /// Render a rectangle with its own blend mode and clip
+    fn render_rect_with_state(pixmap: &mut Pixmap, cmd: &RenderCommand, state: &PaintState, clip: Option<&Mask>) {
+        let Some(path) = Self::rect_path(cmd) else {
+            return;
+        };
+        let mut paint = Paint {
+            blend_mode: state.blend_mode.to_skia(),
+            anti_alias: true,
+            ..Paint::default()
+        };
+        paint.set_color(
+            Color::from_rgba(cmd.color_r, cmd.color_g, cmd.color_b, cmd.color_a).unwrap_or(Color::BLACK),
+        );
+
+        let Some(own_mask) = Self::paint_mask(pixmap, state, clip) else {
+            return;
+        };
+        pixmap.fill_path(
+            &path,
+            &paint,
+            tiny_skia::FillRule::Winding,
+            Transform::identity(),
+            own_mask.as_ref().or(clip),
+        );
+    }
+
+    /// Combine a command's clip rectangle with the damage mask
+    ///
+    /// Returns None if the clip rejects everything, and Some(None) if the
+    /// command has no clip of its own so only the damage mask applies.
+    fn paint_mask(pixmap: &Pixmap, state: &PaintState, clip: Option<&Mask>) -> Option<Option<Mask>> {
+        let Some(bounds) = state.clip_rect() else {
+            return if state.clip.is_some() { None } else { Some(None) };
+        };
+        let mut mask = Mask::new(pixmap.width(), pixmap.height())?;
+        mask.fill_path(
+            &PathBuilder::from_rect(bounds),
+            tiny_skia::FillRule::Winding,
+            true,
+            Transform::identity(),
+        );
+        if let Some(damage) = clip {
+            for (m, d) in mask.data_mut().iter_mut().zip(damage.data()) {
+                *m = ((*m as u16 * *d as u16) / 255) as u8;
+            }
+        }
+        Some(Some(mask))
+    }
+
+    /// Convert a path into a tiny-skia path (None if it has no drawable segments)
+    fn skia_path(path: &Path) -> Option<tiny_skia::Path> {
+        let mut pb = PathBuilder::new();
+        for segment in path.segments() {
+            match *segment {
+                PathSegment::MoveTo(x, y) => pb.move_to(x, y),
+                PathSegment::LineTo(x, y) => pb.line_to(x, y),
+                PathSegment::QuadTo(cx, cy, x, y) => pb.quad_to(cx, cy, x, y),
+                PathSegment::CubicTo(c1x, c1y, c2x, c2y, x, y) => pb.cubic_to(c1x, c1y, c2x, c2y, x, y),
+                PathSegment::Close => pb.close(),
+            }
+        }
+        pb.finish()
+    }
+
+    /// Fill or stroke a path with its own blend mode and clip
+    fn render_path_to_pixmap(pixmap: &mut Pixmap, cmd: &PathCommand, state: &PaintState, clip: Option<&Mask>) {
+        let Some(path) = Self::skia_path(&cmd.path) else {
+            return;
+        };
+        let Some(own_mask) = Self::paint_mask(pixmap, state, clip) else {
+            return;
+        };
+        let mut paint = Paint {
+            blend_mode: state.blend_mode.to_skia(),
+            anti_alias: true,
+            ..Paint::default()
+        };
+        let [r, g, b, a] = cmd.color;
+        paint.set_color(Color::from_rgba(r, g, b, a).unwrap_or(Color::BLACK));
+
+        let mask = own_mask.as_ref().or(clip);
+        match cmd.paint {
+            PathPaint::Fill(rule) => {
+                let rule = match rule {
+                    FillRule::NonZero => tiny_skia::FillRule::Winding,
+                    FillRule::EvenOdd => tiny_skia::FillRule::EvenOdd,
+                };
+                pixmap.fill_path(&path, &paint, rule, Transform::identity(), mask);
+            }
+            PathPaint::Stroke(style) => {
+                let stroke = tiny_skia::Stroke {
+                    width: style.width,
+                    line_cap: match style.cap {
+                        LineCap::Butt => tiny_skia::LineCap::Butt,
+                        LineCap::Round => tiny_skia::LineCap::Round,
+                        LineCap::Square => tiny_skia::LineCap::Square,
+                    },
+                    line_join: match style.join {
+                        LineJoin::Miter => tiny_skia::LineJoin::Miter,
+                        LineJoin::Round => tiny_skia::LineJoin::Round,
+                    },
+                    ..tiny_skia::Stroke::default()
+                };
+                pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), mask);
+            }
+        }
+    }
+
+    /// Fill an axis-aligned rectangle with exact edge coverage, blending in linear light
+    fn render_rect_linear(pixmap: &mut PixmapMut, cmd: &RenderCommand, clip: Option<&Mask>) {
+        let w = pixmap.width() as i32;
+        let h = pixmap.height() as i32;
+        let x0 = cmd.x.max(0.0);
+        let y0 = cmd.y.max(0.0);
+        let x1 = (cmd.x + cmd.width).min(w as f32);
+        let y1 = (cmd.y + cmd.height).min(h as f32);
+        if x0 >= x1 || y0 >= y1 {
+            return;
+        }
+
+        let src = [
+            (cmd.color_r.clamp(0.0, 1.0) * 255.0).round() as u8,
+            (cmd.color_g.clamp(0.0, 1.0) * 255.0).round() as u8,
+            (cmd.color_b.clamp(0.0, 1.0) * 255.0).round() as u8,
+        ];
+        let alpha = cmd.color_a.clamp(0.0, 1.0);
+        let clip_data = clip.map(|m| m.data());
+        let data = pixmap.data_mut();
+
+        // Fraction of the pixel span [p, p + 1) covered by [lo, hi)
+        let coverage = |p: i32, lo: f32, hi: f32| (hi.min(p as f32 + 1.0) - lo.max(p as f32)).clamp(0.0, 1.0);
+
+        for py in y0.floor() as i32..y1.ceil() as i32 {
+            let cy = coverage(py, y0, y1);
+            for px in x0.floor() as i32..x1.ceil() as i32 {
+                let idx = (py * w + px) as usize;
+                if clip_data.is_some_and(|m| m[idx] == 0) {
+                    continue;
+                }
+                let a = alpha * cy * coverage(px, x0, x1);
+                color::blend_linear(&mut data[idx * 4..idx * 4 + 4], src, a);
+            }
+        }
+    }
+
+    /// Blit a cached image to the pixmap (static method to avoid borrow conflicts)
+    /// Returns false if the image is not in the cache
+    #[cfg(feature = "images")]
+    fn render_image_to_pixmap(
+        pixmap: &mut Pixmap,
+        images: &mut ImageCache,
+        cmd: &ImageCommand,
+        paint: &PaintState,
+        clip: Option<&Mask>,
+    ) -> bool {
+        let Some(image) = images.get(cmd.image_id) else {
+            return false;
+        };
+        Self::blit_premultiplied(
+            pixmap,
+            &image.pixels,
+            image.width,
+            image.height,
+            (cmd.x, cmd.y, cmd.width, cmd.height),
+            paint,
+            clip,
+        );
+        true
+    }
+
+    /// Draw premultiplied RGBA8 pixels scaled into the `(x, y, width, height)` rectangle
+    ///
+    /// Only the part inside `state`'s clip is filled.
+    fn blit_premultiplied(
+        pixmap: &mut Pixmap,
+        pixels: &[u8],
+        image_width: u32,
+        image_height: u32,
+        (x, y, width, height): (f32, f32, f32, f32),
+        state: &PaintState,
+        clip: Option<&Mask>,
+    ) {
+        let Some(mut rect) = Rect::from_xywh(x, y, width, height) else {
+            return;
+        };
+        if state.clip.is_some() {
+            match state.clip_rect().and_then(|c| rect.intersect(&c)) {
+                Some(r) => rect = r,
+                None => return,
+            }
+        }
+        let Some(source) = tiny_skia::PixmapRef::from_bytes(pixels, image_width, image_height) else {
+            return;
+        };
+
+        // Map image space onto the destination rectangle
+        let transform = Transform::from_row(
+            width / image_width as f32,
+            0.0,
+            0.0,
+            height / image_height as f32,
+            x,
+            y,
+        );
+        let paint = Paint {
+            shader: tiny_skia::Pattern::new(
+                source,
+                tiny_skia::SpreadMode::Pad,
+                match state.image_filter {
+                    ImageFilter::Bilinear => tiny_skia::FilterQuality::Bilinear,
+                    ImageFilter::Nearest => tiny_skia::FilterQuality::Nearest,
+                },
+                state.opacity,
+                transform,
+            ),
+            blend_mode: state.blend_mode.to_skia(),
+            anti_alias: true,
+            ..Paint::default()
+        };
+        pixmap.fill_rect(rect, &paint, Transform::identity(), clip);
+    }
+
+    /// Render text to the pixmap (static method to avoid borrow conflicts)
+    /// Returns the number of destination pixels blended; pixels outside `bounds` are skipped
+    fn render_text_to_pixmap(
+        pixmap: &mut Pixmap,
+        font_manager: &mut FontManager,
+        cmd: &TextCommand,
+        bounds: Option<ClipBounds>,
+        clip: Option<&Mask>,
+        linear: bool,
+        antialias: TextAntialias,
+    ) -> u64 {
+        if cmd.text.is_empty() {
+            return 0;
+        }
+        // Color glyphs have no per-channel coverage
+        if antialias != TextAntialias::Grayscale && !font_manager.has_color_glyphs(cmd.font_id) {
+            return Self::render_subpixel_text_to_pixmap(pixmap, font_manager, cmd, bounds, clip, linear, antialias);
+        }
+
+        let color = (
+            (cmd.color_r * 255.0) as u8,
+            (cmd.color_g * 255.0) as u8,
+            (cmd.color_b * 255.0) as u8,
+            (cmd.color_a * 255.0) as u8,
+        );
+
+        let (text_buffer, text_w, text_h) = font_manager.rasterize_text(
+            &cmd.text,
+            cmd.font_size,
+            cmd.font_id,
+            color,
+            cmd.spacing,
+        );
+
+        if text_buffer.is_empty() || text_w == 0 || text_h == 0 {
+            return 0;
+        }
+
+        // Blit text to pixmap
+        let tx = cmd.x as i32;
+        let ty = cmd.y as i32;
+        let w = pixmap.width() as i32;
+        let h = pixmap.height() as i32;
+        let (x0, y0, x1, y1) = match bounds {
+            Some([l, t, r, b]) => (
+                (l.round() as i32).max(0),
+                (t.round() as i32).max(0),
+                (r.round() as i32).min(w),
+                (b.round() as i32).min(h),
+            ),
+            None => (0, 0, w, h),
+        };
+        let clip_data = clip.map(|m| m.data());
+        let pixmap_data = pixmap.data_mut();
+        let mut touched = 0;
+
+        // Visible part of the text box, one row span per line
+        let (cx0, cx1) = (tx.max(x0), (tx + text_w as i32).min(x1));
+        if cx0 < cx1 {
+            for py in ty.max(y0)..(ty + text_h as i32).min(y1) {
+                let src_row = ((py - ty) as u32 * text_w) as usize;
+                let src = &text_buffer[(src_row + (cx0 - tx) as usize) * 4..(src_row + (cx1 - tx) as usize) * 4];
+                let dst_row = (py * w) as usize;
+                let span = dst_row + cx0 as usize..dst_row + cx1 as usize;
+                let mask = clip_data.map(|m| &m[span.clone()]);
+                let dst = &mut pixmap_data[span.start * 4..span.end * 4];
+                if !linear {
+                    touched += blend::blend_row(dst, src, mask) as u64;
+                    continue;
+                }
+                for (i, (d, s)) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)).enumerate() {
+                    if mask.is_some_and(|m| m[i] == 0) {
+                        continue;
+                    }
+                    let src_a = s[3] as f32 / 255.0;
+                    if src_a > 0.0 {
+                        touched += 1;
+                    }
+                    color::blend_linear(d, [s[0], s[1], s[2]], src_a);
+                }
+            }
+        }
+
+        touched += Self::render_text_decorations(pixmap, font_manager, cmd, (tx, ty), bounds, clip);
+        touched
+    }
+
+    /// Render text with per-channel coverage, blending each channel by its own coverage
+    fn render_subpixel_text_to_pixmap(
+        pixmap: &mut Pixmap,
+        font_manager: &mut FontManager,
+        cmd: &TextCommand,
+        bounds: Option<ClipBounds>,
+        clip: Option<&Mask>,
+        linear: bool,
+        antialias: TextAntialias,
+    ) -> u64 {
+        let (coverage, text_w, text_h) = font_manager.rasterize_text_subpixel(
+            &cmd.text,
+            cmd.font_size,
+            cmd.font_id,
+            antialias == TextAntialias::SubpixelBgr,
+            cmd.spacing,
+        );
+        if coverage.is_empty() || text_w == 0 || text_h == 0 {
+            return 0;
+        }
+
+        let color = [
+            (cmd.color_r * 255.0) as u8,
+            (cmd.color_g * 255.0) as u8,
+            (cmd.color_b * 255.0) as u8,
+        ];
+        let tx = cmd.x as i32;
+        let ty = cmd.y as i32;
+        let w = pixmap.width() as i32;
+        let h = pixmap.height() as i32;
+        let (x0, y0, x1, y1) = match bounds {
+            Some([l, t, r, b]) => (
+                (l.round() as i32).max(0),
+                (t.round() as i32).max(0),
+                (r.round() as i32).min(w),
+                (b.round() as i32).min(h),
+            ),
+            None => (0, 0, w, h),
+        };
+        let clip_data = clip.map(|m| m.data());
+        let pixmap_data = pixmap.data_mut();
+        let mut touched = 0;
+
+        let (cx0, cx1) = (tx.max(x0), (tx + text_w as i32).min(x1));
+        if cx0 < cx1 {
+            for py in ty.max(y0)..(ty + text_h as i32).min(y1) {
+                let src_row = ((py - ty) as u32 * text_w) as usize;
+                let src = &coverage[(src_row + (cx0 - tx) as usize) * 3..(src_row + (cx1 - tx) as usize) * 3];
+                let dst_row = (py * w) as usize;
+                let span = dst_row + cx0 as usize..dst_row + cx1 as usize;
+                let mask = clip_data.map(|m| &m[span.clone()]);
+                let dst = &mut pixmap_data[span.start * 4..span.end * 4];
+                if !linear {
+                    let alpha = (cmd.color_a.clamp(0.0, 1.0) * 255.0) as u8;
+                    touched += blend::blend_subpixel_row(dst, color, alpha, src, mask) as u64;
+                    continue;
+                }
+                for (i, (d, s)) in dst.chunks_exact_mut(4).zip(src.chunks_exact(3)).enumerate() {
+                    if mask.is_some_and(|m| m[i] == 0) {
+                        continue;
+                    }
+                    let cov = [0, 1, 2].map(|c| s[c] as f32 / 255.0 * cmd.color_a);
+                    if cov.iter().all(|&a| a <= 0.0) {
+                        continue;
+                    }
+                    touched += 1;
+                    color::blend_linear_subpixel(d, color, cov);
+                }
+            }
+        }
+
+        touched + Self::render_text_decorations(pixmap, font_manager, cmd, (tx, ty), bounds, clip)
+    }
+
+    /// Draw underline, overline and line-through for a text run drawn at `origin`
+    fn render_text_decorations(
+        pixmap: &mut Pixmap,
+        font_manager: &FontManager,
+        cmd: &TextCommand,
+        (tx, ty): (i32, i32),
+        bounds: Option<ClipBounds>,
+        clip: Option<&Mask>,
+    ) -> u64 {
+        let mut touched = 0;
+        if !cmd.decoration.is_empty() {
+            let run = font_manager.layout_run(&cmd.text, cmd.font_size, cmd.font_id, cmd.spacing);
+            let [r, g, b, a] = cmd
+                .decoration_color
+                .unwrap_or([cmd.color_r, cmd.color_g, cmd.color_b, cmd.color_a]);
+            let mut paint = Paint {
+                anti_alias: true,
+                ..Paint::default()
+            };
+            paint.set_color(Color::from_rgba(r, g, b, a).unwrap_or(Color::BLACK));
+            let bounds = bounds.and_then(|[l, t, r, b]| Rect::from_ltrb(l, t, r, b));
+            for (x, y, width, height) in font_manager.decoration_rects(&run, cmd.font_size, cmd.font_id, cmd.decoration)
+            {
+                let rect = Rect::from_xywh(tx as f32 + x, ty as f32 + y, width, height);
+                let rect = match bounds {
+                    Some(bounds) => rect.and_then(|r| r.intersect(&bounds)),
+                    None => rect,
+                };
+                if let Some(rect) = rect {
+                    pixmap.fill_rect(rect, &paint, Transform::identity(), clip);
+                    touched += (rect.width() * rect.height()) as u64;
+                }
+            }
+        }
+        touched
+    }
+
+    /// Get the framebuffer as raw RGBA bytes
+    pub fn get_framebuffer(&self) -> &[u8] {
+        self.pixmap.data()
+    }
+
+    /// Replace the framebuffer with straight-alpha RGBA8 pixels (e.g. a GPU read-back)
+    ///
+    /// Returns false if `pixels` does not hold exactly one frame.
+    pub fn write_framebuffer(&mut self, pixels: &[u8]) -> bool {
+        let data = self.pixmap.data_mut();
+        if pixels.len() != data.len() {
+            return false;
+        }
+        data.copy_from_slice(pixels);
+        color::premultiply(data);
+        true
+    }
+
+    /// Get a copy of the framebuffer
+    pub fn get_framebuffer_copy(&self) -> Vec<u8> {
+        self.pixmap.data().to_vec()
+    }
+
+    /// Get the framebuffer size in bytes
+    pub fn get_framebuffer_size(&self) -> usize {
+        self.pixmap.data().len()
+    }
+
+    /// Export the framebuffer to a PNG file
+    pub fn export_png(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let file = std::fs::File::create(path)?;
+        let w = std::io::BufWriter::new(file);
+        let mut encoder = png::Encoder::new(w, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(self.pixmap.data())?;
+
+        Ok(())
+    }
+
+    /// Encode the framebuffer in memory
+    pub fn export_to_buffer(&self, format: ExportFormat) -> Result<Vec<u8>, String> {
+        encode::encode_rgba(self.pixmap.data(), self.width, self.height, format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_software_renderer_creation() {
+        let renderer = SoftwareRenderer::new(100, 100);
+        assert_eq!(renderer.size(), (100, 100));
+    }
+
+    #[test]
+    fn test_software_renderer_clear_color() {
+        let mut renderer = SoftwareRenderer::new(100, 100);
+        renderer.set_clear_color(1.0, 0.0, 0.0, 1.0);
+        renderer.render();
+
+        let data = renderer.get_framebuffer();
+        // First pixel should be red
+        assert_eq!(data[0], 255); // R
+        assert_eq!(data[1], 0);   // G
+        assert_eq!(data[2], 0);   // B
+        assert_eq!(data[3], 255); // A
+    }
+
+    #[test]
+    fn test_software_renderer_add_rect() {
+        let mut renderer = SoftwareRenderer::new(100, 100);
+        renderer.set_clear_color(1.0, 1.0, 1.0, 1.0);
+        renderer.add_rect(RenderCommand {
+            x: 10.0,
+            y: 10.0,
+            width: 50.0,
+            height: 50.0,
+            color_r: 0.0,
+            color_g: 0.0,
+            ..Default::default()
+        });
+        renderer.render();
+
+        let data = renderer.get_framebuffer();
+        // Check a pixel inside the rectangle (at 25, 25)
+        let idx = ((25 * 100) + 25) * 4;
+        assert_eq!(data[idx], 0);     // R
+        assert_eq!(data[idx + 1], 0); // G
+        assert_eq!(data[idx + 2], 255); // B
+        assert_eq!(data[idx + 3], 255); // A
+    }
+
+    #[cfg(feature = "content-ir")]
+    #[test]
+    fn test_software_renderer_content_commands() {
+        use dop_content_ir::render::{render, RenderCommand as ContentCommand};
+        use dop_content_ir::{Color as ContentColor, ContentBuilder};
+
+        let mut builder = ContentBuilder::new();
+        builder.rect().width(40.0).height(40.0).fill(ContentColor::new(0, 255, 0, 255));
+        let (nodes, props) = builder.build();
+
+        let mut renderer = SoftwareRenderer::new(100, 100);
+        renderer.set_clear_color(1.0, 1.0, 1.0, 1.0);
+        renderer.add_content_commands(&render(&nodes, &props, 100.0, 100.0));
+        // A red rectangle clipped to the box below the green one
+        renderer.add_content_commands(&[
+            ContentCommand::PushClip { x: 0.0, y: 50.0, width: 50.0, height: 50.0 },
+            ContentCommand::FillRect { x: 0.0, y: 50.0, width: 80.0, height: 20.0, r: 255, g: 0, b: 0, a: 255, border_radius: 0.0 },
+            ContentCommand::PopClip,
+        ]);
+        renderer.render();
+
+        let data = renderer.get_framebuffer();
+        let idx = ((20 * 100) + 20) * 4;
+        assert_eq!(data[idx], 0);
+        assert_eq!(data[idx + 1], 255);
+        assert_eq!(data[idx + 2], 0);
+        let pixel = |x: usize, y: usize| &data[(y * 100 + x) * 4..][..3];
+        assert_eq!(pixel(25, 60), &[255, 0, 0]);
+        assert_eq!(pixel(75, 60), &[255, 255, 255]);
+    }
+
+    #[cfg(feature = "content-ir")]
+    #[test]
+    fn test_software_renderer_compiled_unit() {
+        use crate::ffi::{
+            dop_renderer_create_headless, dop_renderer_free, dop_renderer_get_framebuffer, dop_renderer_render,
+            dop_renderer_render_compiled_unit,
+        };
+        use dop_content_ir::{Color as ContentColor, ContentBuilder};
+
+        let mut builder = ContentBuilder::new();
+        builder.rect().width(40.0).height(40.0).fill(ContentColor::new(0, 0, 255, 255));
+        let unit = builder.to_binary();
+
+        let handle = dop_renderer_create_headless(100, 100);
+        assert_eq!(dop_renderer_render_compiled_unit(handle, b"not a unit".as_ptr(), 10, 100.0, 100.0), -1);
+        assert!(dop_renderer_render_compiled_unit(handle, unit.as_ptr(), unit.len(), 100.0, 100.0) > 0);
+        dop_renderer_render(handle);
+
+        let data = unsafe { std::slice::from_raw_parts(dop_renderer_get_framebuffer(handle), 100 * 100 * 4) };
+        let idx = ((20 * 100) + 20) * 4;
+        assert_eq!(&data[idx..idx + 3], &[0, 0, 255]);
+        dop_renderer_free(handle);
+    }
+
+    #[cfg(feature = "content-ir")]
+    #[test]
+    fn test_software_renderer_rejects_corrupt_compiled_unit() {
+        use crate::ffi::{dop_renderer_create_headless, dop_renderer_free, dop_renderer_render_compiled_unit};
+        use dop_content_ir::{Color as ContentColor, ContentBuilder};
+
+        let mut builder = ContentBuilder::new();
+        builder.rect().width(40.0).height(40.0).fill(ContentColor::new(0, 0, 255, 255));
+        builder.rect().width(40.0).height(40.0);
+        let unit = builder.to_binary();
+
+        // Header is 24 bytes, then 17 bytes per node with links at +1, +5 and +9
+        let link = |id: usize, field: usize, value: u32| {
+            let mut bytes = unit.clone();
+            let at = 24 + (id - 1) * 17 + field;
+            bytes[at..at + 4].copy_from_slice(&value.to_le_bytes());
+            bytes
+        };
+        let mut huge_styles = unit.clone();
+        huge_styles[24 + 3 * 17..24 + 3 * 17 + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let corrupt = [
+            unit[..unit.len() / 2].to_vec(),
+            link(1, 5, 999),
+            link(2, 9, 2),
+            link(3, 1, 2),
+            huge_styles,
+        ];
+
+        let handle = dop_renderer_create_headless(100, 100);
+        for bytes in &corrupt {
+            assert_eq!(dop_renderer_render_compiled_unit(handle, bytes.as_ptr(), bytes.len(), 100.0, 100.0), -1);
+        }
+        assert!(dop_renderer_render_compiled_unit(handle, unit.as_ptr(), unit.len(), 100.0, 100.0) > 0);
+        dop_renderer_free(handle);
+    }
+
+    #[cfg(feature = "images")]
+    #[test]
+    fn test_software_renderer_image() {
+        use crate::images::DecodedImage;
+
+        let mut renderer = SoftwareRenderer::new(100, 100);
+        renderer.set_clear_color(1.0, 1.0, 1.0, 1.0);
+        // 2x2 opaque red image scaled up to 40x40
+        let id = renderer.images_mut().insert(
+            "red",
+            DecodedImage {
+                width: 2,
+                height: 2,
+                pixels: [255, 0, 0, 255].repeat(4).into(),
+            },
+        );
+        renderer.add_image(ImageCommand {
+            image_id: id,
+            x: 10.0,
+            y: 10.0,
+            width: 40.0,
+            height: 40.0,
+            z_index: 0,
+        });
+        renderer.render();
+
+        let data = renderer.get_framebuffer();
+        let idx = ((30 * 100) + 30) * 4;
+        assert_eq!(&data[idx..idx + 4], &[255, 0, 0, 255]);
+        let outside = ((70 * 100) + 70) * 4;
+        assert_eq!(&data[outside..outside + 4], &[255, 255, 255, 255]);
+    }
+
+    #[test]
+    #[cfg(feature = "images")]
+    fn test_software_renderer_decode_image() {
+        use image::codecs::gif::GifEncoder;
+        use image::{Delay, Frame, ImageFormat, Rgba, RgbaImage};
+
+        let mut renderer = SoftwareRenderer::new(4, 4);
+        let mut png = Vec::new();
+        RgbaImage::from_pixel(3, 2, Rgba([0, 255, 0, 255]))
+            .write_to(&mut std::io::Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let first = renderer.decode_image(&png).unwrap();
+        let second = renderer.decode_image(&png).unwrap();
+        assert_ne!(first, second);
+        let image = renderer.images().peek(first).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        assert!(renderer.decode_image(b"not an image").is_err());
+
+        // Animated GIFs decode to their first frame only
+        let mut gif = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut gif);
+            for color in [[255, 0, 0, 255], [0, 0, 255, 255]] {
+                let frame = Frame::from_parts(RgbaImage::from_pixel(2, 2, Rgba(color)), 0, 0, Delay::from_numer_denom_ms(100, 1));
+                encoder.encode_frame(frame).unwrap();
+            }
+        }
+        let id = renderer.decode_image(&gif).unwrap();
+        assert_eq!(renderer.images().frame_count(id), 1);
+        assert_eq!(&renderer.images().peek(id).unwrap().pixels[..4], &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    #[cfg(feature = "images")]
+    fn test_software_renderer_animated_image_frames() {
+        use image::codecs::gif::{GifEncoder, Repeat};
+        use image::{Delay, Frame, Rgba, RgbaImage};
+
+        let colors = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]];
+        let mut gif = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut gif);
+            encoder.set_repeat(Repeat::Infinite).unwrap();
+            for (i, color) in colors.iter().enumerate() {
+                let delay = Delay::from_numer_denom_ms(100 * (i as u32 + 1), 1);
+                encoder.encode_frame(Frame::from_parts(RgbaImage::from_pixel(2, 2, Rgba(*color)), 0, 0, delay)).unwrap();
+            }
+        }
+        assert_eq!(crate::images::frame_count(&gif), 3);
+
+        let mut renderer = SoftwareRenderer::new(2, 2);
+        let id = renderer.load_image("anim", &gif).unwrap();
+        // Only the first frame is decoded up front
+        assert_eq!(renderer.images().frame_count(id), 3);
+        assert_eq!(renderer.images().used_bytes(), 16);
+
+        // Playback decodes the next frame when its time comes
+        assert!(!renderer.advance_images(0.05));
+        assert!(renderer.advance_images(0.06));
+        assert_eq!(renderer.images().current_frame(id), 1);
+        assert_eq!(&renderer.images().peek(id).unwrap().pixels[..4], &[0, 255, 0, 255]);
+        assert_eq!(renderer.images().used_bytes(), 32);
+
+        // The embedder can also pick frames itself
+        let (frame, delay) = renderer.image_frame(id, 2).unwrap();
+        assert_eq!((&frame.pixels[..4], delay), (&[0, 0, 255, 255][..], 300));
+        assert!(renderer.image_frame(id, 3).is_none());
+        assert!(renderer.set_image_frame(id, 0));
+        assert_eq!(&renderer.images().peek(id).unwrap().pixels[..4], &[255, 0, 0, 255]);
+        assert!(!renderer.set_image_frame(id, 5));
+    }
+
+    #[test]
+    fn test_software_renderer_layer_reuse() {
+        let mut renderer = SoftwareRenderer::new(100, 100);
+        renderer.set_clear_color(1.0, 1.0, 1.0, 1.0);
+        let layer = renderer.create_layer(20, 20);
+        renderer.layers_mut().get_mut(layer).unwrap().add_rect(RenderCommand {
+            width: 20.0,
+            height: 20.0,
+            color_g: 0.0,
+            color_b: 0.0,
+            ..Default::default()
+        });
+        renderer.render();
+
+        // Moving the layer only re-composites the retained pixmap
+        renderer.layers_mut().get_mut(layer).unwrap().x = 50.0;
+        renderer.render();
+        assert_eq!(renderer.layers().get(layer).unwrap().raster_count, 1);
+
+        let data = renderer.get_framebuffer();
+        let moved = ((10 * 100) + 60) * 4;
+        assert_eq!(&data[moved..moved + 4], &[255, 0, 0, 255]);
+        let old = ((10 * 100) + 10) * 4;
+        assert_eq!(&data[old..old + 4], &[255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_software_renderer_occlusion_culling() {
+        let mut renderer = SoftwareRenderer::new(100, 100);
+        let rect = |x: f32, y: f32, w: f32, h: f32, a: f32| RenderCommand {
+            x,
+            y,
+            width: w,
+            height: h,
+            color_r: 0.0,
+            color_g: 0.0,
+            color_a: a,
+            ..Default::default()
+        };
+        renderer.add_rect(rect(10.0, 10.0, 20.0, 20.0, 1.0)); // hidden by the modal
+        renderer.add_rect(rect(70.0, 70.0, 20.0, 20.0, 1.0)); // partially visible
+        renderer.add_rect(rect(0.0, 0.0, 80.0, 80.0, 1.0)); // opaque modal
+        renderer.add_rect(rect(5.0, 5.0, 10.0, 10.0, 0.5)); // translucent, drawn last
+        renderer.render();
+        assert_eq!(renderer.culled_count(), 1);
+    }
+
+    #[test]
+    fn test_software_renderer_stacking_contexts() {
+        let mut renderer = SoftwareRenderer::new(40, 40);
+        let rect = |x: f32, z_index: i32, color: [f32; 3]| RenderCommand {
+            x,
+            width: 20.0,
+            height: 20.0,
+            color_r: color[0],
+            color_g: color[1],
+            color_b: color[2],
+            z_index,
+            ..Default::default()
+        };
+        // A z-index 1 container whose child asks for z-index 100, next to a z-index 2 sibling
+        let container = renderer.push_stacking_context(1);
+        renderer.add_rect(rect(0.0, 0, [1.0, 0.0, 0.0]));
+        renderer.add_rect(rect(10.0, 100, [0.0, 1.0, 0.0]));
+        assert!(renderer.pop_stacking_context());
+        assert!(!renderer.pop_stacking_context());
+        renderer.add_rect(rect(15.0, 2, [0.0, 0.0, 1.0]));
+        // A later sibling context at the same z-index paints above the earlier one
+        renderer.push_stacking_context(1);
+        renderer.add_rect(rect(-15.0, -50, [1.0, 1.0, 0.0]));
+        renderer.pop_stacking_context();
+        assert_ne!(container, 0);
+        renderer.render();
+
+        let fb = renderer.get_framebuffer();
+        let pixel = |x: usize| &fb[(10 * 40 + x) * 4..(10 * 40 + x) * 4 + 3];
+        assert_eq!(pixel(2), &[255, 255, 0]); // later context over the first one's red
+        assert_eq!(pixel(7), &[255, 0, 0]);
+        assert_eq!(pixel(12), &[0, 255, 0]); // child above its own container's red
+        assert_eq!(pixel(17), &[0, 0, 255]); // the child's z-index 100 stays inside the container
+        assert_eq!(pixel(32), &[0, 0, 255]);
+
+        // The GPU frame bakes the same order into plain z-indices
+        #[cfg(feature = "gpu")]
+        {
+            let mut frame = renderer.gpu_frame().commands;
+            frame.sort_by_key(|c| c.z_index);
+            let order: Vec<f32> = frame.iter().map(|c| c.x).collect();
+            assert_eq!(order, [0.0, 10.0, -15.0, 15.0]);
+        }
+    }
+
+    #[test]
+    fn test_software_renderer_stateful_rect_between_stacking_contexts() {
+        let mut renderer = SoftwareRenderer::new(40, 40);
+        let rect = |x: f32, color: [f32; 3]| RenderCommand {
+            x,
+            width: 20.0,
+            height: 20.0,
+            color_r: color[0],
+            color_g: color[1],
+            color_b: color[2],
+            color_a: 1.0,
+            ..Default::default()
+        };
+        // Z-index 0 contexts holding red and green, around a multiplied blue sibling at z-index 0
+        renderer.push_stacking_context(0);
+        renderer.add_rect(rect(0.0, [1.0, 0.0, 0.0]));
+        renderer.pop_stacking_context();
+        renderer.set_blend_mode(BlendMode::Multiply);
+        renderer.add_rect(rect(10.0, [0.0, 0.0, 1.0]));
+        renderer.set_blend_mode(BlendMode::Normal);
+        renderer.push_stacking_context(0);
+        renderer.add_rect(rect(20.0, [0.0, 1.0, 0.0]));
+        renderer.pop_stacking_context();
+        renderer.render();
+
+        let fb = renderer.get_framebuffer();
+        let pixel = |x: usize| &fb[(10 * 40 + x) * 4..(10 * 40 + x) * 4 + 3];
+        assert_eq!(pixel(5), &[255, 0, 0]);
+        assert_eq!(pixel(15), &[0, 0, 0]); // blended over the earlier context's red
+        assert_eq!(pixel(25), &[0, 255, 0]); // the later context paints above it
+    }
+
+    #[test]
+    fn test_software_renderer_scrollbars() {
+        use crate::scroll::ScrollArea;
+
+        // One thumb for the vertical overflow, at the bottom of its track at the end
+        let mut area = ScrollArea::new(100.0, 100.0, 100.0, 400.0);
+        area.scroll_to(0.0, 300.0);
+        let mut renderer = SoftwareRenderer::new(120, 120);
+        assert_eq!(renderer.add_scrollbars(&area, 10.0, 10.0, [0.0, 0.0, 0.0, 1.0], 5), 1);
+        renderer.render();
+        let fb = renderer.get_framebuffer();
+        let pixel = |x: usize, y: usize| &fb[(y * 120 + x) * 4..(y * 120 + x) * 4 + 3];
+        assert_eq!(pixel(105, 96), &[0, 0, 0]);
+        assert_eq!(pixel(105, 80), &[255, 255, 255]);
+    }
+
+    #[test]
+    fn test_software_renderer_caret_and_focus_ring() {
+        use crate::caret::{Caret, FocusRing};
+        use std::sync::Mutex;
+
+        let mut renderer = SoftwareRenderer::new(60, 40);
+        let red = [1.0, 0.0, 0.0, 1.0];
+        let pixel = |r: &SoftwareRenderer, x: usize, y: usize| r.get_framebuffer()[(y * 60 + x) * 4..(y * 60 + x) * 4 + 3].to_vec();
+        renderer.set_auto_damage(true);
+        renderer.render();
+        renderer.set_caret(20.0, 10.0, 20.0, red, 0);
+        renderer.render();
+        assert_eq!(pixel(&renderer, 20, 15), vec![255, 0, 0]);
+        assert_eq!(pixel(&renderer, 21, 15), vec![255, 255, 255]);
+        assert!(renderer.dirty_rect().is_some_and(|(x, _, w, _)| x <= 20 && w <= 4));
+
+        // Setting it again unchanged keeps its phase and repaints nothing
+        let since = renderer.caret().unwrap().since;
+        renderer.set_caret(20.0, 10.0, 20.0, red, 0);
+        assert_eq!(renderer.caret().unwrap().since, since);
+        renderer.render();
+        assert_eq!(renderer.dirty_rect(), None);
+
+        renderer.clear_caret();
+        renderer.render();
+        assert_eq!(pixel(&renderer, 20, 15), vec![255, 255, 255]);
+
+        // An attached window receives the caret in device pixels and draws it itself
+        let window = Arc::new(Mutex::new(None));
+        renderer.set_device_pixel_ratio(2.0);
+        renderer.attach_caret_window(Some(window.clone()));
+        renderer.set_caret(5.0, 5.0, 10.0, red, 500);
+        assert_eq!(window.lock().unwrap().map(|c: Caret| c.rect()), Some([10.0, 10.0, 2.0, 20.0]));
+        assert!(renderer.next_caret_toggle(Instant::now()).is_none());
+        renderer.render();
+        assert_eq!(pixel(&renderer, 10, 15), vec![255, 255, 255]);
+        renderer.attach_caret_window(None);
+        assert!(window.lock().unwrap().is_none());
+        assert!(renderer.next_caret_toggle(Instant::now()).is_some());
+
+        // The ring's top edge alternates between dashes and gaps
+        let mut renderer = SoftwareRenderer::new(60, 40);
+        let ring = FocusRing {
+            gap: 4.0,
+            ..FocusRing::new(10.0, 10.0, 40.0, 20.0, [0.0, 0.0, 1.0, 1.0])
+        };
+        renderer.add_focus_ring(&ring);
+        renderer.render();
+        let edge: Vec<bool> = (14..46).map(|x| pixel(&renderer, x, 9)[0] < 128).collect();
+        assert!(edge.iter().any(|&on| on) && edge.iter().any(|&on| !on));
+        assert_eq!(pixel(&renderer, 30, 20), vec![255, 255, 255]);
+    }
+
+    #[test]
+    fn test_software_renderer_viewport_culling() {
+        let mut renderer = SoftwareRenderer::new(100, 100);
+        let rect = |x: f32, y: f32, w: f32, h: f32, g: f32| RenderCommand {
+            x,
+            y,
+            width: w,
+            height: h,
+            color_r: 0.0,
+            color_g: g,
+            ..Default::default()
+        };
+        let frame = |renderer: &mut SoftwareRenderer| {
+            renderer.clear();
+            renderer.add_rect(rect(0.0, 0.0, 100.0, 100.0, 0.0)); // page background
+            renderer.add_rect(rect(120.0, 10.0, 30.0, 30.0, 0.0)); // off screen
+            renderer.add_rect(rect(20.0, 20.0, 40.0, 40.0, 1.0)); // card over the damaged area
+        };
+        frame(&mut renderer);
+        renderer.render();
+        assert_eq!(renderer.culled_count(), 0);
+
+        frame(&mut renderer);
+        renderer.add_damage_rect(25.0, 25.0, 10.0, 10.0);
+        renderer.render();
+
+        // The background is only hidden inside the damaged area, which is enough to skip it
+        assert_eq!(renderer.culled_count(), 1);
+        let stats = renderer.raster_stats();
+        assert_eq!(stats.clip_rejections, 1);
+        assert_eq!(stats.rects_filled, 1);
+        let data = renderer.get_framebuffer();
+        let idx = ((30 * 100) + 30) * 4;
+        assert_eq!(&data[idx..idx + 4], &[0, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_software_renderer_command_merging() {
+        let mut renderer = SoftwareRenderer::new(100, 100);
+        let rect = |x: f32, w: f32, a: f32| RenderCommand {
+            x,
+            y: 10.0,
+            width: w,
+            height: 10.0,
+            color_g: 0.0,
+            color_b: 0.0,
+            color_a: a,
+            ..Default::default()
+        };
+        renderer.add_rect(rect(0.0, 10.0, 1.0));
+        renderer.add_rect(rect(10.0, 10.0, 1.0)); // merged with the first
+        renderer.add_rect(rect(10.0, 10.0, 1.0)); // overlaps the merged rect, kept
+        renderer.add_rect(rect(10.0, 10.0, 1.0)); // exact repeat
+        renderer.add_rect(rect(50.0, 0.0, 1.0)); // zero area
+        renderer.add_rect(rect(60.0, 10.0, 0.0)); // fully transparent
+        renderer.add_rect(rect(70.0, 5.5, 1.0));
+        renderer.add_rect(rect(75.5, 5.0, 1.0)); // shares an anti-aliased edge, kept
+        renderer.render();
+
+        let stats = renderer.optimize_stats();
+        assert_eq!(stats.dropped, 2);
+        assert_eq!(stats.deduplicated, 1);
+        assert_eq!(stats.merged, 1);
+
+        let data = renderer.get_framebuffer();
+        let idx = ((15 * 100) + 15) * 4;
+        assert_eq!(&data[idx..idx + 4], &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_software_renderer_damage_rect() {
+        let mut renderer = SoftwareRenderer::new(100, 100);
+        let background = |r: f32, b: f32| RenderCommand {
+            width: 100.0,
+            height: 100.0,
+            color_r: r,
+            color_g: 0.0,
+            color_b: b,
+            ..Default::default()
+        };
+        renderer.add_rect(background(1.0, 0.0));
+        renderer.render();
+
+        // Only the damaged area picks up the new color
+        renderer.clear();
+        renderer.add_rect(background(0.0, 1.0));
+        renderer.add_damage_rect(0.0, 0.0, 10.0, 10.0);
+        renderer.render();
+
+        let data = renderer.get_framebuffer();
+        let inside = ((5 * 100) + 5) * 4;
+        assert_eq!(&data[inside..inside + 4], &[0, 0, 255, 255]);
+        let outside = ((50 * 100) + 50) * 4;
+        assert_eq!(&data[outside..outside + 4], &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_software_renderer_auto_damage() {
+        let mut renderer = SoftwareRenderer::new(100, 100);
+        renderer.set_auto_damage(true);
+        let button = |g: f32| RenderCommand {
+            x: 10.0,
+            y: 10.0,
+            width: 20.0,
+            height: 20.0,
+            color_r: 0.0,
+            color_g: g,
+            color_b: 0.0,
+            z_index: 1,
+            ..Default::default()
+        };
+        let frame = |renderer: &mut SoftwareRenderer, g: f32| {
+            renderer.clear();
+            renderer.add_rect(RenderCommand { x: 60.0, y: 60.0, ..button(0.0) });
+            renderer.add_rect(button(g));
+            renderer.render();
+        };
+
+        // The first frame is a full repaint
+        frame(&mut renderer, 0.0);
+        assert_eq!(renderer.dirty_rect(), Some((0, 0, 100, 100)));
+
+        // An identical frame is skipped
+        frame(&mut renderer, 0.0);
+        assert_eq!(renderer.dirty_rect(), None);
+        assert_eq!(renderer.raster_stats().rects_filled, 0);
+
+        // Recoloring the button repaints only the button
+        frame(&mut renderer, 1.0);
+        assert_eq!(renderer.dirty_rect(), Some((10, 10, 20, 20)));
+        assert_eq!(renderer.raster_stats().rects_filled, 1);
+        let data = renderer.get_framebuffer();
+        let idx = (20 * 100 + 20) * 4;
+        assert_eq!(&data[idx..idx + 4], &[0, 255, 0, 255]);
+        let other = (70 * 100 + 70) * 4;
+        assert_eq!(&data[other..other + 4], &[0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_software_renderer_retained_commands() {
+        let mut renderer = SoftwareRenderer::new(40, 40);
+        let id = renderer.create_retained_rect(RenderCommand {
+            width: 10.0,
+            height: 10.0,
+            color_g: 0.0,
+            color_b: 0.0,
+            ..Default::default()
+        });
+        let pixel = |renderer: &SoftwareRenderer, x: usize, y: usize| {
+            let idx = (y * 40 + x) * 4;
+            renderer.get_framebuffer()[idx..idx + 4].to_vec()
+        };
+
+        // Retained rects survive clear() and can be moved without re-adding them
+        for _ in 0..2 {
+            renderer.clear();
+            renderer.render();
+            assert_eq!(pixel(&renderer, 5, 5), [255, 0, 0, 255]);
+        }
+        renderer.retained_mut().get_mut(id).unwrap().x = 20.0;
+        renderer.render();
+        assert_eq!(pixel(&renderer, 5, 5), [255, 255, 255, 255]);
+        assert_eq!(pixel(&renderer, 25, 5), [255, 0, 0, 255]);
+
+        assert!(renderer.remove_retained(id));
+        assert!(!renderer.remove_retained(id));
+        renderer.render();
+        assert_eq!(pixel(&renderer, 25, 5), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_software_renderer_display_p3_output() {
+        let mut renderer = SoftwareRenderer::new(10, 10);
+        renderer
+            .color_manager_mut()
+            .set_output_space(ColorSpace::DisplayP3);
+        renderer.add_rect(RenderCommand {
+            width: 10.0,
+            height: 10.0,
+            color_g: 0.0,
+            color_b: 0.0,
+            ..Default::default()
+        });
+        renderer.render();
+
+        // sRGB red is inside the P3 gamut, so it becomes a less saturated P3 red
+        let data = renderer.get_framebuffer();
+        let expected = [234u8, 51, 35, 255];
+        for (got, want) in data[..4].iter().zip(expected) {
+            assert!(got.abs_diff(want) <= 1, "{:?} != {:?}", &data[..4], expected);
+        }
+    }
+
+    #[test]
+    fn test_software_renderer_linear_blending() {
+        let half_white_over_black = |linear: bool| {
+            let mut renderer = SoftwareRenderer::new(10, 10);
+            renderer.set_clear_color(0.0, 0.0, 0.0, 1.0);
+            renderer.set_linear_blending(linear);
+            renderer.add_rect(RenderCommand {
+                width: 10.0,
+                height: 10.0,
+                color_a: 0.5,
+                ..Default::default()
+            });
+            renderer.render();
+            renderer.get_framebuffer()[0]
+        };
+
+        // 50% coverage is mid-grey in bytes but ~188 when mixed in linear light
+        assert!(half_white_over_black(false).abs_diff(128) <= 1);
+        assert!(half_white_over_black(true).abs_diff(188) <= 1);
+    }
+
+    #[test]
+    fn test_software_renderer_raster_stats() {
+        let mut renderer = SoftwareRenderer::new(100, 100);
+        let rect = |x: f32| RenderCommand {
+            x,
+            width: 10.0,
+            height: 10.0,
+            color_g: 0.0,
+            color_b: 0.0,
+            color_a: 0.5,
+            ..Default::default()
+        };
+        renderer.add_rect(rect(0.0));
+        renderer.add_rect(rect(95.0));
+        renderer.add_rect(rect(200.0));
+        renderer.render();
+
+        let stats = renderer.raster_stats();
+        assert_eq!(stats.rects_filled, 2);
+        assert_eq!(stats.clip_rejections, 1);
+        assert_eq!(stats.pixels_touched, 150);
+    }
+    #[cfg(feature = "images")]
+    #[test]
+    fn test_software_renderer_rgba_image_filters() {
+        let mut renderer = SoftwareRenderer::new(8, 1);
+        // Black and white texels, each stretched over four pixels
+        let pixels = [0, 0, 0, 255, 255, 255, 255, 255];
+        assert!(renderer.load_image_rgba(&pixels, 4, 1).is_none());
+        let id = renderer.load_image_rgba(&pixels, 2, 1).unwrap();
+        let draw = ImageCommand {
+            image_id: id,
+            x: 0.0,
+            y: 0.0,
+            width: 8.0,
+            height: 1.0,
+            z_index: 0,
+        };
+
+        renderer.add_image(draw);
+        renderer.render();
+        let smooth = renderer.get_framebuffer()[3 * 4];
+        assert!(smooth > 0 && smooth < 255, "bilinear edge {}", smooth);
+
+        renderer.clear();
+        renderer.set_image_filter(ImageFilter::Nearest);
+        renderer.add_image(draw);
+        renderer.render();
+        assert_eq!(renderer.get_framebuffer()[3 * 4], 0);
+        assert_eq!(renderer.get_framebuffer()[4 * 4], 255);
+    }
+
+
+    #[test]
+    fn test_software_renderer_registered_bitmap() {
+        let mut renderer = SoftwareRenderer::new(8, 8);
+        let blue = [0u8, 0, 255, 255].repeat(4);
+        assert!(renderer.register_bitmap(7, &blue, 2, 2));
+        assert!(!renderer.register_bitmap(8, &blue, 4, 4));
+
+        for frame in 0..2 {
+            renderer.clear();
+            renderer.add_bitmap(BitmapCommand {
+                bitmap_id: 7,
+                x: 0.0,
+                y: 0.0,
+                width: 4.0,
+                height: 4.0,
+                z_index: 0,
+            });
+            renderer.render();
+            assert_eq!(&renderer.get_framebuffer()[..4], &[0, 0, 255, 255], "frame {}", frame);
+            assert_eq!(renderer.raster_stats().images_drawn, 1);
+        }
+
+        assert!(renderer.unregister_bitmap(7));
+        assert!(renderer.bitmap(7).is_none());
+    }
+
+    #[test]
+    fn test_software_renderer_save_restore() {
+        let mut renderer = SoftwareRenderer::new(10, 10);
+        renderer.set_clear_color(1.0, 1.0, 1.0, 1.0);
+        let black = |x: f32, y: f32| RenderCommand {
+            x,
+            y,
+            width: 10.0,
+            height: 10.0,
+            color_r: 0.0,
+            color_g: 0.0,
+            color_b: 0.0,
+            ..Default::default()
+        };
+
+        renderer.save();
+        renderer.translate(2.0, 0.0);
+        renderer.clip_rect(0.0, 0.0, 3.0, 3.0);
+        renderer.save();
+        renderer.set_opacity(0.5);
+        renderer.add_rect(black(0.0, 0.0));
+        assert!(renderer.restore());
+        renderer.add_rect(black(0.0, 5.0));
+        assert!(renderer.restore());
+        assert!(!renderer.restore());
+        assert!(renderer.graphics_state().is_identity());
+        renderer.render();
+
+        let fb = renderer.get_framebuffer();
+        let pixel = |x: usize, y: usize| fb[(y * 10 + x) * 4];
+        // Translated and clipped to x 2..5, y 0..3; half opacity inside the nested save
+        assert_eq!(pixel(0, 0), 255);
+        assert!((126..=129).contains(&pixel(3, 1)), "got {}", pixel(3, 1));
+        assert_eq!(pixel(6, 1), 255);
+        // The second rect starts below the clip and is dropped entirely
+        assert_eq!(pixel(3, 6), 255);
+        assert_eq!(renderer.raster_stats().rects_filled, 1);
+    }
+
+    #[test]
+    fn test_software_renderer_device_pixel_ratio() {
+        let mut renderer = SoftwareRenderer::new(10, 10);
+        renderer.set_device_pixel_ratio(2.0);
+        renderer.clear();
+        assert_eq!(renderer.graphics_state().transform.sx, 2.0);
+        renderer.add_rect(RenderCommand {
+            x: 1.0,
+            y: 1.0,
+            width: 2.0,
+            height: 2.0,
+            color_r: 0.0,
+            color_g: 0.0,
+            color_b: 0.0,
+            color_a: 1.0,
+            ..Default::default()
+        });
+        renderer.render();
+
+        let fb = renderer.get_framebuffer();
+        let pixel = |x: usize, y: usize| fb[(y * 10 + x) * 4];
+        // Logical 1..3 covers physical 2..6
+        assert_eq!((pixel(1, 1), pixel(2, 2), pixel(5, 5), pixel(6, 6)), (255, 0, 0, 255));
+
+        renderer.set_device_pixel_ratio(0.0);
+        assert_eq!(renderer.device_pixel_ratio(), 2.0);
+    }
+
+    #[test]
+    fn test_software_renderer_rounded_rect() {
+        let mut renderer = SoftwareRenderer::new(20, 20);
+        renderer.set_clear_color(1.0, 0.0, 0.0, 1.0);
+        let rect = |radius: f32| RenderCommand {
+            width: 20.0,
+            height: 20.0,
+            color_r: 0.0,
+            color_g: 0.0,
+            color_b: 0.0,
+            corner_radii: [radius, 0.0, 0.0, 0.0],
+            ..Default::default()
+        };
+        // The rounded rect must not be culled as an occluder of the one below
+        renderer.add_rect(rect(0.0));
+        renderer.add_rect(RenderCommand { color_g: 1.0, ..rect(10.0) });
+        renderer.render();
+        assert_eq!(renderer.culled_count(), 0);
+
+        let fb = renderer.get_framebuffer();
+        let pixel = |x: usize, y: usize| &fb[(y * 20 + x) * 4..(y * 20 + x) * 4 + 3];
+        // Outside the top-left arc the square rect shows through
+        assert_eq!(pixel(0, 0), &[0, 0, 0]);
+        assert_eq!(pixel(10, 10), &[0, 255, 0]);
+        assert_eq!(pixel(19, 0), &[0, 255, 0]);
+    }
+
+    #[test]
+    fn test_software_renderer_nested_clip_stack() {
+        let mut renderer = SoftwareRenderer::new(10, 10);
+        renderer.set_clear_color(1.0, 1.0, 1.0, 1.0);
+
+        // A scroll container at x 0..6 holding an overflow box at x 4..10
+        renderer.push_clip_rect(0.0, 0.0, 6.0, 10.0);
+        renderer.push_clip_rect(4.0, 0.0, 6.0, 10.0);
+        renderer.add_rect(RenderCommand {
+            width: 10.0,
+            height: 10.0,
+            color_r: 0.0,
+            color_g: 0.0,
+            color_b: 0.0,
+            ..Default::default()
+        });
+        assert!(renderer.pop_clip_rect());
+        assert!(renderer.pop_clip_rect());
+        assert!(!renderer.pop_clip_rect());
+        renderer.render();
+
+        let fb = renderer.get_framebuffer();
+        let pixel = |x: usize| fb[(5 * 10 + x) * 4];
+        assert_eq!((pixel(3), pixel(4), pixel(5), pixel(6)), (255, 0, 0, 255));
+    }
+
+    #[test]
+    fn test_software_renderer_path_fill_and_stroke() {
+        use crate::path::{FillRule, Path};
+
+        let mut renderer = SoftwareRenderer::new(20, 20);
+        renderer.set_clear_color(1.0, 1.0, 1.0, 1.0);
+
+        // Triangle covering the top-left half
+        let mut triangle = Path::new();
+        triangle.move_to(0.0, 0.0);
+        triangle.line_to(20.0, 0.0);
+        triangle.line_to(0.0, 20.0);
+        triangle.close();
+        renderer.fill_path(&triangle, FillRule::NonZero, [0.0, 0.0, 0.0, 1.0]);
+
+        let mut line = Path::new();
+        line.move_to(0.0, 15.0);
+        line.line_to(20.0, 15.0);
+        renderer.stroke_path(&line, StrokeStyle::new(2.0), [0.0, 0.0, 1.0, 1.0]);
+        renderer.render();
+
+        let fb = renderer.get_framebuffer();
+        let pixel = |x: usize, y: usize| &fb[(y * 20 + x) * 4..(y * 20 + x) * 4 + 3];
+        assert_eq!(pixel(3, 3), &[0, 0, 0]);
+        assert_eq!(pixel(16, 8), &[255, 255, 255]);
+        assert_eq!(pixel(10, 15), &[0, 0, 255]);
+        assert_eq!(pixel(10, 18), &[255, 255, 255]);
+    }
+
+    #[test]
+    fn test_software_renderer_line_caps_and_joins() {
+        use crate::path::{LineCap, LineJoin, StrokeStyle};
+
+        let mut renderer = SoftwareRenderer::new(20, 20);
+        renderer.set_clear_color(1.0, 1.0, 1.0, 1.0);
+        // Butt caps stop at the end points, square caps reach half the width past them
+        renderer.add_line((4.0, 3.0), (16.0, 3.0), 4.0, [0.0, 0.0, 0.0, 1.0], LineCap::Butt);
+        renderer.add_line((4.0, 10.0), (16.0, 10.0), 4.0, [0.0, 0.0, 0.0, 1.0], LineCap::Square);
+        // A right-angle corner: miter fills the outer corner, round cuts it off
+        let style = StrokeStyle {
+            join: LineJoin::Round,
+            ..StrokeStyle::new(4.0)
+        };
+        renderer.add_polyline(&[(4.0, 16.0), (16.0, 16.0), (16.0, 30.0)], style, [0.0, 0.0, 0.0, 1.0]);
+        renderer.render();
+
+        let fb = renderer.get_framebuffer();
+        let pixel = |x: usize, y: usize| fb[(y * 20 + x) * 4];
+        assert_eq!((pixel(10, 3), pixel(2, 3), pixel(17, 3)), (0, 255, 255));
+        assert_eq!((pixel(10, 10), pixel(2, 10), pixel(17, 10)), (0, 0, 0));
+        assert_eq!((pixel(10, 16), pixel(16, 18)), (0, 0));
+        assert!(pixel(17, 14) > 128);
+    }
+
+    #[test]
+    fn test_software_renderer_transform_stack() {
+        let mut renderer = SoftwareRenderer::new(20, 20);
+        renderer.set_clear_color(1.0, 1.0, 1.0, 1.0);
+        let square = RenderCommand {
+            x: -5.0,
+            y: -5.0,
+            width: 10.0,
+            height: 10.0,
+            color_r: 0.0,
+            color_g: 0.0,
+            color_b: 0.0,
+            ..Default::default()
+        };
+
+        // A diamond: the square rotated 45 degrees around the center
+        renderer.push_transform(1.0, 0.0, 0.0, 1.0, 10.0, 10.0);
+        renderer.rotate(45.0);
+        renderer.add_rect(square);
+        assert!(renderer.pop_transform());
+        assert!(!renderer.pop_transform());
+        assert!(renderer.graphics_state().is_identity());
+        // Back to axis-aligned: a square in the top-left corner
+        renderer.add_rect(RenderCommand { x: 0.0, y: 0.0, width: 2.0, height: 2.0, ..square });
+        renderer.render();
+
+        let fb = renderer.get_framebuffer();
+        let pixel = |x: usize, y: usize| fb[(y * 20 + x) * 4];
+        assert_eq!((pixel(10, 10), pixel(10, 4), pixel(15, 10)), (0, 0, 0));
+        // Corners of the unrotated square lie outside the diamond
+        assert_eq!((pixel(5, 5), pixel(14, 14)), (255, 255));
+        assert_eq!((pixel(1, 1), pixel(3, 3)), (0, 255));
+    }
+
+    #[test]
+    fn test_software_renderer_export_to_buffer() {
+        let mut renderer = SoftwareRenderer::new(2, 1);
+        renderer.set_clear_color(1.0, 0.0, 0.0, 1.0);
+        renderer.render();
+
+        let png = renderer.export_to_buffer(ExportFormat::Png).unwrap();
+        let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        assert_eq!(pixels, [255, 0, 0, 255, 255, 0, 0, 255]);
+
+        // Top-down 32-bit BGRA after a 14 + 108 byte header
+        let bmp = renderer.export_to_buffer(ExportFormat::Bmp).unwrap();
+        assert_eq!(&bmp[..2], b"BM");
+        assert_eq!(bmp.len(), 122 + 8);
+        assert_eq!(&bmp[122..126], &[0, 0, 255, 255]);
+
+        // Red differs from the implicit black start pixel by -1 (wrapping) in red,
+        // then repeats once
+        let qoi = renderer.export_to_buffer(ExportFormat::Qoi).unwrap();
+        assert_eq!(&qoi[..4], b"qoif");
+        assert_eq!(&qoi[14..], &[0x5A, 0xC0, 0, 0, 0, 0, 0, 0, 0, 1]);
+
+        let jpeg = renderer.export_to_buffer(ExportFormat::Jpeg);
+        #[cfg(feature = "images")]
+        assert_eq!(&jpeg.unwrap()[..2], &[0xFF, 0xD8]);
+        #[cfg(not(feature = "images"))]
+        assert!(jpeg.is_err());
+    }
+
+    #[test]
+    fn test_software_renderer_golden_scenes() {
+        use crate::testing::{assert_golden, compare, Scene, Tolerance};
+
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+        let mut scenes: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "scene"))
+            .collect();
+        scenes.sort();
+        assert!(!scenes.is_empty());
+        for path in scenes {
+            let scene = Scene::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            assert_golden(&scene, &path.with_extension("png"), Tolerance::default()).unwrap();
+        }
+
+        assert!(Scene::parse("rect 0 0 1 1 0 0 0 1").is_err());
+        assert!(Scene::parse("size 4 4\ncircle 1 1 1").is_err());
+        // Slight rounding noise is within tolerance, a color change is not
+        let white = [255u8; 4];
+        assert_eq!(compare(&[254, 255, 255, 255], &white, 0.1).mismatched, 0);
+        assert_eq!(compare(&[255, 0, 0, 255], &white, 0.1).mismatched, 1);
+    }
+
+    #[test]
+    fn test_software_renderer_svg() {
+        use crate::svg::SvgDocument;
+
+        // A 10x10 viewBox drawn at 2x: red square, blue circle, green arc path
+        let svg = SvgDocument::parse(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+                <rect x="0" y="0" width="5" height="5" fill="#f00"/>
+                <g style="fill: rgb(0, 0, 255)" transform="translate(5 0)">
+                    <circle cx="2.5" cy="2.5" r="2"/>
+                </g>
+                <path d="M0 10 V5 a5 5 0 0 1 5 5 z" fill="lime" opacity="0.5"/>
+                <line x1="5" y1="8" x2="10" y2="8" stroke="black" stroke-width="1"/>
+                <defs><rect width="10" height="10"/></defs>
+            </svg>"##,
+        )
+        .unwrap();
+
+        let mut renderer = SoftwareRenderer::new(20, 20);
+        renderer.set_clear_color(1.0, 1.0, 1.0, 1.0);
+        renderer.add_svg(&svg, 0.0, 0.0, 20.0, 20.0);
+        renderer.render();
+
+        let fb = renderer.get_framebuffer();
+        let pixel = |x: usize, y: usize| &fb[(y * 20 + x) * 4..(y * 20 + x) * 4 + 3];
+        assert_eq!(pixel(4, 4), &[255, 0, 0]);
+        assert_eq!(pixel(15, 5), &[0, 0, 255]);
+        // Circle corners stay clear
+        assert_eq!(pixel(11, 1), &[255, 255, 255]);
+        // Quarter disc at half opacity, filled near the corner only
+        assert_eq!(pixel(1, 18), &[127, 255, 127]);
+        assert_eq!(pixel(8, 11), &[255, 255, 255]);
+        assert_eq!(pixel(15, 16), &[0, 0, 0]);
+    }
+
+    #[test]
+    fn test_software_renderer_text_anchor() {
+        let ink_rows = |anchor: TextAnchor| {
+            let mut renderer = SoftwareRenderer::new(40, 60);
+            renderer.set_clear_color(1.0, 1.0, 1.0, 1.0);
+            renderer.add_text(TextCommand {
+                text: "H".to_string(),
+                x: 4.0,
+                y: 30.0,
+                font_size: 20.0,
+                color_r: 0.0,
+                color_g: 0.0,
+                color_b: 0.0,
+                color_a: 1.0,
+                font_id: 0,
+                decoration: TextDecoration::NONE,
+                decoration_color: None,
+                anchor,
+                spacing: TextSpacing::default(),
+            });
+            renderer.render();
+            let fb = renderer.get_framebuffer();
+            let rows: Vec<usize> = (0..60).filter(|&y| (0..40).any(|x| fb[(y * 40 + x) * 4] < 128)).collect();
+            (rows[0], rows[rows.len() - 1])
+        };
+        let fonts = FontManager::new();
+        let Some(metrics) = fonts.font_metrics(0, 20.0) else {
+            return;
+        };
+        assert!(metrics.ascent > 10.0 && metrics.descent > 0.0 && metrics.ascent < 25.0);
+        assert_eq!(fonts.font_metrics(999, 20.0), None);
+
+        // "H" sits on the baseline, so its ink ends right above y
+        assert_eq!(ink_rows(TextAnchor::Top).0, 30);
+        assert_eq!(ink_rows(TextAnchor::Baseline).1, 29);
+        assert!(ink_rows(TextAnchor::Bottom).1 < 30);
+    }
+
+    #[test]
+    fn test_software_renderer_text_spacing() {
+        let fonts = FontManager::new();
+        if fonts.get_font(0).is_none() {
+            return;
+        }
+        let spacing = TextSpacing {
+            letter: 2.0,
+            word: 3.0,
+            line_height: Some(40.0),
+        };
+
+        // Every character gets the letter spacing, spaces also the word spacing
+        let (plain, _) = fonts.measure_text("ab cd", 16.0, 0, TextSpacing::default());
+        let (spaced, height) = fonts.measure_text("ab cd", 16.0, 0, spacing);
+        assert!((spaced - plain - 13.0).abs() < 0.01);
+        assert_eq!(height, 40.0);
+
+        let plain = fonts.layout_run("ab\ncd", 16.0, 0, TextSpacing::default());
+        let run = fonts.layout_run("ab\ncd", 16.0, 0, spacing);
+        assert!((run.glyphs[1].x - plain.glyphs[1].x - 2.0).abs() < 0.01);
+        assert_eq!(run.lines[1].baseline - run.lines[0].baseline, 40.0);
+        assert_eq!(run.height, 80);
+
+        // Paragraphs shaped by a TextShaper follow its spacing
+        let mut shaper = crate::text::TextShaper::new();
+        let plain = shaper.shape_paragraph("ab cd", 1000.0, 16.0);
+        shaper.set_spacing(spacing);
+        let shaped = shaper.shape_paragraph("ab cd", 1000.0, 16.0);
+        assert!((shaped.width - plain.width - 13.0).abs() < 0.01);
+        assert_eq!(shaped.height, 40.0);
+        assert_eq!(shaped.clusters[1].x - plain.clusters[1].x, 2.0);
+
+        // Drawn text is as wide as measured
+        let ink_right = |spacing: TextSpacing| {
+            let mut renderer = SoftwareRenderer::new(120, 40);
+            renderer.set_clear_color(1.0, 1.0, 1.0, 1.0);
+            renderer.add_text(TextCommand {
+                text: "iiii".to_string(),
+                x: 2.0,
+                y: 2.0,
+                font_size: 16.0,
+                color_r: 0.0,
+                color_g: 0.0,
+                color_b: 0.0,
+                color_a: 1.0,
+                font_id: 0,
+                decoration: TextDecoration::NONE,
+                decoration_color: None,
+                anchor: TextAnchor::Top,
+                spacing,
+            });
+            renderer.render();
+            let fb = renderer.get_framebuffer();
+            (0..120).rev().find(|&x| (0..40).any(|y| fb[(y * 120 + x) * 4] < 128)).unwrap()
+        };
+        let wide = TextSpacing {
+            letter: 10.0,
+            ..TextSpacing::default()
+        };
+        assert_eq!(ink_right(wide), ink_right(TextSpacing::default()) + 30);
+    }
+
+    #[test]
+    fn test_software_renderer_selection() {
+        let mut shaper = crate::text::TextShaper::new();
+        if shaper.font_manager().get_font(0).is_none() {
+            return;
+        }
+        let shaped = shaper.shape_paragraph("hello wide world", 60.0, 16.0);
+
+        // Highlights paint beneath the glyphs drawn over them
+        let mut renderer = SoftwareRenderer::new(100, 40);
+        renderer.set_clear_color(1.0, 1.0, 1.0, 1.0);
+        assert_eq!(renderer.add_selection(&shaped, 0.0, 0.0, 0..5, [0.0, 0.0, 1.0, 1.0], 0), 1);
+        renderer.add_text(TextCommand {
+            text: "hello".to_string(),
+            x: 0.0,
+            y: 0.0,
+            font_size: 16.0,
+            color_r: 1.0,
+            color_g: 1.0,
+            color_b: 0.0,
+            color_a: 1.0,
+            font_id: 0,
+            decoration: TextDecoration::NONE,
+            decoration_color: None,
+            anchor: TextAnchor::Top,
+            spacing: TextSpacing::default(),
+        });
+        renderer.render();
+        let fb = renderer.get_framebuffer();
+        let [_, _, width, height] = shaped.selection_rects(0..5)[0];
+        let pixels: Vec<&[u8]> = (0..height as usize)
+            .flat_map(|y| (0..width as usize).map(move |x| &fb[(y * 100 + x) * 4..(y * 100 + x) * 4 + 3]))
+            .collect();
+        let count = |color: [u8; 3]| pixels.iter().filter(|&&p| p == color).count();
+        assert!(count([0, 0, 255]) > pixels.len() / 2);
+        assert!(count([255, 255, 0]) > 0);
+        assert_eq!(count([255, 255, 255]), 0);
+    }
+
+    #[test]
+    fn test_software_renderer_paragraph_runs() {
+        let span = |text: &str, font_size: f32, color: [f32; 4]| TextSpan {
+            text: text.to_string(),
+            font_id: 0,
+            font_size,
+            color,
+            decoration: TextDecoration::NONE,
+        };
+        let black = [0.0, 0.0, 0.0, 1.0];
+        let red = [1.0, 0.0, 0.0, 1.0];
+        let mut renderer = SoftwareRenderer::new(200, 80);
+        if renderer.font_manager().get_font(0).is_none() {
+            return;
+        }
+
+        // Splitting text into runs doesn't change its shaping
+        let fonts = renderer.font_manager();
+        let whole = fonts.layout_paragraph(&[span("AVA", 20.0, black)], None);
+        let split = fonts.layout_paragraph(&[span("A", 20.0, black), span("VA", 20.0, red)], None);
+        assert_eq!(split.width, whole.width);
+        assert_eq!(split.fragments.len(), 2);
+        assert_eq!(split.fragments[0].baseline, split.fragments[1].baseline);
+
+        // Wrapping splits a run into one fragment per line
+        let spans = [span("Hello ", 16.0, black), span("big world", 24.0, red)];
+        let wrapped = fonts.layout_paragraph(&spans, Some(90.0));
+        assert!(wrapped.line_count >= 2);
+        assert_eq!(wrapped.fragments.len(), wrapped.line_count as usize);
+        assert!(wrapped.fragments.windows(2).all(|f| f[1].baseline > f[0].baseline));
+        let red: String = wrapped
+            .fragments
+            .iter()
+            .filter(|f| f.span == 1)
+            .map(|f| &spans[1].text[f.range.clone()])
+            .collect();
+        assert_eq!(red, "big world");
+
+        // Runs are drawn in their own colors, left to right
+        renderer.set_clear_color(1.0, 1.0, 1.0, 1.0);
+        let layout = renderer.add_paragraph(&spans, 4.0, 4.0, None);
+        assert_eq!(layout.line_count, 1);
+        renderer.render();
+        let fb = renderer.get_framebuffer();
+        let columns = |is_ink: &dyn Fn(&[u8]) -> bool| -> Vec<usize> {
+            (0..200)
+                .filter(|&x| (0..80).any(|y| is_ink(&fb[(y * 200 + x) * 4..][..4])))
+                .collect()
+        };
+        let black_x = columns(&|p| p[0] < 64 && p[1] < 64);
+        let red_x = columns(&|p| p[0] > 192 && p[1] < 64);
+        assert!(!black_x.is_empty() && !red_x.is_empty());
+        assert!(black_x.iter().max() < red_x.iter().min());
+    }
+
+    #[test]
+    fn test_software_renderer_subpixel_text() {
+        let render = |mode: TextAntialias| {
+            let mut renderer = SoftwareRenderer::new(60, 30);
+            renderer.set_clear_color(1.0, 1.0, 1.0, 1.0);
+            renderer.set_text_antialias(mode);
+            renderer.add_text(TextCommand {
+                text: "Wo".to_string(),
+                x: 2.0,
+                y: 2.0,
+                font_size: 18.0,
+                color_r: 0.0,
+                color_g: 0.0,
+                color_b: 0.0,
+                color_a: 1.0,
+                font_id: 0,
+                decoration: TextDecoration::NONE,
+                decoration_color: None,
+                anchor: TextAnchor::Top,
+                spacing: TextSpacing::default(),
+            });
+            renderer.render();
+            renderer.get_framebuffer().to_vec()
+        };
+        if FontManager::new().get_font(0).is_none() {
+            return;
+        }
+
+        // Grayscale edges stay neutral, subpixel edges get colored fringes
+        let gray = render(TextAntialias::Grayscale);
+        assert!(gray.chunks(4).all(|p| p[0] == p[1] && p[1] == p[2]));
+        let rgb = render(TextAntialias::SubpixelRgb);
+        assert!(rgb.chunks(4).any(|p| p[0] != p[2]));
+        assert!(rgb.chunks(4).any(|p| p[0] < 128));
+
+        // BGR panels get the same coverage with red and blue swapped
+        let bgr = render(TextAntialias::SubpixelBgr);
+        assert!(rgb.chunks(4).zip(bgr.chunks(4)).all(|(a, b)| a[0] == b[2] && a[2] == b[0]));
+    }
+
+    #[test]
+    fn test_software_renderer_text_decoration() {
+        let mut renderer = SoftwareRenderer::new(80, 40);
+        if renderer.font_manager().get_font(0).is_none() {
+            return;
+        }
+        renderer.set_clear_color(1.0, 1.0, 1.0, 1.0);
+        let text = |y: f32, decoration: TextDecoration| TextCommand {
+            text: "ab".to_string(),
+            x: 4.0,
+            y,
+            font_size: 16.0,
+            color_r: 0.0,
+            color_g: 0.0,
+            color_b: 0.0,
+            color_a: 1.0,
+            font_id: 0,
+            decoration,
+            decoration_color: Some([0.0, 0.0, 1.0, 1.0]),
+            anchor: TextAnchor::Top,
+            spacing: TextSpacing::default(),
+        };
+        renderer.add_text(text(0.0, TextDecoration::NONE));
+        renderer.add_text(text(20.0, TextDecoration::UNDERLINE | TextDecoration::LINE_THROUGH));
+        renderer.render();
+
+        // Only the decorated run has blue pixels: one line below the baseline, one through the glyphs
+        let fb = renderer.get_framebuffer();
+        let blue_rows: Vec<usize> = (0..40)
+            .filter(|&y| (0..80).any(|x| fb[(y * 80 + x) * 4..(y * 80 + x) * 4 + 3] == [0, 0, 255]))
+            .collect();
+        assert!(!blue_rows.is_empty());
+        assert!(blue_rows.iter().all(|&y| y >= 20));
+        let run = renderer.font_manager().layout_run("ab", 16.0, 0, TextSpacing::default());
+        let baseline = 20 + run.lines[0].baseline as usize;
+        assert!(blue_rows.iter().any(|&y| y >= baseline));
+        assert!(blue_rows.iter().any(|&y| y < baseline - 2));
+    }
+
+    #[test]
+    fn test_software_renderer_batch_submission() {
+        use crate::ffi::{
+            dop_renderer_create_headless, dop_renderer_free, dop_renderer_get_framebuffer, dop_renderer_intern_string,
+            dop_renderer_render, dop_renderer_submit_commands, dop_renderer_submit_text_commands, PackedTextCommand,
+        };
+
+        let handle = dop_renderer_create_headless(40, 20);
+        let rect = |x: f32, color_r: f32, color_b: f32| RenderCommand {
+            x,
+            width: 10.0,
+            height: 10.0,
+            color_r,
+            color_g: 0.0,
+            color_b,
+            ..Default::default()
+        };
+        let rects = [rect(0.0, 1.0, 0.0), rect(20.0, 0.0, 1.0)];
+        assert_eq!(dop_renderer_submit_commands(handle, rects.as_ptr(), rects.len()), 2);
+
+        let hello = dop_renderer_intern_string(handle, c"Hi".as_ptr());
+        assert_ne!(hello, 0);
+        assert_eq!(dop_renderer_intern_string(handle, c"Hi".as_ptr()), hello);
+        let text = PackedTextCommand {
+            string_id: hello,
+            y: 10.0,
+            font_size: 10.0,
+            color_a: 1.0,
+            ..Default::default()
+        };
+        let unknown = PackedTextCommand { string_id: 99, ..text };
+        assert_eq!(dop_renderer_submit_text_commands(handle, [text, unknown].as_ptr(), 2), 1);
+
+        dop_renderer_render(handle);
+        let pixels = unsafe { std::slice::from_raw_parts(dop_renderer_get_framebuffer(handle), 40 * 20 * 4) };
+        assert_eq!(&pixels[(5 * 40 + 5) * 4..][..3], &[255, 0, 0]);
+        assert_eq!(&pixels[(5 * 40 + 25) * 4..][..3], &[0, 0, 255]);
+        dop_renderer_free(handle);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_software_renderer_parallel_bands_match_serial() {
+        let render = |parallel: bool, radius: f32| {
+            let mut renderer = SoftwareRenderer::new(200, 300);
+            renderer.set_parallel(parallel);
+            // Translucent, rounded and fractional rectangles overlapping across band edges
+            for i in 0..120 {
+                let f = i as f32;
+                renderer.add_rect(RenderCommand {
+                    x: (f * 13.7) % 180.0,
+                    y: (f * 29.3) % 280.0 - 10.0,
+                    width: 15.5 + f % 40.0,
+                    height: 20.25 + (f * 7.0) % 90.0,
+                    color_r: (f * 0.13) % 1.0,
+                    color_g: (f * 0.29) % 1.0,
+                    color_b: (f * 0.71) % 1.0,
+                    color_a: if i % 3 == 0 { 1.0 } else { 0.5 },
+                    z_index: i % 4,
+                    corner_radii: [if i % 5 == 0 { radius } else { 0.0 }; 4],
+                    ..Default::default()
+                });
+            }
+            renderer.render();
+            (renderer.get_framebuffer().to_vec(), renderer.raster_stats())
+        };
+        // A fixed pool makes sure the frame is split even on a single-core machine
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        let (serial, serial_stats) = render(false, 0.0);
+        let (banded, banded_stats) = pool.install(|| render(true, 0.0));
+        assert_eq!(serial_stats, banded_stats);
+        assert!(banded == serial, "banded output differs from serial output");
+
+        // Curves cut at band edges may round their antialiasing differently; nothing else may change
+        let (serial, _) = render(false, 6.0);
+        let (banded, _) = pool.install(|| render(true, 6.0));
+        let diff = crate::testing::compare(&banded, &serial, 0.05);
+        assert_eq!(diff.mismatched, 0, "max distance {}", diff.max_distance);
+    }
+
+    #[test]
+    fn test_software_renderer_fixed_point_blend() {
+        // Row lengths around the 4-pixel SIMD step exercise both the lanes and the scalar tail
+        for len in [1, 3, 4, 5, 8, 13] {
+            let src: Vec<u8> = (0..len * 4).map(|i| (i * 67 % 256) as u8).collect();
+            let mask: Vec<u8> = (0..len).map(|i| if i % 3 == 1 { 0 } else { 255 }).collect();
+            let mut dst: Vec<u8> = (0..len * 4).map(|i| (i * 29 % 256) as u8).collect();
+            let before = dst.clone();
+            let touched = crate::blend::blend_row(&mut dst, &src, Some(&mask));
+
+            let mut expected_touched = 0;
+            for i in 0..len {
+                let (d, b, s) = (&dst[i * 4..i * 4 + 4], &before[i * 4..i * 4 + 4], &src[i * 4..i * 4 + 4]);
+                if mask[i] == 0 || s[3] == 0 {
+                    assert_eq!(d, b, "pixel {} of {} should be untouched", i, len);
+                    continue;
+                }
+                expected_touched += 1;
+                let a = s[3] as f32 / 255.0;
+                for c in 0..4 {
+                    let src_c = if c == 3 { 255.0 } else { s[c] as f32 };
+                    let exact = src_c * a + b[c] as f32 * (1.0 - a);
+                    assert!((d[c] as f32 - exact).abs() <= 0.5, "pixel {} channel {}: {} vs {}", i, c, d[c], exact);
+                }
+            }
+            assert_eq!(touched, expected_touched);
+
+            // Per-channel coverage equal across channels matches a solid color blend
+            let coverage: Vec<u8> = src.chunks_exact(4).map(|p| p[3]).collect();
+            let rgb: Vec<u8> = coverage.iter().flat_map(|&c| [c; 3]).collect();
+            let mut solid = before.clone();
+            let mut subpixel = before.clone();
+            crate::blend::blend_color_row(&mut solid, [10, 200, 90, 180], &coverage);
+            crate::blend::blend_subpixel_row(&mut subpixel, [10, 200, 90], 180, &rgb, None);
+            assert_eq!(solid, subpixel);
+        }
     }
 }
@@ -0,0 +1,153 @@
+//! Stacking contexts for rectangle paint order
+//!
+//! A z-index alone only orders rectangles globally, so a child of a low-z
+//! container can paint above a higher-z sibling of the container. Following
+//! CSS, a stacking context paints as one unit at its own z-index among its
+//! siblings, and its contents are ordered by z-index inside it.
+//!
+//! Each rectangle's paint key is the chain of (z-index, position) pairs of
+//! its enclosing contexts, outermost first, ending with its own z-index and
+//! insertion position. Rectangles and contexts share one insertion sequence,
+//! so sorting by that key gives CSS painting order: sibling contexts and
+//! rectangles compare by z-index, then by the order they were added.
+//!
+//! Only rectangles take part. The software renderer paints paths, images,
+//! bitmaps and text in later fixed stages, so they stay above every
+//! rectangle regardless of stacking context.
+
+use std::cmp::Ordering;
+
+use crate::renderer::RenderCommand;
+
+/// Identifier of a stacking context
+pub type StackingContextId = u32;
+
+/// The root stacking context, which every other context descends from
+pub const ROOT_STACKING_CONTEXT: StackingContextId = 0;
+
+#[derive(Debug, Clone, Copy)]
+struct Context {
+    parent: StackingContextId,
+    z_index: i32,
+    /// Insertion sequence of the next rectangle when the context was opened
+    position: u32,
+}
+
+/// Stacking contexts of one frame, with the stack of open ones
+#[derive(Debug, Clone, Default)]
+pub struct StackingContexts {
+    /// Context `id` is stored at `id - 1`
+    contexts: Vec<Context>,
+    open: Vec<StackingContextId>,
+}
+
+impl StackingContexts {
+    /// Create an empty set with only the root context open
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check if any context besides the root was opened this frame
+    pub fn is_empty(&self) -> bool {
+        self.contexts.is_empty()
+    }
+
+    /// Innermost open context
+    pub fn current(&self) -> StackingContextId {
+        self.open.last().copied().unwrap_or(ROOT_STACKING_CONTEXT)
+    }
+
+    /// Open a context inside the current one, painted at `z_index` among its siblings
+    ///
+    /// `position` is the insertion sequence the next rectangle will get, which
+    /// orders the context against sibling contexts and rectangles with the same
+    /// z-index.
+    pub fn push(&mut self, z_index: i32, position: u32) -> StackingContextId {
+        self.contexts.push(Context {
+            parent: self.current(),
+            z_index,
+            position,
+        });
+        let id = self.contexts.len() as StackingContextId;
+        self.open.push(id);
+        id
+    }
+
+    /// Close the innermost open context; returns false if only the root is open
+    pub fn pop(&mut self) -> bool {
+        self.open.pop().is_some()
+    }
+
+    /// Forget every context, leaving only the root open
+    pub fn clear(&mut self) {
+        self.contexts.clear();
+        self.open.clear();
+    }
+
+    fn get(&self, id: StackingContextId) -> Option<&Context> {
+        id.checked_sub(1).and_then(|i| self.contexts.get(i as usize))
+    }
+
+    /// Paint key of a rectangle with insertion sequence `position`
+    ///
+    /// Unknown context ids, such as those of retained rectangles from an
+    /// earlier frame, count as the root.
+    fn paint_key(&self, cmd: &RenderCommand, position: u32) -> Vec<(i32, u32)> {
+        let mut key = vec![(cmd.z_index, position)];
+        let mut id = cmd.stacking_context;
+        // Parents are always opened before their children, so the walk ends
+        while let Some(context) = self.get(id) {
+            key.push((context.z_index, context.position));
+            id = context.parent;
+        }
+        key.reverse();
+        key
+    }
+
+    /// Compare the paint order of two rectangles with the given insertion sequences
+    pub fn compare(&self, a: (&RenderCommand, u32), b: (&RenderCommand, u32)) -> Ordering {
+        if self.is_empty() {
+            return (a.0.z_index, a.1).cmp(&(b.0.z_index, b.1));
+        }
+        self.paint_key(a.0, a.1).cmp(&self.paint_key(b.0, b.1))
+    }
+
+    /// Sort rectangles paired with their insertion sequence into paint order
+    pub fn sort(&self, commands: &mut [(RenderCommand, u32)]) {
+        self.sort_by(commands, |(cmd, position)| (cmd, *position));
+    }
+
+    /// Sort items holding rectangles into paint order, given each one's rectangle and insertion sequence
+    pub fn sort_by<T: Copy>(&self, items: &mut [T], key: impl Fn(&T) -> (&RenderCommand, u32)) {
+        if self.is_empty() {
+            items.sort_by_key(|item| {
+                let (cmd, position) = key(item);
+                (cmd.z_index, position)
+            });
+            return;
+        }
+        let mut keyed: Vec<(Vec<(i32, u32)>, T)> = items
+            .iter()
+            .map(|item| {
+                let (cmd, position) = key(item);
+                (self.paint_key(cmd, position), *item)
+            })
+            .collect();
+        keyed.sort_by(|a, b| a.0.cmp(&b.0));
+        for (slot, (_, item)) in items.iter_mut().zip(keyed) {
+            *slot = item;
+        }
+    }
+
+    /// Sort rectangles into paint order, then renumber their z-indices in that order and move
+    /// them to the root context
+    ///
+    /// Renderers that only sort by z-index then paint them in the same order.
+    pub fn flatten(&self, commands: &mut [(RenderCommand, u32)]) {
+        self.sort(commands);
+        for (rank, (cmd, _)) in commands.iter_mut().enumerate() {
+            cmd.z_index = rank as i32;
+            cmd.stacking_context = ROOT_STACKING_CONTEXT;
+        }
+    }
+}
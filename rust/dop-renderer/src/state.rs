@@ -0,0 +1,199 @@
+//! Graphics state stack for the software renderer
+//!
+//! Draw calls are recorded and sorted before rasterization, so state is
+//! applied when a command is added rather than at render time: transforms
+//! and clips are folded into command geometry where possible, opacity is
+//! multiplied into the color, and the rest is carried as a [`PaintState`].
+//!
+//! Transforms are full affine matrices. Under translation and scale
+//! rectangles stay axis aligned and are mapped directly; rotated or skewed
+//! rectangles are drawn as paths. Text, images and bitmaps are not rotated:
+//! they are placed upright at the bounds of their transformed position, and
+//! clips are the bounds of the transformed clip rectangle.
+
+use tiny_skia::{Rect, Transform};
+
+use crate::renderer::ImageFilter;
+
+/// Compositing operator for rectangles, images and bitmaps
+///
+/// Text always composites with `Normal`.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    #[default]
+    Normal = 0,
+    Multiply = 1,
+    Screen = 2,
+    Overlay = 3,
+    Darken = 4,
+    Lighten = 5,
+    Difference = 6,
+    Exclusion = 7,
+    Plus = 8,
+}
+
+impl BlendMode {
+    /// Convert a raw FFI value into a BlendMode (unknown values map to Normal)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => BlendMode::Multiply,
+            2 => BlendMode::Screen,
+            3 => BlendMode::Overlay,
+            4 => BlendMode::Darken,
+            5 => BlendMode::Lighten,
+            6 => BlendMode::Difference,
+            7 => BlendMode::Exclusion,
+            8 => BlendMode::Plus,
+            _ => BlendMode::Normal,
+        }
+    }
+
+    pub fn to_skia(self) -> tiny_skia::BlendMode {
+        match self {
+            BlendMode::Normal => tiny_skia::BlendMode::SourceOver,
+            BlendMode::Multiply => tiny_skia::BlendMode::Multiply,
+            BlendMode::Screen => tiny_skia::BlendMode::Screen,
+            BlendMode::Overlay => tiny_skia::BlendMode::Overlay,
+            BlendMode::Darken => tiny_skia::BlendMode::Darken,
+            BlendMode::Lighten => tiny_skia::BlendMode::Lighten,
+            BlendMode::Difference => tiny_skia::BlendMode::Difference,
+            BlendMode::Exclusion => tiny_skia::BlendMode::Exclusion,
+            BlendMode::Plus => tiny_skia::BlendMode::Plus,
+        }
+    }
+}
+
+/// Clip bounds as left, top, right, bottom in device pixels
+pub type ClipBounds = [f32; 4];
+
+/// State a command still needs at rasterization time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaintState {
+    pub clip: Option<ClipBounds>,
+    pub opacity: f32,
+    pub blend_mode: BlendMode,
+    /// Sampling for scaled images and bitmaps
+    pub image_filter: ImageFilter,
+}
+
+impl Default for PaintState {
+    fn default() -> Self {
+        Self {
+            clip: None,
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+            image_filter: ImageFilter::Bilinear,
+        }
+    }
+}
+
+impl PaintState {
+    /// Clip as a tiny-skia rect (None when unclipped or the clip is empty)
+    pub fn clip_rect(&self) -> Option<Rect> {
+        self.clip.and_then(|[l, t, r, b]| Rect::from_ltrb(l, t, r, b))
+    }
+
+    /// Check if the clip rejects everything
+    pub fn is_clipped_out(&self) -> bool {
+        self.clip.is_some_and(|[l, t, r, b]| r <= l || b <= t)
+    }
+}
+
+/// Current transform, clip, opacity, blend mode and image filter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GraphicsState {
+    /// Maps the current coordinate space to device pixels
+    pub transform: Transform,
+    pub paint: PaintState,
+}
+
+impl Default for GraphicsState {
+    fn default() -> Self {
+        Self {
+            transform: Transform::identity(),
+            paint: PaintState::default(),
+        }
+    }
+}
+
+impl GraphicsState {
+    /// Translate subsequent draws (in the current coordinate space)
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        self.transform = self.transform.pre_translate(dx, dy);
+    }
+
+    /// Scale subsequent draws around the current origin
+    pub fn scale(&mut self, sx: f32, sy: f32) {
+        self.transform = self.transform.pre_scale(sx, sy);
+    }
+
+    /// Apply `transform` to subsequent draws before the current transform
+    pub fn concat(&mut self, transform: Transform) {
+        self.transform = self.transform.pre_concat(transform);
+    }
+
+    /// Check if rectangles map to axis-aligned rectangles (no rotation or skew)
+    pub fn is_axis_aligned(&self) -> bool {
+        !self.transform.has_skew()
+    }
+
+    /// Average scale of the transform, for stroke widths, corner radii and font sizes
+    pub fn average_scale(&self) -> f32 {
+        let (sx, sy) = self.transform.get_scale();
+        (sx + sy) * 0.5
+    }
+
+    /// Map a point to device space
+    pub fn map_point(&self, x: f32, y: f32) -> (f32, f32) {
+        let t = &self.transform;
+        (t.sx * x + t.kx * y + t.tx, t.ky * x + t.sy * y + t.ty)
+    }
+
+    /// Map a rectangle to device space, normalizing negative scales
+    ///
+    /// Rotated or skewed rectangles map to their bounding box.
+    pub fn map_rect(&self, x: f32, y: f32, width: f32, height: f32) -> (f32, f32, f32, f32) {
+        let corners = [
+            self.map_point(x, y),
+            self.map_point(x + width, y),
+            self.map_point(x, y + height),
+            self.map_point(x + width, y + height),
+        ];
+        let (mut l, mut t, mut r, mut b) = (f32::INFINITY, f32::INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for (cx, cy) in corners {
+            (l, t, r, b) = (l.min(cx), t.min(cy), r.max(cx), b.max(cy));
+        }
+        (l, t, r - l, b - t)
+    }
+
+    /// Intersect the clip with a rectangle in the current coordinate space
+    pub fn clip(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let (x, y, w, h) = self.map_rect(x, y, width, height);
+        let mut bounds = [x, y, x + w, y + h];
+        if let Some([l, t, r, b]) = self.paint.clip {
+            bounds = [bounds[0].max(l), bounds[1].max(t), bounds[2].min(r), bounds[3].min(b)];
+        }
+        self.paint.clip = Some(bounds);
+    }
+
+    /// Map a rectangle and cut it to the clip (None if nothing remains)
+    pub fn clip_mapped(&self, x: f32, y: f32, width: f32, height: f32) -> Option<(f32, f32, f32, f32)> {
+        let (x, y, w, h) = self.map_rect(x, y, width, height);
+        let Some([l, t, r, b]) = self.paint.clip else {
+            return Some((x, y, w, h));
+        };
+        let (x0, y0) = (x.max(l), y.max(t));
+        let (x1, y1) = ((x + w).min(r), (y + h).min(b));
+        if x1 <= x0 || y1 <= y0 {
+            None
+        } else {
+            Some((x0, y0, x1 - x0, y1 - y0))
+        }
+    }
+
+    /// Check if this is the identity state
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+}
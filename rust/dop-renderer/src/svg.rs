@@ -0,0 +1,744 @@
+//! Inline SVG
+//!
+//! Parses the static subset of SVG that pages embed for icons and simple
+//! illustrations into paths the renderer already knows how to draw:
+//! - `path` (all `d` commands, arcs converted to cubic curves), `rect` (with
+//!   `rx`/`ry`), `circle`, `ellipse`, `line`, `polyline` and `polygon`
+//! - `g`, `a` and nested `svg` elements as groups; `defs`, `symbol`,
+//!   `clipPath`, `mask`, `text` and other unsupported elements are skipped
+//! - `fill`, `stroke`, their opacities, `stroke-width`, `stroke-linecap`,
+//!   `stroke-linejoin`, `fill-rule`, `opacity`, `color` and `transform`, as
+//!   presentation attributes or in a `style` attribute
+//!
+//! Gradient and pattern paints (`url(...)`) are treated as `none`. Group
+//! opacity multiplies into each shape rather than compositing the group.
+
+use crate::path::{FillRule, LineCap, LineJoin, Path, StrokeStyle};
+
+/// Affine transform (a, b, c, d, e, f): (x, y) maps to (a*x + c*y + e, b*x + d*y + f)
+pub type SvgTransform = [f32; 6];
+
+const IDENTITY: SvgTransform = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// A shape ready to be filled and/or stroked
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgShape {
+    /// Geometry in the shape's own user space
+    pub path: Path,
+    /// Element and ancestor transforms, mapping user space to the viewBox
+    pub transform: SvgTransform,
+    /// Straight-alpha sRGB fill, None for `fill="none"`
+    pub fill: Option<[f32; 4]>,
+    pub fill_rule: FillRule,
+    /// Straight-alpha sRGB stroke, None for `stroke="none"`
+    pub stroke: Option<[f32; 4]>,
+    pub stroke_style: StrokeStyle,
+    /// Product of `opacity` on the element and its ancestors
+    pub opacity: f32,
+}
+
+/// How the viewBox is fitted into the drawing rectangle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AspectRatio {
+    /// Alignment along x and y: 0 = min, 0.5 = mid, 1 = max; None stretches
+    pub align: Option<(f32, f32)>,
+    /// Cover the rectangle (`slice`) instead of fitting inside it (`meet`)
+    pub slice: bool,
+}
+
+impl Default for AspectRatio {
+    fn default() -> Self {
+        Self {
+            align: Some((0.5, 0.5)),
+            slice: false,
+        }
+    }
+}
+
+/// A parsed SVG document
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgDocument {
+    /// min-x, min-y, width, height of the user coordinate system
+    pub view_box: [f32; 4],
+    pub aspect_ratio: AspectRatio,
+    /// Shapes in painting order
+    pub shapes: Vec<SvgShape>,
+}
+
+impl SvgDocument {
+    /// Parse an SVG document from XML text
+    pub fn parse(xml: &str) -> Result<Self, String> {
+        let doc = roxmltree::Document::parse(xml).map_err(|e| e.to_string())?;
+        let root = doc.root_element();
+        if root.tag_name().name() != "svg" {
+            return Err(format!("root element is <{}>, not <svg>", root.tag_name().name()));
+        }
+
+        let width = root.attribute("width").and_then(parse_length);
+        let height = root.attribute("height").and_then(parse_length);
+        let view_box = root
+            .attribute("viewBox")
+            .and_then(|v| {
+                let n = parse_numbers(v);
+                (n.len() == 4 && n[2] > 0.0 && n[3] > 0.0).then(|| [n[0], n[1], n[2], n[3]])
+            })
+            .unwrap_or([0.0, 0.0, width.unwrap_or(300.0), height.unwrap_or(150.0)]);
+        let aspect_ratio = root
+            .attribute("preserveAspectRatio")
+            .map(parse_aspect_ratio)
+            .unwrap_or_default();
+
+        let mut shapes = Vec::new();
+        collect_shapes(root, &Style::default(), IDENTITY, 1.0, &mut shapes);
+        Ok(Self {
+            view_box,
+            aspect_ratio,
+            shapes,
+        })
+    }
+
+    /// Transform mapping the viewBox into the rectangle (x, y, width, height)
+    pub fn viewport_transform(&self, x: f32, y: f32, width: f32, height: f32) -> SvgTransform {
+        let [vx, vy, vw, vh] = self.view_box;
+        let (mut sx, mut sy) = (width / vw, height / vh);
+        let (mut tx, mut ty) = (x, y);
+        if let Some((ax, ay)) = self.aspect_ratio.align {
+            let s = if self.aspect_ratio.slice { sx.max(sy) } else { sx.min(sy) };
+            tx += (width - vw * s) * ax;
+            ty += (height - vh * s) * ay;
+            sx = s;
+            sy = s;
+        }
+        [sx, 0.0, 0.0, sy, tx - vx * sx, ty - vy * sy]
+    }
+}
+
+/// Inherited presentation properties
+#[derive(Debug, Clone, Copy)]
+struct Style {
+    fill: Paint,
+    fill_opacity: f32,
+    fill_rule: FillRule,
+    stroke: Paint,
+    stroke_opacity: f32,
+    stroke_width: f32,
+    line_cap: LineCap,
+    line_join: LineJoin,
+    /// Value of `currentColor`
+    color: [f32; 4],
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            fill: Paint::Color([0.0, 0.0, 0.0, 1.0]),
+            fill_opacity: 1.0,
+            fill_rule: FillRule::NonZero,
+            stroke: Paint::None,
+            stroke_opacity: 1.0,
+            stroke_width: 1.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            color: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Paint {
+    None,
+    Color([f32; 4]),
+    CurrentColor,
+}
+
+impl Paint {
+    fn resolve(self, current: [f32; 4], opacity: f32) -> Option<[f32; 4]> {
+        let [r, g, b, a] = match self {
+            Paint::None => return None,
+            Paint::Color(c) => c,
+            Paint::CurrentColor => current,
+        };
+        Some([r, g, b, a * opacity.clamp(0.0, 1.0)])
+    }
+}
+
+/// Walk an element and its children, appending drawable shapes
+fn collect_shapes(node: roxmltree::Node, parent: &Style, transform: SvgTransform, opacity: f32, out: &mut Vec<SvgShape>) {
+    let mut style = *parent;
+    let mut own_opacity = 1.0;
+    // Presentation attributes first, then the style attribute overrides them
+    for attr in node.attributes() {
+        apply_property(&mut style, &mut own_opacity, attr.name(), attr.value());
+    }
+    if let Some(css) = node.attribute("style") {
+        for declaration in css.split(';') {
+            if let Some((name, value)) = declaration.split_once(':') {
+                apply_property(&mut style, &mut own_opacity, name.trim(), value.trim());
+            }
+        }
+    }
+    if node.attribute("display") == Some("none") || node.attribute("visibility") == Some("hidden") {
+        return;
+    }
+    let transform = match node.attribute("transform") {
+        Some(t) => multiply(transform, parse_transform(t)),
+        None => transform,
+    };
+    let opacity = opacity * own_opacity;
+
+    let path = match node.tag_name().name() {
+        "svg" | "g" | "a" => {
+            for child in node.children().filter(|n| n.is_element()) {
+                collect_shapes(child, &style, transform, opacity, out);
+            }
+            return;
+        }
+        "path" => node.attribute("d").map(parse_path_data),
+        "rect" => rect_path(node),
+        "circle" => {
+            let r = number_attr(node, "r");
+            (r > 0.0).then(|| ellipse_path(number_attr(node, "cx"), number_attr(node, "cy"), r, r))
+        }
+        "ellipse" => {
+            let (rx, ry) = (number_attr(node, "rx"), number_attr(node, "ry"));
+            (rx > 0.0 && ry > 0.0).then(|| ellipse_path(number_attr(node, "cx"), number_attr(node, "cy"), rx, ry))
+        }
+        "line" => {
+            let mut path = Path::new();
+            path.move_to(number_attr(node, "x1"), number_attr(node, "y1"));
+            path.line_to(number_attr(node, "x2"), number_attr(node, "y2"));
+            // Lines have no area, so they are never filled
+            style.fill = Paint::None;
+            Some(path)
+        }
+        name @ ("polyline" | "polygon") => node.attribute("points").and_then(|points| {
+            let n = parse_numbers(points);
+            let mut pairs = n.chunks_exact(2);
+            let first = pairs.next()?;
+            let mut path = Path::new();
+            path.move_to(first[0], first[1]);
+            for p in pairs {
+                path.line_to(p[0], p[1]);
+            }
+            if name == "polygon" {
+                path.close();
+            }
+            Some(path)
+        }),
+        _ => None,
+    };
+
+    if let Some(path) = path.filter(|p| !p.is_empty()) {
+        let fill = style.fill.resolve(style.color, style.fill_opacity);
+        let stroke = style
+            .stroke
+            .resolve(style.color, style.stroke_opacity)
+            .filter(|_| style.stroke_width > 0.0);
+        if fill.is_some() || stroke.is_some() {
+            out.push(SvgShape {
+                path,
+                transform,
+                fill,
+                fill_rule: style.fill_rule,
+                stroke,
+                stroke_style: StrokeStyle {
+                    width: style.stroke_width,
+                    cap: style.line_cap,
+                    join: style.line_join,
+                },
+                opacity,
+            });
+        }
+    }
+}
+
+/// Apply one presentation property; unknown names and invalid values are ignored
+fn apply_property(style: &mut Style, opacity: &mut f32, name: &str, value: &str) {
+    let value = value.trim();
+    match name {
+        "fill" => {
+            if let Some(paint) = parse_paint(value) {
+                style.fill = paint;
+            }
+        }
+        "stroke" => {
+            if let Some(paint) = parse_paint(value) {
+                style.stroke = paint;
+            }
+        }
+        "color" => {
+            if let Some(color) = parse_color(value) {
+                style.color = color;
+            }
+        }
+        "fill-opacity" => style.fill_opacity = parse_opacity(value).unwrap_or(style.fill_opacity),
+        "stroke-opacity" => style.stroke_opacity = parse_opacity(value).unwrap_or(style.stroke_opacity),
+        "opacity" => *opacity = parse_opacity(value).unwrap_or(1.0),
+        "stroke-width" => style.stroke_width = parse_length(value).unwrap_or(style.stroke_width),
+        "fill-rule" => {
+            style.fill_rule = if value == "evenodd" { FillRule::EvenOdd } else { FillRule::NonZero };
+        }
+        "stroke-linecap" => {
+            style.line_cap = match value {
+                "round" => LineCap::Round,
+                "square" => LineCap::Square,
+                _ => LineCap::Butt,
+            };
+        }
+        "stroke-linejoin" => {
+            style.line_join = if value == "round" { LineJoin::Round } else { LineJoin::Miter };
+        }
+        _ => {}
+    }
+}
+
+fn parse_paint(value: &str) -> Option<Paint> {
+    match value {
+        "none" => Some(Paint::None),
+        "currentColor" => Some(Paint::CurrentColor),
+        v if v.starts_with("url(") => Some(Paint::None),
+        v => parse_color(v).map(Paint::Color),
+    }
+}
+
+fn parse_opacity(value: &str) -> Option<f32> {
+    let v = match value.strip_suffix('%') {
+        Some(pct) => pct.trim().parse::<f32>().ok()? / 100.0,
+        None => value.parse::<f32>().ok()?,
+    };
+    Some(v.clamp(0.0, 1.0))
+}
+
+/// Parse a hex, `rgb()`/`rgba()` or named color into straight-alpha RGBA
+pub fn parse_color(value: &str) -> Option<[f32; 4]> {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits: Vec<f32> = hex.chars().map(|c| c.to_digit(16).map(|d| d as f32)).collect::<Option<_>>()?;
+        return match digits.len() {
+            3 | 4 => {
+                let mut c = digits.iter().map(|d| d * 17.0 / 255.0);
+                Some([c.next()?, c.next()?, c.next()?, c.next().unwrap_or(1.0)])
+            }
+            6 | 8 => {
+                let mut c = digits.chunks(2).map(|p| (p[0] * 16.0 + p[1]) / 255.0);
+                Some([c.next()?, c.next()?, c.next()?, c.next().unwrap_or(1.0)])
+            }
+            _ => None,
+        };
+    }
+    if let Some(args) = value
+        .strip_prefix("rgba(")
+        .or_else(|| value.strip_prefix("rgb("))
+        .and_then(|v| v.strip_suffix(')'))
+    {
+        let parts: Vec<&str> = args.split([',', ' ', '/']).filter(|p| !p.is_empty()).collect();
+        if parts.len() < 3 {
+            return None;
+        }
+        let channel = |p: &str| match p.strip_suffix('%') {
+            Some(pct) => pct.parse::<f32>().ok().map(|v| v / 100.0),
+            None => p.parse::<f32>().ok().map(|v| v / 255.0),
+        };
+        let alpha = parts.get(3).map_or(Some(1.0), |a| parse_opacity(a))?;
+        return Some([
+            channel(parts[0])?.clamp(0.0, 1.0),
+            channel(parts[1])?.clamp(0.0, 1.0),
+            channel(parts[2])?.clamp(0.0, 1.0),
+            alpha,
+        ]);
+    }
+    let rgb: u32 = match value.to_ascii_lowercase().as_str() {
+        "transparent" => return Some([0.0; 4]),
+        "black" => 0x000000,
+        "white" => 0xffffff,
+        "red" => 0xff0000,
+        "green" => 0x008000,
+        "lime" => 0x00ff00,
+        "blue" => 0x0000ff,
+        "yellow" => 0xffff00,
+        "cyan" | "aqua" => 0x00ffff,
+        "magenta" | "fuchsia" => 0xff00ff,
+        "gray" | "grey" => 0x808080,
+        "silver" => 0xc0c0c0,
+        "maroon" => 0x800000,
+        "olive" => 0x808000,
+        "teal" => 0x008080,
+        "navy" => 0x000080,
+        "purple" => 0x800080,
+        "orange" => 0xffa500,
+        _ => return None,
+    };
+    Some([
+        (rgb >> 16) as f32 / 255.0,
+        ((rgb >> 8) & 0xff) as f32 / 255.0,
+        (rgb & 0xff) as f32 / 255.0,
+        1.0,
+    ])
+}
+
+/// Parse a length in user units (`px` or no unit; other units are rejected)
+fn parse_length(value: &str) -> Option<f32> {
+    let value = value.trim();
+    value.strip_suffix("px").unwrap_or(value).trim().parse().ok()
+}
+
+fn number_attr(node: roxmltree::Node, name: &str) -> f32 {
+    node.attribute(name).and_then(parse_length).unwrap_or(0.0)
+}
+
+fn parse_aspect_ratio(value: &str) -> AspectRatio {
+    let mut parts = value.split_whitespace();
+    let align = match parts.next() {
+        Some("none") => None,
+        Some(a) if a.len() == 8 => {
+            let axis = |s: &str| match s.to_ascii_lowercase().as_str() {
+                "min" => 0.0,
+                "max" => 1.0,
+                _ => 0.5,
+            };
+            Some((axis(&a[1..4]), axis(&a[5..8])))
+        }
+        _ => Some((0.5, 0.5)),
+    };
+    AspectRatio {
+        align,
+        slice: parts.next() == Some("slice"),
+    }
+}
+
+/// Compose two transforms: `inner` is applied first
+fn multiply(outer: SvgTransform, inner: SvgTransform) -> SvgTransform {
+    let [a, b, c, d, e, f] = outer;
+    let [a2, b2, c2, d2, e2, f2] = inner;
+    [
+        a * a2 + c * b2,
+        b * a2 + d * b2,
+        a * c2 + c * d2,
+        b * c2 + d * d2,
+        a * e2 + c * f2 + e,
+        b * e2 + d * f2 + f,
+    ]
+}
+
+/// Parse a `transform` list; an invalid entry ends the list
+fn parse_transform(value: &str) -> SvgTransform {
+    let mut result = IDENTITY;
+    let mut rest = value;
+    while let Some(open) = rest.find('(') {
+        let name = rest[..open].trim_matches(|c: char| c.is_whitespace() || c == ',');
+        let Some(close) = rest[open..].find(')') else {
+            break;
+        };
+        let args = parse_numbers(&rest[open + 1..open + close]);
+        rest = &rest[open + close + 1..];
+        let arg = |i: usize, default: f32| args.get(i).copied().unwrap_or(default);
+        let m = match (name, args.len()) {
+            ("matrix", 6) => [args[0], args[1], args[2], args[3], args[4], args[5]],
+            ("translate", 1 | 2) => [1.0, 0.0, 0.0, 1.0, args[0], arg(1, 0.0)],
+            ("scale", 1 | 2) => [args[0], 0.0, 0.0, arg(1, args[0]), 0.0, 0.0],
+            ("rotate", 1 | 3) => {
+                let (sin, cos) = args[0].to_radians().sin_cos();
+                let (cx, cy) = (arg(1, 0.0), arg(2, 0.0));
+                // Rotate about (cx, cy): translate(cx, cy) rotate(a) translate(-cx, -cy)
+                [cos, sin, -sin, cos, cx - cos * cx + sin * cy, cy - sin * cx - cos * cy]
+            }
+            ("skewX", 1) => [1.0, 0.0, args[0].to_radians().tan(), 1.0, 0.0, 0.0],
+            ("skewY", 1) => [1.0, args[0].to_radians().tan(), 0.0, 1.0, 0.0, 0.0],
+            _ => break,
+        };
+        result = multiply(result, m);
+    }
+    result
+}
+
+/// Reads numbers and arc flags from path data and attribute lists
+struct NumberReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> NumberReader<'a> {
+    fn new(s: &'a str) -> Self {
+        Self { bytes: s.as_bytes(), pos: 0 }
+    }
+
+    fn skip_separators(&mut self) {
+        while self.pos < self.bytes.len() && (self.bytes[self.pos].is_ascii_whitespace() || self.bytes[self.pos] == b',') {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_separators();
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn number(&mut self) -> Option<f32> {
+        self.skip_separators();
+        let start = self.pos;
+        let digits = |r: &mut Self| {
+            let s = r.pos;
+            while r.pos < r.bytes.len() && r.bytes[r.pos].is_ascii_digit() {
+                r.pos += 1;
+            }
+            r.pos > s
+        };
+        if matches!(self.bytes.get(self.pos), Some(b'+' | b'-')) {
+            self.pos += 1;
+        }
+        let mut any = digits(self);
+        if self.bytes.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            any |= digits(self);
+        }
+        if !any {
+            self.pos = start;
+            return None;
+        }
+        // An exponent needs digits, so "1e" stays a number followed by "e"
+        if matches!(self.bytes.get(self.pos), Some(b'e' | b'E')) {
+            let mark = self.pos;
+            self.pos += 1;
+            if matches!(self.bytes.get(self.pos), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if !digits(self) {
+                self.pos = mark;
+            }
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos]).ok()?.parse().ok()
+    }
+
+    /// Arc flags are a single 0 or 1 and need no separator
+    fn flag(&mut self) -> Option<bool> {
+        let flag = match self.peek()? {
+            b'0' => false,
+            b'1' => true,
+            _ => return None,
+        };
+        self.pos += 1;
+        Some(flag)
+    }
+}
+
+fn parse_numbers(s: &str) -> Vec<f32> {
+    let mut reader = NumberReader::new(s);
+    std::iter::from_fn(|| reader.number()).collect()
+}
+
+/// Parse path data; an error keeps the segments parsed before it, as SVG requires
+pub fn parse_path_data(d: &str) -> Path {
+    let mut path = Path::new();
+    let mut r = NumberReader::new(d);
+    let (mut cur, mut start) = ((0.0f32, 0.0f32), (0.0f32, 0.0f32));
+    // Last control point, for reflecting into S/s and T/t
+    let mut last_cubic: Option<(f32, f32)> = None;
+    let mut last_quad: Option<(f32, f32)> = None;
+    let mut command = None;
+
+    while let Some(next) = r.peek() {
+        if next.is_ascii_alphabetic() {
+            r.pos += 1;
+            command = Some(next);
+        } else if command.is_none() {
+            break;
+        }
+        let Some(cmd) = command else { break };
+        let rel = cmd.is_ascii_lowercase();
+        let base = if rel { cur } else { (0.0, 0.0) };
+        let point = |r: &mut NumberReader| -> Option<(f32, f32)> { Some((r.number()? + base.0, r.number()? + base.1)) };
+
+        let (cubic, quad) = match cmd.to_ascii_uppercase() {
+            b'M' => {
+                let Some(p) = point(&mut r) else { break };
+                path.move_to(p.0, p.1);
+                cur = p;
+                start = p;
+                // Further pairs are implicit line-tos
+                command = Some(if rel { b'l' } else { b'L' });
+                (None, None)
+            }
+            b'L' => {
+                let Some(p) = point(&mut r) else { break };
+                path.line_to(p.0, p.1);
+                cur = p;
+                (None, None)
+            }
+            b'H' => {
+                let Some(x) = r.number() else { break };
+                cur.0 = x + base.0;
+                path.line_to(cur.0, cur.1);
+                (None, None)
+            }
+            b'V' => {
+                let Some(y) = r.number() else { break };
+                cur.1 = y + base.1;
+                path.line_to(cur.0, cur.1);
+                (None, None)
+            }
+            b'C' => {
+                let (Some(c1), Some(c2), Some(p)) = (point(&mut r), point(&mut r), point(&mut r)) else { break };
+                path.cubic_to(c1.0, c1.1, c2.0, c2.1, p.0, p.1);
+                cur = p;
+                (Some(c2), None)
+            }
+            b'S' => {
+                let (Some(c2), Some(p)) = (point(&mut r), point(&mut r)) else { break };
+                let c1 = last_cubic.map_or(cur, |c| (2.0 * cur.0 - c.0, 2.0 * cur.1 - c.1));
+                path.cubic_to(c1.0, c1.1, c2.0, c2.1, p.0, p.1);
+                cur = p;
+                (Some(c2), None)
+            }
+            b'Q' => {
+                let (Some(c), Some(p)) = (point(&mut r), point(&mut r)) else { break };
+                path.quad_to(c.0, c.1, p.0, p.1);
+                cur = p;
+                (None, Some(c))
+            }
+            b'T' => {
+                let Some(p) = point(&mut r) else { break };
+                let c = last_quad.map_or(cur, |c| (2.0 * cur.0 - c.0, 2.0 * cur.1 - c.1));
+                path.quad_to(c.0, c.1, p.0, p.1);
+                cur = p;
+                (None, Some(c))
+            }
+            b'A' => {
+                let (Some(rx), Some(ry), Some(angle)) = (r.number(), r.number(), r.number()) else { break };
+                let (Some(large), Some(sweep), Some(p)) = (r.flag(), r.flag(), point(&mut r)) else { break };
+                arc_to(&mut path, cur, rx, ry, angle, large, sweep, p);
+                cur = p;
+                (None, None)
+            }
+            b'Z' => {
+                path.close();
+                cur = start;
+                // Z takes no arguments; numbers after it are an error
+                command = None;
+                (None, None)
+            }
+            _ => break,
+        };
+        last_cubic = cubic;
+        last_quad = quad;
+    }
+    path
+}
+
+/// Append an elliptical arc as cubic curves (SVG endpoint parameterization)
+#[allow(clippy::too_many_arguments)]
+fn arc_to(path: &mut Path, from: (f32, f32), rx: f32, ry: f32, angle: f32, large: bool, sweep: bool, to: (f32, f32)) {
+    if from == to {
+        return;
+    }
+    let (mut rx, mut ry) = (rx.abs(), ry.abs());
+    if rx == 0.0 || ry == 0.0 {
+        path.line_to(to.0, to.1);
+        return;
+    }
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (dx, dy) = ((from.0 - to.0) / 2.0, (from.1 - to.1) / 2.0);
+    let (x1, y1) = (cos * dx + sin * dy, -sin * dx + cos * dy);
+
+    // Scale up radii that cannot reach the end point
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let num = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let den = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let mut coef = (num / den).max(0.0).sqrt();
+    if large == sweep {
+        coef = -coef;
+    }
+    let (cxp, cyp) = (coef * rx * y1 / ry, -coef * ry * x1 / rx);
+    let cx = cos * cxp - sin * cyp + (from.0 + to.0) / 2.0;
+    let cy = sin * cxp + cos * cyp + (from.1 + to.1) / 2.0;
+
+    let angle_between = |u: (f32, f32), v: (f32, f32)| (u.0 * v.1 - u.1 * v.0).atan2(u.0 * v.0 + u.1 * v.1);
+    let u = ((x1 - cxp) / rx, (y1 - cyp) / ry);
+    let v = ((-x1 - cxp) / rx, (-y1 - cyp) / ry);
+    let theta = angle_between((1.0, 0.0), u);
+    let mut delta = angle_between(u, v);
+    if !sweep && delta > 0.0 {
+        delta -= std::f32::consts::TAU;
+    } else if sweep && delta < 0.0 {
+        delta += std::f32::consts::TAU;
+    }
+
+    // One cubic per quarter turn at most
+    let count = (delta.abs() / std::f32::consts::FRAC_PI_2).ceil().max(1.0) as usize;
+    let step = delta / count as f32;
+    let t = 4.0 / 3.0 * (step / 4.0).tan();
+    let map = |ux: f32, uy: f32| (cx + rx * ux * cos - ry * uy * sin, cy + rx * ux * sin + ry * uy * cos);
+    for i in 0..count {
+        let (s1, c1) = (theta + step * i as f32).sin_cos();
+        let (s2, c2) = (theta + step * (i + 1) as f32).sin_cos();
+        let p1 = map(c1 - t * s1, s1 + t * c1);
+        let p2 = map(c2 + t * s2, s2 - t * c2);
+        let p = if i + 1 == count { to } else { map(c2, s2) };
+        path.cubic_to(p1.0, p1.1, p2.0, p2.1, p.0, p.1);
+    }
+}
+
+fn ellipse_path(cx: f32, cy: f32, rx: f32, ry: f32) -> Path {
+    let mut path = Path::new();
+    path.move_to(cx + rx, cy);
+    arc_to(&mut path, (cx + rx, cy), rx, ry, 0.0, false, true, (cx - rx, cy));
+    arc_to(&mut path, (cx - rx, cy), rx, ry, 0.0, false, true, (cx + rx, cy));
+    path.close();
+    path
+}
+
+fn rect_path(node: roxmltree::Node) -> Option<Path> {
+    let (x, y) = (number_attr(node, "x"), number_attr(node, "y"));
+    let (w, h) = (number_attr(node, "width"), number_attr(node, "height"));
+    if w <= 0.0 || h <= 0.0 {
+        return None;
+    }
+    // A missing rx or ry takes the other's value; both are capped at half the side
+    let rx_attr = node.attribute("rx").and_then(parse_length);
+    let ry_attr = node.attribute("ry").and_then(parse_length);
+    let rx = rx_attr.or(ry_attr).unwrap_or(0.0).clamp(0.0, w / 2.0);
+    let ry = ry_attr.or(rx_attr).unwrap_or(0.0).clamp(0.0, h / 2.0);
+    if rx == 0.0 || ry == 0.0 {
+        return Some(Path::rounded_rect(x, y, w, h, [0.0; 4]));
+    }
+    let mut path = Path::new();
+    path.move_to(x + rx, y);
+    path.line_to(x + w - rx, y);
+    arc_to(&mut path, (x + w - rx, y), rx, ry, 0.0, false, true, (x + w, y + ry));
+    path.line_to(x + w, y + h - ry);
+    arc_to(&mut path, (x + w, y + h - ry), rx, ry, 0.0, false, true, (x + w - rx, y + h));
+    path.line_to(x + rx, y + h);
+    arc_to(&mut path, (x + rx, y + h), rx, ry, 0.0, false, true, (x, y + h - ry));
+    path.line_to(x, y + ry);
+    arc_to(&mut path, (x, y + ry), rx, ry, 0.0, false, true, (x + rx, y));
+    path.close();
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let svg = SvgDocument::parse(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+                <rect x="0" y="0" width="5" height="5" fill="#f00"/>
+                <g style="fill: rgb(0, 0, 255)" transform="translate(5 0)">
+                    <circle cx="2.5" cy="2.5" r="2"/>
+                </g>
+                <path d="M0 10 V5 a5 5 0 0 1 5 5 z" fill="lime" opacity="0.5"/>
+                <line x1="5" y1="8" x2="10" y2="8" stroke="black" stroke-width="1"/>
+                <defs><rect width="10" height="10"/></defs>
+            </svg>"##,
+        )
+        .unwrap();
+        assert_eq!(svg.view_box, [0.0, 0.0, 10.0, 10.0]);
+        // Shapes inside <defs> are not drawn
+        assert_eq!(svg.shapes.len(), 4);
+        assert!(SvgDocument::parse("<html/>").is_err());
+    }
+}
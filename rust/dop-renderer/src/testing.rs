@@ -0,0 +1,300 @@
+//! Golden-image testing
+//!
+//! Renders small scene descriptions with the software renderer and compares
+//! the result against checked-in PNGs, so rendering changes are caught by
+//! `cargo test` without a host in the loop.
+//!
+//! Scenes are plain text, one command per line; `#` starts a comment and
+//! colors are straight-alpha sRGB components in 0..1:
+//!
+//! ```text
+//! size 64 48
+//! clear 1 1 1 1
+//! # rect x y width height r g b a [radius]
+//! rect 4 4 20 10 1 0 0 1
+//! # line x0 y0 x1 y1 width r g b a
+//! line 4 30 60 30 2 0 0 1 1
+//! # text x y size r g b a text (the rest of the line, '#' included)
+//! text 4 34 12 0 0 0 1 Hello
+//! ```
+//!
+//! Comparison uses the YIQ color distance from pixelmatch, so differences
+//! the eye barely sees (anti-aliasing noise, rounding) stay under the
+//! threshold while real changes do not. Set `DOP_UPDATE_GOLDEN=1` to
+//! rewrite golden files from the current output.
+
+use std::path::Path as FsPath;
+
+use crate::path::LineCap;
+use crate::renderer::RenderCommand;
+use crate::software::{SoftwareRenderer, TextCommand};
+use crate::text::{TextAnchor, TextDecoration, TextSpacing};
+
+/// One drawing command of a scene
+#[derive(Debug, Clone)]
+pub enum SceneItem {
+    Rect(RenderCommand),
+    Line {
+        from: (f32, f32),
+        to: (f32, f32),
+        width: f32,
+        color: [f32; 4],
+    },
+    Text {
+        x: f32,
+        y: f32,
+        font_size: f32,
+        color: [f32; 4],
+        text: String,
+    },
+}
+
+/// A scene to render for a golden test
+#[derive(Debug, Clone)]
+pub struct Scene {
+    pub width: u32,
+    pub height: u32,
+    pub clear_color: [f32; 4],
+    pub items: Vec<SceneItem>,
+}
+
+impl Scene {
+    /// Parse a scene description (see the module docs for the format)
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut scene = Scene {
+            width: 0,
+            height: 0,
+            clear_color: [1.0, 1.0, 1.0, 1.0],
+            items: Vec::new(),
+        };
+        for (number, line) in source.lines().enumerate() {
+            // Text keeps everything after its seventh argument, including '#'
+            let line = line.trim();
+            let line = if line.starts_with("text ") {
+                line
+            } else {
+                line.split('#').next().unwrap_or("").trim()
+            };
+            if line.is_empty() {
+                continue;
+            }
+            let (command, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let error = |message: &str| format!("line {}: {}", number + 1, message);
+            if command == "text" {
+                let mut parts = args.trim().splitn(8, char::is_whitespace);
+                let mut numbers = [0.0f32; 7];
+                for n in &mut numbers {
+                    *n = parts
+                        .next()
+                        .and_then(|p| p.parse().ok())
+                        .ok_or_else(|| error("text needs x y size r g b a text"))?;
+                }
+                let [x, y, font_size, r, g, b, a] = numbers;
+                scene.items.push(SceneItem::Text {
+                    x,
+                    y,
+                    font_size,
+                    color: [r, g, b, a],
+                    text: parts.next().unwrap_or("").to_string(),
+                });
+                continue;
+            }
+
+            let numbers: Vec<f32> = args
+                .split_whitespace()
+                .map(|n| n.parse().map_err(|_| error(&format!("'{}' is not a number", n))))
+                .collect::<Result<_, _>>()?;
+            match (command, numbers.as_slice()) {
+                ("size", &[w, h]) if w >= 1.0 && h >= 1.0 => {
+                    scene.width = w as u32;
+                    scene.height = h as u32;
+                }
+                ("clear", &[r, g, b, a]) => scene.clear_color = [r, g, b, a],
+                ("rect", &[x, y, width, height, r, g, b, a, ref radius @ ..]) if radius.len() <= 1 => {
+                    scene.items.push(SceneItem::Rect(RenderCommand {
+                        x,
+                        y,
+                        width,
+                        height,
+                        color_r: r,
+                        color_g: g,
+                        color_b: b,
+                        color_a: a,
+                        texture_id: 0,
+                        z_index: 0,
+                        corner_radii: [radius.first().copied().unwrap_or(0.0); 4],
+                        stacking_context: 0,
+                    }));
+                }
+                ("line", &[x0, y0, x1, y1, width, r, g, b, a]) => scene.items.push(SceneItem::Line {
+                    from: (x0, y0),
+                    to: (x1, y1),
+                    width,
+                    color: [r, g, b, a],
+                }),
+                ("size" | "clear" | "rect" | "line", _) => return Err(error(&format!("wrong arguments for {}", command))),
+                _ => return Err(error(&format!("unknown command '{}'", command))),
+            }
+        }
+        if scene.width == 0 || scene.height == 0 {
+            return Err("scene has no size".to_string());
+        }
+        Ok(scene)
+    }
+
+    /// Render the scene and return its RGBA framebuffer
+    pub fn render(&self) -> Vec<u8> {
+        let mut renderer = SoftwareRenderer::new(self.width, self.height);
+        let [r, g, b, a] = self.clear_color;
+        renderer.set_clear_color(r, g, b, a);
+        for item in &self.items {
+            match item {
+                SceneItem::Rect(cmd) => renderer.add_rect(*cmd),
+                SceneItem::Line { from, to, width, color } => {
+                    renderer.add_line(*from, *to, *width, *color, LineCap::Butt);
+                }
+                SceneItem::Text { x, y, font_size, color, text } => renderer.add_text(TextCommand {
+                    text: text.clone(),
+                    x: *x,
+                    y: *y,
+                    font_size: *font_size,
+                    color_r: color[0],
+                    color_g: color[1],
+                    color_b: color[2],
+                    color_a: color[3],
+                    font_id: 0,
+                    decoration: TextDecoration::NONE,
+                    decoration_color: None,
+                    anchor: TextAnchor::Top,
+                    spacing: TextSpacing::default(),
+                }),
+            }
+        }
+        renderer.render();
+        renderer.get_framebuffer().to_vec()
+    }
+}
+
+/// How far an image may drift from its golden before the test fails
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// Per-pixel YIQ distance treated as equal, as a fraction of the largest possible (0..1)
+    pub threshold: f32,
+    /// Fraction of pixels allowed to exceed `threshold`
+    pub max_mismatch: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            threshold: 0.1,
+            max_mismatch: 0.001,
+        }
+    }
+}
+
+/// Result of comparing two images
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ImageDiff {
+    /// Pixels whose distance exceeds the tolerance threshold
+    pub mismatched: usize,
+    /// Largest per-pixel distance, as a fraction of the largest possible
+    pub max_distance: f32,
+}
+
+/// Largest possible value of `yiq_distance` (black against white)
+const MAX_YIQ_DISTANCE: f32 = 35215.0;
+
+/// Squared YIQ distance between two RGBA pixels, blended over white
+fn yiq_distance(a: &[u8], b: &[u8]) -> f32 {
+    let blend = |px: &[u8]| {
+        let alpha = px[3] as f32 / 255.0;
+        let c = |v: u8| 255.0 + (v as f32 - 255.0) * alpha;
+        (c(px[0]), c(px[1]), c(px[2]))
+    };
+    let (r1, g1, b1) = blend(a);
+    let (r2, g2, b2) = blend(b);
+    let (dr, dg, db) = (r1 - r2, g1 - g2, b1 - b2);
+    let y = dr * 0.298_895_3 + dg * 0.586_622_5 + db * 0.114_482_23;
+    let i = dr * 0.595_978 - dg * 0.274_176_1 - db * 0.321_801_9;
+    let q = dr * 0.211_470_17 - dg * 0.522_617_1 + db * 0.311_146_94;
+    0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+}
+
+/// Compare two RGBA8 images of the same size
+pub fn compare(actual: &[u8], expected: &[u8], threshold: f32) -> ImageDiff {
+    let mut diff = ImageDiff::default();
+    for (a, b) in actual.chunks_exact(4).zip(expected.chunks_exact(4)) {
+        if a == b {
+            continue;
+        }
+        let distance = (yiq_distance(a, b) / MAX_YIQ_DISTANCE).sqrt();
+        diff.max_distance = diff.max_distance.max(distance);
+        if distance > threshold {
+            diff.mismatched += 1;
+        }
+    }
+    diff
+}
+
+/// Render `scene` and compare it against the PNG at `golden`
+///
+/// With `DOP_UPDATE_GOLDEN` set, or when the golden file does not exist yet,
+/// the rendered image is written to `golden` instead. On failure the
+/// rendered image is saved beside it with an `.actual.png` extension.
+pub fn assert_golden(scene: &Scene, golden: &FsPath, tolerance: Tolerance) -> Result<(), String> {
+    let pixels = scene.render();
+    if std::env::var_os("DOP_UPDATE_GOLDEN").is_some() || !golden.exists() {
+        return write_png(golden, &pixels, scene.width, scene.height);
+    }
+
+    let (expected, width, height) = read_png(golden)?;
+    if (width, height) != (scene.width, scene.height) {
+        return Err(format!(
+            "{}: golden is {}x{}, scene is {}x{}",
+            golden.display(),
+            width,
+            height,
+            scene.width,
+            scene.height
+        ));
+    }
+    let diff = compare(&pixels, &expected, tolerance.threshold);
+    let allowed = (tolerance.max_mismatch * (width * height) as f32) as usize;
+    if diff.mismatched <= allowed {
+        return Ok(());
+    }
+    let actual = golden.with_extension("actual.png");
+    write_png(&actual, &pixels, width, height)?;
+    Err(format!(
+        "{}: {} pixels differ (allowed {}, max distance {:.3}); output written to {}",
+        golden.display(),
+        diff.mismatched,
+        allowed,
+        diff.max_distance,
+        actual.display()
+    ))
+}
+
+fn read_png(path: &FsPath) -> Result<(Vec<u8>, u32, u32), String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).map_err(|e| e.to_string())?;
+    if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+        return Err(format!("{}: golden must be 8-bit RGBA", path.display()));
+    }
+    pixels.truncate(info.buffer_size());
+    Ok((pixels, info.width, info.height))
+}
+
+fn write_png(path: &FsPath, pixels: &[u8], width: u32, height: u32) -> Result<(), String> {
+    let file = std::fs::File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(pixels).map_err(|e| e.to_string())
+}
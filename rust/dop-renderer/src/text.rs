@@ -4,9 +4,185 @@
 
 use fontdue::layout::{CoordinateSystem, Layout, LayoutSettings, TextStyle};
 use fontdue::{Font, FontSettings, Metrics};
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::sync::{Arc, OnceLock};
+use unicode_bidi::BidiInfo;
+
+use crate::blend;
+
+/// Glyph bitmaps are cached by (font ID, glyph index, font size bits)
+pub type GlyphKey = (u32, u16, u32);
+
+/// Rasterized glyph metrics and coverage bitmap
+type CachedGlyph = Arc<(Metrics, Vec<u8>)>;
+
+/// Glyph key plus the kind of bitmap cached for it
+type GlyphCacheKey = (GlyphKey, GlyphFormat);
+
+/// Pixel layout of a cached glyph bitmap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum GlyphFormat {
+    /// One coverage byte per pixel
+    Coverage,
+    /// RGB coverage, 3 bytes per pixel
+    Subpixel,
+    /// Straight-alpha RGBA from a color font; empty if the glyph has no color version
+    Color,
+}
+
+/// Default glyph cache budget (8 MiB of coverage bitmaps)
+pub const DEFAULT_GLYPH_CACHE_LIMIT: usize = 8 * 1024 * 1024;
+
+/// Below this many missing glyphs, rasterizing serially beats spawning threads
+const PARALLEL_GLYPH_THRESHOLD: usize = 64;
+
+/// Horizontal shear of synthetic oblique glyphs (about 14 degrees)
+const OBLIQUE_SKEW: f32 = 0.25;
+
+/// Font weight at and above which a face counts as bold
+const BOLD_WEIGHT: u16 = 600;
+
+/// How glyph edges are antialiased
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TextAntialias {
+    /// One coverage value per pixel
+    #[default]
+    Grayscale = 0,
+    /// Coverage per color channel for LCDs with red on the left of each pixel
+    SubpixelRgb = 1,
+    /// Coverage per color channel for LCDs with blue on the left of each pixel
+    SubpixelBgr = 2,
+}
+
+impl TextAntialias {
+    /// Convert a raw FFI value into a TextAntialias (unknown values map to Grayscale)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => TextAntialias::SubpixelRgb,
+            2 => TextAntialias::SubpixelBgr,
+            _ => TextAntialias::Grayscale,
+        }
+    }
+}
+
+/// Stage that turns text into positioned glyphs
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ShapingBackend {
+    /// fontdue's layout: one nominal glyph per character, with kerning
+    #[default]
+    Fontdue = 0,
+    /// rustybuzz: GSUB/GPOS ligatures, marks and contextual forms (needs the `harfbuzz` feature)
+    HarfBuzz = 1,
+}
+
+impl ShapingBackend {
+    /// Convert a raw FFI value into a ShapingBackend (unknown values map to Fontdue)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => ShapingBackend::HarfBuzz,
+            _ => ShapingBackend::Fontdue,
+        }
+    }
+}
+
+/// Styles faked for a family that lacks a real bold or italic face
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Synthesis {
+    /// Thicken strokes by smearing coverage to the right
+    pub bold: bool,
+    /// Slant glyphs by shearing rows around the baseline
+    pub oblique: bool,
+}
+
+impl Synthesis {
+    pub fn is_none(self) -> bool {
+        !self.bold && !self.oblique
+    }
+
+    /// Horizontal offset of a synthesized bitmap from the original glyph's left edge
+    fn x_offset(self, metrics: &Metrics) -> f32 {
+        if self.oblique {
+            (OBLIQUE_SKEW * (metrics.ymin as f32 + 0.5)).floor()
+        } else {
+            0.0
+        }
+    }
+
+    /// Apply the faked styles to a rasterized glyph
+    ///
+    /// The glyph keeps its advance, so synthetic bold text is set as tightly
+    /// as the regular face.
+    fn apply(self, (mut metrics, mut bitmap): (Metrics, Vec<u8>), font_size: f32) -> (Metrics, Vec<u8>) {
+        if self.bold && metrics.width > 0 {
+            let offset = (font_size / 24.0).round().max(1.0) as usize;
+            let (w, h) = (metrics.width, metrics.height);
+            let width = w + offset;
+            let mut out = vec![0u8; width * h];
+            for y in 0..h {
+                for x in 0..width {
+                    let from = x.saturating_sub(offset);
+                    let to = x.min(w - 1);
+                    out[y * width + x] = (from..=to).map(|sx| bitmap[y * w + sx]).max().unwrap_or(0);
+                }
+            }
+            metrics.width = width;
+            bitmap = out;
+        }
+        if self.oblique && metrics.width > 0 {
+            let (w, h) = (metrics.width, metrics.height);
+            // Shift each row by its height above the baseline, measured at the row's center
+            let top = (h as i32 + metrics.ymin) as f32;
+            let shift = |y: usize| OBLIQUE_SKEW * (top - y as f32 - 0.5);
+            let left = self.x_offset(&metrics);
+            let width = w + (shift(0).ceil() - left) as usize + 1;
+            let mut out = vec![0f32; width * h];
+            for y in 0..h {
+                let s = shift(y) - left;
+                let (i, f) = (s.floor() as usize, s.fract());
+                for x in 0..w {
+                    let v = bitmap[y * w + x] as f32;
+                    out[y * width + x + i] += v * (1.0 - f);
+                    out[y * width + x + i + 1] += v * f;
+                }
+            }
+            metrics.width = width;
+            bitmap = out.into_iter().map(|v| v.round().min(255.0) as u8).collect();
+        }
+        (metrics, bitmap)
+    }
+}
+
+/// A face registered under a family name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FamilyFace {
+    font_id: u32,
+    weight: u16,
+    italic: bool,
+}
+
+/// How far a face's weight is from the desired one, following the CSS font matching order
+///
+/// Desired weights of 400-500 try heavier weights up to 500, then lighter,
+/// then heavier; lighter desired weights look lighter first, bolder ones
+/// heavier first.
+fn weight_rank(desired: u16, weight: u16) -> (u8, u16) {
+    if weight == desired {
+        return (0, 0);
+    }
+    let distance = weight.abs_diff(desired);
+    let preferred = match desired {
+        400..=500 if weight > desired && weight <= 500 => return (1, distance),
+        400..=500 => weight < desired,
+        0..=399 => weight < desired,
+        _ => weight > desired,
+    };
+    (if preferred { 2 } else { 3 }, distance)
+}
 
 /// A text rendering command
 #[repr(C)]
@@ -21,6 +197,7 @@ pub struct TextCommand {
     pub color_b: f32,
     pub color_a: f32,
     pub font_id: u32,
+    pub spacing: TextSpacing,
 }
 
 impl Default for TextCommand {
@@ -35,10 +212,203 @@ impl Default for TextCommand {
             color_b: 0.0,
             color_a: 1.0,
             font_id: 0,
+            spacing: TextSpacing::default(),
+        }
+    }
+}
+
+/// Extra space between characters and words, and the height of lines
+///
+/// Mirrors CSS `letter-spacing`, `word-spacing` and `line-height`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TextSpacing {
+    /// Added after every character; negative values tighten text
+    pub letter: f32,
+    /// Added after every space, on top of `letter`
+    pub word: f32,
+    /// Distance between lines, None for 1.2 times the font size
+    pub line_height: Option<f32>,
+}
+
+impl TextSpacing {
+    /// Same spacing with every length multiplied by `scale`
+    pub fn scaled(self, scale: f32) -> Self {
+        Self {
+            letter: self.letter * scale,
+            word: self.word * scale,
+            line_height: self.line_height.map(|h| h * scale),
+        }
+    }
+
+    /// Extra advance after `c`
+    fn extra(&self, c: char) -> f32 {
+        if c == ' ' {
+            self.letter + self.word
+        } else {
+            self.letter
+        }
+    }
+
+    fn line_height(&self, font_size: f32) -> f32 {
+        self.line_height.unwrap_or(font_size * 1.2)
+    }
+}
+
+impl std::hash::Hash for TextSpacing {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.letter.to_bits().hash(state);
+        self.word.to_bits().hash(state);
+        self.line_height.map(f32::to_bits).hash(state);
+    }
+}
+
+/// Which point of a text run its `y` coordinate refers to
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TextAnchor {
+    /// Top edge of the run's bitmap
+    #[default]
+    Top = 0,
+    /// Baseline of the first line
+    Baseline = 1,
+    /// Bottom edge of the last line
+    Bottom = 2,
+}
+
+impl TextAnchor {
+    /// Convert a raw FFI value into a TextAnchor (unknown values map to Top)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => TextAnchor::Baseline,
+            2 => TextAnchor::Bottom,
+            _ => TextAnchor::Top,
+        }
+    }
+}
+
+/// Vertical metrics of a font at a size, in pixels
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FontMetrics {
+    /// Distance from the baseline up to the top of the tallest glyphs
+    pub ascent: f32,
+    /// Distance from the baseline down to the bottom of the deepest glyphs (positive)
+    pub descent: f32,
+    /// Extra space the font asks for between lines
+    pub line_gap: f32,
+}
+
+/// Lines drawn along a text run, combined as bit flags
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TextDecoration(pub u8);
+
+impl TextDecoration {
+    pub const NONE: Self = Self(0);
+    pub const UNDERLINE: Self = Self(1);
+    pub const OVERLINE: Self = Self(2);
+    pub const LINE_THROUGH: Self = Self(4);
+
+    /// Convert a raw FFI value, dropping unknown flags
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits & 0b111)
+    }
+
+    /// Check if every flag of `other` is set
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl std::ops::BitOr for TextDecoration {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+/// Placement of decoration lines in em units
+///
+/// Offsets locate the top of each line relative to the baseline, positive
+/// downwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecorationMetrics {
+    pub underline_offset: f32,
+    pub underline_thickness: f32,
+    pub strikeout_offset: f32,
+    pub strikeout_thickness: f32,
+    /// Top of the font's ascender, where overlines go
+    pub ascent: f32,
+}
+
+impl Default for DecorationMetrics {
+    /// Typical Latin values, used when a font has no post or OS/2 table
+    fn default() -> Self {
+        Self {
+            underline_offset: 0.1,
+            underline_thickness: 0.05,
+            strikeout_offset: -0.3,
+            strikeout_thickness: 0.05,
+            ascent: -0.8,
         }
     }
 }
 
+impl DecorationMetrics {
+    /// Read the metrics from font file data
+    fn from_font_data(data: &[u8], index: u32) -> Option<Self> {
+        let face = ttf_parser::Face::parse(data, index).ok()?;
+        let em = face.units_per_em() as f32;
+        let default = Self::default();
+        let underline = face.underline_metrics();
+        let strikeout = face.strikeout_metrics();
+        Some(Self {
+            underline_offset: underline.map_or(default.underline_offset, |m| -(m.position as f32) / em),
+            underline_thickness: underline.map_or(default.underline_thickness, |m| m.thickness as f32 / em),
+            strikeout_offset: strikeout.map_or(default.strikeout_offset, |m| -(m.position as f32) / em),
+            strikeout_thickness: strikeout.map_or(default.strikeout_thickness, |m| m.thickness as f32 / em),
+            ascent: -(face.ascender() as f32) / em,
+        })
+    }
+}
+
+/// A styled run of a rich-text paragraph
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSpan {
+    pub text: String,
+    pub font_id: u32,
+    pub font_size: f32,
+    /// RGBA in sRGB
+    pub color: [f32; 4],
+    pub decoration: TextDecoration,
+}
+
+/// The part of a span that falls on one line of a laid-out paragraph
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanFragment {
+    /// Index of the span in the paragraph
+    pub span: usize,
+    /// Byte range of the fragment in the span's text
+    pub range: Range<usize>,
+    /// Pen position where the fragment starts, relative to the paragraph's top-left
+    pub x: f32,
+    /// Baseline of the fragment's line, relative to the paragraph's top
+    pub baseline: f32,
+}
+
+/// Spans shaped together by `FontManager::layout_paragraph`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParagraphLayout {
+    pub fragments: Vec<SpanFragment>,
+    pub width: f32,
+    pub height: f32,
+    pub line_count: u32,
+}
+
 /// Text shaping result
 #[derive(Debug, Clone)]
 pub struct ShapedText {
@@ -46,6 +416,214 @@ pub struct ShapedText {
     pub height: f32,
     pub line_count: u32,
     pub glyphs: Vec<ShapedGlyph>,
+    /// Line breaks, filled by `TextShaper::shape_paragraph`
+    pub lines: Vec<ShapedLine>,
+    /// Glyph positions mapped to source text, filled by `TextShaper::shape_paragraph`
+    ///
+    /// Each line's clusters are in visual order, left to right.
+    pub clusters: Vec<GlyphCluster>,
+    /// Base direction of the paragraph, from its first strong character
+    pub direction: TextDirection,
+}
+
+/// Base direction of a paragraph
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TextDirection {
+    #[default]
+    Ltr = 0,
+    Rtl = 1,
+}
+
+impl ShapedText {
+    /// Byte offset of the caret position nearest to (x, y), relative to the paragraph
+    ///
+    /// Points above the first or below the last line hit that line; points
+    /// left or right of a line hit its visual edge. Within a glyph, the caret
+    /// goes on the side of the half that was hit: before it on the left half
+    /// of left-to-right text, after it on the left half of right-to-left text.
+    pub fn hit_test(&self, x: f32, y: f32) -> usize {
+        let Some(line) = self
+            .lines
+            .iter()
+            .find(|line| y < line.y + line.height)
+            .or(self.lines.last())
+        else {
+            return 0;
+        };
+        let clusters = &self.clusters[line.first_cluster..line.first_cluster + line.cluster_count];
+        let Some(hit) = clusters
+            .iter()
+            .find(|c| x < c.x + c.advance)
+            .or(clusters.last())
+        else {
+            return line.start;
+        };
+        let left_half = x < hit.x + hit.advance / 2.0;
+        if left_half != hit.rtl {
+            hit.byte_offset
+        } else {
+            hit.byte_offset + hit.byte_len
+        }
+    }
+
+    /// Caret position for a byte offset as (x, top, height), relative to the paragraph
+    ///
+    /// An offset where a line wraps is placed at the end of the earlier line
+    /// (trailing edge); the first offset of the next line starts it. Offsets
+    /// inside a character snap to its start, offsets past the text to its end.
+    /// In right-to-left runs the caret sits on a character's right side.
+    pub fn offset_to_position(&self, offset: usize) -> (f32, f32, f32) {
+        let Some(line) = self
+            .lines
+            .iter()
+            .rev()
+            .find(|line| line.start <= offset)
+            .or(self.lines.first())
+        else {
+            return (0.0, 0.0, self.height);
+        };
+        let clusters = &self.clusters[line.first_cluster..line.first_cluster + line.cluster_count];
+        let x = match clusters
+            .iter()
+            .find(|c| c.byte_offset <= offset && offset < c.byte_offset + c.byte_len)
+        {
+            Some(c) => c.leading_x(),
+            // Past the line's last character
+            None => clusters
+                .iter()
+                .max_by_key(|c| c.byte_offset)
+                .map_or(0.0, |c| c.trailing_x()),
+        };
+        (x, line.y, line.height)
+    }
+
+    /// Highlight rectangles for a byte range as `[x, y, width, height]`, relative to the paragraph
+    ///
+    /// Each line gives one rectangle per visually contiguous run of selected
+    /// characters, so a selection crossing a bidi boundary can split into
+    /// several. Characters partly inside the range are selected whole.
+    pub fn selection_rects(&self, bytes: Range<usize>) -> Vec<[f32; 4]> {
+        let mut rects = Vec::new();
+        for line in &self.lines {
+            let clusters = &self.clusters[line.first_cluster..line.first_cluster + line.cluster_count];
+            let mut run: Option<(f32, f32)> = None;
+            for c in clusters {
+                if c.byte_offset < bytes.end && c.byte_offset + c.byte_len > bytes.start {
+                    let left = run.map_or(c.x, |(left, _)| left);
+                    run = Some((left, c.x + c.advance));
+                } else if let Some((left, right)) = run.take() {
+                    rects.push([left, line.y, right - left, line.height]);
+                }
+            }
+            if let Some((left, right)) = run {
+                rects.push([left, line.y, right - left, line.height]);
+            }
+        }
+        rects
+    }
+}
+
+/// A line of a shaped paragraph
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ShapedLine {
+    /// Byte range of the line in the paragraph text (the space it broke at is excluded)
+    pub start: usize,
+    pub end: usize,
+    /// Top of the line relative to the paragraph
+    pub y: f32,
+    pub height: f32,
+    pub width: f32,
+    /// Index of the line's first entry in `ShapedText::clusters`
+    pub first_cluster: usize,
+    pub cluster_count: usize,
+    /// Mark drawn after the line's text, included in `width`
+    pub suffix: LineSuffix,
+}
+
+/// Mark drawn after a line that the text itself doesn't contain
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LineSuffix {
+    #[default]
+    None = 0,
+    /// The line broke at a soft hyphen
+    Hyphen = 1,
+    /// Text past the line was cut off by `WrapOptions::max_lines`
+    Ellipsis = 2,
+}
+
+/// Where `TextShaper` may break lines that overflow the wrap width
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum WrapMode {
+    /// Break at spaces; words longer than a line overflow it
+    #[default]
+    Word = 0,
+    /// Break between any two characters
+    Char = 1,
+    /// Break at spaces, and inside words that don't fit on a line of their own
+    BreakWord = 2,
+    /// Keep the paragraph on one line
+    NoWrap = 3,
+}
+
+impl WrapMode {
+    /// Convert a raw FFI value into a WrapMode (unknown values map to Word)
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => WrapMode::Char,
+            2 => WrapMode::BreakWord,
+            3 => WrapMode::NoWrap,
+            _ => WrapMode::Word,
+        }
+    }
+}
+
+/// Line breaking options for `TextShaper::shape_paragraph_with`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct WrapOptions {
+    pub mode: WrapMode,
+    /// Also break at soft hyphens (U+00AD), ending the line with a hyphen
+    pub hyphens: bool,
+    /// Lines kept before the rest is cut off with an ellipsis (0 = no limit)
+    ///
+    /// A kept last line that overflows the width is ellipsized as well.
+    pub max_lines: u32,
+}
+
+/// A glyph of a shaped paragraph and the bytes of text it was shaped from
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GlyphCluster {
+    /// Byte offset of the cluster in the paragraph text
+    pub byte_offset: usize,
+    pub byte_len: usize,
+    pub line: u32,
+    /// Pen position relative to the line's left edge
+    pub x: f32,
+    pub advance: f32,
+    /// Part of a right-to-left run: the text before it is on its right
+    pub rtl: bool,
+}
+
+impl GlyphCluster {
+    /// Caret x before the cluster's text
+    fn leading_x(&self) -> f32 {
+        if self.rtl {
+            self.x + self.advance
+        } else {
+            self.x
+        }
+    }
+
+    /// Caret x after the cluster's text
+    fn trailing_x(&self) -> f32 {
+        if self.rtl {
+            self.x
+        } else {
+            self.x + self.advance
+        }
+    }
 }
 
 /// A shaped glyph
@@ -58,6 +636,173 @@ pub struct ShapedGlyph {
     pub bitmap: Vec<u8>,
 }
 
+/// A glyph placed in a laid-out text run
+#[derive(Debug, Clone)]
+pub struct RunGlyph {
+    /// Glyph cache key
+    pub key: GlyphKey,
+    /// Top-left of the glyph bitmap relative to the run origin
+    pub x: f32,
+    pub y: f32,
+    pub width: u32,
+    pub height: u32,
+    glyph: CachedGlyph,
+    color: Option<CachedGlyph>,
+}
+
+impl RunGlyph {
+    /// Coverage bitmap, `width * height` bytes
+    ///
+    /// For a color glyph this is its alpha channel.
+    pub fn coverage(&self) -> &[u8] {
+        &self.glyph.1
+    }
+
+    /// Straight-alpha RGBA pixels of a color glyph, `width * height * 4` bytes
+    pub fn color(&self) -> Option<&[u8]> {
+        self.color.as_ref().map(|glyph| glyph.1.as_slice())
+    }
+}
+
+/// One line of a text run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunLine {
+    /// Baseline relative to the run's top edge
+    pub baseline: f32,
+    pub width: f32,
+}
+
+/// A glyph placed on one line by the shaping stage
+struct LineGlyph {
+    index: u16,
+    /// First character the glyph was shaped from
+    ch: char,
+    /// Pen position of the glyph origin from the line start
+    x: f32,
+    /// Shift up from the baseline, for marks
+    rise: f32,
+    advance: f32,
+}
+
+/// Text laid out into positioned glyphs by `FontManager::layout_run`
+#[derive(Debug, Clone, Default)]
+pub struct TextRun {
+    pub glyphs: Vec<RunGlyph>,
+    pub lines: Vec<RunLine>,
+    /// Size of the run's bitmap as drawn by `rasterize_text`
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Running glyph work counters kept by a `FontManager`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GlyphCounters {
+    /// Glyph bitmaps rasterized (glyph cache misses)
+    pub rasterized: u64,
+    /// Glyph metrics served from the cache
+    pub metrics_hits: u64,
+    /// Glyph metrics computed and inserted into the cache
+    pub metrics_misses: u64,
+    /// Glyph bitmaps evicted from the cache to stay within its budget
+    pub evicted: u64,
+}
+
+struct GlyphEntry {
+    glyph: CachedGlyph,
+    last_used: u64,
+}
+
+/// Glyph bitmaps with least-recently-used eviction under a memory budget
+struct GlyphCache {
+    entries: HashMap<GlyphCacheKey, GlyphEntry>,
+    clock: u64,
+    used_bytes: usize,
+    budget_bytes: usize,
+}
+
+impl GlyphCache {
+    fn new(budget_bytes: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            clock: 0,
+            used_bytes: 0,
+            budget_bytes,
+        }
+    }
+
+    fn byte_size(glyph: &CachedGlyph) -> usize {
+        std::mem::size_of::<GlyphEntry>() + std::mem::size_of::<Metrics>() + glyph.1.len()
+    }
+
+    /// Get a glyph and mark it as recently used
+    fn get(&mut self, key: &GlyphCacheKey) -> Option<CachedGlyph> {
+        let entry = self.entries.get_mut(key)?;
+        self.clock += 1;
+        entry.last_used = self.clock;
+        Some(entry.glyph.clone())
+    }
+
+    /// Insert glyphs as the most recently used, then evict older glyphs to fit the budget
+    ///
+    /// Glyphs past the point where the batch alone fills the budget are not
+    /// cached, so an oversized batch (a first render of a large CJK page)
+    /// never evicts its own glyphs before they are drawn.
+    fn insert(&mut self, glyphs: Vec<(GlyphCacheKey, CachedGlyph)>) -> usize {
+        let mut batch_bytes = 0;
+        for (key, glyph) in glyphs {
+            batch_bytes += Self::byte_size(&glyph);
+            if batch_bytes > self.budget_bytes {
+                break;
+            }
+            self.clock += 1;
+            self.used_bytes += Self::byte_size(&glyph);
+            let entry = GlyphEntry {
+                glyph,
+                last_used: self.clock,
+            };
+            if let Some(old) = self.entries.insert(key, entry) {
+                self.used_bytes -= Self::byte_size(&old.glyph);
+            }
+        }
+        self.evict()
+    }
+
+    /// Evict least-recently-used glyphs once over budget, returning how many
+    ///
+    /// Evicts down to three quarters of the budget so a full cache doesn't
+    /// sort its entries again for every new glyph.
+    fn evict(&mut self) -> usize {
+        if self.used_bytes <= self.budget_bytes {
+            return 0;
+        }
+        let target = self.budget_bytes / 4 * 3;
+        let mut by_age: Vec<(u64, GlyphCacheKey)> = self.entries.iter().map(|(key, e)| (e.last_used, *key)).collect();
+        by_age.sort_unstable();
+        let mut evicted = 0;
+        for (_, key) in by_age {
+            if self.used_bytes <= target {
+                break;
+            }
+            if let Some(entry) = self.entries.remove(&key) {
+                self.used_bytes -= Self::byte_size(&entry.glyph);
+                evicted += 1;
+            }
+        }
+        log::debug!("glyph cache: evicted {} glyphs", evicted);
+        evicted
+    }
+
+    fn set_budget(&mut self, budget_bytes: usize) -> usize {
+        self.budget_bytes = budget_bytes;
+        self.evict()
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.used_bytes = 0;
+    }
+}
+
 /// Font manager for loading and caching fonts
 pub struct FontManager {
     fonts: HashMap<u32, Arc<Font>>,
@@ -65,6 +810,22 @@ pub struct FontManager {
     next_id: u32,
     // Cache glyph metrics to avoid rasterizing when only metrics are needed
     metrics_cache: RefCell<HashMap<u64, Metrics>>,
+    counters: Cell<GlyphCounters>,
+    glyph_cache: RefCell<GlyphCache>,
+    decoration_metrics: HashMap<u32, DecorationMetrics>,
+    /// Faces by lowercase family name
+    families: HashMap<String, Vec<FamilyFace>>,
+    /// Styles faked by synthetic font IDs, which share the base face's font
+    synthesis: HashMap<u32, Synthesis>,
+    synthetic_ids: HashMap<(u32, Synthesis), u32>,
+    /// Font IDs of faces loaded from the system font database
+    system_faces: HashMap<fontdb::ID, u32>,
+    shaping: ShapingBackend,
+    /// Font file bytes and face index of fonts with color glyph tables
+    color_faces: HashMap<u32, (Arc<[u8]>, u32)>,
+    /// Font file bytes and face index by font ID, which rustybuzz shapes from
+    #[cfg(feature = "harfbuzz")]
+    font_data: HashMap<u32, (Arc<[u8]>, u32)>,
 }
 
 impl Default for FontManager {
@@ -80,6 +841,17 @@ impl FontManager {
             default_font: None,
             next_id: 1,
             metrics_cache: RefCell::new(HashMap::new()),
+            counters: Cell::new(GlyphCounters::default()),
+            glyph_cache: RefCell::new(GlyphCache::new(DEFAULT_GLYPH_CACHE_LIMIT)),
+            decoration_metrics: HashMap::new(),
+            families: HashMap::new(),
+            synthesis: HashMap::new(),
+            synthetic_ids: HashMap::new(),
+            system_faces: HashMap::new(),
+            shaping: ShapingBackend::default(),
+            color_faces: HashMap::new(),
+            #[cfg(feature = "harfbuzz")]
+            font_data: HashMap::new(),
         };
 
         // Load default embedded font
@@ -95,19 +867,58 @@ impl FontManager {
 
         for path in font_paths {
             if let Ok(data) = std::fs::read(&path) {
-                if let Ok(font) = Font::from_bytes(data, FontSettings::default()) {
-                    let font = Arc::new(font);
-                    self.default_font = Some(font.clone());
-                    self.fonts.insert(0, font);
+                if self.set_default_font(&data, 0) {
                     return;
                 }
             }
         }
 
+        // Distros lay fonts out differently; ask the system font database
+        let db = system_font_db();
+        let regular = db
+            .query(&fontdb::Query {
+                families: &[fontdb::Family::SansSerif],
+                ..fontdb::Query::default()
+            })
+            .or_else(|| {
+                db.faces()
+                    .find(|f| f.style == fontdb::Style::Normal && f.weight == fontdb::Weight::NORMAL)
+                    .map(|f| f.id)
+            });
+        if let Some(id) = regular {
+            if db.with_face_data(id, |data, index| self.set_default_font(data, index)) == Some(true) {
+                return;
+            }
+        }
+
         // If no system font found, we'll work without a default font
         log::warn!("No system font found for default font loading");
     }
 
+    /// Use a font face as font 0
+    fn set_default_font(&mut self, data: &[u8], index: u32) -> bool {
+        let settings = FontSettings {
+            collection_index: index,
+            ..FontSettings::default()
+        };
+        let Ok(font) = Font::from_bytes(data, settings) else {
+            return false;
+        };
+        let font = Arc::new(font);
+        self.default_font = Some(font.clone());
+        self.fonts.insert(0, font);
+        let metrics = DecorationMetrics::from_font_data(data, index);
+        self.decoration_metrics.insert(0, metrics.unwrap_or_default());
+        #[cfg(feature = "harfbuzz")]
+        self.font_data.insert(0, (Arc::from(data), index));
+        if crate::color_font::has_color_tables(data, index) {
+            self.color_faces.insert(0, (Arc::from(data), index));
+        } else {
+            self.color_faces.remove(&0);
+        }
+        true
+    }
+
     /// Load a font from file
     pub fn load_font(&mut self, path: &str) -> Option<u32> {
         match std::fs::read(path) {
@@ -119,17 +930,49 @@ impl FontManager {
         }
     }
 
-    /// Load a font from bytes
+    /// Load a font from bytes (TrueType, OpenType or WOFF)
     pub fn load_font_from_bytes(&mut self, data: &[u8]) -> Option<u32> {
-        match Font::from_bytes(data.to_vec(), FontSettings::default()) {
+        self.load_font_face(data, 0)
+    }
+
+    /// Load one face of a font file or collection (.ttc) from bytes
+    fn load_font_face(&mut self, data: &[u8], index: u32) -> Option<u32> {
+        let data = crate::woff::to_sfnt(data)?;
+        let data = &*data;
+        let settings = FontSettings {
+            collection_index: index,
+            ..FontSettings::default()
+        };
+        match Font::from_bytes(data, settings) {
             Ok(font) => {
                 let id = self.next_id;
                 self.next_id += 1;
                 let font = Arc::new(font);
+                let metrics = DecorationMetrics::from_font_data(data, index).unwrap_or_default();
+                let color = crate::color_font::has_color_tables(data, index);
+                #[cfg(feature = "harfbuzz")]
+                let data: (Arc<[u8]>, u32) = (Arc::from(data), index);
+                #[cfg(not(feature = "harfbuzz"))]
+                let data: (Arc<[u8]>, u32) = match color {
+                    true => (Arc::from(data), index),
+                    false => (Arc::from([]), index),
+                };
                 if self.default_font.is_none() {
                     self.default_font = Some(font.clone());
+                    self.decoration_metrics.insert(0, metrics);
+                    #[cfg(feature = "harfbuzz")]
+                    self.font_data.insert(0, data.clone());
+                    if color {
+                        self.color_faces.insert(0, data.clone());
+                    }
                 }
                 self.fonts.insert(id, font);
+                self.decoration_metrics.insert(id, metrics);
+                if color {
+                    self.color_faces.insert(id, data.clone());
+                }
+                #[cfg(feature = "harfbuzz")]
+                self.font_data.insert(id, data);
                 Some(id)
             }
             Err(e) => {
@@ -139,6 +982,136 @@ impl FontManager {
         }
     }
 
+    /// Load a font file as one face of a family (weight 100-900, 400 = regular)
+    pub fn load_font_family(&mut self, family: &str, weight: u16, italic: bool, path: &str) -> Option<u32> {
+        let id = self.load_font(path)?;
+        self.add_font_to_family(family, weight, italic, id);
+        Some(id)
+    }
+
+    /// Load an installed font by family name, e.g. "Noto Sans"
+    ///
+    /// Picks the installed face closest to `weight` and style, registers it
+    /// with its family for `resolve_font`, and returns its font ID. Family
+    /// names match case-insensitively. Loading the same face again returns
+    /// the same ID.
+    pub fn load_system_font(&mut self, family: &str, weight: u16, italic: bool) -> Option<u32> {
+        let db = system_font_db();
+        // fontdb compares family names exactly
+        let name = db
+            .faces()
+            .flat_map(|f| &f.families)
+            .map(|(name, _)| name.as_str())
+            .find(|name| name.eq_ignore_ascii_case(family))?;
+        let id = db.query(&fontdb::Query {
+            families: &[fontdb::Family::Name(name)],
+            weight: fontdb::Weight(weight),
+            style: if italic { fontdb::Style::Italic } else { fontdb::Style::Normal },
+            ..fontdb::Query::default()
+        })?;
+        if let Some(&font_id) = self.system_faces.get(&id) {
+            return Some(font_id);
+        }
+        let face = db.face(id)?;
+        let face_italic = face.style != fontdb::Style::Normal;
+        let font_id = db.with_face_data(id, |data, index| self.load_font_face(data, index))??;
+        self.add_font_to_family(name, face.weight.0, face_italic, font_id);
+        self.system_faces.insert(id, font_id);
+        Some(font_id)
+    }
+
+    /// Register a loaded font as one face of a family
+    ///
+    /// Family names match case-insensitively. Returns false if the font ID is unknown.
+    pub fn add_font_to_family(&mut self, family: &str, weight: u16, italic: bool, font_id: u32) -> bool {
+        if !self.fonts.contains_key(&font_id) {
+            return false;
+        }
+        let faces = self.families.entry(family.to_lowercase()).or_default();
+        faces.retain(|f| f.weight != weight || f.italic != italic);
+        faces.push(FamilyFace {
+            font_id,
+            weight,
+            italic,
+        });
+        true
+    }
+
+    /// Find the font ID that best renders a family at a weight and style
+    ///
+    /// Faces are matched like CSS: the requested style first, then the
+    /// nearest weight. When the family has no bold (weight >= 600) or italic
+    /// face for a bold or italic request, the closest face is emboldened or
+    /// slanted under a synthetic font ID. None if the family is unknown.
+    pub fn resolve_font(&mut self, family: &str, weight: u16, italic: bool) -> Option<u32> {
+        let faces = self.families.get(&family.to_lowercase())?;
+        let has_style = faces.iter().any(|f| f.italic == italic);
+        let face = faces
+            .iter()
+            .filter(|f| !has_style || f.italic == italic)
+            .min_by_key(|f| weight_rank(weight, f.weight))
+            .copied()?;
+        let synthesis = Synthesis {
+            bold: weight >= BOLD_WEIGHT && face.weight < BOLD_WEIGHT,
+            oblique: italic && !face.italic,
+        };
+        if synthesis.is_none() {
+            return Some(face.font_id);
+        }
+        Some(self.synthetic_font(face.font_id, synthesis))
+    }
+
+    /// ID of `font_id` with faked styles, registered on first use
+    fn synthetic_font(&mut self, font_id: u32, synthesis: Synthesis) -> u32 {
+        if let Some(&id) = self.synthetic_ids.get(&(font_id, synthesis)) {
+            return id;
+        }
+        let font = self.fonts[&font_id].clone();
+        let id = self.next_id;
+        self.next_id += 1;
+        self.fonts.insert(id, font);
+        if let Some(&metrics) = self.decoration_metrics.get(&font_id) {
+            self.decoration_metrics.insert(id, metrics);
+        }
+        #[cfg(feature = "harfbuzz")]
+        if let Some(data) = self.font_data.get(&font_id).cloned() {
+            self.font_data.insert(id, data);
+        }
+        if let Some(data) = self.color_faces.get(&font_id).cloned() {
+            self.color_faces.insert(id, data);
+        }
+        self.synthesis.insert(id, synthesis);
+        self.synthetic_ids.insert((font_id, synthesis), id);
+        id
+    }
+
+    /// Styles faked when drawing a font ID (none for real faces)
+    pub fn synthesis(&self, font_id: u32) -> Synthesis {
+        self.synthesis.get(&font_id).copied().unwrap_or_default()
+    }
+
+    /// Fonts held by this manager with their IDs, for sharing with another manager
+    pub fn shared_fonts(&self) -> Vec<(u32, Arc<Font>)> {
+        self.fonts.iter().map(|(id, font)| (*id, font.clone())).collect()
+    }
+
+    /// Register a font loaded by another manager under the same ID
+    ///
+    /// Replacing a different font under an existing ID drops the glyph caches.
+    pub fn insert_font(&mut self, id: u32, font: Arc<Font>) {
+        if self.fonts.get(&id).is_some_and(|f| Arc::ptr_eq(f, &font)) {
+            return;
+        }
+        if self.fonts.insert(id, font.clone()).is_some() {
+            self.metrics_cache.borrow_mut().clear();
+            self.glyph_cache.borrow_mut().clear();
+        }
+        if id == 0 || self.default_font.is_none() {
+            self.default_font = Some(font);
+        }
+        self.next_id = self.next_id.max(id + 1);
+    }
+
     /// Get a font by ID (0 = default)
     pub fn get_font(&self, id: u32) -> Option<&Arc<Font>> {
         if id == 0 {
@@ -148,6 +1121,45 @@ impl FontManager {
         }
     }
 
+    /// Underline, strikeout and overline placement of a font (0 = default)
+    pub fn decoration_metrics(&self, font_id: u32) -> DecorationMetrics {
+        self.decoration_metrics.get(&font_id).copied().unwrap_or_default()
+    }
+
+    /// Decoration lines of a laid-out run as (x, y, width, height) relative to its top-left corner
+    ///
+    /// Every line of the run is decorated across its full width. Lines are
+    /// snapped to whole pixels and at least one pixel thick, so they stay
+    /// crisp at any size.
+    pub fn decoration_rects(
+        &self,
+        run: &TextRun,
+        font_size: f32,
+        font_id: u32,
+        decoration: TextDecoration,
+    ) -> Vec<(f32, f32, f32, f32)> {
+        let m = self.decoration_metrics(font_id);
+        let mut offsets = Vec::new();
+        if decoration.contains(TextDecoration::UNDERLINE) {
+            offsets.push((m.underline_offset, m.underline_thickness));
+        }
+        if decoration.contains(TextDecoration::OVERLINE) {
+            offsets.push((m.ascent, m.underline_thickness));
+        }
+        if decoration.contains(TextDecoration::LINE_THROUGH) {
+            offsets.push((m.strikeout_offset, m.strikeout_thickness));
+        }
+        run.lines
+            .iter()
+            .flat_map(|line| {
+                offsets.iter().map(move |&(offset, thickness)| {
+                    let thickness = (thickness * font_size).round().max(1.0);
+                    (0.0, (line.baseline + offset * font_size).round(), line.width, thickness)
+                })
+            })
+            .collect()
+    }
+
     /// Internal: compute a cache key for a glyph metrics lookup
     fn metrics_cache_key(ch: char, font_size: f32, font_id: u32) -> u64 {
         use std::collections::hash_map::DefaultHasher;
@@ -166,9 +1178,14 @@ impl FontManager {
     fn get_glyph_metrics(&self, font: &Font, ch: char, font_size: f32, font_id: u32) -> Metrics {
         let key = Self::metrics_cache_key(ch, font_size, font_id);
 
+        let mut counters = self.counters.get();
         if let Some(m) = self.metrics_cache.borrow().get(&key) {
+            counters.metrics_hits += 1;
+            self.counters.set(counters);
             return *m;
         }
+        counters.metrics_misses += 1;
+        self.counters.set(counters);
 
         // fontdue provides `metrics` that does not produce a bitmap
         let m = font.metrics(ch, font_size);
@@ -177,7 +1194,7 @@ impl FontManager {
     }
 
     /// Measure text width and height
-    pub fn measure_text(&self, text: &str, font_size: f32, font_id: u32) -> (f32, f32) {
+    pub fn measure_text(&self, text: &str, font_size: f32, font_id: u32, spacing: TextSpacing) -> (f32, f32) {
         let font = match self.get_font(font_id) {
             Some(f) => f,
             None => return (text.len() as f32 * font_size * 0.6, font_size),
@@ -188,14 +1205,16 @@ impl FontManager {
         let mut max_width = 0.0f32;
         let mut total_height = 0.0f32;
 
-        let line_height = font_size * 1.2;
+        let line_height = spacing.line_height(font_size);
 
         for line in lines {
-            let mut line_width = 0.0f32;
-            for c in line.chars() {
-                let metrics = self.get_glyph_metrics(font, c, font_size, font_id);
-                line_width += metrics.advance_width;
-            }
+            let line_width = match self.harfbuzz_glyphs(font_id, line, font_size) {
+                Some(glyphs) => glyphs.iter().map(|g| g.advance + spacing.extra(g.ch)).sum(),
+                None => line
+                    .chars()
+                    .map(|c| self.get_glyph_metrics(font, c, font_size, font_id).advance_width + spacing.extra(c))
+                    .sum(),
+            };
             max_width = max_width.max(line_width);
             total_height += line_height;
         }
@@ -204,7 +1223,7 @@ impl FontManager {
     }
 
     /// Shape and rasterize text
-    pub fn shape_text(&self, text: &str, font_size: f32, font_id: u32) -> ShapedText {
+    pub fn shape_text(&self, text: &str, font_size: f32, font_id: u32, spacing: TextSpacing) -> ShapedText {
         let font = match self.get_font(font_id) {
             Some(f) => f,
             None => {
@@ -213,6 +1232,9 @@ impl FontManager {
                     height: font_size,
                     line_count: 1,
                     glyphs: Vec::new(),
+                    lines: Vec::new(),
+                    clusters: Vec::new(),
+                    direction: TextDirection::Ltr,
                 }
             }
         };
@@ -225,7 +1247,7 @@ impl FontManager {
         let mut total_height = 0.0f32;
 
         let lines: Vec<&str> = text.split('\n').collect();
-        let line_height = font_size * 1.2;
+        let line_height = spacing.line_height(font_size);
 
         let mut layout = Layout::new(CoordinateSystem::PositiveYDown);
 
@@ -239,11 +1261,13 @@ impl FontManager {
             // Append the whole line at once. font_index 0 refers to our single font.
             layout.append(&[font.as_ref()], &TextStyle::new(line, font_size, 0));
 
-            // Collect glyphs from the layout result
+            // Collect glyphs from the layout result, shifted by the spacing before them
             let mut line_max_x = 0.0f32;
+            let mut shift = 0.0f32;
             for glyph in layout.glyphs() {
                 // glyph has position and a glyph key referencing the font/glyph index
-                let gx = glyph.x;
+                let gx = glyph.x + shift;
+                shift += spacing.extra(glyph.parent);
                 let gy = glyph.y;
 
                 // Rasterize by glyph index to preserve ligatures and combined glyph shapes.
@@ -264,7 +1288,7 @@ impl FontManager {
                     bitmap,
                 });
 
-                line_max_x = line_max_x.max(gx + metrics.advance_width);
+                line_max_x = line_max_x.max(gx + metrics.advance_width + spacing.extra(glyph.parent));
             }
 
             max_line_width = max_line_width.max(line_max_x);
@@ -276,23 +1300,319 @@ impl FontManager {
             height: total_height.max(font_size),
             line_count: lines.len() as u32,
             glyphs,
+            lines: Vec::new(),
+            clusters: Vec::new(),
+            direction: TextDirection::Ltr,
         }
     }
 
-    /// Rasterize text to a bitmap buffer
-    pub fn rasterize_text(
+    /// Choose the stage that turns text into glyphs for drawing and measuring
+    ///
+    /// HarfBuzz shaping needs the `harfbuzz` feature and the font file's
+    /// bytes; otherwise text falls back to fontdue's layout. That includes
+    /// fonts shared with the GPU renderer, which carry no bytes, and
+    /// `TextShaper` paragraphs, which always wrap with fontdue.
+    pub fn set_shaping_backend(&mut self, backend: ShapingBackend) {
+        if backend == ShapingBackend::HarfBuzz && !cfg!(feature = "harfbuzz") {
+            log::warn!("HarfBuzz shaping requested but the harfbuzz feature is disabled; using fontdue");
+        }
+        self.shaping = backend;
+    }
+
+    /// Stage selected to shape text
+    pub fn shaping_backend(&self) -> ShapingBackend {
+        self.shaping
+    }
+
+    /// Shape one line into glyphs in visual order, with `spacing` added to their advances
+    fn shape_line(
+        &self,
+        layout: &mut Layout,
+        font: &Font,
+        font_id: u32,
+        line: &str,
+        font_size: f32,
+        spacing: TextSpacing,
+    ) -> Vec<LineGlyph> {
+        let mut glyphs = self.harfbuzz_glyphs(font_id, line, font_size).unwrap_or_else(|| {
+            layout.reset(&LayoutSettings::default());
+            layout.append(&[font], &TextStyle::new(&visual_order(line), font_size, 0));
+            layout
+                .glyphs()
+                .iter()
+                .map(|glyph| {
+                    // fontdue positions the bitmap's left edge; step back to the pen position
+                    let metrics = self.get_glyph_metrics(font, glyph.parent, font_size, font_id);
+                    LineGlyph {
+                        index: glyph.key.glyph_index,
+                        ch: glyph.parent,
+                        x: glyph.x - metrics.xmin as f32,
+                        rise: 0.0,
+                        advance: metrics.advance_width,
+                    }
+                })
+                .collect()
+        });
+        let mut shift = 0.0f32;
+        for glyph in &mut glyphs {
+            let extra = spacing.extra(glyph.ch);
+            glyph.x += shift;
+            glyph.advance += extra;
+            shift += extra;
+        }
+        glyphs
+    }
+
+    /// Shape one line with rustybuzz, None when fontdue should shape it
+    #[cfg(feature = "harfbuzz")]
+    fn harfbuzz_glyphs(&self, font_id: u32, line: &str, font_size: f32) -> Option<Vec<LineGlyph>> {
+        if self.shaping != ShapingBackend::HarfBuzz {
+            return None;
+        }
+        let (data, index) = self.font_data.get(&font_id)?;
+        let face = rustybuzz::Face::from_slice(data, *index)?;
+        let scale = font_size / face.units_per_em() as f32;
+
+        // Each level run is shaped on its own, in its direction, and placed in visual order
+        let bidi = BidiInfo::new(line, None);
+        let mut glyphs = Vec::new();
+        let mut pen = 0.0f32;
+        for (run, rtl) in visual_runs(&bidi, 0..line.len()) {
+            let mut buffer = rustybuzz::UnicodeBuffer::new();
+            buffer.push_str(&line[run.clone()]);
+            buffer.set_direction(if rtl {
+                rustybuzz::Direction::RightToLeft
+            } else {
+                rustybuzz::Direction::LeftToRight
+            });
+            buffer.guess_segment_properties();
+            let shaped = rustybuzz::shape(&face, &[], buffer);
+            let run = &line[run];
+            for (info, pos) in shaped.glyph_infos().iter().zip(shaped.glyph_positions()) {
+                glyphs.push(LineGlyph {
+                    index: info.glyph_id as u16,
+                    ch: run[info.cluster as usize..].chars().next().unwrap_or(' '),
+                    x: pen + pos.x_offset as f32 * scale,
+                    rise: pos.y_offset as f32 * scale,
+                    advance: pos.x_advance as f32 * scale,
+                });
+                pen += pos.x_advance as f32 * scale;
+            }
+        }
+        Some(glyphs)
+    }
+
+    #[cfg(not(feature = "harfbuzz"))]
+    fn harfbuzz_glyphs(&self, _font_id: u32, _line: &str, _font_size: f32) -> Option<Vec<LineGlyph>> {
+        None
+    }
+
+    /// Ascent, descent and line gap of a font at `font_size`, None if the font is missing
+    pub fn font_metrics(&self, font_id: u32, font_size: f32) -> Option<FontMetrics> {
+        let metrics = self.get_font(font_id)?.horizontal_line_metrics(font_size)?;
+        Some(FontMetrics {
+            ascent: metrics.ascent,
+            descent: -metrics.descent,
+            line_gap: metrics.line_gap,
+        })
+    }
+
+    /// Distance from the top of a laid-out run down to `anchor`
+    pub fn anchor_offset(
         &self,
         text: &str,
         font_size: f32,
         font_id: u32,
-        color: (u8, u8, u8, u8),
-    ) -> (Vec<u8>, u32, u32) {
-        let font = match self.get_font(font_id) {
-            Some(f) => f,
+        anchor: TextAnchor,
+        spacing: TextSpacing,
+    ) -> f32 {
+        match anchor {
+            TextAnchor::Top => 0.0,
+            TextAnchor::Baseline => {
+                let run = self.layout_run(text, font_size, font_id, spacing);
+                run.lines.first().map_or(0.0, |line| line.baseline)
+            }
+            TextAnchor::Bottom => self.layout_run(text, font_size, font_id, spacing).height as f32,
+        }
+    }
+
+    /// Cumulative glyph counters since the manager was created
+    pub fn glyph_counters(&self) -> GlyphCounters {
+        self.counters.get()
+    }
+
+    /// Bytes of glyph bitmaps currently cached
+    pub fn glyph_cache_bytes(&self) -> usize {
+        self.glyph_cache.borrow().used_bytes
+    }
+
+    /// Current glyph cache budget in bytes
+    pub fn glyph_cache_limit(&self) -> usize {
+        self.glyph_cache.borrow().budget_bytes
+    }
+
+    /// Change the glyph cache budget, evicting least-recently-used glyphs if it is now over
+    ///
+    /// A budget of 0 disables caching: every glyph is rasterized each time it is drawn.
+    pub fn set_glyph_cache_limit(&mut self, bytes: usize) {
+        let evicted = self.glyph_cache.get_mut().set_budget(bytes);
+        self.count_evicted(evicted);
+    }
+
+    fn count_evicted(&self, evicted: usize) {
+        let mut counters = self.counters.get();
+        counters.evicted += evicted as u64;
+        self.counters.set(counters);
+    }
+
+    /// Add rasterized glyphs to the cache, evicting least-recently-used glyphs past the budget
+    fn insert_glyphs(&self, glyphs: Vec<(GlyphCacheKey, CachedGlyph)>) {
+        let mut counters = self.counters.get();
+        counters.rasterized += glyphs.len() as u64;
+        self.counters.set(counters);
+
+        let evicted = self.glyph_cache.borrow_mut().insert(glyphs);
+        self.count_evicted(evicted);
+    }
+
+    /// Get a glyph bitmap from the cache, rasterizing it on a miss
+    fn cached_glyph(&self, font: &Font, font_id: u32, glyph_index: u16, font_size: f32) -> CachedGlyph {
+        let key = ((font_id, glyph_index, font_size.to_bits()), GlyphFormat::Coverage);
+        if let Some(glyph) = self.glyph_cache.borrow_mut().get(&key) {
+            return glyph;
+        }
+        // Color glyphs cover the pixels they are opaque in
+        let glyph = match self.cached_color_glyph(font_id, glyph_index, font_size) {
+            Some(color) => Arc::new((color.0, color.1.chunks_exact(4).map(|p| p[3]).collect())),
             None => {
-                // Return empty buffer if no font
-                return (Vec::new(), 0, 0);
+                let glyph = font.rasterize_indexed(glyph_index, font_size);
+                Arc::new(self.synthesis(font_id).apply(glyph, font_size))
+            }
+        };
+        self.insert_glyphs(vec![(key, glyph.clone())]);
+        glyph
+    }
+
+    /// Get a glyph's RGBA color bitmap, decoding it on a miss; None if it has no color version
+    fn cached_color_glyph(&self, font_id: u32, glyph_index: u16, font_size: f32) -> Option<CachedGlyph> {
+        let (data, index) = self.color_faces.get(&font_id)?;
+        let key = ((font_id, glyph_index, font_size.to_bits()), GlyphFormat::Color);
+        let cached = self.glyph_cache.borrow_mut().get(&key);
+        let glyph = match cached {
+            Some(glyph) => glyph,
+            None => {
+                let font = self.get_font(font_id)?;
+                let glyph = crate::color_font::rasterize(font, data, *index, glyph_index, font_size)
+                    .unwrap_or_else(|| (Metrics::default(), Vec::new()));
+                let glyph = Arc::new(glyph);
+                self.insert_glyphs(vec![(key, glyph.clone())]);
+                glyph
+            }
+        };
+        (!glyph.1.is_empty()).then_some(glyph)
+    }
+
+    /// Check if a font has color glyphs (CBDT, sbix or COLR tables)
+    ///
+    /// Color glyphs keep their own colors in `rasterize_text`; only the text
+    /// color's alpha applies to them.
+    pub fn has_color_glyphs(&self, font_id: u32) -> bool {
+        self.color_faces.contains_key(&font_id)
+    }
+
+    /// Get a glyph's RGB subpixel coverage (3 bytes per pixel), rasterizing it on a miss
+    ///
+    /// Synthetic bold and oblique glyphs repeat their grayscale coverage in
+    /// every channel.
+    fn cached_subpixel_glyph(&self, font: &Font, font_id: u32, glyph_index: u16, font_size: f32) -> CachedGlyph {
+        let key = ((font_id, glyph_index, font_size.to_bits()), GlyphFormat::Subpixel);
+        if let Some(glyph) = self.glyph_cache.borrow_mut().get(&key) {
+            return glyph;
+        }
+        let glyph = if self.synthesis(font_id).is_none() {
+            Arc::new(font.rasterize_indexed_subpixel(glyph_index, font_size))
+        } else {
+            let gray = self.cached_glyph(font, font_id, glyph_index, font_size);
+            Arc::new((gray.0, gray.1.iter().flat_map(|&c| [c, c, c]).collect()))
+        };
+        self.insert_glyphs(vec![(key, glyph.clone())]);
+        glyph
+    }
+
+    /// Rasterize every glyph needed by `runs` (text, font size, font ID) that isn't cached yet
+    ///
+    /// Large batches, such as a first render of a CJK paragraph, are split
+    /// across threads so the serial blit in `rasterize_text` only hits the
+    /// cache. Returns the number of glyphs rasterized.
+    pub fn prepare_glyphs<'a>(&self, runs: impl IntoIterator<Item = (&'a str, f32, u32)>) -> usize {
+        let mut missing: Vec<(GlyphKey, Arc<Font>, Synthesis)> = Vec::new();
+        {
+            let mut cache = self.glyph_cache.borrow_mut();
+            let mut seen = HashSet::new();
+            let mut layout = Layout::new(CoordinateSystem::PositiveYDown);
+            for (text, font_size, font_id) in runs {
+                let Some(font) = self.get_font(font_id) else {
+                    continue;
+                };
+                // Color glyphs are decoded on first draw instead
+                if self.has_color_glyphs(font_id) {
+                    continue;
+                }
+                let synthesis = self.synthesis(font_id);
+                for line in text.split('\n') {
+                    for glyph in self.shape_line(&mut layout, font, font_id, line, font_size, TextSpacing::default()) {
+                        let key = (font_id, glyph.index, font_size.to_bits());
+                        // Looking a glyph up marks it used, so the glyphs inserted below don't evict it
+                        if cache.get(&(key, GlyphFormat::Coverage)).is_none() && seen.insert(key) {
+                            missing.push((key, font.clone(), synthesis));
+                        }
+                    }
+                }
             }
+        }
+
+        let rasterize = |part: &[(GlyphKey, Arc<Font>, Synthesis)]| -> Vec<(GlyphCacheKey, CachedGlyph)> {
+            part.iter()
+                .map(|(key, font, synthesis)| {
+                    let font_size = f32::from_bits(key.2);
+                    let glyph = font.rasterize_indexed(key.1, font_size);
+                    ((*key, GlyphFormat::Coverage), Arc::new(synthesis.apply(glyph, font_size)))
+                })
+                .collect()
+        };
+
+        let glyphs = if missing.len() < PARALLEL_GLYPH_THRESHOLD {
+            rasterize(&missing)
+        } else {
+            let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+            let chunk = missing.len().div_ceil(threads);
+            std::thread::scope(|scope| {
+                let workers: Vec<_> = missing
+                    .chunks(chunk)
+                    .map(|part| scope.spawn(move || rasterize(part)))
+                    .collect();
+                workers
+                    .into_iter()
+                    .flat_map(|worker| worker.join().unwrap_or_default())
+                    .collect()
+            })
+        };
+
+        let count = glyphs.len();
+        self.insert_glyphs(glyphs);
+        count
+    }
+
+    /// Lay out text into glyphs positioned relative to the run's top-left corner
+    ///
+    /// Lines are stacked using their own ascent and descent, exactly as
+    /// `rasterize_text` draws them. A `spacing` line height taller than a
+    /// line's glyphs centers them in the extra space; a shorter one never
+    /// overlaps lines. Returns an empty run if the font is missing.
+    pub fn layout_run(&self, text: &str, font_size: f32, font_id: u32, spacing: TextSpacing) -> TextRun {
+        let font = match self.get_font(font_id) {
+            Some(f) => f,
+            None => return TextRun::default(),
         };
 
         // Support multi-line text. For each line compute glyph metrics and per-line
@@ -300,63 +1620,55 @@ impl FontManager {
         let lines: Vec<&str> = text.split('\n').collect();
 
         struct GlyphDatum {
-            metrics: Metrics,
-            bitmap: Vec<u8>,
+            index: u16,
+            bitmap: CachedGlyph,
             x: f32,
+            rise: f32,
         }
 
         let mut lines_glyphs: Vec<Vec<GlyphDatum>> = Vec::new();
         let mut line_ascent: Vec<f32> = Vec::new();
         let mut line_descent: Vec<f32> = Vec::new();
+        let mut line_widths: Vec<f32> = Vec::new();
         let mut max_width = 0.0f32;
         let mut total_height = 0.0f32;
-        let line_height = font_size * 1.2;
+        let line_height = spacing.line_height(font_size);
 
-        // Use fontdue's layout per-line so ligatures and proper positioning are preserved.
+        // Shape per line so ligatures and proper positioning are preserved.
         let mut layout = Layout::new(CoordinateSystem::PositiveYDown);
 
         for line in lines.iter() {
-            layout.reset(&LayoutSettings {
-                max_width: None,
-                ..LayoutSettings::default()
-            });
-
-            layout.append(&[font.as_ref()], &TextStyle::new(line, font_size, 0));
-
             let mut glyphs_line: Vec<GlyphDatum> = Vec::new();
             let mut max_ascent = 0.0f32;
             let mut max_descent = 0.0f32;
             let mut line_width = 0.0f32;
 
-            for glyph in layout.glyphs() {
-                // Position for this glyph
-                let glyph_x = glyph.x;
-                let _glyph_y = glyph.y;
-
-                // Rasterize by glyph index when available to support ligatures
-                let (metrics, bitmap) = {
-                    let gindex = glyph.key.glyph_index;
-                    font.rasterize_indexed(gindex, font_size)
-                };
+            for glyph in self.shape_line(&mut layout, font, font_id, line, font_size, spacing) {
+                // Rasterize by glyph index to support ligatures
+                let bitmap = self.cached_glyph(font, font_id, glyph.index, font_size);
+                let metrics = bitmap.0;
 
-                let ascent = metrics.ymin as f32 + metrics.height as f32;
-                let descent = -metrics.ymin as f32;
+                let ascent = metrics.ymin as f32 + metrics.height as f32 + glyph.rise;
+                let descent = -metrics.ymin as f32 - glyph.rise;
 
                 max_ascent = max_ascent.max(ascent);
                 max_descent = max_descent.max(descent);
 
+                let x = glyph.x + metrics.xmin as f32;
                 glyphs_line.push(GlyphDatum {
-                    metrics,
+                    index: glyph.index,
                     bitmap,
-                    x: glyph_x,
+                    x,
+                    rise: glyph.rise,
                 });
 
-                line_width = line_width.max(glyph_x + metrics.advance_width);
+                line_width = line_width.max(x + glyph.advance);
             }
 
             lines_glyphs.push(glyphs_line);
             line_ascent.push(max_ascent);
             line_descent.push(max_descent);
+            line_widths.push(line_width);
 
             max_width = max_width.max(line_width);
             let used_height = (max_ascent + max_descent).max(line_height);
@@ -365,72 +1677,285 @@ impl FontManager {
 
         let width = max_width.ceil() as u32;
         let height = total_height.ceil().max(font_size) as u32;
-
         if width == 0 || height == 0 {
-            return (Vec::new(), 0, 0);
+            return TextRun::default();
         }
 
-        // Create RGBA buffer
-        let mut buffer = vec![0u8; (width * height * 4) as usize];
-
-        // Second pass: render glyphs line by line
+        // Second pass: place glyphs line by line
+        let synthesis = self.synthesis(font_id);
+        let mut glyphs = Vec::new();
+        let mut lines = Vec::new();
         let mut y_cursor = 0.0f32;
         for (li, glyphs_line) in lines_glyphs.into_iter().enumerate() {
             let ascent = line_ascent[li];
             let descent = line_descent[li];
             let used_height = (ascent + descent).max(line_height);
-            let baseline = y_cursor + ascent;
+            let leading = match spacing.line_height {
+                Some(_) => (used_height - ascent - descent) / 2.0,
+                None => 0.0,
+            };
+            let baseline = y_cursor + leading + ascent;
+            lines.push(RunLine {
+                baseline,
+                width: line_widths[li],
+            });
 
             for g in glyphs_line {
-                let metrics = g.metrics;
-                let bitmap = g.bitmap;
+                let metrics = g.bitmap.0;
+                if g.bitmap.1.is_empty() {
+                    continue;
+                }
+                glyphs.push(RunGlyph {
+                    key: (font_id, g.index, font_size.to_bits()),
+                    x: g.x + synthesis.x_offset(&metrics),
+                    y: baseline - metrics.ymin as f32 - metrics.height as f32 - g.rise,
+                    width: metrics.width as u32,
+                    height: metrics.height as u32,
+                    glyph: g.bitmap,
+                    color: self.cached_color_glyph(font_id, g.index, font_size),
+                });
+            }
 
-                if bitmap.is_empty() {
+            y_cursor += used_height;
+        }
+
+        TextRun {
+            glyphs,
+            lines,
+            width,
+            height,
+        }
+    }
+
+    /// Shape styled spans as one paragraph, wrapping words past `max_width`
+    ///
+    /// Spans are laid out in a single pass, so kerning and line breaks
+    /// carry across span boundaries. Each span is split into one fragment
+    /// per line it touches. Spans whose font is missing are skipped.
+    pub fn layout_paragraph(&self, spans: &[TextSpan], max_width: Option<f32>) -> ParagraphLayout {
+        let mut fonts: Vec<&Font> = Vec::new();
+        let mut layout: Layout<usize> = Layout::new(CoordinateSystem::PositiveYDown);
+        layout.reset(&LayoutSettings {
+            max_width: max_width.filter(|w| *w > 0.0),
+            ..LayoutSettings::default()
+        });
+        for (i, span) in spans.iter().enumerate() {
+            let Some(font) = self.get_font(span.font_id) else {
+                continue;
+            };
+            let font_index = match fonts.iter().position(|f| std::ptr::eq(*f, font.as_ref())) {
+                Some(index) => index,
+                None => {
+                    fonts.push(font);
+                    fonts.len() - 1
+                }
+            };
+            layout.append(&fonts, &TextStyle::with_user_data(&span.text, span.font_size, font_index, i));
+        }
+
+        let Some(lines) = layout.lines() else {
+            return ParagraphLayout::default();
+        };
+        let glyphs = layout.glyphs();
+        let mut paragraph = ParagraphLayout {
+            line_count: lines.len() as u32,
+            height: layout.height(),
+            ..ParagraphLayout::default()
+        };
+        for line in lines {
+            let line_glyphs = &glyphs[line.glyph_start..=line.glyph_end];
+            let mut line_width = 0.0f32;
+            for piece in line_glyphs.chunk_by(|a, b| a.user_data == b.user_data) {
+                let span = &spans[piece[0].user_data];
+                let last = &piece[piece.len() - 1];
+                let start = piece[0].byte_offset;
+                let end = last.byte_offset + last.parent.len_utf8();
+                // Hard line breaks end a line but aren't drawn
+                let text = span.text[start..end].trim_end_matches(['\n', '\r']);
+                if text.is_empty() {
                     continue;
                 }
+                // Glyph positions include the left bearing; fragments start at the pen position
+                let font = fonts[piece[0].font_index];
+                let first = font.metrics_indexed(piece[0].key.glyph_index, span.font_size);
+                let end_metrics = font.metrics_indexed(last.key.glyph_index, span.font_size);
+                line_width = line_width.max(last.x - end_metrics.xmin as f32 + end_metrics.advance_width);
+                paragraph.fragments.push(SpanFragment {
+                    span: piece[0].user_data,
+                    range: start..start + text.len(),
+                    x: piece[0].x - first.xmin as f32,
+                    baseline: line.baseline_y,
+                });
+            }
+            paragraph.width = paragraph.width.max(line_width);
+        }
+        paragraph
+    }
 
-                let glyph_x = g.x;
-                let glyph_y = baseline - metrics.ymin as f32 - metrics.height as f32;
+    /// Rasterize text to a bitmap buffer
+    pub fn rasterize_text(
+        &self,
+        text: &str,
+        font_size: f32,
+        font_id: u32,
+        color: (u8, u8, u8, u8),
+        spacing: TextSpacing,
+    ) -> (Vec<u8>, u32, u32) {
+        let TextRun {
+            glyphs, width, height, ..
+        } = self.layout_run(text, font_size, font_id, spacing);
+        if width == 0 || height == 0 {
+            // Return empty buffer if no font or nothing to draw
+            return (Vec::new(), 0, 0);
+        }
 
-                for gy in 0..metrics.height {
-                    for gx in 0..metrics.width {
-                        let src_idx = gy * metrics.width + gx;
-                        let alpha = bitmap[src_idx];
+        // Create RGBA buffer
+        let mut buffer = vec![0u8; (width * height * 4) as usize];
 
-                        if alpha == 0 {
-                            continue;
-                        }
+        for g in &glyphs {
+            let bitmap = g.coverage();
+            let glyph_width = g.width as usize;
+            // Columns of the glyph that land inside the buffer
+            let gx0 = g.x.floor() as i32;
+            let (first, last) = ((-gx0).max(0) as usize, ((width as i32 - gx0).max(0) as usize).min(glyph_width));
+            if first >= last {
+                continue;
+            }
 
-                        let px = (glyph_x + gx as f32) as i32;
-                        let py = (glyph_y + gy as f32) as i32;
-
-                        if px >= 0 && py >= 0 && (px as u32) < width && (py as u32) < height {
-                            let dst_idx = ((py as u32 * width + px as u32) * 4) as usize;
-
-                            // Alpha blend
-                            let a = (alpha as f32 / 255.0) * (color.3 as f32 / 255.0);
-                            buffer[dst_idx] =
-                                ((color.0 as f32 * a) + (buffer[dst_idx] as f32 * (1.0 - a))) as u8;
-                            buffer[dst_idx + 1] = ((color.1 as f32 * a)
-                                + (buffer[dst_idx + 1] as f32 * (1.0 - a)))
-                                as u8;
-                            buffer[dst_idx + 2] = ((color.2 as f32 * a)
-                                + (buffer[dst_idx + 2] as f32 * (1.0 - a)))
-                                as u8;
-                            buffer[dst_idx + 3] =
-                                ((a * 255.0) + (buffer[dst_idx + 3] as f32 * (1.0 - a))) as u8;
+            for gy in 0..g.height as usize {
+                let py = (g.y + gy as f32) as i32;
+                if py < 0 || py as u32 >= height {
+                    continue;
+                }
+                let src_row = gy * glyph_width;
+                let dst_row = (py as u32 * width) as usize;
+                let dst = &mut buffer[(dst_row + (gx0 + first as i32) as usize) * 4..(dst_row + (gx0 + last as i32) as usize) * 4];
+                match g.color() {
+                    None => {
+                        blend::blend_color_row(dst, [color.0, color.1, color.2, color.3], &bitmap[src_row + first..src_row + last]);
+                    }
+                    // Color glyphs keep their own colors
+                    Some(pixels) => {
+                        for (i, d) in dst.chunks_exact_mut(4).enumerate() {
+                            let src_idx = src_row + first + i;
+                            let a = blend::mul_255(bitmap[src_idx], color.3);
+                            if a > 0 {
+                                let p = &pixels[src_idx * 4..][..3];
+                                blend::blend_pixel(d, [p[0], p[1], p[2]], a);
+                            }
                         }
                     }
                 }
             }
+        }
 
-            y_cursor += used_height;
+        (buffer, width, height)
+    }
+
+    /// Rasterize text to per-channel coverage, 3 bytes per pixel in R, G, B order
+    ///
+    /// Glyphs sit exactly where `rasterize_text` puts them, so both return
+    /// the same size. `bgr` swaps the outer channels for BGR panels.
+    pub fn rasterize_text_subpixel(
+        &self,
+        text: &str,
+        font_size: f32,
+        font_id: u32,
+        bgr: bool,
+        spacing: TextSpacing,
+    ) -> (Vec<u8>, u32, u32) {
+        let Some(font) = self.get_font(font_id) else {
+            return (Vec::new(), 0, 0);
+        };
+        let TextRun {
+            glyphs, width, height, ..
+        } = self.layout_run(text, font_size, font_id, spacing);
+        if width == 0 || height == 0 {
+            return (Vec::new(), 0, 0);
+        }
+
+        let mut buffer = vec![0u8; (width * height * 3) as usize];
+        for g in &glyphs {
+            let glyph = self.cached_subpixel_glyph(font, g.key.0, g.key.1, font_size);
+            let glyph_width = glyph.0.width;
+            if glyph.1.len() < glyph_width * glyph.0.height * 3 {
+                continue;
+            }
+            for gy in 0..glyph.0.height {
+                for gx in 0..glyph_width {
+                    let px = (g.x + gx as f32) as i32;
+                    let py = (g.y + gy as f32) as i32;
+                    if px < 0 || py < 0 || px as u32 >= width || py as u32 >= height {
+                        continue;
+                    }
+                    let src = &glyph.1[(gy * glyph_width + gx) * 3..][..3];
+                    let dst_idx = ((py as u32 * width + px as u32) * 3) as usize;
+                    for c in 0..3 {
+                        let coverage = src[if bgr { 2 - c } else { c }];
+                        buffer[dst_idx + c] = coverage + blend::mul_255(buffer[dst_idx + c], 255 - coverage);
+                    }
+                }
+            }
         }
 
         (buffer, width, height)
     }
 }
 
+/// Reorder one line of text from logical to visual order (Unicode Bidirectional Algorithm)
+///
+/// Right-to-left runs come out reversed so a left-to-right layout draws
+/// them correctly. Mirrored brackets and contextual forms are not applied.
+fn visual_order(line: &str) -> Cow<'_, str> {
+    if line.is_ascii() {
+        return Cow::Borrowed(line);
+    }
+    let bidi = BidiInfo::new(line, None);
+    match bidi.paragraphs.first() {
+        Some(para) if bidi.has_rtl() => Cow::Owned(bidi.reorder_line(para, para.range.clone()).into_owned()),
+        _ => Cow::Borrowed(line),
+    }
+}
+
+/// Level runs of a line in visual order, each with whether it runs right to left
+fn visual_runs(bidi: &BidiInfo, line: Range<usize>) -> Vec<(Range<usize>, bool)> {
+    if line.is_empty() {
+        return Vec::new();
+    }
+    let Some(para) = bidi.paragraphs.iter().find(|p| p.range.contains(&line.start)) else {
+        return vec![(line, false)];
+    };
+    let (levels, runs) = bidi.visual_runs(para, line);
+    runs.into_iter()
+        .map(|run| {
+            let rtl = levels[run.start].is_rtl();
+            (run, rtl)
+        })
+        .collect()
+}
+
+/// Fonts installed on the system, scanned on first use
+fn system_font_db() -> &'static fontdb::Database {
+    static DB: OnceLock<fontdb::Database> = OnceLock::new();
+    DB.get_or_init(|| {
+        let mut db = fontdb::Database::new();
+        db.load_system_fonts();
+        log::debug!("Found {} system font faces", db.len());
+        db
+    })
+}
+
+/// Family names of the fonts installed on the system, sorted and deduplicated
+pub fn system_font_families() -> Vec<String> {
+    let mut names: Vec<String> = system_font_db()
+        .faces()
+        .filter_map(|f| f.families.first().map(|(name, _)| name.clone()))
+        .collect();
+    names.sort_unstable_by_key(|name| name.to_lowercase());
+    names.dedup();
+    names
+}
+
 /// Get system font paths based on OS
 fn get_system_font_paths() -> Vec<String> {
     let mut paths = Vec::new();
@@ -466,6 +1991,7 @@ fn get_system_font_paths() -> Vec<String> {
 pub struct TextShaper {
     font_manager: FontManager,
     cache: HashMap<u64, ShapedText>,
+    spacing: TextSpacing,
 }
 
 impl Default for TextShaper {
@@ -479,6 +2005,7 @@ impl TextShaper {
         Self {
             font_manager: FontManager::new(),
             cache: HashMap::new(),
+            spacing: TextSpacing::default(),
         }
     }
 
@@ -492,50 +2019,99 @@ impl TextShaper {
         &mut self.font_manager
     }
 
+    /// Set the letter spacing, word spacing and line height of paragraphs shaped from now on
+    pub fn set_spacing(&mut self, spacing: TextSpacing) {
+        self.spacing = spacing;
+    }
+
+    /// Spacing applied to shaped paragraphs
+    pub fn spacing(&self) -> TextSpacing {
+        self.spacing
+    }
+
     /// Shape a paragraph with word wrapping
     pub fn shape_paragraph(&mut self, text: &str, max_width: f32, font_size: f32) -> ShapedText {
+        self.shape_paragraph_with(text, max_width, font_size, WrapOptions::default())
+    }
+
+    /// Shape a paragraph, breaking and truncating lines as `wrap` asks
+    pub fn shape_paragraph_with(&mut self, text: &str, max_width: f32, font_size: f32, wrap: WrapOptions) -> ShapedText {
         // Simple hash for caching
-        let hash = text_hash(text, max_width, font_size);
+        let hash = text_hash(text, max_width, font_size, wrap, self.spacing);
 
         if let Some(cached) = self.cache.get(&hash) {
             return cached.clone();
         }
 
-        // Simple word wrapping
-        let mut lines: Vec<&str> = Vec::new();
-        let mut current_line_start = 0;
-        let mut current_width = 0.0f32;
-        let mut last_space = 0;
-
-        for (i, c) in text.char_indices() {
-            let char_width = self
-                .font_manager
-                .measure_text(&c.to_string(), font_size, 0)
-                .0;
-
-            if c == ' ' {
-                last_space = i;
-            }
-
-            current_width += char_width;
-
-            if current_width > max_width && last_space > current_line_start {
-                lines.push(&text[current_line_start..last_space]);
-                current_line_start = last_space + 1;
-                current_width = 0.0;
-            }
-        }
-
-        if current_line_start < text.len() {
-            lines.push(&text[current_line_start..]);
-        }
+        let font = self.font_manager.get_font(0).cloned();
+        let advance = |c: char| match c {
+            // Soft hyphens only show when a line breaks at them
+            '\u{AD}' => 0.0,
+            _ => self.font_manager.measure_text(c.encode_utf8(&mut [0; 4]), font_size, 0, self.spacing).0,
+        };
+        let chars: Vec<(usize, char, f32)> = text.char_indices().map(|(i, c)| (i, c, advance(c))).collect();
+        let hyphen_width = advance('-');
+        let ellipsis_width = advance('…');
+        let lines = wrap_lines(&chars, text.len(), max_width, wrap, hyphen_width, ellipsis_width);
+        let suffix_width = |suffix: LineSuffix| match suffix {
+            LineSuffix::None => 0.0,
+            LineSuffix::Hyphen => hyphen_width,
+            LineSuffix::Ellipsis => ellipsis_width,
+        };
 
-        let line_height = font_size * 1.2;
+        let line_height = self.spacing.line_height(font_size);
         let mut total_height = 0.0f32;
         let mut max_line_width = 0.0f32;
-
-        for line in &lines {
-            let (w, _) = self.font_manager.measure_text(line, font_size, 0);
+        let mut shaped_lines = Vec::with_capacity(lines.len());
+        let mut clusters = Vec::new();
+        let bidi = BidiInfo::new(text, None);
+
+        for (li, &(start, end, suffix)) in lines.iter().enumerate() {
+            let line = &text[start..end];
+            let first_cluster = clusters.len();
+            // Advances are summed per character, the same way `measure_text` does.
+            // Runs are placed in visual order; right-to-left runs place their characters reversed.
+            let w = match &font {
+                Some(font) => {
+                    let mut x = 0.0f32;
+                    for (run, rtl) in visual_runs(&bidi, start..end) {
+                        let mut chars: Vec<(usize, char)> = text[run.clone()].char_indices().collect();
+                        if rtl {
+                            chars.reverse();
+                        }
+                        for (offset, c) in chars {
+                            let advance = match c {
+                                '\u{AD}' => 0.0,
+                                _ => {
+                                    self.font_manager.get_glyph_metrics(font, c, font_size, 0).advance_width
+                                        + self.spacing.extra(c)
+                                }
+                            };
+                            clusters.push(GlyphCluster {
+                                byte_offset: run.start + offset,
+                                byte_len: c.len_utf8(),
+                                line: li as u32,
+                                x,
+                                advance,
+                                rtl,
+                            });
+                            x += advance;
+                        }
+                    }
+                    x
+                }
+                None => self.font_manager.measure_text(line, font_size, 0, self.spacing).0,
+            } + suffix_width(suffix);
+            shaped_lines.push(ShapedLine {
+                start,
+                end,
+                y: total_height,
+                height: line_height,
+                width: w,
+                first_cluster,
+                cluster_count: clusters.len() - first_cluster,
+                suffix,
+            });
             max_line_width = max_line_width.max(w);
             total_height += line_height;
         }
@@ -545,6 +2121,12 @@ impl TextShaper {
             height: total_height,
             line_count: lines.len() as u32,
             glyphs: Vec::new(), // Glyphs would be filled for actual rendering
+            lines: shaped_lines,
+            clusters,
+            direction: match bidi.paragraphs.first() {
+                Some(para) if para.level.is_rtl() => TextDirection::Rtl,
+                _ => TextDirection::Ltr,
+            },
         };
 
         self.cache.insert(hash, result.clone());
@@ -557,7 +2139,7 @@ impl TextShaper {
     }
 }
 
-fn text_hash(text: &str, max_width: f32, font_size: f32) -> u64 {
+fn text_hash(text: &str, max_width: f32, font_size: f32, wrap: WrapOptions, spacing: TextSpacing) -> u64 {
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
 
@@ -565,5 +2147,499 @@ fn text_hash(text: &str, max_width: f32, font_size: f32) -> u64 {
     text.hash(&mut hasher);
     max_width.to_bits().hash(&mut hasher);
     font_size.to_bits().hash(&mut hasher);
+    wrap.hash(&mut hasher);
+    spacing.hash(&mut hasher);
     hasher.finish()
 }
+
+/// Break characters (byte offset, char, advance) into lines of at most `max_width`
+///
+/// Returns each line's byte range and suffix. Lines break greedily at the
+/// last opportunity `wrap` allows; spaces a line breaks at are left out of
+/// it and may hang past the edge.
+fn wrap_lines(
+    chars: &[(usize, char, f32)],
+    text_len: usize,
+    max_width: f32,
+    wrap: WrapOptions,
+    hyphen_width: f32,
+    ellipsis_width: f32,
+) -> Vec<(usize, usize, LineSuffix)> {
+    let byte = |i: usize| chars.get(i).map_or(text_len, |c| c.0);
+    let width_of = |range: Range<usize>| chars[range].iter().map(|c| c.2).sum::<f32>();
+
+    // Lines as char index ranges
+    let mut lines: Vec<(usize, usize, LineSuffix)> = Vec::new();
+    let mut start = 0;
+    let mut width = 0.0f32;
+    // Last break opportunity in the current line: where the line would end and the next start
+    let mut opportunity: Option<(usize, usize, LineSuffix)> = None;
+
+    for (i, &(_, c, advance)) in chars.iter().enumerate() {
+        if c == ' ' {
+            opportunity = Some((i, i + 1, LineSuffix::None));
+            width += advance;
+            continue;
+        }
+
+        while wrap.mode != WrapMode::NoWrap && i > start && width + advance > max_width {
+            let at_opportunity = opportunity.filter(|o| o.0 > start && wrap.mode != WrapMode::Char);
+            match at_opportunity {
+                Some((end, next, suffix)) => {
+                    lines.push((start, end, suffix));
+                    start = next;
+                    width = width_of(next..i);
+                }
+                None if wrap.mode == WrapMode::Word => break,
+                None => {
+                    lines.push((start, i, LineSuffix::None));
+                    start = i;
+                    width = 0.0;
+                }
+            }
+            opportunity = None;
+        }
+
+        width += advance;
+        if c == '\u{AD}' && wrap.hyphens && width + hyphen_width <= max_width {
+            opportunity = Some((i + 1, i + 1, LineSuffix::Hyphen));
+        }
+    }
+    if start < chars.len() {
+        lines.push((start, chars.len(), LineSuffix::None));
+    }
+
+    // Cut off lines past the limit, ellipsizing the last one kept
+    if wrap.max_lines > 0 {
+        let max_lines = wrap.max_lines as usize;
+        let truncated = lines.len() > max_lines;
+        lines.truncate(max_lines);
+        if let Some(last) = lines.last_mut() {
+            if truncated || width_of(last.0..last.1) > max_width {
+                while last.1 > last.0 && (width_of(last.0..last.1) + ellipsis_width > max_width || chars[last.1 - 1].1 == ' ') {
+                    last.1 -= 1;
+                }
+                last.2 = LineSuffix::Ellipsis;
+            }
+        }
+    }
+
+    lines
+        .into_iter()
+        .map(|(start, end, suffix)| (byte(start), byte(end), suffix))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_font_manager_parallel_glyph_preparation() {
+        let fonts = FontManager::new();
+        if fonts.get_font(0).is_none() {
+            return;
+        }
+        let text: String = (0..200u32).filter_map(|i| char::from_u32(0x21 + i % 94)).collect();
+
+        let prepared = fonts.prepare_glyphs([(text.as_str(), 17.0, 0)]);
+        assert!(prepared > 0);
+        // Everything is cached now, so neither pass rasterizes again
+        assert_eq!(fonts.prepare_glyphs([(text.as_str(), 17.0, 0)]), 0);
+        let before = fonts.glyph_counters().rasterized;
+        fonts.rasterize_text(&text, 17.0, 0, (0, 0, 0, 255), TextSpacing::default());
+        assert_eq!(fonts.glyph_counters().rasterized, before);
+    }
+
+    #[test]
+    fn test_font_manager_glyph_cache_limit() {
+        let mut fonts = FontManager::new();
+        if fonts.get_font(0).is_none() {
+            return;
+        }
+        fonts.prepare_glyphs([("abc", 24.0, 0)]);
+        let abc = fonts.glyph_cache_bytes();
+        assert!(abc > 0);
+
+        // Shrinking the budget evicts the least recently used glyphs first
+        fonts.rasterize_text("c", 24.0, 0, (0, 0, 0, 255), TextSpacing::default());
+        fonts.set_glyph_cache_limit(abc / 2);
+        assert!(fonts.glyph_cache_bytes() <= abc / 2);
+        assert!(fonts.glyph_counters().evicted > 0);
+        let before = fonts.glyph_counters().rasterized;
+        fonts.rasterize_text("c", 24.0, 0, (0, 0, 0, 255), TextSpacing::default());
+        assert_eq!(fonts.glyph_counters().rasterized, before);
+
+        // A batch bigger than the budget is cached only up to it, without evicting itself
+        let text: String = (0x21..0x7f).filter_map(char::from_u32).collect();
+        let mut fonts = FontManager::new();
+        fonts.set_glyph_cache_limit(abc * 2);
+        assert!(fonts.prepare_glyphs([(text.as_str(), 24.0, 0)]) > 0);
+        assert!(fonts.glyph_cache_bytes() <= abc * 2);
+        assert_eq!(fonts.glyph_counters().evicted, 0);
+
+        // With no budget nothing stays cached, but text still renders
+        fonts.set_glyph_cache_limit(0);
+        assert_eq!(fonts.glyph_cache_bytes(), 0);
+        let (pixels, _, _) = fonts.rasterize_text("c", 24.0, 0, (0, 0, 0, 255), TextSpacing::default());
+        assert!(pixels.chunks(4).any(|p| p[3] > 0));
+        assert_eq!(fonts.glyph_cache_bytes(), 0);
+    }
+
+    #[cfg(feature = "harfbuzz")]
+    #[test]
+    fn test_font_manager_harfbuzz_shaping() {
+        let mut fonts = FontManager::new();
+        let Some(font) = fonts.get_font(0).cloned() else {
+            return;
+        };
+        // Arabic letters join (the initial seen differs) and lam-alef is a required
+        // ligature; fontdue draws isolated forms
+        let text = "سلام";
+        if text.chars().any(|c| font.lookup_glyph_index(c) == 0) {
+            return;
+        }
+        let nominal: Vec<u16> = text.chars().rev().map(|c| font.lookup_glyph_index(c)).collect();
+        let glyphs = |run: &TextRun| run.glyphs.iter().map(|g| g.key.1).collect::<Vec<u16>>();
+
+        assert_eq!(glyphs(&fonts.layout_run(text, 24.0, 0, TextSpacing::default())), nominal);
+        fonts.set_shaping_backend(ShapingBackend::HarfBuzz);
+        let run = fonts.layout_run(text, 24.0, 0, TextSpacing::default());
+        assert_eq!(run.glyphs.len(), nominal.len() - 1);
+        assert_ne!(glyphs(&run).last(), nominal.last());
+
+        // Measuring and glyph preparation follow the shaped glyphs
+        let (width, _) = fonts.measure_text(text, 24.0, 0, TextSpacing::default());
+        assert!((width - run.lines[0].width).abs() < 2.0);
+        assert_eq!(fonts.prepare_glyphs([(text, 24.0, 0)]), 0);
+    }
+
+    /// Copy of a font file with extra tables added to its table directory
+    fn font_with_tables(data: &[u8], extra: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+        let u16_at = |at: usize| u16::from_be_bytes([data[at], data[at + 1]]);
+        let u32_at = |at: usize| u32::from_be_bytes(data[at..at + 4].try_into().unwrap());
+        let mut tables: Vec<([u8; 4], Vec<u8>)> = (0..u16_at(4) as usize)
+            .map(|i| {
+                let record = 12 + i * 16;
+                let (offset, len) = (u32_at(record + 8) as usize, u32_at(record + 12) as usize);
+                (data[record..record + 4].try_into().unwrap(), data[offset..offset + len].to_vec())
+            })
+            .collect();
+        tables.extend(extra);
+        tables.sort_by_key(|(tag, _)| *tag);
+
+        let mut out = data[..4].to_vec();
+        out.extend((tables.len() as u16).to_be_bytes());
+        out.extend([0; 6]);
+        let mut offset = 12 + tables.len() * 16;
+        for (tag, table) in &tables {
+            out.extend(tag);
+            out.extend([0; 4]);
+            out.extend((offset as u32).to_be_bytes());
+            out.extend((table.len() as u32).to_be_bytes());
+            offset += table.len().div_ceil(4) * 4;
+        }
+        for (_, table) in &tables {
+            out.extend(table);
+            out.resize(out.len().div_ceil(4) * 4, 0);
+        }
+        out
+    }
+
+    #[test]
+    fn test_font_manager_color_glyphs() {
+        let Ok(data) = std::fs::read("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf") else {
+            return;
+        };
+        let face = ttf_parser::Face::parse(&data, 0).unwrap();
+        let (a, b) = (face.glyph_index('A').unwrap().0, face.glyph_index('B').unwrap().0);
+
+        // 'A' gets a 16 ppem sbix strike: a solid red PNG
+        let mut png_data = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png_data, 8, 8);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(&[255, 0, 0, 255].repeat(64)).unwrap();
+        let mut glyph = vec![0, 0, 0, 0];
+        glyph.extend(b"png ");
+        glyph.extend(&png_data);
+        let num_glyphs = face.number_of_glyphs() as u32;
+        let header = 4 + (num_glyphs + 1) * 4;
+        let mut strike = vec![0, 16, 0, 72];
+        for id in 0..=num_glyphs {
+            let offset = header + if id > a as u32 { glyph.len() as u32 } else { 0 };
+            strike.extend(offset.to_be_bytes());
+        }
+        strike.extend(&glyph);
+        let mut sbix = vec![0, 1, 0, 1, 0, 0, 0, 1, 0, 0, 0, 12];
+        sbix.extend(strike);
+
+        // 'B' gets one COLR layer of itself in palette entry 0, blue
+        let mut colr = vec![0, 0, 0, 1, 0, 0, 0, 14, 0, 0, 0, 20, 0, 1];
+        colr.extend(b.to_be_bytes());
+        colr.extend([0, 0, 0, 1]);
+        colr.extend(b.to_be_bytes());
+        colr.extend([0, 0]);
+        let cpal = vec![0, 0, 0, 1, 0, 1, 0, 1, 0, 0, 0, 14, 0, 0, 255, 0, 0, 255];
+
+        let font = font_with_tables(&data, vec![(*b"sbix", sbix), (*b"COLR", colr), (*b"CPAL", cpal)]);
+        let mut fonts = FontManager::new();
+        let plain = fonts.load_font_from_bytes(&data).unwrap();
+        let color = fonts.load_font_from_bytes(&font).unwrap();
+        assert!(!fonts.has_color_glyphs(plain));
+        assert!(fonts.has_color_glyphs(color));
+
+        let pixels = |text: &str| {
+            let (buffer, _, _) = fonts.rasterize_text(text, 32.0, color, (0, 255, 0, 255), TextSpacing::default());
+            buffer.chunks_exact(4).filter(|p| p[3] == 255).map(|p| [p[0], p[1], p[2]]).collect::<Vec<_>>()
+        };
+        // The 8x8 strike image is scaled to 16x16 and keeps its own color
+        let red = pixels("A");
+        assert_eq!(red.len(), 256);
+        assert!(red.iter().all(|p| *p == [255, 0, 0]));
+        let blue = pixels("B");
+        assert!(!blue.is_empty());
+        assert!(blue.iter().all(|p| p[2] > 200 && p[1] < 50));
+        // Glyphs without a color version use the text color
+        let green = pixels("C");
+        assert!(!green.is_empty());
+        assert!(green.iter().all(|p| *p == [0, 255, 0]));
+    }
+
+    #[test]
+    fn test_font_manager_layout_run_matches_rasterized_size() {
+        let fonts = FontManager::new();
+        if fonts.get_font(0).is_none() {
+            return;
+        }
+
+        let run = fonts.layout_run("Hg\nA", 16.0, 0, TextSpacing::default());
+        let (_, w, h) = fonts.rasterize_text("Hg\nA", 16.0, 0, (0, 0, 0, 255), TextSpacing::default());
+        assert_eq!((run.width, run.height), (w, h));
+        assert_eq!(run.glyphs.len(), 3);
+        // The glyph on the second line sits below the first line's glyphs
+        assert!(run.glyphs[2].y > run.glyphs[0].y);
+        for glyph in &run.glyphs {
+            assert_eq!(glyph.coverage().len(), (glyph.width * glyph.height) as usize);
+        }
+        assert!(fonts.layout_run("Hg", 16.0, 999, TextSpacing::default()).glyphs.is_empty());
+    }
+
+    #[test]
+    fn test_font_manager_family_resolution() {
+        let mut fonts = FontManager::new();
+        if fonts.get_font(0).is_none() {
+            return;
+        }
+        assert_eq!(fonts.resolve_font("Sans", 400, false), None);
+        assert!(fonts.add_font_to_family("Sans", 400, false, 0));
+        assert!(!fonts.add_font_to_family("Sans", 700, false, 999));
+
+        // Only a regular face: bold and italic requests are synthesized
+        assert_eq!(fonts.resolve_font("sans", 400, false), Some(0));
+        let bold_italic = fonts.resolve_font("Sans", 700, true).unwrap();
+        assert_ne!(bold_italic, 0);
+        assert_eq!(fonts.resolve_font("SANS", 700, true), Some(bold_italic));
+        let synthesis = fonts.synthesis(bold_italic);
+        assert!(synthesis.bold && synthesis.oblique);
+
+        let regular = fonts.layout_run("l", 32.0, 0, TextSpacing::default());
+        let bold = fonts.layout_run("l", 32.0, bold_italic, TextSpacing::default());
+        assert!(bold.glyphs[0].width > regular.glyphs[0].width);
+
+        // A real bold face wins over synthesis, and 600 matches it before 400
+        assert!(fonts.add_font_to_family("Sans", 700, false, 0));
+        assert_eq!(fonts.resolve_font("Sans", 600, false), Some(0));
+        let oblique = fonts.resolve_font("Sans", 900, true).unwrap();
+        assert_eq!(fonts.synthesis(oblique), Synthesis { bold: false, oblique: true });
+    }
+
+    #[test]
+    fn test_font_manager_system_fonts() {
+        let families = system_font_families();
+        let Some(name) = families.first() else {
+            return;
+        };
+        let mut fonts = FontManager::new();
+        let id = fonts.load_system_font(&name.to_uppercase(), 400, false).unwrap();
+        assert!(fonts.get_font(id).is_some());
+        // Same face again, and the family is registered for resolution
+        assert_eq!(fonts.load_system_font(name, 400, false), Some(id));
+        assert!(fonts.resolve_font(name, 400, false).is_some());
+        assert_eq!(fonts.load_system_font("No Such Family", 400, false), None);
+    }
+
+    #[test]
+    fn test_text_shaper_lines_and_clusters() {
+        let mut shaper = TextShaper::new();
+        if shaper.font_manager().get_font(0).is_none() {
+            return;
+        }
+        let text = "héllo wide world";
+        let shaped = shaper.shape_paragraph(text, 60.0, 16.0);
+        assert_eq!(shaped.lines.len(), shaped.line_count as usize);
+        assert!(shaped.lines.len() > 1);
+
+        // Lines cover the text apart from the spaces they broke at
+        for pair in shaped.lines.windows(2) {
+            assert_eq!(&text[pair[0].end..pair[1].start], " ");
+            assert!(pair[1].y > pair[0].y);
+        }
+        assert_eq!(shaped.lines[0].start, 0);
+        assert_eq!(shaped.lines.last().unwrap().end, text.len());
+
+        // Clusters map back to characters and advance left to right within a line
+        let chars: usize = shaped.lines.iter().map(|l| text[l.start..l.end].chars().count()).sum();
+        assert_eq!(shaped.clusters.len(), chars);
+        assert_eq!(shaped.clusters[1].byte_len, 'é'.len_utf8());
+        for line in &shaped.lines {
+            let clusters = &shaped.clusters[line.first_cluster..line.first_cluster + line.cluster_count];
+            assert_eq!(clusters[0].byte_offset, line.start);
+            assert!(clusters.windows(2).all(|c| c[1].x == c[0].x + c[0].advance));
+            let last = clusters[clusters.len() - 1];
+            assert_eq!(last.x + last.advance, line.width);
+        }
+    }
+
+    #[test]
+    fn test_text_shaper_wrap_modes() {
+        let mut shaper = TextShaper::new();
+        let measure = |shaper: &TextShaper, text: &str| shaper.font_manager().measure_text(text, 16.0, 0, TextSpacing::default()).0;
+        let wrap = |mode| WrapOptions {
+            mode,
+            ..WrapOptions::default()
+        };
+        let url = "https://example.com/a/very/long/path";
+        let max_width = measure(&shaper, "https://exa");
+        let line_texts = |text: &str, shaped: &ShapedText| -> Vec<String> {
+            shaped.lines.iter().map(|l| text[l.start..l.end].to_string()).collect()
+        };
+
+        // Word wrapping lets a long URL overflow; char and break-word split it
+        let shaped = shaper.shape_paragraph_with(url, max_width, 16.0, wrap(WrapMode::Word));
+        assert_eq!(shaped.line_count, 1);
+        let shaped = shaper.shape_paragraph_with(url, max_width, 16.0, wrap(WrapMode::Char));
+        assert!(shaped.line_count > 2);
+        assert_eq!(line_texts(url, &shaped).concat(), url);
+        assert!(shaped.lines.iter().all(|l| l.width <= max_width));
+
+        let text = format!("go {url}");
+        let shaped = shaper.shape_paragraph_with(&text, max_width, 16.0, wrap(WrapMode::BreakWord));
+        let lines = line_texts(&text, &shaped);
+        assert_eq!(lines[0], "go");
+        assert_eq!(lines[1..].concat(), url);
+        let shaped = shaper.shape_paragraph_with(&text, max_width, 16.0, wrap(WrapMode::NoWrap));
+        assert_eq!(shaped.line_count, 1);
+
+        // Soft hyphens break only when asked to, and end the line with a hyphen
+        let text = "extra\u{AD}ordinary";
+        let max_width = measure(&shaper, "extra-ord");
+        let shaped = shaper.shape_paragraph_with(text, max_width, 16.0, WrapOptions::default());
+        assert_eq!(shaped.line_count, 1);
+        let hyphens = WrapOptions {
+            hyphens: true,
+            ..WrapOptions::default()
+        };
+        let shaped = shaper.shape_paragraph_with(text, max_width, 16.0, hyphens);
+        assert_eq!(line_texts(text, &shaped), ["extra\u{AD}", "ordinary"]);
+        assert_eq!(shaped.lines[0].suffix, LineSuffix::Hyphen);
+        assert_eq!(shaped.lines[1].suffix, LineSuffix::None);
+
+        // Lines past max_lines are dropped and the last kept line is ellipsized
+        let text = "one two three four five six";
+        let max_width = measure(&shaper, "one two");
+        let truncated = WrapOptions {
+            max_lines: 2,
+            ..WrapOptions::default()
+        };
+        let shaped = shaper.shape_paragraph_with(text, max_width, 16.0, truncated);
+        assert_eq!(shaped.line_count, 2);
+        assert_eq!(shaped.lines[0].suffix, LineSuffix::None);
+        assert_eq!(shaped.lines[1].suffix, LineSuffix::Ellipsis);
+        assert!(shaped.lines[1].width <= max_width);
+        assert!(text[shaped.lines[1].start..shaped.lines[1].end].starts_with("thr"));
+    }
+
+    #[test]
+    fn test_text_shaper_hit_testing() {
+        let mut shaper = TextShaper::new();
+        if shaper.font_manager().get_font(0).is_none() {
+            return;
+        }
+        let text = "hello wide world";
+        let shaped = shaper.shape_paragraph(text, 60.0, 16.0);
+        let [first, second] = [shaped.lines[0], shaped.lines[1]];
+
+        // Offsets round-trip through caret positions, nudged into their glyph
+        for offset in [0, 1, 3, second.start, second.start + 2] {
+            let (x, y, height) = shaped.offset_to_position(offset);
+            assert_eq!(shaped.hit_test(x + 0.1, y + height / 2.0), offset);
+        }
+
+        // The wrap point sits at the end of the first line, the next offset starts the second
+        assert_eq!(shaped.offset_to_position(first.end), (first.width, first.y, first.height));
+        assert_eq!(shaped.offset_to_position(second.start), (0.0, second.y, second.height));
+
+        // Points outside the text clamp to the nearest line and edge
+        assert_eq!(shaped.hit_test(-5.0, -5.0), 0);
+        assert_eq!(shaped.hit_test(1000.0, first.y + 1.0), first.end);
+        assert_eq!(shaped.hit_test(1000.0, 1000.0), text.len());
+        assert_eq!(shaped.offset_to_position(1000).0, shaped.lines.last().unwrap().width);
+    }
+
+    #[test]
+    fn test_text_shaper_selection() {
+        let mut shaper = TextShaper::new();
+        if shaper.font_manager().get_font(0).is_none() {
+            return;
+        }
+        let text = "hello wide world";
+        let shaped = shaper.shape_paragraph(text, 60.0, 16.0);
+        let [first, second] = [shaped.lines[0], shaped.lines[1]];
+
+        // A selection across a wrap covers the rest of the first line and the start of the second
+        let rects = shaped.selection_rects(3..second.start + 2);
+        let (x3, _, _) = shaped.offset_to_position(3);
+        let (x2, _, _) = shaped.offset_to_position(second.start + 2);
+        assert_eq!(rects, vec![[x3, first.y, first.width - x3, first.height], [0.0, second.y, x2, second.height]]);
+        assert!(shaped.selection_rects(4..4).is_empty());
+    }
+
+    #[test]
+    fn test_text_shaper_bidi() {
+        let mut shaper = TextShaper::new();
+        let Some(font) = shaper.font_manager().get_font(0).cloned() else {
+            return;
+        };
+        if font.lookup_glyph_index('ש') == 0 {
+            return;
+        }
+
+        // A Hebrew word inside English text is reversed in place
+        let text = "ab שלום cd";
+        let shaped = shaper.shape_paragraph(text, 1000.0, 16.0);
+        assert_eq!(shaped.direction, TextDirection::Ltr);
+        let visual: String = shaped.clusters.iter().map(|c| &text[c.byte_offset..c.byte_offset + c.byte_len]).collect();
+        assert_eq!(visual, "ab םולש cd");
+        assert!(shaped.clusters.windows(2).all(|c| c[1].x == c[0].x + c[0].advance));
+
+        // Carets in the Hebrew run sit on each character's right side
+        let shin = text.find('ש').unwrap();
+        let cluster = shaped.clusters.iter().find(|c| c.byte_offset == shin).unwrap();
+        assert!(cluster.rtl);
+        assert_eq!(shaped.offset_to_position(shin).0, cluster.x + cluster.advance);
+        assert_eq!(shaped.hit_test(cluster.x + cluster.advance - 0.1, 1.0), shin);
+
+        // An all-Hebrew paragraph runs right to left: its start is on the right
+        let text = "שלום";
+        let shaped = shaper.shape_paragraph(text, 1000.0, 16.0);
+        assert_eq!(shaped.direction, TextDirection::Rtl);
+        assert_eq!(shaped.hit_test(1000.0, 1.0), 0);
+        assert_eq!(shaped.hit_test(-1.0, 1.0), text.len());
+        assert_eq!(shaped.offset_to_position(text.len()).0, 0.0);
+
+        // Drawn runs use the same visual order
+        let run = shaper.font_manager().layout_run(text, 16.0, 0, TextSpacing::default());
+        assert_eq!(run.glyphs[0].key.1, font.lookup_glyph_index('ם'));
+    }
+}
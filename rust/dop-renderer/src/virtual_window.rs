@@ -0,0 +1,150 @@
+//! Virtual window for headless interaction tests
+//!
+//! A `VirtualWindow` has no platform window or event loop. Tests inject
+//! scripted events with timestamps and advance a virtual clock with
+//! [`VirtualWindow::step`]; events become visible in timestamp order and
+//! every step ends with a `Redraw` event, mirroring one iteration of the
+//! real event loop. Scroll input runs through the same [`ScrollProcessor`]
+//! as `DopApp`, driven by the virtual clock, so coalescing and momentum are
+//! reproducible.
+
+use std::time::{Duration, Instant};
+
+use crate::scroll::ScrollProcessor;
+use crate::window::{DopEvent, EventType, WindowConfig};
+
+/// Headless window driven by scripted events and a virtual clock
+pub struct VirtualWindow {
+    config: WindowConfig,
+    /// Scripted events sorted by timestamp (seconds)
+    script: Vec<DopEvent>,
+    events: Vec<DopEvent>,
+    scroll: ScrollProcessor,
+    epoch: Instant,
+    time: f64,
+    is_open: bool,
+    mouse_x: f64,
+    mouse_y: f64,
+}
+
+impl VirtualWindow {
+    pub fn new(config: WindowConfig) -> Self {
+        let scroll = ScrollProcessor::new();
+        scroll
+            .kinetic_flag()
+            .store(config.kinetic_scrolling, std::sync::atomic::Ordering::Relaxed);
+        Self {
+            config,
+            script: Vec::new(),
+            events: Vec::new(),
+            scroll,
+            epoch: Instant::now(),
+            time: 0.0,
+            is_open: true,
+            mouse_x: 0.0,
+            mouse_y: 0.0,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.config.width, self.config.height)
+    }
+
+    pub fn mouse_position(&self) -> (f64, f64) {
+        (self.mouse_x, self.mouse_y)
+    }
+
+    /// Current virtual time in seconds
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn scroll_processor_mut(&mut self) -> &mut ScrollProcessor {
+        &mut self.scroll
+    }
+
+    /// Number of scripted events not yet delivered
+    pub fn pending(&self) -> usize {
+        self.script.len()
+    }
+
+    /// Schedule an event at `at` seconds of virtual time
+    ///
+    /// Events with equal timestamps are delivered in injection order.
+    pub fn inject(&mut self, mut event: DopEvent, at: f64) {
+        event.timestamp = at.max(self.time);
+        let idx = self
+            .script
+            .partition_point(|e| e.timestamp <= event.timestamp);
+        self.script.insert(idx, event);
+    }
+
+    /// Advance the virtual clock by `dt` seconds and deliver due events
+    ///
+    /// Returns the number of events produced by this step, including the
+    /// trailing `Redraw`.
+    pub fn step(&mut self, dt: f64) -> usize {
+        let before = self.events.len();
+        self.time += dt.max(0.0);
+        let now = self.epoch + Duration::from_secs_f64(self.time);
+
+        let due = self.script.partition_point(|e| e.timestamp <= self.time);
+        let delivered: Vec<DopEvent> = self.script.drain(..due).collect();
+        for event in delivered {
+            self.apply(event);
+        }
+
+        for mut event in self.scroll.tick(self.mouse_x, self.mouse_y, now) {
+            event.timestamp = self.time;
+            self.events.push(event);
+        }
+
+        if self.is_open {
+            let mut redraw = DopEvent::redraw();
+            redraw.timestamp = self.time;
+            self.events.push(redraw);
+        }
+
+        self.events.len() - before
+    }
+
+    /// Update window state for a delivered event and queue it for the host
+    fn apply(&mut self, event: DopEvent) {
+        let at = self.epoch + Duration::from_secs_f64(event.timestamp);
+        match event.event_type {
+            EventType::Close => self.is_open = false,
+            EventType::Resize => {
+                self.config.width = event.width.max(0) as u32;
+                self.config.height = event.height.max(0) as u32;
+            }
+            EventType::MouseMove => {
+                self.mouse_x = event.x;
+                self.mouse_y = event.y;
+            }
+            EventType::MouseScroll => {
+                // Scripted scrolls are treated as precise trackpad input and
+                // surface through the coalescing pass like real ones
+                self.mouse_x = event.x;
+                self.mouse_y = event.y;
+                self.scroll.push(event.scroll_x, event.scroll_y, true, at);
+                return;
+            }
+            _ => {}
+        }
+        self.events.push(event);
+    }
+
+    /// End the current scripted scroll gesture, starting momentum if enabled
+    pub fn release_scroll(&mut self) {
+        let now = self.epoch + Duration::from_secs_f64(self.time);
+        self.scroll.release(now);
+    }
+
+    pub fn poll_events(&mut self) -> Vec<DopEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
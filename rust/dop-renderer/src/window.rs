@@ -2,15 +2,26 @@
 //!
 //! Provides cross-platform window creation and event handling.
 
-use std::sync::{Arc, Mutex};
+use std::collections::VecDeque;
+use std::sync::{mpsc, Arc, Mutex};
 use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::caret::SharedCaret;
+use crate::ffi_guard::LockExt;
+use crate::renderer::GpuFrame;
+use crate::scroll::ScrollProcessor;
+#[cfg(feature = "accessibility")]
+use crate::accessibility::{AccessibilityBridge, BridgeHandler, SharedAccessibility};
 use winit::{
     application::ApplicationHandler,
-    dpi::LogicalSize,
-    event::{ElementState, MouseButton, WindowEvent as WinitWindowEvent},
+    dpi::{LogicalSize, PhysicalPosition, PhysicalSize, Size},
+    event::{
+        DeviceEvent, DeviceId, ElementState, Force, Ime, MouseButton, Touch, TouchPhase,
+        WindowEvent as WinitWindowEvent,
+    },
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
     keyboard::{Key, NamedKey},
-    window::{CursorIcon, Window, WindowAttributes, WindowId},
+    window::{CursorIcon, Fullscreen, Window, WindowAttributes, WindowId},
 };
 
 /// Window configuration options
@@ -26,6 +37,10 @@ pub struct WindowConfig {
     pub min_height: u32,
     pub max_width: u32,
     pub max_height: u32,
+    /// Synthesize momentum scroll events after trackpad gestures end
+    pub kinetic_scrolling: bool,
+    /// Interpret the width/height and minimum size as physical pixels instead of logical pixels
+    pub physical_size: bool,
 }
 
 impl Default for WindowConfig {
@@ -41,6 +56,8 @@ impl Default for WindowConfig {
             min_height: 1,
             max_width: u32::MAX,
             max_height: u32::MAX,
+            kinetic_scrolling: false,
+            physical_size: false,
         }
     }
 }
@@ -64,7 +81,24 @@ pub enum EventType {
     MouseLeave = 12,
     Focus = 13,
     Blur = 14,
+    /// A frame was presented; the host can produce the next one (paced by vsync)
     Redraw = 15,
+    /// Assistive technology requested an action (`key` = action code, `char_code` = node ID)
+    AccessibilityAction = 16,
+    /// Assistive technology attached; the host should publish an accessibility tree
+    AccessibilityActivated = 17,
+    /// Synthesized kinetic scroll after a trackpad gesture (deltas in pixels)
+    MomentumScroll = 18,
+    /// Unaccelerated relative mouse motion (`x`/`y` hold the delta in device units)
+    RawMouseMotion = 19,
+    /// Pen/stylus contact with pressure, tilt and eraser state
+    Pen = 20,
+    /// The display scale factor changed (`scale_factor`); also sent once when the window opens
+    ScaleFactorChanged = 21,
+    /// IME composition text changed (`char_code` = text ID, `key`..`scancode` = cursor byte range, -1 if hidden)
+    ImePreedit = 22,
+    /// IME committed text to insert (`char_code` = text ID)
+    ImeCommit = 23,
 }
 
 /// Mouse button identifiers
@@ -100,6 +134,17 @@ pub mod modifiers {
     pub const SUPER: u8 = 8;
 }
 
+/// Pen state flags carried in `pen_flags`
+pub mod pen {
+    pub const NONE: u32 = 0;
+    /// The pen tip is touching the surface
+    pub const CONTACT: u32 = 1;
+    /// The eraser end is in use
+    pub const ERASER: u32 = 2;
+    /// The contact was cancelled by the platform
+    pub const CANCELLED: u32 = 4;
+}
+
 /// A window event with associated data
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
@@ -117,6 +162,14 @@ pub struct DopEvent {
     pub width: i32,
     pub height: i32,
     pub timestamp: f64,
+    /// Normalized pen pressure (0.0 to 1.0)
+    pub pressure: f32,
+    /// Pen tilt from vertical along the X and Y axes, in degrees
+    pub tilt_x: f32,
+    pub tilt_y: f32,
+    pub pen_flags: u32,
+    /// Physical pixels per logical pixel, for `ScaleFactorChanged`
+    pub scale_factor: f64,
 }
 
 impl Default for DopEvent {
@@ -135,6 +188,11 @@ impl Default for DopEvent {
             width: 0,
             height: 0,
             timestamp: 0.0,
+            pressure: 0.0,
+            tilt_x: 0.0,
+            tilt_y: 0.0,
+            pen_flags: pen::NONE,
+            scale_factor: 0.0,
         }
     }
 }
@@ -229,6 +287,66 @@ impl DopEvent {
         }
     }
 
+    pub fn momentum_scroll(x: f64, y: f64, scroll_x: f64, scroll_y: f64) -> Self {
+        Self {
+            event_type: EventType::MomentumScroll,
+            x,
+            y,
+            scroll_x,
+            scroll_y,
+            ..Default::default()
+        }
+    }
+
+    pub fn raw_mouse_motion(dx: f64, dy: f64) -> Self {
+        Self {
+            event_type: EventType::RawMouseMotion,
+            x: dx,
+            y: dy,
+            ..Default::default()
+        }
+    }
+
+    pub fn pen(x: f64, y: f64, pressure: f32, tilt_x: f32, tilt_y: f32, pen_flags: u32) -> Self {
+        Self {
+            event_type: EventType::Pen,
+            x,
+            y,
+            pressure,
+            tilt_x,
+            tilt_y,
+            pen_flags,
+            ..Default::default()
+        }
+    }
+
+    pub fn scale_factor_changed(scale_factor: f64) -> Self {
+        Self {
+            event_type: EventType::ScaleFactorChanged,
+            scale_factor,
+            ..Default::default()
+        }
+    }
+
+    pub fn ime_preedit(text_id: u32, cursor: Option<(usize, usize)>) -> Self {
+        let (start, end) = cursor.map_or((-1, -1), |(s, e)| (s as i32, e as i32));
+        Self {
+            event_type: EventType::ImePreedit,
+            char_code: text_id,
+            key: start,
+            scancode: end,
+            ..Default::default()
+        }
+    }
+
+    pub fn ime_commit(text_id: u32) -> Self {
+        Self {
+            event_type: EventType::ImeCommit,
+            char_code: text_id,
+            ..Default::default()
+        }
+    }
+
     pub fn mouse_enter() -> Self {
         Self {
             event_type: EventType::MouseEnter,
@@ -256,6 +374,69 @@ impl DopEvent {
             ..Default::default()
         }
     }
+
+    pub fn accessibility_action(action: i32, node_id: u32) -> Self {
+        Self {
+            event_type: EventType::AccessibilityAction,
+            key: action,
+            char_code: node_id,
+            ..Default::default()
+        }
+    }
+
+    pub fn accessibility_activated() -> Self {
+        Self {
+            event_type: EventType::AccessibilityActivated,
+            ..Default::default()
+        }
+    }
+}
+
+/// Number of IME strings kept for the host to fetch
+pub const IME_TEXT_CAPACITY: usize = 256;
+
+/// Strings carried by IME events, looked up by the text ID in `DopEvent::char_code`
+///
+/// `DopEvent` is `Copy` and cannot own a string, so the composition and
+/// committed text live here; only the most recent `IME_TEXT_CAPACITY` are kept.
+#[derive(Debug, Default)]
+pub struct ImeTextStore {
+    texts: VecDeque<(u32, String)>,
+    next_id: u32,
+}
+
+impl ImeTextStore {
+    /// Store a string and return its ID (never 0)
+    pub fn push(&mut self, text: String) -> u32 {
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        if self.texts.len() == IME_TEXT_CAPACITY {
+            self.texts.pop_front();
+        }
+        self.texts.push_back((self.next_id, text));
+        self.next_id
+    }
+
+    /// Get a stored string
+    pub fn get(&self, id: u32) -> Option<&str> {
+        self.texts.iter().find(|(i, _)| *i == id).map(|(_, t)| t.as_str())
+    }
+}
+
+/// IME text store shared between a window and its host
+pub type SharedImeText = Arc<Mutex<ImeTextStore>>;
+
+/// A request from the host, applied to the window on its event loop thread
+#[derive(Debug, Clone, PartialEq)]
+pub enum WindowCommand {
+    SetTitle(String),
+    /// Request a new inner size in physical pixels
+    SetSize(u32, u32),
+    /// Borderless fullscreen on the current monitor, or windowed
+    SetFullscreen(bool),
+    SetMinimized(bool),
+    SetMaximized(bool),
+    /// Place the IME candidate window at the caret (bottom-left, physical pixels)
+    SetImePosition(f64, f64),
 }
 
 /// Window handle that wraps winit Window
@@ -309,18 +490,68 @@ impl WindowHandle {
         }
     }
 
+    /// Physical pixels per logical pixel of the display the window is on (1.0 before it opens)
+    pub fn scale_factor(&self) -> f64 {
+        self.window.as_ref().map_or(1.0, |w| w.scale_factor())
+    }
+
+    /// Place the IME candidate window at the caret (bottom-left, in physical pixels)
+    pub fn set_ime_position(&self, x: f64, y: f64) {
+        if let Some(window) = &self.window {
+            window.set_ime_cursor_area(PhysicalPosition::new(x, y), PhysicalSize::new(1, 1));
+        }
+    }
+
     pub fn set_title(&self, title: &str) {
         if let Some(window) = &self.window {
             window.set_title(title);
         }
     }
 
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        if let Some(window) = &self.window {
+            window.set_fullscreen(fullscreen.then_some(Fullscreen::Borderless(None)));
+        }
+    }
+
+    pub fn set_minimized(&self, minimized: bool) {
+        if let Some(window) = &self.window {
+            window.set_minimized(minimized);
+        }
+    }
+
+    pub fn set_maximized(&self, maximized: bool) {
+        if let Some(window) = &self.window {
+            window.set_maximized(maximized);
+        }
+    }
+
+    /// Apply a host request to the window
+    pub fn apply(&self, command: WindowCommand) {
+        match command {
+            WindowCommand::SetTitle(title) => self.set_title(&title),
+            WindowCommand::SetSize(width, height) => self.set_size(width, height),
+            WindowCommand::SetFullscreen(fullscreen) => self.set_fullscreen(fullscreen),
+            WindowCommand::SetMinimized(minimized) => self.set_minimized(minimized),
+            WindowCommand::SetMaximized(maximized) => self.set_maximized(maximized),
+            WindowCommand::SetImePosition(x, y) => self.set_ime_position(x, y),
+        }
+    }
+
     pub fn set_cursor(&self, cursor: CursorIcon) {
         if let Some(window) = &self.window {
             window.set_cursor(cursor);
         }
     }
 
+    /// Tell the platform a frame is about to be presented, so it can pace redraws
+    /// to the display (frame callbacks on Wayland)
+    pub fn pre_present_notify(&self) {
+        if let Some(window) = &self.window {
+            window.pre_present_notify();
+        }
+    }
+
     pub fn request_redraw(&self) {
         if let Some(window) = &self.window {
             window.request_redraw();
@@ -388,12 +619,114 @@ fn key_to_code(key: &Key) -> i32 {
     }
 }
 
+/// Convert a winit touch carrying force data into a pen event
+///
+/// winit reports stylus input as touches with a force reading (Windows pen
+/// pointers, Apple Pencil). Plain finger touches without force are ignored.
+/// winit does not expose the eraser end or azimuth, so tilt is only derived
+/// from the altitude angle along the X axis and the eraser flag is never set.
+fn pen_event(touch: &Touch) -> Option<DopEvent> {
+    let force = touch.force?;
+    let tilt_x = match force {
+        Force::Calibrated {
+            altitude_angle: Some(altitude),
+            ..
+        } => 90.0 - altitude.to_degrees() as f32,
+        _ => 0.0,
+    };
+    let flags = match touch.phase {
+        TouchPhase::Started | TouchPhase::Moved => pen::CONTACT,
+        TouchPhase::Ended => pen::NONE,
+        TouchPhase::Cancelled => pen::CANCELLED,
+    };
+    Some(DopEvent::pen(
+        touch.location.x,
+        touch.location.y,
+        force.normalized() as f32,
+        tilt_x,
+        0.0,
+        flags,
+    ))
+}
+
+/// Host-provided RGBA frame presented by a window
+pub struct ExternalFrame {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    /// Area (x, y, width, height) changed since the frame was last presented
+    pub damage: Option<(u32, u32, u32, u32)>,
+}
+
+impl ExternalFrame {
+    /// A frame that is entirely new
+    pub fn new(data: Vec<u8>, width: u32, height: u32) -> Self {
+        Self {
+            data,
+            width,
+            height,
+            damage: Some((0, 0, width, height)),
+        }
+    }
+
+    /// Whole pixels (x, y, width, height) of a float rectangle that lie inside the frame
+    pub fn pixel_rect(&self, [x, y, width, height]: [f32; 4]) -> Option<(u32, u32, u32, u32)> {
+        let x0 = x.round().clamp(0.0, self.width as f32) as u32;
+        let y0 = y.round().clamp(0.0, self.height as f32) as u32;
+        let x1 = (x + width.max(1.0)).round().clamp(0.0, self.width as f32) as u32;
+        let y1 = (y + height).round().clamp(0.0, self.height as f32) as u32;
+        (x1 > x0 && y1 > y0).then_some((x0, y0, x1 - x0, y1 - y0))
+    }
+
+    /// Blend a solid RGBA color over an area from `pixel_rect`, returning the pixels it covered
+    pub fn overlay_rect(&mut self, (x, y, width, height): (u32, u32, u32, u32), color: [u8; 4]) -> Vec<u8> {
+        let stride = self.width as usize * 4;
+        let (start, len) = (x as usize * 4, width as usize * 4);
+        let coverage = vec![255; width as usize];
+        let mut saved = Vec::with_capacity(len * height as usize);
+        for row in self.data.chunks_exact_mut(stride).skip(y as usize).take(height as usize) {
+            let span = &mut row[start..start + len];
+            saved.extend_from_slice(span);
+            crate::blend::blend_color_row(span, color, &coverage);
+        }
+        saved
+    }
+
+    /// Put back the pixels returned by `overlay_rect` for the same area
+    pub fn restore_rect(&mut self, (x, y, width, height): (u32, u32, u32, u32), saved: &[u8]) {
+        let stride = self.width as usize * 4;
+        let (start, len) = (x as usize * 4, width as usize * 4);
+        let rows = self.data.chunks_exact_mut(stride).skip(y as usize).take(height as usize);
+        for (row, saved) in rows.zip(saved.chunks_exact(len)) {
+            row[start..start + len].copy_from_slice(saved);
+        }
+    }
+
+    /// Grow the damage to also cover the (x, y, width, height) area
+    pub fn add_damage(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        self.damage = Some(match self.damage {
+            Some((dx, dy, dw, dh)) => {
+                let (x0, y0) = (dx.min(x), dy.min(y));
+                let x1 = (dx + dw).max(x + width);
+                let y1 = (dy + dh).max(y + height);
+                (x0, y0, x1 - x0, y1 - y0)
+            }
+            None => (x, y, width, height),
+        });
+    }
+}
+
+/// Host-provided framebuffer presented by a window
+pub type SharedFramebuffer = Arc<Mutex<Option<ExternalFrame>>>;
+
 /// Application handler for winit event loop
 pub struct DopApp {
     handle: Option<WindowHandle>,
     renderer: Option<crate::renderer::WgpuRenderer>,
     event_queue: Option<Arc<Mutex<Vec<DopEvent>>>>,
-    external_framebuffer: Option<Arc<Mutex<Option<(Vec<u8>, u32, u32)>>>>,
+    external_framebuffer: Option<SharedFramebuffer>,
+    // Host-recorded rect/text commands drawn natively instead of an external framebuffer
+    gpu_frame: Option<Arc<Mutex<Option<GpuFrame>>>>,
     // When resizing, some platforms emit a rapid stream of `Resized` events.
     // To avoid reconfiguring the GPU surface on every single event (which
     // causes stutters), we store a pending resize and apply it once during
@@ -405,35 +738,174 @@ pub struct DopApp {
     // throttle repeated reconfigurations when the platform issues many
     // resize events in quick succession (e.g. during interactive drags).
     last_resize_time: Option<Instant>,
+    // Scroll events are summed per event loop iteration and may coast
+    // with synthesized momentum after the gesture ends.
+    scroll: ScrollProcessor,
+    // Raw device motion summed since the last event loop iteration
+    pending_motion: Option<(f64, f64)>,
+    // Closing the window ends the event loop unless other windows share it
+    exit_on_close: bool,
+    // Latest display scale factor, shared with the host
+    scale_factor: Option<Arc<Mutex<f64>>>,
+    // Text of IME events
+    ime_text: SharedImeText,
+    // Requests from the host (title, size, fullscreen, ...) applied on wakeup
+    commands: Option<mpsc::Receiver<WindowCommand>>,
+    // Caret blended over the external framebuffer, blinking on this thread's clock
+    caret: Option<SharedCaret>,
+    // Caret bar (device pixels) included in the last present
+    presented_caret: Option<[f32; 4]>,
+    // Accessibility tree shared with the host and the platform adapter
+    #[cfg(feature = "accessibility")]
+    accessibility: SharedAccessibility,
+    #[cfg(feature = "accessibility")]
+    accessibility_adapter: Option<accesskit_winit::Adapter>,
 }
 
 impl DopApp {
     pub fn new(config: WindowConfig) -> Self {
-        Self {
+        let kinetic = config.kinetic_scrolling;
+        let app = Self {
             handle: Some(WindowHandle::new(config)),
             renderer: None,
             event_queue: None,
             external_framebuffer: None,
+            gpu_frame: None,
             pending_resize: None,
             last_resize_time: None,
-        }
+            scroll: ScrollProcessor::new(),
+            pending_motion: None,
+            exit_on_close: true,
+            scale_factor: None,
+            ime_text: SharedImeText::default(),
+            commands: None,
+            caret: None,
+            presented_caret: None,
+            #[cfg(feature = "accessibility")]
+            accessibility: AccessibilityBridge::new_shared(),
+            #[cfg(feature = "accessibility")]
+            accessibility_adapter: None,
+        };
+        app.scroll.kinetic_flag().store(kinetic, Ordering::Relaxed);
+        app
     }
 
     pub fn new_with_shared_events(
         config: WindowConfig,
         event_queue: Arc<Mutex<Vec<DopEvent>>>,
-        external_framebuffer: Option<Arc<Mutex<Option<(Vec<u8>, u32, u32)>>>>,
+        external_framebuffer: Option<SharedFramebuffer>,
     ) -> Self {
-        Self {
+        let kinetic = config.kinetic_scrolling;
+        let app = Self {
             handle: Some(WindowHandle::new(config)),
             renderer: None,
             event_queue: Some(event_queue),
             external_framebuffer,
+            gpu_frame: None,
             pending_resize: None,
             last_resize_time: None,
+            scroll: ScrollProcessor::new(),
+            pending_motion: None,
+            exit_on_close: true,
+            scale_factor: None,
+            ime_text: SharedImeText::default(),
+            commands: None,
+            caret: None,
+            presented_caret: None,
+            #[cfg(feature = "accessibility")]
+            accessibility: AccessibilityBridge::new_shared(),
+            #[cfg(feature = "accessibility")]
+            accessibility_adapter: None,
+        };
+        app.scroll.kinetic_flag().store(kinetic, Ordering::Relaxed);
+        app
+    }
+
+    /// Share a kinetic scrolling flag with the host so it can be toggled at runtime
+    /// Draw frames submitted by the host with the GPU pipeline
+    pub fn set_gpu_frame_source(&mut self, frames: Arc<Mutex<Option<GpuFrame>>>) {
+        self.gpu_frame = Some(frames);
+    }
+
+    /// Keep a host-visible copy of the display scale factor up to date
+    pub fn set_scale_factor_source(&mut self, scale_factor: Arc<Mutex<f64>>) {
+        self.scale_factor = Some(scale_factor);
+    }
+
+    /// Share the IME text store with the host
+    pub fn set_ime_text_store(&mut self, text: SharedImeText) {
+        self.ime_text = text;
+    }
+
+    /// Receive window commands from the host; they are applied when the event loop is woken
+    pub fn set_command_source(&mut self, commands: mpsc::Receiver<WindowCommand>) {
+        self.commands = Some(commands);
+    }
+
+    /// Blink a caret shared with a renderer over the presented external framebuffer
+    pub fn set_caret_source(&mut self, caret: SharedCaret) {
+        self.caret = Some(caret);
+    }
+
+    /// Caret bar and sRGB color to show at `now`, if any
+    fn visible_caret(&self, now: Instant) -> Option<([f32; 4], [f32; 4])> {
+        let caret = (*self.caret.as_ref()?.lock_or_recover())?;
+        caret.is_visible_at(now).then(|| (caret.rect(), caret.color))
+    }
+
+    /// Text referenced by IME events
+    pub fn ime_text(&self) -> &SharedImeText {
+        &self.ime_text
+    }
+
+    pub fn set_kinetic_scrolling_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.scroll.set_kinetic_flag(flag);
+    }
+
+    pub fn scroll_processor(&self) -> &ScrollProcessor {
+        &self.scroll
+    }
+
+    pub fn scroll_processor_mut(&mut self) -> &mut ScrollProcessor {
+        &mut self.scroll
+    }
+
+    /// Flush coalesced scrolling and advance momentum for this iteration
+    fn flush_scroll(&mut self) {
+        let Some((x, y)) = self.handle.as_ref().map(|h| h.mouse_position()) else {
+            return;
+        };
+        for event in self.scroll.tick(x, y, Instant::now()) {
+            self.push_event(event);
         }
     }
 
+    /// Share an accessibility bridge with the host (must be set before the window is created)
+    #[cfg(feature = "accessibility")]
+    pub fn set_accessibility(&mut self, bridge: SharedAccessibility) {
+        self.accessibility = bridge;
+    }
+
+    /// Accessibility bridge used to publish trees and receive action requests
+    #[cfg(feature = "accessibility")]
+    pub fn accessibility(&self) -> &SharedAccessibility {
+        &self.accessibility
+    }
+
+    /// Keep the event loop running when the window is closed (for shared event loops)
+    pub fn set_exit_on_close(&mut self, exit: bool) {
+        self.exit_on_close = exit;
+    }
+
+    pub fn handle(&self) -> Option<&WindowHandle> {
+        self.handle.as_ref()
+    }
+
+    /// ID of the created window, None before `resumed` created it
+    pub fn window_id(&self) -> Option<WindowId> {
+        self.handle.as_ref()?.window().map(|w| w.id())
+    }
+
     pub fn take_handle(&mut self) -> Option<WindowHandle> {
         self.handle.take()
     }
@@ -452,6 +924,40 @@ impl DopApp {
             handle.push_event(event);
         }
     }
+
+    /// Record a new scale factor and tell the host
+    fn scale_factor_changed(&mut self, scale_factor: f64) {
+        if let Some(shared) = &self.scale_factor {
+            if let Ok(mut value) = shared.lock() {
+                *value = scale_factor;
+            }
+        }
+        self.push_event(DopEvent::scale_factor_changed(scale_factor));
+    }
+
+    /// Store IME text and push the event referencing it
+    fn push_ime_text(&mut self, text: String, event: impl FnOnce(u32) -> DopEvent) {
+        let id = match self.ime_text.lock() {
+            Ok(mut store) => store.push(text),
+            Err(_) => return,
+        };
+        self.push_event(event(id));
+    }
+
+    /// Push pending tree updates to the platform and forward queued action requests
+    #[cfg(feature = "accessibility")]
+    fn sync_accessibility(&mut self) {
+        let (update, events) = match self.accessibility.lock() {
+            Ok(mut bridge) => (bridge.take_pending(), bridge.take_events()),
+            Err(_) => return,
+        };
+        if let (Some(update), Some(adapter)) = (update, &mut self.accessibility_adapter) {
+            adapter.update_if_active(|| update);
+        }
+        for event in events {
+            self.push_event(event);
+        }
+    }
 }
 
 impl ApplicationHandler for DopApp {
@@ -463,18 +969,45 @@ impl ApplicationHandler for DopApp {
         let handle = self.handle.as_ref().unwrap();
         let config = &handle.config;
 
+        let size = |width: u32, height: u32| -> Size {
+            if config.physical_size {
+                PhysicalSize::new(width, height).into()
+            } else {
+                LogicalSize::new(width, height).into()
+            }
+        };
         let window_attrs = WindowAttributes::default()
             .with_title(&config.title)
-            .with_inner_size(LogicalSize::new(config.width, config.height))
+            .with_inner_size(size(config.width, config.height))
             .with_resizable(config.resizable)
             .with_decorations(config.decorated)
             .with_transparent(config.transparent)
-            .with_min_inner_size(LogicalSize::new(config.min_width, config.min_height));
+            .with_min_inner_size(size(config.min_width, config.min_height));
+
+        // The accesskit adapter must exist before the window is first shown
+        #[cfg(feature = "accessibility")]
+        let window_attrs = window_attrs.with_visible(false);
 
         match event_loop.create_window(window_attrs) {
             Ok(window) => {
+                #[cfg(feature = "accessibility")]
+                {
+                    self.accessibility_adapter = Some(accesskit_winit::Adapter::with_direct_handlers(
+                        event_loop,
+                        &window,
+                        BridgeHandler::new(self.accessibility.clone()),
+                        BridgeHandler::new(self.accessibility.clone()),
+                        BridgeHandler::new(self.accessibility.clone()),
+                    ));
+                    window.set_visible(true);
+                }
+
+                // Let input methods compose text (CJK and other complex scripts)
+                window.set_ime_allowed(true);
+
                 let window = Arc::new(window);
                 let size = window.inner_size();
+                let scale_factor = window.scale_factor();
 
                 // Create renderer (handle initialization failures safely)
                 let renderer =
@@ -489,6 +1022,7 @@ impl ApplicationHandler for DopApp {
                     handle.window = Some(window);
                 }
                 self.push_event(DopEvent::resize(size.width, size.height));
+                self.scale_factor_changed(scale_factor);
                 self.renderer = renderer;
             }
             Err(e) => {
@@ -504,9 +1038,41 @@ impl ApplicationHandler for DopApp {
         // provided by the host can be presented.
         if let Some(handle) = &self.handle {
             handle.request_redraw();
+            if let Some(commands) = &self.commands {
+                for command in commands.try_iter() {
+                    handle.apply(command);
+                }
+            }
+        }
+    }
+
+    fn device_event(
+        &mut self,
+        _event_loop: &ActiveEventLoop,
+        _device_id: DeviceId,
+        event: DeviceEvent,
+    ) {
+        if let DeviceEvent::MouseMotion { delta: (dx, dy) } = event {
+            let (px, py) = self.pending_motion.unwrap_or((0.0, 0.0));
+            self.pending_motion = Some((px + dx, py + dy));
         }
     }
 
+    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some((dx, dy)) = self.pending_motion.take() {
+            self.push_event(DopEvent::raw_mouse_motion(dx, dy));
+        }
+        self.flush_scroll();
+        // Present again when the caret blinks
+        if self.caret.is_some() && self.visible_caret(Instant::now()).map(|(rect, _)| rect) != self.presented_caret {
+            if let Some(handle) = &self.handle {
+                handle.request_redraw();
+            }
+        }
+        #[cfg(feature = "accessibility")]
+        self.sync_accessibility();
+    }
+
     fn window_event(
         &mut self,
         event_loop: &ActiveEventLoop,
@@ -520,13 +1086,23 @@ impl ApplicationHandler for DopApp {
             return;
         };
 
+        #[cfg(feature = "accessibility")]
+        if let (Some(adapter), Some(window)) = (
+            &mut self.accessibility_adapter,
+            self.handle.as_ref().and_then(|h| h.window.as_ref()),
+        ) {
+            adapter.process_event(window, &event);
+        }
+
         match event {
             WinitWindowEvent::CloseRequested => {
                 self.push_event(DopEvent::close());
                 if let Some(handle) = &mut self.handle {
                     handle.is_open = false;
                 }
-                event_loop.exit();
+                if self.exit_on_close {
+                    event_loop.exit();
+                }
             }
             WinitWindowEvent::Resized(size) => {
                 // Coalesce frequent Resized events. We do NOT immediately notify
@@ -540,9 +1116,11 @@ impl ApplicationHandler for DopApp {
                     size.height
                 );
             }
+            WinitWindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                // The platform follows up with a Resized event for the new physical size
+                self.scale_factor_changed(scale_factor);
+            }
             WinitWindowEvent::RedrawRequested => {
-                self.push_event(DopEvent::redraw());
-
                 // Query current logical size from the handle (no borrow of renderer yet)
                 let (width, height) = if let Some(handle) = &self.handle {
                     handle.get_size()
@@ -588,21 +1166,55 @@ impl ApplicationHandler for DopApp {
                     // If we deferred (too soon), keep pending_resize for next redraw.
                 }
 
+                if let Some(handle) = &self.handle {
+                    handle.pre_present_notify();
+                }
+
+                let caret = self.visible_caret(Instant::now());
+
                 // Now do presenting/rendering with a mutable borrow of renderer.
                 if let Some(renderer) = &mut self.renderer {
-                    // If an external CPU framebuffer was provided, present it
+                    // A newly submitted GPU frame replaces the commands; otherwise the last one is redrawn
+                    if let Some(frame) = self.gpu_frame.as_ref().and_then(|f| f.lock().ok()?.take()) {
+                        renderer.submit_frame(frame);
+                    }
+
+                    // If an external CPU framebuffer was provided, present it. The frame is
+                    // kept so region updates can patch it and redraws can present it again.
                     let mut presented = false;
                     if let Some(ext) = &self.external_framebuffer {
                         log::debug!("window: attempting to lock external_framebuffer");
                         if let Ok(mut guard) = ext.lock() {
-                            if let Some((buf, w, h)) = guard.take() {
+                            if let Some(frame) = guard.as_mut() {
                                 log::debug!(
                                     "window: received external framebuffer {}x{} (data_len={})",
-                                    w,
-                                    h,
-                                    buf.len()
+                                    frame.width,
+                                    frame.height,
+                                    frame.data.len()
                                 );
-                                match renderer.present_rgba(&buf, w, h) {
+                                // The caret is blended in for this present only; its area is
+                                // uploaded again when it blinks or the frame changed
+                                let shown = caret.map(|(rect, _)| rect);
+                                if shown != self.presented_caret || frame.damage.is_some() {
+                                    for rect in [self.presented_caret, shown].into_iter().flatten() {
+                                        if let Some((x, y, w, h)) = frame.pixel_rect(rect) {
+                                            frame.add_damage(x, y, w, h);
+                                        }
+                                    }
+                                }
+                                let overlay = caret.and_then(|(rect, color)| {
+                                    let area = frame.pixel_rect(rect)?;
+                                    let color = color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+                                    Some((area, frame.overlay_rect(area, color)))
+                                });
+                                self.presented_caret = shown;
+                                // Only the area changed since the last present is uploaded
+                                let damage = frame.damage.take();
+                                let result = renderer.present_rgba_region(&frame.data, frame.width, frame.height, damage);
+                                if let Some((area, saved)) = overlay {
+                                    frame.restore_rect(area, &saved);
+                                }
+                                match result {
                                     Ok(_) => presented = true,
                                     Err(wgpu::SurfaceError::Lost) => {
                                         // Try to recover the surface but avoid repeated
@@ -660,6 +1272,9 @@ impl ApplicationHandler for DopApp {
                         }
                     }
                 }
+
+                // Sent after presenting so hosts that draw on Redraw run at the display rate
+                self.push_event(DopEvent::redraw());
             }
             WinitWindowEvent::KeyboardInput { event, .. } => {
                 let key_code = key_to_code(&event.logical_key);
@@ -681,6 +1296,15 @@ impl ApplicationHandler for DopApp {
                     }
                 }
             }
+            WinitWindowEvent::Ime(ime) => match ime {
+                Ime::Preedit(text, cursor) => {
+                    self.push_ime_text(text, |id| DopEvent::ime_preedit(id, cursor));
+                }
+                Ime::Commit(text) => {
+                    self.push_ime_text(text, DopEvent::ime_commit);
+                }
+                Ime::Enabled | Ime::Disabled => {}
+            },
             WinitWindowEvent::ModifiersChanged(state) => {
                 let state = state.state();
                 let mut mods = modifiers::NONE;
@@ -718,12 +1342,24 @@ impl ApplicationHandler for DopApp {
                     }
                 }
             }
-            WinitWindowEvent::MouseWheel { delta, .. } => {
-                let (dx, dy) = match delta {
-                    winit::event::MouseScrollDelta::LineDelta(x, y) => (x as f64, y as f64),
-                    winit::event::MouseScrollDelta::PixelDelta(pos) => (pos.x, pos.y),
+            WinitWindowEvent::MouseWheel { delta, phase, .. } => {
+                let (dx, dy, precise) = match delta {
+                    winit::event::MouseScrollDelta::LineDelta(x, y) => (x as f64, y as f64, false),
+                    winit::event::MouseScrollDelta::PixelDelta(pos) => (pos.x, pos.y, true),
                 };
-                self.push_event(DopEvent::mouse_scroll(mouse_x, mouse_y, dx, dy));
+                let now = Instant::now();
+                if phase == TouchPhase::Started {
+                    self.scroll.cancel();
+                }
+                self.scroll.push(dx, dy, precise, now);
+                if phase == TouchPhase::Ended {
+                    self.scroll.release(now);
+                }
+            }
+            WinitWindowEvent::Touch(touch) => {
+                if let Some(event) = pen_event(&touch) {
+                    self.push_event(event);
+                }
             }
             WinitWindowEvent::CursorEntered { .. } => {
                 self.push_event(DopEvent::mouse_enter());
@@ -743,6 +1379,44 @@ impl ApplicationHandler for DopApp {
     }
 }
 
+/// Create an event loop that may run on a thread other than the main thread
+///
+/// Off the main thread this only works on X11/Wayland and Windows; macOS
+/// requires the event loop on the main thread.
+pub(crate) fn any_thread_event_loop() -> Result<EventLoop<()>, winit::error::EventLoopError> {
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    {
+        use winit::platform::x11::EventLoopBuilderExtX11;
+
+        EventLoop::builder().with_any_thread(true).build()
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        use winit::platform::windows::EventLoopBuilderExtWindows;
+
+        EventLoop::builder().with_any_thread(true).build()
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "windows"
+    )))]
+    {
+        EventLoop::new()
+    }
+}
+
 /// Create and run a window with the event loop
 pub fn run_window(config: WindowConfig) -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
@@ -0,0 +1,460 @@
+//! Multiple windows sharing one event loop
+//!
+//! winit allows a single event loop per process, and on macOS only on the
+//! main thread, so spawning a threaded window per page does not scale. A
+//! `WindowManager` owns one event loop and opens windows on it when the host
+//! asks; every window keeps its own event queue and framebuffer, addressed
+//! by a window ID.
+//!
+//! `WindowManager::new` runs the event loop on a thread of its own, which
+//! works on Linux and Windows but not on macOS. There, create the manager
+//! with `WindowManager::new_main_thread` on the main thread and call `pump`
+//! from the host's main loop.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use winit::application::ApplicationHandler;
+use winit::event::{DeviceEvent, DeviceId, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop, EventLoopProxy};
+use winit::window::WindowId;
+
+use crate::ffi_guard::LockExt;
+use crate::renderer::GpuFrame;
+use crate::window::{DopApp, DopEvent, ExternalFrame, SharedFramebuffer, SharedImeText, WindowCommand, WindowConfig};
+
+/// Host-visible window ID (0 = no window)
+pub type ManagedWindowId = u32;
+
+/// State shared between the host and one window on the event loop thread
+#[derive(Clone)]
+pub struct ManagedWindow {
+    events: Arc<Mutex<Vec<DopEvent>>>,
+    is_open: Arc<AtomicBool>,
+    size: Arc<Mutex<(u32, u32)>>,
+    external_framebuffer: SharedFramebuffer,
+    gpu_frame: Arc<Mutex<Option<GpuFrame>>>,
+    gpu_native: bool,
+    kinetic_scrolling: Arc<AtomicBool>,
+    scale_factor: Arc<Mutex<f64>>,
+    ime_text: SharedImeText,
+    commands: mpsc::Sender<WindowCommand>,
+}
+
+impl ManagedWindow {
+    /// Create the shared state and the receiving end of its command channel
+    fn new(config: &WindowConfig, gpu_native: bool) -> (Self, mpsc::Receiver<WindowCommand>) {
+        let (commands, command_rx) = mpsc::channel();
+        let window = Self {
+            events: Arc::new(Mutex::new(Vec::new())),
+            is_open: Arc::new(AtomicBool::new(true)),
+            size: Arc::new(Mutex::new((config.width, config.height))),
+            external_framebuffer: Arc::new(Mutex::new(None)),
+            gpu_frame: Arc::new(Mutex::new(None)),
+            gpu_native,
+            kinetic_scrolling: Arc::new(AtomicBool::new(config.kinetic_scrolling)),
+            scale_factor: Arc::new(Mutex::new(1.0)),
+            ime_text: SharedImeText::default(),
+            commands,
+        };
+        (window, command_rx)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.is_open.load(Ordering::Relaxed)
+    }
+
+    pub fn poll_events(&self) -> Vec<DopEvent> {
+        self.events.lock().map(|mut e| std::mem::take(&mut *e)).unwrap_or_default()
+    }
+
+    pub fn get_size(&self) -> (u32, u32) {
+        *self.size.lock_or_recover()
+    }
+
+    /// Display scale factor of the window (1.0 until the window opens)
+    pub fn scale_factor(&self) -> f64 {
+        *self.scale_factor.lock_or_recover()
+    }
+
+    /// Text referenced by IME events
+    pub fn ime_text(&self) -> &SharedImeText {
+        &self.ime_text
+    }
+
+    pub fn set_kinetic_scrolling(&self, enabled: bool) {
+        self.kinetic_scrolling.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Store an RGBA framebuffer (copied) for presentation
+    fn update_framebuffer(&self, data: &[u8], width: u32, height: u32) -> bool {
+        if self.gpu_native || !self.is_open() {
+            return false;
+        }
+        match self.external_framebuffer.lock() {
+            Ok(mut guard) => *guard = Some(ExternalFrame::new(data.to_vec(), width, height)),
+            Err(_) => return false,
+        }
+        true
+    }
+
+    /// Store a frame for a GPU-mode window
+    fn submit_gpu_frame(&self, frame: GpuFrame) -> bool {
+        if !self.gpu_native || !self.is_open() {
+            return false;
+        }
+        match self.gpu_frame.lock() {
+            Ok(mut guard) => *guard = Some(frame),
+            Err(_) => return false,
+        }
+        true
+    }
+}
+
+/// A window the event loop thread should open
+struct WindowRequest {
+    config: WindowConfig,
+    window: ManagedWindow,
+    commands: mpsc::Receiver<WindowCommand>,
+}
+
+/// Where the shared event loop runs
+enum EventLoopHost {
+    /// On a thread of its own; kept so the thread is detached, never joined,
+    /// when the manager is dropped
+    Thread(#[allow(dead_code)] thread::JoinHandle<()>),
+    /// On the thread that created the manager, driven by `pump`
+    MainThread(Box<(EventLoop<()>, ManagerApp)>),
+}
+
+/// Host-side owner of the shared event loop and its windows
+pub struct WindowManager {
+    windows: HashMap<ManagedWindowId, ManagedWindow>,
+    requests: Arc<Mutex<Vec<WindowRequest>>>,
+    running: Arc<AtomicBool>,
+    proxy: EventLoopProxy<()>,
+    next_id: ManagedWindowId,
+    host: EventLoopHost,
+}
+
+impl WindowManager {
+    /// Start the event loop thread; None if the event loop could not be created
+    ///
+    /// Not supported on macOS, where the event loop must run on the main
+    /// thread; use `new_main_thread` there.
+    pub fn new() -> Option<Self> {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let running = Arc::new(AtomicBool::new(true));
+        let (proxy_tx, proxy_rx) = std::sync::mpsc::channel();
+
+        let thread_requests = requests.clone();
+        let thread_running = running.clone();
+        let thread_handle = thread::spawn(move || {
+            let event_loop = match crate::window::any_thread_event_loop() {
+                Ok(el) => el,
+                Err(e) => {
+                    log::error!("Failed to create event loop: {:?}", e);
+                    thread_running.store(false, Ordering::Relaxed);
+                    return;
+                }
+            };
+            let _ = proxy_tx.send(event_loop.create_proxy());
+            event_loop.set_control_flow(ControlFlow::Poll);
+
+            let mut app = ManagerApp {
+                requests: thread_requests,
+                running: thread_running.clone(),
+                windows: HashMap::new(),
+                focused: None,
+            };
+            if let Err(e) = event_loop.run_app(&mut app) {
+                log::error!("Event loop error: {:?}", e);
+            }
+            thread_running.store(false, Ordering::Relaxed);
+        });
+
+        let proxy = match proxy_rx.recv_timeout(Duration::from_millis(5000)) {
+            Ok(proxy) => proxy,
+            Err(_) => {
+                log::warn!("Failed to receive EventLoopProxy from window manager thread within timeout");
+                running.store(false, Ordering::Relaxed);
+                return None;
+            }
+        };
+
+        Some(Self {
+            windows: HashMap::new(),
+            requests,
+            running,
+            proxy,
+            next_id: 0,
+            host: EventLoopHost::Thread(thread_handle),
+        })
+    }
+
+    /// Create the event loop on the calling thread, which then drives it with `pump`
+    ///
+    /// Call this and `pump` from the main thread on macOS. Windows open and
+    /// present only while the host pumps. None if the event loop could not be
+    /// created.
+    pub fn new_main_thread() -> Option<Self> {
+        let event_loop = match EventLoop::new() {
+            Ok(el) => el,
+            Err(e) => {
+                log::error!("Failed to create event loop: {:?}", e);
+                return None;
+            }
+        };
+        event_loop.set_control_flow(ControlFlow::Poll);
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let running = Arc::new(AtomicBool::new(true));
+        let app = ManagerApp {
+            requests: requests.clone(),
+            running: running.clone(),
+            windows: HashMap::new(),
+            focused: None,
+        };
+        Some(Self {
+            windows: HashMap::new(),
+            requests,
+            running,
+            proxy: event_loop.create_proxy(),
+            next_id: 0,
+            host: EventLoopHost::MainThread(Box::new((event_loop, app))),
+        })
+    }
+
+    /// Dispatch the pending events of a main-thread event loop, waiting at most `timeout` for one
+    ///
+    /// Returns false once the event loop has stopped, and for a manager whose
+    /// event loop runs on its own thread.
+    pub fn pump(&mut self, timeout: Duration) -> bool {
+        if !self.is_running() {
+            return false;
+        }
+        let EventLoopHost::MainThread(main) = &mut self.host else {
+            return false;
+        };
+        let (event_loop, app) = &mut **main;
+        pump_event_loop(event_loop, app, timeout, &self.running);
+        self.is_running()
+    }
+
+    /// Check if the event loop is still running
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    /// Ask the event loop to open a window; it appears on the next loop iteration
+    ///
+    /// Returns 0 if the event loop has stopped.
+    pub fn create_window(&mut self, config: WindowConfig, gpu_native: bool) -> ManagedWindowId {
+        if !self.is_running() {
+            return 0;
+        }
+        let (window, commands) = ManagedWindow::new(&config, gpu_native);
+        match self.requests.lock() {
+            Ok(mut requests) => requests.push(WindowRequest {
+                config,
+                window: window.clone(),
+                commands,
+            }),
+            Err(_) => return 0,
+        }
+        self.next_id += 1;
+        self.windows.insert(self.next_id, window);
+        self.wake();
+        self.next_id
+    }
+
+    /// Get a window
+    pub fn window(&self, id: ManagedWindowId) -> Option<&ManagedWindow> {
+        self.windows.get(&id)
+    }
+
+    /// Close a window and forget its ID
+    pub fn close_window(&mut self, id: ManagedWindowId) -> bool {
+        let Some(window) = self.windows.remove(&id) else {
+            return false;
+        };
+        window.is_open.store(false, Ordering::Relaxed);
+        self.wake();
+        true
+    }
+
+    /// Copy an RGBA framebuffer into a window and wake the event loop to present it
+    pub fn update_framebuffer(&self, id: ManagedWindowId, data: &[u8], width: u32, height: u32) -> bool {
+        let updated = self
+            .window(id)
+            .is_some_and(|w| w.update_framebuffer(data, width, height));
+        if updated {
+            self.wake();
+        }
+        updated
+    }
+
+    /// Ask for a redraw of a window; a `Redraw` event follows once the frame is presented
+    pub fn request_redraw(&self, id: ManagedWindowId) -> bool {
+        let open = self.window(id).is_some_and(|w| w.is_open());
+        if open {
+            // Waking the event loop requests a redraw of every window
+            self.wake();
+        }
+        open
+    }
+
+    /// Send a command to a window and wake the event loop to apply it
+    pub fn send_command(&self, id: ManagedWindowId, command: WindowCommand) -> bool {
+        let sent = self
+            .window(id)
+            .is_some_and(|w| w.is_open() && w.commands.send(command).is_ok());
+        if sent {
+            self.wake();
+        }
+        sent
+    }
+
+    /// Queue a frame for a GPU-mode window and wake the event loop to draw it
+    pub fn submit_gpu_frame(&self, id: ManagedWindowId, frame: GpuFrame) -> bool {
+        let submitted = self.window(id).is_some_and(|w| w.submit_gpu_frame(frame));
+        if submitted {
+            self.wake();
+        }
+        submitted
+    }
+
+    fn wake(&self) {
+        let _ = self.proxy.send_event(());
+    }
+}
+
+impl Drop for WindowManager {
+    fn drop(&mut self) {
+        // Like threaded windows, signal the loop and detach instead of joining
+        self.running.store(false, Ordering::Relaxed);
+        for window in self.windows.values() {
+            window.is_open.store(false, Ordering::Relaxed);
+        }
+        self.wake();
+    }
+}
+
+/// Run one iteration of an event loop owned by the calling thread
+#[cfg(not(any(target_os = "ios", target_arch = "wasm32")))]
+fn pump_event_loop(event_loop: &mut EventLoop<()>, app: &mut ManagerApp, timeout: Duration, running: &AtomicBool) {
+    use winit::platform::pump_events::{EventLoopExtPumpEvents, PumpStatus};
+
+    if let PumpStatus::Exit(_) = event_loop.pump_app_events(Some(timeout), app) {
+        running.store(false, Ordering::Relaxed);
+    }
+}
+
+/// iOS and the web only run event loops that never return to the host
+#[cfg(any(target_os = "ios", target_arch = "wasm32"))]
+fn pump_event_loop(_event_loop: &mut EventLoop<()>, _app: &mut ManagerApp, _timeout: Duration, running: &AtomicBool) {
+    log::warn!("pumping the event loop is not supported on this platform");
+    running.store(false, Ordering::Relaxed);
+}
+
+/// Event loop side: one `DopApp` per open window
+struct ManagerApp {
+    requests: Arc<Mutex<Vec<WindowRequest>>>,
+    running: Arc<AtomicBool>,
+    windows: HashMap<WindowId, (DopApp, ManagedWindow)>,
+    // Device events have no window, so they go to the focused one
+    focused: Option<WindowId>,
+}
+
+impl ManagerApp {
+    /// Open the windows requested since the last iteration
+    fn open_requested(&mut self, event_loop: &ActiveEventLoop) {
+        let requests = match self.requests.lock() {
+            Ok(mut requests) => std::mem::take(&mut *requests),
+            Err(_) => return,
+        };
+        for request in requests {
+            let window = request.window;
+            let mut app = DopApp::new_with_shared_events(
+                request.config,
+                window.events.clone(),
+                (!window.gpu_native).then(|| window.external_framebuffer.clone()),
+            );
+            if window.gpu_native {
+                app.set_gpu_frame_source(window.gpu_frame.clone());
+            }
+            app.set_kinetic_scrolling_flag(window.kinetic_scrolling.clone());
+            app.set_scale_factor_source(window.scale_factor.clone());
+            app.set_ime_text_store(window.ime_text.clone());
+            app.set_command_source(request.commands);
+            app.set_exit_on_close(false);
+            app.resumed(event_loop);
+            match app.window_id() {
+                Some(id) => {
+                    self.windows.insert(id, (app, window));
+                }
+                None => window.is_open.store(false, Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Drop windows closed by the user or the host
+    fn close_finished(&mut self) {
+        self.windows.retain(|_, (app, window)| {
+            let open = window.is_open() && app.handle().is_some_and(|h| h.is_open());
+            if !open {
+                window.is_open.store(false, Ordering::Relaxed);
+            }
+            open
+        });
+    }
+}
+
+impl ApplicationHandler for ManagerApp {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.open_requested(event_loop);
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: ()) {
+        if !self.running.load(Ordering::Relaxed) {
+            event_loop.exit();
+            return;
+        }
+        self.open_requested(event_loop);
+        self.close_finished();
+        for (app, _) in self.windows.values_mut() {
+            app.user_event(event_loop, event);
+        }
+    }
+
+    fn device_event(&mut self, event_loop: &ActiveEventLoop, device_id: DeviceId, event: DeviceEvent) {
+        let focused = self.focused;
+        if let Some((app, _)) = focused.and_then(|id| self.windows.get_mut(&id)) {
+            app.device_event(event_loop, device_id, event);
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        for (app, _) in self.windows.values_mut() {
+            app.about_to_wait(event_loop);
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, window_id: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::Focused(true) => self.focused = Some(window_id),
+            WindowEvent::Focused(false) if self.focused == Some(window_id) => self.focused = None,
+            _ => {}
+        }
+        let Some((app, window)) = self.windows.get_mut(&window_id) else {
+            return;
+        };
+        app.window_event(event_loop, window_id, event);
+        if let (Some(handle), Ok(mut size)) = (app.handle(), window.size.lock()) {
+            *size = handle.get_size();
+        }
+        self.close_finished();
+    }
+}
@@ -0,0 +1,165 @@
+//! WOFF web font decoding
+//!
+//! WOFF wraps an OpenType/TrueType font with each table zlib-compressed on
+//! its own. Decoding rebuilds the plain sfnt file that fontdue and
+//! ttf-parser read. WOFF2 needs Brotli and a glyf/loca reconstruction that
+//! aren't built in, so it is detected and rejected.
+
+use std::borrow::Cow;
+
+const WOFF_SIGNATURE: &[u8; 4] = b"wOFF";
+const WOFF2_SIGNATURE: &[u8; 4] = b"wOF2";
+const HEADER_SIZE: usize = 44;
+const TABLE_ENTRY_SIZE: usize = 20;
+
+/// Get the sfnt font in `data`, decoding it if it is WOFF
+///
+/// Non-WOFF data is returned as is. Returns None (and logs why) for WOFF2
+/// and for malformed WOFF files.
+pub fn to_sfnt(data: &[u8]) -> Option<Cow<'_, [u8]>> {
+    if data.starts_with(WOFF2_SIGNATURE) {
+        log::warn!("WOFF2 fonts are not supported; convert to WOFF or TTF");
+        return None;
+    }
+    if !data.starts_with(WOFF_SIGNATURE) {
+        return Some(Cow::Borrowed(data));
+    }
+    match decode(data) {
+        Ok(sfnt) => Some(Cow::Owned(sfnt)),
+        Err(e) => {
+            log::warn!("Failed to decode WOFF font: {}", e);
+            None
+        }
+    }
+}
+
+fn read_u16(data: &[u8], at: usize) -> Result<u16, String> {
+    data.get(at..at + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| format!("truncated at byte {}", at))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32, String> {
+    data.get(at..at + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| format!("truncated at byte {}", at))
+}
+
+/// Rebuild the sfnt file from a WOFF file
+fn decode(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < HEADER_SIZE {
+        return Err("file shorter than the WOFF header".to_string());
+    }
+    let flavor = read_u32(data, 4)?;
+    let num_tables = read_u16(data, 12)? as usize;
+
+    // Table data follows the sfnt directory, each table padded to 4 bytes
+    let mut sfnt = Vec::new();
+    let mut offset = 12 + num_tables * 16;
+    let mut directory = Vec::with_capacity(num_tables * 16);
+    let mut tables = Vec::with_capacity(num_tables);
+    for i in 0..num_tables {
+        let entry = HEADER_SIZE + i * TABLE_ENTRY_SIZE;
+        let tag = read_u32(data, entry)?;
+        let comp_offset = read_u32(data, entry + 4)? as usize;
+        let comp_length = read_u32(data, entry + 8)? as usize;
+        let orig_length = read_u32(data, entry + 12)? as usize;
+        let checksum = read_u32(data, entry + 16)?;
+        let stored = data
+            .get(comp_offset..comp_offset + comp_length)
+            .ok_or_else(|| format!("table {} out of bounds", i))?;
+        let table = match comp_length.cmp(&orig_length) {
+            std::cmp::Ordering::Equal => stored.to_vec(),
+            std::cmp::Ordering::Less => {
+                let table = miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(stored, orig_length)
+                    .map_err(|e| format!("table {} failed to inflate: {:?}", i, e.status))?;
+                if table.len() != orig_length {
+                    return Err(format!("table {} inflated to the wrong size", i));
+                }
+                table
+            }
+            std::cmp::Ordering::Greater => return Err(format!("table {} is larger compressed", i)),
+        };
+
+        directory.extend(tag.to_be_bytes());
+        directory.extend(checksum.to_be_bytes());
+        directory.extend((offset as u32).to_be_bytes());
+        directory.extend((orig_length as u32).to_be_bytes());
+        offset += orig_length.div_ceil(4) * 4;
+        tables.push(table);
+    }
+
+    let entry_selector = (num_tables.max(1) as u32).ilog2();
+    let search_range = (1u32 << entry_selector) * 16;
+    sfnt.extend(flavor.to_be_bytes());
+    sfnt.extend((num_tables as u16).to_be_bytes());
+    sfnt.extend((search_range as u16).to_be_bytes());
+    sfnt.extend((entry_selector as u16).to_be_bytes());
+    sfnt.extend(((num_tables as u32 * 16 - search_range) as u16).to_be_bytes());
+    sfnt.extend(directory);
+    for table in tables {
+        sfnt.extend(&table);
+        sfnt.resize(sfnt.len().div_ceil(4) * 4, 0);
+    }
+    Ok(sfnt)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::text::{FontManager, TextSpacing};
+
+    #[test]
+    fn test_font_manager_load_woff() {
+        let Ok(data) = std::fs::read("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf") else {
+            return;
+        };
+        // Wrap the font as WOFF, storing tables that don't shrink uncompressed
+        let u32_at = |at: usize| u32::from_be_bytes(data[at..at + 4].try_into().unwrap());
+        let num_tables = u16::from_be_bytes([data[4], data[5]]) as usize;
+        let mut directory = Vec::new();
+        let mut tables = Vec::new();
+        let mut offset = 44 + num_tables * 20;
+        for i in 0..num_tables {
+            let record = 12 + i * 16;
+            let (start, len) = (u32_at(record + 8) as usize, u32_at(record + 12) as usize);
+            let mut compressed = miniz_oxide::deflate::compress_to_vec_zlib(&data[start..start + len], 6);
+            if compressed.len() >= len {
+                compressed = data[start..start + len].to_vec();
+            }
+            directory.extend(&data[record..record + 4]);
+            directory.extend((offset as u32).to_be_bytes());
+            directory.extend((compressed.len() as u32).to_be_bytes());
+            directory.extend((len as u32).to_be_bytes());
+            directory.extend(&data[record + 4..record + 8]);
+            offset += compressed.len().div_ceil(4) * 4;
+            tables.push(compressed);
+        }
+        let mut woff = b"wOFF".to_vec();
+        woff.extend(&data[..4]);
+        woff.extend((offset as u32).to_be_bytes());
+        woff.extend((num_tables as u16).to_be_bytes());
+        woff.extend([0; 2]);
+        woff.extend((data.len() as u32).to_be_bytes());
+        woff.extend([0; 24]);
+        woff.extend(directory);
+        for table in tables {
+            woff.extend(&table);
+            woff.resize(woff.len().div_ceil(4) * 4, 0);
+        }
+        assert!(woff.len() < data.len());
+
+        let mut fonts = FontManager::new();
+        let ttf = fonts.load_font_from_bytes(&data).unwrap();
+        let web = fonts.load_font_from_bytes(&woff).unwrap();
+        assert_eq!(
+            fonts.rasterize_text("Woff", 20.0, web, (0, 0, 0, 255), TextSpacing::default()),
+            fonts.rasterize_text("Woff", 20.0, ttf, (0, 0, 0, 255), TextSpacing::default())
+        );
+
+        // Truncated WOFF and WOFF2 are rejected
+        assert!(fonts.load_font_from_bytes(&woff[..woff.len() / 2]).is_none());
+        let mut woff2 = woff.clone();
+        woff2[3] = b'2';
+        assert!(fonts.load_font_from_bytes(&woff2).is_none());
+    }
+}
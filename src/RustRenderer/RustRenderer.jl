@@ -39,7 +39,7 @@ destroy!(renderer)
 module RustRenderer
 
 export RustRendererHandle, RustWindowHandle
-export create_renderer, create_window, destroy!
+export create_renderer, create_window, destroy!, adopt!
 export add_rect!, render!, get_framebuffer, get_framebuffer_size
 export set_clear_color!, clear!
 export is_open, close!, poll_events!
@@ -173,6 +173,14 @@ Event types matching the Rust enum.
     EVENT_FOCUS = 13
     EVENT_BLUR = 14
     EVENT_REDRAW = 15
+    EVENT_ACCESSIBILITY_ACTION = 16
+    EVENT_ACCESSIBILITY_ACTIVATED = 17
+    EVENT_MOMENTUM_SCROLL = 18
+    EVENT_RAW_MOUSE_MOTION = 19
+    EVENT_PEN = 20
+    EVENT_SCALE_FACTOR_CHANGED = 21
+    EVENT_IME_PREEDIT = 22
+    EVENT_IME_COMMIT = 23
 end
 
 export DopEventType, EVENT_NONE, EVENT_CLOSE, EVENT_RESIZE, EVENT_MOVE
@@ -180,6 +188,9 @@ export EVENT_KEY_DOWN, EVENT_KEY_UP, EVENT_CHAR
 export EVENT_MOUSE_DOWN, EVENT_MOUSE_UP, EVENT_MOUSE_MOVE
 export EVENT_MOUSE_SCROLL, EVENT_MOUSE_ENTER, EVENT_MOUSE_LEAVE
 export EVENT_FOCUS, EVENT_BLUR, EVENT_REDRAW
+export EVENT_ACCESSIBILITY_ACTION, EVENT_ACCESSIBILITY_ACTIVATED
+export EVENT_MOMENTUM_SCROLL, EVENT_RAW_MOUSE_MOTION, EVENT_PEN
+export EVENT_SCALE_FACTOR_CHANGED, EVENT_IME_PREEDIT, EVENT_IME_COMMIT
 
 """
 Mouse button identifiers.
@@ -224,10 +235,25 @@ struct DopEvent
     width::Int32
     height::Int32
     timestamp::Float64
+    pressure::Float32
+    tilt_x::Float32
+    tilt_y::Float32
+    pen_flags::UInt32
+    scale_factor::Float64
 end
 
 export DopEvent
 
+"""
+Pen state flags (`DopEvent.pen_flags`).
+"""
+const PEN_NONE = UInt32(0)
+const PEN_CONTACT = UInt32(1)
+const PEN_ERASER = UInt32(2)
+const PEN_CANCELLED = UInt32(4)
+
+export PEN_NONE, PEN_CONTACT, PEN_ERASER, PEN_CANCELLED
+
 # ============================================================================
 # Window Handle
 # ============================================================================
@@ -324,15 +350,17 @@ export get_size
     RustThreadedWindowHandle
 
 Handle to a Rust-based onscreen window running in a separate thread.
+
+`id` is an opaque generational handle; the library rejects it once the window is freed.
 """
 mutable struct RustThreadedWindowHandle
-    ptr::Ptr{Nothing}
+    id::UInt64
     is_valid::Bool
     
-    function RustThreadedWindowHandle(ptr::Ptr{Nothing})
-        h = new(ptr, ptr != C_NULL)
+    function RustThreadedWindowHandle(id::UInt64)
+        h = new(id, id != 0)
         finalizer(h) do handle
-            if handle.is_valid && handle.ptr != C_NULL
+            if handle.is_valid && handle.id != 0
                 destroy_threaded!(handle)
             end
         end
@@ -348,10 +376,10 @@ export RustThreadedWindowHandle
 Create a new onscreen window running in a separate thread with its own event loop.
 """
 function create_onscreen_window(; width::Integer=800, height::Integer=600, title::String="DOP Browser")::RustThreadedWindowHandle
-    ptr = ccall(get_func(:dop_window_create_onscreen), 
-                Ptr{Nothing}, (Cint, Cint, Cstring), 
-                width, height, title)
-    return RustThreadedWindowHandle(ptr)
+    id = ccall(get_func(:dop_window_create_onscreen), 
+               UInt64, (Cint, Cint, Cstring), 
+               width, height, title)
+    return RustThreadedWindowHandle(id)
 end
 
 export create_onscreen_window
@@ -362,11 +390,11 @@ export create_onscreen_window
 Destroy a threaded window and release resources.
 """
 function destroy_threaded!(handle::RustThreadedWindowHandle)
-    if handle.is_valid && handle.ptr != C_NULL
+    if handle.is_valid && handle.id != 0
         # Request a clean shutdown of the threaded window: ask the thread to
         # close and wait up to 5 seconds for it to exit, then free the handle.
         try
-            ccall(get_func(:dop_window_request_close_threaded), Cvoid, (Ptr{Nothing},), handle.ptr)
+            ccall(get_func(:dop_window_request_close_threaded), Cvoid, (UInt64,), handle.id)
         catch e
             @warn "Failed to request threaded window close" exception=e
         end
@@ -376,7 +404,7 @@ function destroy_threaded!(handle::RustThreadedWindowHandle)
         # side resource.
         joined = 0
         try
-            joined = ccall(get_func(:dop_window_join_threaded_timeout), Cint, (Ptr{Nothing}, Cint), handle.ptr, Int32(5000))
+            joined = ccall(get_func(:dop_window_join_threaded_timeout), Cint, (UInt64, Cint), handle.id, Int32(5000))
         catch e
             @warn "Failed to join threaded window" exception=e
         end
@@ -387,12 +415,12 @@ function destroy_threaded!(handle::RustThreadedWindowHandle)
 
         # Finally, free the underlying handle (non-blocking on Rust side).
         try
-            ccall(get_func(:dop_window_free_threaded), Cvoid, (Ptr{Nothing},), handle.ptr)
+            ccall(get_func(:dop_window_free_threaded), Cvoid, (UInt64,), handle.id)
         catch e
             @warn "Failed to free threaded window handle" exception=e
         end
 
-        handle.ptr = C_NULL
+        handle.id = 0
         handle.is_valid = false
     end
 end
@@ -405,10 +433,10 @@ export destroy_threaded!
 Check if the threaded window is still open.
 """
 function is_open_threaded(handle::RustThreadedWindowHandle)::Bool
-    if !handle.is_valid || handle.ptr == C_NULL
+    if !handle.is_valid || handle.id == 0
         return false
     end
-    return ccall(get_func(:dop_window_is_open_threaded), Cint, (Ptr{Nothing},), handle.ptr) != 0
+    return ccall(get_func(:dop_window_is_open_threaded), Cint, (UInt64,), handle.id) != 0
 end
 
 export is_open_threaded
@@ -419,7 +447,7 @@ export is_open_threaded
 Poll events from the threaded window.
 """
 function poll_events_threaded!(handle::RustThreadedWindowHandle; max_events::Integer=100)::Vector{DopEvent}
-    if !handle.is_valid || handle.ptr == C_NULL
+    if !handle.is_valid || handle.id == 0
         return DopEvent[]
     end
     
@@ -427,8 +455,8 @@ function poll_events_threaded!(handle::RustThreadedWindowHandle; max_events::Int
     events = Vector{DopEvent}(undef, max_events)
     
     count = ccall(get_func(:dop_window_poll_events_threaded),
-                  Cint, (Ptr{Nothing}, Ptr{DopEvent}, Cint),
-                  handle.ptr, pointer(events), max_events)
+                  Cint, (UInt64, Ptr{DopEvent}, Cint),
+                  handle.id, pointer(events), max_events)
     
     # Return only the events that were actually filled
     return events[1:count]
@@ -442,388 +470,1370 @@ export poll_events_threaded!
 Get the size of the threaded window.
 """
 function get_size_threaded(handle::RustThreadedWindowHandle)::Tuple{Int, Int}
-    if !handle.is_valid || handle.ptr == C_NULL
+    if !handle.is_valid || handle.id == 0
         return (0, 0)
     end
-    width = ccall(get_func(:dop_window_get_width_threaded), Cint, (Ptr{Nothing},), handle.ptr)
-    height = ccall(get_func(:dop_window_get_height_threaded), Cint, (Ptr{Nothing},), handle.ptr)
+    width = ccall(get_func(:dop_window_get_width_threaded), Cint, (UInt64,), handle.id)
+    height = ccall(get_func(:dop_window_get_height_threaded), Cint, (UInt64,), handle.id)
     return (Int(width), Int(height))
 end
 
 export get_size_threaded
 
 """
-    update_framebuffer_threaded(handle::RustThreadedWindowHandle, buf::Vector{UInt8}, width::Integer, height::Integer)
+    get_scale_factor_threaded(handle::RustThreadedWindowHandle) -> Float64
 
-Copy an RGBA framebuffer into the threaded window for presentation. The data is copied.
+Get the display scale factor (physical pixels per logical pixel) of the threaded window.
 """
-function update_framebuffer_threaded(handle::RustThreadedWindowHandle, buf::Vector{UInt8}, width::Integer, height::Integer)
-    if !handle.is_valid || handle.ptr == C_NULL || isempty(buf)
-        return
+function get_scale_factor_threaded(handle::RustThreadedWindowHandle)::Float64
+    if !handle.is_valid || handle.id == 0
+        return 1.0
     end
-    ccall(get_func(:dop_window_update_framebuffer_threaded),
-          Cvoid, (Ptr{Nothing}, Ptr{UInt8}, Cint, Cint, Cint),
-          handle.ptr, pointer(buf), Int32(length(buf)), Int32(width), Int32(height))
+    return ccall(get_func(:dop_window_get_scale_factor_threaded), Cdouble, (UInt64,), handle.id)
 end
 
-export update_framebuffer_threaded
-
-# ============================================================================
-# Renderer Handle
-# ============================================================================
+export get_scale_factor_threaded
 
 """
-    RustRendererHandle
+    get_ime_text_threaded(handle::RustThreadedWindowHandle, event::DopEvent) -> String
 
-Handle to a Rust-based renderer.
+Text of an `EVENT_IME_PREEDIT` or `EVENT_IME_COMMIT` event ("" if no longer available).
+For preedit events, `event.key` and `event.scancode` hold the cursor byte range (-1 if hidden).
 """
-mutable struct RustRendererHandle
-    ptr::Ptr{Nothing}
-    width::UInt32
-    height::UInt32
-    is_valid::Bool
-    
-    function RustRendererHandle(ptr::Ptr{Nothing}, width::UInt32, height::UInt32)
-        h = new(ptr, width, height, ptr != C_NULL)
-        finalizer(h) do handle
-            if handle.is_valid && handle.ptr != C_NULL
-                destroy!(handle)
-            end
-        end
-        return h
+function get_ime_text_threaded(handle::RustThreadedWindowHandle, event::DopEvent)::String
+    if !handle.is_valid || handle.id == 0
+        return ""
     end
+    len = ccall(get_func(:dop_window_get_ime_text_threaded), Cint,
+                (UInt64, UInt32, Ptr{UInt8}, Cint), handle.id, event.char_code, C_NULL, 0)
+    len <= 0 && return ""
+    buf = Vector{UInt8}(undef, len)
+    ccall(get_func(:dop_window_get_ime_text_threaded), Cint,
+          (UInt64, UInt32, Ptr{UInt8}, Cint), handle.id, event.char_code, buf, len)
+    return String(buf)
 end
 
 """
-    create_renderer(width::Integer, height::Integer) -> RustRendererHandle
+    set_ime_position_threaded!(handle::RustThreadedWindowHandle, x::Real, y::Real)
 
-Create a new headless renderer.
+Show the IME candidate window at the caret (bottom-left corner, in physical pixels).
 """
-function create_renderer(width::Integer, height::Integer)::RustRendererHandle
-    ptr = ccall(get_func(:dop_renderer_create_headless), 
-                Ptr{Nothing}, (Cint, Cint), 
-                width, height)
-    return RustRendererHandle(ptr, UInt32(width), UInt32(height))
+function set_ime_position_threaded!(handle::RustThreadedWindowHandle, x::Real, y::Real)
+    if !handle.is_valid || handle.id == 0
+        return
+    end
+    ccall(get_func(:dop_window_set_ime_position_threaded), Cvoid,
+          (UInt64, Cfloat, Cfloat), handle.id, Float32(x), Float32(y))
 end
 
+export get_ime_text_threaded, set_ime_position_threaded!
+
 """
-    destroy!(handle::RustRendererHandle)
+    request_redraw_threaded!(handle::RustThreadedWindowHandle) -> Bool
 
-Destroy a renderer and release resources.
+Request a redraw. An `EVENT_REDRAW` event follows once the frame is presented, so
+producing one frame per `EVENT_REDRAW` runs at the display rate.
 """
-function destroy!(handle::RustRendererHandle)
-    if handle.is_valid && handle.ptr != C_NULL
-        ccall(get_func(:dop_renderer_free), Cvoid, (Ptr{Nothing},), handle.ptr)
-        handle.ptr = C_NULL
-        handle.is_valid = false
+function request_redraw_threaded!(handle::RustThreadedWindowHandle)::Bool
+    if !handle.is_valid || handle.id == 0
+        return false
     end
+    return ccall(get_func(:dop_window_request_redraw_threaded), Cint, (UInt64,), handle.id) != 0
 end
 
 """
-    clear!(handle::RustRendererHandle)
+    set_title_threaded!(handle::RustThreadedWindowHandle, title::AbstractString) -> Bool
 
-Clear all render commands.
+Change the title of the threaded window.
 """
-function clear!(handle::RustRendererHandle)
-    if handle.is_valid && handle.ptr != C_NULL
-        ccall(get_func(:dop_renderer_clear), Cvoid, (Ptr{Nothing},), handle.ptr)
+function set_title_threaded!(handle::RustThreadedWindowHandle, title::AbstractString)::Bool
+    if !handle.is_valid || handle.id == 0
+        return false
     end
+    return ccall(get_func(:dop_window_set_title_threaded), Cint,
+                 (UInt64, Cstring), handle.id, title) != 0
 end
 
 """
-    set_clear_color!(handle::RustRendererHandle, r::Float32, g::Float32, b::Float32, a::Float32)
+    set_size_threaded!(handle::RustThreadedWindowHandle, width::Integer, height::Integer) -> Bool
 
-Set the clear color.
+Request a new inner size in physical pixels. A resize event reports the size actually applied.
 """
-function set_clear_color!(handle::RustRendererHandle, 
-                          r::Real, g::Real, b::Real, a::Real=1.0)
-    if handle.is_valid && handle.ptr != C_NULL
-        ccall(get_func(:dop_renderer_set_clear_color), 
-              Cvoid, (Ptr{Nothing}, Cfloat, Cfloat, Cfloat, Cfloat), 
-              handle.ptr, Float32(r), Float32(g), Float32(b), Float32(a))
+function set_size_threaded!(handle::RustThreadedWindowHandle, width::Integer, height::Integer)::Bool
+    if !handle.is_valid || handle.id == 0
+        return false
     end
+    return ccall(get_func(:dop_window_set_size_threaded), Cint,
+                 (UInt64, Cint, Cint), handle.id, width, height) != 0
 end
 
 """
-    add_rect!(handle::RustRendererHandle, x, y, width, height, r, g, b, a; z_index=0)
+    set_fullscreen_threaded!(handle::RustThreadedWindowHandle, fullscreen::Bool) -> Bool
 
-Add a rectangle render command.
+Switch the threaded window to borderless fullscreen or back to windowed.
 """
-function add_rect!(handle::RustRendererHandle,
-                   x::Real, y::Real, width::Real, height::Real,
-                   r::Real, g::Real, b::Real, a::Real;
-                   z_index::Integer=0)
-    if handle.is_valid && handle.ptr != C_NULL
-        ccall(get_func(:dop_renderer_add_rect), 
-              Cvoid, (Ptr{Nothing}, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cint), 
-              handle.ptr, 
-              Float32(x), Float32(y), Float32(width), Float32(height),
-              Float32(r), Float32(g), Float32(b), Float32(a),
-              Int32(z_index))
+function set_fullscreen_threaded!(handle::RustThreadedWindowHandle, fullscreen::Bool)::Bool
+    if !handle.is_valid || handle.id == 0
+        return false
     end
+    return ccall(get_func(:dop_window_set_fullscreen_threaded), Cint,
+                 (UInt64, Cint), handle.id, fullscreen ? 1 : 0) != 0
 end
 
 """
-    render!(handle::RustRendererHandle)
+    minimize_threaded!(handle::RustThreadedWindowHandle) -> Bool
 
-Render the current frame.
+Minimize the threaded window.
 """
-function render!(handle::RustRendererHandle)
-    if handle.is_valid && handle.ptr != C_NULL
-        ccall(get_func(:dop_renderer_render), Cvoid, (Ptr{Nothing},), handle.ptr)
+function minimize_threaded!(handle::RustThreadedWindowHandle)::Bool
+    if !handle.is_valid || handle.id == 0
+        return false
     end
+    return ccall(get_func(:dop_window_minimize_threaded), Cint, (UInt64,), handle.id) != 0
 end
 
 """
-    get_framebuffer(handle::RustRendererHandle) -> Vector{UInt8}
+    set_maximized_threaded!(handle::RustThreadedWindowHandle, maximized::Bool) -> Bool
 
-Get the framebuffer as a copy.
+Maximize or restore the threaded window.
 """
-function get_framebuffer(handle::RustRendererHandle)::Vector{UInt8}
-    if !handle.is_valid || handle.ptr == C_NULL
-        return UInt8[]
+function set_maximized_threaded!(handle::RustThreadedWindowHandle, maximized::Bool)::Bool
+    if !handle.is_valid || handle.id == 0
+        return false
     end
-    
-    ptr = ccall(get_func(:dop_renderer_get_framebuffer), 
-                Ptr{UInt8}, (Ptr{Nothing},), 
-                handle.ptr)
-    size = ccall(get_func(:dop_renderer_get_framebuffer_size), 
-                 Cint, (Ptr{Nothing},), 
-                 handle.ptr)
-    
-    if ptr == C_NULL || size <= 0
-        return UInt8[]
+    return ccall(get_func(:dop_window_set_maximized_threaded), Cint,
+                 (UInt64, Cint), handle.id, maximized ? 1 : 0) != 0
+end
+
+export set_title_threaded!, set_size_threaded!, set_fullscreen_threaded!
+export minimize_threaded!, set_maximized_threaded!, request_redraw_threaded!
+
+"""
+    update_framebuffer_threaded(handle::RustThreadedWindowHandle, buf::Vector{UInt8}, width::Integer, height::Integer)
+
+Copy an RGBA framebuffer into the threaded window for presentation. The data is copied.
+"""
+function update_framebuffer_threaded(handle::RustThreadedWindowHandle, buf::Vector{UInt8}, width::Integer, height::Integer)
+    if !handle.is_valid || handle.id == 0 || isempty(buf)
+        return
     end
-    
-    # Copy the data to Julia-owned array
-    buffer = Vector{UInt8}(undef, size)
-    unsafe_copyto!(pointer(buffer), ptr, size)
-    return buffer
+    ccall(get_func(:dop_window_update_framebuffer_threaded),
+          Cvoid, (UInt64, Ptr{UInt8}, Cint, Cint, Cint),
+          handle.id, pointer(buf), Int32(length(buf)), Int32(width), Int32(height))
 end
 
+export update_framebuffer_threaded
+
 """
-    get_framebuffer_size(handle::RustRendererHandle) -> Int
+    map_framebuffer_threaded(handle::RustThreadedWindowHandle) -> Union{Nothing, Tuple{Vector{UInt8}, Int, Int, Int}}
 
-Get the framebuffer size in bytes.
+Map the window's back buffer for drawing without a copy. Returns `(buf, width, height, stride)`,
+where `buf` wraps Rust-owned memory sized to the window, or `nothing` if the window is closed
+or in GPU mode. Draw into `buf`, then call `swap_framebuffer_threaded!`; `buf` must not be used
+after the swap.
 """
-function get_framebuffer_size(handle::RustRendererHandle)::Int
-    if !handle.is_valid || handle.ptr == C_NULL
-        return 0
+function map_framebuffer_threaded(handle::RustThreadedWindowHandle)
+    if !handle.is_valid || handle.id == 0
+        return nothing
     end
-    return Int(ccall(get_func(:dop_renderer_get_framebuffer_size), 
-                     Cint, (Ptr{Nothing},), 
-                     handle.ptr))
+    ptr = Ref{Ptr{UInt8}}(C_NULL)
+    stride = Ref{Cint}(0)
+    width = Ref{Cint}(0)
+    height = Ref{Cint}(0)
+    ok = ccall(get_func(:dop_window_map_framebuffer_threaded), Cint,
+               (UInt64, Ref{Ptr{UInt8}}, Ref{Cint}, Ref{Cint}, Ref{Cint}),
+               handle.id, ptr, stride, width, height)
+    ok == 0 && return nothing
+    buf = unsafe_wrap(Vector{UInt8}, ptr[], Int(stride[]) * Int(height[]); own=false)
+    return (buf, Int(width[]), Int(height[]), Int(stride[]))
 end
 
 """
-    renderer_resize!(handle::RustRendererHandle, width::Integer, height::Integer)
+    swap_framebuffer_threaded!(handle::RustThreadedWindowHandle) -> Bool
 
-Resize the renderer.
+Present the mapped back buffer by swapping it with the front buffer (no copy).
 """
-function renderer_resize!(handle::RustRendererHandle, width::Integer, height::Integer)
-    if handle.is_valid && handle.ptr != C_NULL
-        ccall(get_func(:dop_renderer_resize), 
-              Cvoid, (Ptr{Nothing}, Cint, Cint), 
-              handle.ptr, width, height)
-        handle.width = UInt32(width)
-        handle.height = UInt32(height)
+function swap_framebuffer_threaded!(handle::RustThreadedWindowHandle)::Bool
+    if !handle.is_valid || handle.id == 0
+        return false
     end
+    return ccall(get_func(:dop_window_swap_framebuffer_threaded), Cint, (UInt64,), handle.id) != 0
 end
 
-export renderer_resize!
+export map_framebuffer_threaded, swap_framebuffer_threaded!
 
 # ============================================================================
-# Text Rendering Functions
+# Window Manager (several windows on one event loop)
 # ============================================================================
 
 """
-    add_text!(handle::RustRendererHandle, text::String, x, y; 
-              font_size=16.0, r=0.0, g=0.0, b=0.0, a=1.0, font_id=0)
+    RustWindowManager
 
-Add a text render command.
+Handle to a Rust window manager that runs a single event loop thread shared by
+all of its windows. Windows are addressed by integer IDs.
 """
-function add_text!(handle::RustRendererHandle, text::String,
-                   x::Real, y::Real;
-                   font_size::Real=16.0,
-                   r::Real=0.0, g::Real=0.0, b::Real=0.0, a::Real=1.0,
-                   font_id::Integer=0)
-    if handle.is_valid && handle.ptr != C_NULL
-        ccall(get_func(:dop_renderer_add_text), 
-              Cvoid, (Ptr{Nothing}, Cstring, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cint), 
-              handle.ptr, text,
-              Float32(x), Float32(y), Float32(font_size),
-              Float32(r), Float32(g), Float32(b), Float32(a),
-              Int32(font_id))
+mutable struct RustWindowManager
+    ptr::Ptr{Nothing}
+    is_valid::Bool
+
+    function RustWindowManager(ptr::Ptr{Nothing})
+        m = new(ptr, ptr != C_NULL)
+        finalizer(m) do manager
+            if manager.is_valid && manager.ptr != C_NULL
+                destroy_window_manager!(manager)
+            end
+        end
+        return m
     end
 end
 
-export add_text!
+export RustWindowManager
 
 """
-    measure_text(handle::RustRendererHandle, text::String; font_size=16.0, font_id=0) -> Tuple{Float32, Float32}
+    create_window_manager(; main_thread=false) -> RustWindowManager
 
-Measure text width and height.
+Start a window manager. The handle is invalid if the event loop could not be created.
+
+By default the event loop runs on a thread of its own, which macOS does not
+allow. With `main_thread=true` it runs on the calling thread (the main thread
+on macOS) and only while `pump_window_manager!` is called.
 """
-function measure_text(handle::RustRendererHandle, text::String;
-                      font_size::Real=16.0, font_id::Integer=0)::Tuple{Float32, Float32}
-    if !handle.is_valid || handle.ptr == C_NULL
-        return (Float32(0), Float32(0))
-    end
-    
-    width = Ref{Cfloat}(0.0)
-    height = Ref{Cfloat}(0.0)
-    
-    ccall(get_func(:dop_renderer_measure_text), 
-          Cvoid, (Ptr{Nothing}, Cstring, Cfloat, Cint, Ptr{Cfloat}, Ptr{Cfloat}), 
-          handle.ptr, text, Float32(font_size), Int32(font_id),
-          width, height)
-    
-    return (Float32(width[]), Float32(height[]))
+function create_window_manager(; main_thread::Bool=false)::RustWindowManager
+    func = main_thread ? :dop_window_manager_create_main_thread : :dop_window_manager_create
+    ptr = ccall(get_func(func), Ptr{Nothing}, ())
+    return RustWindowManager(ptr)
 end
 
-export measure_text
+"""
+    pump_window_manager!(manager::RustWindowManager; timeout_ms=0) -> Bool
 
+Dispatch pending events of a `main_thread=true` manager, waiting up to
+`timeout_ms` for one. Returns false once the event loop has stopped.
 """
-    load_font!(handle::RustRendererHandle, path::String) -> Int
+function pump_window_manager!(manager::RustWindowManager; timeout_ms::Integer=0)::Bool
+    if !manager.is_valid || manager.ptr == C_NULL
+        return false
+    end
+    return ccall(get_func(:dop_window_manager_pump), Cint, (Ptr{Nothing}, Cint),
+                 manager.ptr, timeout_ms) != 0
+end
 
-Load a font from file. Returns font ID or -1 on failure.
 """
-function load_font!(handle::RustRendererHandle, path::String)::Int
-    if !handle.is_valid || handle.ptr == C_NULL
-        return -1
+    destroy_window_manager!(manager::RustWindowManager)
+
+Stop the manager's event loop and close all of its windows.
+"""
+function destroy_window_manager!(manager::RustWindowManager)
+    if manager.is_valid && manager.ptr != C_NULL
+        ccall(get_func(:dop_window_manager_free), Cvoid, (Ptr{Nothing},), manager.ptr)
+        manager.ptr = C_NULL
+        manager.is_valid = false
     end
-    
-    result = ccall(get_func(:dop_renderer_load_font), 
-                   Cint, (Ptr{Nothing}, Cstring), 
-                   handle.ptr, path)
-    return Int(result)
 end
 
-export load_font!
+"""
+    create_managed_window(manager; width=800, height=600, title="DOP Browser", gpu=false) -> Int
 
+Open a window on the manager's event loop and return its ID (0 on failure).
 """
-    has_default_font(handle::RustRendererHandle) -> Bool
+function create_managed_window(manager::RustWindowManager; width::Integer=800, height::Integer=600,
+                               title::String="DOP Browser", gpu::Bool=false)::Int
+    if !manager.is_valid || manager.ptr == C_NULL
+        return 0
+    end
+    return Int(ccall(get_func(:dop_window_manager_create_window),
+                     UInt32, (Ptr{Nothing}, Cint, Cint, Cstring, Cint),
+                     manager.ptr, width, height, title, gpu ? Cint(1) : Cint(0)))
+end
 
-Check if a default font is available.
 """
-function has_default_font(handle::RustRendererHandle)::Bool
-    if !handle.is_valid || handle.ptr == C_NULL
+    close_managed_window!(manager::RustWindowManager, window_id::Integer) -> Bool
+
+Close a managed window.
+"""
+function close_managed_window!(manager::RustWindowManager, window_id::Integer)::Bool
+    if !manager.is_valid || manager.ptr == C_NULL
         return false
     end
-    
-    result = ccall(get_func(:dop_renderer_has_default_font), 
-                   Cint, (Ptr{Nothing},), 
-                   handle.ptr)
-    return result != 0
+    return ccall(get_func(:dop_window_manager_close_window), Cint,
+                 (Ptr{Nothing}, UInt32), manager.ptr, window_id) != 0
 end
 
-export has_default_font
-
 """
-    export_png!(handle::RustRendererHandle, path::String) -> Bool
+    is_open(manager::RustWindowManager, window_id::Integer) -> Bool
 
-Export the framebuffer to a PNG file.
+Check if a managed window is still open.
 """
-function export_png!(handle::RustRendererHandle, path::String)::Bool
-    if !handle.is_valid || handle.ptr == C_NULL
+function is_open(manager::RustWindowManager, window_id::Integer)::Bool
+    if !manager.is_valid || manager.ptr == C_NULL
         return false
     end
-    
-    result = ccall(get_func(:dop_renderer_export_png), 
-                   Cint, (Ptr{Nothing}, Cstring), 
-                   handle.ptr, path)
-    return result != 0
+    return ccall(get_func(:dop_window_manager_is_open), Cint,
+                 (Ptr{Nothing}, UInt32), manager.ptr, window_id) != 0
 end
 
-export export_png!
+"""
+    poll_events!(manager::RustWindowManager, window_id::Integer; max_events::Integer=100) -> Vector{DopEvent}
 
-# ============================================================================
-# Text Shaper
-# ============================================================================
+Poll events from a managed window.
+"""
+function poll_events!(manager::RustWindowManager, window_id::Integer; max_events::Integer=100)::Vector{DopEvent}
+    if !manager.is_valid || manager.ptr == C_NULL
+        return DopEvent[]
+    end
+    events = Vector{DopEvent}(undef, max_events)
+    count = ccall(get_func(:dop_window_manager_poll_events),
+                  Cint, (Ptr{Nothing}, UInt32, Ptr{DopEvent}, Cint),
+                  manager.ptr, window_id, pointer(events), max_events)
+    return events[1:count]
+end
 
 """
-    TextShaperHandle
+    get_size(manager::RustWindowManager, window_id::Integer) -> Tuple{Int, Int}
 
-Handle to a text shaper for paragraph layout.
+Get the size of a managed window.
 """
-mutable struct TextShaperHandle
-    ptr::Ptr{Nothing}
-    is_valid::Bool
-    
-    function TextShaperHandle(ptr::Ptr{Nothing})
-        h = new(ptr, ptr != C_NULL)
-        finalizer(h) do handle
-            if handle.is_valid && handle.ptr != C_NULL
-                destroy_shaper!(handle)
-            end
-        end
-        return h
+function get_size(manager::RustWindowManager, window_id::Integer)::Tuple{Int, Int}
+    if !manager.is_valid || manager.ptr == C_NULL
+        return (0, 0)
     end
+    width = Ref{Cint}(0)
+    height = Ref{Cint}(0)
+    ccall(get_func(:dop_window_manager_get_size), Cint,
+          (Ptr{Nothing}, UInt32, Ref{Cint}, Ref{Cint}), manager.ptr, window_id, width, height)
+    return (Int(width[]), Int(height[]))
 end
 
-export TextShaperHandle
-
 """
-    create_text_shaper() -> TextShaperHandle
+    get_scale_factor(manager::RustWindowManager, window_id::Integer) -> Float64
 
-Create a new text shaper.
+Get the display scale factor of a managed window.
 """
-function create_text_shaper()::TextShaperHandle
-    ptr = ccall(get_func(:dop_text_shaper_create), Ptr{Nothing}, ())
-    return TextShaperHandle(ptr)
+function get_scale_factor(manager::RustWindowManager, window_id::Integer)::Float64
+    if !manager.is_valid || manager.ptr == C_NULL
+        return 1.0
+    end
+    return ccall(get_func(:dop_window_manager_get_scale_factor), Cdouble,
+                 (Ptr{Nothing}, UInt32), manager.ptr, window_id)
 end
 
-export create_text_shaper
-
 """
-    destroy_shaper!(handle::TextShaperHandle)
+    get_ime_text(manager::RustWindowManager, window_id::Integer, event::DopEvent) -> String
 
-Destroy a text shaper.
+Text of a managed window's `EVENT_IME_PREEDIT` or `EVENT_IME_COMMIT` event.
 """
-function destroy_shaper!(handle::TextShaperHandle)
-    if handle.is_valid && handle.ptr != C_NULL
-        ccall(get_func(:dop_text_shaper_free), Cvoid, (Ptr{Nothing},), handle.ptr)
-        handle.ptr = C_NULL
-        handle.is_valid = false
+function get_ime_text(manager::RustWindowManager, window_id::Integer, event::DopEvent)::String
+    if !manager.is_valid || manager.ptr == C_NULL
+        return ""
     end
+    len = ccall(get_func(:dop_window_manager_get_ime_text), Cint,
+                (Ptr{Nothing}, UInt32, UInt32, Ptr{UInt8}, Cint), manager.ptr, window_id, event.char_code, C_NULL, 0)
+    len <= 0 && return ""
+    buf = Vector{UInt8}(undef, len)
+    ccall(get_func(:dop_window_manager_get_ime_text), Cint,
+          (Ptr{Nothing}, UInt32, UInt32, Ptr{UInt8}, Cint), manager.ptr, window_id, event.char_code, buf, len)
+    return String(buf)
 end
 
-export destroy_shaper!
-
 """
-    ShapedTextResult
+    set_ime_position!(manager::RustWindowManager, window_id::Integer, x::Real, y::Real)
 
-Result of text shaping.
+Show a managed window's IME candidate window at the caret (bottom-left corner, in physical pixels).
 """
-struct ShapedTextResult
-    width::Float32
-    height::Float32
-    line_count::Int32
+function set_ime_position!(manager::RustWindowManager, window_id::Integer, x::Real, y::Real)
+    if !manager.is_valid || manager.ptr == C_NULL
+        return
+    end
+    ccall(get_func(:dop_window_manager_set_ime_position), Cvoid,
+          (Ptr{Nothing}, UInt32, Cfloat, Cfloat), manager.ptr, window_id, Float32(x), Float32(y))
 end
 
-export ShapedTextResult
-
 """
-    shape_paragraph(handle::TextShaperHandle, text::String, max_width::Real; font_size::Real=16.0) -> ShapedTextResult
+    request_redraw!(manager::RustWindowManager, window_id::Integer) -> Bool
 
-Shape a paragraph with word wrapping.
+Request a redraw of a managed window. An `EVENT_REDRAW` event follows once the frame is presented.
+"""
+function request_redraw!(manager::RustWindowManager, window_id::Integer)::Bool
+    if !manager.is_valid || manager.ptr == C_NULL
+        return false
+    end
+    return ccall(get_func(:dop_window_manager_request_redraw), Cint,
+                 (Ptr{Nothing}, UInt32), manager.ptr, window_id) != 0
+end
+
+"""
+    set_title!(manager::RustWindowManager, window_id::Integer, title::AbstractString) -> Bool
+
+Change a managed window's title.
+"""
+function set_title!(manager::RustWindowManager, window_id::Integer, title::AbstractString)::Bool
+    if !manager.is_valid || manager.ptr == C_NULL
+        return false
+    end
+    return ccall(get_func(:dop_window_manager_set_title), Cint,
+                 (Ptr{Nothing}, UInt32, Cstring), manager.ptr, window_id, title) != 0
+end
+
+"""
+    set_size!(manager::RustWindowManager, window_id::Integer, width::Integer, height::Integer) -> Bool
+
+Request a new inner size for a managed window in physical pixels.
+"""
+function set_size!(manager::RustWindowManager, window_id::Integer, width::Integer, height::Integer)::Bool
+    if !manager.is_valid || manager.ptr == C_NULL
+        return false
+    end
+    return ccall(get_func(:dop_window_manager_set_size), Cint,
+                 (Ptr{Nothing}, UInt32, Cint, Cint), manager.ptr, window_id, width, height) != 0
+end
+
+"""
+    set_fullscreen!(manager::RustWindowManager, window_id::Integer, fullscreen::Bool) -> Bool
+
+Switch a managed window to borderless fullscreen or back to windowed.
+"""
+function set_fullscreen!(manager::RustWindowManager, window_id::Integer, fullscreen::Bool)::Bool
+    if !manager.is_valid || manager.ptr == C_NULL
+        return false
+    end
+    return ccall(get_func(:dop_window_manager_set_fullscreen), Cint,
+                 (Ptr{Nothing}, UInt32, Cint), manager.ptr, window_id, fullscreen ? 1 : 0) != 0
+end
+
+"""
+    minimize!(manager::RustWindowManager, window_id::Integer) -> Bool
+
+Minimize a managed window.
+"""
+function minimize!(manager::RustWindowManager, window_id::Integer)::Bool
+    if !manager.is_valid || manager.ptr == C_NULL
+        return false
+    end
+    return ccall(get_func(:dop_window_manager_minimize), Cint,
+                 (Ptr{Nothing}, UInt32), manager.ptr, window_id) != 0
+end
+
+"""
+    set_maximized!(manager::RustWindowManager, window_id::Integer, maximized::Bool) -> Bool
+
+Maximize or restore a managed window.
+"""
+function set_maximized!(manager::RustWindowManager, window_id::Integer, maximized::Bool)::Bool
+    if !manager.is_valid || manager.ptr == C_NULL
+        return false
+    end
+    return ccall(get_func(:dop_window_manager_set_maximized), Cint,
+                 (Ptr{Nothing}, UInt32, Cint), manager.ptr, window_id, maximized ? 1 : 0) != 0
+end
+
+"""
+    update_framebuffer(manager::RustWindowManager, window_id::Integer, buf::Vector{UInt8}, width::Integer, height::Integer) -> Bool
+
+Copy an RGBA framebuffer into a managed window for presentation. The data is copied.
+"""
+function update_framebuffer(manager::RustWindowManager, window_id::Integer, buf::Vector{UInt8},
+                            width::Integer, height::Integer)::Bool
+    if !manager.is_valid || manager.ptr == C_NULL || isempty(buf)
+        return false
+    end
+    return ccall(get_func(:dop_window_manager_update_framebuffer),
+                 Cint, (Ptr{Nothing}, UInt32, Ptr{UInt8}, Cint, Cint, Cint),
+                 manager.ptr, window_id, pointer(buf), Int32(length(buf)), Int32(width), Int32(height)) != 0
+end
+
+export create_window_manager, destroy_window_manager!, pump_window_manager!, create_managed_window
+export close_managed_window!, update_framebuffer, get_scale_factor
+export get_ime_text, set_ime_position!
+export set_title!, set_size!, set_fullscreen!, minimize!, set_maximized!, request_redraw!
+
+# ============================================================================
+# Renderer Handle
+# ============================================================================
+
+"""
+    RustRendererHandle
+
+Handle to a Rust-based renderer.
+
+`id` is an opaque generational handle; the library rejects it once the renderer is freed.
+A renderer only accepts calls from the thread that created it; a task that
+moved to another thread must call [`adopt!`](@ref) first.
+"""
+mutable struct RustRendererHandle
+    id::UInt64
+    width::UInt32
+    height::UInt32
+    is_valid::Bool
+    
+    function RustRendererHandle(id::UInt64, width::UInt32, height::UInt32)
+        h = new(id, width, height, id != 0)
+        finalizer(h) do handle
+            if handle.is_valid && handle.id != 0
+                destroy!(handle)
+            end
+        end
+        return h
+    end
+end
+
+"""
+    create_renderer(width::Integer, height::Integer; shaping::Symbol=:fontdue) -> RustRendererHandle
+
+Create a new headless renderer.
+
+`shaping` selects how text is shaped: `:fontdue` (default) or `:harfbuzz` for
+ligatures and complex scripts. `:harfbuzz` falls back to fontdue when the
+library was built without its `harfbuzz` feature.
+"""
+function create_renderer(width::Integer, height::Integer; shaping::Symbol=:fontdue)::RustRendererHandle
+    id = if shaping == :fontdue
+        ccall(get_func(:dop_renderer_create_headless), 
+              UInt64, (Cint, Cint), 
+              width, height)
+    else
+        shaping == :harfbuzz || throw(ArgumentError("unknown shaping backend: $shaping"))
+        ccall(get_func(:dop_renderer_create_headless_with_shaping),
+              UInt64, (Cint, Cint, Cint),
+              width, height, 1)
+    end
+    return RustRendererHandle(id, UInt32(width), UInt32(height))
+end
+
+"""
+    destroy!(handle::RustRendererHandle)
+
+Destroy a renderer and release resources.
+"""
+function destroy!(handle::RustRendererHandle)
+    if handle.is_valid && handle.id != 0
+        ccall(get_func(:dop_renderer_free), Cvoid, (UInt64,), handle.id)
+        handle.id = 0
+        handle.is_valid = false
+    end
+end
+
+"""
+    adopt!(handle::RustRendererHandle) -> Bool
+
+Move the renderer to the calling thread. The thread that used it before must
+no longer touch it.
+"""
+function adopt!(handle::RustRendererHandle)::Bool
+    if !handle.is_valid || handle.id == 0
+        return false
+    end
+    return ccall(get_func(:dop_renderer_adopt), Cint, (UInt64,), handle.id) != 0
+end
+
+"""
+    clear!(handle::RustRendererHandle)
+
+Clear all render commands.
+"""
+function clear!(handle::RustRendererHandle)
+    if handle.is_valid && handle.id != 0
+        ccall(get_func(:dop_renderer_clear), Cvoid, (UInt64,), handle.id)
+    end
+end
+
+"""
+    set_clear_color!(handle::RustRendererHandle, r::Float32, g::Float32, b::Float32, a::Float32)
+
+Set the clear color.
+"""
+function set_clear_color!(handle::RustRendererHandle, 
+                          r::Real, g::Real, b::Real, a::Real=1.0)
+    if handle.is_valid && handle.id != 0
+        ccall(get_func(:dop_renderer_set_clear_color), 
+              Cvoid, (UInt64, Cfloat, Cfloat, Cfloat, Cfloat), 
+              handle.id, Float32(r), Float32(g), Float32(b), Float32(a))
+    end
+end
+
+"""
+    add_rect!(handle::RustRendererHandle, x, y, width, height, r, g, b, a; z_index=0)
+
+Add a rectangle render command.
+"""
+function add_rect!(handle::RustRendererHandle,
+                   x::Real, y::Real, width::Real, height::Real,
+                   r::Real, g::Real, b::Real, a::Real;
+                   z_index::Integer=0)
+    if handle.is_valid && handle.id != 0
+        ccall(get_func(:dop_renderer_add_rect), 
+              Cvoid, (UInt64, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cint), 
+              handle.id, 
+              Float32(x), Float32(y), Float32(width), Float32(height),
+              Float32(r), Float32(g), Float32(b), Float32(a),
+              Int32(z_index))
+    end
+end
+
+"""
+    RectCommand
+
+A rectangle command laid out like the library's `RenderCommand`, for
+[`submit_commands!`](@ref). A `stacking_context` of 0 puts the rectangle in
+the context open when it is submitted.
+"""
+struct RectCommand
+    x::Float32
+    y::Float32
+    width::Float32
+    height::Float32
+    color_r::Float32
+    color_g::Float32
+    color_b::Float32
+    color_a::Float32
+    texture_id::UInt32
+    z_index::Int32
+    corner_radii::NTuple{4, Float32}
+    stacking_context::UInt32
+end
+
+"""
+    PackedTextCommand
+
+A text command for [`submit_text_commands!`](@ref); `string_id` comes from
+[`intern_string!`](@ref).
+"""
+struct PackedTextCommand
+    string_id::UInt32
+    font_id::UInt32
+    x::Float32
+    y::Float32
+    font_size::Float32
+    color_r::Float32
+    color_g::Float32
+    color_b::Float32
+    color_a::Float32
+    decoration::UInt32
+    anchor::UInt32
+end
+
+"""
+    submit_commands!(handle::RustRendererHandle, commands::Vector{RectCommand}) -> Int
+
+Add a whole batch of rectangles in one call. Returns the number added.
+"""
+function submit_commands!(handle::RustRendererHandle, commands::Vector{RectCommand})::Int
+    if !handle.is_valid || handle.id == 0 || isempty(commands)
+        return 0
+    end
+    return Int(ccall(get_func(:dop_renderer_submit_commands), Cint,
+                     (UInt64, Ptr{RectCommand}, Csize_t), handle.id, commands, length(commands)))
+end
+
+"""
+    intern_string!(handle::RustRendererHandle, text::String) -> UInt32
+
+Intern a string for packed text commands. The same string always gets the
+same ID; 0 means failure.
+"""
+function intern_string!(handle::RustRendererHandle, text::String)::UInt32
+    if !handle.is_valid || handle.id == 0
+        return UInt32(0)
+    end
+    return ccall(get_func(:dop_renderer_intern_string), UInt32, (UInt64, Cstring), handle.id, text)
+end
+
+"""
+    submit_text_commands!(handle::RustRendererHandle, commands::Vector{PackedTextCommand}) -> Int
+
+Add a whole batch of text commands in one call. Commands with unknown string
+IDs are skipped. Returns the number added.
+"""
+function submit_text_commands!(handle::RustRendererHandle, commands::Vector{PackedTextCommand})::Int
+    if !handle.is_valid || handle.id == 0 || isempty(commands)
+        return 0
+    end
+    return Int(ccall(get_func(:dop_renderer_submit_text_commands), Cint,
+                     (UInt64, Ptr{PackedTextCommand}, Csize_t), handle.id, commands, length(commands)))
+end
+
+export RectCommand, PackedTextCommand, submit_commands!, intern_string!, submit_text_commands!
+
+"""
+    push_stacking_context!(handle::RustRendererHandle, z_index::Integer) -> UInt32
+
+Open a stacking context painted as one unit at `z_index` among its siblings.
+Rectangles added until [`pop_stacking_context!`](@ref) are ordered inside it,
+so their z-indices never lift them above the context's siblings. Paths,
+images, bitmaps and text are not ordered by contexts and still paint above all
+rectangles. Returns the context ID (0 on error).
+"""
+function push_stacking_context!(handle::RustRendererHandle, z_index::Integer)::UInt32
+    if !handle.is_valid || handle.id == 0
+        return UInt32(0)
+    end
+    return ccall(get_func(:dop_renderer_push_stacking_context), UInt32, (UInt64, Cint), handle.id, Int32(z_index))
+end
+
+"""
+    pop_stacking_context!(handle::RustRendererHandle) -> Bool
+
+Close the innermost stacking context. Returns false if none was open.
+"""
+function pop_stacking_context!(handle::RustRendererHandle)::Bool
+    if !handle.is_valid || handle.id == 0
+        return false
+    end
+    return ccall(get_func(:dop_renderer_pop_stacking_context), Cint, (UInt64,), handle.id) == 1
+end
+
+export push_stacking_context!, pop_stacking_context!
+
+"""
+    render_compiled_unit!(handle::RustRendererHandle, unit::Vector{UInt8}, viewport_width, viewport_height) -> Int
+
+Lay out a compiled unit (CMMB binary) and queue its commands in one call.
+Returns the number of commands queued, or -1 if `unit` is not valid CMMB.
+Needs a library built with the `content-ir` feature.
+"""
+function render_compiled_unit!(handle::RustRendererHandle, unit::Vector{UInt8},
+                               viewport_width::Real, viewport_height::Real)::Int
+    if !handle.is_valid || handle.id == 0
+        return -1
+    end
+    return Int(ccall(get_func(:dop_renderer_render_compiled_unit), Cint,
+                     (UInt64, Ptr{UInt8}, Csize_t, Cfloat, Cfloat),
+                     handle.id, unit, length(unit), Float32(viewport_width), Float32(viewport_height)))
+end
+
+export render_compiled_unit!
+
+"""
+    render!(handle::RustRendererHandle)
+
+Render the current frame.
+"""
+function render!(handle::RustRendererHandle)
+    if handle.is_valid && handle.id != 0
+        ccall(get_func(:dop_renderer_render), Cvoid, (UInt64,), handle.id)
+    end
+end
+
+"""
+    get_framebuffer(handle::RustRendererHandle) -> Vector{UInt8}
+
+Get the framebuffer as a copy.
+"""
+function get_framebuffer(handle::RustRendererHandle)::Vector{UInt8}
+    if !handle.is_valid || handle.id == 0
+        return UInt8[]
+    end
+    
+    ptr = ccall(get_func(:dop_renderer_get_framebuffer), 
+                Ptr{UInt8}, (UInt64,), 
+                handle.id)
+    size = ccall(get_func(:dop_renderer_get_framebuffer_size), 
+                 Cint, (UInt64,), 
+                 handle.id)
+    
+    if ptr == C_NULL || size <= 0
+        return UInt8[]
+    end
+    
+    # Copy the data to Julia-owned array
+    buffer = Vector{UInt8}(undef, size)
+    unsafe_copyto!(pointer(buffer), ptr, size)
+    return buffer
+end
+
+"""
+    get_framebuffer_size(handle::RustRendererHandle) -> Int
+
+Get the framebuffer size in bytes.
+"""
+function get_framebuffer_size(handle::RustRendererHandle)::Int
+    if !handle.is_valid || handle.id == 0
+        return 0
+    end
+    return Int(ccall(get_func(:dop_renderer_get_framebuffer_size), 
+                     Cint, (UInt64,), 
+                     handle.id))
+end
+
+"""
+    renderer_resize!(handle::RustRendererHandle, width::Integer, height::Integer)
+
+Resize the renderer.
+"""
+function renderer_resize!(handle::RustRendererHandle, width::Integer, height::Integer)
+    if handle.is_valid && handle.id != 0
+        ccall(get_func(:dop_renderer_resize), 
+              Cvoid, (UInt64, Cint, Cint), 
+              handle.id, width, height)
+        handle.width = UInt32(width)
+        handle.height = UInt32(height)
+    end
+end
+
+export renderer_resize!
+
+"""
+    set_device_pixel_ratio!(handle::RustRendererHandle, ratio::Real)
+
+Draw in logical pixels scaled by `ratio`. Size the renderer in physical pixels
+(logical size times `ratio`) so text renders crisp on HiDPI displays.
+"""
+function set_device_pixel_ratio!(handle::RustRendererHandle, ratio::Real)
+    if !handle.is_valid || handle.id == 0
+        return
+    end
+    ccall(get_func(:dop_renderer_set_device_pixel_ratio), Cvoid, (UInt64, Cfloat), handle.id, Float32(ratio))
+end
+
+export set_device_pixel_ratio!
+
+# ============================================================================
+# Text Rendering Functions
+# ============================================================================
+
+# Text decoration flags, combined with `|`
+const TEXT_DECORATION_NONE = 0
+const TEXT_UNDERLINE = 1
+const TEXT_OVERLINE = 2
+const TEXT_LINE_THROUGH = 4
+
+export TEXT_DECORATION_NONE, TEXT_UNDERLINE, TEXT_OVERLINE, TEXT_LINE_THROUGH
+
+# What the `y` of a text command refers to
+const TEXT_ANCHOR_TOP = 0
+const TEXT_ANCHOR_BASELINE = 1
+const TEXT_ANCHOR_BOTTOM = 2
+
+export TEXT_ANCHOR_TOP, TEXT_ANCHOR_BASELINE, TEXT_ANCHOR_BOTTOM
+
+"""
+    add_text!(handle::RustRendererHandle, text::String, x, y; 
+              font_size=16.0, r=0.0, g=0.0, b=0.0, a=1.0, font_id=0,
+              decoration=TEXT_DECORATION_NONE, decoration_color=(r, g, b, a),
+              anchor=TEXT_ANCHOR_TOP, letter_spacing=0.0, word_spacing=0.0,
+              line_height=nothing)
+
+Add a text render command. `decoration` combines `TEXT_UNDERLINE`,
+`TEXT_OVERLINE` and `TEXT_LINE_THROUGH`. `anchor` selects whether `y` is the
+top of the text, the baseline of its first line, or the bottom of its last line.
+`letter_spacing` and `word_spacing` add space after each character and space,
+and `line_height` overrides the default of 1.2 times the font size.
+"""
+function add_text!(handle::RustRendererHandle, text::String,
+                   x::Real, y::Real;
+                   font_size::Real=16.0,
+                   r::Real=0.0, g::Real=0.0, b::Real=0.0, a::Real=1.0,
+                   font_id::Integer=0,
+                   decoration::Integer=TEXT_DECORATION_NONE,
+                   decoration_color::NTuple{4, Real}=(r, g, b, a),
+                   anchor::Integer=TEXT_ANCHOR_TOP,
+                   letter_spacing::Real=0.0, word_spacing::Real=0.0,
+                   line_height::Union{Real, Nothing}=nothing)
+    if !handle.is_valid || handle.id == 0
+        return
+    end
+    if decoration == TEXT_DECORATION_NONE && anchor == TEXT_ANCHOR_TOP &&
+       letter_spacing == 0 && word_spacing == 0 && line_height === nothing
+        ccall(get_func(:dop_renderer_add_text), 
+              Cvoid, (UInt64, Cstring, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cint), 
+              handle.id, text,
+              Float32(x), Float32(y), Float32(font_size),
+              Float32(r), Float32(g), Float32(b), Float32(a),
+              Int32(font_id))
+    else
+        dr, dg, db, da = decoration_color
+        ccall(get_func(:dop_renderer_add_text_ex),
+              Cvoid, (UInt64, Cstring, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cint,
+                      Cint, Cfloat, Cfloat, Cfloat, Cfloat, Cint, Cfloat, Cfloat, Cfloat),
+              handle.id, text,
+              Float32(x), Float32(y), Float32(font_size),
+              Float32(r), Float32(g), Float32(b), Float32(a),
+              Int32(font_id), Int32(decoration),
+              Float32(dr), Float32(dg), Float32(db), Float32(da), Int32(anchor),
+              Float32(letter_spacing), Float32(word_spacing),
+              Float32(something(line_height, 0.0)))
+    end
+end
+
+export add_text!
+
+"""
+    font_metrics(handle::RustRendererHandle; font_size=16.0, font_id=0) -> Union{NamedTuple, Nothing}
+
+Ascent, descent (positive, below the baseline) and line gap of a font in pixels,
+or `nothing` if the font is not loaded.
+"""
+function font_metrics(handle::RustRendererHandle;
+                      font_size::Real=16.0, font_id::Integer=0)
+    if !handle.is_valid || handle.id == 0
+        return nothing
+    end
+
+    ascent = Ref{Cfloat}(0)
+    descent = Ref{Cfloat}(0)
+    line_gap = Ref{Cfloat}(0)
+    ok = ccall(get_func(:dop_renderer_get_font_metrics),
+               Cint, (UInt64, Cint, Cfloat, Ref{Cfloat}, Ref{Cfloat}, Ref{Cfloat}),
+               handle.id, Int32(font_id), Float32(font_size), ascent, descent, line_gap)
+    ok == 0 && return nothing
+    return (ascent=ascent[], descent=descent[], line_gap=line_gap[])
+end
+
+export font_metrics
+
+"""
+    measure_text(handle::RustRendererHandle, text::String; font_size=16.0, font_id=0) -> Tuple{Float32, Float32}
+
+Measure text width and height.
+"""
+function measure_text(handle::RustRendererHandle, text::String;
+                      font_size::Real=16.0, font_id::Integer=0)::Tuple{Float32, Float32}
+    if !handle.is_valid || handle.id == 0
+        return (Float32(0), Float32(0))
+    end
+    
+    width = Ref{Cfloat}(0.0)
+    height = Ref{Cfloat}(0.0)
+    
+    ccall(get_func(:dop_renderer_measure_text), 
+          Cvoid, (UInt64, Cstring, Cfloat, Cint, Ptr{Cfloat}, Ptr{Cfloat}), 
+          handle.id, text, Float32(font_size), Int32(font_id),
+          width, height)
+    
+    return (Float32(width[]), Float32(height[]))
+end
+
+export measure_text
+
+"""
+    load_font!(handle::RustRendererHandle, path::String) -> Int
+
+Load a font from file. Returns font ID or -1 on failure.
+"""
+function load_font!(handle::RustRendererHandle, path::String)::Int
+    if !handle.is_valid || handle.id == 0
+        return -1
+    end
+    
+    result = ccall(get_func(:dop_renderer_load_font), 
+                   Cint, (UInt64, Cstring), 
+                   handle.id, path)
+    return Int(result)
+end
+
+export load_font!
+
+"""
+    load_font_from_memory!(handle::RustRendererHandle, data::AbstractVector{UInt8}) -> Int
+
+Load a font from bytes (TrueType, OpenType or WOFF, but not WOFF2), e.g. a
+downloaded web font. The bytes are copied. Returns font ID or -1 on failure.
+"""
+function load_font_from_memory!(handle::RustRendererHandle, data::AbstractVector{UInt8})::Int
+    if !handle.is_valid || handle.id == 0
+        return -1
+    end
+    
+    bytes = Vector{UInt8}(data)
+    result = ccall(get_func(:dop_renderer_load_font_from_memory), 
+                   Cint, (UInt64, Ptr{UInt8}, Csize_t), 
+                   handle.id, bytes, length(bytes))
+    return Int(result)
+end
+
+export load_font_from_memory!
+
+"""
+    load_font_family!(handle::RustRendererHandle, family::String, path::String;
+                      weight=400, italic=false) -> Int
+
+Load a font file as one face of `family`. Returns font ID or -1 on failure.
+"""
+function load_font_family!(handle::RustRendererHandle, family::String, path::String;
+                           weight::Integer=400, italic::Bool=false)::Int
+    if !handle.is_valid || handle.id == 0
+        return -1
+    end
+
+    result = ccall(get_func(:dop_renderer_load_font_family),
+                   Cint, (UInt64, Cstring, Cint, Cint, Cstring),
+                   handle.id, family, Int32(weight), Int32(italic), path)
+    return Int(result)
+end
+
+"""
+    resolve_font(handle::RustRendererHandle, family::String; weight=400, italic=false) -> Int
+
+Font ID that renders `family` at `weight` and style, or -1 if the family is unknown.
+Missing bold or italic faces are synthesized from the closest face.
+"""
+function resolve_font(handle::RustRendererHandle, family::String;
+                      weight::Integer=400, italic::Bool=false)::Int
+    if !handle.is_valid || handle.id == 0
+        return -1
+    end
+
+    result = ccall(get_func(:dop_renderer_resolve_font),
+                   Cint, (UInt64, Cstring, Cint, Cint),
+                   handle.id, family, Int32(weight), Int32(italic))
+    return Int(result)
+end
+
+"""
+    font_families() -> Vector{String}
+
+Family names of the fonts installed on the system, sorted.
+"""
+function font_families()::Vector{String}
+    len = ccall(get_func(:dop_font_enumerate), Cint, (Ptr{UInt8}, Cint), C_NULL, 0)
+    len <= 0 && return String[]
+    buf = Vector{UInt8}(undef, len)
+    ccall(get_func(:dop_font_enumerate), Cint, (Ptr{UInt8}, Cint), buf, len)
+    return split(String(buf), '\n')
+end
+
+"""
+    load_font_by_name!(handle::RustRendererHandle, name::String) -> Int
+
+Load an installed font by family name (e.g. "Noto Sans"). Returns the font ID,
+or -1 if the family is not installed. The family can then be used with `resolve_font`.
+"""
+function load_font_by_name!(handle::RustRendererHandle, name::String)::Int
+    if !handle.is_valid || handle.id == 0
+        return -1
+    end
+
+    result = ccall(get_func(:dop_renderer_load_font_by_name),
+                   Cint, (UInt64, Cstring), handle.id, name)
+    return Int(result)
+end
+
+"""
+    set_glyph_cache_limit!(handle::RustRendererHandle, bytes::Integer)
+
+Set the memory budget of the glyph bitmap cache. Least-recently-used glyphs are
+evicted past it; 0 disables the cache.
+"""
+function set_glyph_cache_limit!(handle::RustRendererHandle, bytes::Integer)
+    if !handle.is_valid || handle.id == 0
+        return
+    end
+    ccall(get_func(:dop_renderer_set_glyph_cache_limit), Cvoid,
+          (UInt64, Csize_t), handle.id, bytes)
+end
+
+export load_font_family!, resolve_font, font_families, load_font_by_name!
+export set_glyph_cache_limit!
+
+"""
+    has_default_font(handle::RustRendererHandle) -> Bool
+
+Check if a default font is available.
+"""
+function has_default_font(handle::RustRendererHandle)::Bool
+    if !handle.is_valid || handle.id == 0
+        return false
+    end
+    
+    result = ccall(get_func(:dop_renderer_has_default_font), 
+                   Cint, (UInt64,), 
+                   handle.id)
+    return result != 0
+end
+
+export has_default_font
+
+"""
+    export_png!(handle::RustRendererHandle, path::String) -> Bool
+
+Export the framebuffer to a PNG file.
+"""
+function export_png!(handle::RustRendererHandle, path::String)::Bool
+    if !handle.is_valid || handle.id == 0
+        return false
+    end
+    
+    result = ccall(get_func(:dop_renderer_export_png), 
+                   Cint, (UInt64, Cstring), 
+                   handle.id, path)
+    return result != 0
+end
+
+export export_png!
+
+# ============================================================================
+# Text Shaper
+# ============================================================================
+
+"""
+    TextShaperHandle
+
+Handle to a text shaper for paragraph layout.
+"""
+mutable struct TextShaperHandle
+    ptr::Ptr{Nothing}
+    is_valid::Bool
+    
+    function TextShaperHandle(ptr::Ptr{Nothing})
+        h = new(ptr, ptr != C_NULL)
+        finalizer(h) do handle
+            if handle.is_valid && handle.ptr != C_NULL
+                destroy_shaper!(handle)
+            end
+        end
+        return h
+    end
+end
+
+export TextShaperHandle
+
+"""
+    create_text_shaper() -> TextShaperHandle
+
+Create a new text shaper.
+"""
+function create_text_shaper()::TextShaperHandle
+    ptr = ccall(get_func(:dop_text_shaper_create), Ptr{Nothing}, ())
+    return TextShaperHandle(ptr)
+end
+
+export create_text_shaper
+
+"""
+    destroy_shaper!(handle::TextShaperHandle)
+
+Destroy a text shaper.
+"""
+function destroy_shaper!(handle::TextShaperHandle)
+    if handle.is_valid && handle.ptr != C_NULL
+        ccall(get_func(:dop_text_shaper_free), Cvoid, (Ptr{Nothing},), handle.ptr)
+        handle.ptr = C_NULL
+        handle.is_valid = false
+    end
+end
+
+export destroy_shaper!
+
+"""
+    ShapedTextResult
+
+Result of text shaping.
+"""
+struct ShapedTextResult
+    width::Float32
+    height::Float32
+    line_count::Int32
+end
+
+export ShapedTextResult
+
+# Line wrap modes for shape_paragraph
+const WRAP_WORD = Int32(0)
+const WRAP_CHAR = Int32(1)
+const WRAP_BREAK_WORD = Int32(2)
+const WRAP_NONE = Int32(3)
+
+export WRAP_WORD, WRAP_CHAR, WRAP_BREAK_WORD, WRAP_NONE
+
+"""
+    shape_paragraph(handle::TextShaperHandle, text::String, max_width::Real;
+                    font_size::Real=16.0, wrap::Integer=WRAP_WORD,
+                    hyphens::Bool=false, max_lines::Integer=0) -> ShapedTextResult
+
+Shape a paragraph, wrapping lines at `max_width`.
+
+`wrap` is one of `WRAP_WORD`, `WRAP_CHAR`, `WRAP_BREAK_WORD` or `WRAP_NONE`.
+`hyphens` also breaks at soft hyphens (U+00AD). With `max_lines > 0`, text past
+that many lines is cut off and the last line ends in an ellipsis.
 """
 function shape_paragraph(handle::TextShaperHandle, text::String, max_width::Real;
-                         font_size::Real=16.0)::ShapedTextResult
+                         font_size::Real=16.0, wrap::Integer=WRAP_WORD,
+                         hyphens::Bool=false, max_lines::Integer=0)::ShapedTextResult
     if !handle.is_valid || handle.ptr == C_NULL
         return ShapedTextResult(0.0f0, 0.0f0, 0)
     end
     
     # The FFI returns a struct, we need to call and get the result
-    result = ccall(get_func(:dop_text_shaper_shape), 
-                   NTuple{3, Cfloat}, (Ptr{Nothing}, Cstring, Cfloat, Cfloat), 
-                   handle.ptr, text, Float32(max_width), Float32(font_size))
+    result = ccall(get_func(:dop_text_shaper_shape_ex), 
+                   NTuple{3, Cfloat}, (Ptr{Nothing}, Cstring, Cfloat, Cfloat, Cint, Cint, Cint), 
+                   handle.ptr, text, Float32(max_width), Float32(font_size),
+                   Int32(wrap), Int32(hyphens), Int32(max_lines))
     
     return ShapedTextResult(result[1], result[2], Int32(result[3]))
 end
 
 export shape_paragraph
 
+"""
+    set_text_spacing!(handle::TextShaperHandle; letter_spacing=0.0, word_spacing=0.0,
+                      line_height=nothing)
+
+Set the letter spacing, word spacing and line height (default 1.2 times the
+font size) used by later `shape_paragraph` calls.
+"""
+function set_text_spacing!(handle::TextShaperHandle; letter_spacing::Real=0.0,
+                           word_spacing::Real=0.0, line_height::Union{Real, Nothing}=nothing)
+    if !handle.is_valid || handle.ptr == C_NULL
+        return
+    end
+    ccall(get_func(:dop_text_shaper_set_spacing), Cvoid,
+          (Ptr{Nothing}, Cfloat, Cfloat, Cfloat),
+          handle.ptr, Float32(letter_spacing), Float32(word_spacing),
+          Float32(something(line_height, 0.0)))
+end
+
+export set_text_spacing!
+
+"""
+    ShapedLine
+
+A line of the last shaped paragraph. `start`/`stop` are a 0-based, end-exclusive
+byte range of the paragraph text; `first_glyph` indexes `shaped_glyphs` from 0.
+`suffix` is a mark to draw after the line's text (0 = none, 1 = hyphen,
+2 = ellipsis); `width` includes it.
+"""
+struct ShapedLine
+    start::Int32
+    stop::Int32
+    y::Float32
+    height::Float32
+    width::Float32
+    first_glyph::Int32
+    glyph_count::Int32
+    suffix::Int32
+end
+
+"""
+    ShapedGlyph
+
+A glyph of the last shaped paragraph: the 0-based byte range of text it came from,
+its line, its pen position and advance relative to the line's left edge, and
+whether it belongs to a right-to-left run. Glyphs of a line are in visual order.
+"""
+struct ShapedGlyph
+    byte_offset::Int32
+    byte_len::Int32
+    line::Int32
+    x::Float32
+    advance::Float32
+    rtl::Int32
+end
+
+export ShapedLine, ShapedGlyph
+
+"""
+    shaped_lines(handle::TextShaperHandle) -> Vector{ShapedLine}
+
+Lines of the paragraph last shaped with `shape_paragraph`.
+"""
+function shaped_lines(handle::TextShaperHandle)::Vector{ShapedLine}
+    lines = ShapedLine[]
+    if !handle.is_valid || handle.ptr == C_NULL
+        return lines
+    end
+    line = Ref{ShapedLine}()
+    while ccall(get_func(:dop_text_shaper_get_line), Cint,
+                (Ptr{Nothing}, Cint, Ref{ShapedLine}), handle.ptr, Int32(length(lines)), line) != 0
+        push!(lines, line[])
+    end
+    return lines
+end
+
+"""
+    shaped_glyphs(handle::TextShaperHandle) -> Vector{ShapedGlyph}
+
+Glyphs of the paragraph last shaped with `shape_paragraph`, in visual order within each line.
+"""
+function shaped_glyphs(handle::TextShaperHandle)::Vector{ShapedGlyph}
+    if !handle.is_valid || handle.ptr == C_NULL
+        return ShapedGlyph[]
+    end
+    count = ccall(get_func(:dop_text_shaper_glyph_count), Cint, (Ptr{Nothing},), handle.ptr)
+    glyphs = Vector{ShapedGlyph}(undef, count)
+    glyph = Ref{ShapedGlyph}()
+    for i in 1:count
+        ccall(get_func(:dop_text_shaper_get_glyph), Cint,
+              (Ptr{Nothing}, Cint, Ref{ShapedGlyph}), handle.ptr, Int32(i - 1), glyph)
+        glyphs[i] = glyph[]
+    end
+    return glyphs
+end
+
+export shaped_lines, shaped_glyphs
+
+"""
+    shaped_direction(handle::TextShaperHandle) -> Symbol
+
+Base direction of the last shaped paragraph (`:ltr` or `:rtl`), from its first
+strong character, so layout can align lines accordingly.
+"""
+function shaped_direction(handle::TextShaperHandle)::Symbol
+    if !handle.is_valid || handle.ptr == C_NULL
+        return :ltr
+    end
+    dir = ccall(get_func(:dop_text_shaper_get_direction), Cint, (Ptr{Nothing},), handle.ptr)
+    return dir == 1 ? :rtl : :ltr
+end
+
+export shaped_direction
+
+"""
+    text_hit_test(handle::TextShaperHandle, x::Real, y::Real) -> Int
+
+0-based byte offset of the caret nearest to (x, y) in the last shaped paragraph.
+"""
+function text_hit_test(handle::TextShaperHandle, x::Real, y::Real)::Int
+    if !handle.is_valid || handle.ptr == C_NULL
+        return 0
+    end
+    return Int(ccall(get_func(:dop_text_hit_test), Cint,
+                     (Ptr{Nothing}, Cfloat, Cfloat), handle.ptr, Float32(x), Float32(y)))
+end
+
+"""
+    text_offset_to_position(handle::TextShaperHandle, byte_offset::Integer) -> Union{NamedTuple, Nothing}
+
+Caret `(x, y, height)` for a 0-based byte offset in the last shaped paragraph,
+or `nothing` if nothing has been shaped. Offsets where a line wraps stay at the
+end of the earlier line.
+"""
+function text_offset_to_position(handle::TextShaperHandle, byte_offset::Integer)
+    if !handle.is_valid || handle.ptr == C_NULL
+        return nothing
+    end
+    x = Ref{Cfloat}(0)
+    y = Ref{Cfloat}(0)
+    height = Ref{Cfloat}(0)
+    ok = ccall(get_func(:dop_text_offset_to_position), Cint,
+               (Ptr{Nothing}, Cint, Ref{Cfloat}, Ref{Cfloat}, Ref{Cfloat}),
+               handle.ptr, Int32(byte_offset), x, y, height)
+    ok == 0 && return nothing
+    return (x=x[], y=y[], height=height[])
+end
+
+export text_hit_test, text_offset_to_position
+
+"""
+    add_selection!(renderer::RustRendererHandle, shaper::TextShaperHandle, x::Real, y::Real,
+                   bytes::UnitRange; color=(0.2, 0.45, 1.0, 0.35), z_index::Integer=0) -> Int
+
+Highlight the 0-based, end-exclusive byte range `bytes` of the shaper's last
+paragraph, drawn with its top-left corner at (x, y). One rectangle is added
+per line; they paint beneath the text. Returns the number added.
+"""
+function add_selection!(renderer::RustRendererHandle, shaper::TextShaperHandle, x::Real, y::Real,
+                        bytes::UnitRange; color=(0.2, 0.45, 1.0, 0.35), z_index::Integer=0)::Int
+    if !renderer.is_valid || renderer.id == 0 || !shaper.is_valid || shaper.ptr == C_NULL
+        return 0
+    end
+    r, g, b, a = Float32.(color)
+    return Int(ccall(get_func(:dop_renderer_add_selection), Cint,
+                     (UInt64, Ptr{Nothing}, Cfloat, Cfloat, Cint, Cint, Cfloat, Cfloat, Cfloat, Cfloat, Cint),
+                     renderer.id, shaper.ptr, Float32(x), Float32(y), Int32(first(bytes)), Int32(last(bytes) + 1),
+                     r, g, b, a, Int32(z_index)))
+end
+
+export add_selection!
+
 """
     shaper_load_font!(handle::TextShaperHandle, path::String) -> Int
 
@@ -842,6 +1852,25 @@ end
 
 export shaper_load_font!
 
+"""
+    shaper_load_font_from_memory!(handle::TextShaperHandle, data::AbstractVector{UInt8}) -> Int
+
+Load a font from bytes (TrueType, OpenType or WOFF) into the text shaper.
+"""
+function shaper_load_font_from_memory!(handle::TextShaperHandle, data::AbstractVector{UInt8})::Int
+    if !handle.is_valid || handle.ptr == C_NULL
+        return -1
+    end
+    
+    bytes = Vector{UInt8}(data)
+    result = ccall(get_func(:dop_text_shaper_load_font_from_memory), 
+                   Cint, (Ptr{Nothing}, Ptr{UInt8}, Csize_t), 
+                   handle.ptr, bytes, length(bytes))
+    return Int(result)
+end
+
+export shaper_load_font_from_memory!
+
 """
     shaper_has_font(handle::TextShaperHandle) -> Bool
 
@@ -860,6 +1889,302 @@ end
 
 export shaper_has_font
 
+"""
+    begin_paragraph!(handle::RustRendererHandle)
+
+Start a rich-text paragraph, discarding runs added since the last one.
+"""
+function begin_paragraph!(handle::RustRendererHandle)
+    if !handle.is_valid || handle.id == 0
+        return
+    end
+    ccall(get_func(:dop_text_begin_paragraph), Cvoid, (UInt64,), handle.id)
+end
+
+"""
+    add_run!(handle::RustRendererHandle, text::String;
+             font_size=16.0, r=0.0, g=0.0, b=0.0, a=1.0, font_id=0, decoration=TEXT_DECORATION_NONE)
+
+Append a styled run to the current paragraph.
+"""
+function add_run!(handle::RustRendererHandle, text::String;
+                  font_size::Real=16.0,
+                  r::Real=0.0, g::Real=0.0, b::Real=0.0, a::Real=1.0,
+                  font_id::Integer=0,
+                  decoration::Integer=TEXT_DECORATION_NONE)
+    if !handle.is_valid || handle.id == 0
+        return
+    end
+    ccall(get_func(:dop_text_add_run), Cvoid,
+          (UInt64, Cstring, Cint, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cint),
+          handle.id, text, Int32(font_id), Float32(font_size),
+          Float32(r), Float32(g), Float32(b), Float32(a), Int32(decoration))
+end
+
+"""
+    shape_and_draw!(handle::RustRendererHandle, x::Real, y::Real; max_width::Real=0) -> ShapedTextResult
+
+Shape the current paragraph's runs together (kerning and wrapping carry across runs)
+and draw it with its top-left at (x, y). `max_width <= 0` disables wrapping.
+"""
+function shape_and_draw!(handle::RustRendererHandle, x::Real, y::Real;
+                         max_width::Real=0)::ShapedTextResult
+    if !handle.is_valid || handle.id == 0
+        return ShapedTextResult(0.0f0, 0.0f0, 0)
+    end
+    return ccall(get_func(:dop_text_shape_and_draw), ShapedTextResult,
+                 (UInt64, Cfloat, Cfloat, Cfloat),
+                 handle.id, Float32(x), Float32(y), Float32(max_width))
+end
+
+export begin_paragraph!, add_run!, shape_and_draw!
+
+# ============================================================================
+# Scroll Areas
+# ============================================================================
+
+"""
+    RustScrollArea
+
+A viewport scrolled over larger content. Wheel, trackpad and drag input
+become clamped offsets with optional momentum; freed when garbage collected.
+Use it from the thread that created it.
+"""
+mutable struct RustScrollArea
+    id::UInt64
+
+    function RustScrollArea(id::UInt64)
+        area = new(id)
+        finalizer(area) do a
+            destroy!(a)
+        end
+        return area
+    end
+end
+
+"""
+    create_scroll_area(width, height, content_width, content_height; kinetic=false) -> RustScrollArea
+
+Create a scroll area at offset (0, 0).
+"""
+function create_scroll_area(width::Real, height::Real, content_width::Real, content_height::Real;
+                            kinetic::Bool=false)::RustScrollArea
+    id = ccall(get_func(:dop_scroll_area_create), UInt64, (Cfloat, Cfloat, Cfloat, Cfloat),
+               Float32(width), Float32(height), Float32(content_width), Float32(content_height))
+    area = RustScrollArea(id)
+    kinetic && set_kinetic!(area, true)
+    return area
+end
+
+function destroy!(area::RustScrollArea)
+    if area.id != 0
+        ccall(get_func(:dop_scroll_area_free), Cvoid, (UInt64,), area.id)
+        area.id = 0
+    end
+end
+
+"""
+    set_scroll_size!(area::RustScrollArea; viewport=nothing, content=nothing)
+
+Resize the viewport and/or content, each given as `(width, height)`.
+"""
+function set_scroll_size!(area::RustScrollArea; viewport=nothing, content=nothing)
+    if viewport !== nothing
+        ccall(get_func(:dop_scroll_area_set_viewport_size), Cvoid, (UInt64, Cfloat, Cfloat),
+              area.id, Float32(viewport[1]), Float32(viewport[2]))
+    end
+    if content !== nothing
+        ccall(get_func(:dop_scroll_area_set_content_size), Cvoid, (UInt64, Cfloat, Cfloat),
+              area.id, Float32(content[1]), Float32(content[2]))
+    end
+end
+
+"""
+    set_kinetic!(area::RustScrollArea, enabled::Bool)
+
+Enable or disable momentum after trackpad gestures and drags.
+"""
+function set_kinetic!(area::RustScrollArea, enabled::Bool)
+    ccall(get_func(:dop_scroll_area_set_kinetic), Cvoid, (UInt64, Cint), area.id, enabled ? 1 : 0)
+end
+
+"""
+    scroll!(area::RustScrollArea, dx::Real, dy::Real; precise::Bool=true) -> Bool
+
+Scroll by the delta of a `EVENT_MOUSE_SCROLL` event; `precise=false` counts
+lines instead of pixels. Returns whether the offset changed.
+"""
+function scroll!(area::RustScrollArea, dx::Real, dy::Real; precise::Bool=true)::Bool
+    return ccall(get_func(:dop_scroll_area_scroll), Cint, (UInt64, Cdouble, Cdouble, Cint),
+                 area.id, Float64(dx), Float64(dy), precise ? 1 : 0) == 1
+end
+
+"""
+    release_scroll!(area::RustScrollArea)
+
+End a trackpad gesture, starting momentum when kinetic scrolling is on.
+"""
+function release_scroll!(area::RustScrollArea)
+    ccall(get_func(:dop_scroll_area_release), Cvoid, (UInt64,), area.id)
+end
+
+"""
+    drag_begin!(area, x, y), drag_move!(area, x, y) -> Bool, drag_end!(area)
+
+Drag the content with the pointer; ending a drag flings it when kinetic
+scrolling is on.
+"""
+function drag_begin!(area::RustScrollArea, x::Real, y::Real)
+    ccall(get_func(:dop_scroll_area_drag_begin), Cvoid, (UInt64, Cdouble, Cdouble), area.id, Float64(x), Float64(y))
+end
+
+function drag_move!(area::RustScrollArea, x::Real, y::Real)::Bool
+    return ccall(get_func(:dop_scroll_area_drag_move), Cint, (UInt64, Cdouble, Cdouble),
+                 area.id, Float64(x), Float64(y)) == 1
+end
+
+function drag_end!(area::RustScrollArea)
+    ccall(get_func(:dop_scroll_area_drag_end), Cvoid, (UInt64,), area.id)
+end
+
+"""
+    scroll_to!(area::RustScrollArea, x::Real, y::Real)
+
+Jump to an offset (clamped), stopping any momentum.
+"""
+function scroll_to!(area::RustScrollArea, x::Real, y::Real)
+    ccall(get_func(:dop_scroll_area_scroll_to), Cvoid, (UInt64, Cfloat, Cfloat), area.id, Float32(x), Float32(y))
+end
+
+"""
+    tick!(area::RustScrollArea) -> Bool
+
+Advance momentum; call once per frame. Returns whether it is still running.
+"""
+function tick!(area::RustScrollArea)::Bool
+    return ccall(get_func(:dop_scroll_area_tick), Cint, (UInt64,), area.id) == 1
+end
+
+"""
+    scroll_offset(area::RustScrollArea) -> Tuple{Float32, Float32}
+
+Current offset of the viewport into the content.
+"""
+function scroll_offset(area::RustScrollArea)::Tuple{Float32, Float32}
+    x = Ref{Cfloat}(0)
+    y = Ref{Cfloat}(0)
+    ccall(get_func(:dop_scroll_area_get_offset), Cvoid, (UInt64, Ref{Cfloat}, Ref{Cfloat}), area.id, x, y)
+    return (x[], y[])
+end
+
+"""
+    add_scrollbars!(handle::RustRendererHandle, area::RustScrollArea, x::Real, y::Real;
+                    color=(0.0, 0.0, 0.0, 0.5), z_index::Integer=typemax(Int32)) -> Int
+
+Draw the area's scrollbar thumbs with the viewport's top-left corner at
+(x, y). Returns the number of thumbs drawn.
+"""
+function add_scrollbars!(handle::RustRendererHandle, area::RustScrollArea, x::Real, y::Real;
+                         color=(0.0, 0.0, 0.0, 0.5), z_index::Integer=typemax(Int32))::Int
+    if !handle.is_valid || handle.id == 0
+        return 0
+    end
+    r, g, b, a = Float32.(color)
+    return Int(ccall(get_func(:dop_scroll_area_add_scrollbars), Cint,
+                     (UInt64, UInt64, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cint),
+                     area.id, handle.id, Float32(x), Float32(y), r, g, b, a, Int32(z_index)))
+end
+
+export RustScrollArea, create_scroll_area, set_scroll_size!, set_kinetic!, scroll!, release_scroll!
+export drag_begin!, drag_move!, drag_end!, scroll_to!, tick!, scroll_offset, add_scrollbars!
+
+# ============================================================================
+# Caret and Focus Ring
+# ============================================================================
+
+"""
+    set_caret!(handle::RustRendererHandle, x::Real, y::Real, height::Real;
+               color=(0.0, 0.0, 0.0, 1.0), blink_ms::Integer=530)
+
+Show a caret bar at (x, y) that blinks every `blink_ms` milliseconds
+(0 = steady). It stays across frames until [`clear_caret!`](@ref). Setting it
+again unchanged keeps the blink phase, so it can be set every frame.
+"""
+function set_caret!(handle::RustRendererHandle, x::Real, y::Real, height::Real;
+                    color=(0.0, 0.0, 0.0, 1.0), blink_ms::Integer=530)
+    if !handle.is_valid || handle.id == 0
+        return
+    end
+    r, g, b, a = Float32.(color)
+    ccall(get_func(:dop_renderer_set_caret), Cvoid,
+          (UInt64, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cint),
+          handle.id, Float32(x), Float32(y), Float32(height), r, g, b, a, Int32(blink_ms))
+end
+
+"""
+    clear_caret!(handle::RustRendererHandle)
+
+Remove the caret.
+"""
+function clear_caret!(handle::RustRendererHandle)
+    if !handle.is_valid || handle.id == 0
+        return
+    end
+    ccall(get_func(:dop_renderer_clear_caret), Cvoid, (UInt64,), handle.id)
+end
+
+"""
+    caret_next_toggle_ms(handle::RustRendererHandle) -> Union{Int, Nothing}
+
+Milliseconds until the caret blinks in the renderer's framebuffer, or
+`nothing` if it does not blink there.
+"""
+function caret_next_toggle_ms(handle::RustRendererHandle)::Union{Int, Nothing}
+    if !handle.is_valid || handle.id == 0
+        return nothing
+    end
+    ms = ccall(get_func(:dop_renderer_caret_next_toggle_ms), Cint, (UInt64,), handle.id)
+    return ms < 0 ? nothing : Int(ms)
+end
+
+"""
+    attach_caret!(handle::RustRendererHandle, window::Union{RustThreadedWindowHandle, Nothing}) -> Bool
+
+Let an onscreen window draw the caret over presented frames and present
+again on every blink, so no frames are rendered for blinking. Pass `nothing`
+to draw it into the framebuffer again. Returns true if attached.
+"""
+function attach_caret!(handle::RustRendererHandle, window::Union{RustThreadedWindowHandle, Nothing})::Bool
+    if !handle.is_valid || handle.id == 0
+        return false
+    end
+    window_id = window === nothing || !window.is_valid ? UInt64(0) : window.id
+    return ccall(get_func(:dop_renderer_attach_caret_window), Cint, (UInt64, UInt64), handle.id, window_id) != 0
+end
+
+"""
+    add_focus_ring!(handle::RustRendererHandle, x::Real, y::Real, width::Real, height::Real;
+                    radius::Real=0, line_width::Real=2, dash::Real=4, gap::Real=2,
+                    color=(0.1, 0.45, 0.95, 1.0))
+
+Draw a dashed rounded outline just outside the box, e.g. around a focused
+field. `radius` is the box's corner radius; a `gap` of 0 draws a solid ring.
+"""
+function add_focus_ring!(handle::RustRendererHandle, x::Real, y::Real, width::Real, height::Real;
+                         radius::Real=0, line_width::Real=2, dash::Real=4, gap::Real=2,
+                         color=(0.1, 0.45, 0.95, 1.0))
+    if !handle.is_valid || handle.id == 0
+        return
+    end
+    r, g, b, a = Float32.(color)
+    ccall(get_func(:dop_renderer_add_focus_ring), Cvoid,
+          (UInt64, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat, Cfloat),
+          handle.id, Float32(x), Float32(y), Float32(width), Float32(height), Float32(radius),
+          Float32(line_width), Float32(dash), Float32(gap), r, g, b, a)
+end
+
+export set_caret!, clear_caret!, caret_next_toggle_ms, attach_caret!, add_focus_ring!
+
 # ============================================================================
 # Utility Functions
 # ============================================================================
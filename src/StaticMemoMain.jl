@@ -41,9 +41,9 @@ struct DopEvent
     timestamp::Float64
 end
 
-# Opaque handle types (just pointers)
-const RendererHandle = Ptr{Cvoid}
-const WindowHandle = Ptr{Cvoid}
+# Opaque handle types (generational ids issued by the renderer library)
+const RendererHandle = UInt64
+const WindowHandle = UInt64
 
 # Rust library path (will be resolved at link time or runtime)
 # For static compilation, the library should be linked directly
@@ -61,24 +61,24 @@ end
 # ============================================================================
 
 function create_renderer(width::Int32, height::Int32)::RendererHandle
-    ccall((:dop_renderer_create, LIBRENDERER), Ptr{Cvoid}, (Int32, Int32), width, height)
+    ccall((:dop_renderer_create, LIBRENDERER), UInt64, (Int32, Int32), width, height)
 end
 
 function destroy_renderer(renderer::RendererHandle)::Nothing
-    ccall((:dop_renderer_destroy, LIBRENDERER), Cvoid, (Ptr{Cvoid},), renderer)
+    ccall((:dop_renderer_destroy, LIBRENDERER), Cvoid, (UInt64,), renderer)
     nothing
 end
 
 function set_clear_color(renderer::RendererHandle, r::Float32, g::Float32, b::Float32, a::Float32)::Nothing
     ccall((:dop_renderer_set_clear_color, LIBRENDERER), Cvoid, 
-          (Ptr{Cvoid}, Float32, Float32, Float32, Float32), renderer, r, g, b, a)
+          (UInt64, Float32, Float32, Float32, Float32), renderer, r, g, b, a)
     nothing
 end
 
 function add_rect(renderer::RendererHandle, x::Float32, y::Float32, w::Float32, h::Float32,
                   r::Float32, g::Float32, b::Float32, a::Float32)::Nothing
     ccall((:dop_renderer_add_rect, LIBRENDERER), Cvoid,
-          (Ptr{Cvoid}, Float32, Float32, Float32, Float32, Float32, Float32, Float32, Float32),
+          (UInt64, Float32, Float32, Float32, Float32, Float32, Float32, Float32, Float32),
           renderer, x, y, w, h, r, g, b, a)
     nothing
 end
@@ -86,22 +86,22 @@ end
 function add_text(renderer::RendererHandle, text::Ptr{UInt8}, x::Float32, y::Float32,
                   font_size::Float32, r::Float32, g::Float32, b::Float32, a::Float32)::Nothing
     ccall((:dop_renderer_add_text, LIBRENDERER), Cvoid,
-          (Ptr{Cvoid}, Ptr{UInt8}, Float32, Float32, Float32, Float32, Float32, Float32, Float32),
+          (UInt64, Ptr{UInt8}, Float32, Float32, Float32, Float32, Float32, Float32, Float32),
           renderer, text, x, y, font_size, r, g, b, a)
     nothing
 end
 
 function render(renderer::RendererHandle)::Nothing
-    ccall((:dop_renderer_render, LIBRENDERER), Cvoid, (Ptr{Cvoid},), renderer)
+    ccall((:dop_renderer_render, LIBRENDERER), Cvoid, (UInt64,), renderer)
     nothing
 end
 
 function export_png(renderer::RendererHandle, filename::Ptr{UInt8})::Int32
-    ccall((:dop_renderer_export_png, LIBRENDERER), Int32, (Ptr{Cvoid}, Ptr{UInt8}), renderer, filename)
+    ccall((:dop_renderer_export_png, LIBRENDERER), Int32, (UInt64, Ptr{UInt8}), renderer, filename)
 end
 
 function get_framebuffer(renderer::RendererHandle, out_len::Ptr{Int64})::Ptr{UInt8}
-    ccall((:dop_renderer_get_framebuffer, LIBRENDERER), Ptr{UInt8}, (Ptr{Cvoid}, Ptr{Int64}), renderer, out_len)
+    ccall((:dop_renderer_get_framebuffer, LIBRENDERER), Ptr{UInt8}, (UInt64, Ptr{Int64}), renderer, out_len)
 end
 
 # ============================================================================
@@ -109,27 +109,27 @@ end
 # ============================================================================
 
 function create_onscreen_window(width::Int32, height::Int32, title::Ptr{UInt8})::WindowHandle
-    ccall((:dop_window_create_onscreen, LIBRENDERER), Ptr{Cvoid}, (Int32, Int32, Ptr{UInt8}), width, height, title)
+    ccall((:dop_window_create_onscreen, LIBRENDERER), UInt64, (Int32, Int32, Ptr{UInt8}), width, height, title)
 end
 
 function is_window_open(window::WindowHandle)::Int32
-    ccall((:dop_window_is_open_threaded, LIBRENDERER), Int32, (Ptr{Cvoid},), window)
+    ccall((:dop_window_is_open_threaded, LIBRENDERER), Int32, (UInt64,), window)
 end
 
 function poll_events(window::WindowHandle, events::Ptr{DopEvent}, max_events::Int32)::Int32
-    ccall((:dop_window_poll_events_threaded, LIBRENDERER), Int32, (Ptr{Cvoid}, Ptr{DopEvent}, Int32), 
+    ccall((:dop_window_poll_events_threaded, LIBRENDERER), Int32, (UInt64, Ptr{DopEvent}, Int32), 
           window, events, max_events)
 end
 
 function update_framebuffer(window::WindowHandle, data::Ptr{UInt8}, data_len::Int64, 
                              width::Int32, height::Int32)::Nothing
     ccall((:dop_window_update_framebuffer_threaded, LIBRENDERER), Cvoid,
-          (Ptr{Cvoid}, Ptr{UInt8}, Int64, Int32, Int32), window, data, data_len, width, height)
+          (UInt64, Ptr{UInt8}, Int64, Int32, Int32), window, data, data_len, width, height)
     nothing
 end
 
 function destroy_window(window::WindowHandle)::Nothing
-    ccall((:dop_window_free_threaded, LIBRENDERER), Cvoid, (Ptr{Cvoid},), window)
+    ccall((:dop_window_free_threaded, LIBRENDERER), Cvoid, (UInt64,), window)
     nothing
 end
 
@@ -174,7 +174,7 @@ Returns 0 on success, non-zero on failure.
 """
 function run_headless()::Int32
     renderer = create_renderer(Int32(400), Int32(600))
-    renderer == C_NULL && return Int32(1)
+    renderer == 0 && return Int32(1)
     
     render_simple_memo(renderer)
     
@@ -195,11 +195,11 @@ Returns 0 on success, non-zero on failure.
 function run_onscreen()::Int32
     # Create window
     window = create_onscreen_window(Int32(400), Int32(600), c"Static Memo App")
-    window == C_NULL && return Int32(1)
+    window == 0 && return Int32(1)
     
     # Create renderer for offscreen rendering
     renderer = create_renderer(Int32(400), Int32(600))
-    if renderer == C_NULL
+    if renderer == 0
         destroy_window(window)
         return Int32(1)
     end